@@ -0,0 +1,20 @@
+// Imports
+use opengl_raytracing_engine::{ Engine, EngineConfig, EngineError };
+
+extern crate nalgebra_glm as glm;
+
+/**
+ * Embeds the engine: opens the window on the knight, and slowly pans the camera around on the spot while it renders.
+ * Run with `cargo run --example pan`.
+ */
+fn main() -> Result<(), EngineError> {
+    tracing_subscriber::fmt().without_time().init();
+
+    let mut engine = Engine::new( EngineConfig::default() );
+    engine.load_scene( "resources/Knight.obj" )?;
+    engine.on_update( |frame| {
+        let angle = glm::vec3( 0.0, frame.time * 0.2, 0.0 );
+        frame.camera.set_vars( None, Some( angle ), None, None, None );
+    } );
+    engine.run()
+}
@@ -0,0 +1,13 @@
+#version 440 core
+
+// --- Inputs / outputs ---
+out vec4 color;
+
+uniform sampler2D image;    // The image after the tonemapper, [0, 1]
+uniform float levels;       // How many levels each channel is rounded to
+
+// The main function
+void main() {
+    vec3 col = texelFetch(image, ivec2(gl_FragCoord.xy), 0).rgb;
+    color = vec4( floor(col * levels + 0.5) / levels, 1 );
+}
@@ -0,0 +1,55 @@
+// Imports
+use opengl_raytracing_engine::{ Engine, EngineConfig, EngineError };
+use opengl_raytracing_engine::device::TextureFormat;
+use opengl_raytracing_engine::framegraph::{ PassIo, PassTargets, TargetDesc, TargetSize };
+use opengl_raytracing_engine::postchain::{ self, FrameContext, PassImage, PassPlacement, RenderPass };
+use opengl_raytracing_engine::shader::Shader;
+
+/**
+ * Struct for a pass which posterizes the image after it is tonemapped, rounding each channel to a few levels.
+ */
+struct PosterizePass {
+    shader: Option<Shader>, // Built once the context is current, see setup()
+    levels: f32,
+}
+
+impl RenderPass for PosterizePass {
+    fn name( &self ) -> &'static str { "Posterize" }
+
+    fn io( &self ) -> PassIo {
+        PassIo { reads: vec![], targets: vec![TargetDesc { name: "output", format: TextureFormat::Rgba8, size: TargetSize::Input }] }
+    }
+
+    unsafe fn setup( &mut self ) -> Result<(), EngineError> {
+        self.shader = Some( postchain::link_shader( "examples/posterize.frag" )? );
+        Ok( () )
+    }
+
+    unsafe fn execute( &mut self, image: PassImage, frame: &mut FrameContext, targets: &mut PassTargets ) -> Result<PassImage, EngineError> {
+        let Some( shader ) = self.shader.as_ref() else { return Ok( image ) };
+        targets.bind( "output" )?;
+        shader.activate();
+        postchain::bind_texture( shader, "image", image.texture, 0 );
+        gl::Uniform1f( shader.get_uniform_location( "levels" ), self.levels );
+        ( frame.draw_quad )();
+        targets.image( "output" )
+    }
+
+    unsafe fn delete( self: Box<Self> ) {
+        if let Some( shader ) = self.shader {
+            shader.delete();
+        }
+    }
+}
+
+/**
+ * Embeds the engine with a pass of its own: opens the window, and posterizes the image before it is shown.
+ * Run with `cargo run --example posterize`.
+ */
+fn main() -> Result<(), EngineError> {
+    tracing_subscriber::fmt().without_time().init();
+
+    let mut engine = Engine::new( EngineConfig::default() );
+    engine.add_render_pass( PosterizePass { shader: None, levels: 6.0 }, PassPlacement::After( "Tonemap" ) );
+    engine.run()
+}
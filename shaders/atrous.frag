@@ -0,0 +1,131 @@
+#version 440 core
+
+// --- Structs ---
+// AtrousSettings
+struct AtrousSettings {
+    float sigmaColor;
+    float sigmaNormal;
+    float sigmaDepth;
+    uint varianceGuided;
+};
+
+// --- Inputs / outputs ---
+out vec4 color;
+
+uniform sampler2D image;            // The image to filter, which is the accumulated image in the first iteration
+                                    // When variance guided, it is the light from the temporal pass with its variance in the alpha channel
+uniform sampler2D guideAlbedo;      // Albedo of the first surface, or white if nothing was hit
+uniform sampler2D guideNormalDepth; // Normal and distance to the first surface, or a negative distance if nothing was hit
+uniform AtrousSettings denoise;
+uniform int stepSize;               // Distance between the kernel's taps, in pixels
+uniform uint iteration;             // The iteration, counting from 0
+uniform uint lastIteration;         // If this is the last iteration
+
+// The B3 spline, which is the kernel of each iteration along each axis
+const float KERNEL[5] = float[](1.0 / 16, 1.0 / 4, 3.0 / 8, 1.0 / 4, 1.0 / 16);
+
+/**
+ * Gets the luminance of a linear color.
+ */
+float Luminance(vec3 col) {
+    return dot(col, vec3(0.2126, 0.7152, 0.0722));
+}
+
+/**
+ * Gets the light arriving at a pixel's surface, which is what is filtered.
+ * In the first iteration this divides the albedo out of the accumulated color, unless the temporal pass already did.
+ *
+ * @param pixel The pixel.
+ *
+ * @return The light, with the variance in the alpha channel when variance guided.
+ */
+vec4 LoadLight(ivec2 pixel) {
+    vec4 col = texelFetch(image, pixel, 0);
+    if (iteration == 0 && denoise.varianceGuided == 0)
+        col.rgb /= max(texelFetch(guideAlbedo, pixel, 0).rgb, vec3(1e-3));
+    return col;
+}
+
+/**
+ * Gets the variance of a pixel's luminance, blurred over its neighbours to make it more reliable.
+ *
+ * @param pixel The pixel.
+ *
+ * @return The variance.
+ */
+float BlurredVariance(ivec2 pixel) {
+    const float kernel[2] = float[](1.0 / 2, 1.0 / 4);
+    ivec2 size = textureSize(image, 0);
+
+    float sum = 0, weightSum = 0;
+    for (int y = -1; y <= 1; y++)
+    for (int x = -1; x <= 1; x++) {
+        ivec2 tap = clamp(pixel + ivec2(x, y), ivec2(0), size - 1);
+        float weight = kernel[abs(x)] * kernel[abs(y)];
+        sum += texelFetch(image, tap, 0).a * weight;
+        weightSum += weight;
+    }
+    return sum / weightSum;
+}
+
+// The main function
+void main() {
+    ivec2   size = textureSize(image, 0);
+    ivec2   pixel = ivec2(gl_FragCoord.xy);
+    vec4    centerLight = LoadLight(pixel);
+    vec4    centerNormalDepth = texelFetch(guideNormalDepth, pixel, 0);
+    bool    varianceGuided = denoise.varianceGuided != 0;
+
+    // (The colors get smoother every iteration, so they are allowed to differ less)
+    // (When variance guided, they are instead allowed to differ as much as they are uncertain)
+    float   sigmaColor = varianceGuided
+                ? max(denoise.sigmaColor * sqrt(BlurredVariance(pixel)), 1e-4)
+                : max(denoise.sigmaColor * exp2(-float(iteration)), 1e-4),
+            sigmaNormal = max(denoise.sigmaNormal, 1e-4),
+            sigmaDepth = max(denoise.sigmaDepth, 1e-4);
+
+    vec3    sum = vec3(0);
+    float   weightSum = 0,
+            varianceSum = 0;
+    for (int y = -2; y <= 2; y++)
+    for (int x = -2; x <= 2; x++) {
+        ivec2 tap = pixel + ivec2(x, y) * stepSize;
+        if (any(lessThan(tap, ivec2(0))) || any(greaterThanEqual(tap, size))) continue;
+
+        vec4 tapLight = LoadLight(tap);
+        vec4 tapNormalDepth = texelFetch(guideNormalDepth, tap, 0);
+
+        // Pixels which hit nothing are only filtered with each other
+        bool centerHit = centerNormalDepth.w >= 0,
+             tapHit = tapNormalDepth.w >= 0;
+        if (centerHit != tapHit) continue;
+
+        vec3    normalDiff = centerNormalDepth.xyz - tapNormalDepth.xyz;
+        float   depthDiff = centerHit ? (centerNormalDepth.w - tapNormalDepth.w) / max(centerNormalDepth.w, 1e-4) : 0;
+        float   colorWeight;
+        if (varianceGuided) {
+            colorWeight = exp(-abs(Luminance(centerLight.rgb) - Luminance(tapLight.rgb)) / sigmaColor);
+        } else {
+            vec3 colorDiff = centerLight.rgb - tapLight.rgb;
+            colorWeight = exp(-dot(colorDiff, colorDiff) / (sigmaColor * sigmaColor));
+        }
+
+        float weight = KERNEL[x + 2] * KERNEL[y + 2]
+            * colorWeight
+            * exp(-dot(normalDiff, normalDiff) / (sigmaNormal * sigmaNormal))
+            * exp(-depthDiff * depthDiff / (sigmaDepth * sigmaDepth));
+        sum += tapLight.rgb * weight;
+        weightSum += weight;
+        varianceSum += tapLight.a * weight * weight;
+    }
+
+    // (The center tap always has a weight, so the sum is never 0)
+    // (The variance of a weighted average is the variances weighted by the squared weights)
+    vec3    light = sum / weightSum;
+    float   variance = varianceGuided ? varianceSum / (weightSum * weightSum) : 1;
+    if (lastIteration != 0) {
+        light *= max(texelFetch(guideAlbedo, pixel, 0).rgb, vec3(1e-3));
+        variance = 1;
+    }
+    color = vec4(light, variance);
+}
@@ -0,0 +1,97 @@
+#version 440 core
+
+// --- Structs ---
+// Bloom
+struct Bloom {
+    float threshold;
+    float strength;
+    float radius;
+};
+
+// --- Inputs / outputs ---
+out vec4 color;
+
+uniform sampler2D image;        // The image of the stage, which is the HDR image when prefiltering and compositing
+uniform sampler2D bloomTexture; // The blurred bright parts, when compositing
+uniform Bloom bloom;
+uniform uint stage;             // Which stage of the bloom is rendered, see the constants
+uniform ivec2 direction;        // The axis which is blurred along, when blurring
+
+// --- Constants ---
+// The stages of the bloom, see BloomPass in postchain.rs
+const uint STAGE_PREFILTER = 0; // Keeps what is brighter than the threshold, at half the size
+const uint STAGE_BLUR = 1;      // Blurs along one axis
+const uint STAGE_COMPOSITE = 2; // Adds the blurred bright parts onto the image
+
+// Limit for how far the blur reaches, in taps to each side
+const int MAX_BLUR_TAPS = 32;
+
+/**
+ * Gets the luminance of a linear color.
+ */
+float Luminance(vec3 col) {
+    return dot(col, vec3(0.2126, 0.7152, 0.0722));
+}
+
+/**
+ * Keeps the part of a color that is brighter than the threshold.
+ * The cutoff has a soft knee half the threshold wide, so surfaces don't pop in and out of the bloom.
+ *
+ * @param col The linear color.
+ * @return What is left of the color.
+ */
+vec3 Prefilter(vec3 col) {
+    float   luminance = Luminance(max(col, vec3(0))),
+            knee = bloom.threshold * 0.5,
+            soft = clamp(luminance - bloom.threshold + knee, 0, 2 * knee);
+    soft = soft * soft / (4 * knee + 1e-4);
+    return col * max(soft, luminance - bloom.threshold) / max(luminance, 1e-4);
+}
+
+/**
+ * Blurs the image along one axis with a Gaussian, whose standard deviation is the radius.
+ *
+ * @param pixel The pixel.
+ * @return The blurred color.
+ */
+vec3 Blur(ivec2 pixel) {
+    ivec2   size = textureSize(image, 0);
+    float   sigma = max(bloom.radius, 1e-2);
+    int     taps = min(int(ceil(3 * sigma)), MAX_BLUR_TAPS);
+
+    vec3    sum = vec3(0);
+    float   weightSum = 0;
+    for (int i = -taps; i <= taps; i++) {
+        float weight = exp(-0.5 * float(i * i) / (sigma * sigma));
+        sum += texelFetch(image, clamp(pixel + direction * i, ivec2(0), size - 1), 0).rgb * weight;
+        weightSum += weight;
+    }
+    return sum / weightSum;
+}
+
+// The main function
+void main() {
+    ivec2 pixel = ivec2(gl_FragCoord.xy);
+    vec3 col;
+    switch (stage) {
+        case STAGE_PREFILTER: {
+            // (Each pixel averages the 2x2 pixels of the image it covers)
+            ivec2 size = textureSize(image, 0);
+            col = vec3(0);
+            for (int y = 0; y <= 1; y++)
+            for (int x = 0; x <= 1; x++)
+                col += Prefilter(texelFetch(image, min(pixel * 2 + ivec2(x, y), size - 1), 0).rgb) / 4;
+            break;
+        }
+        case STAGE_BLUR:
+            col = Blur(pixel);
+            break;
+        default: {
+            // (The bloom is half the size, so it is filtered up to the image)
+            vec2 uv = gl_FragCoord.xy / vec2(textureSize(image, 0));
+            col = texelFetch(image, pixel, 0).rgb + texture(bloomTexture, uv).rgb * bloom.strength;
+            break;
+        }
+    }
+    color = vec4( col, 1 );
+}
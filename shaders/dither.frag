@@ -0,0 +1,48 @@
+#version 440 core
+
+// --- Inputs / outputs ---
+out vec4 color;
+
+uniform sampler2D image;    // The final image, before it is quantized to 8 bits
+uniform uint frameIndex;    // Counts the displayed frames, so the noise changes every frame
+
+// --- Noise functions ---
+/**
+ * Hashes a pixel and frame into a random float, using PCG.
+ *
+ * @param pixel The pixel.
+ * @param frame The frame.
+ *
+ * @return The random float, [0, 1).
+ */
+float Hash(uvec2 pixel, uint frame) {
+    uint state = (pixel.x * 1973u + pixel.y * 9277u + frame * 26699u + 12345u) * 747796405u + 2891336453u;
+    uint word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return float((word >> 22u) ^ word) / 4294967296.0;
+}
+
+/**
+ * Dithers a color before it is quantized to 8 bits, with triangular noise of a step either way.
+ * Triangular noise makes the error of the quantization independent of the color, so gradients don't band.
+ * The noise changes every frame, so it averages out over time.
+ *
+ * @param col The color, [0, 1].
+ * @param pixel The pixel's coordinates.
+ *
+ * @return The dithered color.
+ */
+vec3 Dither(vec3 col, vec2 pixel) {
+    // (Each channel gets its own noise)
+    uvec2 cell = uvec2(pixel);
+    vec3 noise = vec3(
+        Hash(cell, frameIndex * 6) + Hash(cell, frameIndex * 6 + 1),
+        Hash(cell, frameIndex * 6 + 2) + Hash(cell, frameIndex * 6 + 3),
+        Hash(cell, frameIndex * 6 + 4) + Hash(cell, frameIndex * 6 + 5)
+    ) - 1;
+    return col + noise / 255;
+}
+
+// The main function
+void main() {
+    color = vec4( Dither(texelFetch(image, ivec2(gl_FragCoord.xy), 0).rgb, gl_FragCoord.xy), 1 );
+}
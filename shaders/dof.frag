@@ -0,0 +1,85 @@
+#version 440 core
+
+// --- Structs ---
+// DofPreview
+struct DofPreview {
+    float apertureRadius;
+    float focusDistance;
+    float tanHalfFov;
+};
+
+// --- Inputs / outputs ---
+out vec4 color;
+
+uniform sampler2D image;            // The image rendered through a pinhole
+uniform sampler2D guideNormalDepth; // Normal and distance to the first surface, or a negative distance if nothing was hit
+uniform DofPreview dof;
+
+// --- Constants ---
+// Taps of the gather, which are spread over a disc
+const int TAPS = 48;
+// Limit for the radius of the blur, in pixels, which keeps the taps from spreading too thin
+const float MAX_COC = 24;
+
+const float GOLDEN_ANGLE = 2.39996323;
+
+/**
+ * Gets a pixel's depth along the camera's front, from its distance along the ray.
+ * The focus plane is perpendicular to the front, so the blur follows this depth rather than the distance.
+ *
+ * @param pixel The pixel.
+ * @param size The size of the image, in pixels.
+ *
+ * @return The depth, or a negative value if nothing was hit.
+ */
+float Depth(ivec2 pixel, vec2 size) {
+    float dist = texelFetch(guideNormalDepth, pixel, 0).w;
+    vec2 uv = (vec2(pixel) + 0.5) / size;
+    vec3 dirLocal = vec3((uv - 0.5) * vec2(size.x / size.y, 1) * 2 * dof.tanHalfFov, 1);
+    return dist < 0 ? -1 : dist / length(dirLocal);
+}
+
+/**
+ * Gets the radius of the circle of confusion at a depth, which is how much a thin lens blurs it.
+ * The lens blurs a point at depth z over a radius of apertureRadius * |z - focusDistance| / z on the focus plane.
+ *
+ * @param depth The depth, where a negative one is infinitely far away.
+ * @param size The size of the image, in pixels.
+ *
+ * @return The radius, in pixels.
+ */
+float CircleOfConfusion(float depth, vec2 size) {
+    float blur = depth < 0 ? dof.apertureRadius : dof.apertureRadius * abs(depth - dof.focusDistance) / max(depth, 1e-4);
+    float pixelsPerUnit = size.y * 0.5 / max(dof.focusDistance * dof.tanHalfFov, 1e-4);
+    return min(blur * pixelsPerUnit, MAX_COC);
+}
+
+// The main function
+void main() {
+    vec2    size = vec2(textureSize(image, 0));
+    ivec2   pixel = ivec2(gl_FragCoord.xy);
+    float   centerDepth = Depth(pixel, size),
+            centerCoc = CircleOfConfusion(centerDepth, size);
+
+    // Each tap is gathered if its own blur reaches this pixel
+    // (Taps behind this pixel only count as far as its blur does, so they don't bleed over sharp surfaces in front of them)
+    vec3    sum = texelFetch(image, pixel, 0).rgb;
+    float   weightSum = 1;
+    for (int i = 1; i < TAPS; i++) {
+        float   r = sqrt(float(i) / TAPS) * MAX_COC,
+                theta = float(i) * GOLDEN_ANGLE;
+        ivec2   tap = pixel + ivec2(round(vec2(cos(theta), sin(theta)) * r));
+        if (any(lessThan(tap, ivec2(0))) || any(greaterThanEqual(tap, ivec2(size)))) continue;
+
+        float   tapDepth = Depth(tap, size),
+                tapCoc = CircleOfConfusion(tapDepth, size);
+        bool    behind = tapDepth < 0 ? centerDepth >= 0 : (centerDepth >= 0 && tapDepth > centerDepth);
+        float   weight = clamp(tapCoc - r + 1, 0, 1);
+        if (behind)
+            weight *= clamp(centerCoc - r + 1, 0, 1);
+
+        sum += texelFetch(image, tap, 0).rgb * weight;
+        weightSum += weight;
+    }
+    color = vec4( sum / weightSum, 1 );
+}
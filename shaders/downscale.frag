@@ -0,0 +1,101 @@
+#version 440 core
+
+// --- Structs ---
+// Supersampling
+struct Supersampling {
+    int factor;
+    uint reconstructionFilter;
+};
+
+// --- Inputs / outputs ---
+out vec4 color;
+
+uniform sampler2D image;            // The supersampled image
+uniform Supersampling downscale;    // How much larger the image is than the screen, and what it is filtered with
+
+// --- Constants ---
+// Reconstruction filter ids, see ReconstructionFilter in postprocess.rs
+const uint FILTER_BOX = 0;
+const uint FILTER_GAUSSIAN = 1;
+const uint FILTER_BLACKMAN_HARRIS = 2;
+const uint FILTER_MITCHELL = 3;
+
+const float PI = 3.14159265359;
+
+// --- Downscaling functions ---
+/**
+ * Gets the radius of a reconstruction filter, in screen pixels.
+ */
+float FilterRadius(uint filterId) {
+    switch (filterId) {
+        case FILTER_GAUSSIAN:       return 1.5;
+        case FILTER_BLACKMAN_HARRIS:return 1.5;
+        case FILTER_MITCHELL:       return 2.0;
+        default:                    return 0.5;
+    }
+}
+
+/**
+ * Evaluates a reconstruction filter along one axis.
+ *
+ * @param filterId The id of the filter.
+ * @param x The distance from the filter's center, in screen pixels.
+ *
+ * @return The filter's weight, which may be negative.
+ */
+float FilterWeight(uint filterId, float x) {
+    x = abs(x);
+    switch (filterId) {
+        case FILTER_GAUSSIAN:
+            return exp(-2 * x * x); // (A standard deviation of 0.5)
+        case FILTER_BLACKMAN_HARRIS: {
+            // (The window spans the filter's diameter)
+            float t = 2 * PI * (x / 3 + 0.5);
+            return 0.35875 - 0.48829 * cos(t) + 0.14128 * cos(2 * t) - 0.01168 * cos(3 * t);
+        }
+        case FILTER_MITCHELL: {
+            const float B = 1.0 / 3, C = 1.0 / 3;
+            if (x < 1)
+                return ((12 - 9 * B - 6 * C) * x * x * x + (-18 + 12 * B + 6 * C) * x * x + (6 - 2 * B)) / 6;
+            return ((-B - 6 * C) * x * x * x + (6 * B + 30 * C) * x * x + (-12 * B - 48 * C) * x + (8 * B + 24 * C)) / 6;
+        }
+        default:
+            return 1;
+    }
+}
+
+/**
+ * Filters the supersampled image down to a screen pixel.
+ *
+ * @param downscale The supersampling.
+ * @param pixel The screen pixel.
+ *
+ * @return The pixel's color.
+ */
+vec3 Downscale(Supersampling downscale, ivec2 pixel) {
+    ivec2   size = textureSize(image, 0);
+    float   factor = float(downscale.factor),
+            radius = FilterRadius(downscale.reconstructionFilter);
+    vec2    center = (vec2(pixel) + 0.5) * factor;
+    ivec2   first = max(ivec2(floor(center - radius * factor)), ivec2(0)),
+            last = min(ivec2(ceil(center + radius * factor)), size - 1);
+
+    vec3    sum = vec3(0);
+    float   weightSum = 0;
+    for (int y = first.y; y <= last.y; y++)
+    for (int x = first.x; x <= last.x; x++) {
+        vec2 offset = ((vec2(x, y) + 0.5) - center) / factor;
+        if (any(greaterThan(abs(offset), vec2(radius)))) continue;
+
+        float weight = FilterWeight(downscale.reconstructionFilter, offset.x) * FilterWeight(downscale.reconstructionFilter, offset.y);
+        sum += texelFetch(image, ivec2(x, y), 0).rgb * weight;
+        weightSum += weight;
+    }
+    // (The negative lobes can make dark pixels next to bright ones negative)
+    return max(sum / weightSum, vec3(0));
+}
+
+// The main function
+void main() {
+    color = vec4( Downscale(downscale, ivec2(gl_FragCoord.xy)), 1 );
+}
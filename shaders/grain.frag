@@ -0,0 +1,61 @@
+#version 440 core
+
+// --- Structs ---
+// FilmGrain
+struct FilmGrain {
+    float strength;
+    float size;
+};
+
+// --- Inputs / outputs ---
+out vec4 color;
+
+uniform sampler2D image;    // The tonemapped image
+uniform FilmGrain grain;
+uniform uint frameIndex;    // Counts the displayed frames, so the grain changes every frame
+
+// --- Noise functions ---
+/**
+ * Gets the luminance of a color.
+ */
+float Luminance(vec3 col) {
+    return dot(col, vec3(0.2126, 0.7152, 0.0722));
+}
+
+/**
+ * Hashes a pixel and frame into a random float, using PCG.
+ *
+ * @param pixel The pixel.
+ * @param frame The frame.
+ *
+ * @return The random float, [0, 1).
+ */
+float Hash(uvec2 pixel, uint frame) {
+    uint state = (pixel.x * 1973u + pixel.y * 9277u + frame * 26699u) * 747796405u + 2891336453u;
+    uint word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return float((word >> 22u) ^ word) / 4294967296.0;
+}
+
+/**
+ * Adds film grain to a tonemapped color.
+ * The grain has a triangular distribution, and is scaled down towards black and white so neither clips.
+ *
+ * @param grain The film grain.
+ * @param col The color, [0, 1].
+ * @param pixel The pixel's coordinates.
+ *
+ * @return The grainy color.
+ */
+vec3 AddFilmGrain(FilmGrain grain, vec3 col, vec2 pixel) {
+    uvec2   cell = uvec2(pixel / max(grain.size, 1));
+    float   noise = Hash(cell, frameIndex * 2) + Hash(cell, frameIndex * 2 + 1) - 1;
+    float   luminance = Luminance(col);
+    float   midtones = 4 * luminance * (1 - luminance);
+    // (A triangular distribution over [-1, 1] has a standard deviation of 1/sqrt(6))
+    return clamp(col + noise * sqrt(6.0) * grain.strength * midtones, 0, 1);
+}
+
+// The main function
+void main() {
+    color = vec4( AddFilmGrain(grain, texelFetch(image, ivec2(gl_FragCoord.xy), 0).rgb, gl_FragCoord.xy), 1 );
+}
@@ -0,0 +1,19 @@
+#version 440 core
+
+// --- Inputs / outputs ---
+out vec4 color;
+
+uniform sampler2D image;    // The image to measure
+uniform int meterSize;      // The size of the meter's texture, which the image is stretched over
+
+// The main function
+void main() {
+    // Each texel of the meter covers a block of pixels, of which the one in the middle is measured
+    ivec2 pixel = ivec2((gl_FragCoord.xy / meterSize) * textureSize(image, 0));
+    vec3 col = texelFetch(image, pixel, 0).rgb;
+
+    // The log is averaged instead of the luminance itself, so a few very bright pixels don't darken everything
+    // (The luminance has a floor, as black pixels would otherwise make the average -infinity)
+    float luminance = dot(max(col, vec3(0)), vec3(0.2126, 0.7152, 0.0722));
+    color = vec4(log(max(luminance, 1e-4)), 0, 0, 1);
+}
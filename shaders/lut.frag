@@ -0,0 +1,28 @@
+#version 440 core
+
+// --- Inputs / outputs ---
+out vec4 color;
+
+uniform sampler2D image;        // The tonemapped image
+uniform sampler3D lutTexture;
+uniform float lutSize;          // The amount of entries along each axis of the LUT
+uniform vec3 lutDomainMin;      // The colors at the first and last entries of the LUT
+uniform vec3 lutDomainMax;
+
+// --- Grading functions ---
+/**
+ * Grades a color with the LUT.
+ * The entries of the LUT are at the centers of the texture's texels, so the color is mapped onto those.
+ *
+ * @param col The color, after tonemapping and encoding to sRGB, which is what .cube LUTs are made for.
+ * @return The graded color.
+ */
+vec3 ApplyLut(vec3 col) {
+    vec3 uvw = clamp((col - lutDomainMin) / (lutDomainMax - lutDomainMin), 0, 1);
+    return texture(lutTexture, (uvw * (lutSize - 1) + 0.5) / lutSize).rgb;
+}
+
+// The main function
+void main() {
+    color = vec4( ApplyLut(texelFetch(image, ivec2(gl_FragCoord.xy), 0).rgb), 1 );
+}
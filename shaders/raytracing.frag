@@ -1,7 +1,13 @@
 #version 440 core
 
 // --- Macros ---
-#define HitInfo0 HitInfo( false, 0.0, vec3(0), vec3(0), Material(vec4(0), vec4(0), vec4(0), 0.0) )
+#define HitInfo0 HitInfo( false, 0.0, vec3(0), vec3(0), Material(vec4(0), vec4(0), vec4(0), 0.0), -1, -1 )
+// Samples one of an array of MAX_VOLUMES 3D textures, which GLSL ES can only index with constants
+#ifdef GLES
+#define SampleVolumeTexture(textures, volume, uvw) ((volume) == 0 ? texture(textures[0], uvw) : (volume) == 1 ? texture(textures[1], uvw) : (volume) == 2 ? texture(textures[2], uvw) : texture(textures[3], uvw))
+#else
+#define SampleVolumeTexture(textures, volume, uvw) texture(textures[volume], uvw)
+#endif
 
 // --- Constants ---
 const float PI = 3.1415926;
@@ -10,13 +16,153 @@ const bool  CULL_FACE = true;
 const bool  CLIP_MESHES = false; // Disable until triangle raycasting becomes more expensive
 const float kEpsilion = 0.001;
 
+// Camera projections (RTProjection)
+const uint PROJECTION_PERSPECTIVE = 0;
+const uint PROJECTION_ORTHOGRAPHIC = 1;
+const uint PROJECTION_FISHEYE = 2;
+const uint PROJECTION_EQUIRECTANGULAR = 3;
+
+// Stereo modes (RTStereo)
+const uint STEREO_MONO = 0;
+const uint STEREO_SIDE_BY_SIDE = 1;
+const uint STEREO_OVER_UNDER = 2;
+
+// Samplers (RTSampler)
+const uint SAMPLER_RANDOM = 0;
+const uint SAMPLER_BLUE_NOISE = 1;
+const uint SAMPLER_SOBOL = 2;
+
+// Integrators (INTEGRATOR_*) and debug views (DEBUG_VIEW_*) are defined when the shader is built, see RTIntegrator::shader_defines()
+
+// Light tracing parameters
+const float SPLAT_SCALE = 1024;         // Scale of the light splatted to the camera, which is stored as integers
+const float SPLAT_MAX = 1000000;        // Limit for the light of a single splat, to keep the integers from overflowing
+
+// ReSTIR passes (RestirPass)
+const uint RESTIR_PASS_SHADE = 0;
+const uint RESTIR_PASS_INITIAL = 1;
+const uint RESTIR_PASS_SPATIAL = 2;
+
+// ReSTIR parameters
+const int   RESTIR_CANDIDATES = 16;         // Amount of random light samples each pixel picks from in the initial pass
+const float RESTIR_TEMPORAL_MAX_M = 20;     // Limit for how many candidates the previous frames may count as, relative to the current frame
+const int   RESTIR_SPATIAL_NEIGHBOURS = 5;  // Amount of neighbours each pixel reuses reservoirs from
+const float RESTIR_SPATIAL_RADIUS = 30;     // Distance to the neighbours, in pixels
+
+// AOVs (accumulator::Aov), which are stored in this many textures after the image
+const int AOV_TEXTURES = 5;
+
+// Stages of the wavefront path (WavefrontStage), where 0 is the megakernel
+const uint WAVEFRONT_OFF = 0;
+const uint WAVEFRONT_GENERATE = 1;
+const uint WAVEFRONT_INTERSECT = 2;
+const uint WAVEFRONT_SHADE = 3;
+const uint WAVEFRONT_SHADOW = 4;
+const uint WAVEFRONT_ACCUMULATE = 5;
+
+// Photon map parameters
+const uint PHOTONS_PER_FRAG = 1;        // Amount of photons each frag traces per frame
+const uint PHOTON_GRID_SIZE = 1 << 20;  // Amount of cells in the photon map's hash grid
+const uint PHOTON_NONE = 0xffffffff;    // Marks the end of a cell's list of photons
+const int  PHOTON_MAX_GATHER = 256;     // Limit for how many photons are visited per cell when gathering
+
+// Path guiding parameters
+const int   GUIDING_RESOLUTION = 16;    // Amount of cells along each axis of the guide's grid
+const int   GUIDING_BINS_SIDE = 8;      // Each cell has GUIDING_BINS_SIDE^2 directional bins
+const int   GUIDING_BINS = GUIDING_BINS_SIDE * GUIDING_BINS_SIDE;
+const float GUIDING_FRACTION = 0.5;     // Fraction of diffuse bounces which are steered by the guide, the rest sample the surface
+const float GUIDING_SCALE = 64;         // Scale of the light added to the guide, which stores it as integers
+const int   GUIDING_MAX_RECORDS = 8;    // Amount of bounces per path which the guide learns from
+
+// Russian roulette strategies (RTRoulette)
+const uint ROULETTE_OFF = 0;
+const uint ROULETTE_THROUGHPUT = 1;
+const uint ROULETTE_CONSTANT = 2;
+
+// Sample dimensions, each of which is a pair of random numbers
+// Every random decision of a camera path has its own dimension, so the sampler's samples are never reused for two decisions
+// The first BLUE_NOISE_DIMENSIONS dimensions can use blue noise, the first 16 can use Sobol, and the rest use white noise
+const uint DIMENSION_PIXEL = 0;     // Jitter within the pixel
+const uint DIMENSION_LENS = 1;      // Point on the lens
+const uint DIMENSION_TIME = 2;      // Shutter time and color channel (wavelength)
+const uint DIMENSION_BOUNCE = 3;    // The first bounce's dimensions, followed by the next bounce's, see BounceDimension()
+const uint DIMENSIONS_PER_BOUNCE = 5;
+const uint BLUE_NOISE_DIMENSIONS = 4;
+
+// Volumes
+const int   MAX_VOLUMES = 4;        // Maximum amount of volumes (volumes::MAX_VOLUMES)
+const int   MAX_TRACKING_STEPS = 256;   // Limit for the steps of delta and ratio tracking through a volume
+
+// Dimensions of each bounce
+const uint BOUNCE_BSDF = 0;         // Direction of the bounce
+const uint BOUNCE_CHOICES = 1;      // Lobe choice and Russian roulette
+const uint BOUNCE_LIGHT = 2;        // Direction to the light which is sampled directly
+const uint BOUNCE_LIGHT_CHOICES = 3;// Which light is sampled directly, and if the path guide is used
+const uint BOUNCE_MEDIUM = 4;       // Distance to where the ray scatters in the fog (Scattering uses BOUNCE_BSDF for the direction)
+
 // --- Structs ---
 
+// RTMedium
+struct Medium {
+    float density;
+    vec3 albedo;
+    float anisotropy;
+};
+
+// RTSun
+struct Sun {
+    vec3 direction;
+    vec3 color;
+    uint godRaySteps;
+};
+
+// RTCrop
+struct Crop {
+    vec2 min;
+    vec2 max;
+};
+
+// volumes::Volume
+struct Volume {
+    vec3 boundsMin;
+    vec3 boundsMax;
+    float density;
+    vec3 albedo;
+    float anisotropy;
+    float emission;
+    float maxTemperature;
+};
+
 // RTSettings
 struct Settings {
-    uint maxBounces;
+    uint maxBounces;    // The total, on top of the limit of each lobe
+    uint maxDiffuseBounces;
+    uint maxGlossyBounces;
+    uint maxTransmissionBounces;
     uint raysPerFrag;
     float divergeStrength;
+    uint sampler;
+    uint roulette;
+    uint rouletteStart;
+    float rouletteSurvival;
+    uint integrator;
+    uint nextEventEstimation;
+    uint restir;
+    uint pathGuiding;
+    uint caustics;
+    float causticRadius;
+    uint seed;
+    float directClamp;
+    float indirectClamp;
+    float tMax;
+    uint clipToBounds;
+    float regularization;
+    uint fog;
+    Medium fogMedium;
+    uint sun;
+    Sun sunLight;
+    uint crop;
+    Crop cropRegion;
 };
 
 // RTCamera
@@ -26,6 +172,19 @@ struct Camera {
     float focusDistance;
     vec3 pos;
     mat4 localToWorld;
+    uint projection;
+    float orthoHeight;
+    float fisheyeFov;
+    float apertureRadius;
+    vec2 distortion;
+    float chromaticAberration;
+    uint stereo;
+    float ipd;
+    vec2 pixelJitter;
+    uint motionBlur;
+    mat4 localToWorldClose;
+    vec2 windowOffset;
+    vec2 imageSize;
 };
 
 // RTMaterial
@@ -54,6 +213,13 @@ struct Triangle {
     Material    material;
 };
 
+// RTQuantizedTriangle, which is decoded into a Triangle, see LoadTriangle()
+struct QuantizedTriangle {
+    uint        positions[5],
+                normals[3];
+    Material    material;
+};
+
 // RTMeshInfo
 struct MeshInfo {
     uint    startIndex,
@@ -69,6 +235,45 @@ struct HitInfo {
     vec3        pos;
     vec3        normal;
     Material    material;
+    int         sphere;     // Index of the sphere which was hit, or -1 if it wasn't a sphere
+    int         mesh;       // Index of the mesh which was hit, or -1 if it wasn't a mesh
+};
+
+// State of a path in the wavefront path, which is kept between its stages
+// The light of its shadow rays is pending until the shadow stage has checked that they aren't blocked
+struct WavefrontPath {
+    vec3    throughput;     // How much of the light found from here on reaches the camera
+    uint    seed;
+    vec3    radiance;       // The light found so far, summed over the frag's rays this frame
+    uint    bounce;
+    vec3    pendingLight;   // Light from the emissive sphere sampled at the last bounce
+    uint    diffuseBounces;
+    vec3    pendingSun;     // Light from the sun at the last bounce
+    uint    glossyBounces;
+    float   pathRoughness;
+    uint    sampledLights;  // If the lights were sampled directly at the last bounce
+};
+
+// Ray in a queue of the wavefront path, which belongs to the path of a pixel
+struct WavefrontRay {
+    vec3    origin;
+    uint    pixel;
+    vec3    dir;
+};
+
+// Shadow ray in the wavefront path, which must reach a light to hit for its path's pending light to count
+struct WavefrontShadowRay {
+    vec3    origin;
+    uint    pixel;
+    vec3    dir;
+    int     light;  // The sphere which must be hit, or -1 for the sun, where nothing may be hit
+};
+
+// Photon, which carries light from a light to a surface
+struct Photon {
+    vec3    pos;
+    uint    next;   // Index of the next photon in the same cell of the photon map, or PHOTON_NONE
+    vec3    power;
 };
 
 // Ray
@@ -77,13 +282,73 @@ struct Ray {
     vec3 dir;
 };
 
+// ReSTIR reservoir, holding a light sample picked out of many for a surface
+struct Reservoir {
+    vec3    surfacePos;
+    float   wSum;           // Sum of the weights of all samples seen
+    vec3    surfaceNormal;
+    float   M;              // Amount of samples seen
+    vec3    samplePos;      // Point on the light
+    float   W;              // Weight of the picked sample, which makes it unbiased
+    int     light;          // Index of the light, or -1 if no sample was picked
+};
+
 // --- Inputs / outputs ---
-out vec4 color;
+#ifdef COMPUTE
+// In the compute path, each invocation is a frag, and main() stores the outputs to the images bound in place of the framebuffer's attachments
+// (Work groups are WORKGROUP_SIZE_X by WORKGROUP_SIZE_Y pixels, which are defined by WorkgroupSize in wavefront.rs)
+layout (local_size_x = WORKGROUP_SIZE_X, local_size_y = WORKGROUP_SIZE_Y) in;
+layout (binding = 0) uniform writeonly image2D colorImage; // (Either RGBA32F or RGBA16F, so it has no format)
+layout (rgba32f, binding = 1) uniform writeonly image2D aovImages[AOV_TEXTURES];
+layout (rgba32f, binding = 6) uniform writeonly image2D guideNormalDepthImage;
+layout (rgba32f, binding = 7) uniform writeonly image2D guideMotionImage;
+vec4 color, guideNormalDepth, guideMotion, aovAlbedo, aovNormal, aovDepthId, aovDirect, aovIndirect;
+#else
+layout (location = 0) out vec4 color;
+layout (location = 1) out vec4 guideNormalDepth;   // Only written by the guide pass, which writes the albedo to color
+layout (location = 2) out vec4 guideMotion;        // Only written by the guide pass
+layout (location = 3) out vec4 aovAlbedo;          // The AOVs, which are only written by the shading pass when the accumulator has them
+layout (location = 4) out vec4 aovNormal;
+layout (location = 5) out vec4 aovDepthId;         // The depth along the camera's front and the object's ID, or -1 for both if nothing was hit
+layout (location = 6) out vec4 aovDirect;
+layout (location = 7) out vec4 aovIndirect;
+#endif
+vec4 fragCoord;     // gl_FragCoord, or the center of the invocation's pixel in the compute path
+bool primaryRay = false;    // If the next ray to be traced is a camera ray, see GenerateRay()
 
 uniform Settings settings;  // Raytracing settings
 uniform Camera camera;      // Raytracing camera variables
 uniform int spheresCount;
 uniform int meshesCount;
+uniform vec3 sceneBoundsMin;    // Bounds of all objects in the scene
+uniform vec3 sceneBoundsMax;
+
+uniform sampler2D accumulation; // The accumulated image from previous frames
+uniform uint frameCount;        // How many frames have been accumulated
+uniform uint halfAccumulation;  // If the accumulated image is stored at half precision, see AccumulationPrecision in accumulator.rs
+uniform uint aovs;              // If the accumulator has AOVs, which are accumulated along with the image
+uniform sampler2D aovAccumulation[AOV_TEXTURES]; // The accumulated AOVs from previous frames, in the order of the outputs
+uniform sampler2D blueNoiseTexture; // Tileable blue noise, with an independent pattern in each channel
+uniform uint restirPass;            // The ReSTIR pass being rendered
+uniform uint lightTracingPass;      // If the light tracing pass is being rendered
+uniform uint photonPass;            // If the photon pass is being rendered
+uniform uint guidePass;             // If the denoiser's guide pass is being rendered
+uniform uint wavefrontStage;        // The stage of the wavefront path being dispatched, or WAVEFRONT_OFF
+uniform uint wavefrontRay;          // Which of the frag's rays the wavefront path is tracing
+uniform uint wavefrontQueue;        // Which of the two ray queues holds the rays being traced, where the other gets the next bounce's
+uniform uint countRays;             // If the rays are counted, for the stats (see RenderStats)
+uniform uint motionVectors;         // If prevWorldToScreen is known, for the guide pass
+uniform mat4 prevWorldToScreen;     // Projects points onto the screen of the previous frame, see RTCamera::world_to_screen()
+uniform uint photonCapacity;        // Amount of photons the photon map can store
+uniform vec3 guidingBoundsMin;      // Bounds of the path guide's grid
+uniform vec3 guidingBoundsMax;
+uniform float aoDistance;           // Distance within which surfaces occlude each other, for the ambient occlusion integrator
+uniform float aoFalloff;            // Exponent of how much less far away occluders count
+uniform uint debugView;             // What the debug integrator shows (DebugView)
+uniform Volume volumes[MAX_VOLUMES];
+uniform sampler3D volumeDensities[MAX_VOLUMES]; // Densities of each volume, [0, 1]
+uniform sampler3D volumeTemperatures[MAX_VOLUMES]; // Temperatures of each volume, [0, 1], for those which glow
+uniform int volumesCount;
 
 // Buffer for holding sphere objects
 layout (std430, binding=0) buffer SphereBuffer
@@ -94,7 +359,7 @@ layout (std430, binding=0) buffer SphereBuffer
 // Buffer for holding triangle objects
 layout (std430, binding=1) buffer TriangleBuffer
 {
-    Triangle triangles[];
+    QuantizedTriangle triangles[];
 };
 
 // Buffer for holding triangle mesh info
@@ -103,6 +368,76 @@ layout (std430, binding=2) buffer MeshInfoBuffer
     MeshInfo meshes[];
 };
 
+// Buffer for holding the Sobol sequence's generator matrices, 32 uints per dimension
+layout (std430, binding=3) buffer SobolBuffer
+{
+    uint sobolMatrices[];
+};
+
+// Buffers for holding ReSTIR reservoirs, one per pixel, from the initial pass and the spatial pass (final)
+layout (std430, binding=4) buffer ReservoirBuffer
+{
+    Reservoir reservoirs[];
+};
+layout (std430, binding=5) buffer FinalReservoirBuffer
+{
+    Reservoir finalReservoirs[];
+};
+
+// Buffer for holding the light splatted to each pixel by light tracing, as fixed-point RGB
+layout (std430, binding=8) buffer SplatBuffer
+{
+    uint splats[];
+};
+
+// Buffers for holding the photon map's photons, and the first photon in each cell of its hash grid
+layout (std430, binding=9) buffer PhotonBuffer
+{
+    uint photonCount;
+    Photon photons[];
+};
+layout (std430, binding=10) buffer PhotonGridBuffer
+{
+    uint photonGrid[];
+};
+
+// Buffers for holding the wavefront path's paths (one per pixel), its two ray queues, the hits of the rays being traced, and its shadow rays
+layout (std430, binding=11) buffer WavefrontPathBuffer
+{
+    WavefrontPath paths[];
+};
+layout (std430, binding=12) buffer WavefrontRayBuffer
+{
+    uint rayCounts[2];
+    WavefrontRay rays[];    // The first queue, followed by the second
+};
+layout (std430, binding=13) buffer WavefrontHitBuffer
+{
+    HitInfo hits[];
+};
+layout (std430, binding=14) buffer WavefrontShadowRayBuffer
+{
+    uint shadowRayCount;
+    WavefrontShadowRay shadowRays[];
+};
+
+// Buffer for counting the rays traced, the primary rays and then the secondary rays, see RenderStats
+layout (std430, binding=15) buffer RayCounterBuffer
+{
+    uint rayCounter[2];
+};
+
+// Buffers for holding the path guide's directional histograms, GUIDING_BINS per cell
+// Light is added to the learning buffer, while the sampling buffer holds what was learned by the previous frames
+layout (std430, binding=6) buffer GuidingLearningBuffer
+{
+    uint guidingLearning[];
+};
+layout (std430, binding=7) buffer GuidingSamplingBuffer
+{
+    uint guidingSampling[];
+};
+
 
 // --- Randomness functions ---
 
@@ -169,6 +504,167 @@ vec2 randVecCartesianNormDist(inout uint seed) {
     return pos * sqrt(abs(randFloatNormDist(seed))); // Normal distribution
 }
 
+/**
+ * Maps a pair of uniform random numbers to a uniformly distributed point on the unit disc.
+ *
+ * @param u The random numbers, [0, 1].
+ * @return A point within the unit disc.
+ */
+vec2 SampleDisc(vec2 u) {
+    float ang = u.x * 2 * PI;
+    return vec2(cos(ang), sin(ang)) * sqrt(u.y);
+}
+
+/**
+ * Maps a pair of uniform random numbers to a uniformly distributed direction.
+ *
+ * @param u The random numbers, [0, 1].
+ * @return A point on the unit sphere.
+ */
+vec3 SampleSphere(vec2 u) {
+    float   ang = u.x * 2 * PI,
+            z = 1 - 2 * u.y,
+            r = sqrt(max(0, 1 - z*z));
+    return vec3(cos(ang) * r, sin(ang) * r, z);
+}
+
+// Index of the sample currently being taken by the frag, counting all rays of all accumulated frames
+uint sampleIndex;
+
+// Index of the ray currently being fired by the frag this frame
+uint rayIndex;
+
+// Hash of the frag's position, which decorrelates the samples of neighbouring frags
+uint fragHash;
+
+/**
+ * Gets the sample dimension of one of a bounce's decisions.
+ *
+ * @param bounce The bounce, starting at 0.
+ * @param decision The decision (BOUNCE_*).
+ *
+ * @return The dimension.
+ */
+uint BounceDimension(int bounce, uint decision) {
+    return DIMENSION_BOUNCE + uint(bounce) * DIMENSIONS_PER_BOUNCE + decision;
+}
+
+/**
+ * Hashes an unsigned integer.
+ *
+ * @param x The integer.
+ * @return The hash.
+ */
+uint hash(uint x) {
+    return randInt(x);
+}
+
+/**
+ * Gets a value of the Sobol sequence.
+ *
+ * @param index The index in the sequence.
+ * @param dimension The dimension of the sequence.
+ *
+ * @return The value, as a fixed-point fraction of 2^32.
+ */
+uint Sobol(uint index, uint dimension) {
+    uint result = 0;
+    for (uint bit = 0; index != 0; bit++, index >>= 1)
+        if ((index & 1) != 0) result ^= sobolMatrices[dimension * 32 + bit];
+    return result;
+}
+
+// Burley, "Practical Hash-based Owen Scrambling" (2020)
+/**
+ * Scrambles the bits of an integer as a nested uniform (Owen) scramble, where each bit is flipped depending on all the bits above it.
+ *
+ * @param x The integer, as a fixed-point fraction.
+ * @param seed The seed of the scramble.
+ *
+ * @return The scrambled integer.
+ */
+uint OwenScramble(uint x, uint seed) {
+    x = bitfieldReverse(x);
+    x += seed;
+    x ^= x * 0x6c50b47c;
+    x ^= x * 0xb82f1e52;
+    x ^= x * 0xc7afe638;
+    x ^= x * 0x8d22f6e6;
+    return bitfieldReverse(x);
+}
+
+/**
+ * Gets a pair of white noise random numbers for a sample dimension.
+ * They are hashed from the frag, the sample and the dimension, so they don't depend on which other random numbers were used before.
+ *
+ * @param dimension The sample dimension.
+ *
+ * @return Two random floats, [0, 1].
+ */
+vec2 SampleWhiteNoise2D(uint dimension) {
+    uint seed = hash(fragHash ^ hash(sampleIndex ^ hash(dimension)));
+    return vec2(randFloat(seed), randFloat(seed));
+}
+
+/**
+ * Gets a pair of random numbers which are stratified over the frag's rays this frame.
+ * The rays are spread over a grid of cells with one random point in each (jittered grid), so they can't clump together.
+ * When there are more cells than rays, which cells are used is rotated randomly, so every cell is equally likely to be used.
+ *
+ * @param dimension The sample dimension, which decides the rotation so different dimensions don't use the same cells together.
+ *
+ * @return Two random floats, [0, 1].
+ */
+vec2 SampleStratified2D(uint dimension) {
+    uint    columns = uint(ceil(sqrt(float(settings.raysPerFrag)))),
+            rows = (settings.raysPerFrag + columns - 1) / columns,
+            cells = columns * rows,
+            cell = (rayIndex + hash(fragHash ^ hash(frameCount) ^ hash(dimension + 1))) % cells;
+    vec2    cellPos = vec2(cell % columns, cell / columns);
+    return (cellPos + SampleWhiteNoise2D(dimension)) / vec2(columns, rows);
+}
+
+/**
+ * Gets a pair of random numbers for a sample dimension, using the sampler in the settings.
+ *
+ * Blue noise is used for the first few dimensions, which spreads the error of neighbouring frags evenly instead of in clumps.
+ * It is offset by a different amount for each sample (Cranley-Patterson rotation), so every sample of a frag gets a different value.
+ *
+ * Sobol uses two dimensions of the sequence per sample dimension, Owen-scrambled and shuffled per frag so neighbouring frags are decorrelated.
+ *
+ * Dimensions which the sampler doesn't cover fall back to white noise, which is stratified over the frag's rays for the pixel and lens.
+ *
+ * @param dimension The sample dimension.
+ *
+ * @return Two random floats, [0, 1].
+ */
+vec2 Sample2D(uint dimension) {
+    if (settings.sampler == SAMPLER_BLUE_NOISE && dimension < BLUE_NOISE_DIMENSIONS) {
+        // Every second dimension uses the other half of the channels, and each pair is read from a different place in the texture
+        ivec2   texel = (ivec2(fragCoord.xy) + ivec2(23, 41) * int(dimension / 2)) % textureSize(blueNoiseTexture, 0);
+        vec4    noise = texelFetch(blueNoiseTexture, texel, 0);
+        vec2    value = (dimension % 2 == 0) ? noise.rg : noise.ba;
+
+        // Rotate by the R2 sequence, which is shifted per dimension so they don't rotate in lockstep
+        vec2    rotation = vec2(0.7548776662, 0.5698402910) * float(sampleIndex) + vec2(0.4142135624, 0.7320508076) * float(dimension)
+                         + vec2(hash(settings.seed), hash(settings.seed + 1)) / 4294967296.0; // 2^32
+        return fract(value + rotation);
+    }
+
+    if (settings.sampler == SAMPLER_SOBOL && dimension * 2 + 1 < sobolMatrices.length() / 32) {
+        uint    dimensionHash = hash(fragHash ^ hash(dimension)),
+                index = OwenScramble(sampleIndex, dimensionHash),
+                x = OwenScramble(Sobol(index, dimension * 2), hash(dimensionHash)),
+                y = OwenScramble(Sobol(index, dimension * 2 + 1), hash(dimensionHash + 1));
+        return vec2(x, y) / 4294967296.0; // 2^32
+    }
+
+    if ((dimension == DIMENSION_PIXEL || dimension == DIMENSION_LENS) && settings.raysPerFrag > 1)
+        return SampleStratified2D(dimension);
+
+    return SampleWhiteNoise2D(dimension);
+}
+
 // --- Environment functions ---
 /**
  * Gets the environment light where a ray goes.
@@ -306,6 +802,70 @@ bool RayBoundingBox(Ray ray, vec3 boxMin, vec3 boxMax) {
     return maxMinAxis <= minMaxAxis;
 }
 
+/**
+ * Gets the distances along a ray where it enters and leaves a bounding box.
+ *
+ * @param ray The ray.
+ * @param boxMin The bottom left corner of the box.
+ * @param boxMax The top right corner of the box.
+ *
+ * @return The distances, where the first is greater than the second if the ray misses the box.
+ */
+vec2 RayBoundingBoxDistances(Ray ray, vec3 boxMin, vec3 boxMax) {
+    vec3    rayDirInverted = 1.0 / ray.dir,
+            boxMinRelative = (boxMin - ray.origin) * rayDirInverted,
+            boxMaxRelative = (boxMax - ray.origin) * rayDirInverted,
+            boxMinNew = min( boxMinRelative, boxMaxRelative ),
+            boxMaxNew = max( boxMinRelative, boxMaxRelative );
+
+    return vec2( max( max( boxMinNew.x, boxMinNew.y ), boxMinNew.z ), min( min( boxMaxNew.x, boxMaxNew.y ), boxMaxNew.z ) );
+}
+
+// --- Mesh functions ---
+/**
+ * Decodes a normal which was octahedral-encoded into a 16-bit pair, see encode_octahedral() in raytracing.rs.
+ *
+ * @param encoded The encoded normal.
+ * @return The normal.
+ */
+vec3 DecodeOctahedral(uint encoded) {
+    vec2 f = unpackUnorm2x16(encoded) * 2 - 1;
+    vec3 n = vec3(f, 1 - abs(f.x) - abs(f.y));
+
+    // (The lower half of the octahedron was folded over the corners of the upper half)
+    float t = max(-n.z, 0);
+    n.xy += vec2(n.x >= 0 ? -t : t, n.y >= 0 ? -t : t);
+    return normalize(n);
+}
+
+/**
+ * Loads a triangle from the triangle buffer, decoding its quantized positions and normals.
+ *
+ * @param index The index of the triangle.
+ * @param mesh The mesh which the triangle is in, whose bounds the positions are relative to.
+ *
+ * @return The triangle.
+ */
+Triangle LoadTriangle(uint index, MeshInfo mesh) {
+    QuantizedTriangle quantized = triangles[index];
+    vec2    a = unpackUnorm2x16(quantized.positions[0]),
+            b = unpackUnorm2x16(quantized.positions[1]),
+            c = unpackUnorm2x16(quantized.positions[2]),
+            d = unpackUnorm2x16(quantized.positions[3]),
+            e = unpackUnorm2x16(quantized.positions[4]);
+    vec3    extent = mesh.boundingBoxMax - mesh.boundingBoxMin;
+
+    Triangle triangle;
+    triangle.p0 = mesh.boundingBoxMin + vec3(a.x, a.y, b.x) * extent;
+    triangle.p1 = mesh.boundingBoxMin + vec3(b.y, c.x, c.y) * extent;
+    triangle.p2 = mesh.boundingBoxMin + vec3(d.x, d.y, e.x) * extent;
+    triangle.normal0 = DecodeOctahedral(quantized.normals[0]);
+    triangle.normal1 = DecodeOctahedral(quantized.normals[1]);
+    triangle.normal2 = DecodeOctahedral(quantized.normals[2]);
+    triangle.material = quantized.material;
+    return triangle;
+}
+
 // --- Raytracing functions ---
 /**
  * Gets the first intersection which the ray might make.
@@ -317,6 +877,14 @@ HitInfo CalculateRayCollision(Ray ray) {
     HitInfo closestHit = HitInfo0;
     closestHit.dist = -1;
 
+    if (countRays != 0)
+        atomicAdd(rayCounter[primaryRay ? 0 : 1], 1);
+    primaryRay = false;
+
+    // Rays which miss the whole scene can't hit anything in it
+    if (settings.clipToBounds != 0 && !RayBoundingBox( ray, sceneBoundsMin, sceneBoundsMax ))
+        return closestHit;
+
     // Raycast spheres
     for (int i = 0; i < spheresCount; i++) {
         Sphere sphere = spheres[i];
@@ -325,6 +893,7 @@ HitInfo CalculateRayCollision(Ray ray) {
         {
             closestHit = hitInfo;
             closestHit.material = sphere.material;
+            closestHit.sphere = i;
         }
     }
 
@@ -337,7 +906,7 @@ HitInfo CalculateRayCollision(Ray ray) {
             continue;
         
         for (uint j = meshInfo.startIndex; j < meshInfo.startIndex + meshInfo.count; j++) {
-            Triangle triangle = triangles[j];
+            Triangle triangle = LoadTriangle(j, meshInfo);
 
             // "Clip" meshes (cull triangles) if enabled
             if ( CLIP_MESHES && !RayBoundingBox( ray, min(min(triangle.p0, triangle.p1), triangle.p2), max(max(triangle.p0, triangle.p1), triangle.p2) ) )
@@ -348,99 +917,1771 @@ HitInfo CalculateRayCollision(Ray ray) {
             {
                 closestHit = hitInfo;
                 closestHit.material = triangle.material;
+                closestHit.mesh = i;
             }
         }
     }
 
+    // Ignore collisions beyond the maximum distance
+    if (closestHit.didHit && closestHit.dist > settings.tMax) {
+        closestHit = HitInfo0;
+        closestHit.dist = -1;
+    }
+
     // Return the collision which occured closest to the origin
     return closestHit;
 }
 
+// --- Medium functions ---
 /**
- * Traces a ray's path as it bounces around the scene, collecting hit information along the way.
+ * Gets the fraction of light which makes it through the fog over a distance without scattering.
+ *
+ * @param dist The distance.
+ * @return The transmittance, or 1 without fog.
+ */
+float FogTransmittance(float dist) {
+    return settings.fog != 0 ? exp(-settings.fogMedium.density * dist) : 1;
+}
+
+/**
+ * Gets the Henyey-Greenstein phase function, which is how likely light is to scatter by an angle.
+ *
+ * @param cosTheta The cosine of the angle between the directions the light travels in before and after scattering.
+ * @param g The anisotropy, [-1, 1].
+ *
+ * @return The phase function, which is also the pdf of SampleHenyeyGreenstein().
+ */
+float HenyeyGreenstein(float cosTheta, float g) {
+    float denominator = 1 + g*g - 2 * g * cosTheta;
+    return (1 - g*g) / (4 * PI * denominator * sqrt(denominator));
+}
+
+/**
+ * Picks the direction light scatters in, proportionally to the Henyey-Greenstein phase function.
+ *
+ * @param dir The direction the light travels in before scattering.
+ * @param g The anisotropy, [-1, 1].
+ * @param u A pair of random numbers, [0, 1].
+ *
+ * @return The direction after scattering.
+ */
+vec3 SampleHenyeyGreenstein(vec3 dir, float g, vec2 u) {
+    float   cosTheta = abs(g) < 1e-3
+                ? 1 - 2 * u.x
+                : (1 + g*g - pow((1 - g*g) / (1 - g + 2 * g * u.x), 2)) / (2 * g),
+            sinTheta = sqrt(max(0, 1 - cosTheta*cosTheta)),
+            phi = u.y * 2 * PI;
+    vec3    tangent = normalize(cross(abs(dir.x) > 0.5 ? vec3(0, 1, 0) : vec3(1, 0, 0), dir)),
+            bitangent = cross(dir, tangent);
+    return tangent * cos(phi) * sinTheta + bitangent * sin(phi) * sinTheta + dir * cosTheta;
+}
+
+/**
+ * Gets the density of a volume at a point.
+ *
+ * @param volume The index of the volume.
+ * @param pos The point, which must be within the volume's bounds.
+ *
+ * @return The density.
+ */
+float VolumeDensity(int volume, vec3 pos) {
+    vec3 uvw = (pos - volumes[volume].boundsMin) / (volumes[volume].boundsMax - volumes[volume].boundsMin);
+    return SampleVolumeTexture(volumeDensities, volume, uvw).r * volumes[volume].density;
+}
+
+/**
+ * Gets the color of the light emitted by a black body, using Planck's law at a wavelength for each color channel.
+ *
+ * @param temperature The temperature, in kelvin.
+ * @return The color, where the brightest channel is 1.
+ */
+vec3 BlackbodyColor(float temperature) {
+    const vec3  wavelengths = vec3(610e-9, 550e-9, 465e-9);
+    const float c2 = 1.4388e-2; // Second radiation constant, h*c/k (m*K)
+    vec3 radiance = 1 / (pow(wavelengths, vec3(5)) * (exp(c2 / (wavelengths * max(temperature, 1))) - 1));
+    return radiance / max(radiance.r, max(radiance.g, radiance.b));
+}
+
+/**
+ * Gets the light a volume emits at a point, per unit of absorption.
+ *
+ * @param volume The index of the volume.
+ * @param pos The point, which must be within the volume's bounds.
+ *
+ * @return The light.
+ */
+vec3 VolumeEmission(int volume, vec3 pos) {
+    if (volumes[volume].emission <= 0) return vec3(0);
+
+    vec3    uvw = (pos - volumes[volume].boundsMin) / (volumes[volume].boundsMax - volumes[volume].boundsMin);
+    float   t = SampleVolumeTexture(volumeTemperatures, volume, uvw).r;
+    return BlackbodyColor(t * volumes[volume].maxTemperature) * volumes[volume].emission * t*t*t*t;
+}
+
+/**
+ * Finds where a ray scatters in the volumes, if it does so before a distance, using delta tracking.
+ * Delta tracking takes steps as if the volume was as dense as it gets everywhere, and at each step it scatters with the probability of the density there being real.
  *
  * @param ray The ray.
+ * @param dist The distance, which is changed to where the ray scatters if it does.
+ * @param albedo The albedo of the volume the ray scatters in.
+ * @param anisotropy The anisotropy of the volume the ray scatters in.
+ * @param volume The index of the volume the ray scatters in.
  * @param seed The seed, which is changed after use.
  *
- * @return The end color of the ray.
+ * @return If the ray scatters before the distance.
  */
-vec3 Trace(Ray ray, inout uint seed) {
-    vec3 	incomingLight = vec3(0),
-            rayColor = vec3(1);
-    bool	hitAny = false;
-    
-    for (int i = 0; i < settings.maxBounces; i++)
-    {
-        HitInfo hitInfo = CalculateRayCollision(ray);
-        if (hitInfo.didHit)
-        {
-            hitAny = true;
-            Material material = hitInfo.material;
-            
-            // Calculate new pos and dir
-            ray.origin = hitInfo.pos;
-
-            bool 	isSpecular  = material.specularColor.w >= randFloat(seed);
-            vec3 	specularDir = reflect(ray.dir, hitInfo.normal),
-                    diffuseDir  = normalize(hitInfo.normal + randVecNormDist(seed));
-            ray.dir = normalize(mix(diffuseDir, specularDir, material.smoothness * int(isSpecular)));
+bool SampleVolumes(Ray ray, inout float dist, inout vec3 albedo, inout float anisotropy, out int volume, inout uint seed) {
+    bool scattered = false;
+    volume = -1;
+    for (int v = 0; v < volumesCount; v++) {
+        vec2    range = RayBoundingBoxDistances(ray, volumes[v].boundsMin, volumes[v].boundsMax);
+        float   t = max(range.x, 0),
+                end = min(range.y, dist);
+        if (t >= end || volumes[v].density <= 0) continue;
 
-            // Update light and color
-            vec3 emittedLight = material.emissionColor.xyz * material.emissionColor.w;
-            incomingLight += emittedLight * rayColor;
-            rayColor *= mix(material.color, material.specularColor, isSpecular);
-
-            // Early exit if ray color ~= 0
-            // (Use some randomness to avoid "artificial" look)
-            float p = max(rayColor.r, max(rayColor.g, rayColor.b));
-            if (randFloat(seed) >= p) break;
-            rayColor *= 1.0f / p;
-        } else 
-        {
-            // If the ray did not hit anything, sample color from environment and return
-            incomingLight += GetEnvironmentLight(ray) * rayColor;
-            break;
+        for (int j = 0; j < MAX_TRACKING_STEPS; j++) {
+            t -= log(1 - randFloat(seed)) / volumes[v].density;
+            if (t >= end) break;
+            if (randFloat(seed) * volumes[v].density < VolumeDensity(v, ray.origin + ray.dir * t)) {
+                // (Later volumes only need to be tracked up to this point)
+                dist = t;
+                albedo = volumes[v].albedo;
+                anisotropy = volumes[v].anisotropy;
+                volume = v;
+                scattered = true;
+                break;
+            }
         }
     }
-
-    // (Return)
-    return incomingLight;
+    return scattered;
 }
 
-// The main function
-void main()
-{
-    // Create seed for RNG
-    vec2 uv = vec2( gl_FragCoord.x / camera.screenSize.x, gl_FragCoord.y / camera.screenSize.y );
-    uint i = uint( gl_FragCoord.y * camera.screenSize.x + gl_FragCoord.x );
-    uint seed = i; //+ Frame * 719393; // TODO: Add frame counter so RNG changes over time
+/**
+ * Gets the fraction of light which makes it through the fog and volumes along a ray without scattering.
+ * The volumes use ratio tracking, which takes the same steps as delta tracking but multiplies by the chance of passing each step instead of stopping.
+ *
+ * @param ray The ray.
+ * @param dist The distance along the ray.
+ * @param seed The seed, which is changed after use.
+ *
+ * @return An estimate of the transmittance, which is right on average.
+ */
+float Transmittance(Ray ray, float dist, inout uint seed) {
+    float transmittance = FogTransmittance(dist);
+    for (int v = 0; v < volumesCount && transmittance > 0; v++) {
+        vec2    range = RayBoundingBoxDistances(ray, volumes[v].boundsMin, volumes[v].boundsMax);
+        float   t = max(range.x, 0),
+                end = min(range.y, dist);
+        if (t >= end || volumes[v].density <= 0) continue;
 
-    // Calculate focus point
-    float   planeHeight = camera.focusDistance * tan(camera.fov * 0.5 * PI / 180.0) * 2.0,
-            planeWidth = planeHeight * (camera.screenSize.x / camera.screenSize.y);
-    vec3    viewParams = vec3( planeWidth, planeHeight, camera.focusDistance );
+        for (int j = 0; j < MAX_TRACKING_STEPS; j++) {
+            t -= log(1 - randFloat(seed)) / volumes[v].density;
+            if (t >= end) break;
+            transmittance *= 1 - VolumeDensity(v, ray.origin + ray.dir * t) / volumes[v].density;
+        }
+    }
+    return transmittance;
+}
 
-    vec3    focusPointLocal = vec3(uv - 0.5, 1) * viewParams,
-            focusPoint = (camera.localToWorld * vec4(focusPointLocal, 1)).xyz,
-            camUp = normalize(camera.localToWorld[1].xyz),
-            camRight = normalize(camera.localToWorld[0].xyz);
+// --- Light sampling functions ---
+/**
+ * Picks one of the emissive spheres at random, and a direction uniformly within the cone it covers as seen from a point.
+ *
+ * @param pos The point.
+ * @param surfaceSphere The index of the sphere the point is on, which can't light itself, or -1.
+ * @param bounce The bounce the point was found at, which decides the sample dimensions to use.
+ * @param light The index of the picked light, or -1 if none could be picked.
+ * @param dir The direction.
+ *
+ * @return The light arriving along the direction if it isn't blocked, divided by the probability of picking it.
+ */
+vec3 SampleLightDirection(vec3 pos, int surfaceSphere, int bounce, out int light, out vec3 dir) {
+    dir = vec3(0);
 
-    // Fire rays
-    Ray ray;
-    vec3 totalIncomingLight = vec3(0);
+    // Count the lights, then pick one
+    int lightCount = 0;
+    for (int i = 0; i < spheresCount; i++)
+        if (spheres[i].material.emissionColor.w > 0) lightCount++;
+    light = -1;
+    if (lightCount == 0) return vec3(0);
 
-    for ( int i = 0; i < settings.raysPerFrag; i++ )
-    {
-        // Calculate ray origin and dir
-        vec2 jitter = randVecCartesianNormDist(seed) * settings.divergeStrength / camera.screenSize.x;
-        vec3 focusPointJittered = focusPoint + camRight*jitter.x + camUp*jitter.y;
+    for (int i = 0, skip = min(int(Sample2D(BounceDimension(bounce, BOUNCE_LIGHT_CHOICES)).x * lightCount), lightCount - 1); light < 0; i++)
+        if (spheres[i].material.emissionColor.w > 0 && skip-- == 0) light = i;
+    Sphere sphere = spheres[light];
 
-        ray.origin = camera.pos;
-        ray.dir = normalize(focusPointJittered - ray.origin);
-        totalIncomingLight += Trace(ray, seed);
+    // Pick a direction within the cone of the sphere, as seen from the point
+    vec3    toLight = sphere.center - pos;
+    float   dist2 = dot(toLight, toLight);
+    if (light == surfaceSphere || dist2 <= sphere.radius * sphere.radius) {
+        light = -1;
+        return vec3(0);
     }
 
-    // Return final color (average of the frag's rays)
-    vec3 fragCol = totalIncomingLight / settings.raysPerFrag;
-    color = vec4( fragCol, 1 );
-}
\ No newline at end of file
+    vec2    u = Sample2D(BounceDimension(bounce, BOUNCE_LIGHT));
+    float   cosMax = sqrt(1 - sphere.radius * sphere.radius / dist2),
+            cosTheta = 1 - u.x * (1 - cosMax),
+            sinTheta = sqrt(max(0, 1 - cosTheta*cosTheta)),
+            phi = u.y * 2 * PI;
+    vec3    w = normalize(toLight),
+            tangent = normalize(cross(abs(w.x) > 0.5 ? vec3(0, 1, 0) : vec3(1, 0, 0), w)),
+            bitangent = cross(w, tangent);
+    dir = tangent * cos(phi) * sinTheta + bitangent * sin(phi) * sinTheta + w * cosTheta;
+
+    // Divided by the pdf of the cone (1 / (2*PI*(1 - cosMax))) and the probability of picking the light
+    vec3 emittedLight = sphere.material.emissionColor.xyz * sphere.material.emissionColor.w;
+    return emittedLight * 2 * PI * (1 - cosMax) * lightCount;
+}
+
+/**
+ * Samples the light arriving directly from emissive spheres at a point (next-event estimation).
+ * One light is picked at random, and a direction is picked uniformly within the cone it covers, after which a shadow ray checks if it's visible.
+ * The result is weighted for diffuse surfaces, so it only needs to be multiplied by the surface's color.
+ *
+ * @param pos The point.
+ * @param normal The surface normal at the point.
+ * @param surfaceSphere The index of the sphere the point is on, which can't light itself, or -1.
+ * @param bounce The bounce the point was found at, which decides the sample dimensions to use.
+ * @param seed The seed, which is changed after use.
+ *
+ * @return The light reflected from the point, divided by the surface's color.
+ */
+vec3 SampleLights(vec3 pos, vec3 normal, int surfaceSphere, int bounce, inout uint seed) {
+    int     light;
+    vec3    dir,
+            lightRadiance = SampleLightDirection(pos, surfaceSphere, bounce, light, dir);
+    float   cosSurface = dot(dir, normal);
+    if (light < 0 || cosSurface <= 0) return vec3(0);
+
+    // Check that the light isn't blocked
+    Ray     shadowRay = Ray(pos + normal * kEpsilion, dir);
+    HitInfo shadowHit = CalculateRayCollision(shadowRay);
+    if (!shadowHit.didHit || shadowHit.sphere != light) return vec3(0);
+
+    // Lambertian BRDF (1/PI) * cosine
+    return lightRadiance * cosSurface / PI * Transmittance(shadowRay, shadowHit.dist, seed);
+}
+
+/**
+ * Samples the light arriving directly from emissive spheres at a point in the fog or a volume, which scatters it towards the previous point of the path.
+ *
+ * @param pos The point.
+ * @param rayDir The direction the path was travelling in when it reached the point.
+ * @param anisotropy The anisotropy of the medium at the point.
+ * @param bounce The bounce the point was found at, which decides the sample dimensions to use.
+ * @param seed The seed, which is changed after use.
+ *
+ * @return The light scattered from the point, divided by the medium's albedo.
+ */
+vec3 SampleLightsInMedium(vec3 pos, vec3 rayDir, float anisotropy, int bounce, inout uint seed) {
+    int     light;
+    vec3    dir,
+            lightRadiance = SampleLightDirection(pos, -1, bounce, light, dir);
+    if (light < 0) return vec3(0);
+
+    Ray     shadowRay = Ray(pos, dir);
+    HitInfo shadowHit = CalculateRayCollision(shadowRay);
+    if (!shadowHit.didHit || shadowHit.sphere != light) return vec3(0);
+
+    return lightRadiance * HenyeyGreenstein(dot(rayDir, dir), anisotropy) * Transmittance(shadowRay, shadowHit.dist, seed);
+}
+
+// --- Sun functions ---
+/**
+ * Gets the light arriving from the sun at a point, if it isn't blocked.
+ *
+ * @param pos The point.
+ * @param seed The seed, which is changed after use.
+ *
+ * @return The light, after passing through the fog and volumes within the scene's bounds.
+ */
+vec3 SunLight(vec3 pos, inout uint seed) {
+    Ray     shadowRay = Ray(pos, settings.sunLight.direction);
+    if (CalculateRayCollision(shadowRay).didHit) return vec3(0);
+
+    float   dist = clamp(RayBoundingBoxDistances(shadowRay, sceneBoundsMin, sceneBoundsMax).y, 0, settings.tMax);
+    return settings.sunLight.color * Transmittance(shadowRay, dist, seed);
+}
+
+/**
+ * Gets the sun's light scattered towards the start of a ray by the fog (god rays), by taking evenly spaced steps along it.
+ * The steps are offset randomly, so the accumulated result is smooth.
+ *
+ * @param ray The ray.
+ * @param dist The distance to where the ray hits a surface, or settings.tMax.
+ * @param seed The seed, which is changed after use.
+ *
+ * @return The light.
+ */
+vec3 GodRays(Ray ray, float dist, inout uint seed) {
+    if (settings.fog == 0 || settings.sun == 0 || settings.sunLight.godRaySteps == 0) return vec3(0);
+
+    float   end = min(dist, RayBoundingBoxDistances(ray, sceneBoundsMin, sceneBoundsMax).y);
+    if (end <= 0) return vec3(0);
+
+    // Single scattering: Transmittance to the point * scattering coefficient * phase function * light arriving at the point
+    float   stepLength = end / settings.sunLight.godRaySteps,
+            offset = randFloat(seed),
+            phase = HenyeyGreenstein(dot(ray.dir, settings.sunLight.direction), settings.fogMedium.anisotropy);
+    vec3    light = vec3(0);
+    for (uint j = 0; j < settings.sunLight.godRaySteps; j++) {
+        float t = (j + offset) * stepLength;
+        light += Transmittance(ray, t, seed) * SunLight(ray.origin + ray.dir * t, seed);
+    }
+    return light * settings.fogMedium.density * settings.fogMedium.albedo * phase * stepLength;
+}
+
+// --- ReSTIR functions ---
+// Index of the frag's pixel, for looking up its reservoir
+uint pixelIndex;
+
+/**
+ * Gets the luminance of a color.
+ *
+ * @param color The color.
+ * @return The luminance.
+ */
+float Luminance(vec3 color) {
+    return dot(color, vec3(0.2126, 0.7152, 0.0722));
+}
+
+/**
+ * Gets the light a diffuse surface reflects from a point on a light, ignoring whether it's blocked.
+ *
+ * @param pos The point on the surface.
+ * @param normal The surface normal.
+ * @param lightPos The point on the light.
+ * @param light The index of the light.
+ *
+ * @return The light reflected from the surface, divided by its color.
+ */
+vec3 LightContribution(vec3 pos, vec3 normal, vec3 lightPos, int light) {
+    Sphere  sphere = spheres[light];
+    vec3    toLight = lightPos - pos;
+    float   dist2 = dot(toLight, toLight);
+    vec3    dir = toLight / sqrt(dist2);
+    float   cosSurface = dot(dir, normal),
+            cosLight = dot(-dir, normalize(lightPos - sphere.center));
+    if (cosSurface <= 0 || cosLight <= 0) return vec3(0);
+
+    vec3 emittedLight = sphere.material.emissionColor.xyz * sphere.material.emissionColor.w;
+    return emittedLight / PI * cosSurface * cosLight / dist2;
+}
+
+/**
+ * Gets the target function of a light sample, which the reservoirs pick samples proportionally to.
+ *
+ * @param pos The point on the surface.
+ * @param normal The surface normal.
+ * @param lightPos The point on the light.
+ * @param light The index of the light, or -1.
+ *
+ * @return The target function.
+ */
+float RestirTarget(vec3 pos, vec3 normal, vec3 lightPos, int light) {
+    return light < 0 ? 0 : Luminance(LightContribution(pos, normal, lightPos, light));
+}
+
+/**
+ * Checks if a point on a light can be seen from a surface.
+ *
+ * @param pos The point on the surface.
+ * @param normal The surface normal.
+ * @param lightPos The point on the light.
+ * @param light The index of the light.
+ *
+ * @return If the point on the light is visible.
+ */
+bool LightVisible(vec3 pos, vec3 normal, vec3 lightPos, int light) {
+    HitInfo shadowHit = CalculateRayCollision(Ray(pos + normal * kEpsilion, normalize(lightPos - pos)));
+    return shadowHit.didHit && shadowHit.sphere == light;
+}
+
+/**
+ * Streams a sample into a reservoir, which then picks it with a probability proportional to its weight.
+ *
+ * @param reservoir The reservoir.
+ * @param lightPos The point on the light.
+ * @param light The index of the light.
+ * @param weight The weight of the sample.
+ * @param M The amount of samples the sample counts as.
+ * @param seed The seed, which is changed after use.
+ */
+void UpdateReservoir(inout Reservoir reservoir, vec3 lightPos, int light, float weight, float M, inout uint seed) {
+    reservoir.wSum += weight;
+    reservoir.M += M;
+    if (weight > 0 && randFloat(seed) * reservoir.wSum <= weight) {
+        reservoir.samplePos = lightPos;
+        reservoir.light = light;
+    }
+}
+
+/**
+ * Merges a reservoir into another, as seen from the other's surface.
+ *
+ * @param reservoir The reservoir to merge into.
+ * @param other The reservoir to merge.
+ * @param seed The seed, which is changed after use.
+ */
+void MergeReservoir(inout Reservoir reservoir, Reservoir other, inout uint seed) {
+    float target = RestirTarget(reservoir.surfacePos, reservoir.surfaceNormal, other.samplePos, other.light);
+    UpdateReservoir(reservoir, other.samplePos, other.light, target * other.W * other.M, other.M, seed);
+}
+
+/**
+ * Calculates the weight of the sample a reservoir picked.
+ *
+ * @param reservoir The reservoir.
+ */
+void FinalizeReservoir(inout Reservoir reservoir) {
+    float target = RestirTarget(reservoir.surfacePos, reservoir.surfaceNormal, reservoir.samplePos, reservoir.light);
+    reservoir.W = target > 0 ? reservoir.wSum / (reservoir.M * target) : 0;
+}
+
+/**
+ * Creates an empty reservoir for a surface.
+ *
+ * @param pos The point on the surface.
+ * @param normal The surface normal.
+ *
+ * @return The reservoir.
+ */
+Reservoir EmptyReservoir(vec3 pos, vec3 normal) {
+    return Reservoir(pos, 0, normal, 0, vec3(0), 0, -1);
+}
+
+/**
+ * Checks if a reservoir was made for a similar enough surface that it can be reused.
+ *
+ * @param reservoir The reservoir.
+ * @param pos The point on the surface.
+ * @param normal The surface normal.
+ *
+ * @return If the reservoir can be reused.
+ */
+bool ReservoirReusable(Reservoir reservoir, vec3 pos, vec3 normal) {
+    return reservoir.M > 0
+        && dot(reservoir.surfaceNormal, normal) > 0.9
+        && distance(reservoir.surfacePos, pos) < 0.05 * distance(pos, camera.pos);
+}
+
+/**
+ * The initial ReSTIR pass.
+ * Picks a light sample for the first surface the frag's ray hits out of random candidates, then reuses the previous frame's reservoir.
+ *
+ * @param ray The ray.
+ * @param seed The seed, which is changed after use.
+ */
+void RestirInitial(Ray ray, inout uint seed) {
+    HitInfo hitInfo = CalculateRayCollision(ray);
+    Reservoir reservoir = EmptyReservoir(hitInfo.pos, hitInfo.normal);
+
+    int lightCount = 0;
+    for (int i = 0; i < spheresCount; i++)
+        if (spheres[i].material.emissionColor.w > 0) lightCount++;
+
+    if (!hitInfo.didHit || lightCount == 0) {
+        reservoirs[pixelIndex] = reservoir;
+        return;
+    }
+
+    // Pick out of candidates, which are uniformly distributed over the lights' surfaces
+    for (int k = 0; k < RESTIR_CANDIDATES; k++) {
+        int light = -1;
+        for (int i = 0, skip = int(randInt(seed) % lightCount); light < 0; i++)
+            if (spheres[i].material.emissionColor.w > 0 && skip-- == 0) light = i;
+        if (light == hitInfo.sphere) {
+            reservoir.M += 1;
+            continue;
+        }
+
+        Sphere  sphere = spheres[light];
+        vec3    lightPos = sphere.center + SampleSphere(vec2(randFloat(seed), randFloat(seed))) * sphere.radius;
+        float   sourcePdf = 1.0 / (lightCount * 4 * PI * sphere.radius * sphere.radius),
+                target = RestirTarget(hitInfo.pos, hitInfo.normal, lightPos, light);
+        UpdateReservoir(reservoir, lightPos, light, target / sourcePdf, 1, seed);
+    }
+    FinalizeReservoir(reservoir);
+
+    // Blocked samples are thrown away, so they aren't spread to other frames and pixels
+    if (reservoir.light >= 0 && !LightVisible(hitInfo.pos, hitInfo.normal, reservoir.samplePos, reservoir.light))
+        reservoir.W = 0;
+
+    // Temporal reuse
+    Reservoir previous = finalReservoirs[pixelIndex];
+    if (ReservoirReusable(previous, hitInfo.pos, hitInfo.normal)) {
+        previous.M = min(previous.M, RESTIR_TEMPORAL_MAX_M * reservoir.M);
+
+        Reservoir merged = EmptyReservoir(hitInfo.pos, hitInfo.normal);
+        MergeReservoir(merged, reservoir, seed);
+        MergeReservoir(merged, previous, seed);
+        FinalizeReservoir(merged);
+        reservoir = merged;
+    }
+
+    reservoirs[pixelIndex] = reservoir;
+}
+
+/**
+ * The spatial ReSTIR pass.
+ * Reuses the reservoirs of random neighbouring pixels which see similar surfaces.
+ *
+ * @param seed The seed, which is changed after use.
+ */
+void RestirSpatial(inout uint seed) {
+    Reservoir center = reservoirs[pixelIndex];
+    if (center.M == 0) {
+        finalReservoirs[pixelIndex] = center;
+        return;
+    }
+
+    Reservoir merged = EmptyReservoir(center.surfacePos, center.surfaceNormal);
+    MergeReservoir(merged, center, seed);
+
+    ivec2 screenSize = ivec2(camera.screenSize);
+    for (int k = 0; k < RESTIR_SPATIAL_NEIGHBOURS; k++) {
+        ivec2 neighbourPixel = clamp(
+            ivec2(fragCoord.xy + SampleDisc(vec2(randFloat(seed), randFloat(seed))) * RESTIR_SPATIAL_RADIUS),
+            ivec2(0), screenSize - 1
+        );
+        Reservoir neighbour = reservoirs[neighbourPixel.y * screenSize.x + neighbourPixel.x];
+        if (ReservoirReusable(neighbour, center.surfacePos, center.surfaceNormal))
+            MergeReservoir(merged, neighbour, seed);
+    }
+    FinalizeReservoir(merged);
+
+    finalReservoirs[pixelIndex] = merged;
+}
+
+/**
+ * Gets the direct light at the first surface from the frag's final reservoir.
+ *
+ * @param pos The point on the surface.
+ * @param normal The surface normal.
+ *
+ * @return The light reflected from the surface, divided by its color.
+ */
+vec3 RestirShade(vec3 pos, vec3 normal) {
+    Reservoir reservoir = finalReservoirs[pixelIndex];
+    if (reservoir.light < 0 || reservoir.W <= 0 || !LightVisible(pos, normal, reservoir.samplePos, reservoir.light))
+        return vec3(0);
+
+    return LightContribution(pos, normal, reservoir.samplePos, reservoir.light) * reservoir.W;
+}
+
+// --- Path guiding functions ---
+/**
+ * Gets the path guide's cell containing a point.
+ * Points outside the guide's bounds use the closest cell.
+ *
+ * @param pos The point.
+ * @return The index of the cell.
+ */
+uint GuidingCell(vec3 pos) {
+    ivec3 cell = clamp(
+        ivec3((pos - guidingBoundsMin) / (guidingBoundsMax - guidingBoundsMin) * GUIDING_RESOLUTION),
+        ivec3(0), ivec3(GUIDING_RESOLUTION - 1)
+    );
+    return (cell.z * GUIDING_RESOLUTION + cell.y) * GUIDING_RESOLUTION + cell.x;
+}
+
+/**
+ * Gets the directional bin a direction falls in.
+ * The bins cover equal areas of the sphere, splitting it evenly by height (z) and angle around the z axis.
+ *
+ * @param dir The direction.
+ * @return The index of the bin.
+ */
+int GuidingBin(vec3 dir) {
+    int u = clamp(int((dir.z + 1) * 0.5 * GUIDING_BINS_SIDE), 0, GUIDING_BINS_SIDE - 1),
+        v = clamp(int((atan(dir.y, dir.x) / (2 * PI) + 0.5) * GUIDING_BINS_SIDE), 0, GUIDING_BINS_SIDE - 1);
+    return v * GUIDING_BINS_SIDE + u;
+}
+
+/**
+ * Gets the total light learned by a cell.
+ *
+ * @param cell The index of the cell.
+ * @return The total light.
+ */
+float GuidingTotal(uint cell) {
+    float total = 0;
+    for (int bin = 0; bin < GUIDING_BINS; bin++)
+        total += guidingSampling[cell * GUIDING_BINS + bin];
+    return total;
+}
+
+/**
+ * Gets the probability density of the guide picking a direction.
+ *
+ * @param cell The index of the cell.
+ * @param total The total light learned by the cell.
+ * @param dir The direction.
+ *
+ * @return The probability density, per steradian.
+ */
+float GuidingPdf(uint cell, float total, vec3 dir) {
+    return guidingSampling[cell * GUIDING_BINS + GuidingBin(dir)] / total * GUIDING_BINS / (4 * PI);
+}
+
+/**
+ * Picks a direction using the guide, proportionally to the light learned for each bin.
+ *
+ * @param cell The index of the cell.
+ * @param total The total light learned by the cell.
+ * @param u A pair of random numbers, [0, 1], for picking the direction within the bin.
+ * @param seed The seed, which is changed after use.
+ *
+ * @return The direction.
+ */
+vec3 SampleGuide(uint cell, float total, vec2 u, inout uint seed) {
+    float target = randFloat(seed) * total;
+    int bin = 0;
+    for (; bin < GUIDING_BINS - 1; bin++) {
+        target -= guidingSampling[cell * GUIDING_BINS + bin];
+        if (target < 0) break;
+    }
+
+    float   z = ((bin % GUIDING_BINS_SIDE) + u.x) / GUIDING_BINS_SIDE * 2 - 1,
+            phi = (((bin / GUIDING_BINS_SIDE) + u.y) / GUIDING_BINS_SIDE - 0.5) * 2 * PI,
+            r = sqrt(max(0, 1 - z*z));
+    return vec3(cos(phi) * r, sin(phi) * r, z);
+}
+
+/**
+ * Adds light arriving at a point from a direction to the guide.
+ * Bins stop learning once they're close to overflowing.
+ *
+ * @param pos The point.
+ * @param dir The direction the light arrives from.
+ * @param light The luminance of the light.
+ */
+void LearnGuide(vec3 pos, vec3 dir, float light) {
+    uint    index = GuidingCell(pos) * GUIDING_BINS + GuidingBin(dir),
+            value = uint(min(light * GUIDING_SCALE, 1024));
+    if (value > 0 && guidingLearning[index] < (1u << 30))
+        atomicAdd(guidingLearning[index], value);
+}
+
+// --- Light tracing functions ---
+/**
+ * Checks if light tracing is used, which needs the bidirectional integrator and a pinhole camera.
+ *
+ * @return If light tracing is used.
+ */
+bool LightTracingActive() {
+    return settings.integrator == INTEGRATOR_BIDIRECTIONAL
+        && camera.projection == PROJECTION_PERSPECTIVE
+        && camera.stereo == STEREO_MONO
+        && camera.apertureRadius == 0
+        && camera.motionBlur == 0
+        && camera.distortion == vec2(0)
+        && camera.chromaticAberration == 0;
+}
+
+/**
+ * Connects a point on a surface to the camera, splatting the light it reflects towards it onto the pixel it lands on.
+ *
+ * @param pos The point.
+ * @param normal The surface normal.
+ * @param weight The light arriving at the point, multiplied by the surface's BRDF.
+ */
+void SplatToCamera(vec3 pos, vec3 normal, vec3 weight) {
+    vec3    camRight = normalize(camera.localToWorld[0].xyz),
+            camUp = normalize(camera.localToWorld[1].xyz),
+            camFront = normalize(camera.localToWorld[2].xyz),
+            eyePos = camera.localToWorld[3].xyz,
+            toPoint = pos - eyePos;
+    float   dist = length(toPoint);
+    vec3    dir = toPoint / dist,
+            local = vec3(dot(dir, camRight), dot(dir, camUp), dot(dir, camFront));
+    float   cosSurface = -dot(dir, normal);
+    if (local.z <= 0 || cosSurface <= 0) return;
+
+    // Find the pixel, using the size of the image's plane at distance 1
+    // (Pixels outside of the screen's window of the image are left to the windows they are in)
+    float   planeHeight = tan(camera.fov * 0.5 * PI / 180.0) * 2.0,
+            planeWidth = planeHeight * camera.imageSize.x / camera.imageSize.y;
+    vec2    uv = local.xy / local.z / vec2(planeWidth, planeHeight) + 0.5;
+    vec2    screenPixel = uv * camera.imageSize - camera.windowOffset;
+    if (any(lessThan(screenPixel, vec2(0))) || any(greaterThanEqual(screenPixel, camera.screenSize))) return;
+
+    // Check that the camera sees the point (which also makes sure faces culled by camera rays aren't splatted)
+    HitInfo cameraHit = CalculateRayCollision(Ray(eyePos, dir));
+    if (!cameraHit.didHit || abs(cameraHit.dist - dist) > kEpsilion * max(1, dist)) return;
+
+    // The camera's importance is 1 / (planeArea * cos^4), and converting from the point's solid angle to the camera adds cosSurface * cos / dist^2
+    float   cosCamera = local.z,
+            importance = cosSurface / (planeWidth * planeHeight * cosCamera * cosCamera * cosCamera * dist * dist);
+    // (The importance is for as many paths as the image has pixels, so windows which trace fewer make up for it)
+    importance *= (camera.imageSize.x * camera.imageSize.y) / (camera.screenSize.x * camera.screenSize.y);
+    uvec3   value = uvec3(min(weight * importance, SPLAT_MAX) * SPLAT_SCALE);
+
+    ivec2   pixel = ivec2(screenPixel);
+    uint    index = (uint(pixel.y) * uint(camera.screenSize.x) + uint(pixel.x)) * 3;
+    atomicAdd(splats[index], value.r);
+    atomicAdd(splats[index + 1], value.g);
+    atomicAdd(splats[index + 2], value.b);
+}
+
+/**
+ * Traces paths from the lights, connecting every surface they hit to the camera.
+ * Each frag traces as many paths as it fires rays, so every pixel gets the same amount of paths from the camera and the lights.
+ *
+ * @param seed The seed, which is changed after use.
+ */
+void TraceLightPaths(inout uint seed) {
+    int lightCount = 0;
+    for (int i = 0; i < spheresCount; i++)
+        if (spheres[i].material.emissionColor.w > 0) lightCount++;
+    if (lightCount == 0) return;
+
+    // Paths from the camera reach at most maxBounces surfaces before a light when sampling lights directly, and one less otherwise
+    int maxVertices = int(settings.maxBounces) - (settings.nextEventEstimation != 0 ? 0 : 1);
+
+    for (int r = 0; r < settings.raysPerFrag; r++) {
+        // Pick a point uniformly on the lights' surfaces, and a cosine-weighted direction from it
+        int light = -1;
+        for (int i = 0, skip = int(randInt(seed) % lightCount); light < 0; i++)
+            if (spheres[i].material.emissionColor.w > 0 && skip-- == 0) light = i;
+        Sphere  sphere = spheres[light];
+        vec3    lightNormal = SampleSphere(vec2(randFloat(seed), randFloat(seed))),
+                lightPos = sphere.center + lightNormal * sphere.radius;
+        Ray     ray = Ray(lightPos + lightNormal * kEpsilion, normalize(lightNormal + SampleSphere(vec2(randFloat(seed), randFloat(seed)))));
+
+        // Emitted light * cos / (area pdf * cosine-weighted direction pdf), where the area pdf is 1 / (lightCount * sphere area)
+        vec3    emittedLight = sphere.material.emissionColor.xyz * sphere.material.emissionColor.w,
+                beta = emittedLight * PI * lightCount * 4 * PI * sphere.radius * sphere.radius;
+
+        for (int i = 0; i < maxVertices; i++) {
+            HitInfo hitInfo = CalculateRayCollision(ray);
+            if (!hitInfo.didHit) break;
+            Material material = hitInfo.material;
+
+            // Connect the diffuse part of the surface to the camera, weighted equally with the paths from the camera
+            bool isDiffusePossible = material.specularColor.w < 1;
+            if (isDiffusePossible)
+                SplatToCamera(hitInfo.pos, hitInfo.normal, beta * (1 - material.specularColor.w) * material.color.xyz / PI * 0.5);
+
+            // Bounce, like paths from the camera
+            bool    isSpecular = material.specularColor.w >= randFloat(seed);
+            vec3    specularDir = reflect(ray.dir, hitInfo.normal),
+                    diffuseDir = normalize(hitInfo.normal + SampleSphere(vec2(randFloat(seed), randFloat(seed))));
+            ray = Ray(hitInfo.pos, normalize(mix(diffuseDir, specularDir, material.smoothness * int(isSpecular))));
+            beta *= isSpecular ? material.specularColor.xyz : material.color.xyz;
+
+            if (settings.roulette != ROULETTE_OFF && i >= settings.rouletteStart) {
+                float p = settings.roulette == ROULETTE_CONSTANT
+                    ? settings.rouletteSurvival
+                    : max(beta.r, max(beta.g, beta.b));
+                p = min(p, 1);
+                if (randFloat(seed) >= p) break;
+                beta *= 1.0f / p;
+            }
+        }
+    }
+}
+
+/**
+ * Gets the light splatted onto the frag's pixel by light tracing.
+ *
+ * @return The light, averaged over the paths each frag traced.
+ */
+vec3 GetSplattedLight() {
+    uint index = pixelIndex * 3;
+    return vec3(splats[index], splats[index + 1], splats[index + 2]) / SPLAT_SCALE / settings.raysPerFrag;
+}
+
+// --- Photon map functions ---
+/**
+ * Gets the photon map's cell containing a point.
+ * The cells are as large as the gather radius, so gathering only needs to look at the neighbouring cells.
+ *
+ * @param pos The point.
+ * @return The cell's coordinates.
+ */
+ivec3 PhotonCell(vec3 pos) {
+    return ivec3(floor(pos / settings.causticRadius));
+}
+
+/**
+ * Hashes a cell of the photon map to an index in its grid.
+ *
+ * @param cell The cell's coordinates.
+ * @return The index in the hash grid.
+ */
+uint PhotonCellHash(ivec3 cell) {
+    return (uint(cell.x * 73856093) ^ uint(cell.y * 19349663) ^ uint(cell.z * 83492791)) % PHOTON_GRID_SIZE;
+}
+
+/**
+ * Stores a photon in the photon map.
+ * Photons are dropped when the photon map is full.
+ *
+ * @param pos The point the photon hit.
+ * @param power The power of the photon.
+ */
+void StorePhoton(vec3 pos, vec3 power) {
+    uint index = atomicAdd(photonCount, 1);
+    if (index >= photonCapacity) return;
+
+    photons[index].pos = pos;
+    photons[index].power = power;
+    photons[index].next = atomicExchange(photonGrid[PhotonCellHash(PhotonCell(pos))], index);
+}
+
+/**
+ * Traces caustic photons from the lights.
+ * Photons are stored at every surface they hit after bouncing specularly at least once, and stop at the first diffuse bounce.
+ *
+ * @param seed The seed, which is changed after use.
+ */
+void TracePhotons(inout uint seed) {
+    int lightCount = 0;
+    for (int i = 0; i < spheresCount; i++)
+        if (spheres[i].material.emissionColor.w > 0) lightCount++;
+    if (lightCount == 0) return;
+
+    // The power of each photon is shared with all photons traced this frame
+    float photonsTraced = camera.screenSize.x * camera.screenSize.y * PHOTONS_PER_FRAG;
+
+    for (int p = 0; p < PHOTONS_PER_FRAG; p++) {
+        // Pick a point uniformly on the lights' surfaces, and a cosine-weighted direction from it
+        int light = -1;
+        for (int i = 0, skip = int(randInt(seed) % lightCount); light < 0; i++)
+            if (spheres[i].material.emissionColor.w > 0 && skip-- == 0) light = i;
+        Sphere  sphere = spheres[light];
+        vec3    lightNormal = SampleSphere(vec2(randFloat(seed), randFloat(seed))),
+                lightPos = sphere.center + lightNormal * sphere.radius;
+        Ray     ray = Ray(lightPos + lightNormal * kEpsilion, normalize(lightNormal + SampleSphere(vec2(randFloat(seed), randFloat(seed)))));
+
+        vec3    emittedLight = sphere.material.emissionColor.xyz * sphere.material.emissionColor.w,
+                power = emittedLight * PI * lightCount * 4 * PI * sphere.radius * sphere.radius / photonsTraced;
+        bool    bouncedSpecular = false;
+
+        for (int i = 0; i < settings.maxBounces; i++) {
+            HitInfo hitInfo = CalculateRayCollision(ray);
+            if (!hitInfo.didHit) break;
+            Material material = hitInfo.material;
+
+            if (bouncedSpecular)
+                StorePhoton(hitInfo.pos, power);
+
+            // Only specular bounces continue the caustic
+            bool isSpecular = material.specularColor.w >= randFloat(seed);
+            if (!isSpecular) break;
+            bouncedSpecular = true;
+
+            vec3    specularDir = reflect(ray.dir, hitInfo.normal),
+                    diffuseDir = normalize(hitInfo.normal + SampleSphere(vec2(randFloat(seed), randFloat(seed))));
+            ray = Ray(hitInfo.pos, normalize(mix(diffuseDir, specularDir, material.smoothness)));
+            power *= material.specularColor.xyz;
+
+            if (settings.roulette != ROULETTE_OFF && i >= settings.rouletteStart) {
+                float p = settings.roulette == ROULETTE_CONSTANT
+                    ? settings.rouletteSurvival
+                    : max(power.r, max(power.g, power.b)) * photonsTraced;
+                p = min(p, 1);
+                if (randFloat(seed) >= p) break;
+                power *= 1.0f / p;
+            }
+        }
+    }
+}
+
+/**
+ * Gathers the caustic photons around a point on a diffuse surface.
+ *
+ * @param pos The point.
+ * @return The light reflected from the point, divided by the surface's color.
+ */
+vec3 GatherPhotons(vec3 pos) {
+    float   radius = settings.causticRadius;
+    ivec3   center = PhotonCell(pos);
+    vec3    power = vec3(0);
+
+    for (int x = -1; x <= 1; x++)
+    for (int y = -1; y <= 1; y++)
+    for (int z = -1; z <= 1; z++) {
+        uint index = photonGrid[PhotonCellHash(center + ivec3(x, y, z))];
+        for (int n = 0; index != PHOTON_NONE && n < PHOTON_MAX_GATHER; n++) {
+            Photon photon = photons[index];
+            if (distance(photon.pos, pos) < radius)
+                power += photon.power;
+            index = photon.next;
+        }
+    }
+
+    // Lambertian BRDF (1/PI), spread over the area of the gather disc
+    return power / PI / (PI * radius * radius);
+}
+
+// Direct light found by the path being traced, which is kept apart for the AOVs, see ClampLight()
+vec3 pathDirectLight;
+
+/**
+ * Clamps the light a path carries to the camera, removing fireflies (rare, very bright samples) at the cost of some energy.
+ * Light which reflected off at most one surface before reaching the camera is direct light, the rest is indirect light.
+ * A limit of 0 disables clamping. The direct light is also added to pathDirectLight.
+ *
+ * @param light The light.
+ * @param bounces How many surfaces the light reflected off before reaching the camera.
+ *
+ * @return The clamped light.
+ */
+vec3 ClampLight(vec3 light, int bounces) {
+    float   limit = bounces <= 1 ? settings.directClamp : settings.indirectClamp,
+            brightest = max(light.r, max(light.g, light.b));
+    vec3    clamped = (limit > 0 && brightest > limit) ? light * (limit / brightest) : light;
+    if (bounces <= 1)
+        pathDirectLight += clamped;
+    return clamped;
+}
+
+/**
+ * Gets the ambient occlusion where a ray hits, by firing a cosine-weighted ray from the surface.
+ * Surfaces are white, and darkened by other surfaces within the AO distance, giving a clay look.
+ *
+ * @param ray The ray.
+ *
+ * @return The brightness of the surface, or white if nothing was hit.
+ */
+vec3 AmbientOcclusion(Ray ray) {
+    HitInfo hitInfo = CalculateRayCollision(ray);
+    if (!hitInfo.didHit) return vec3(1);
+
+    vec3    dir = normalize(hitInfo.normal + SampleSphere(Sample2D(BounceDimension(0, BOUNCE_BSDF))));
+    HitInfo occluder = CalculateRayCollision(Ray(hitInfo.pos + hitInfo.normal * kEpsilion, dir));
+    float   occlusion = occluder.didHit && occluder.dist < aoDistance
+        ? pow(1 - occluder.dist / aoDistance, aoFalloff)
+        : 0;
+    return vec3(1 - occlusion);
+}
+
+/**
+ * Gets the debug view of the first surface a ray hits.
+ *
+ * @param ray The ray.
+ *
+ * @return The view's color, or black if nothing was hit.
+ */
+vec3 DebugView(Ray ray) {
+    HitInfo hitInfo = CalculateRayCollision(ray);
+    if (!hitInfo.didHit) return vec3(0);
+
+    switch (debugView) {
+        case DEBUG_VIEW_NORMAL: return hitInfo.normal * 0.5 + 0.5;
+        case DEBUG_VIEW_ALBEDO: return hitInfo.material.color.rgb;
+        case DEBUG_VIEW_DEPTH:  return vec3(1 / (1 + hitInfo.dist));
+    }
+    return vec3(0);
+}
+
+/**
+ * Traces a ray's path as it bounces around the scene, collecting hit information along the way.
+ *
+ * @param ray The ray.
+ * @param seed The seed, which is changed after use.
+ *
+ * @return The end color of the ray.
+ */
+vec3 Trace(Ray ray, inout uint seed) {
+    if (settings.integrator == INTEGRATOR_AMBIENT_OCCLUSION)
+        return AmbientOcclusion(ray);
+    if (settings.integrator == INTEGRATOR_DEBUG)
+        return DebugView(ray);
+
+    vec3 	incomingLight = vec3(0),
+            rayColor = vec3(1);
+    bool	hitAny = false,
+            sampledLights = false; // If the lights were sampled directly at the previous bounce
+    float   lightWeight = 1;        // Weight of light found from lights, which is shared with light tracing once it could reach the path
+
+    // Whether caustics were gathered, and if the path has only bounced specularly since then
+    // Such paths are caustics, which would be counted twice if the lights they hit were counted too
+    bool    gatheredCaustics = false,
+            causticChain = false,
+            causticPath = false;
+
+    // Bounces which the path guide learns from, once the light the path finds after them is known
+    vec3    recordPos[GUIDING_MAX_RECORDS],
+            recordDir[GUIDING_MAX_RECORDS],
+            recordLight[GUIDING_MAX_RECORDS],
+            recordColor[GUIDING_MAX_RECORDS];
+    int     records = 0;
+
+    // Bounces of each lobe so far, which have their own limits
+    uint    diffuseBounces = 0,
+            glossyBounces = 0;
+
+    // Roughest bounce of the path so far, which smooth surfaces are roughened by with path regularization
+    float   pathRoughness = 0;
+    
+    for (int i = 0; i < settings.maxBounces; i++)
+    {
+        HitInfo hitInfo = CalculateRayCollision(ray);
+
+        // In fog or volumes, the ray may scatter before reaching the surface, at a distance picked proportionally to the transmittance
+        // (Picking it this way means the transmittance cancels out, leaving only the albedo)
+        float   mediumDist = hitInfo.didHit ? hitInfo.dist : settings.tMax,
+                mediumAnisotropy = 0;
+        vec3    mediumAlbedo = vec3(0);
+        bool    scattered = false;
+        if (settings.fog != 0) {
+            float dist = -log(1 - Sample2D(BounceDimension(i, BOUNCE_MEDIUM)).x) / settings.fogMedium.density;
+            if (dist < mediumDist) {
+                mediumDist = dist;
+                mediumAlbedo = settings.fogMedium.albedo;
+                mediumAnisotropy = settings.fogMedium.anisotropy;
+                scattered = true;
+            }
+        }
+        int     volume;
+        bool    scatteredInVolume = SampleVolumes(ray, mediumDist, mediumAlbedo, mediumAnisotropy, volume, seed),
+                scatteredInFog = scattered && !scatteredInVolume;
+        scattered = scattered || scatteredInVolume;
+
+        // The sun's light scattered by the fog along camera rays is added with god rays instead, if they're on
+        bool    godRays = i == 0 && settings.fog != 0 && settings.sun != 0 && settings.sunLight.godRaySteps > 0;
+        if (godRays)
+            incomingLight += ClampLight(GodRays(ray, hitInfo.didHit ? hitInfo.dist : settings.tMax, seed), 1);
+
+        if (scattered) {
+            hitAny = true;
+            vec3 pos = ray.origin + ray.dir * mediumDist;
+
+            // Glowing volumes emit light where they absorb it, which is the part of the albedo that doesn't scatter
+            if (scatteredInVolume)
+                incomingLight += ClampLight(VolumeEmission(volume, pos) * (1 - mediumAlbedo) * rayColor, i);
+
+            sampledLights = settings.nextEventEstimation != 0 || settings.integrator == INTEGRATOR_DIRECT_LIGHT;
+            if (sampledLights)
+                incomingLight += ClampLight(SampleLightsInMedium(pos, ray.dir, mediumAnisotropy, i, seed) * mediumAlbedo * rayColor * lightWeight, i + 1);
+            if (settings.sun != 0 && !(godRays && scatteredInFog)) {
+                vec3 sunLight = SunLight(pos, seed) * HenyeyGreenstein(dot(ray.dir, settings.sunLight.direction), mediumAnisotropy);
+                incomingLight += ClampLight(sunLight * mediumAlbedo * rayColor, i + 1);
+            }
+            if (settings.integrator == INTEGRATOR_DIRECT_LIGHT)
+                break;
+
+            ray = Ray(pos, SampleHenyeyGreenstein(ray.dir, mediumAnisotropy, Sample2D(BounceDimension(i, BOUNCE_BSDF))));
+            rayColor *= mediumAlbedo;
+            causticChain = false;
+            causticPath = false;
+            continue;
+        }
+
+        if (hitInfo.didHit)
+        {
+            hitAny = true;
+            Material material = hitInfo.material;
+            
+            // Calculate new pos and dir
+            ray.origin = hitInfo.pos;
+
+            vec2    choices     = Sample2D(BounceDimension(i, BOUNCE_CHOICES));
+            bool 	isSpecular  = material.specularColor.w >= choices.x;
+            vec3 	specularDir = reflect(ray.dir, hitInfo.normal),
+                    diffuseDir  = normalize(hitInfo.normal + SampleSphere(Sample2D(BounceDimension(i, BOUNCE_BSDF))));
+
+            // With path guiding, diffuse bounces are sometimes steered by the guide instead
+            // Either way, the bounce is weighted by the combined probability of both picking it, so the result stays unbiased
+            float guidingWeight = 1;
+            if (settings.pathGuiding != 0 && !isSpecular) {
+                uint    cell = GuidingCell(hitInfo.pos);
+                float   total = GuidingTotal(cell);
+                if (total > 0) {
+                    if (Sample2D(BounceDimension(i, BOUNCE_LIGHT_CHOICES)).y < GUIDING_FRACTION)
+                        diffuseDir = SampleGuide(cell, total, Sample2D(BounceDimension(i, BOUNCE_BSDF)), seed);
+
+                    float   surfacePdf = max(0, dot(diffuseDir, hitInfo.normal)) / PI,
+                            guidePdf = GuidingPdf(cell, total, diffuseDir);
+                    guidingWeight = surfacePdf / (GUIDING_FRACTION * guidePdf + (1 - GUIDING_FRACTION) * surfacePdf);
+                }
+            }
+            // Path regularization: After rough bounces, smooth surfaces are made rougher, which blurs the caustics they would give
+            float smoothness = min(material.smoothness, 1 - settings.regularization * pathRoughness);
+            pathRoughness = max(pathRoughness, isSpecular ? 1 - smoothness : 1);
+            ray.dir = normalize(mix(diffuseDir, specularDir, smoothness * int(isSpecular)));
+
+            // Update light and color
+            // (Light emitted by spheres was already counted if they were sampled directly at the previous bounce)
+            vec3 emittedLight = material.emissionColor.xyz * material.emissionColor.w;
+            if ((!sampledLights && !causticPath) || hitInfo.sphere < 0)
+                incomingLight += ClampLight(emittedLight * rayColor * (hitInfo.sphere < 0 ? 1 : lightWeight), i);
+
+            // Stop if the path has bounced off the chosen lobe as much as it may
+            if (isSpecular ? glossyBounces++ >= settings.maxGlossyBounces : diffuseBounces++ >= settings.maxDiffuseBounces)
+                break;
+
+            // Light tracing connects diffuse surfaces to the camera, so it finds the same light as paths which start with a diffuse bounce
+            if (i == 0 && !isSpecular && LightTracingActive())
+                lightWeight = 0.5;
+
+            // Gather caustics at the first diffuse surface
+            causticChain = causticChain && isSpecular;
+            causticPath = gatheredCaustics && causticChain;
+            if (settings.caustics != 0 && !gatheredCaustics && !isSpecular) {
+                incomingLight += ClampLight(GatherPhotons(hitInfo.pos) * material.color.xyz * rayColor * lightWeight, i + 2);
+                gatheredCaustics = true;
+                causticChain = true;
+            }
+
+            // Sample the lights directly from diffuse surfaces
+            // (With ReSTIR, the first surface uses the light sample from its reservoir)
+            bool useRestir = settings.restir != 0 && i == 0;
+            sampledLights = !isSpecular && (useRestir || settings.nextEventEstimation != 0 || settings.integrator == INTEGRATOR_DIRECT_LIGHT);
+            if (sampledLights) {
+                vec3 directLight = useRestir
+                    ? RestirShade(hitInfo.pos, hitInfo.normal)
+                    : SampleLights(hitInfo.pos, hitInfo.normal, hitInfo.sphere, i, seed);
+                incomingLight += ClampLight(directLight * material.color.xyz * rayColor * lightWeight, i + 1);
+            }
+
+            // The sun can't be hit by rays, so it always lights diffuse surfaces directly
+            float cosSun = dot(hitInfo.normal, settings.sunLight.direction);
+            if (settings.sun != 0 && !isSpecular && cosSun > 0) {
+                vec3 sunLight = SunLight(hitInfo.pos + hitInfo.normal * kEpsilion, seed) * cosSun / PI;
+                incomingLight += ClampLight(sunLight * material.color.xyz * rayColor, i + 1);
+            }
+
+            // The direct light integrator stops at the first surface
+            if (settings.integrator == INTEGRATOR_DIRECT_LIGHT)
+                break;
+
+            rayColor *= isSpecular ? material.specularColor.xyz : material.color.xyz * guidingWeight;
+
+            // Russian roulette: Randomly terminate paths, and weight up the survivors so the result stays the same on average
+            if (settings.roulette != ROULETTE_OFF && i >= settings.rouletteStart) {
+                float p = settings.roulette == ROULETTE_CONSTANT
+                    ? settings.rouletteSurvival
+                    : max(rayColor.r, max(rayColor.g, rayColor.b));
+                p = min(p, 1);
+                if (choices.y >= p) break;
+                rayColor *= 1.0f / p;
+            }
+
+            // Remember diffuse bounces for the path guide
+            if (settings.pathGuiding != 0 && !isSpecular && records < GUIDING_MAX_RECORDS) {
+                recordPos[records] = hitInfo.pos;
+                recordDir[records] = ray.dir;
+                recordLight[records] = incomingLight;
+                recordColor[records] = rayColor;
+                records++;
+            }
+        } else 
+        {
+            // If the ray did not hit anything, sample color from environment and return
+            incomingLight += ClampLight(GetEnvironmentLight(ray) * rayColor, i);
+            break;
+        }
+    }
+
+    // Teach the path guide how much light was found after each bounce
+    for (int i = 0; i < records; i++) {
+        vec3 light = (incomingLight - recordLight[i]) / max(recordColor[i], vec3(1e-4));
+        LearnGuide(recordPos[i], recordDir[i], Luminance(light));
+    }
+
+    // (Return)
+    return incomingLight;
+}
+
+// --- Camera functions ---
+// The view currently being rendered, which is a part of the screen in stereo modes
+vec2 viewSize;
+vec3 viewOffset;
+
+/**
+ * Applies radial lens distortion (Brown-Conrady) to a point on the screen.
+ *
+ * @param uv The point on the screen, [0, 1] in both axes.
+ * @param channelScale The magnification of the color channel, which differs per channel with chromatic aberration.
+ *
+ * @return The distorted point on the screen.
+ */
+vec2 DistortUV(vec2 uv, float channelScale) {
+    vec2    aspect = vec2(viewSize.x / viewSize.y, 1),
+            p = (uv - 0.5) * aspect;
+    float   r2 = dot(p, p),
+            scale = 1.0 + camera.distortion.x * r2 + camera.distortion.y * r2 * r2;
+    return p * scale * channelScale / aspect + 0.5;
+}
+
+/**
+ * Gets the camera's transformation at a point in time while the shutter is open.
+ * The position is interpolated linearly, while the rotation is interpolated by blending and re-orthogonalizing the camera's basis.
+ *
+ * @param time The time, where 0 is when the shutter opens and 1 is when it closes.
+ * @return The camera's local to world transformation.
+ */
+mat4 CameraTransform(float time) {
+    if (camera.motionBlur == 0)
+        return camera.localToWorld;
+
+    mat4    open = camera.localToWorld,
+            close = camera.localToWorldClose;
+    vec3    front = normalize(mix(open[2].xyz, close[2].xyz, time)),
+            right = normalize(cross(mix(open[1].xyz, close[1].xyz, time), front)),
+            up = cross(front, right);
+    return mat4(vec4(right, 0), vec4(up, 0), vec4(front, 0), mix(open[3], close[3], time));
+}
+
+/**
+ * Generates a camera ray through a point on the screen.
+ *
+ * @param uv The point on the screen, [0, 1] in both axes.
+ * @param seed The seed, which is changed after use.
+ * @param ray The generated ray.
+ *
+ * @return If the point on the screen maps to a ray at all (fisheye projections leave the corners blank).
+ */
+bool GenerateRay(vec2 uv, inout uint seed, out Ray ray) {
+    primaryRay = true;
+    mat4    localToWorld = CameraTransform(Sample2D(DIMENSION_TIME).x);
+    vec3    camUp = normalize(localToWorld[1].xyz),
+            camRight = normalize(localToWorld[0].xyz),
+            camFront = normalize(localToWorld[2].xyz);
+    vec2    jitter = SampleDisc(Sample2D(DIMENSION_PIXEL)) * settings.divergeStrength / viewSize.x;
+    float   aspect = viewSize.x / viewSize.y;
+    vec3    eyePos = localToWorld[3].xyz + camRight * viewOffset.x;
+
+    // Orthographic: Parallel rays fired from a plane centered on the camera
+    if (camera.projection == PROJECTION_ORTHOGRAPHIC) {
+        float   planeHeight = camera.orthoHeight,
+                planeWidth = planeHeight * aspect;
+        vec2    planePos = (uv - 0.5) * vec2(planeWidth, planeHeight) + jitter * planeHeight;
+
+        ray.origin = eyePos + camRight*planePos.x + camUp*planePos.y;
+        ray.dir = camFront;
+        return true;
+    }
+
+    // Fisheye (equidistant): The distance from the center of the screen is proportional to the angle from the front
+    // The fov covers the circle which fits the screen's height, outside of which (in the corners, and the sides of wide screens) is left blank
+    if (camera.projection == PROJECTION_FISHEYE) {
+        vec2    p = (uv + jitter * vec2(1, aspect) - 0.5) * 2.0 * vec2(aspect, 1);
+        float   r = length(p),
+                theta = r * camera.fisheyeFov * 0.5 * PI / 180.0;
+        if (theta > camera.fisheyeFov * 0.5 * PI / 180.0) return false;
+
+        vec2    dirPlane = r > 0 ? p / r * sin(theta) : vec2(0);
+        ray.origin = eyePos;
+        ray.dir = normalize(camRight*dirPlane.x + camUp*dirPlane.y + camFront*cos(theta));
+        return true;
+    }
+
+    // Equirectangular: x maps to longitude [-PI, PI], y maps to latitude [-PI/2, PI/2]
+    if (camera.projection == PROJECTION_EQUIRECTANGULAR) {
+        vec2    p = uv + jitter * vec2(1, aspect);
+        float   phi = (p.x - 0.5) * 2.0 * PI,
+                theta = (p.y - 0.5) * PI;
+
+        ray.origin = eyePos;
+        ray.dir = normalize(camRight*sin(phi)*cos(theta) + camUp*sin(theta) + camFront*cos(phi)*cos(theta));
+        return true;
+    }
+
+    // Perspective: Rays fired from the lens through the focus plane
+    // (The lens is a disc, which gives depth of field when its radius is above 0)
+    float   planeHeight = camera.focusDistance * tan(camera.fov * 0.5 * PI / 180.0) * 2.0,
+            planeWidth = planeHeight * aspect;
+    vec3    viewParams = vec3( planeWidth, planeHeight, camera.focusDistance );
+
+    vec3    focusPointLocal = vec3(uv - 0.5, 1) * viewParams,
+            focusPoint = (localToWorld * vec4(focusPointLocal + viewOffset, 1)).xyz,
+            focusPointJittered = focusPoint + camRight*jitter.x + camUp*jitter.y;
+    vec2    lensPoint = SampleDisc(Sample2D(DIMENSION_LENS)) * camera.apertureRadius;
+
+    ray.origin = eyePos + camRight*lensPoint.x + camUp*lensPoint.y;
+    ray.dir = normalize(focusPointJittered - ray.origin);
+    return true;
+}
+
+/**
+ * Rounds a value to one of the two half-precision floats around it, at random, with the closer one being the likelier.
+ * Unlike rounding to the nearest, this is right on average, so tiny steps (which would always round away) still add up.
+ *
+ * @param v The value.
+ * @param u A uniform random number, [0, 1).
+ *
+ * @return The value, rounded to half precision.
+ */
+float RoundHalfStochastic(float v, float u) {
+    uint    bits = packHalf2x16(vec2(v, 0)) & 0xFFFF;
+    float   nearest = unpackHalf2x16(bits).x;
+    if (nearest == v || isinf(nearest))
+        return nearest;
+
+    // (Stepping the bits rounds away from 0, and back steps towards it)
+    uint    other = (v > nearest) == (v >= 0) ? bits + 1 : bits - 1;
+    float   otherValue = unpackHalf2x16(other).x;
+    return u < (v - nearest) / (otherValue - nearest) ? otherValue : nearest;
+}
+
+/**
+ * Blends this frame's color into the accumulated image, weighting every frame equally.
+ * At half precision the steps of the blend get too small to round to anything after a few thousand frames,
+ * so the result is rounded stochastically instead, keeping the accumulation converging at the cost of a little noise.
+ *
+ * @param prevCol The accumulated image at the frag.
+ * @param fragCol The frag's color this frame.
+ *
+ * @return The new accumulated color.
+ */
+vec4 AccumulateColor(vec3 prevCol, vec3 fragCol) {
+    vec3 blended = mix(prevCol, fragCol, 1.0 / (frameCount + 1));
+    if (halfAccumulation != 0) {
+        float u = float(hash(uint(fragCoord.x) ^ hash(uint(fragCoord.y) ^ hash(frameCount)))) / 4294967296.0; // 2^32
+        blended = vec3(RoundHalfStochastic(blended.r, u), RoundHalfStochastic(blended.g, u), RoundHalfStochastic(blended.b, u));
+    }
+    return vec4(blended, 1);
+}
+
+/**
+ * Blends this frame's AOVs into the accumulated ones, weighting every frame equally like the image.
+ * The depth and the object ID can't be averaged, so they are kept from the first frame instead.
+ *
+ * @param hitInfo What the frag's first ray this frame hit.
+ * @param direct The direct light this frame, see ClampLight().
+ * @param indirect The rest of the light this frame.
+ * @param blend How much of this frame is blended in, where 0 keeps the accumulated AOVs as they are.
+ */
+void AccumulateAovs(HitInfo hitInfo, vec3 direct, vec3 indirect, float blend) {
+    ivec2   texel = ivec2(fragCoord.xy);
+    vec3    camFront = normalize(camera.localToWorld[2].xyz);
+    float   depth = hitInfo.didHit ? dot(hitInfo.pos - camera.localToWorld[3].xyz, camFront) : -1,
+            objectId = hitInfo.sphere >= 0 ? hitInfo.sphere : (hitInfo.mesh >= 0 ? spheresCount + hitInfo.mesh : -1);
+    vec3    albedo = hitInfo.didHit ? hitInfo.material.color.rgb : vec3(0),
+            normal = hitInfo.didHit ? hitInfo.normal : vec3(0);
+
+    aovAlbedo = vec4(mix(texelFetch(aovAccumulation[0], texel, 0).rgb, albedo, blend), 1);
+    aovNormal = vec4(mix(texelFetch(aovAccumulation[1], texel, 0).rgb, normal, blend), 1);
+    aovDepthId = blend > 0 && frameCount == 0 ? vec4(depth, objectId, 0, 1) : texelFetch(aovAccumulation[2], texel, 0);
+    aovDirect = vec4(mix(texelFetch(aovAccumulation[3], texel, 0).rgb, direct, blend), 1);
+    aovIndirect = vec4(mix(texelFetch(aovAccumulation[4], texel, 0).rgb, indirect, blend), 1);
+}
+
+/**
+ * Sets up the frag at fragCoord: which view it belongs to, where within it, and its random numbers.
+ *
+ * @param uv Where the frag is within its view, [0, 1] in both axes.
+ * @param cropped If the frag is outside of the crop region, so it fires no rays from the camera.
+ *
+ * @return The frag's seed for this frame.
+ */
+uint SetUpFrag(out vec2 uv, out bool cropped)
+{
+    // Find which view the fragment belongs to, and where within it
+    // In stereo modes, the left eye is on the left/top and the eyes are offset by half the IPD each
+    // (When the screen is a window of the image, the frag fires the rays of the image's pixel under it)
+    vec2 imagePixel = fragCoord.xy + camera.windowOffset;
+    uv = (imagePixel + camera.pixelJitter) / camera.imageSize;
+    viewSize = camera.imageSize;
+    viewOffset = vec3(0);
+
+    // Pixels outside of the crop region fire no rays from the camera
+    vec2 imageUV = imagePixel / camera.imageSize;
+    cropped = settings.crop != 0 && (any(lessThan(imageUV, settings.cropRegion.min)) || any(greaterThanEqual(imageUV, settings.cropRegion.max)));
+
+    if (camera.stereo == STEREO_SIDE_BY_SIDE) {
+        bool leftEye = uv.x < 0.5;
+        viewSize.x *= 0.5;
+        uv.x = fract(uv.x * 2);
+        viewOffset.x = camera.ipd * (leftEye ? -0.5 : 0.5);
+    } else if (camera.stereo == STEREO_OVER_UNDER) {
+        bool leftEye = uv.y >= 0.5;
+        viewSize.y *= 0.5;
+        uv.y = fract(uv.y * 2);
+        viewOffset.x = camera.ipd * (leftEye ? -0.5 : 0.5);
+    }
+
+    // Create seed for RNG
+    // Every random number depends only on the pixel, the frame and the seed in the settings, so renders can be reproduced exactly
+    // (Which is the image's pixel, so an image rendered in windows is the same as one rendered whole)
+    uint i = uint( imagePixel.y * camera.imageSize.x + imagePixel.x );
+    uint seed = i + frameCount * 719393 + hash(settings.seed);
+    fragHash = hash(i ^ hash(settings.seed));
+    pixelIndex = uint(fragCoord.y) * uint(camera.screenSize.x) + uint(fragCoord.x);
+    return seed;
+}
+
+// Renders the frag at fragCoord, for whichever pass is being rendered
+void RenderFrag()
+{
+    vec2 uv;
+    bool cropped;
+    uint seed = SetUpFrag(uv, cropped);
+
+    // The photon pass only stores photons
+    if (photonPass != 0) {
+        seed += 4 * 2654435761u;
+        TracePhotons(seed);
+        return;
+    }
+
+    // The light tracing pass only splats light onto the pixels
+    if (lightTracingPass != 0) {
+        seed += 3 * 2654435761u;
+        if (LightTracingActive())
+            TraceLightPaths(seed);
+        return;
+    }
+
+    // The guide pass only stores the first surface, for the denoiser
+    if (guidePass != 0) {
+        sampleIndex = frameCount;
+        rayIndex = 0;
+
+        Ray ray;
+        HitInfo hitInfo;
+        hitInfo.didHit = false;
+        if (GenerateRay(uv, seed, ray))
+            hitInfo = CalculateRayCollision(ray);
+
+        color = hitInfo.didHit ? vec4(hitInfo.material.color.rgb, 1) : vec4(1);
+        guideNormalDepth = hitInfo.didHit ? vec4(hitInfo.normal, hitInfo.dist) : vec4(0, 0, 0, -1);
+
+        // Where the surface was on the screen last frame, if the camera was in front of it
+        vec4 prevScreen = prevWorldToScreen * vec4(hitInfo.pos, 1);
+        bool motionKnown = hitInfo.didHit && motionVectors != 0 && prevScreen.w > 0;
+        guideMotion = motionKnown ? vec4(prevScreen.xy / prevScreen.w, 1, 1) : vec4(0);
+        return;
+    }
+
+    // ReSTIR passes only prepare the reservoirs
+    if (restirPass != RESTIR_PASS_SHADE) {
+        seed += restirPass * 2654435761u;
+        sampleIndex = frameCount;
+        rayIndex = 0;
+
+        Ray ray;
+        // (Cropped pixels leave empty reservoirs for their neighbours inside the region)
+        if (cropped) {
+            if (restirPass == RESTIR_PASS_INITIAL)
+                reservoirs[pixelIndex] = EmptyReservoir(vec3(0), vec3(0));
+        } else if (restirPass == RESTIR_PASS_SPATIAL)
+            RestirSpatial(seed);
+        else if (GenerateRay(uv, seed, ray))
+            RestirInitial(ray, seed);
+        else
+            reservoirs[pixelIndex] = EmptyReservoir(vec3(0), vec3(0));
+        return;
+    }
+
+    // (Cropped pixels keep what they had, so the rest of the image is still there to look at)
+    vec3 prevCol = texelFetch(accumulation, ivec2(fragCoord.xy), 0).rgb;
+    if (cropped) {
+        color = vec4(prevCol, 1);
+        if (aovs != 0)
+            AccumulateAovs(HitInfo0, vec3(0), vec3(0), 0);
+        return;
+    }
+
+    // Fire rays
+    // (With AOVs, the first ray also finds the first surface, and the direct light is summed apart)
+    Ray ray;
+    vec3 totalIncomingLight = vec3(0),
+         totalDirectLight = vec3(0);
+    HitInfo primaryHit = HitInfo0;
+
+    bool chromatic = camera.chromaticAberration != 0;
+    for ( int i = 0; i < settings.raysPerFrag; i++ )
+    {
+        sampleIndex = frameCount * settings.raysPerFrag + i;
+        rayIndex = i;
+
+        // With chromatic aberration, each ray carries a single random color channel (weighted to keep the average intact)
+        vec3 channelWeight = vec3(1);
+        float channelScale = 1;
+        if (chromatic) {
+            uint channel = min(uint(Sample2D(DIMENSION_TIME).y * 3), 2u);
+            channelWeight = vec3(0);
+            channelWeight[channel] = 3;
+            channelScale = 1 + camera.chromaticAberration * (1 - float(channel));
+        }
+
+        pathDirectLight = vec3(0);
+        if (GenerateRay(DistortUV(uv, channelScale), seed, ray)) {
+            if (aovs != 0 && i == 0)
+                primaryHit = CalculateRayCollision(ray);
+            totalIncomingLight += Trace(ray, seed) * channelWeight;
+            totalDirectLight += pathDirectLight * channelWeight;
+        }
+    }
+
+    // Blend the average of the frag's rays into the accumulated image, weighting every frame equally
+    vec3    fragCol = totalIncomingLight / settings.raysPerFrag;
+    if (LightTracingActive())
+        fragCol += GetSplattedLight();
+
+    color = AccumulateColor(prevCol, fragCol);
+
+    // (Light from light tracing can't be told apart by bounces, so it is all indirect)
+    if (aovs != 0) {
+        vec3 directCol = totalDirectLight / settings.raysPerFrag;
+        AccumulateAovs(primaryHit, directCol, fragCol - directCol, 1.0 / (frameCount + 1));
+    }
+}
+
+#ifdef COMPUTE
+// --- Wavefront functions ---
+// The wavefront path splits the paths into stages which are dispatched one after the other, where each bounce intersects the queued rays,
+// shades their hits (queueing the next bounce's rays and the shadow rays), then traces the shadow rays, see wavefront.rs
+// This only covers surfaces lit by the emissive spheres and the sun, see Wavefront::supports()
+
+/**
+ * Sets up the frag of a pixel for a stage of the wavefront path, like RenderFrag() does for its frag.
+ *
+ * @param pixel The index of the pixel.
+ * @param uv Where the frag is within its view, [0, 1] in both axes.
+ * @param cropped If the frag is outside of the crop region.
+ *
+ * @return The frag's seed for this frame, before any of its rays were traced.
+ */
+uint WavefrontSetUpFrag(uint pixel, out vec2 uv, out bool cropped) {
+    uint width = uint(camera.screenSize.x);
+    fragCoord = vec4(vec2(pixel % width, pixel / width) + 0.5, 0, 1);
+    uint seed = SetUpFrag(uv, cropped);
+    sampleIndex = frameCount * settings.raysPerFrag + wavefrontRay;
+    rayIndex = wavefrontRay;
+    return seed;
+}
+
+/**
+ * Adds the pending light of a path's shadow rays to its radiance, once the shadow stage has checked them.
+ *
+ * @param path The path.
+ */
+void WavefrontResolve(inout WavefrontPath path) {
+    path.radiance += path.pendingLight + path.pendingSun;
+    path.pendingLight = vec3(0);
+    path.pendingSun = vec3(0);
+}
+
+/**
+ * Queues a ray for the next bounce.
+ *
+ * @param pixel The pixel whose path the ray belongs to.
+ * @param ray The ray.
+ */
+void WavefrontQueueRay(uint pixel, Ray ray) {
+    uint    queue = 1 - wavefrontQueue,
+            index = atomicAdd(rayCounts[queue], 1);
+    rays[queue * (rays.length() / 2) + index] = WavefrontRay(ray.origin, pixel, ray.dir);
+}
+
+/**
+ * Generation stage: Starts the path of a pixel, queueing its camera ray.
+ *
+ * @param pixel The index of the pixel.
+ */
+void WavefrontGenerate(uint pixel) {
+    vec2    uv;
+    bool    cropped;
+    uint    seed = WavefrontSetUpFrag(pixel, uv, cropped);
+
+    // (Each of the frag's rays continues the seed of the one before, and their light is summed)
+    WavefrontPath path = wavefrontRay == 0
+        ? WavefrontPath(vec3(1), seed, vec3(0), 0, vec3(0), 0, vec3(0), 0, 0, 0)
+        : paths[pixel];
+    WavefrontResolve(path);
+    path.throughput = vec3(1);
+    path.bounce = 0;
+    path.diffuseBounces = 0;
+    path.glossyBounces = 0;
+    path.pathRoughness = 0;
+    path.sampledLights = 0;
+
+    Ray ray;
+    if (!cropped && GenerateRay(DistortUV(uv, 1), path.seed, ray))
+        WavefrontQueueRay(pixel, ray);
+    paths[pixel] = path;
+}
+
+/**
+ * Intersection stage: Finds what a queued ray hits.
+ *
+ * @param index The index of the ray in the queue.
+ */
+void WavefrontIntersect(uint index) {
+    if (index >= rayCounts[wavefrontQueue]) return;
+
+    WavefrontRay queued = rays[wavefrontQueue * (rays.length() / 2) + index];
+    primaryRay = paths[queued.pixel].bounce == 0;
+    hits[index] = CalculateRayCollision(Ray(queued.origin, queued.dir));
+}
+
+/**
+ * Shading stage: Adds the light a queued ray found, samples the lights with shadow rays, and queues the ray of the next bounce.
+ * This is a bounce of Trace(), without what the wavefront path doesn't cover.
+ *
+ * @param index The index of the ray in the queue.
+ */
+void WavefrontShade(uint index) {
+    if (index >= rayCounts[wavefrontQueue]) return;
+
+    WavefrontRay queued = rays[wavefrontQueue * (rays.length() / 2) + index];
+    HitInfo hitInfo = hits[index];
+    vec2    uv;
+    bool    cropped;
+    WavefrontSetUpFrag(queued.pixel, uv, cropped);
+    WavefrontPath path = paths[queued.pixel];
+    WavefrontResolve(path);
+
+    Ray ray = Ray(queued.origin, queued.dir);
+    int i = int(path.bounce);
+    if (!hitInfo.didHit) {
+        path.radiance += ClampLight(GetEnvironmentLight(ray) * path.throughput, i);
+        paths[queued.pixel] = path;
+        return;
+    }
+
+    Material material = hitInfo.material;
+    ray.origin = hitInfo.pos;
+
+    vec2    choices     = Sample2D(BounceDimension(i, BOUNCE_CHOICES));
+    bool    isSpecular  = material.specularColor.w >= choices.x;
+    vec3    specularDir = reflect(ray.dir, hitInfo.normal),
+            diffuseDir  = normalize(hitInfo.normal + SampleSphere(Sample2D(BounceDimension(i, BOUNCE_BSDF))));
+    float   smoothness = min(material.smoothness, 1 - settings.regularization * path.pathRoughness);
+    path.pathRoughness = max(path.pathRoughness, isSpecular ? 1 - smoothness : 1);
+    ray.dir = normalize(mix(diffuseDir, specularDir, smoothness * int(isSpecular)));
+
+    // (Light emitted by spheres was already counted if they were sampled directly at the previous bounce)
+    vec3 emittedLight = material.emissionColor.xyz * material.emissionColor.w;
+    if (path.sampledLights == 0 || hitInfo.sphere < 0)
+        path.radiance += ClampLight(emittedLight * path.throughput, i);
+
+    bool stop = isSpecular ? path.glossyBounces++ >= settings.maxGlossyBounces : path.diffuseBounces++ >= settings.maxDiffuseBounces;
+    path.sampledLights = uint(!stop && !isSpecular && (settings.nextEventEstimation != 0 || settings.integrator == INTEGRATOR_DIRECT_LIGHT));
+    if (stop) {
+        paths[queued.pixel] = path;
+        return;
+    }
+
+    // Sample the lights directly from diffuse surfaces, where the light is pending until the shadow ray is traced
+    // (The light is clamped before knowing if it's blocked, which is the same as clamping it after)
+    vec3 shadowOrigin = hitInfo.pos + hitInfo.normal * kEpsilion;
+    if (path.sampledLights != 0) {
+        int     light;
+        vec3    dir,
+                lightRadiance = SampleLightDirection(hitInfo.pos, hitInfo.sphere, i, light, dir);
+        float   cosSurface = dot(dir, hitInfo.normal);
+        if (light >= 0 && cosSurface > 0) {
+            path.pendingLight = ClampLight(lightRadiance * cosSurface / PI * material.color.xyz * path.throughput, i + 1);
+            shadowRays[atomicAdd(shadowRayCount, 1)] = WavefrontShadowRay(shadowOrigin, queued.pixel, dir, light);
+        }
+    }
+    float cosSun = dot(hitInfo.normal, settings.sunLight.direction);
+    if (settings.sun != 0 && !isSpecular && cosSun > 0) {
+        path.pendingSun = ClampLight(settings.sunLight.color * cosSun / PI * material.color.xyz * path.throughput, i + 1);
+        shadowRays[atomicAdd(shadowRayCount, 1)] = WavefrontShadowRay(shadowOrigin, queued.pixel, settings.sunLight.direction, -1);
+    }
+
+    // The direct light integrator stops at the first surface
+    path.throughput *= isSpecular ? material.specularColor.xyz : material.color.xyz;
+    bool survived = settings.integrator != INTEGRATOR_DIRECT_LIGHT && i + 1 < settings.maxBounces;
+
+    // Russian roulette: Randomly terminate paths, and weight up the survivors so the result stays the same on average
+    if (survived && settings.roulette != ROULETTE_OFF && i >= settings.rouletteStart) {
+        float p = settings.roulette == ROULETTE_CONSTANT
+            ? settings.rouletteSurvival
+            : max(path.throughput.r, max(path.throughput.g, path.throughput.b));
+        p = min(p, 1);
+        survived = choices.y < p;
+        path.throughput *= 1.0f / p;
+    }
+
+    if (survived) {
+        path.bounce++;
+        WavefrontQueueRay(queued.pixel, ray);
+    }
+    paths[queued.pixel] = path;
+}
+
+/**
+ * Shadow stage: Drops the pending light of a shadow ray's path if the ray is blocked.
+ *
+ * @param index The index of the shadow ray.
+ */
+void WavefrontShadow(uint index) {
+    if (index >= shadowRayCount) return;
+
+    WavefrontShadowRay queued = shadowRays[index];
+    HitInfo hit = CalculateRayCollision(Ray(queued.origin, queued.dir));
+    if (queued.light < 0 && hit.didHit)
+        paths[queued.pixel].pendingSun = vec3(0);
+    else if (queued.light >= 0 && (!hit.didHit || hit.sphere != queued.light))
+        paths[queued.pixel].pendingLight = vec3(0);
+}
+
+/**
+ * Accumulation stage: Blends the average of a pixel's rays into the accumulated image, like RenderFrag() does.
+ *
+ * @param pixel The index of the pixel.
+ */
+void WavefrontAccumulate(uint pixel) {
+    vec2    uv;
+    bool    cropped;
+    WavefrontSetUpFrag(pixel, uv, cropped);
+
+    vec3 prevCol = texelFetch(accumulation, ivec2(fragCoord.xy), 0).rgb;
+    if (cropped) {
+        color = vec4(prevCol, 1);
+        return;
+    }
+
+    WavefrontPath path = paths[pixel];
+    WavefrontResolve(path);
+    vec3 fragCol = path.radiance / settings.raysPerFrag;
+    color = AccumulateColor(prevCol, fragCol);
+}
+
+// The main function of the compute path
+void main()
+{
+    ivec2 texel = ivec2(gl_GlobalInvocationID.xy);
+    if (any(greaterThanEqual(texel, imageSize(colorImage))))
+        return;
+
+    // (The stages of the wavefront path are dispatched over the pixels too, where those which work through queues number the invocations like the pixels)
+    if (wavefrontStage != WAVEFRONT_OFF) {
+        uint index = uint(texel.y) * uint(camera.screenSize.x) + uint(texel.x);
+        switch (wavefrontStage) {
+            case WAVEFRONT_GENERATE:    WavefrontGenerate(index); return;
+            case WAVEFRONT_INTERSECT:   WavefrontIntersect(index); return;
+            case WAVEFRONT_SHADE:       WavefrontShade(index); return;
+            case WAVEFRONT_SHADOW:      WavefrontShadow(index); return;
+        }
+        WavefrontAccumulate(index);
+        imageStore(colorImage, texel, color);
+        return;
+    }
+
+    fragCoord = vec4(vec2(texel) + 0.5, 0, 1);
+    RenderFrag();
+
+    // (Only the passes which the fragment path lets write to the framebuffer store anything)
+    if (photonPass != 0 || lightTracingPass != 0 || (guidePass == 0 && restirPass != RESTIR_PASS_SHADE))
+        return;
+    imageStore(colorImage, texel, color);
+    if (guidePass != 0) {
+        imageStore(guideNormalDepthImage, texel, guideNormalDepth);
+        imageStore(guideMotionImage, texel, guideMotion);
+    } else if (aovs != 0) {
+        imageStore(aovImages[0], texel, aovAlbedo);
+        imageStore(aovImages[1], texel, aovNormal);
+        imageStore(aovImages[2], texel, aovDepthId);
+        imageStore(aovImages[3], texel, aovDirect);
+        imageStore(aovImages[4], texel, aovIndirect);
+    }
+}
+#else
+// The main function
+void main()
+{
+    fragCoord = gl_FragCoord;
+    RenderFrag();
+}
+#endif
@@ -0,0 +1,49 @@
+#version 440 core
+
+// --- Structs ---
+// TaaSettings
+struct TaaSettings {
+    float alpha;
+};
+
+// --- Inputs / outputs ---
+out vec4 color;
+
+uniform sampler2D image;        // The current image
+uniform sampler2D history;      // The resolved image of last frame
+uniform sampler2D guideMotion;  // Where each pixel's surface was on the screen last frame, and if that is known
+uniform TaaSettings taa;
+uniform uint historyValid;      // If the history holds anything
+uniform uint frameCount;        // How many frames have been accumulated into the current image
+
+// The main function
+void main() {
+    ivec2   size = textureSize(image, 0);
+    ivec2   pixel = ivec2(gl_FragCoord.xy);
+    vec3    current = texelFetch(image, pixel, 0).rgb;
+
+    // The colors around the pixel, which the history is clamped to so that it doesn't leave trails behind moving edges
+    vec3    neighbourMin = current,
+            neighbourMax = current;
+    for (int y = -1; y <= 1; y++)
+    for (int x = -1; x <= 1; x++) {
+        vec3 tap = texelFetch(image, clamp(pixel + ivec2(x, y), ivec2(0), size - 1), 0).rgb;
+        neighbourMin = min(neighbourMin, tap);
+        neighbourMax = max(neighbourMax, tap);
+    }
+
+    // Pixels without motion vectors (nothing was hit) are assumed to have stayed in place
+    vec4    motion = texelFetch(guideMotion, pixel, 0);
+    vec2    prevUV = motion.z != 0 ? motion.xy : (vec2(pixel) + 0.5) / vec2(size);
+    bool    onScreen = all(greaterThanEqual(prevUV, vec2(0))) && all(lessThanEqual(prevUV, vec2(1)));
+    if (historyValid == 0 || !onScreen) {
+        color = vec4(current, 1);
+        return;
+    }
+
+    // Blend the current image into the history
+    // (The more frames have been accumulated into the image, the more it is trusted, until it replaces the history)
+    vec3    prev = clamp(texture(history, prevUV).rgb, neighbourMin, neighbourMax);
+    float   alpha = clamp(float(frameCount) * taa.alpha, taa.alpha, 1);
+    color = vec4(mix(prev, current, alpha), 1);
+}
@@ -0,0 +1,102 @@
+#version 440 core
+
+// --- Structs ---
+// TemporalSettings
+struct TemporalSettings {
+    float colorAlpha;
+    float momentsAlpha;
+};
+
+// --- Inputs / outputs ---
+layout (location = 0) out vec4 color;   // The light blended into the history, with its variance in the alpha channel
+layout (location = 1) out vec4 moments; // The first and second moments of the light's luminance, and how many frames they cover
+
+uniform sampler2D image;            // The current image
+uniform sampler2D guideAlbedo;      // Albedo of the first surface, or white if nothing was hit
+uniform sampler2D guideNormalDepth; // Normal and distance to the first surface, or a negative distance if nothing was hit
+uniform sampler2D guideMotion;      // Where the first surface was on the screen last frame, and if that is known
+uniform sampler2D prevNormalDepth;  // The normal and depth guide of last frame
+uniform sampler2D historyLight;     // The light of last frame, with its variance in the alpha channel
+uniform sampler2D historyMoments;   // The moments of last frame
+uniform TemporalSettings temporal;
+uniform uint historyValid;          // If the history holds anything
+
+// Frames after which the variance is estimated from the moments, rather than from the pixel's neighbours
+const float MIN_HISTORY_FOR_MOMENTS = 4;
+// Limit for how many frames the history may cover, to keep blending in new frames
+const float MAX_HISTORY = 255;
+
+/**
+ * Gets the luminance of a linear color.
+ */
+float Luminance(vec3 col) {
+    return dot(col, vec3(0.2126, 0.7152, 0.0722));
+}
+
+/**
+ * Gets the light arriving at a pixel's surface, by dividing the albedo out of its color.
+ */
+vec3 LoadLight(ivec2 pixel) {
+    return texelFetch(image, pixel, 0).rgb / max(texelFetch(guideAlbedo, pixel, 0).rgb, vec3(1e-3));
+}
+
+// The main function
+void main() {
+    ivec2   size = textureSize(image, 0);
+    ivec2   pixel = ivec2(gl_FragCoord.xy);
+    vec3    light = LoadLight(pixel);
+    vec4    normalDepth = texelFetch(guideNormalDepth, pixel, 0);
+    float   luminance = Luminance(light);
+
+    // The neighbourhood of the pixel, which the history is clamped to, and which the variance is estimated from at first
+    vec3    neighbourMin = light,
+            neighbourMax = light;
+    vec2    neighbourMoments = vec2(0);
+    float   neighbourCount = 0;
+    for (int y = -1; y <= 1; y++)
+    for (int x = -1; x <= 1; x++) {
+        ivec2 tap = clamp(pixel + ivec2(x, y), ivec2(0), size - 1);
+        if ((texelFetch(guideNormalDepth, tap, 0).w >= 0) != (normalDepth.w >= 0)) continue;
+
+        vec3    tapLight = LoadLight(tap);
+        float   tapLuminance = Luminance(tapLight);
+        neighbourMin = min(neighbourMin, tapLight);
+        neighbourMax = max(neighbourMax, tapLight);
+        neighbourMoments += vec2(tapLuminance, tapLuminance * tapLuminance);
+        neighbourCount++;
+    }
+    neighbourMoments /= neighbourCount;
+
+    // Find the surface in the history, which must be at about the same depth and facing the same way
+    vec4    motion = texelFetch(guideMotion, pixel, 0);
+    ivec2   prevPixel = ivec2(floor(motion.xy * vec2(size)));
+    bool    reprojected = historyValid != 0 && motion.z != 0 && normalDepth.w >= 0
+                       && all(greaterThanEqual(prevPixel, ivec2(0))) && all(lessThan(prevPixel, size));
+    if (reprojected) {
+        vec4 prev = texelFetch(prevNormalDepth, prevPixel, 0);
+        reprojected = prev.w >= 0
+                   && abs(prev.w - normalDepth.w) < 0.1 * normalDepth.w
+                   && dot(prev.xyz, normalDepth.xyz) > 0.9;
+    }
+
+    // Blend the current frame into the history, weighting every frame equally until the alphas are reached
+    vec3    blendedLight = light;
+    vec2    blendedMoments = vec2(luminance, luminance * luminance);
+    float   historyLength = 1;
+    if (reprojected) {
+        vec4 prevMoments = texelFetch(historyMoments, prevPixel, 0);
+        historyLength = min(prevMoments.z + 1, MAX_HISTORY);
+
+        // (The history is clamped to what's around the pixel now, so light that has changed doesn't linger)
+        vec3 prevLight = clamp(texelFetch(historyLight, prevPixel, 0).rgb, neighbourMin, neighbourMax);
+        blendedLight = mix(prevLight, light, max(1 / historyLength, temporal.colorAlpha));
+        blendedMoments = mix(prevMoments.xy, blendedMoments, max(1 / historyLength, temporal.momentsAlpha));
+    }
+
+    // A short history doesn't say much about the variance, so the neighbourhood is used instead
+    vec2    varianceMoments = historyLength < MIN_HISTORY_FOR_MOMENTS ? neighbourMoments : blendedMoments;
+    float   variance = max(varianceMoments.y - varianceMoments.x * varianceMoments.x, 0);
+
+    color = vec4(blendedLight, variance);
+    moments = vec4(blendedMoments, historyLength, 1);
+}
@@ -0,0 +1,117 @@
+#version 440 core
+
+// --- Structs ---
+// Vignette
+struct Vignette {
+    float strength;
+    float radius;
+    float softness;
+};
+
+// --- Inputs / outputs ---
+out vec4 color;
+
+uniform sampler2D image;        // The HDR image, same size as the screen
+uniform uint whiteBalance;      // If the image is white balanced
+uniform mat3 whiteBalanceMatrix;// Adapts the white of the white balance's temperature to that of sRGB, see WhiteBalance in postprocess.rs
+uniform float exposure;         // Exposure compensation plus the physical camera's exposure, in stops
+uniform float autoExposure;     // The exposure found by auto-exposure, in stops, or 0 if it is off
+uniform uint tonemapper;        // The id of the tonemapper
+uniform uint vignette;          // If the image is vignetted
+uniform Vignette vignetteEffect;
+uniform uint srgbEncode;        // If the image is encoded to sRGB, which is only turned off to compare
+
+// --- Constants ---
+// Tonemapper ids, see Tonemapper in postprocess.rs
+const uint TONEMAPPER_CLAMP = 0;
+const uint TONEMAPPER_REINHARD = 1;
+const uint TONEMAPPER_FILMIC = 2;
+const uint TONEMAPPER_ACES = 3;
+
+// --- Tonemapping functions ---
+/**
+ * Gets the luminance of a linear color.
+ */
+float Luminance(vec3 col) {
+    return dot(col, vec3(0.2126, 0.7152, 0.0722));
+}
+
+/**
+ * The curve of John Hable's Uncharted 2 tonemapper, before it is scaled to white.
+ */
+vec3 Uncharted2Curve(vec3 x) {
+    const float A = 0.15; // Shoulder strength
+    const float B = 0.50; // Linear strength
+    const float C = 0.10; // Linear angle
+    const float D = 0.20; // Toe strength
+    const float E = 0.02; // Toe numerator
+    const float F = 0.30; // Toe denominator
+    return ((x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F)) - E / F;
+}
+
+/**
+ * Maps a linear HDR color into [0, 1] with one of the tonemappers.
+ *
+ * @param col The color.
+ * @param tonemapper The id of the tonemapper.
+ *
+ * @return The mapped color, which is still linear.
+ */
+vec3 Tonemap(vec3 col, uint tonemapper) {
+    col = max(col, vec3(0));
+    switch (tonemapper) {
+        case TONEMAPPER_REINHARD:
+            // (Applied to the luminance, so the hue and saturation stay the same)
+            return clamp(col / (1 + Luminance(col)), 0, 1);
+        case TONEMAPPER_FILMIC: {
+            const float exposureBias = 2.0;
+            const float white = 11.2;
+            return clamp(Uncharted2Curve(col * exposureBias) / Uncharted2Curve(vec3(white)), 0, 1);
+        }
+        case TONEMAPPER_ACES:
+            return clamp((col * (2.51 * col + 0.03)) / (col * (2.43 * col + 0.59) + 0.14), 0, 1);
+        default:
+            return clamp(col, 0, 1);
+    }
+}
+
+/**
+ * Encodes a linear color with the sRGB transfer function, which is what the screen expects.
+ * The default framebuffer isn't sRGB, so this is the only place the image is encoded.
+ *
+ * @param col The linear color, [0, 1].
+ * @return The encoded color.
+ */
+vec3 LinearToSrgb(vec3 col) {
+    return mix(col * 12.92, 1.055 * pow(col, vec3(1 / 2.4)) - 0.055, greaterThan(col, vec3(0.0031308)));
+}
+
+/**
+ * Gets how much a vignette darkens a pixel.
+ *
+ * @param vignette The vignette.
+ * @param pixel The pixel's coordinates.
+ * @param size The size of the image, in pixels.
+ *
+ * @return The factor to multiply the pixel's color with.
+ */
+float VignetteFactor(Vignette vignette, vec2 pixel, vec2 size) {
+    float dist = length(pixel / size * 2 - 1) / sqrt(2);
+    return 1 - vignette.strength * smoothstep(vignette.radius, vignette.radius + max(vignette.softness, 1e-4), dist);
+}
+
+// The main function
+void main() {
+    vec3 col = texelFetch(image, ivec2(gl_FragCoord.xy), 0).rgb;
+    if (whiteBalance != 0)
+        col = max(whiteBalanceMatrix * col, vec3(0));
+    col *= exp2(exposure + autoExposure);
+    if (vignette != 0)
+        col *= VignetteFactor(vignetteEffect, gl_FragCoord.xy, vec2(textureSize(image, 0)));
+
+    // (Everything up to here is linear, everything after this pass works on what is shown on the screen)
+    col = Tonemap(col, tonemapper);
+    if (srgbEncode != 0)
+        col = LinearToSrgb(col);
+    color = vec4( col, 1 );
+}
@@ -0,0 +1,54 @@
+#version 440 core
+
+// --- Structs ---
+// Preview
+struct Preview {
+    uint temporal;
+    float alpha;
+};
+
+// --- Inputs / outputs ---
+out vec4 color;
+
+uniform sampler2D image;        // The image rendered at the preview's resolution
+uniform sampler2D history;      // The upscaled image of last frame, at the screen's size
+uniform sampler2D guideMotion;  // Where each rendered pixel's surface was on the screen last frame, and if that is known
+uniform Preview preview;
+uniform uint historyValid;      // If the history holds anything
+uniform ivec2 screenSize;
+
+// The main function
+void main() {
+    vec2    uv = gl_FragCoord.xy / vec2(screenSize);
+    vec3    current = texture(image, uv).rgb;
+    if (preview.temporal == 0) {
+        color = vec4(current, 1);
+        return;
+    }
+
+    // The rendered colors around the pixel, which the history is clamped to so that it doesn't leave trails behind moving edges
+    ivec2   size = textureSize(image, 0);
+    ivec2   pixel = clamp(ivec2(uv * vec2(size)), ivec2(0), size - 1);
+    vec3    neighbourMin = current,
+            neighbourMax = current;
+    for (int y = -1; y <= 1; y++)
+    for (int x = -1; x <= 1; x++) {
+        vec3 tap = texelFetch(image, clamp(pixel + ivec2(x, y), ivec2(0), size - 1), 0).rgb;
+        neighbourMin = min(neighbourMin, tap);
+        neighbourMax = max(neighbourMax, tap);
+    }
+
+    // Pixels without motion vectors (nothing was hit) are assumed to have stayed in place
+    // (The motion vectors are in uv, so they are the same at either resolution)
+    vec4    motion = texelFetch(guideMotion, pixel, 0);
+    vec2    prevUV = motion.z != 0 ? motion.xy : uv;
+    bool    onScreen = all(greaterThanEqual(prevUV, vec2(0))) && all(lessThanEqual(prevUV, vec2(1)));
+    if (historyValid == 0 || !onScreen) {
+        color = vec4(current, 1);
+        return;
+    }
+
+    // Blend the current image into the history, which gathers the detail of the jittered frames at the screen's resolution
+    vec3    prev = clamp(texture(history, prevUV).rgb, neighbourMin, neighbourMax);
+    color = vec4(mix(prev, current, preview.alpha), 1);
+}
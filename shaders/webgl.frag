@@ -0,0 +1,573 @@
+#version 300 es
+
+// The fragment-only path tracer of the WebGL2 renderer (web::WebRenderer)
+// WebGL2 has no storage buffers, compute shaders or image stores, so this is the shading path of raytracing.frag cut down to what it can run:
+// the scene is read from textures (see webscene.rs), and each frame is blended into an accumulation texture which the next frame reads.
+// Only spheres and unquantized triangle meshes are traced, lit by the emissive objects, without the fog, volumes, sun, ReSTIR, guiding or photons.
+
+// --- Constants ---
+const float PI = 3.1415926;
+const bool  CULL_FACE = true;
+const float kEpsilion = 0.001;
+
+// The projections, as RTProjection::id() numbers them
+const int PROJECTION_PERSPECTIVE = 0;
+const int PROJECTION_ORTHOGRAPHIC = 1;
+const int PROJECTION_FISHEYE = 2;
+const int PROJECTION_EQUIRECTANGULAR = 3;
+
+// Layout of the scene's textures (webscene::SCENE_TEXTURE_WIDTH and webscene::*_TEXELS)
+const int   SCENE_TEXTURE_WIDTH = 1024;
+const int   SPHERE_TEXELS = 5;
+const int   TRIANGLE_TEXELS = 9;
+const int   MESH_TEXELS = 2;
+
+// --- Structs ---
+
+// RTMaterial
+struct Material {
+    vec4 color;
+    vec4 emissionColor;
+    vec4 specularColor;
+    float smoothness;
+};
+
+// RTSphere
+struct Sphere {
+    float radius;
+    vec3 center;
+    Material material;
+};
+
+// RTTriangle
+struct Triangle {
+    vec3        p0,
+                p1,
+                p2,
+                normal0,
+                normal1,
+                normal2;
+    Material    material;
+};
+
+// RTMeshInfo
+struct MeshInfo {
+    int     startIndex,
+            count;
+    vec3    boundingBoxMin,
+            boundingBoxMax;
+};
+
+// Hit information
+struct HitInfo {
+    bool        didHit;
+    float       dist;
+    vec3        pos;
+    vec3        normal;
+    Material    material;
+    int         sphere;     // Index of the sphere which was hit, or -1 if it wasn't a sphere
+};
+
+struct Ray {
+    vec3 origin;
+    vec3 dir;
+};
+
+// --- Macros ---
+#define HitInfo0 HitInfo( false, 0.0, vec3(0), vec3(0), Material(vec4(0), vec4(0), vec4(0), 0.0), -1 )
+
+// --- Uniforms ---
+out vec4 color;
+
+uniform sampler2D sphereTexture;
+uniform sampler2D triangleTexture;
+uniform sampler2D meshTexture;
+uniform sampler2D accumulation;     // The image accumulated over the previous frames, or the image which is shown in the display pass
+uniform int spheresCount;
+uniform int meshesCount;
+
+// RTCamera, without its stereo, distortion and motion blur
+uniform vec2 screenSize;
+uniform float fov;
+uniform float focusDistance;
+uniform float apertureRadius;
+uniform mat4 localToWorld;
+uniform int projection;
+uniform float orthoHeight;
+uniform float fisheyeFov;
+
+// RTSettings
+uniform int maxBounces;
+uniform int raysPerFrag;
+uniform float divergeStrength;
+uniform bool nextEventEstimation;
+uniform float directClamp;
+uniform float indirectClamp;
+uniform float tMax;
+uniform uint seed;
+
+uniform uint frameCount;            // Frames accumulated so far
+uniform bool displayPass;           // If the accumulated image is only shown (gamma corrected) rather than traced into
+
+// --- Randomness functions ---
+
+// www.pcg-random.org, www.shadertoy.com/view/XlGcRh
+/**
+ * Generates a psuedo-random unsigned integer with value [0, 2^32 - 1].
+ *
+ * @param state The seed, which is changed after use.
+ * @return A psuedo-random unsigned integer.
+ */
+uint randInt(inout uint state) {
+    state = state * 747796405u + 2891336453u;
+    uint result = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    result = (result >> 22u) ^ result;
+    return result;
+}
+
+/**
+ * Generates a psuedo-float with value [0, 1].
+ *
+ * @param state The seed, which is changed after use.
+ * @return A psuedo-random float.
+ */
+float randFloat(inout uint state) {
+    return float(randInt(state)) / 4294967295.0; // 2^32 - 1
+}
+
+/**
+ * Maps a pair of uniform random numbers to a uniformly distributed point on the unit disc.
+ *
+ * @param u The random numbers, [0, 1].
+ * @return A point within the unit disc.
+ */
+vec2 SampleDisc(vec2 u) {
+    float ang = u.x * 2.0 * PI;
+    return vec2(cos(ang), sin(ang)) * sqrt(u.y);
+}
+
+/**
+ * Maps a pair of uniform random numbers to a uniformly distributed direction.
+ *
+ * @param u The random numbers, [0, 1].
+ * @return A point on the unit sphere.
+ */
+vec3 SampleSphere(vec2 u) {
+    float   ang = u.x * 2.0 * PI,
+            z = 1.0 - 2.0 * u.y,
+            r = sqrt(max(0.0, 1.0 - z*z));
+    return vec3(cos(ang) * r, sin(ang) * r, z);
+}
+
+// --- Scene functions ---
+/**
+ * Fetches a texel of one of the scene's textures.
+ *
+ * @param scene The texture.
+ * @param index The index of the texel, counting along the rows.
+ * @return The texel.
+ */
+vec4 FetchTexel(sampler2D scene, int index) {
+    return texelFetch(scene, ivec2(index % SCENE_TEXTURE_WIDTH, index / SCENE_TEXTURE_WIDTH), 0);
+}
+
+/**
+ * Loads a sphere from the sphere texture.
+ *
+ * @param index The index of the sphere.
+ * @return The sphere.
+ */
+Sphere LoadSphere(int index) {
+    int     first = index * SPHERE_TEXELS;
+    vec4    centerRadius = FetchTexel(sphereTexture, first);
+    return Sphere(
+        centerRadius.w,
+        centerRadius.xyz,
+        Material(FetchTexel(sphereTexture, first + 1), FetchTexel(sphereTexture, first + 2), FetchTexel(sphereTexture, first + 3), FetchTexel(sphereTexture, first + 4).x)
+    );
+}
+
+/**
+ * Loads a triangle from the triangle texture.
+ *
+ * @param index The index of the triangle.
+ * @return The triangle.
+ */
+Triangle LoadTriangle(int index) {
+    int     first = index * TRIANGLE_TEXELS;
+    vec4    p0Smoothness = FetchTexel(triangleTexture, first);
+    return Triangle(
+        p0Smoothness.xyz,
+        FetchTexel(triangleTexture, first + 1).xyz,
+        FetchTexel(triangleTexture, first + 2).xyz,
+        FetchTexel(triangleTexture, first + 3).xyz,
+        FetchTexel(triangleTexture, first + 4).xyz,
+        FetchTexel(triangleTexture, first + 5).xyz,
+        Material(FetchTexel(triangleTexture, first + 6), FetchTexel(triangleTexture, first + 7), FetchTexel(triangleTexture, first + 8), p0Smoothness.w)
+    );
+}
+
+/**
+ * Loads a mesh from the mesh texture.
+ *
+ * @param index The index of the mesh.
+ * @return The mesh.
+ */
+MeshInfo LoadMesh(int index) {
+    vec4    boxMinStart = FetchTexel(meshTexture, index * MESH_TEXELS),
+            boxMaxCount = FetchTexel(meshTexture, index * MESH_TEXELS + 1);
+    return MeshInfo(int(boxMinStart.w), int(boxMaxCount.w), boxMinStart.xyz, boxMaxCount.xyz);
+}
+
+// --- Ray intersection functions ---
+/**
+ * Checks for an intersection between a ray and a sphere.
+ *
+ * @param ray The ray.
+ * @param sphere The sphere.
+ *
+ * @return The hit information from the (possible) intersection.
+ */
+HitInfo RaySphere(Ray ray, Sphere sphere) {
+    HitInfo hitInfo = HitInfo0;
+    vec3 offsetRayOrigin = ray.origin - sphere.center;
+
+    // Solve for distance with a quadratic equation
+    float a = dot(ray.dir, ray.dir);
+    float b = 2.0 * dot(offsetRayOrigin, ray.dir);
+    float c = dot(offsetRayOrigin, offsetRayOrigin) - sphere.radius*sphere.radius;
+
+    // Quadratic discriminant
+    float discriminant = b * b - 4.0 * a * c;
+
+    // If d > 0, the ray intersects the sphere => calculate hitinfo
+    if (discriminant >= 0.0) {
+        float dist = (-b - sqrt(abs(discriminant))) / (2.0 * a);
+
+        // (If the intersection happens behind the ray, ignore it)
+        if (dist >= 0.0) {
+            hitInfo.didHit = true;
+            hitInfo.dist = dist;
+            hitInfo.pos = ray.origin + ray.dir * dist;
+            hitInfo.normal = normalize(hitInfo.pos - sphere.center);
+        }
+    }
+
+    return hitInfo;
+}
+
+/**
+ * Checks for an intersection between a ray and a triangle, with the Möller-Trumbore algorithm like raytracing.frag.
+ *
+ * @param ray The ray.
+ * @param triangle The triangle.
+ *
+ * @return The hit information from the (possible) intersection.
+ */
+HitInfo RayTriangle(Ray ray, Triangle triangle) {
+    HitInfo hitInfo = HitInfo0;
+
+    // Define vectors
+    vec3    v0 = triangle.p1 - triangle.p0,
+            v1 = triangle.p2 - triangle.p0,
+            v0v1c = cross( v0, v1 );
+
+    // Define determinant and inverse determinant
+    float   determinant = -dot( ray.dir, v0v1c ),
+            invDeterminant = 1.0 / determinant;
+
+    // If culling is enabled, verify that ray passes through triangle the right direction
+    if ( CULL_FACE && determinant < kEpsilion )
+        return hitInfo;
+
+    // (Check if ray is parallel with triangle)
+    else if ( abs(determinant) < kEpsilion )
+        return hitInfo;
+
+    // Calculate distance to triangle and barycentric coordinates
+    vec3    v3 = ray.origin - triangle.p0,
+            v3dirc = cross( v3, ray.dir );
+
+    float   dist = dot( v3, v0v1c ) * invDeterminant,
+            u = dot( v1, v3dirc ) * invDeterminant,
+            v = -dot( v0, v3dirc ) * invDeterminant,
+            w = 1.0 - u - v;
+
+    // Calculate intersection information and return
+    hitInfo.didHit  = dist >= 0.0 && u >= 0.0 && v >= 0.0 && w >= 0.0;
+    hitInfo.dist    = dist;
+    hitInfo.pos     = ray.origin + ray.dir * dist;
+    hitInfo.normal  = normalize( triangle.normal0 * w + triangle.normal1 * u + triangle.normal2 * v );
+
+    return hitInfo;
+}
+
+/**
+ * Checks for an intersection between a ray and a bounding box.
+ *
+ * @param ray The ray.
+ * @param boxMin The bottom left corner of the box.
+ * @param boxMax The top right corner of the box.
+ *
+ * @return If the ray intersects the box at all.
+ */
+bool RayBoundingBox(Ray ray, vec3 boxMin, vec3 boxMax) {
+    vec3    rayDirInverted = 1.0 / ray.dir,
+            boxMinRelative = (boxMin - ray.origin) * rayDirInverted,
+            boxMaxRelative = (boxMax - ray.origin) * rayDirInverted,
+            boxMinNew = min( boxMinRelative, boxMaxRelative ),
+            boxMaxNew = max( boxMinRelative, boxMaxRelative );
+
+    float   maxMinAxis = max( max( boxMinNew.x, boxMinNew.y ), boxMinNew.z ),
+            minMaxAxis = min( min( boxMaxNew.x, boxMaxNew.y ), boxMaxNew.z );
+
+    return maxMinAxis <= minMaxAxis;
+}
+
+// --- Raytracing functions ---
+/**
+ * Gets the first intersection which the ray might make.
+ *
+ * @param ray The ray.
+ * @return The hit information from the (possible) intersection.
+ */
+HitInfo CalculateRayCollision(Ray ray) {
+    HitInfo closestHit = HitInfo0;
+    closestHit.dist = -1.0;
+
+    // Raycast spheres
+    for (int i = 0; i < spheresCount; i++) {
+        Sphere sphere = LoadSphere(i);
+        HitInfo hitInfo = RaySphere(ray, sphere);
+        if (hitInfo.didHit && ( closestHit.dist < 0.0 || hitInfo.dist < closestHit.dist ) )
+        {
+            closestHit = hitInfo;
+            closestHit.material = sphere.material;
+            closestHit.sphere = i;
+        }
+    }
+
+    // Raycast meshes (triangles)
+    for (int i = 0; i < meshesCount; i++) {
+        MeshInfo meshInfo = LoadMesh(i);
+
+        // Cull meshes
+        if (!RayBoundingBox( ray, meshInfo.boundingBoxMin, meshInfo.boundingBoxMax ))
+            continue;
+
+        for (int j = meshInfo.startIndex; j < meshInfo.startIndex + meshInfo.count; j++) {
+            Triangle triangle = LoadTriangle(j);
+            HitInfo hitInfo = RayTriangle(ray, triangle);
+            if (hitInfo.didHit && ( closestHit.dist < 0.0 || hitInfo.dist < closestHit.dist ) )
+            {
+                closestHit = hitInfo;
+                closestHit.material = triangle.material;
+            }
+        }
+    }
+
+    // Ignore collisions beyond the maximum distance
+    if (closestHit.didHit && closestHit.dist > tMax) {
+        closestHit = HitInfo0;
+        closestHit.dist = -1.0;
+    }
+
+    return closestHit;
+}
+
+/**
+ * Samples the light arriving directly from emissive spheres at a point (next-event estimation), like SampleLights() in raytracing.frag.
+ * One light is picked at random, and a direction is picked uniformly within the cone it covers, after which a shadow ray checks if it's visible.
+ *
+ * @param pos The point.
+ * @param normal The surface normal at the point.
+ * @param surfaceSphere The index of the sphere the point is on, which can't light itself, or -1.
+ * @param state The seed, which is changed after use.
+ *
+ * @return The light reflected from the point, divided by the surface's color.
+ */
+vec3 SampleLights(vec3 pos, vec3 normal, int surfaceSphere, inout uint state) {
+    // Count the lights, then pick one
+    int lightCount = 0;
+    for (int i = 0; i < spheresCount; i++)
+        if (LoadSphere(i).material.emissionColor.w > 0.0) lightCount++;
+    if (lightCount == 0) return vec3(0);
+
+    int light = -1;
+    for (int i = 0, skip = min(int(randFloat(state) * float(lightCount)), lightCount - 1); light < 0; i++)
+        if (LoadSphere(i).material.emissionColor.w > 0.0 && skip-- == 0) light = i;
+    Sphere sphere = LoadSphere(light);
+
+    // Pick a direction within the cone of the sphere, as seen from the point
+    vec3    toLight = sphere.center - pos;
+    float   dist2 = dot(toLight, toLight);
+    if (light == surfaceSphere || dist2 <= sphere.radius * sphere.radius) return vec3(0);
+
+    float   cosMax = sqrt(1.0 - sphere.radius * sphere.radius / dist2),
+            cosTheta = 1.0 - randFloat(state) * (1.0 - cosMax),
+            sinTheta = sqrt(max(0.0, 1.0 - cosTheta*cosTheta)),
+            phi = randFloat(state) * 2.0 * PI;
+    vec3    w = normalize(toLight),
+            tangent = normalize(cross(abs(w.x) > 0.5 ? vec3(0, 1, 0) : vec3(1, 0, 0), w)),
+            bitangent = cross(w, tangent),
+            dir = tangent * cos(phi) * sinTheta + bitangent * sin(phi) * sinTheta + w * cosTheta;
+    float   cosSurface = dot(dir, normal);
+    if (cosSurface <= 0.0) return vec3(0);
+
+    // Check that the light isn't blocked
+    HitInfo shadowHit = CalculateRayCollision(Ray(pos + normal * kEpsilion, dir));
+    if (!shadowHit.didHit || shadowHit.sphere != light) return vec3(0);
+
+    // Divided by the pdf of the cone (1 / (2*PI*(1 - cosMax))) and the probability of picking the light, with a Lambertian BRDF (1/PI) * cosine
+    vec3 emittedLight = sphere.material.emissionColor.xyz * sphere.material.emissionColor.w;
+    return emittedLight * 2.0 * PI * (1.0 - cosMax) * float(lightCount) * cosSurface / PI;
+}
+
+/**
+ * Limits the brightness of light reaching the camera, with the settings' limits for direct and indirect light.
+ *
+ * @param light The light.
+ * @param bounces How many surfaces the light reflected off before reaching the camera.
+ *
+ * @return The clamped light.
+ */
+vec3 ClampLight(vec3 light, int bounces) {
+    float   limit = bounces <= 1 ? directClamp : indirectClamp,
+            brightest = max(light.r, max(light.g, light.b));
+    return (limit > 0.0 && brightest > limit) ? light * (limit / brightest) : light;
+}
+
+/**
+ * Traces a path through the scene, collecting the light along it.
+ * The environment is black like in raytracing.frag, so all light comes from the emissive objects.
+ *
+ * @param ray The camera ray.
+ * @param state The seed, which is changed after use.
+ *
+ * @return The light which reaches the camera along the ray.
+ */
+vec3 Trace(Ray ray, inout uint state) {
+    vec3 	incomingLight = vec3(0),
+            rayColor = vec3(1);
+    bool    sampledLights = false; // If the lights were sampled directly at the previous bounce
+
+    for (int i = 0; i < maxBounces; i++)
+    {
+        HitInfo hitInfo = CalculateRayCollision(ray);
+        if (!hitInfo.didHit)
+            break;
+        Material material = hitInfo.material;
+
+        // Calculate new pos and dir
+        ray.origin = hitInfo.pos;
+        bool 	isSpecular  = material.specularColor.w >= randFloat(state);
+        vec3 	specularDir = reflect(ray.dir, hitInfo.normal),
+                diffuseDir  = normalize(hitInfo.normal + SampleSphere(vec2(randFloat(state), randFloat(state))));
+        ray.dir = normalize(mix(diffuseDir, specularDir, material.smoothness * float(isSpecular)));
+
+        // Update light and color
+        // (Light emitted by spheres was already counted if they were sampled directly at the previous bounce)
+        vec3 emittedLight = material.emissionColor.xyz * material.emissionColor.w;
+        if (!sampledLights || hitInfo.sphere < 0)
+            incomingLight += ClampLight(emittedLight * rayColor, i);
+
+        // Sample the lights directly from diffuse surfaces
+        sampledLights = !isSpecular && nextEventEstimation;
+        if (sampledLights)
+            incomingLight += ClampLight(SampleLights(hitInfo.pos, hitInfo.normal, hitInfo.sphere, state) * material.color.xyz * rayColor, i + 1);
+
+        rayColor *= isSpecular ? material.specularColor.xyz : material.color.xyz;
+
+        // Russian roulette by the path's throughput
+        float p = min(max(rayColor.r, max(rayColor.g, rayColor.b)), 1.0);
+        if (randFloat(state) >= p) break;
+        rayColor *= 1.0 / p;
+    }
+
+    return incomingLight;
+}
+
+/**
+ * Generates a camera ray through a point on the screen, like GenerateRay() in raytracing.frag.
+ *
+ * @param uv The point on the screen, [0, 1] in both axes.
+ * @param state The seed, which is changed after use.
+ * @param ray The generated ray.
+ *
+ * @return If the point on the screen maps to a ray at all (fisheye projections leave the corners blank).
+ */
+bool GenerateRay(vec2 uv, inout uint state, out Ray ray) {
+    vec3    camUp = normalize(localToWorld[1].xyz),
+            camRight = normalize(localToWorld[0].xyz),
+            camFront = normalize(localToWorld[2].xyz);
+    vec2    jitter = SampleDisc(vec2(randFloat(state), randFloat(state))) * divergeStrength / screenSize.x;
+    float   aspect = screenSize.x / screenSize.y;
+
+    // Orthographic: Parallel rays fired from a plane centered on the camera
+    if (projection == PROJECTION_ORTHOGRAPHIC) {
+        vec2 planePos = (uv - 0.5) * vec2(orthoHeight * aspect, orthoHeight) + jitter * orthoHeight;
+        ray.origin = localToWorld[3].xyz + camRight*planePos.x + camUp*planePos.y;
+        ray.dir = camFront;
+        return true;
+    }
+
+    // Fisheye (equidistant): The angle from the front grows with the distance from the center, up to the circle which fits the screen's height
+    if (projection == PROJECTION_FISHEYE) {
+        vec2    p = (uv + jitter * vec2(1, aspect) - 0.5) * 2.0 * vec2(aspect, 1);
+        float   r = length(p),
+                theta = r * fisheyeFov * 0.5 * PI / 180.0;
+        if (theta > fisheyeFov * 0.5 * PI / 180.0) return false;
+
+        vec2 dirPlane = r > 0.0 ? p / r * sin(theta) : vec2(0);
+        ray.origin = localToWorld[3].xyz;
+        ray.dir = normalize(camRight*dirPlane.x + camUp*dirPlane.y + camFront*cos(theta));
+        return true;
+    }
+
+    // Equirectangular: x maps to longitude [-PI, PI], y maps to latitude [-PI/2, PI/2]
+    if (projection == PROJECTION_EQUIRECTANGULAR) {
+        vec2    p = uv + jitter * vec2(1, aspect);
+        float   phi = (p.x - 0.5) * 2.0 * PI,
+                theta = (p.y - 0.5) * PI;
+        ray.origin = localToWorld[3].xyz;
+        ray.dir = normalize(camRight*sin(phi)*cos(theta) + camUp*sin(theta) + camFront*cos(phi)*cos(theta));
+        return true;
+    }
+
+    // Perspective: Rays fired from a random point on the lens through the focus plane
+    float   planeHeight = focusDistance * tan(fov * 0.5 * PI / 180.0) * 2.0,
+            planeWidth = planeHeight * aspect;
+
+    vec3    focusPoint = (localToWorld * vec4(vec3(uv - 0.5, 1) * vec3(planeWidth, planeHeight, focusDistance), 1)).xyz,
+            focusPointJittered = focusPoint + camRight*jitter.x + camUp*jitter.y;
+    vec2    lensPoint = SampleDisc(vec2(randFloat(state), randFloat(state))) * apertureRadius;
+
+    ray.origin = localToWorld[3].xyz + camRight*lensPoint.x + camUp*lensPoint.y;
+    ray.dir = normalize(focusPointJittered - ray.origin);
+    return true;
+}
+
+// The main function
+void main() {
+    vec3 prevCol = texelFetch(accumulation, ivec2(gl_FragCoord.xy), 0).rgb;
+
+    // The display pass shows the accumulated image, clamped and gamma corrected
+    if (displayPass) {
+        color = vec4(pow(clamp(prevCol, 0.0, 1.0), vec3(1.0 / 2.2)), 1);
+        return;
+    }
+
+    // Each frag has its own seed every frame
+    uvec2   pixel = uvec2(gl_FragCoord.xy);
+    uint    state = (pixel.y * uint(screenSize.x) + pixel.x) * 719393u + frameCount * 2654435761u + seed;
+    vec2    uv = gl_FragCoord.xy / screenSize;
+
+    // Blend the average of the frag's rays into the accumulated image, weighting every frame equally
+    vec3 totalIncomingLight = vec3(0);
+    for (int i = 0; i < raysPerFrag; i++) {
+        Ray ray;
+        if (GenerateRay(uv, state, ray))
+            totalIncomingLight += Trace(ray, state);
+    }
+    color = vec4(mix(prevCol, totalIncomingLight / float(raysPerFrag), 1.0 / float(frameCount + 1u)), 1);
+}
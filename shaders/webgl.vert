@@ -0,0 +1,8 @@
+#version 300 es
+
+// The main function
+// (The screen is covered by one triangle made from the vertex IDs, so WebGL2 needs no vertex buffer for it)
+void main() {
+    vec2 position = vec2(float((gl_VertexID << 1) & 2), float(gl_VertexID & 2)) * 2.0 - 1.0;
+    gl_Position = vec4(position, 0.0, 1.0);
+}
@@ -0,0 +1,494 @@
+use std::{ any::Any, collections::HashMap };
+
+use crate::device::{ self, Device, TextureFilter, TextureFormat };
+use crate::error::EngineError;
+use crate::glapi;
+use crate::shader::Shader;
+use crate::util;
+use crate::gltracker::{ self, GlObject };
+
+// How many textures the AOVs are stored in, see Aov
+const AOV_TEXTURES: usize = 5;
+
+// The texture units which the accumulated AOVs are bound to, after the blue noise and the volumes
+const AOV_FIRST_TEXTURE_UNIT: u32 = 10;
+
+// The image units which the compute path stores the image and the AOVs to, see shaders/raytracing.frag
+const IMAGE_UNIT: u32 = 0;
+const AOV_FIRST_IMAGE_UNIT: u32 = 1;
+
+/**
+ * Enum for the arbitrary output variables (AOVs), which can be accumulated along with the image for compositing and denoising.
+ * Every AOV is of the first surface the camera's rays hit, except for the light, which is split into direct and indirect.
+ */
+// (They are only read back to be saved to EXR files, which need the exr feature)
+#[cfg_attr(not(feature = "exr"), allow(dead_code))]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Aov {
+    Albedo,     // The color of the surface
+    Normal,     // The shading normal of the surface, in world space
+    Depth,      // The distance to the surface along the camera's front, or -1 if nothing was hit
+    ObjectId,   // The index of the object, where the spheres come before the meshes, or -1 if nothing was hit
+    Direct,     // The light which reached the camera after at most one bounce
+    Indirect,   // The rest of the light
+}
+
+/**
+ * Aov functions.
+ */
+#[cfg_attr(not(feature = "exr"), allow(dead_code))]
+impl Aov {
+    // Every AOV, in the order they are stored in
+    pub const ALL: [Aov; 6] = [Aov::Albedo, Aov::Normal, Aov::Depth, Aov::ObjectId, Aov::Direct, Aov::Indirect];
+
+    /**
+     * Gets the name of the AOV, as used in file names.
+     */
+    pub fn name( &self ) -> &'static str {
+        match self {
+            Aov::Albedo     => { "albedo" },
+            Aov::Normal     => { "normal" },
+            Aov::Depth      => { "depth" },
+            Aov::ObjectId   => { "objectid" },
+            Aov::Direct     => { "direct" },
+            Aov::Indirect   => { "indirect" },
+        }
+    }
+
+    /**
+     * Gets how many channels the AOV has, which is 3 for colors and vectors, and 1 otherwise.
+     */
+    pub fn channels( &self ) -> usize {
+        match self {
+            Aov::Depth | Aov::ObjectId  => { 1 },
+            _                           => { 3 },
+        }
+    }
+
+    /**
+     * Gets which texture the AOV is stored in, and the format to read it back with.
+     * The depth and the object ID share a texture, in its red and green channels.
+     */
+    fn storage( &self ) -> ( usize, u32 ) {
+        match self {
+            Aov::Albedo     => { ( 0, gl::RGB ) },
+            Aov::Normal     => { ( 1, gl::RGB ) },
+            Aov::Depth      => { ( 2, gl::RED ) },
+            Aov::ObjectId   => { ( 2, gl::GREEN ) },
+            Aov::Direct     => { ( 3, gl::RGB ) },
+            Aov::Indirect   => { ( 4, gl::RGB ) },
+        }
+    }
+}
+
+/**
+ * Enum for the precision which the accumulated image is stored at.
+ * Every frame reads and writes the whole image, so on GPUs which are limited by memory bandwidth half precision is faster.
+ * Half precision has an 11-bit mantissa, where the blend of each frame into the rest soon becomes too small to round to
+ * anything, so the shader rounds it stochastically instead. That keeps the image converging, but leaves noise of about
+ * 1/2000 of each value which more frames don't remove.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AccumulationPrecision {
+    Full,   // RGBA32F, 16 bytes per pixel
+    Half,   // RGBA16F, 8 bytes per pixel
+}
+
+/**
+ * AccumulationPrecision functions.
+ */
+impl AccumulationPrecision {
+    /**
+     * Gets the format of the image's textures.
+     */
+    pub fn format( &self ) -> TextureFormat {
+        match self {
+            AccumulationPrecision::Full => { TextureFormat::Rgba32F },
+            AccumulationPrecision::Half => { TextureFormat::Rgba16F },
+        }
+    }
+}
+
+/**
+ * Struct for a progressive accumulation buffer.
+ * Owns two floating point framebuffers which are rendered to in turn ("ping-pong"), where each frame reads the previous result and blends the new frame into it.
+ * This lets noise converge over time, as long as nothing in the scene changes.
+ *
+ * To know when the scene changes, the inputs of the render (camera, settings, objects) are passed to watch() every frame.
+ * The accumulator keeps a copy of each, and resets when any of them differ from the previous frame.
+ *
+ * With AOVs, each framebuffer also has a texture per AOV (see Aov), which the shading pass accumulates the same way as the image.
+ * The AOVs are always RGBA32F, whatever the image's precision is, since depths and object IDs need the precision.
+ */
+pub struct Accumulator {
+    framebuffers: [u32; 2],
+    textures: [device::Texture; 2],
+    aov_textures: Vec<[device::Texture; 2]>, // The AOVs of each framebuffer, or empty if the AOVs are off
+    precision: AccumulationPrecision,
+    width: u32,
+    height: u32,
+    current: usize,
+    frame_count: u32,
+    watched: HashMap<String, Box<dyn Any>>,
+}
+
+/**
+ * Accumulator functions.
+ */
+#[allow(dead_code)]
+impl Accumulator {
+    /**
+     * Creates a new accumulator with two framebuffers, at full precision.
+     *
+     * @param width The width of the framebuffers, in pixels.
+     * @param height The height of the framebuffers, in pixels.
+     *
+     * @return The accumulator, or an error if the driver can't render to its framebuffers.
+     */
+    pub unsafe fn new( width: u32, height: u32 ) -> Result<Accumulator, EngineError> {
+        let mut accumulator = Accumulator {
+            framebuffers: [0; 2],
+            textures: [0; 2],
+            aov_textures: Vec::new(),
+            precision: AccumulationPrecision::Full,
+            width,
+            height,
+            current: 0,
+            frame_count: 0,
+            watched: HashMap::new(),
+        };
+        accumulator.create_framebuffers( false )?;
+        Ok( accumulator )
+    }
+
+    /**
+     * Creates a floating point texture at the accumulator's current size.
+     *
+     * @param format The format, such as RGBA32F.
+     * @return The texture.
+     */
+    unsafe fn create_texture( &self, format: TextureFormat ) -> device::Texture {
+        device::current().create_texture( self.width, self.height, format, TextureFilter::Nearest )
+    }
+
+    /**
+     * Creates the framebuffers and their textures, at the accumulator's current size.
+     *
+     * @param aovs If the framebuffers also get textures for the AOVs.
+     *
+     * @return An error if a framebuffer is incomplete, whose objects are still created, so they are deleted along with the rest.
+     */
+    unsafe fn create_framebuffers( &mut self, aovs: bool ) -> Result<(), EngineError> {
+        gl::GenFramebuffers( 2, self.framebuffers.as_mut_ptr() );
+        gltracker::created( GlObject::Framebuffer, &self.framebuffers );
+        self.aov_textures.clear();
+        if aovs {
+            for _ in 0..AOV_TEXTURES {
+                let textures = [self.create_texture( TextureFormat::Rgba32F ), self.create_texture( TextureFormat::Rgba32F )];
+                self.aov_textures.push( textures );
+            }
+        }
+
+        let mut complete = Ok( () );
+        for i in 0..2 {
+            // Create texture
+            self.textures[i] = self.create_texture( self.precision.format() );
+
+            // Attach it to the framebuffer, along with the AOVs, which come after the denoiser's guides in the shader's outputs
+            gl::BindFramebuffer( gl::FRAMEBUFFER, self.framebuffers[i] );
+            gl::FramebufferTexture2D( gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D, self.textures[i], 0 );
+            if aovs {
+                let mut draw_buffers = vec![gl::COLOR_ATTACHMENT0, gl::NONE, gl::NONE];
+                for ( k, textures ) in self.aov_textures.iter().enumerate() {
+                    let attachment = gl::COLOR_ATTACHMENT3 + k as u32;
+                    gl::FramebufferTexture2D( gl::FRAMEBUFFER, attachment, gl::TEXTURE_2D, textures[i], 0 );
+                    draw_buffers.push( attachment );
+                }
+                gl::DrawBuffers( draw_buffers.len() as i32, draw_buffers.as_ptr() );
+            }
+            complete = complete.and( util::check_framebuffer( "ACCUMULATOR::FRAMEBUFFER_INCOMPLETE" ) );
+        }
+
+        // Unbind
+        gl::BindTexture( gl::TEXTURE_2D, 0 );
+        gl::BindFramebuffer( gl::FRAMEBUFFER, 0 );
+        complete
+    }
+
+    /**
+     * Deletes the framebuffers and all their textures.
+     */
+    unsafe fn delete_framebuffers( &mut self ) {
+        gl::DeleteFramebuffers( 2, self.framebuffers.as_ptr() );
+        gltracker::deleted( GlObject::Framebuffer, &self.framebuffers );
+        for texture in self.textures.iter().chain( self.aov_textures.iter().flatten() ) {
+            device::current().delete_texture( *texture );
+        }
+    }
+
+    /**
+     * Deletes the accumulator's framebuffers and textures, once it is done with.
+     */
+    pub unsafe fn delete( mut self ) {
+        self.delete_framebuffers();
+    }
+
+    /**
+     * Resizes the framebuffers, which also resets the accumulation.
+     *
+     * @param width The new width, in pixels.
+     * @param height The new height, in pixels.
+     *
+     * @return An error if the driver can't render to the framebuffers at the new size.
+     */
+    pub unsafe fn resize( &mut self, width: u32, height: u32 ) -> Result<(), EngineError> {
+        let aovs = self.has_aovs();
+        self.delete_framebuffers();
+
+        self.width = width;
+        self.height = height;
+        self.reset();
+        self.create_framebuffers( aovs )
+    }
+
+    /**
+     * Resizes the framebuffers, resampling the accumulated image (and AOVs) to the new size so the accumulation continues from it.
+     * The image is filtered linearly, while the AOVs are taken from the nearest pixel, as object IDs can't be blended.
+     *
+     * @param width The new width, in pixels.
+     * @param height The new height, in pixels.
+     *
+     * @return An error if the driver can't render to the framebuffers at the new size, which is reset then.
+     */
+    pub unsafe fn rescale( &mut self, width: u32, height: u32 ) -> Result<(), EngineError> {
+        let ( old_framebuffers, old_textures, old_aov_textures ) = ( self.framebuffers, self.textures, self.aov_textures.clone() );
+        let ( old_width, old_height ) = ( self.width, self.height );
+        self.width = width;
+        self.height = height;
+        let complete = self.create_framebuffers( !old_aov_textures.is_empty() );
+        if complete.is_err() {
+            self.reset();
+        }
+
+        // (Each texture is blitted through a framebuffer of its own, so those of the accumulator keep their draw buffers)
+        let mut blit_framebuffers = [0; 2];
+        gl::GenFramebuffers( 2, blit_framebuffers.as_mut_ptr() );
+        gltracker::created( GlObject::Framebuffer, &blit_framebuffers );
+        gl::BindFramebuffer( gl::READ_FRAMEBUFFER, blit_framebuffers[0] );
+        gl::BindFramebuffer( gl::DRAW_FRAMEBUFFER, blit_framebuffers[1] );
+        let mut blits = vec![( old_textures[self.current], self.textures[self.current], gl::LINEAR )];
+        for ( old, new ) in old_aov_textures.iter().zip( &self.aov_textures ) {
+            blits.push( ( old[self.current], new[self.current], gl::NEAREST ) );
+        }
+        for ( from, to, filter ) in blits {
+            gl::FramebufferTexture2D( gl::READ_FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D, from, 0 );
+            gl::FramebufferTexture2D( gl::DRAW_FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D, to, 0 );
+            gl::BlitFramebuffer(
+                0, 0, old_width as i32, old_height as i32,
+                0, 0, width as i32, height as i32,
+                gl::COLOR_BUFFER_BIT, filter
+            );
+        }
+        gl::BindFramebuffer( gl::FRAMEBUFFER, 0 );
+        gl::DeleteFramebuffers( 2, blit_framebuffers.as_ptr() );
+        gltracker::deleted( GlObject::Framebuffer, &blit_framebuffers );
+
+        gl::DeleteFramebuffers( 2, old_framebuffers.as_ptr() );
+        gltracker::deleted( GlObject::Framebuffer, &old_framebuffers );
+        for texture in old_textures.iter().chain( old_aov_textures.iter().flatten() ) {
+            device::current().delete_texture( *texture );
+        }
+        complete
+    }
+
+    /**
+     * Turns the AOVs on or off, which recreates the framebuffers and so also resets the accumulation.
+     *
+     * @param enabled If the AOVs are accumulated along with the image.
+     *
+     * @return An error if the driver can't render to the new framebuffers.
+     */
+    pub unsafe fn set_aovs( &mut self, enabled: bool ) -> Result<(), EngineError> {
+        if enabled == self.has_aovs() {
+            return Ok( () );
+        }
+        self.delete_framebuffers();
+        self.reset();
+        self.create_framebuffers( enabled )
+    }
+
+    /**
+     * Sets the precision which the image is accumulated at, which recreates the framebuffers and so also resets the accumulation.
+     *
+     * @param precision The precision.
+     *
+     * @return An error if the driver can't render to the new framebuffers.
+     */
+    pub unsafe fn set_precision( &mut self, precision: AccumulationPrecision ) -> Result<(), EngineError> {
+        if precision == self.precision {
+            return Ok( () );
+        }
+        let aovs = self.has_aovs();
+        self.delete_framebuffers();
+        self.precision = precision;
+        self.reset();
+        self.create_framebuffers( aovs )
+    }
+
+    /**
+     * Gets the precision which the image is accumulated at.
+     */
+    pub fn precision( &self ) -> AccumulationPrecision {
+        self.precision
+    }
+
+    /**
+     * Gets if the AOVs are accumulated along with the image.
+     */
+    pub fn has_aovs( &self ) -> bool {
+        !self.aov_textures.is_empty()
+    }
+
+    /**
+     * Resets the accumulation, so the next frame starts from scratch.
+     * Must be called whenever anything affecting the image changes.
+     */
+    pub fn reset( &mut self ) {
+        self.frame_count = 0;
+    }
+
+    /**
+     * Watches an input of the render, resetting the accumulation if it changed since the last time it was watched.
+     * Inputs are told apart by name, so each must be watched under its own name every frame.
+     *
+     * @param name The name of the input.
+     * @param value The current value of the input.
+     *
+     * @return If the input changed (which is always the case the first time it's watched).
+     */
+    pub fn watch<T: PartialEq + Clone + 'static>( &mut self, name: &str, value: &T ) -> bool {
+        let changed = match self.watched.get( name ).and_then( |prev| prev.downcast_ref::<T>() ) {
+            Some( prev ) => { prev != value },
+            None => { true },
+        };
+
+        if changed {
+            self.watched.insert( name.to_string(), Box::new( value.clone() ) );
+            self.reset();
+        }
+        changed
+    }
+
+    /**
+     * Gets the amount of frames accumulated since the last reset.
+     */
+    pub fn frame_count( &self ) -> u32 {
+        self.frame_count
+    }
+
+    /**
+     * Gets the size of the framebuffers, in pixels.
+     */
+    pub fn size( &self ) -> ( u32, u32 ) {
+        ( self.width, self.height )
+    }
+
+    /**
+     * Gets the texture holding the latest accumulated image.
+     */
+    pub fn texture( &self ) -> u32 {
+        self.textures[self.current]
+    }
+
+    /**
+     * Gets the framebuffer holding the latest accumulated image.
+     */
+    pub fn framebuffer( &self ) -> u32 {
+        self.framebuffers[self.current]
+    }
+
+    /**
+     * Reads back the latest accumulated image, which is linear and unbounded (HDR).
+     *
+     * @return The pixels as RGB, with the bottom row first.
+     */
+    pub unsafe fn read_pixels( &self ) -> Vec<f32> {
+        let channels = glapi::format_channels( glapi::read_format( gl::RGB ) ).1;
+        let mut pixels = vec![0.0f32; self.width as usize * self.height as usize * channels];
+        glapi::read_texture( self.texture(), 0, self.width, self.height, gl::RGB, pixels.as_mut_ptr() as *mut _ );
+        glapi::keep_channels( &pixels, gl::RGB )
+    }
+
+    /**
+     * Reads back an accumulated AOV.
+     *
+     * @param aov The AOV.
+     *
+     * @return The pixels with as many channels as the AOV has, with the bottom row first, or None if the AOVs are off.
+     */
+    pub unsafe fn read_aov( &self, aov: Aov ) -> Option<Vec<f32>> {
+        let ( index, format ) = aov.storage();
+        let textures = self.aov_textures.get( index )?;
+        let channels = glapi::format_channels( glapi::read_format( format ) ).1;
+        let mut pixels = vec![0.0f32; self.width as usize * self.height as usize * channels];
+        glapi::read_texture( textures[self.current], 0, self.width, self.height, format, pixels.as_mut_ptr() as *mut _ );
+        Some( glapi::keep_channels( &pixels, format ) )
+    }
+
+    /**
+     * Restores an accumulated image, such as one read back with read_pixels(), so the accumulation continues from it.
+     * The inputs must already be watched, or the next frame resets the accumulation again.
+     *
+     * @param pixels The pixels as RGB, with the bottom row first, at the accumulator's size.
+     * @param frame_count The amount of frames which were accumulated into the image.
+     */
+    pub unsafe fn restore( &mut self, pixels: &[f32], frame_count: u32 ) {
+        gl::BindTexture( gl::TEXTURE_2D, self.texture() );
+        gl::PixelStorei( gl::UNPACK_ALIGNMENT, 1 );
+        gl::TexSubImage2D( gl::TEXTURE_2D, 0, 0, 0, self.width as i32, self.height as i32, gl::RGB, gl::FLOAT, pixels.as_ptr() as *const _ );
+        gl::BindTexture( gl::TEXTURE_2D, 0 );
+        self.frame_count = frame_count;
+    }
+
+    /**
+     * Prepares a shader for rendering the next frame into the accumulator.
+     * Binds the framebuffer to write to, and passes the previous result (and AOVs) and frame count to the shader.
+     * The shader must be active.
+     *
+     * @param shader The shader which renders the frame.
+     * @param texture_unit The texture unit to bind the previous result to.
+     */
+    pub unsafe fn begin_frame( &self, shader: &Shader, texture_unit: u32 ) {
+        let ( read, write ) = ( self.current, 1 - self.current );
+
+        gl::BindFramebuffer( gl::FRAMEBUFFER, self.framebuffers[write] );
+        gl::Viewport( 0, 0, self.width as i32, self.height as i32 );
+        // (The compute path has no framebuffer, so it stores to the same textures as images)
+        gl::BindImageTexture( IMAGE_UNIT, self.textures[write], 0, gl::FALSE, 0, gl::WRITE_ONLY, self.precision.format().gl_internal_format() );
+
+        gl::ActiveTexture( gl::TEXTURE0 + texture_unit );
+        gl::BindTexture( gl::TEXTURE_2D, self.textures[read] );
+        gl::Uniform1i( shader.get_uniform_location( "accumulation" ), texture_unit as i32 );
+        gl::Uniform1ui( shader.get_uniform_location( "frameCount" ), self.frame_count );
+        gl::Uniform1ui( shader.get_uniform_location( "halfAccumulation" ), ( self.precision == AccumulationPrecision::Half ) as u32 );
+
+        gl::Uniform1ui( shader.get_uniform_location( "aovs" ), self.has_aovs() as u32 );
+        for ( k, textures ) in self.aov_textures.iter().enumerate() {
+            let unit = AOV_FIRST_TEXTURE_UNIT + k as u32;
+            gl::ActiveTexture( gl::TEXTURE0 + unit );
+            gl::BindTexture( gl::TEXTURE_2D, textures[read] );
+            gl::Uniform1i( shader.get_uniform_location( &format!( "aovAccumulation[{k}]" ) ), unit as i32 );
+            gl::BindImageTexture( AOV_FIRST_IMAGE_UNIT + k as u32, textures[write], 0, gl::FALSE, 0, gl::WRITE_ONLY, gl::RGBA32F );
+        }
+    }
+
+    /**
+     * Finishes rendering a frame into the accumulator, making it the latest result.
+     * Unbinds the framebuffer, so following draws go to the screen.
+     */
+    pub unsafe fn end_frame( &mut self ) {
+        gl::BindFramebuffer( gl::FRAMEBUFFER, 0 );
+        self.current = 1 - self.current;
+        self.frame_count += 1;
+    }
+}
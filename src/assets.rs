@@ -0,0 +1,67 @@
+use std::path::Path;
+use std::sync::RwLock;
+
+// The directory the engine's shaders and resources are in, or None for the crate's own
+static ROOT: RwLock<Option<String>> = RwLock::new( None );
+
+/**
+ * Sets the directory the engine's shaders and resources are loaded from, see RenderConfig::asset_root.
+ * It applies to the whole process, to whatever is built after it is set.
+ *
+ * @param root The directory, which holds the shaders/ and resources/ directories.
+ */
+pub fn set_root( root: &str ) {
+    if let Ok( mut current ) = ROOT.write() {
+        *current = Some( root.to_string() );
+    }
+}
+
+/**
+ * Gets the directory the engine's shaders and resources are loaded from, which is the crate's own until another is set (see set_root()).
+ */
+pub fn root() -> String {
+    ROOT.read().ok().and_then( |root| root.clone() ).unwrap_or_else( default_root )
+}
+
+/**
+ * Gets the directory the engine's shaders and resources are in when it is built, so they are found from wherever it is run or embedded.
+ */
+pub fn default_root() -> String {
+    env!( "CARGO_MANIFEST_DIR" ).to_string()
+}
+
+/**
+ * Gets the path of one of the engine's shaders or resources, in the asset root (see root()).
+ * Paths which are absolute are kept as they are.
+ *
+ * @param relative The path within the asset root, such as "shaders/raytracing.frag".
+ */
+pub fn path( relative: &str ) -> String {
+    Path::new( &root() ).join( relative ).to_string_lossy().into_owned()
+}
+
+/**
+ * Gets the path a scene is loaded from, which is the path as it is if it can be found from the working directory,
+ * and otherwise the path in the asset root (see path()), so the default scene is found from wherever the engine is run or embedded.
+ *
+ * @param scene The path of the scene, such as "resources/Knight.obj".
+ */
+pub fn scene_path( scene: &str ) -> String {
+    if Path::new( scene ).exists() { scene.to_string() } else { path( scene ) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_are_in_the_crate_by_default() {
+        assert!( Path::new( &path( "shaders/raytracing.frag" ) ).exists() );
+    }
+
+    #[test]
+    fn scenes_are_found_outside_the_working_directory() {
+        assert!( Path::new( &scene_path( "resources/Knight.obj" ) ).exists() );
+        assert_eq!( scene_path( file!() ), file!() );
+    }
+}
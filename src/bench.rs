@@ -0,0 +1,478 @@
+use std::io::Write;
+use std::time::Instant;
+
+use serde::Serialize;
+use tracing::info;
+
+use crate::error::EngineError;
+use crate::headless;
+use crate::offscreen::{ self, OffscreenRenderer };
+use crate::raytracing::{ RTCamera, RTMaterial, RTSettings, RTSphere, RTTriangle };
+use crate::stats::RenderStats;
+
+// Columns of the CSV report, which has a row per scene of each run
+const CSV_HEADER: &str = "version,commit,renderer,gl_version,width,height,scene,frames,samples_per_pixel,ms_per_frame_mean,ms_per_frame_median,rays_per_second,primary_rays_per_second,secondary_rays_per_second,halfway_rms";
+
+/**
+ * Enum for the scenes which the benchmarks render.
+ * They are made in code rather than loaded, so they stay the same across commits.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BenchScene {
+    SphereField,    // Hundreds of spheres of different materials, lit by the sky and an emissive sphere
+    CornellBox,     // A few walls and spheres, lit by a small emissive sphere, so most light is indirect
+    BigMesh,        // Tori of thousands of smooth triangles, split over meshes like a large model
+}
+
+/**
+ * Gets a quad of two triangles, with flat normals.
+ *
+ * @param corners The corners, going around the quad.
+ * @param normal Which way the quad faces, which the triangles are wound to face (see RTTriangle::intersect()).
+ * @param material The material.
+ */
+fn quad( corners: [glm::Vec3; 4], normal: glm::Vec3, material: &RTMaterial ) -> Vec<RTTriangle> {
+    let triangle = |p0: glm::Vec3, mut p1: glm::Vec3, mut p2: glm::Vec3| {
+        if ( p1 - p0 ).cross( &( p2 - p0 ) ).dot( &normal ) < 0.0 {
+            std::mem::swap( &mut p1, &mut p2 );
+        }
+        RTTriangle {
+            p0: p0.into(),
+            p1: p1.into(),
+            p2: p2.into(),
+            normal0: normal.into(),
+            normal1: normal.into(),
+            normal2: normal.into(),
+            material: material.clone(),
+        }
+    };
+    vec![triangle( corners[0], corners[1], corners[2] ), triangle( corners[0], corners[2], corners[3] )]
+}
+
+/**
+ * Gets a torus of triangles with smooth normals, lying flat around the y axis.
+ *
+ * @param center The center.
+ * @param radius The distance from the center to the middle of the tube.
+ * @param tube_radius The radius of the tube.
+ * @param segments How many times the torus is split around the y axis and around the tube.
+ * @param material The material.
+ */
+fn torus( center: glm::Vec3, radius: f32, tube_radius: f32, segments: ( u32, u32 ), material: &RTMaterial ) -> Vec<RTTriangle> {
+    let vertex = |i: u32, j: u32| {
+        let ( u, v ) = ( i as f32 / segments.0 as f32 * std::f32::consts::TAU, j as f32 / segments.1 as f32 * std::f32::consts::TAU );
+        let normal = glm::vec3( u.cos() * v.cos(), v.sin(), u.sin() * v.cos() );
+        ( center + glm::vec3( u.cos(), 0.0, u.sin() ) * radius + normal * tube_radius, normal )
+    };
+    let mut triangles = Vec::with_capacity( ( segments.0 * segments.1 * 2 ) as usize );
+    for i in 0..segments.0 {
+        for j in 0..segments.1 {
+            let corners = [vertex( i, j ), vertex( i + 1, j ), vertex( i + 1, j + 1 ), vertex( i, j + 1 )];
+            for [a, b, c] in [[0, 2, 1], [0, 3, 2]] {
+                triangles.push( RTTriangle {
+                    p0: corners[a].0.into(),
+                    p1: corners[b].0.into(),
+                    p2: corners[c].0.into(),
+                    normal0: corners[a].1.into(),
+                    normal1: corners[b].1.into(),
+                    normal2: corners[c].1.into(),
+                    material: material.clone(),
+                } );
+            }
+        }
+    }
+    triangles
+}
+
+/**
+ * Gets a diffuse material of a color.
+ */
+fn diffuse( color: glm::Vec3 ) -> RTMaterial {
+    RTMaterial { color: glm::vec4( color.x, color.y, color.z, 1.0 ), ..RTMaterial::new() }
+}
+
+/**
+ * Gets a sphere which only emits light.
+ */
+fn light( center: glm::Vec3, radius: f32, strength: f32 ) -> RTSphere {
+    RTSphere { radius, center: center.into(), material: RTMaterial { emission_color: glm::vec4( 1.0, 0.9, 0.8, strength ), ..RTMaterial::new() } }
+}
+
+/**
+ * BenchScene functions.
+ */
+impl BenchScene {
+    /**
+     * Gets every scene, in the order they are rendered.
+     */
+    pub fn all() -> [BenchScene; 3] {
+        [BenchScene::SphereField, BenchScene::CornellBox, BenchScene::BigMesh]
+    }
+
+    /**
+     * Gets the name of the scene, which it is given as and reported by.
+     */
+    pub fn name( &self ) -> &'static str {
+        match self {
+            BenchScene::SphereField => { "sphere-field" },
+            BenchScene::CornellBox  => { "cornell-box" },
+            BenchScene::BigMesh     => { "big-mesh" },
+        }
+    }
+
+    /**
+     * Gets a scene from its name.
+     *
+     * @param name The name, such as "cornell-box".
+     *
+     * @return The scene, or None if there is none by that name.
+     */
+    pub fn from_name( name: &str ) -> Option<BenchScene> {
+        BenchScene::all().into_iter().find( |scene| scene.name() == name )
+    }
+
+    /**
+     * Builds the scene.
+     *
+     * @param screen_size The size of the frames, which the camera is made for.
+     *
+     * @return The spheres, the triangles of each mesh, and the camera.
+     */
+    pub fn build( &self, screen_size: glm::Vec2 ) -> ( Vec<RTSphere>, Vec<Vec<RTTriangle>>, RTCamera ) {
+        let up = glm::vec3( 0.0, 1.0, 0.0 );
+        match self {
+            BenchScene::SphereField => {
+                let mut spheres = vec![
+                    RTSphere { radius: 1000.0, center: glm::vec3( 0.0, -1000.0, 0.0 ).into(), material: diffuse( glm::vec3( 0.5, 0.5, 0.5 ) ) },
+                    light( glm::vec3( 0.0, 12.0, 0.0 ), 3.0, 4.0 ),
+                ];
+                for x in -8..8 {
+                    for z in -8..8 {
+                        let i = ( x + 8 + ( z + 8 ) * 16 ) as f32;
+                        let color = glm::vec3( 0.5 + 0.5 * ( i * 0.7 ).sin(), 0.5 + 0.5 * ( i * 1.3 ).sin(), 0.5 + 0.5 * ( i * 2.1 ).sin() );
+                        let material = RTMaterial {
+                            specular_color: glm::vec4( 1.0, 1.0, 1.0, if ( x + z ) % 3 == 0 { 0.5 } else { 0.0 } ),
+                            smoothness: ( i * 0.37 ).fract(),
+                            ..diffuse( color )
+                        };
+                        spheres.push( RTSphere { radius: 0.4, center: glm::vec3( x as f32 + 0.5, 0.4, z as f32 + 0.5 ).into(), material } );
+                    }
+                }
+                ( spheres, vec![], RTCamera::look_at( glm::vec3( 0.0, 6.0, -14.0 ), glm::vec3( 0.0, 0.0, 0.0 ), up, 60.0, screen_size ) )
+            },
+            BenchScene::CornellBox => {
+                let corner = |x: f32, y: f32, z: f32| glm::vec3( x, y, z );
+                let white = diffuse( glm::vec3( 0.73, 0.73, 0.73 ) );
+                let walls = [
+                    quad( [corner( -1.0, 0.0, -1.0 ), corner( 1.0, 0.0, -1.0 ), corner( 1.0, 0.0, 1.0 ), corner( -1.0, 0.0, 1.0 )], glm::vec3( 0.0, 1.0, 0.0 ), &white ),
+                    quad( [corner( -1.0, 2.0, -1.0 ), corner( 1.0, 2.0, -1.0 ), corner( 1.0, 2.0, 1.0 ), corner( -1.0, 2.0, 1.0 )], glm::vec3( 0.0, -1.0, 0.0 ), &white ),
+                    quad( [corner( -1.0, 0.0, 1.0 ), corner( 1.0, 0.0, 1.0 ), corner( 1.0, 2.0, 1.0 ), corner( -1.0, 2.0, 1.0 )], glm::vec3( 0.0, 0.0, -1.0 ), &white ),
+                    quad( [corner( -1.0, 0.0, -1.0 ), corner( -1.0, 0.0, 1.0 ), corner( -1.0, 2.0, 1.0 ), corner( -1.0, 2.0, -1.0 )], glm::vec3( 1.0, 0.0, 0.0 ), &diffuse( glm::vec3( 0.65, 0.05, 0.05 ) ) ),
+                    quad( [corner( 1.0, 0.0, -1.0 ), corner( 1.0, 0.0, 1.0 ), corner( 1.0, 2.0, 1.0 ), corner( 1.0, 2.0, -1.0 )], glm::vec3( -1.0, 0.0, 0.0 ), &diffuse( glm::vec3( 0.12, 0.45, 0.15 ) ) ),
+                ];
+                let spheres = vec![
+                    light( glm::vec3( 0.0, 1.85, 0.0 ), 0.12, 40.0 ),
+                    RTSphere { radius: 0.35, center: glm::vec3( -0.4, 0.35, 0.3 ).into(), material: white.clone() },
+                    RTSphere {
+                        radius: 0.3,
+                        center: glm::vec3( 0.45, 0.3, -0.2 ).into(),
+                        material: RTMaterial { specular_color: glm::vec4( 1.0, 1.0, 1.0, 1.0 ), smoothness: 0.95, ..white.clone() },
+                    },
+                ];
+                // (The walls are one mesh, like a model of a room would be)
+                ( spheres, vec![walls.concat()], RTCamera::look_at( glm::vec3( 0.0, 1.0, -3.4 ), glm::vec3( 0.0, 1.0, 0.0 ), up, 40.0, screen_size ) )
+            },
+            BenchScene::BigMesh => {
+                let floor = quad(
+                    [glm::vec3( -8.0, 0.0, -8.0 ), glm::vec3( 8.0, 0.0, -8.0 ), glm::vec3( 8.0, 0.0, 8.0 ), glm::vec3( -8.0, 0.0, 8.0 )],
+                    up,
+                    &diffuse( glm::vec3( 0.5, 0.5, 0.5 ) )
+                );
+                let mut meshes = vec![floor];
+                for x in 0..4 {
+                    for z in 0..4 {
+                        let material = RTMaterial { specular_color: glm::vec4( 1.0, 1.0, 1.0, 0.3 ), smoothness: 0.8, ..diffuse( glm::vec3( 0.8, 0.4 + 0.1 * x as f32, 0.2 + 0.1 * z as f32 ) ) };
+                        let center = glm::vec3( x as f32 * 3.0 - 4.5, 0.4, z as f32 * 3.0 - 4.5 );
+                        meshes.push( torus( center, 1.0, 0.4, ( 48, 24 ), &material ) );
+                    }
+                }
+                ( vec![light( glm::vec3( 0.0, 10.0, 0.0 ), 2.0, 6.0 )], meshes, RTCamera::look_at( glm::vec3( 0.0, 7.0, -13.0 ), glm::vec3( 0.0, 0.0, 0.0 ), up, 55.0, screen_size ) )
+            },
+        }
+    }
+}
+
+/**
+ * Enum for the formats which the report can be saved in.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ReportFormat {
+    Json,   // The whole report, which replaces the file
+    Csv,    // A row per scene, which are added to the file so it collects the runs
+}
+
+/**
+ * Struct for the settings of the benchmarks, which render each scene for a while and report how fast they were.
+ * They are started from the command line with --bench, see from_args() and headless::USAGE.
+ */
+#[derive(Clone, Debug)]
+pub struct BenchSettings {
+    pub scenes: Vec<BenchScene>,
+    pub seconds: f32, // How long each scene is rendered for
+    pub width: u32,
+    pub height: u32,
+    pub output: String, // The file the report is saved to
+    pub format: ReportFormat,
+}
+
+/**
+ * BenchSettings functions.
+ */
+impl BenchSettings {
+    /**
+     * Reads the settings from the command line, for example: --bench --bench-scenes cornell-box,big-mesh --output bench.csv
+     *
+     * @param args The command line's arguments, without the program's name.
+     *
+     * @return The settings, or None if --bench wasn't given, or an error if the arguments are wrong.
+     */
+    pub fn from_args( args: &[String] ) -> Result<Option<BenchSettings>, EngineError> {
+        if !args.iter().any( |arg| arg == "--bench" ) {
+            return Ok( None );
+        }
+
+        let mut settings = BenchSettings {
+            scenes: BenchScene::all().to_vec(),
+            seconds: 10.0,
+            width: 1280,
+            height: 720,
+            output: "bench.json".to_string(),
+            format: ReportFormat::Json,
+        };
+        let mut args = args.iter();
+        while let Some( arg ) = args.next() {
+            if arg == "--bench" {
+                continue;
+            }
+            let value = args.next().ok_or( EngineError::parse( "BENCH::MISSING_VALUE", arg.to_string() ) )?;
+            let count = || headless::parse_value::<u32>( arg, value ).ok().filter( |&n| n > 0 ).ok_or( EngineError::parse( "BENCH::INVALID_VALUE", format!( "{arg} {value}" ) ) );
+            match arg.as_str() {
+                "--bench-scenes"    => {
+                    settings.scenes = value.split( ',' )
+                        .map( |name| BenchScene::from_name( name.trim() ).ok_or( EngineError::parse( "BENCH::UNKNOWN_SCENE", format!( "{name}\nThe scenes are sphere-field, cornell-box and big-mesh" ) ) ) )
+                        .collect::<Result<Vec<BenchScene>, EngineError>>()?;
+                },
+                "--bench-seconds"   => { settings.seconds = headless::parse_value::<f32>( arg, value ).ok().filter( |&seconds| seconds > 0.0 ).ok_or( EngineError::parse( "BENCH::INVALID_VALUE", format!( "{arg} {value}" ) ) )? },
+                "--width"           => { settings.width = count()? },
+                "--height"          => { settings.height = count()? },
+                "--output"          => { settings.output = value.clone() },
+                _                   => { return Err( EngineError::parse( "BENCH::UNKNOWN_ARGUMENT", format!( "{arg}\nRun with --help to see the arguments" ) ) ) },
+            }
+        }
+
+        let extension = std::path::Path::new( &settings.output ).extension().and_then( |extension| extension.to_str() ).unwrap_or( "" );
+        settings.format = match extension.to_ascii_lowercase().as_str() {
+            "json"  => { ReportFormat::Json },
+            "csv"   => { ReportFormat::Csv },
+            _       => { return Err( EngineError::parse( "BENCH::UNKNOWN_FORMAT", format!( "{}\nThe report must be a .json or .csv file", settings.output ) ) ) },
+        };
+        Ok( Some( settings ) )
+    }
+}
+
+/**
+ * Struct for how fast a scene was rendered, see run().
+ */
+#[derive(Clone, Debug, Serialize)]
+pub struct SceneResult {
+    pub scene: &'static str,
+    pub frames: u32, // Frames rendered in the time
+    pub samples_per_pixel: u32, // Samples accumulated into each pixel by the end
+    pub ms_per_frame_mean: f64,
+    pub ms_per_frame_median: f64,
+    pub rays_per_second: f64, // Every ray traced, primary and secondary
+    pub primary_rays_per_second: f64,
+    pub secondary_rays_per_second: f64,
+    pub halfway_rms: f64, // RMS difference between the image halfway through and at the end, which is lower the faster it converges
+}
+
+/**
+ * Struct for the report of a run of the benchmarks, with what it was run on so runs on other commits and GPUs can be told apart.
+ */
+#[derive(Clone, Debug, Serialize)]
+pub struct BenchReport {
+    pub version: &'static str, // Version of the engine
+    pub commit: Option<String>, // The git commit it was built from, if it was run in its repository
+    pub renderer: String, // The GPU and driver, as OpenGL names them
+    pub gl_version: String,
+    pub width: u32,
+    pub height: u32,
+    pub seconds: f32, // How long each scene was rendered for
+    pub scenes: Vec<SceneResult>,
+}
+
+/**
+ * Gets a string of the driver, such as gl::RENDERER.
+ */
+unsafe fn gl_string( name: gl::types::GLenum ) -> String {
+    let string = gl::GetString( name );
+    if string.is_null() { String::new() } else { std::ffi::CStr::from_ptr( string as *const _ ).to_string_lossy().into_owned() }
+}
+
+/**
+ * Gets the git commit of the working directory, or None if it isn't a repository or git isn't installed.
+ */
+fn git_commit() -> Option<String> {
+    let output = std::process::Command::new( "git" ).args( ["rev-parse", "--short", "HEAD"] ).output().ok()?;
+    let commit = String::from_utf8( output.stdout ).ok()?.trim().to_string();
+    ( output.status.success() && !commit.is_empty() ).then_some( commit )
+}
+
+/**
+ * Gets the RMS difference of two images, with each channel clamped to [0, 1] so a few bright pixels don't outweigh the rest.
+ */
+fn rms_difference( a: &[f32], b: &[f32] ) -> f64 {
+    let sum: f64 = a.iter().zip( b ).map( |( &a, &b )| ( a.clamp( 0.0, 1.0 ) as f64 - b.clamp( 0.0, 1.0 ) as f64 ).powi( 2 ) ).sum();
+    ( sum / a.len().max( 1 ) as f64 ).sqrt()
+}
+
+/**
+ * Renders a scene for a while and measures how fast it went.
+ * Each frame is waited for with gl::Finish(), so its time is the GPU's, which costs some speed as frames can't overlap.
+ * The first frame's time isn't counted, as drivers finish building the shader when it is first drawn with.
+ * The context must be current.
+ *
+ * @param scene The scene.
+ * @param settings The settings of the benchmarks.
+ * @param stats The ray counter, which is turned on for the scene.
+ *
+ * @return The result, or an error if the shaders couldn't be built.
+ */
+unsafe fn bench_scene( scene: BenchScene, settings: &BenchSettings, stats: &mut RenderStats ) -> Result<SceneResult, EngineError> {
+    let ( spheres, parts, camera ) = scene.build( glm::vec2( settings.width as f32, settings.height as f32 ) );
+    let ( triangles, meshes ) = offscreen::meshes_of( &parts );
+    let mut renderer = OffscreenRenderer::new( spheres, &triangles, meshes, settings.width, settings.height )?;
+    let rt_settings = RTSettings { seed: 1, ..crate::engine::initial_settings( None ) };
+    stats.set_enabled( true );
+
+    let ( mut frame_times, mut counted_rays ) = ( Vec::new(), Vec::new() );
+    let mut halfway_pixels = None;
+    let mut frame_time = 0.0;
+    let start = Instant::now();
+    while start.elapsed().as_secs_f32() < settings.seconds {
+        let frame_start = Instant::now();
+        renderer.shader().activate();
+        stats.begin_frame( renderer.shader(), frame_time );
+        renderer.render_frame( &camera, &rt_settings );
+        gl::Finish();
+        frame_time = frame_start.elapsed().as_secs_f32();
+        if renderer.frame_count() > 1 {
+            frame_times.push( frame_time as f64 * 1000.0 );
+        }
+        // (The counter is read two frames after it was counted into, so the first reads are of nothing)
+        let ( primary, secondary ) = stats.rays();
+        if primary > 0 {
+            counted_rays.push( ( primary as f64, secondary as f64 ) );
+        }
+        if halfway_pixels.is_none() && start.elapsed().as_secs_f32() >= settings.seconds * 0.5 {
+            halfway_pixels = Some( renderer.read_pixels() );
+        }
+    }
+    let pixels = renderer.read_pixels();
+    let frames = renderer.frame_count();
+    renderer.delete();
+
+    let ms_per_frame_mean = frame_times.iter().sum::<f64>() / frame_times.len().max( 1 ) as f64;
+    frame_times.sort_by( f64::total_cmp );
+    let ms_per_frame_median = frame_times.get( frame_times.len() / 2 ).copied().unwrap_or( 0.0 );
+    let per_second = |rays: f64| if ms_per_frame_mean > 0.0 { rays / counted_rays.len().max( 1 ) as f64 / ( ms_per_frame_mean / 1000.0 ) } else { 0.0 };
+    let primary_rays_per_second = per_second( counted_rays.iter().map( |rays| rays.0 ).sum() );
+    let secondary_rays_per_second = per_second( counted_rays.iter().map( |rays| rays.1 ).sum() );
+    Ok( SceneResult {
+        scene: scene.name(),
+        frames,
+        samples_per_pixel: frames * rt_settings.rays_per_frag,
+        ms_per_frame_mean,
+        ms_per_frame_median,
+        rays_per_second: primary_rays_per_second + secondary_rays_per_second,
+        primary_rays_per_second,
+        secondary_rays_per_second,
+        halfway_rms: halfway_pixels.map_or( 0.0, |halfway| rms_difference( &halfway, &pixels ) ),
+    } )
+}
+
+/**
+ * BenchReport functions.
+ */
+impl BenchReport {
+    /**
+     * Saves the report, replacing a JSON file, or adding to a CSV file (with its header, if it is new).
+     *
+     * @param path The file.
+     * @param format The format of the file.
+     */
+    pub fn save( &self, path: &str, format: ReportFormat ) -> Result<(), EngineError> {
+        match format {
+            ReportFormat::Json => {
+                let contents = serde_json::to_string_pretty( self )
+                    .map_err( |err| EngineError::parse( "BENCH::REPORT_NOT_SERIALIZED", err.to_string() ) )?;
+                std::fs::write( path, contents ).map_err( |err| EngineError::io( "BENCH::REPORT_NOT_WRITTEN", path, err ) )
+            },
+            ReportFormat::Csv => {
+                // (Names from the driver may have commas or quotes, which are quoted)
+                let quote = |field: &str| format!( "\"{}\"", field.replace( '"', "\"\"" ) );
+                let is_new = std::fs::metadata( path ).map_or( true, |metadata| metadata.len() == 0 );
+                let mut contents = if is_new { format!( "{CSV_HEADER}\n" ) } else { String::new() };
+                for result in &self.scenes {
+                    contents += &format!(
+                        "{},{},{},{},{},{},{},{},{},{:.3},{:.3},{:.0},{:.0},{:.0},{:.6}\n",
+                        self.version, self.commit.as_deref().unwrap_or( "" ), quote( &self.renderer ), quote( &self.gl_version ), self.width, self.height,
+                        result.scene, result.frames, result.samples_per_pixel, result.ms_per_frame_mean, result.ms_per_frame_median,
+                        result.rays_per_second, result.primary_rays_per_second, result.secondary_rays_per_second, result.halfway_rms
+                    );
+                }
+                std::fs::OpenOptions::new().create( true ).append( true ).open( path )
+                    .and_then( |mut file| file.write_all( contents.as_bytes() ) )
+                    .map_err( |err| EngineError::io( "BENCH::REPORT_NOT_WRITTEN", path, err ) )
+            },
+        }
+    }
+}
+
+/**
+ * Runs the benchmarks without a window, and saves the report.
+ *
+ * @param settings The settings of the benchmarks.
+ *
+ * @return An error if there is no OpenGL context, the shaders couldn't be built or the report couldn't be saved.
+ */
+pub fn run( settings: &BenchSettings ) -> Result<(), EngineError> {
+    let ( context, _event_loop ) = headless::create_context( settings.width, settings.height )?;
+    let _context = unsafe { context.make_current()? };
+    let mut report = unsafe {
+        BenchReport {
+            version: env!( "CARGO_PKG_VERSION" ),
+            commit: git_commit(),
+            renderer: gl_string( gl::RENDERER ),
+            gl_version: gl_string( gl::VERSION ),
+            width: settings.width,
+            height: settings.height,
+            seconds: settings.seconds,
+            scenes: Vec::new(),
+        }
+    };
+    info!( "Benchmarking on {} ({}), {} seconds per scene at {}x{}", report.renderer, report.gl_version, settings.seconds, settings.width, settings.height );
+
+    let mut stats = unsafe { RenderStats::new( true ) };
+    for &scene in &settings.scenes {
+        let result = unsafe { bench_scene( scene, settings, &mut stats )? };
+        info!(
+            "{}: {} frames, {:.2} ms/frame (median {:.2}), {:.1} M rays/s, {:.4} RMS between halfway and the end",
+            result.scene, result.frames, result.ms_per_frame_mean, result.ms_per_frame_median, result.rays_per_second / 1e6, result.halfway_rms
+        );
+        report.scenes.push( result );
+    }
+    report.save( &settings.output, settings.format )?;
+    info!( "Saved the report to {}", settings.output );
+    Ok( () )
+}
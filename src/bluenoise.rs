@@ -0,0 +1,158 @@
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use tracing::{ error, info };
+
+use crate::util::pointer_to_array;
+use crate::gltracker::{ self, GlObject };
+
+// Size of the (square) blue noise textures, in pixels
+pub const BLUE_NOISE_SIZE: usize = 64;
+
+// Standard deviation of the gaussian used to measure how clustered points are, as recommended by Ulichney
+const SIGMA: f32 = 1.5;
+
+/**
+ * Generates a tileable blue noise texture using the void-and-cluster method.
+ * https://blog.demofox.org/2019/06/25/generating-blue-noise-textures-with-void-and-cluster/
+ *
+ * @param size The width and height of the texture, in pixels.
+ * @param seed The seed for the initial random pattern.
+ *
+ * @return The rank of each pixel, [0, size*size), row by row.
+ */
+pub fn generate( size: usize, seed: u64 ) -> Vec<u32> {
+    let n = size * size;
+    let mut rng = StdRng::seed_from_u64( seed );
+
+    // Precalculate the (toroidal) energy contributed by a point to each offset from it
+    let kernel: Vec<f32> = ( 0..n ).map( |i| {
+        let ( dx, dy ) = ( ( i % size ) as i32, ( i / size ) as i32 );
+        let ( dx, dy ) = ( dx.min( size as i32 - dx ) as f32, dy.min( size as i32 - dy ) as f32 );
+        ( -( dx * dx + dy * dy ) / ( 2.0 * SIGMA * SIGMA ) ).exp()
+    } ).collect();
+
+    // State of the pattern, and the energy at each pixel
+    let mut pattern = vec![false; n];
+    let mut energy = vec![0.0f32; n];
+    let toggle = | pattern: &mut Vec<bool>, energy: &mut Vec<f32>, p: usize | {
+        pattern[p] = !pattern[p];
+        let sign = if pattern[p] { 1.0 } else { -1.0 };
+        let ( px, py ) = ( p % size, p / size );
+        for ( y, row ) in energy.chunks_mut( size ).enumerate() {
+            let kernel_row = &kernel[( y + size - py ) % size * size..][..size];
+            for ( x, e ) in row.iter_mut().enumerate() {
+                *e += sign * kernel_row[( x + size - px ) % size];
+            }
+        }
+    };
+
+    // Finds the tightest cluster (set pixel with most energy) or largest void (unset pixel with least energy)
+    let tightest_cluster = | pattern: &Vec<bool>, energy: &Vec<f32> | {
+        ( 0..n ).filter( |&p| pattern[p] ).max_by( |&a, &b| energy[a].total_cmp( &energy[b] ) ).unwrap()
+    };
+    let largest_void = | pattern: &Vec<bool>, energy: &Vec<f32> | {
+        ( 0..n ).filter( |&p| !pattern[p] ).min_by( |&a, &b| energy[a].total_cmp( &energy[b] ) ).unwrap()
+    };
+
+    // Create an initial random pattern, then spread it out until it's evenly distributed
+    let initial_count = n / 10;
+    while pattern.iter().filter( |&&set| set ).count() < initial_count {
+        let p = rng.gen_range( 0..n );
+        if !pattern[p] {
+            toggle( &mut pattern, &mut energy, p );
+        }
+    }
+    loop {
+        let cluster = tightest_cluster( &pattern, &energy );
+        toggle( &mut pattern, &mut energy, cluster );
+        let void = largest_void( &pattern, &energy );
+        toggle( &mut pattern, &mut energy, void );
+        if cluster == void { break; }
+    }
+
+    let mut ranks = vec![0u32; n];
+
+    // Phase 1: Rank the initial points by removing the tightest clusters one by one
+    let ( mut phase1_pattern, mut phase1_energy ) = ( pattern.clone(), energy.clone() );
+    for rank in ( 0..initial_count ).rev() {
+        let cluster = tightest_cluster( &phase1_pattern, &phase1_energy );
+        toggle( &mut phase1_pattern, &mut phase1_energy, cluster );
+        ranks[cluster] = rank as u32;
+    }
+
+    // Phase 2/3: Rank the remaining points by filling the largest voids one by one
+    for rank in initial_count..n {
+        let void = largest_void( &pattern, &energy );
+        toggle( &mut pattern, &mut energy, void );
+        ranks[void] = rank as u32;
+    }
+
+    ranks
+}
+
+/**
+ * Generates a set of four independent blue noise textures, one per channel, as an RGBA8 image.
+ *
+ * @param size The width and height of the image, in pixels.
+ *
+ * @return The image.
+ */
+pub fn generate_rgba( size: usize ) -> image::RgbaImage {
+    let n = size * size;
+    let channels: Vec<Vec<u32>> = ( 0..4 ).map( |c| generate( size, c ) ).collect();
+
+    // Interleave channels and quantize ranks to bytes
+    let mut pixels = Vec::<u8>::with_capacity( n * 4 );
+    for p in 0..n {
+        for channel in &channels {
+            pixels.push( ( channel[p] as usize * 256 / n ) as u8 );
+        }
+    }
+    image::RgbaImage::from_raw( size as u32, size as u32, pixels ).unwrap()
+}
+
+/**
+ * Loads the blue noise textures from a file and uploads them as a repeating RGBA8 texture, so they can be tiled across the screen.
+ * The noise is data rather than color, so unlike color textures it isn't decoded from sRGB.
+ * Generating the textures is slow, so if the file doesn't exist they are generated once and saved to it.
+ *
+ * @param path The path of the image file.
+ *
+ * @return The id of the texture.
+ */
+pub unsafe fn create_texture( path: &str ) -> u32 {
+    let noise = match image::open( path ) {
+        Ok( img ) => { img.into_rgba8() },
+        Err( _ ) => {
+            info!( "Generating blue noise textures, saving to \"{path}\"" );
+            let img = generate_rgba( BLUE_NOISE_SIZE );
+            if let Err( e ) = img.save( path ) {
+                error!( "Failed to save blue noise textures: {e}" );
+            }
+            img
+        },
+    };
+
+    // Upload
+    let mut texture: gl::types::GLuint = 0;
+    gl::GenTextures( 1, &mut texture );
+    gltracker::created( GlObject::Texture, &[texture] );
+    gl::BindTexture( gl::TEXTURE_2D, texture );
+    gl::TexImage2D(
+        gl::TEXTURE_2D,
+        0,
+        gl::RGBA8 as i32,
+        noise.width() as i32,
+        noise.height() as i32,
+        0,
+        gl::RGBA,
+        gl::UNSIGNED_BYTE,
+        pointer_to_array( noise.as_raw() )
+    );
+    gl::TexParameteri( gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32 );
+    gl::TexParameteri( gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32 );
+    gl::TexParameteri( gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::REPEAT as i32 );
+    gl::TexParameteri( gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::REPEAT as i32 );
+    gl::BindTexture( gl::TEXTURE_2D, 0 );
+
+    texture
+}
@@ -1,5 +1,10 @@
 extern crate nalgebra_glm as glm;
 
+use serde::{Serialize, Deserialize};
+
+use crate::error::EngineError;
+use crate::raytracing::{ RTCamera, RTProjection, RTStereo };
+
 /**
  * Struct for a camera.
  */
@@ -111,6 +116,18 @@ impl Camera {
         self.calculate_rts()
     }
 
+    /**
+     * Sets the field of view to a physical camera's.
+     *
+     * @param physical The physical camera.
+     *
+     * @return The radius of the physical camera's aperture, which the camera doesn't keep itself.
+     */
+    pub fn apply_physical( &mut self, physical: &PhysicalCamera ) -> f32 {
+        self.fov = physical.fov();
+        physical.aperture_radius()
+    }
+
     // --- Getters
     pub fn pos( &self )     -> glm::Vec3 { self.pos }
     pub fn ang( &self )     -> glm::Vec3 { self.ang }
@@ -122,4 +139,518 @@ impl Camera {
     pub fn front( &self )   -> glm::Vec3 { self.front }
     pub fn up( &self )      -> glm::Vec3 { self.up }
 
-}
\ No newline at end of file
+}
+
+/**
+ * Struct for a physical camera.
+ * Describes a camera the way a photographer would, and derives the camera's fov and aperture from it, along with the exposure the tonemapper adds.
+ * Lengths are in millimeters, while the scene is assumed to be in meters.
+ */
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PhysicalCamera {
+    pub focal_length: f32,
+    pub sensor_size: glm::Vec2,
+    pub f_stop: f32,
+    pub shutter_time: f32, // In seconds
+    pub iso: f32,
+}
+
+impl Default for PhysicalCamera {
+    fn default() -> PhysicalCamera {
+        PhysicalCamera::new()
+    }
+}
+
+/**
+ * PhysicalCamera functions.
+ */
+impl PhysicalCamera {
+    /**
+     * Creates a physical camera resembling a full-frame camera with a 50mm lens at f/8, 1/125s, ISO 100.
+     */
+    pub fn new() -> PhysicalCamera {
+        PhysicalCamera {
+            focal_length: 50.0,
+            sensor_size: glm::vec2( 36.0, 24.0 ),
+            f_stop: 8.0,
+            shutter_time: 1.0 / 125.0,
+            iso: 100.0,
+        }
+    }
+
+    /**
+     * Gets the vertical field of view, in degrees.
+     */
+    pub fn fov( &self ) -> f32 {
+        ( 2.0 * ( self.sensor_size.y / ( 2.0 * self.focal_length ) ).atan() ).to_degrees()
+    }
+
+    /**
+     * Gets the radius of the lens aperture, in meters.
+     */
+    pub fn aperture_radius( &self ) -> f32 {
+        self.focal_length / self.f_stop * 0.5 / 1000.0
+    }
+
+    /**
+     * Gets the exposure value of the settings, normalized to ISO 100.
+     * https://en.wikipedia.org/wiki/Exposure_value
+     */
+    pub fn ev100( &self ) -> f32 {
+        ( self.f_stop * self.f_stop / self.shutter_time ).log2() - ( self.iso / 100.0 ).log2()
+    }
+
+    /**
+     * Gets the exposure the tonemapper adds to the exposure compensation, in stops, see PassContext::camera_exposure.
+     * Uses the saturation-based sensitivity model, which scales the light by 1 / (1.2 * 2^EV100), where 1.2 accounts for the sensor's headroom.
+     * https://seblagarde.files.wordpress.com/2015/07/course_notes_moving_frostbite_to_pbr_v32.pdf
+     */
+    pub fn exposure( &self ) -> f32 {
+        -( 1.2_f32.log2() + self.ev100() )
+    }
+}
+
+/**
+ * Struct for how a camera's rays are projected into the scene, beyond its fov and aperture, see raytracing::RTProjection.
+ * Its distortion and chromatic aberration are those of real lenses, for matching renders to footage, see RTCamera.
+ * It is set in the config's [camera] section or on the command line, and carried into the RTCamera of every frame.
+ */
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Lens {
+    pub projection: RTProjection,
+    pub distortion: glm::Vec2, // The radial distortion's k1 and k2, where positive values are barrel distortion and negative ones pincushion
+    pub chromatic_aberration: f32, // How much larger the red channel is than the green, and the blue smaller, within -1 and 1
+    pub stereo: RTStereo, // If each half of the image is seen by an eye, for VR video
+}
+
+impl Default for Lens {
+    fn default() -> Lens {
+        Lens::new()
+    }
+}
+
+/**
+ * Lens functions.
+ */
+impl Lens {
+    /**
+     * Creates a lens which projects like a pinhole camera, with perspective.
+     */
+    pub fn new() -> Lens {
+        Lens { projection: RTProjection::Perspective, distortion: glm::zero(), chromatic_aberration: 0.0, stereo: RTStereo::Mono }
+    }
+
+    /**
+     * Checks that the lens can be rendered, such as an orthographic projection having a height.
+     *
+     * @return Ok, or which setting can't be rendered and why.
+     */
+    pub fn validate( &self ) -> Result<(), String> {
+        let positive = |name: &str, value: f32| match value > 0.0 && value.is_finite() {
+            true => { Ok( () ) },
+            false => { Err( format!( "The camera's {name} must be above 0, but is {value}" ) ) },
+        };
+        match self.projection {
+            RTProjection::Orthographic { height } => { positive( "orthographic height", height )? },
+            RTProjection::Fisheye { fov } => { positive( "fisheye fov", fov )? },
+            _ => {},
+        }
+        if self.stereo.ipd().is_nan() || self.stereo.ipd() < 0.0 {
+            return Err( format!( "The camera's ipd must not be below 0, but is {}", self.stereo.ipd() ) );
+        }
+        if !( self.distortion.x.is_finite() && self.distortion.y.is_finite() ) {
+            return Err( format!( "The camera's distortion must be finite, but is {}, {}", self.distortion.x, self.distortion.y ) );
+        }
+        // (The red and blue channels are scaled by 1 + and 1 - it, which would turn them inside out past 1)
+        if self.chromatic_aberration.is_nan() || self.chromatic_aberration.abs() >= 1.0 {
+            return Err( format!( "The camera's chromatic aberration must be within -1 and 1, but is {}", self.chromatic_aberration ) );
+        }
+        Ok( () )
+    }
+}
+
+/**
+ * Struct for a single keyframe in a camera path.
+ */
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct CameraKeyframe {
+    pub time: f32,
+    pub position: glm::Vec3,
+    pub target: glm::Vec3,
+    pub fov: f32,
+    pub focus_distance: f32,
+}
+
+/**
+ * Struct for a camera path.
+ * Holds timed keyframes which are interpolated with Catmull-Rom splines, for rendering flythrough animations.
+ * Paths are stored as JSON, e.g. `{ "keyframes": [ { "time": 0.0, "position": [0, 1, -5], "target": [0, 0, 0], "fov": 60.0, "focus_distance": 5.0 } ] }`.
+ */
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CameraPath {
+    keyframes: Vec<CameraKeyframe>,
+}
+
+/**
+ * Evaluates a uniform Catmull-Rom spline segment between p1 and p2.
+ *
+ * @param p0, p1, p2, p3 The control points, where p0 and p3 are the neighbours of the segment.
+ * @param t How far along the segment to evaluate, [0, 1].
+ *
+ * @return The interpolated value.
+ */
+fn catmull_rom<T>( p0: T, p1: T, p2: T, p3: T, t: f32 ) -> T
+where T: Copy + std::ops::Add<Output = T> + std::ops::Sub<Output = T> + std::ops::Mul<f32, Output = T> {
+    let ( t2, t3 ) = ( t * t, t * t * t );
+    (
+        p1 * 2.0
+        + ( p2 - p0 ) * t
+        + ( p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3 ) * t2
+        + ( p1 * 3.0 - p0 - p2 * 3.0 + p3 ) * t3
+    ) * 0.5
+}
+
+/**
+ * CameraPath functions.
+ */
+#[allow(dead_code)]
+impl CameraPath {
+    /**
+     * Creates a new, empty camera path.
+     */
+    pub fn new() -> CameraPath {
+        CameraPath { keyframes: Vec::new() }
+    }
+
+    /**
+     * Adds a keyframe to the path.
+     * Keyframes are kept sorted by time, so they can be added in any order.
+     *
+     * @param keyframe The keyframe.
+     */
+    pub fn add_keyframe( mut self, keyframe: CameraKeyframe ) -> CameraPath {
+        let index = self.keyframes.partition_point( |k| k.time <= keyframe.time );
+        self.keyframes.insert( index, keyframe );
+        self
+    }
+
+    /**
+     * Loads a camera path from a JSON file.
+     * The keyframes are sorted by time, so they can be written in any order.
+     *
+     * @param path The path to the file.
+     * @return The camera path, or an error message if the file could not be read or parsed.
+     */
+    pub fn load( path: &str ) -> Result<CameraPath, EngineError> {
+        let contents = std::fs::read_to_string( path )
+            .map_err( |err| EngineError::io( "CAMERA::PATH_NOT_READ", path, err ) )?;
+        let mut camera_path: CameraPath = serde_json::from_str( &contents )
+            .map_err( |err| EngineError::parse( "CAMERA::INVALID_PATH", format!( "{path}: {err}" ) ) )?;
+
+        camera_path.keyframes.sort_by( |a, b| a.time.total_cmp( &b.time ) );
+        Ok( camera_path )
+    }
+
+    /**
+     * Saves the camera path to a JSON file.
+     *
+     * @param path The path to the file.
+     * @return Ok, or an error message if the file could not be written.
+     */
+    pub fn save( &self, path: &str ) -> Result<(), EngineError> {
+        let contents = serde_json::to_string_pretty( self )
+            .map_err( |err| EngineError::parse( "CAMERA::PATH_NOT_SERIALIZED", err.to_string() ) )?;
+        std::fs::write( path, contents )
+            .map_err( |err| EngineError::io( "CAMERA::PATH_NOT_WRITTEN", path, err ) )
+    }
+
+    /**
+     * Gets the keyframes of the path, sorted by time.
+     */
+    pub fn keyframes( &self ) -> &[CameraKeyframe] { &self.keyframes }
+
+    /**
+     * Gets the time of the first and last keyframe.
+     *
+     * @return The start and end time, or None if the path is empty.
+     */
+    pub fn time_range( &self ) -> Option<(f32, f32)> {
+        Some( ( self.keyframes.first()?.time, self.keyframes.last()?.time ) )
+    }
+
+    /**
+     * Samples the path at a given time, interpolating between the surrounding keyframes.
+     * Times outside of the path are clamped to the first/last keyframe.
+     *
+     * @param t The time.
+     *
+     * @return The interpolated keyframe, or None if the path is empty.
+     */
+    pub fn sample_keyframe( &self, t: f32 ) -> Option<CameraKeyframe> {
+        let ( first, last ) = self.time_range()?;
+        let t = t.clamp( first, last );
+
+        let n = self.keyframes.len();
+        if n == 1 {
+            return Some( self.keyframes[0] );
+        }
+
+        // Find the segment [i, i+1] containing t
+        let i = self.keyframes.partition_point( |k| k.time <= t ).saturating_sub( 1 ).min( n - 2 );
+
+        // The neighbouring keyframes are repeated at the ends of the path
+        let ( k0, k1, k2, k3 ) = (
+            &self.keyframes[ i.saturating_sub( 1 ) ],
+            &self.keyframes[ i ],
+            &self.keyframes[ i + 1 ],
+            &self.keyframes[ ( i + 2 ).min( n - 1 ) ],
+        );
+        let segment_length = k2.time - k1.time;
+        let local_t = if segment_length > 0.0 { ( t - k1.time ) / segment_length } else { 0.0 };
+
+        Some( CameraKeyframe {
+            time: t,
+            position: catmull_rom( k0.position, k1.position, k2.position, k3.position, local_t ),
+            target: catmull_rom( k0.target, k1.target, k2.target, k3.target, local_t ),
+            fov: catmull_rom( k0.fov, k1.fov, k2.fov, k3.fov, local_t ),
+            focus_distance: catmull_rom( k0.focus_distance, k1.focus_distance, k2.focus_distance, k3.focus_distance, local_t ),
+        } )
+    }
+
+    /**
+     * Samples the path at a given time, creating a raytracing camera looking from the interpolated position towards the interpolated target.
+     * (This is on the path rather than RTCamera, as a camera has no keyframes to sample, and it takes the screen size as the camera it creates needs one)
+     *
+     * @param t The time.
+     * @param screen_size The size of the screen, in pixels.
+     *
+     * @return The raytracing camera, or None if the path is empty.
+     */
+    pub fn sample( &self, t: f32, screen_size: glm::Vec2 ) -> Option<RTCamera> {
+        let keyframe = self.sample_keyframe( t )?;
+        let mut camera = RTCamera::look_at( keyframe.position, keyframe.target, glm::vec3( 0.0, 1.0, 0.0 ), keyframe.fov, screen_size );
+        camera.focus_distance = keyframe.focus_distance;
+        Some( camera )
+    }
+
+    /**
+     * Samples the path over a shutter interval, creating a raytracing camera which is motion blurred between the two ends.
+     *
+     * @param t The time the shutter opens.
+     * @param shutter_time How long the shutter stays open.
+     * @param screen_size The size of the screen, in pixels.
+     *
+     * @return The raytracing camera, or None if the path is empty.
+     */
+    pub fn sample_shutter( &self, t: f32, shutter_time: f32, screen_size: glm::Vec2 ) -> Option<RTCamera> {
+        let mut camera = self.sample( t, screen_size )?;
+        camera.shutter_close = Some( self.sample( t + shutter_time, screen_size )?.local_to_world );
+        Some( camera )
+    }
+}
+
+/**
+ * Struct for a saved camera state.
+ */
+#[derive(Clone, Copy)]
+pub struct CameraBookmark {
+    pub position: glm::Vec3,
+    pub angle: glm::Vec3,
+    pub fov: f32,
+    pub focus_distance: f32,
+}
+
+/**
+ * Struct for a collection of named camera bookmarks, persisted to a file.
+ * The file is plain text with one bookmark per line: `name px py pz ax ay az fov focus_distance`.
+ */
+pub struct CameraBookmarks {
+    path: std::path::PathBuf,
+    bookmarks: std::collections::BTreeMap<String, CameraBookmark>,
+}
+
+/**
+ * CameraBookmarks functions.
+ */
+#[allow(dead_code)]
+impl CameraBookmarks {
+    /**
+     * Loads the bookmarks from a file.
+     * If the file does not exist yet, starts with no bookmarks, and creates the file on the first save.
+     *
+     * @param path The path to the bookmarks file.
+     * @return The bookmarks, or an error if the file could not be read or has a line which can't be parsed.
+     */
+    pub fn load( path: &str ) -> Result<CameraBookmarks, EngineError> {
+        let contents = match std::fs::read_to_string( path ) {
+            Ok( contents ) => { contents },
+            Err( err ) if err.kind() == std::io::ErrorKind::NotFound => { String::new() },
+            Err( err ) => { return Err( EngineError::io( "CAMERA::BOOKMARKS_NOT_READ", path, err ) ) },
+        };
+
+        let mut bookmarks = std::collections::BTreeMap::new();
+        for ( number, line ) in contents.lines().enumerate() {
+            let mut parts = line.split_whitespace();
+            let name = match parts.next() { Some( name ) => name.to_string(), None => continue };
+            let values: Vec<f32> = parts.map( |v| v.parse() ).collect::<Result<_, _>>().unwrap_or_default();
+            if values.len() != 8 {
+                return Err( EngineError::parse( "CAMERA::INVALID_BOOKMARKS", format!( "{path}:{}\nThe bookmark '{name}' must have 8 numbers after its name", number + 1 ) ) );
+            }
+
+            bookmarks.insert( name, CameraBookmark {
+                position: glm::vec3( values[0], values[1], values[2] ),
+                angle: glm::vec3( values[3], values[4], values[5] ),
+                fov: values[6],
+                focus_distance: values[7],
+            } );
+        }
+
+        Ok( CameraBookmarks { path: std::path::PathBuf::from( path ), bookmarks } )
+    }
+
+    /**
+     * Writes the bookmarks to the file they were loaded from.
+     *
+     * @return Ok, or an error if the file could not be written.
+     */
+    pub fn save( &self ) -> Result<(), EngineError> {
+        let contents: String = self.bookmarks.iter().map( |( name, b )| format!(
+            "{name} {} {} {} {} {} {} {} {}\n",
+            b.position.x, b.position.y, b.position.z,
+            b.angle.x, b.angle.y, b.angle.z,
+            b.fov, b.focus_distance,
+        ) ).collect();
+        std::fs::write( &self.path, contents )
+            .map_err( |err| EngineError::io( "CAMERA::BOOKMARKS_NOT_WRITTEN", self.path.to_string_lossy(), err ) )
+    }
+
+    /**
+     * Sets a bookmark and writes all bookmarks to file.
+     *
+     * @param name The name of the bookmark, which can't be empty or contain whitespace.
+     * @param bookmark The camera state.
+     *
+     * @return Ok, or an error if the name can't be written to the file, or the file could not be written.
+     */
+    pub fn set( &mut self, name: &str, bookmark: CameraBookmark ) -> Result<(), EngineError> {
+        // (The name is the first word of its line, so it can't be split in two or left out)
+        if name.is_empty() || name.contains( char::is_whitespace ) {
+            return Err( EngineError::parse( "CAMERA::INVALID_BOOKMARK_NAME", format!( "The bookmark name '{name}' is empty or contains whitespace" ) ) );
+        }
+        self.bookmarks.insert( name.to_string(), bookmark );
+        self.save()
+    }
+
+    /**
+     * Gets a bookmark.
+     *
+     * @param name The name of the bookmark.
+     * @return The camera state, or None if there is no bookmark with the name.
+     */
+    pub fn get( &self, name: &str ) -> Option<CameraBookmark> {
+        self.bookmarks.get( name ).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path( name: &str ) -> String {
+        std::env::temp_dir().join( format!( "camera-{}-{name}", std::process::id() ) ).to_string_lossy().into_owned()
+    }
+
+    fn keyframe( time: f32, position: glm::Vec3, fov: f32 ) -> CameraKeyframe {
+        CameraKeyframe { time, position, target: glm::vec3( 0.0, 0.0, 10.0 ), fov, focus_distance: 5.0 }
+    }
+
+    fn test_path() -> CameraPath {
+        // (Added out of order, as they are kept sorted)
+        CameraPath::new()
+            .add_keyframe( keyframe( 2.0, glm::vec3( 4.0, 1.0, 0.0 ), 40.0 ) )
+            .add_keyframe( keyframe( 0.0, glm::vec3( 0.0, 0.0, 0.0 ), 60.0 ) )
+            .add_keyframe( keyframe( 1.0, glm::vec3( 1.0, 2.0, 0.0 ), 50.0 ) )
+    }
+
+    fn assert_at( sampled: CameraKeyframe, expected: &CameraKeyframe ) {
+        assert!( glm::distance( &sampled.position, &expected.position ) < 1e-5, "{:?} instead of {:?}", sampled.position, expected.position );
+        assert!( ( sampled.fov - expected.fov ).abs() < 1e-4 );
+    }
+
+    #[test]
+    fn paths_pass_through_their_keyframes() {
+        let path = test_path();
+        assert_eq!( path.time_range(), Some( ( 0.0, 2.0 ) ) );
+        for keyframe in path.keyframes() {
+            assert_at( path.sample_keyframe( keyframe.time ).unwrap(), keyframe );
+        }
+
+        // (Between keyframes the spline is smooth, rather than on the straight line between them)
+        let halfway = path.sample_keyframe( 1.5 ).unwrap();
+        assert!( halfway.position.x > 1.0 && halfway.position.x < 4.0 && halfway.position.y != 1.5 );
+    }
+
+    #[test]
+    fn paths_clamp_at_both_ends() {
+        let path = test_path();
+        assert_at( path.sample_keyframe( -5.0 ).unwrap(), &path.keyframes()[0] );
+        assert_at( path.sample_keyframe( 10.0 ).unwrap(), &path.keyframes()[2] );
+        assert!( CameraPath::new().sample_keyframe( 0.0 ).is_none() );
+
+        let screen_size = glm::vec2( 64.0, 64.0 );
+        let camera = path.sample_shutter( 10.0, 0.5, screen_size ).unwrap();
+        assert_eq!( camera.shutter_close, Some( camera.local_to_world ) );
+    }
+
+    #[test]
+    fn paths_round_trip_through_json() {
+        let path = test_path();
+        let file = temp_path( "round-trip.path.json" );
+        path.save( &file ).unwrap();
+        let loaded = CameraPath::load( &file );
+        let _ = std::fs::remove_file( &file );
+        let loaded = loaded.unwrap();
+        assert_eq!( loaded.keyframes().len(), path.keyframes().len() );
+        for ( loaded, keyframe ) in loaded.keyframes().iter().zip( path.keyframes() ) {
+            assert_eq!( ( loaded.time, loaded.position, loaded.target, loaded.fov, loaded.focus_distance ), ( keyframe.time, keyframe.position, keyframe.target, keyframe.fov, keyframe.focus_distance ) );
+        }
+    }
+
+    #[test]
+    fn bookmarks_round_trip() {
+        let path = temp_path( "round-trip.bookmarks" );
+        let bookmark = CameraBookmark { position: glm::vec3( 1.0, -2.5, 3.0 ), angle: glm::vec3( 0.1, 0.2, 0.0 ), fov: 45.0, focus_distance: 7.5 };
+        let mut bookmarks = CameraBookmarks::load( &path ).unwrap();
+        bookmarks.set( "1", bookmark ).unwrap();
+
+        let loaded = CameraBookmarks::load( &path ).unwrap().get( "1" );
+        let _ = std::fs::remove_file( &path );
+        let loaded = loaded.unwrap();
+        assert_eq!( ( loaded.position, loaded.angle, loaded.fov, loaded.focus_distance ), ( bookmark.position, bookmark.angle, bookmark.fov, bookmark.focus_distance ) );
+    }
+
+    #[test]
+    fn bookmark_names_with_whitespace_are_refused() {
+        let path = temp_path( "whitespace.bookmarks" );
+        let bookmark = CameraBookmark { position: glm::zero(), angle: glm::zero(), fov: 60.0, focus_distance: 1.0 };
+        let mut bookmarks = CameraBookmarks::load( &path ).unwrap();
+        assert!( matches!( bookmarks.set( "front door", bookmark ), Err( EngineError::Parse { code: "CAMERA::INVALID_BOOKMARK_NAME", .. } ) ) );
+        assert!( matches!( bookmarks.set( "", bookmark ), Err( EngineError::Parse { code: "CAMERA::INVALID_BOOKMARK_NAME", .. } ) ) );
+        assert!( bookmarks.get( "front door" ).is_none() && !std::path::Path::new( &path ).exists() );
+    }
+
+    #[test]
+    fn corrupt_bookmarks_are_reported() {
+        let path = temp_path( "corrupt.bookmarks" );
+        std::fs::write( &path, "1 0 0 0 0 0 0 60 1\n2 0 0 zero 0 0 0 60 1\n" ).unwrap();
+        let result = CameraBookmarks::load( &path );
+        let _ = std::fs::remove_file( &path );
+        assert!( matches!( result, Err( EngineError::Parse { code: "CAMERA::INVALID_BOOKMARKS", .. } ) ) );
+
+        // (Only a file which isn't there yet is taken as no bookmarks, while one which can't be read is an error)
+        assert!( CameraBookmarks::load( &temp_path( "missing.bookmarks" ) ).unwrap().get( "1" ).is_none() );
+        assert!( matches!( CameraBookmarks::load( &std::env::temp_dir().to_string_lossy() ), Err( EngineError::Io { code: "CAMERA::BOOKMARKS_NOT_READ", .. } ) ) );
+    }
+}
@@ -0,0 +1,241 @@
+use std::sync::OnceLock;
+
+use tracing::info;
+
+use crate::glapi::GlApi;
+
+// What the context the engine renders with can do, once it is made current, see Capabilities::detect()
+static CURRENT_CAPABILITIES: OnceLock<Capabilities> = OnceLock::new();
+
+/**
+ * Struct for what the GPU and its driver can do, which is queried once the context is current.
+ * The subsystems which need more than the API guarantees check it, and fall back to what it does guarantee instead of failing on older hardware:
+ * the compute path only uses work group sizes which fit, persistent mapping falls back to orphaning buffers, and renders are kept within the texture size.
+ */
+#[derive(Clone, PartialEq, Debug)]
+pub struct Capabilities {
+    pub api: GlApi,
+    pub version: ( u32, u32 ),           // The major and minor version of the context, such as (4, 6)
+    pub renderer: String,                // The name of the GPU, as the driver gives it
+    pub extensions: Vec<String>,
+    pub max_ssbo_size: u64,              // The largest storage buffer a shader can read, in bytes
+    pub max_texture_size: u32,           // The largest width and height of a 2D texture, in pixels
+    pub max_compute_invocations: u32,    // The most invocations in one compute work group
+    pub max_compute_size: [u32; 3],      // The largest compute work group along each axis
+    pub compute_shaders: bool,
+    pub buffer_storage: bool,            // If buffers can be given immutable storage, which persistent mapping needs
+    pub timer_queries: bool,             // If the GPU's work can be timed, which the profiler and the work group tuner need
+    pub bindless_textures: bool,         // If textures can be used without binding them (ARB_bindless_texture)
+}
+
+/**
+ * Capabilities functions.
+ */
+impl Capabilities {
+    /**
+     * Gets what every context of an API can do, which is assumed until one is made current.
+     *
+     * @param api The API.
+     */
+    pub fn minimum( api: GlApi ) -> Capabilities {
+        let ( version, max_texture_size, max_compute_invocations, max_compute_size ) = match api {
+            GlApi::Desktop  => { ( ( 4, 4 ), 16384, 1024, [1024, 1024, 64] ) },
+            GlApi::Es       => { ( ( 3, 1 ), 2048, 128, [128, 128, 64] ) },
+            GlApi::WebGl2   => { ( ( 2, 0 ), 2048, 0, [0, 0, 0] ) },
+        };
+        Capabilities {
+            api,
+            version,
+            renderer: String::new(),
+            extensions: Vec::new(),
+            // (WebGL2 has neither storage buffers nor compute shaders, so its scenes are read from textures, see webscene)
+            max_ssbo_size: if api == GlApi::WebGl2 { 0 } else { 1 << 27 },
+            max_texture_size,
+            max_compute_invocations,
+            max_compute_size,
+            compute_shaders: api != GlApi::WebGl2,
+            // (ES only has these as extensions, whose functions the gl crate doesn't load)
+            buffer_storage: api == GlApi::Desktop,
+            timer_queries: api == GlApi::Desktop,
+            bindless_textures: false,
+        }
+    }
+
+    /**
+     * Queries what the current context can do, logs it, and remembers it for the rest of the process.
+     * The OpenGL functions must be loaded, and the API detected (see GlApi::detect()).
+     */
+    pub unsafe fn detect() -> &'static Capabilities {
+        CURRENT_CAPABILITIES.get_or_init( || {
+            let api = GlApi::current();
+            let mut capabilities = Capabilities::minimum( api );
+
+            let ( mut major, mut minor ) = ( 0, 0 );
+            gl::GetIntegerv( gl::MAJOR_VERSION, &mut major );
+            gl::GetIntegerv( gl::MINOR_VERSION, &mut minor );
+            capabilities.version = ( major.max( 0 ) as u32, minor.max( 0 ) as u32 );
+            let renderer = gl::GetString( gl::RENDERER );
+            if !renderer.is_null() {
+                capabilities.renderer = std::ffi::CStr::from_ptr( renderer as *const _ ).to_string_lossy().into_owned();
+            }
+            let mut extension_count = 0;
+            gl::GetIntegerv( gl::NUM_EXTENSIONS, &mut extension_count );
+            capabilities.extensions = ( 0..extension_count.max( 0 ) as u32 ).filter_map( |i| {
+                let extension = gl::GetStringi( gl::EXTENSIONS, i );
+                ( !extension.is_null() ).then( || std::ffi::CStr::from_ptr( extension as *const _ ).to_string_lossy().into_owned() )
+            } ).collect();
+
+            // (Limits the driver doesn't report keep the API's minimums)
+            let mut max_ssbo_size: i64 = 0;
+            gl::GetInteger64v( gl::MAX_SHADER_STORAGE_BLOCK_SIZE, &mut max_ssbo_size );
+            if max_ssbo_size > 0 {
+                capabilities.max_ssbo_size = max_ssbo_size as u64;
+            }
+            let mut max_texture_size = 0;
+            gl::GetIntegerv( gl::MAX_TEXTURE_SIZE, &mut max_texture_size );
+            if max_texture_size > 0 {
+                capabilities.max_texture_size = max_texture_size as u32;
+            }
+            let mut max_compute_invocations = 0;
+            gl::GetIntegerv( gl::MAX_COMPUTE_WORK_GROUP_INVOCATIONS, &mut max_compute_invocations );
+            if max_compute_invocations > 0 {
+                capabilities.max_compute_invocations = max_compute_invocations as u32;
+            }
+            for axis in 0..3 {
+                let mut size = 0;
+                gl::GetIntegeri_v( gl::MAX_COMPUTE_WORK_GROUP_SIZE, axis, &mut size );
+                if size > 0 {
+                    capabilities.max_compute_size[axis as usize] = size as u32;
+                }
+            }
+
+            capabilities.compute_shaders = capabilities.at_least( api.version() ) || capabilities.has_extension( "GL_ARB_compute_shader" );
+            capabilities.bindless_textures = capabilities.has_extension( "GL_ARB_bindless_texture" );
+            info!( "{}", capabilities.summary() );
+            capabilities
+        } )
+    }
+
+    /**
+     * Queries what the browser's WebGL2 context can do, logs it, and remembers it for the rest of the process.
+     * The API must have been detected (see GlApi::detect_webgl2()).
+     *
+     * @param gl The context.
+     */
+    #[cfg(target_arch = "wasm32")]
+    pub unsafe fn detect_webgl2( gl: &glow::Context ) -> &'static Capabilities {
+        use glow::HasContext;
+
+        CURRENT_CAPABILITIES.get_or_init( || {
+            let mut capabilities = Capabilities::minimum( GlApi::WebGl2 );
+            capabilities.renderer = gl.get_parameter_string( glow::RENDERER );
+            capabilities.extensions = gl.supported_extensions().iter().cloned().collect();
+            let max_texture_size = gl.get_parameter_i32( glow::MAX_TEXTURE_SIZE );
+            if max_texture_size > 0 {
+                capabilities.max_texture_size = max_texture_size as u32;
+            }
+            capabilities.timer_queries = capabilities.has_extension( "EXT_disjoint_timer_query_webgl2" );
+            info!( "{}", capabilities.summary() );
+            capabilities
+        } )
+    }
+
+    /**
+     * Gets what the context the engine renders with can do, which is the API's minimum until one has been made current.
+     */
+    pub fn current() -> Capabilities {
+        CURRENT_CAPABILITIES.get().cloned().unwrap_or_else( || Capabilities::minimum( GlApi::current() ) )
+    }
+
+    /**
+     * Checks if the context has an extension.
+     *
+     * @param name The name of the extension, such as "GL_ARB_bindless_texture".
+     */
+    pub fn has_extension( &self, name: &str ) -> bool {
+        self.extensions.iter().any( |extension| extension == name )
+    }
+
+    /**
+     * Checks if the context's version is at least a version.
+     *
+     * @param version The major and minor version.
+     */
+    pub fn at_least( &self, version: ( u8, u8 ) ) -> bool {
+        self.version >= ( version.0 as u32, version.1 as u32 )
+    }
+
+    /**
+     * Checks if a compute work group of a size can be dispatched.
+     *
+     * @param x The width of the work group.
+     * @param y The height of the work group.
+     */
+    pub fn fits_work_group( &self, x: u32, y: u32 ) -> bool {
+        self.compute_shaders && x <= self.max_compute_size[0] && y <= self.max_compute_size[1] && x * y <= self.max_compute_invocations
+    }
+
+    /**
+     * Shrinks a size to fit in a texture, keeping its aspect ratio.
+     *
+     * @param size The width and height, in pixels.
+     *
+     * @return The size, or the largest one with its aspect ratio which fits.
+     */
+    pub fn clamp_texture_size( &self, size: ( u32, u32 ) ) -> ( u32, u32 ) {
+        let largest = size.0.max( size.1 );
+        if largest <= self.max_texture_size {
+            return size;
+        }
+        let scale = self.max_texture_size as f64 / largest as f64;
+        (
+            ( ( size.0 as f64 * scale ).floor() as u32 ).clamp( 1, self.max_texture_size ),
+            ( ( size.1 as f64 * scale ).floor() as u32 ).clamp( 1, self.max_texture_size ),
+        )
+    }
+
+    /**
+     * Gets a line describing the capabilities, as logged at startup.
+     */
+    pub fn summary( &self ) -> String {
+        let yes_no = |supported: bool| if supported { "yes" } else { "no" };
+        format!(
+            "{} ({} {}.{}, {} extensions): textures up to {}, storage buffers up to {} MB, compute work groups up to {} ({}x{}x{}), persistent mapping {}, timer queries {}, bindless textures {}",
+            if self.renderer.is_empty() { "Unknown GPU" } else { &self.renderer },
+            self.api.name(), self.version.0, self.version.1, self.extensions.len(),
+            self.max_texture_size, self.max_ssbo_size >> 20,
+            self.max_compute_invocations, self.max_compute_size[0], self.max_compute_size[1], self.max_compute_size[2],
+            yes_no( self.buffer_storage ), yes_no( self.timer_queries ), yes_no( self.bindless_textures ),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_are_shrunk_to_the_largest_texture() {
+        let capabilities = Capabilities::minimum( GlApi::Es );
+        assert_eq!( capabilities.clamp_texture_size( ( 1920, 1080 ) ), ( 1920, 1080 ) );
+        assert_eq!( capabilities.clamp_texture_size( ( 4096, 2048 ) ), ( 2048, 1024 ) );
+        assert_eq!( capabilities.clamp_texture_size( ( 100000, 1 ) ), ( 2048, 1 ) );
+    }
+
+    #[test]
+    fn work_groups_fit_the_compute_limits() {
+        let mut capabilities = Capabilities::minimum( GlApi::Es );
+        assert!( capabilities.fits_work_group( 16, 8 ) );
+        assert!( !capabilities.fits_work_group( 16, 16 ) );
+        capabilities.compute_shaders = false;
+        assert!( !capabilities.fits_work_group( 8, 8 ) );
+    }
+
+    #[test]
+    fn webgl2_has_no_compute_or_storage_buffers() {
+        let capabilities = Capabilities::minimum( GlApi::WebGl2 );
+        assert!( !capabilities.fits_work_group( 1, 1 ) );
+        assert_eq!( capabilities.max_ssbo_size, 0 );
+        assert!( capabilities.summary().contains( "(WebGL 2.0, " ) );
+    }
+}
@@ -0,0 +1,211 @@
+use std::io::{ Read, Write };
+use std::time::{ Duration, Instant };
+use tracing::info;
+
+use crate::error::EngineError;
+use crate::accumulator::Accumulator;
+
+// What checkpoint files start with, which also tells their version apart
+const MAGIC: &[u8; 8] = b"RTCKPT1\n";
+
+/**
+ * Hashes data with 64-bit FNV-1a, which is the same on every machine and build, unlike std's hashers.
+ *
+ * @param parts The data, which is hashed as if the parts were one.
+ *
+ * @return The hash.
+ */
+pub fn hash( parts: &[&[u8]] ) -> u64 {
+    let mut hash = 0xcbf29ce484222325u64;
+    for byte in parts.iter().flat_map( |part| part.iter() ) {
+        hash = ( hash ^ *byte as u64 ).wrapping_mul( 0x100000001b3 );
+    }
+    hash
+}
+
+/**
+ * Struct for the state of an accumulation, saved so that a render can be resumed exactly where it was stopped.
+ * Every random number of a frame only depends on the pixel, the frame's number and the seed, so resuming at the same frame count continues the same render.
+ */
+pub struct Checkpoint {
+    pub hash: u64, // Hash of what the render depends on, which must match for the checkpoint to be resumed from
+    pub width: u32,
+    pub height: u32,
+    pub frame_count: u32,
+    pub pixels: Vec<f32>, // The accumulated image, as RGB with the bottom row first, see Accumulator::read_pixels()
+}
+
+/**
+ * Checkpoint functions.
+ */
+impl Checkpoint {
+    /**
+     * Saves the checkpoint to a file.
+     * It is written next to the file and then moved over it, so a render stopped while saving leaves the previous checkpoint intact.
+     *
+     * @param path The path of the file.
+     */
+    pub fn save( &self, path: &str ) -> Result<(), EngineError> {
+        let temporary_path = format!( "{path}.tmp" );
+        let write = || -> std::io::Result<()> {
+            let mut file = std::io::BufWriter::new( std::fs::File::create( &temporary_path )? );
+            file.write_all( MAGIC )?;
+            file.write_all( &self.hash.to_le_bytes() )?;
+            for value in [self.width, self.height, self.frame_count] {
+                file.write_all( &value.to_le_bytes() )?;
+            }
+            for value in &self.pixels {
+                file.write_all( &value.to_le_bytes() )?;
+            }
+            file.flush()
+        };
+        write()
+            .and_then( |()| std::fs::rename( &temporary_path, path ) )
+            .map_err( |err| EngineError::io( "CHECKPOINT::FILE_NOT_SUCCESSFULLY_WRITTEN", path, err ) )
+    }
+
+    /**
+     * Loads a checkpoint from a file.
+     *
+     * @param path The path of the file.
+     *
+     * @return The checkpoint, or an error if the file couldn't be read or isn't a checkpoint.
+     */
+    pub fn load( path: &str ) -> Result<Checkpoint, EngineError> {
+        let mut bytes = Vec::new();
+        std::fs::File::open( path )
+            .and_then( |mut file| file.read_to_end( &mut bytes ) )
+            .map_err( |err| EngineError::io( "CHECKPOINT::FILE_NOT_SUCCESSFULLY_READ", path, err ) )?;
+
+        let invalid = || EngineError::parse( "CHECKPOINT::INVALID_FILE", path.to_string() );
+        if bytes.len() < 28 || &bytes[0..8] != MAGIC {
+            return Err( invalid() );
+        }
+        let word = |i: usize| u32::from_le_bytes( bytes[i..i + 4].try_into().unwrap() );
+        let ( hash, width, height, frame_count ) = ( u64::from_le_bytes( bytes[8..16].try_into().unwrap() ), word( 16 ), word( 20 ), word( 24 ) );
+        // (A size whose pixel count overflows is as invalid as one which doesn't match the file's length)
+        let pixel_count = ( width as usize ).checked_mul( height as usize ).and_then( |count| count.checked_mul( 3 ) ).ok_or_else( invalid )?;
+        if ( bytes.len() - 28 ) != pixel_count.checked_mul( 4 ).ok_or_else( invalid )? {
+            return Err( invalid() );
+        }
+        let pixels: Vec<f32> = bytes[28..].chunks_exact( 4 ).map( |value| f32::from_le_bytes( value.try_into().unwrap() ) ).collect();
+        Ok( Checkpoint { hash, width, height, frame_count, pixels } )
+    }
+}
+
+/**
+ * Struct for saving checkpoints of a render now and then, and for resuming it from the last one.
+ */
+pub struct Checkpointer {
+    path: String,
+    interval: Duration,
+    last_saved: Instant,
+    resume_from: Option<Checkpoint>,
+}
+
+/**
+ * Checkpointer functions.
+ */
+impl Checkpointer {
+    /**
+     * Starts checkpointing a render, loading its checkpoint if there already is one.
+     * Whether the checkpoint is of the same render is only told by the first update, once the render's settings are known.
+     *
+     * @param path The path of the checkpoint.
+     * @param interval How often a checkpoint is saved.
+     *
+     * @return The checkpointer, or an error if the file there isn't a checkpoint.
+     */
+    pub fn new( path: &str, interval: Duration ) -> Result<Checkpointer, EngineError> {
+        let resume_from = match std::path::Path::new( path ).exists() {
+            true => { Some( Checkpoint::load( path )? ) },
+            false => { None },
+        };
+        Ok( Checkpointer { path: path.to_string(), interval, last_saved: Instant::now(), resume_from } )
+    }
+
+    /**
+     * Tells whether the next update does anything, so the render's hash is only made when it is needed.
+     *
+     * @return Whether there is a checkpoint to resume from or one is due to be saved.
+     */
+    pub fn is_due( &self ) -> bool {
+        self.resume_from.is_some() || self.last_saved.elapsed() >= self.interval
+    }
+
+    /**
+     * Resumes from the checkpoint if it hasn't been yet, and otherwise saves one if it is time to.
+     * Must be called at the end of a frame, as the first frame resets the accumulation when it sees the render's inputs.
+     *
+     * @param accumulator The accumulator of the render.
+     * @param hash Hash of what the frame was rendered with, see Checkpoint.
+     *
+     * @return An error if the checkpoint is of another render, or it couldn't be saved.
+     */
+    pub unsafe fn update( &mut self, accumulator: &mut Accumulator, hash: u64 ) -> Result<(), EngineError> {
+        if let Some( checkpoint ) = self.resume_from.take() {
+            if checkpoint.hash != hash {
+                return Err( EngineError::render( "CHECKPOINT::OTHER_RENDER", format!( "{}\nThe checkpoint is of a render with other settings, so remove it or checkpoint to another file", self.path ) ) );
+            }
+            if ( checkpoint.width, checkpoint.height ) != accumulator.size() {
+                return Err( EngineError::render( "CHECKPOINT::WRONG_SIZE", format!( "{}\n{}x{} instead of {}x{}", self.path, checkpoint.width, checkpoint.height, accumulator.size().0, accumulator.size().1 ) ) );
+            }
+            accumulator.restore( &checkpoint.pixels, checkpoint.frame_count );
+            info!( "Resumed from {} at {} samples", self.path, checkpoint.frame_count );
+            return Ok( () );
+        }
+
+        if self.last_saved.elapsed() >= self.interval {
+            let ( width, height ) = accumulator.size();
+            Checkpoint { hash, width, height, frame_count: accumulator.frame_count(), pixels: accumulator.read_pixels() }.save( &self.path )?;
+            self.last_saved = Instant::now();
+        }
+        Ok( () )
+    }
+
+    /**
+     * Removes the checkpoint, once the render it is of is done.
+     */
+    pub fn finish( self ) {
+        // (There is no checkpoint if the render finished before the first one was due)
+        let _ = std::fs::remove_file( &self.path );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path( name: &str ) -> String {
+        std::env::temp_dir().join( format!( "checkpoint-{}-{name}", std::process::id() ) ).to_string_lossy().into_owned()
+    }
+
+    fn load_bytes( name: &str, bytes: &[u8] ) -> Result<Checkpoint, EngineError> {
+        let path = temp_path( name );
+        std::fs::write( &path, bytes ).unwrap();
+        let checkpoint = Checkpoint::load( &path );
+        let _ = std::fs::remove_file( &path );
+        checkpoint
+    }
+
+    #[test]
+    fn checkpoints_round_trip() {
+        let path = temp_path( "round-trip" );
+        Checkpoint { hash: 42, width: 2, height: 1, frame_count: 7, pixels: vec![0.5; 6] }.save( &path ).unwrap();
+        let checkpoint = Checkpoint::load( &path );
+        let _ = std::fs::remove_file( &path );
+        let checkpoint = checkpoint.unwrap();
+        assert_eq!( ( checkpoint.hash, checkpoint.width, checkpoint.height, checkpoint.frame_count ), ( 42, 2, 1, 7 ) );
+        assert_eq!( checkpoint.pixels, vec![0.5; 6] );
+    }
+
+    #[test]
+    fn sizes_which_overflow_are_invalid() {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice( &0u64.to_le_bytes() );
+        for value in [u32::MAX, u32::MAX, 1] {
+            bytes.extend_from_slice( &value.to_le_bytes() );
+        }
+        assert!( matches!( load_bytes( "overflow", &bytes ), Err( EngineError::Parse { code: "CHECKPOINT::INVALID_FILE", .. } ) ) );
+    }
+}
@@ -0,0 +1,347 @@
+use std::time::{ Duration, Instant, SystemTime };
+
+use glutin::event::VirtualKeyCode;
+use serde::{ Serialize, Deserialize };
+use tracing::{ info, warn };
+
+use crate::assets;
+use crate::camera::{ Lens, PhysicalCamera };
+use crate::denoiser::AtrousSettings;
+use crate::error::EngineError;
+
+// The environment variable which the config file's path can be given with, instead of the default
+pub const CONFIG_VAR: &str = "OPENGL_RAYTRACING_CONFIG";
+
+// The config file which is used unless CONFIG_VAR is set
+pub const DEFAULT_CONFIG_PATH: &str = "engine.toml";
+
+// How often the config file is checked for changes
+const POLL_INTERVAL: Duration = Duration::from_millis( 500 );
+
+/**
+ * Struct for the engine's config, which is read from a TOML file at startup, e.g.
+ * ```toml
+ * [window]
+ * width = 1280
+ * height = 720
+ *
+ * [keys]
+ * forward = "Up"
+ * ```
+ * where anything left out keeps its default.
+ * The file is watched while the window is open, and changes apply straight away except for the scene, the asset root, and vsync with winit, which need a restart.
+ */
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EngineConfig {
+    pub window: WindowConfig,
+    pub render: RenderConfig,
+    pub camera: CameraConfig,
+    pub keys: KeyBindings,
+    pub denoiser: DenoiserConfig,
+}
+
+/**
+ * Struct for the window the engine opens.
+ */
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowConfig {
+    pub width: u32,  // The width of the window, in logical pixels
+    pub height: u32, // The height of the window, in logical pixels
+    pub vsync: bool, // If the frames are shown in step with the display's refresh rate
+}
+
+/**
+ * Struct for what the window renders, and at which size.
+ */
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RenderConfig {
+    pub scale: f32,    // The size the image is rendered at relative to the window, before supersampling, previews and dynamic resolution
+    pub scene: String, // The model which is loaded, see mesh::Model::load_from_file()
+    pub asset_root: String, // The directory the engine's shaders and resources are loaded from, which is the crate's own by default, see assets::set_root()
+}
+
+/**
+ * Struct for how fast the camera is moved with the keys, how it projects the rays, and the physical camera it is seen through, if any.
+ * The projection is given by its name, or as a table with its values, e.g.
+ * ```toml
+ * [camera]
+ * projection = { Orthographic = { height = 10.0 } }
+ * ```
+ * A physical camera is given as a table of its own, e.g.
+ * ```toml
+ * [camera.physical]
+ * focal_length = 35.0
+ * f_stop = 2.8
+ * ```
+ * where anything left out is as in PhysicalCamera::new().
+ */
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraConfig {
+    pub move_speed: f32,     // Units per second
+    pub rotation_speed: f32, // Radians per second
+    pub physical: Option<PhysicalCamera>, // Decides the fov, aperture and exposure, or None to keep them as they are set in the window (removing it puts back those from before it)
+    #[serde(flatten)]
+    pub lens: Lens, // The projection, distortion, chromatic aberration and stereo, whose settings are in [camera] itself
+}
+
+/**
+ * Struct for the keys the camera is moved and turned with, named like winit's VirtualKeyCode (e.g. "W", "Space" or "LShift").
+ */
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyBindings {
+    pub forward: VirtualKeyCode,
+    pub back: VirtualKeyCode,
+    pub left: VirtualKeyCode,
+    pub right: VirtualKeyCode,
+    pub up: VirtualKeyCode,
+    pub down: VirtualKeyCode,
+    pub turn_left: VirtualKeyCode,
+    pub turn_right: VirtualKeyCode,
+    pub turn_up: VirtualKeyCode,
+    pub turn_down: VirtualKeyCode,
+}
+
+/**
+ * Struct for the denoiser's settings in the window, see denoiser::AtrousSettings.
+ */
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DenoiserConfig {
+    pub enabled: bool, // If the denoiser is on from the start, rather than only after N is pressed
+    pub iterations: u32,
+    pub sigma_color: f32,
+    pub sigma_normal: f32,
+    pub sigma_depth: f32,
+    pub temporal: bool, // If the frames are filtered over time first, see AtrousSettings::temporal
+}
+
+impl Default for WindowConfig {
+    fn default() -> WindowConfig {
+        WindowConfig { width: 720, height: 400, vsync: true }
+    }
+}
+
+impl Default for RenderConfig {
+    fn default() -> RenderConfig {
+        RenderConfig { scale: 1.0, scene: "resources/Knight.obj".to_string(), asset_root: assets::default_root() }
+    }
+}
+
+impl Default for CameraConfig {
+    fn default() -> CameraConfig {
+        CameraConfig { move_speed: 5.0, rotation_speed: 3.0, physical: None, lens: Lens::new() }
+    }
+}
+
+impl Default for KeyBindings {
+    fn default() -> KeyBindings {
+        KeyBindings {
+            forward: VirtualKeyCode::W,
+            back: VirtualKeyCode::S,
+            left: VirtualKeyCode::A,
+            right: VirtualKeyCode::D,
+            up: VirtualKeyCode::Space,
+            down: VirtualKeyCode::LShift,
+            turn_left: VirtualKeyCode::Left,
+            turn_right: VirtualKeyCode::Right,
+            turn_up: VirtualKeyCode::Up,
+            turn_down: VirtualKeyCode::Down,
+        }
+    }
+}
+
+impl Default for DenoiserConfig {
+    fn default() -> DenoiserConfig {
+        let interactive = AtrousSettings::interactive();
+        DenoiserConfig {
+            enabled: false,
+            iterations: interactive.iterations,
+            sigma_color: interactive.sigma_color,
+            sigma_normal: interactive.sigma_normal,
+            sigma_depth: interactive.sigma_depth,
+            temporal: interactive.temporal.is_some(),
+        }
+    }
+}
+
+/**
+ * DenoiserConfig functions.
+ */
+impl DenoiserConfig {
+    /**
+     * Gets the settings the denoiser is turned on with.
+     */
+    pub fn settings( &self ) -> AtrousSettings {
+        AtrousSettings {
+            iterations: self.iterations,
+            sigma_color: self.sigma_color,
+            sigma_normal: self.sigma_normal,
+            sigma_depth: self.sigma_depth,
+            // (The temporal filter's blending isn't configurable, as it is tuned to the rest)
+            temporal: if self.temporal { AtrousSettings::interactive().temporal } else { None },
+        }
+    }
+}
+
+/**
+ * EngineConfig functions.
+ */
+impl EngineConfig {
+    /**
+     * Gets the path of the config file, which is CONFIG_VAR if it is set, and DEFAULT_CONFIG_PATH otherwise.
+     */
+    pub fn path() -> String {
+        std::env::var( CONFIG_VAR ).unwrap_or_else( |_| DEFAULT_CONFIG_PATH.to_string() )
+    }
+
+    /**
+     * Loads the config from a TOML file.
+     * If the file does not exist, the defaults are used, so the engine runs without one.
+     *
+     * @param path The path to the file.
+     * @return The config, or an error if the file could not be read or parsed.
+     */
+    pub fn load( path: &str ) -> Result<EngineConfig, EngineError> {
+        let contents = match std::fs::read_to_string( path ) {
+            Ok( contents ) => { contents },
+            Err( err ) if err.kind() == std::io::ErrorKind::NotFound => { return Ok( EngineConfig::default() ) },
+            Err( err ) => { return Err( EngineError::io( "CONFIG::FILE_NOT_READ", path, err ) ) },
+        };
+        let config: EngineConfig = toml::from_str( &contents )
+            .map_err( |err| EngineError::parse( "CONFIG::INVALID_FILE", format!( "{path}: {err}" ) ) )?;
+
+        // (Scales of 0 would make the render targets empty)
+        if !( config.render.scale > 0.0 && config.render.scale.is_finite() ) {
+            return Err( EngineError::parse( "CONFIG::INVALID_FILE", format!( "{path}\nThe render scale must be above 0, but is {}", config.render.scale ) ) );
+        }
+        // (Physical cameras with settings of 0 would have an infinite or NaN aperture and exposure, which the command line refuses as well)
+        if let Some( physical ) = &config.camera.physical {
+            let settings = [
+                ( "focal_length", physical.focal_length ),
+                ( "sensor_size", physical.sensor_size.x.min( physical.sensor_size.y ) ),
+                ( "f_stop", physical.f_stop ),
+                ( "shutter_time", physical.shutter_time ),
+                ( "iso", physical.iso ),
+            ];
+            if let Some( ( name, value ) ) = settings.iter().find( |( _, value )| !( *value > 0.0 && value.is_finite() ) ) {
+                return Err( EngineError::parse( "CONFIG::INVALID_FILE", format!( "{path}\nThe physical camera's {name} must be above 0, but is {value}" ) ) );
+            }
+        }
+        config.camera.lens.validate().map_err( |err| EngineError::parse( "CONFIG::INVALID_FILE", format!( "{path}\n{err}" ) ) )?;
+        Ok( config )
+    }
+}
+
+/**
+ * Struct for noticing when the config file changes, by checking when it was last modified every so often.
+ */
+pub struct ConfigWatcher {
+    path: String,
+    modified: Option<SystemTime>, // When the file was last modified when it was last loaded, or None if it didn't exist
+    last_poll: Instant,
+}
+
+/**
+ * ConfigWatcher functions.
+ */
+impl ConfigWatcher {
+    /**
+     * Starts watching a config file, as it is now.
+     *
+     * @param path The path to the file.
+     */
+    pub fn new( path: &str ) -> ConfigWatcher {
+        ConfigWatcher { path: path.to_string(), modified: Self::modified( path ), last_poll: Instant::now() }
+    }
+
+    /**
+     * Gets when a file was last modified.
+     */
+    fn modified( path: &str ) -> Option<SystemTime> {
+        std::fs::metadata( path ).and_then( |metadata| metadata.modified() ).ok()
+    }
+
+    /**
+     * Checks if the file has changed since it was last loaded, and loads it again if so.
+     * Files which can't be parsed are warned about and skipped, so the config in use is kept until the file is fixed.
+     *
+     * @return The new config, if the file changed and could be loaded.
+     */
+    pub fn poll( &mut self ) -> Option<EngineConfig> {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return None;
+        }
+        self.last_poll = Instant::now();
+
+        let modified = Self::modified( &self.path );
+        if modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+        match EngineConfig::load( &self.path ) {
+            Ok( config ) => { info!( "Reloaded {}", self.path ); Some( config ) },
+            Err( err ) => { warn!( "{err}" ); None },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raytracing::{ RTProjection, RTStereo };
+
+    #[test]
+    fn configs_may_leave_anything_out() {
+        let config: EngineConfig = toml::from_str( "[window]\nwidth = 1280\n\n[keys]\nforward = \"Up\"\n" ).unwrap();
+        assert_eq!( ( config.window.width, config.window.height ), ( 1280, WindowConfig::default().height ) );
+        assert_eq!( config.keys.forward, VirtualKeyCode::Up );
+        assert_eq!( config.keys.back, VirtualKeyCode::S );
+        assert_eq!( config.render, RenderConfig::default() );
+    }
+
+    #[test]
+    fn physical_cameras_may_leave_anything_out() {
+        let config: EngineConfig = toml::from_str( "[camera.physical]\nf_stop = 2.8\n" ).unwrap();
+        assert_eq!( config.camera.physical, Some( PhysicalCamera { f_stop: 2.8, ..PhysicalCamera::new() } ) );
+        assert_eq!( EngineConfig::default().camera.physical, None );
+    }
+
+    #[test]
+    fn physical_cameras_must_have_positive_settings() {
+        let path = std::env::temp_dir().join( format!( "config-{}.toml", std::process::id() ) );
+        std::fs::write( &path, "[camera.physical]\nf_stop = 0.0\n" ).unwrap();
+        let result = EngineConfig::load( &path.to_string_lossy() );
+        let _ = std::fs::remove_file( &path );
+        assert!( matches!( result, Err( EngineError::Parse { code: "CONFIG::INVALID_FILE", .. } ) ) );
+    }
+
+    #[test]
+    fn lenses_are_set_in_the_camera_section() {
+        let config: EngineConfig = toml::from_str( "[camera]\nmove_speed = 2.0\nprojection = { Orthographic = { height = 10.0 } }\n" ).unwrap();
+        assert_eq!( config.camera.lens.projection, RTProjection::Orthographic { height: 10.0 } );
+        assert_eq!( config.camera.move_speed, 2.0 );
+        assert_eq!( EngineConfig::default().camera.lens, Lens::new() );
+
+        let config: EngineConfig = toml::from_str( "[camera]\ndistortion = [0.1, -0.02]\nchromatic_aberration = 0.01\n" ).unwrap();
+        assert_eq!( ( config.camera.lens.distortion, config.camera.lens.chromatic_aberration ), ( glm::vec2( 0.1, -0.02 ), 0.01 ) );
+
+        let config: EngineConfig = toml::from_str( "[camera]\nstereo = { SideBySide = { ipd = 0.064 } }\n" ).unwrap();
+        assert_eq!( config.camera.lens.stereo, RTStereo::SideBySide { ipd: 0.064 } );
+
+        let path = std::env::temp_dir().join( format!( "config-ortho-{}.toml", std::process::id() ) );
+        std::fs::write( &path, "[camera]\nprojection = { Orthographic = { height = 0.0 } }\n" ).unwrap();
+        let result = EngineConfig::load( &path.to_string_lossy() );
+        let _ = std::fs::remove_file( &path );
+        assert!( matches!( result, Err( EngineError::Parse { code: "CONFIG::INVALID_FILE", .. } ) ) );
+    }
+
+    #[test]
+    fn default_denoiser_is_the_interactive_one() {
+        assert!( DenoiserConfig::default().settings() == AtrousSettings::interactive() );
+    }
+}
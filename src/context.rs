@@ -0,0 +1,115 @@
+use glutin::{ ContextCurrentState, NotCurrent, PossiblyCurrent };
+
+use crate::capabilities::Capabilities;
+use crate::error::EngineError;
+use crate::glapi::GlApi;
+use crate::gltracker;
+use crate::window::WindowContext;
+
+/**
+ * Enum for the OpenGL context which is rendered with, which is a window's unless rendering offline.
+ */
+#[allow(dead_code)]
+pub enum RenderContext<T: ContextCurrentState> {
+    Windowed( Box<dyn WindowContext> ),     // Renders to a window, which is shown, made with one of the backends (see window::WindowBackend)
+    Headless( glutin::Context<T> ),         // Renders without a window, see headless::HeadlessSettings
+}
+
+/**
+ * RenderContext functions, for before it is made current.
+ */
+impl RenderContext<NotCurrent> {
+    /**
+     * Makes the context current on this thread, and loads the OpenGL functions from it.
+     * Which API it has (desktop OpenGL or ES) and what it can do are then known, see GlApi::current() and Capabilities::current().
+     *
+     * @return The current context, or an error if it couldn't be made current.
+     */
+    pub unsafe fn make_current( self ) -> Result<RenderContext<PossiblyCurrent>, EngineError> {
+        let context = match self {
+            RenderContext::Windowed( mut context ) => { context.make_current()?; RenderContext::Windowed( context ) },
+            RenderContext::Headless( context ) => {
+                RenderContext::Headless( context.make_current().map_err( |( _, err )| EngineError::gl( "HEADLESS::CONTEXT_NOT_CURRENT", err.to_string() ) )? )
+            },
+        };
+        gl::load_with( | symbol | context.get_proc_address( symbol ) as *const _ );
+        gltracker::context_made_current();
+        GlApi::detect();
+        Capabilities::detect();
+        Ok( context )
+    }
+}
+
+/**
+ * RenderContext functions, for once it is current.
+ */
+impl RenderContext<PossiblyCurrent> {
+    /**
+     * Gets the address of an OpenGL function.
+     */
+    pub fn get_proc_address( &self, symbol: &str ) -> *const core::ffi::c_void {
+        match self {
+            RenderContext::Windowed( context ) => { context.get_proc_address( symbol ) },
+            RenderContext::Headless( context ) => { context.get_proc_address( symbol ) },
+        }
+    }
+
+    /**
+     * Resizes the window's framebuffer, which headless contexts have a fixed size for.
+     *
+     * @param size The new size, in pixels.
+     */
+    pub fn resize( &self, size: glutin::dpi::PhysicalSize<u32> ) {
+        if let RenderContext::Windowed( context ) = self {
+            context.resize( size.width, size.height );
+        }
+    }
+
+    /**
+     * Sets the window's title, which headless contexts have no window for.
+     *
+     * @param title The new title.
+     */
+    pub fn set_title( &self, title: &str ) {
+        if let RenderContext::Windowed( context ) = self {
+            context.set_title( title );
+        }
+    }
+
+    /**
+     * Sets the size of the window, which headless contexts have none of.
+     *
+     * @param width The new width, in logical pixels.
+     * @param height The new height, in logical pixels.
+     */
+    pub fn set_size( &self, width: u32, height: u32 ) {
+        if let RenderContext::Windowed( context ) = self {
+            context.set_size( width, height );
+        }
+    }
+
+    /**
+     * Turns the window's vsync on or off, see WindowContext::set_vsync().
+     *
+     * @param vsync If the frames are shown in step with the display's refresh rate.
+     *
+     * @return If vsync was changed, which it isn't for headless contexts.
+     */
+    pub unsafe fn set_vsync( &self, vsync: bool ) -> bool {
+        match self {
+            RenderContext::Windowed( context ) => { context.set_vsync( vsync ) },
+            RenderContext::Headless( _ ) => { false },
+        }
+    }
+
+    /**
+     * Shows what has been drawn, by swapping the window's buffers.
+     * Headless contexts have nothing to show, so this does nothing for them.
+     */
+    pub fn swap_buffers( &self ) -> Result<(), EngineError> {
+        match self {
+            RenderContext::Windowed( context ) => { context.swap_buffers() },
+            RenderContext::Headless( _ ) => { Ok( () ) },
+        }
+    }
+}
@@ -0,0 +1,471 @@
+use crate::error::EngineError;
+use crate::device::{ self, Device, TextureFilter, TextureFormat };
+use crate::glapi;
+use crate::shader::Shader;
+use crate::util;
+use crate::gltracker::{ self, GlObject };
+
+/**
+ * Struct for the settings of the temporal filtering of SVGF (spatiotemporal variance-guided filtering).
+ * Each frame is blended into the history of the surfaces it shows, which follows them on the screen as the camera moves.
+ */
+#[derive(Clone, Copy, PartialEq)]
+pub struct TemporalSettings {
+    pub color_alpha: f32, // The least the current frame is blended into the history with, where lower is smoother but lags more
+    pub moments_alpha: f32, // The same for the moments of the luminance, which the variance is estimated from
+}
+
+/**
+ * TemporalSettings functions.
+ */
+impl TemporalSettings {
+    /**
+     * Sends the settings to the temporal shader.
+     * The shader must be active.
+     *
+     * @param shader The temporal shader.
+     * @param uniform_name The name of the settings uniform.
+     */
+    pub unsafe fn send_uniform( &self, shader: &Shader, uniform_name: &str ) {
+        gl::Uniform1f( shader.get_uniform_location( format!("{uniform_name}.colorAlpha").as_str() ), self.color_alpha );
+        gl::Uniform1f( shader.get_uniform_location( format!("{uniform_name}.momentsAlpha").as_str() ), self.moments_alpha );
+    }
+}
+
+/**
+ * Struct for the settings of the À-Trous denoiser.
+ * Each iteration blurs the image with a 5x5 kernel whose taps are twice as far apart as the last, so a few iterations cover a wide area.
+ * Taps are weighted down the more they differ from the pixel in color, normal and depth, so the blur stops at edges.
+ */
+#[derive(Clone, Copy, PartialEq)]
+pub struct AtrousSettings {
+    pub iterations: u32, // How many times the image is blurred, where the kernel covers 4 * 2^iterations + 1 pixels
+    pub sigma_color: f32, // How much the colors of taps can differ, which is halved every iteration as the image gets smoother
+                          // With temporal filtering, it is instead scaled by the standard deviation of the pixel's luminance
+    pub sigma_normal: f32, // How much the normals of taps can differ
+    pub sigma_depth: f32, // How much the depths of taps can differ, relative to the pixel's depth
+    pub temporal: Option<TemporalSettings>, // Filters the frames over time first like SVGF, for when the accumulation keeps restarting
+                                            // This needs at least one iteration, as the last one multiplies the albedo back in
+}
+
+/**
+ * AtrousSettings functions.
+ */
+impl AtrousSettings {
+    /**
+     * Gets the settings the denoiser is turned on with in the window, which filter over time as the accumulation restarts whenever the camera moves.
+     */
+    pub fn interactive() -> AtrousSettings {
+        AtrousSettings {
+            iterations: 5,
+            sigma_color: 1.0,
+            sigma_normal: 0.2,
+            sigma_depth: 0.05,
+            temporal: Some( TemporalSettings {
+                color_alpha: 0.2,
+                moments_alpha: 0.2,
+            } ),
+        }
+    }
+
+    /**
+     * Sends the settings to the À-Trous shader.
+     * The shader must be active.
+     *
+     * @param shader The À-Trous shader.
+     * @param uniform_name The name of the settings uniform.
+     */
+    pub unsafe fn send_uniform( &self, shader: &Shader, uniform_name: &str ) {
+        gl::Uniform1f( shader.get_uniform_location( format!("{uniform_name}.sigmaColor").as_str() ), self.sigma_color );
+        gl::Uniform1f( shader.get_uniform_location( format!("{uniform_name}.sigmaNormal").as_str() ), self.sigma_normal );
+        gl::Uniform1f( shader.get_uniform_location( format!("{uniform_name}.sigmaDepth").as_str() ), self.sigma_depth );
+        gl::Uniform1ui( shader.get_uniform_location( format!("{uniform_name}.varianceGuided").as_str() ), self.temporal.is_some() as u32 );
+    }
+}
+
+/**
+ * Struct for the edge-avoiding À-Trous wavelet denoiser.
+ * https://jo.dreggn.org/home/2010_atrous.pdf
+ *
+ * The guides (albedo, normal and depth of the first surface) are rendered by the raytracing shader in its guide pass,
+ * into a framebuffer with one texture for the albedo and one for the normal and depth.
+ * The accumulated image is then filtered over a couple of iterations, which are rendered into two framebuffers in turn.
+ * The filtering is done on the light arriving at the surfaces rather than their color, so that textures aren't blurred.
+ *
+ * With temporal filtering (SVGF), every frame is first blended into a history, which is reprojected with motion vectors.
+ * https://research.nvidia.com/publication/2017-07_spatiotemporal-variance-guided-filtering-real-time-reconstruction-path-traced
+ * The guides then also hold where each surface was on the screen the frame before, and the normal and depth are kept for a frame to check it's the same surface.
+ * The history holds the light (after the first iteration, as in the paper) and its variance, and the moments of its luminance with their age.
+ * Only the camera's motion is reprojected, so moving objects are rejected by the check and start over.
+ */
+pub struct Denoiser {
+    guide_framebuffer: u32,
+    guide_textures: [device::Texture; 3],
+    prev_normal_depth: device::Texture,
+    framebuffers: [u32; 2],
+    textures: [device::Texture; 2],
+    temporal_framebuffer: u32,
+    temporal_textures: [device::Texture; 2],
+    history_textures: [device::Texture; 2],
+    width: u32,
+    height: u32,
+    current: usize,
+    has_guides: bool,
+    has_history: bool,
+    feed_history: bool,
+}
+
+/**
+ * Denoiser functions.
+ */
+impl Denoiser {
+    /**
+     * Creates the denoiser's framebuffers.
+     *
+     * @param width The width of the framebuffers, in pixels.
+     * @param height The height of the framebuffers, in pixels.
+     *
+     * @return The denoiser, or an error if the driver can't render to its framebuffers.
+     */
+    pub unsafe fn new( width: u32, height: u32 ) -> Result<Denoiser, EngineError> {
+        let mut denoiser = Denoiser {
+            guide_framebuffer: 0,
+            guide_textures: [0; 3],
+            prev_normal_depth: 0,
+            framebuffers: [0; 2],
+            textures: [0; 2],
+            temporal_framebuffer: 0,
+            temporal_textures: [0; 2],
+            history_textures: [0; 2],
+            width,
+            height,
+            current: 0,
+            has_guides: false,
+            has_history: false,
+            feed_history: false,
+        };
+        denoiser.create_framebuffers()?;
+        Ok( denoiser )
+    }
+
+    /**
+     * Creates an RGBA32F texture at the denoiser's current size.
+     */
+    unsafe fn create_texture( &self ) -> device::Texture {
+        device::current().create_texture( self.width, self.height, TextureFormat::Rgba32F, TextureFilter::Nearest )
+    }
+
+    /**
+     * Creates the framebuffers and their textures, at the denoiser's current size.
+     *
+     * @return An error if a framebuffer is incomplete, whose objects are still created, so they are deleted along with the rest.
+     */
+    unsafe fn create_framebuffers( &mut self ) -> Result<(), EngineError> {
+        // Guides, with the albedo, the normal and depth, and the motion in the attachments
+        gl::GenFramebuffers( 1, &mut self.guide_framebuffer );
+        gltracker::created( GlObject::Framebuffer, &[self.guide_framebuffer] );
+        gl::BindFramebuffer( gl::FRAMEBUFFER, self.guide_framebuffer );
+        for i in 0..3 {
+            self.guide_textures[i] = self.create_texture();
+            gl::FramebufferTexture2D( gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0 + i as u32, gl::TEXTURE_2D, self.guide_textures[i], 0 );
+        }
+        gl::DrawBuffers( 3, [gl::COLOR_ATTACHMENT0, gl::COLOR_ATTACHMENT1, gl::COLOR_ATTACHMENT2].as_ptr() );
+        let mut complete = util::check_framebuffer( "DENOISER::FRAMEBUFFER_INCOMPLETE" );
+        self.prev_normal_depth = self.create_texture();
+
+        // Temporal filtering, with the light and variance in the first attachment and the moments in the second
+        gl::GenFramebuffers( 1, &mut self.temporal_framebuffer );
+        gltracker::created( GlObject::Framebuffer, &[self.temporal_framebuffer] );
+        gl::BindFramebuffer( gl::FRAMEBUFFER, self.temporal_framebuffer );
+        for i in 0..2 {
+            self.temporal_textures[i] = self.create_texture();
+            self.history_textures[i] = self.create_texture();
+            gl::FramebufferTexture2D( gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0 + i as u32, gl::TEXTURE_2D, self.temporal_textures[i], 0 );
+        }
+        gl::DrawBuffers( 2, [gl::COLOR_ATTACHMENT0, gl::COLOR_ATTACHMENT1].as_ptr() );
+        complete = complete.and( util::check_framebuffer( "DENOISER::FRAMEBUFFER_INCOMPLETE" ) );
+
+        // Iterations
+        gl::GenFramebuffers( 2, self.framebuffers.as_mut_ptr() );
+        gltracker::created( GlObject::Framebuffer, &self.framebuffers );
+        for i in 0..2 {
+            self.textures[i] = self.create_texture();
+            gl::BindFramebuffer( gl::FRAMEBUFFER, self.framebuffers[i] );
+            gl::FramebufferTexture2D( gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D, self.textures[i], 0 );
+            complete = complete.and( util::check_framebuffer( "DENOISER::FRAMEBUFFER_INCOMPLETE" ) );
+        }
+
+        gl::BindFramebuffer( gl::FRAMEBUFFER, 0 );
+        complete
+    }
+
+    /**
+     * Deletes the framebuffers and all their textures.
+     */
+    unsafe fn delete_framebuffers( &mut self ) {
+        gl::DeleteFramebuffers( 1, &self.guide_framebuffer );
+        gltracker::deleted( GlObject::Framebuffer, &[self.guide_framebuffer] );
+        gl::DeleteFramebuffers( 2, self.framebuffers.as_ptr() );
+        gltracker::deleted( GlObject::Framebuffer, &self.framebuffers );
+        gl::DeleteFramebuffers( 1, &self.temporal_framebuffer );
+        gltracker::deleted( GlObject::Framebuffer, &[self.temporal_framebuffer] );
+        let textures = self.guide_textures.iter().chain( [&self.prev_normal_depth] ).chain( &self.textures ).chain( &self.temporal_textures ).chain( &self.history_textures );
+        for texture in textures {
+            device::current().delete_texture( *texture );
+        }
+    }
+
+    /**
+     * Deletes the denoiser's framebuffers and textures, once it is done with.
+     */
+    pub unsafe fn delete( mut self ) {
+        self.delete_framebuffers();
+    }
+
+    /**
+     * Copies the whole of one of the denoiser's textures into another.
+     */
+    unsafe fn copy_texture( &self, from: u32, to: u32 ) {
+        glapi::copy_texture( from, to, self.width, self.height );
+    }
+
+    /**
+     * Resizes the framebuffers, which invalidates the guides and the history.
+     *
+     * @param width The new width, in pixels.
+     * @param height The new height, in pixels.
+     *
+     * @return An error if the driver can't render to the framebuffers at the new size.
+     */
+    pub unsafe fn resize( &mut self, width: u32, height: u32 ) -> Result<(), EngineError> {
+        self.delete_framebuffers();
+        self.width = width;
+        self.height = height;
+        self.invalidate_guides();
+        self.create_framebuffers()
+    }
+
+    /**
+     * Marks the guides as out of date, so they are rendered again before they are used.
+     * Also forgets the history, which starts over from the next frame.
+     */
+    pub fn invalidate_guides( &mut self ) {
+        self.has_guides = false;
+        self.has_history = false;
+    }
+
+    /**
+     * Checks if the guides have been rendered since they were last invalidated.
+     */
+    pub fn has_guides( &self ) -> bool {
+        self.has_guides
+    }
+
+    /**
+     * Prepares the raytracing shader for rendering the guides, and binds the guide framebuffer.
+     * The normal and depth of the guides being replaced are kept, for checking the reprojection of the history.
+     * Blending is turned off during the pass, as the depth is stored in the alpha channel.
+     * The shader must be active.
+     *
+     * @param shader The raytracing shader.
+     */
+    pub unsafe fn begin_guide_pass( &self, shader: &Shader ) {
+        if self.has_guides {
+            self.copy_texture( self.guide_textures[1], self.prev_normal_depth );
+        }
+        gl::BindFramebuffer( gl::FRAMEBUFFER, self.guide_framebuffer );
+        gl::Viewport( 0, 0, self.width as i32, self.height as i32 );
+        // (The compute path stores the guides as images instead, in place of the image and after the AOVs)
+        for ( unit, texture ) in [0, 6, 7].into_iter().zip( self.guide_textures ) {
+            gl::BindImageTexture( unit, texture, 0, gl::FALSE, 0, gl::WRITE_ONLY, gl::RGBA32F );
+        }
+        gl::Disable( gl::BLEND );
+        gl::Uniform1ui( shader.get_uniform_location( "guidePass" ), 1 );
+    }
+
+    /**
+     * Finishes rendering the guides.
+     * Unbinds the framebuffer, so following draws go to the screen.
+     *
+     * @param shader The raytracing shader.
+     */
+    pub unsafe fn end_guide_pass( &mut self, shader: &Shader ) {
+        self.has_guides = true;
+        gl::Uniform1ui( shader.get_uniform_location( "guidePass" ), 0 );
+        gl::Enable( gl::BLEND );
+        gl::BindFramebuffer( gl::FRAMEBUFFER, 0 );
+    }
+
+    /**
+     * Prepares the temporal shader for blending an image into the history, and binds the framebuffer to write to.
+     * The guides must have been rendered this frame.
+     * Blending is turned off during the pass, as the variance is stored in the alpha channel.
+     * The shader must be active.
+     *
+     * @param shader The temporal shader.
+     * @param image The texture holding the current image.
+     * @param settings The settings of the temporal filtering.
+     */
+    pub unsafe fn begin_temporal_pass( &self, shader: &Shader, image: u32, settings: &TemporalSettings ) {
+        gl::BindFramebuffer( gl::FRAMEBUFFER, self.temporal_framebuffer );
+        gl::Viewport( 0, 0, self.width as i32, self.height as i32 );
+        gl::Disable( gl::BLEND );
+
+        let textures = [
+            ( image, "image" ),
+            ( self.guide_textures[0], "guideAlbedo" ),
+            ( self.guide_textures[1], "guideNormalDepth" ),
+            ( self.guide_textures[2], "guideMotion" ),
+            ( self.prev_normal_depth, "prevNormalDepth" ),
+            ( self.history_textures[0], "historyLight" ),
+            ( self.history_textures[1], "historyMoments" ),
+        ];
+        for ( unit, ( texture, name ) ) in textures.into_iter().enumerate() {
+            gl::ActiveTexture( gl::TEXTURE0 + unit as u32 );
+            gl::BindTexture( gl::TEXTURE_2D, texture );
+            gl::Uniform1i( shader.get_uniform_location( name ), unit as i32 );
+        }
+        settings.send_uniform( shader, "temporal" );
+        gl::Uniform1ui( shader.get_uniform_location( "historyValid" ), self.has_history as u32 );
+    }
+
+    /**
+     * Finishes blending into the history, replacing it with the result.
+     * The light is then replaced again by the result of the first iteration, if there is more than one.
+     * Unbinds the framebuffer, so following draws go to the screen.
+     */
+    pub unsafe fn end_temporal_pass( &mut self ) {
+        gl::Enable( gl::BLEND );
+        gl::BindFramebuffer( gl::FRAMEBUFFER, 0 );
+        self.copy_texture( self.temporal_textures[0], self.history_textures[0] );
+        self.copy_texture( self.temporal_textures[1], self.history_textures[1] );
+        self.has_history = true;
+    }
+
+    /**
+     * Gets the texture holding the image blended into the history, with the variance in the alpha channel.
+     */
+    pub fn temporal_texture( &self ) -> u32 {
+        self.temporal_textures[0]
+    }
+
+    /**
+     * Prepares the À-Trous shader for rendering an iteration, and binds the framebuffer to write to.
+     * The first iteration reads the given image, and the others read the result of the iteration before.
+     * With temporal filtering, the image is the one from temporal_texture(), and the first iteration's result becomes the history.
+     * Blending is turned off during the iterations, as the variance is stored in the alpha channel.
+     * The shader must be active.
+     *
+     * @param shader The À-Trous shader.
+     * @param image The texture holding the image to denoise.
+     * @param settings The settings of the denoiser.
+     * @param iteration The iteration, counting from 0.
+     */
+    pub unsafe fn begin_iteration( &mut self, shader: &Shader, image: u32, settings: &AtrousSettings, iteration: u32 ) {
+        let input = if iteration == 0 { image } else { self.textures[self.current] };
+        let write = if iteration == 0 { 0 } else { 1 - self.current };
+
+        gl::BindFramebuffer( gl::FRAMEBUFFER, self.framebuffers[write] );
+        gl::Viewport( 0, 0, self.width as i32, self.height as i32 );
+        gl::Disable( gl::BLEND );
+
+        for ( unit, ( texture, name ) ) in [( input, "image" ), ( self.guide_textures[0], "guideAlbedo" ), ( self.guide_textures[1], "guideNormalDepth" )].into_iter().enumerate() {
+            gl::ActiveTexture( gl::TEXTURE0 + unit as u32 );
+            gl::BindTexture( gl::TEXTURE_2D, texture );
+            gl::Uniform1i( shader.get_uniform_location( name ), unit as i32 );
+        }
+        settings.send_uniform( shader, "denoise" );
+        gl::Uniform1i( shader.get_uniform_location( "stepSize" ), 1 << iteration );
+        gl::Uniform1ui( shader.get_uniform_location( "iteration" ), iteration );
+        gl::Uniform1ui( shader.get_uniform_location( "lastIteration" ), ( iteration + 1 == settings.iterations ) as u32 );
+
+        self.current = write;
+        // (The last iteration multiplies the albedo back in, so with only one the history keeps the temporal pass's result instead)
+        self.feed_history = iteration == 0 && settings.iterations > 1 && settings.temporal.is_some();
+    }
+
+    /**
+     * Finishes rendering an iteration.
+     * Unbinds the framebuffer, so following draws go to the screen.
+     */
+    pub unsafe fn end_iteration( &self ) {
+        gl::Enable( gl::BLEND );
+        gl::BindFramebuffer( gl::FRAMEBUFFER, 0 );
+        if self.feed_history {
+            self.copy_texture( self.textures[self.current], self.history_textures[0] );
+        }
+    }
+
+    /**
+     * Gets the texture of the guides holding the normal of each pixel's first surface, and the distance to it in the alpha channel.
+     * The distance is negative if nothing was hit.
+     */
+    pub fn normal_depth_texture( &self ) -> u32 {
+        self.guide_textures[1]
+    }
+
+    /**
+     * Gets the texture of the guides holding where each pixel's surface was on the screen the frame before.
+     * The uv coordinates are in the red and green channels, and blue is 1 if they are known.
+     */
+    pub fn motion_texture( &self ) -> u32 {
+        self.guide_textures[2]
+    }
+
+    /**
+     * Gets the texture holding the result of the last iteration.
+     */
+    pub fn texture( &self ) -> u32 {
+        self.textures[self.current]
+    }
+
+    /**
+     * Gets the texture holding the albedo guide, which Open Image Denoise is given along with the image.
+     */
+    pub fn albedo_texture( &self ) -> u32 {
+        self.guide_textures[0]
+    }
+
+    /**
+     * Denoises an image on the CPU with Intel Open Image Denoise, which is slow but much better than the À-Trous filter.
+     * The image and the guides (see albedo_texture() and normal_depth_texture()) are read back beforehand, such as with a Readback, so reading them doesn't stall.
+     * The result is also put in the texture returned by texture(), until the next iteration is rendered.
+     *
+     * @param color The image to denoise as RGB, with the bottom row first, at the denoiser's size.
+     * @param albedo The albedo guide, the same way.
+     * @param normal The normal guide, the same way.
+     *
+     * @return The denoised pixels as RGB, with the bottom row first.
+     */
+    #[cfg(feature = "oidn")]
+    pub unsafe fn denoise_oidn( &mut self, color: &[f32], albedo: &[f32], normal: &[f32] ) -> Result<Vec<f32>, EngineError> {
+        if !self.has_guides {
+            return Err( EngineError::render( "DENOISER::NO_GUIDES", "The guides must be rendered before denoising" ) );
+        }
+        // (The denoiser may have been resized since the pixels were read)
+        let size = self.width as usize * self.height as usize * 3;
+        if color.len() != size || albedo.len() != size || normal.len() != size {
+            return Err( EngineError::render( "DENOISER::SIZE_MISMATCH", "The image was read back at another size than the denoiser's" ) );
+        }
+        let mut output = vec![0.0f32; color.len()];
+
+        let device = oidn::Device::cpu().map_err( |err| EngineError::render( "DENOISER::OIDN_DEVICE", err.to_string() ) )?;
+        oidn::RayTracing::try_new( &device )
+            .map_err( |err| EngineError::render( "DENOISER::OIDN_FILTER", err.to_string() ) )?
+            .hdr( true )
+            .srgb( false )
+            .image_dimensions( self.width as usize, self.height as usize )
+            .albedo_normal( albedo, normal )
+            .filter( color, &mut output )
+            .map_err( |err| EngineError::render( "DENOISER::OIDN_FILTER", err.to_string() ) )?;
+
+        // Show the result in place of the iterations
+        self.current = 0;
+        gl::BindTexture( gl::TEXTURE_2D, self.textures[self.current] );
+        gl::PixelStorei( gl::UNPACK_ALIGNMENT, 1 );
+        gl::TexSubImage2D( gl::TEXTURE_2D, 0, 0, 0, self.width as i32, self.height as i32, gl::RGB, gl::FLOAT, output.as_ptr() as *const _ );
+        gl::BindTexture( gl::TEXTURE_2D, 0 );
+
+        Ok( output )
+    }
+}
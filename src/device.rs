@@ -0,0 +1,399 @@
+use std::ptr;
+
+use glow::HasContext;
+use tracing::warn;
+
+use crate::error::EngineError;
+use crate::glapi::GlApi;
+use crate::shader::{ self, Shader, ShaderBuilder, ShaderType };
+use crate::gltracker::{ self, GlObject };
+use crate::util::bytes_of_array;
+
+/**
+ * Enum for what a buffer is used for, which the backend may place it by.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BufferUsage {
+    Storage,    // Read and written by the shaders
+    Readback,   // Written by the shaders and read back by the CPU
+}
+
+/**
+ * Enum for the formats of the textures which the renderer creates.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TextureFormat {
+    Rgba32F,    // 16 bytes per pixel
+    Rgba16F,    // 8 bytes per pixel
+    Rgba8,      // 4 bytes per pixel, for images which are only displayed
+}
+
+/**
+ * TextureFormat functions.
+ */
+impl TextureFormat {
+    /**
+     * Gets the format's OpenGL internal format, for the calls which still take it directly (such as binding images).
+     */
+    pub fn gl_internal_format( &self ) -> gl::types::GLenum {
+        match self {
+            TextureFormat::Rgba32F  => { gl::RGBA32F },
+            TextureFormat::Rgba16F  => { gl::RGBA16F },
+            TextureFormat::Rgba8    => { gl::RGBA8 },
+        }
+    }
+}
+
+/**
+ * Enum for how a texture is filtered when it is sampled.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TextureFilter {
+    Nearest,    // For textures which are read pixel for pixel
+    Linear,     // For textures which are sampled between their pixels
+}
+
+/**
+ * Trait for the graphics API which the renderer creates its buffers, textures and pipelines with.
+ * The renderer's passes go through it rather than calling the API, so another backend (such as wgpu or Vulkan)
+ * can be added by implementing it, and switching Backend.
+ *
+ * The handles are plain values which the backend looks its resources up by, so buffers keep their handle when they are reallocated.
+ * Resources are bound to the slots the shaders declare (binding=N), which every backend has.
+ */
+#[allow(dead_code)]
+pub trait Device {
+    type Buffer: Copy;
+    type Texture: Copy;
+    type Pipeline;
+
+    /**
+     * Creates a buffer, without any storage until it is allocated.
+     */
+    unsafe fn create_buffer( &self ) -> Self::Buffer;
+
+    /**
+     * Gives a buffer new storage, replacing its contents.
+     *
+     * @param buffer The buffer.
+     * @param size The size of the storage, in bytes.
+     * @param data The contents of the storage, or None to leave them undefined.
+     * @param usage What the buffer is used for.
+     */
+    unsafe fn allocate_buffer( &self, buffer: Self::Buffer, size: usize, data: Option<&[u8]>, usage: BufferUsage );
+
+    /**
+     * Writes into a buffer's storage.
+     *
+     * @param buffer The buffer.
+     * @param offset Where to write, in bytes.
+     * @param data The bytes to write, which must fit in the storage.
+     */
+    unsafe fn write_buffer( &self, buffer: Self::Buffer, offset: usize, data: &[u8] );
+
+    /**
+     * Binds a buffer to a storage buffer binding of the shaders.
+     *
+     * @param buffer The buffer.
+     * @param binding The binding.
+     */
+    unsafe fn bind_storage_buffer( &self, buffer: Self::Buffer, binding: u32 );
+
+    /**
+     * Deletes a buffer.
+     */
+    unsafe fn delete_buffer( &self, buffer: Self::Buffer );
+
+    /**
+     * Creates a 2D texture, clamped at its edges.
+     *
+     * @param width The width, in pixels.
+     * @param height The height, in pixels.
+     * @param format The format of its pixels.
+     * @param filter How it is filtered.
+     */
+    unsafe fn create_texture( &self, width: u32, height: u32, format: TextureFormat, filter: TextureFilter ) -> Self::Texture;
+
+    /**
+     * Deletes a texture.
+     */
+    unsafe fn delete_texture( &self, texture: Self::Texture );
+
+    /**
+     * Creates a pipeline from shader files.
+     *
+     * @param stages The path and type of each shader.
+     * @param defines The defines which the shaders are compiled with.
+     *
+     * @return The pipeline, or an error if a shader couldn't be read, compiled or linked.
+     */
+    unsafe fn create_pipeline( &self, stages: &[( &str, ShaderType )], defines: &[( &str, u32 )] ) -> Result<Self::Pipeline, EngineError>;
+
+    /**
+     * Dispatches the active compute pipeline.
+     *
+     * @param groups How many work groups to dispatch along each axis.
+     */
+    unsafe fn dispatch( &self, groups: [u32; 3] );
+}
+
+/**
+ * Struct for the desktop OpenGL (and ES) backend, which the engine renders with. WebGL2 has GlowDevice instead.
+ * Its handles are the GL names of the objects, so the parts of the renderer which aren't behind Device yet (framebuffers, uniforms) can still use them.
+ */
+pub struct GlDevice;
+
+// The backend the renderer is built with
+pub type Backend = GlDevice;
+pub type Buffer = <Backend as Device>::Buffer;
+pub type Texture = <Backend as Device>::Texture;
+
+/**
+ * Gets the device which the renderer's resources are created with.
+ */
+pub fn current() -> &'static Backend {
+    &GlDevice
+}
+
+/**
+ * Device functions for OpenGL.
+ */
+impl Device for GlDevice {
+    type Buffer = u32;
+    type Texture = u32;
+    type Pipeline = Shader;
+
+    unsafe fn create_buffer( &self ) -> u32 {
+        let mut buffer = 0;
+        gl::GenBuffers( 1, &mut buffer );
+        gltracker::created( GlObject::Buffer, &[buffer] );
+        buffer
+    }
+
+    unsafe fn allocate_buffer( &self, buffer: u32, size: usize, data: Option<&[u8]>, usage: BufferUsage ) {
+        let usage = match usage {
+            BufferUsage::Storage    => { gl::DYNAMIC_COPY },
+            BufferUsage::Readback   => { gl::DYNAMIC_READ },
+        };
+        gl::BindBuffer( gl::SHADER_STORAGE_BUFFER, buffer );
+        gl::BufferData( gl::SHADER_STORAGE_BUFFER, size as isize, data.map_or( ptr::null(), |data| data.as_ptr() as *const _ ), usage );
+        gl::BindBuffer( gl::SHADER_STORAGE_BUFFER, 0 );
+    }
+
+    unsafe fn write_buffer( &self, buffer: u32, offset: usize, data: &[u8] ) {
+        gl::BindBuffer( gl::SHADER_STORAGE_BUFFER, buffer );
+        gl::BufferSubData( gl::SHADER_STORAGE_BUFFER, offset as isize, data.len() as isize, data.as_ptr() as *const _ );
+        gl::BindBuffer( gl::SHADER_STORAGE_BUFFER, 0 );
+    }
+
+    unsafe fn bind_storage_buffer( &self, buffer: u32, binding: u32 ) {
+        gl::BindBufferBase( gl::SHADER_STORAGE_BUFFER, binding, buffer );
+    }
+
+    unsafe fn delete_buffer( &self, buffer: u32 ) {
+        gl::DeleteBuffers( 1, &buffer );
+        gltracker::deleted( GlObject::Buffer, &[buffer] );
+    }
+
+    unsafe fn create_texture( &self, width: u32, height: u32, format: TextureFormat, filter: TextureFilter ) -> u32 {
+        let filter = match filter {
+            TextureFilter::Nearest  => { gl::NEAREST },
+            TextureFilter::Linear   => { gl::LINEAR },
+        };
+        // (ES only takes the types which match the format, even without any data)
+        let data_type = match format {
+            TextureFormat::Rgba8    => { gl::UNSIGNED_BYTE },
+            _                       => { gl::FLOAT },
+        };
+        let mut texture = 0;
+        gl::GenTextures( 1, &mut texture );
+        gltracker::created( GlObject::Texture, &[texture] );
+        gl::BindTexture( gl::TEXTURE_2D, texture );
+        gl::TexImage2D(
+            gl::TEXTURE_2D,
+            0,
+            format.gl_internal_format() as i32,
+            width as i32,
+            height as i32,
+            0,
+            gl::RGBA,
+            data_type,
+            ptr::null()
+        );
+        gl::TexParameteri( gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, filter as i32 );
+        gl::TexParameteri( gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, filter as i32 );
+        gl::TexParameteri( gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32 );
+        gl::TexParameteri( gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32 );
+        gl::BindTexture( gl::TEXTURE_2D, 0 );
+        texture
+    }
+
+    unsafe fn delete_texture( &self, texture: u32 ) {
+        gl::DeleteTextures( 1, &texture );
+        gltracker::deleted( GlObject::Texture, &[texture] );
+    }
+
+    unsafe fn create_pipeline( &self, stages: &[( &str, ShaderType )], defines: &[( &str, u32 )] ) -> Result<Shader, EngineError> {
+        stages.iter().try_fold( ShaderBuilder::new().define( defines ), |builder, ( path, shader_type )| builder.attach_shader_as( path, *shader_type ) )?.link()
+    }
+
+    unsafe fn dispatch( &self, groups: [u32; 3] ) {
+        gl::DispatchCompute( groups[0], groups[1], groups[2] );
+    }
+}
+
+// The shaders which GlowDevice can build, which are built into the engine as a browser has no files to read them from
+const GLOW_SHADERS: &[( &str, &str )] = &[
+    ( "shaders/webgl.vert", include_str!( "../shaders/webgl.vert" ) ),
+    ( "shaders/webgl.frag", include_str!( "../shaders/webgl.frag" ) ),
+];
+
+/**
+ * Struct for the WebGL2 backend, which goes through glow so the same code runs in a browser and (for the tests) on a desktop context.
+ * WebGL2 has no storage buffers or compute shaders, so buffers can only be filled, and the renderer built on it reads its scene from textures instead
+ * (see webscene and web::WebRenderer).
+ * Its shaders are compiled as GLSL ES 3.00 whatever the context is, and its objects aren't tracked by gltracker, as glow's handles aren't GL names in a browser.
+ */
+pub struct GlowDevice {
+    pub gl: glow::Context,
+}
+
+/**
+ * GlowDevice functions.
+ */
+impl GlowDevice {
+    /**
+     * Wraps a glow context, which must be current whenever the device is used.
+     *
+     * @param gl The context.
+     */
+    pub fn new( gl: glow::Context ) -> GlowDevice {
+        GlowDevice { gl }
+    }
+
+    /**
+     * Fills a float texture with pixels.
+     *
+     * @param texture The texture.
+     * @param width The width of the texture, in pixels.
+     * @param height The height of the texture, in pixels.
+     * @param pixels The RGBA pixels, row by row from the bottom, which must fill the texture.
+     */
+    pub unsafe fn write_texture( &self, texture: glow::Texture, width: u32, height: u32, pixels: &[f32] ) {
+        self.gl.bind_texture( glow::TEXTURE_2D, Some( texture ) );
+        self.gl.tex_sub_image_2d( glow::TEXTURE_2D, 0, 0, 0, width as i32, height as i32, glow::RGBA, glow::FLOAT, glow::PixelUnpackData::Slice( bytes_of_array( pixels ) ) );
+        self.gl.bind_texture( glow::TEXTURE_2D, None );
+    }
+}
+
+/**
+ * Device functions for WebGL2.
+ */
+impl Device for GlowDevice {
+    type Buffer = glow::Buffer;
+    type Texture = glow::Texture;
+    type Pipeline = glow::Program;
+
+    // (glow only fails to create objects once the context is lost, after which nothing can be rendered anyway)
+    unsafe fn create_buffer( &self ) -> glow::Buffer {
+        self.gl.create_buffer().expect( "The WebGL2 context couldn't create a buffer" )
+    }
+
+    unsafe fn allocate_buffer( &self, buffer: glow::Buffer, size: usize, data: Option<&[u8]>, usage: BufferUsage ) {
+        let usage = match usage {
+            BufferUsage::Storage    => { glow::DYNAMIC_COPY },
+            BufferUsage::Readback   => { glow::DYNAMIC_READ },
+        };
+        self.gl.bind_buffer( glow::COPY_WRITE_BUFFER, Some( buffer ) );
+        match data {
+            Some( data ) => { self.gl.buffer_data_u8_slice( glow::COPY_WRITE_BUFFER, data, usage ) },
+            None => { self.gl.buffer_data_size( glow::COPY_WRITE_BUFFER, size as i32, usage ) },
+        }
+        self.gl.bind_buffer( glow::COPY_WRITE_BUFFER, None );
+    }
+
+    unsafe fn write_buffer( &self, buffer: glow::Buffer, offset: usize, data: &[u8] ) {
+        self.gl.bind_buffer( glow::COPY_WRITE_BUFFER, Some( buffer ) );
+        self.gl.buffer_sub_data_u8_slice( glow::COPY_WRITE_BUFFER, offset as i32, data );
+        self.gl.bind_buffer( glow::COPY_WRITE_BUFFER, None );
+    }
+
+    unsafe fn bind_storage_buffer( &self, _buffer: glow::Buffer, binding: u32 ) {
+        warn!( binding, "WebGL2 has no storage buffers, so the buffer isn't bound" );
+    }
+
+    unsafe fn delete_buffer( &self, buffer: glow::Buffer ) {
+        self.gl.delete_buffer( buffer );
+    }
+
+    unsafe fn create_texture( &self, width: u32, height: u32, format: TextureFormat, filter: TextureFilter ) -> glow::Texture {
+        let filter = match filter {
+            TextureFilter::Nearest  => { glow::NEAREST },
+            TextureFilter::Linear   => { glow::LINEAR },
+        };
+        // (Like ES, WebGL2 only takes the types which match the format)
+        let data_type = match format {
+            TextureFormat::Rgba8    => { glow::UNSIGNED_BYTE },
+            TextureFormat::Rgba16F  => { glow::HALF_FLOAT },
+            TextureFormat::Rgba32F  => { glow::FLOAT },
+        };
+        let texture = self.gl.create_texture().expect( "The WebGL2 context couldn't create a texture" );
+        self.gl.bind_texture( glow::TEXTURE_2D, Some( texture ) );
+        self.gl.tex_image_2d( glow::TEXTURE_2D, 0, format.gl_internal_format() as i32, width as i32, height as i32, 0, glow::RGBA, data_type, None );
+        self.gl.tex_parameter_i32( glow::TEXTURE_2D, glow::TEXTURE_MIN_FILTER, filter as i32 );
+        self.gl.tex_parameter_i32( glow::TEXTURE_2D, glow::TEXTURE_MAG_FILTER, filter as i32 );
+        self.gl.tex_parameter_i32( glow::TEXTURE_2D, glow::TEXTURE_WRAP_S, glow::CLAMP_TO_EDGE as i32 );
+        self.gl.tex_parameter_i32( glow::TEXTURE_2D, glow::TEXTURE_WRAP_T, glow::CLAMP_TO_EDGE as i32 );
+        self.gl.bind_texture( glow::TEXTURE_2D, None );
+        texture
+    }
+
+    unsafe fn delete_texture( &self, texture: glow::Texture ) {
+        self.gl.delete_texture( texture );
+    }
+
+    unsafe fn create_pipeline( &self, stages: &[( &str, ShaderType )], defines: &[( &str, u32 )] ) -> Result<glow::Program, EngineError> {
+        let defines: String = defines.iter().map( |( name, value )| format!( "#define {name} {value}\n" ) ).collect();
+        let program = self.gl.create_program().map_err( |err| EngineError::gl( "WEBGL::PROGRAM_NOT_CREATED", err ) )?;
+
+        let mut shaders = Vec::new();
+        let mut compiled = Ok( () );
+        for ( path, shader_type ) in stages {
+            let Some( ( _, source ) ) = GLOW_SHADERS.iter().find( |( name, _ )| name == path ) else {
+                compiled = Err( EngineError::shader( "NOT_BUILT_IN", *path, "Only the shaders in GLOW_SHADERS can be built with WebGL2" ) );
+                break;
+            };
+            let shader = match self.gl.create_shader( gl::types::GLenum::from( *shader_type ) ) {
+                Ok( shader ) => { shader },
+                Err( err ) => { compiled = Err( EngineError::gl( "WEBGL::SHADER_NOT_CREATED", err ) ); break },
+            };
+            shaders.push( shader );
+            self.gl.shader_source( shader, &shader::prepare_source( source, GlApi::WebGl2, &defines ) );
+            self.gl.compile_shader( shader );
+            if !self.gl.get_shader_compile_status( shader ) {
+                compiled = Err( EngineError::shader( "COMPILATION_FAILED", *path, self.gl.get_shader_info_log( shader ) ) );
+                break;
+            }
+            self.gl.attach_shader( program, shader );
+        }
+        if compiled.is_ok() {
+            self.gl.link_program( program );
+            if !self.gl.get_program_link_status( program ) {
+                compiled = Err( EngineError::shader( "LINKING_FAILED", "", self.gl.get_program_info_log( program ) ) );
+            }
+        }
+
+        // (The shaders are part of the program once it is linked)
+        for shader in shaders {
+            self.gl.delete_shader( shader );
+        }
+        match compiled {
+            Ok( () ) => { Ok( program ) },
+            Err( err ) => { self.gl.delete_program( program ); Err( err ) },
+        }
+    }
+
+    unsafe fn dispatch( &self, groups: [u32; 3] ) {
+        warn!( ?groups, "WebGL2 has no compute shaders, so nothing is dispatched" );
+    }
+}
@@ -0,0 +1,2197 @@
+use std::{ thread, ptr, collections::{ HashMap, hash_map::Entry } };
+use std::sync::{Mutex, Arc, RwLock, mpsc, atomic::{ AtomicBool, Ordering }};
+
+use glutin::event::{MouseButton, VirtualKeyCode::{self}};
+use tracing::{ error, info, warn };
+use crate::{ util, assets, shader, camera, raytracing, config, events, mesh, accumulator, bluenoise, sobol, restir, guiding, lighttracing, photons, volumes, postprocess, denoiser, taa, postchain, output, device, glapi, gldebug, gltracker, headless, sequence, video, tiles, checkpoint, farm, progress, wavefront, profiler, stats, readback, reference, uploads, multigpu, window };
+#[cfg(feature = "egui")]
+use crate::ui;
+use crate::capabilities::Capabilities;
+use crate::context::RenderContext;
+use crate::device::Device;
+use crate::error::EngineError;
+use crate::scene::scene_spheres;
+use crate::events::EngineEvent;
+use crate::window::WindowEvent;
+use crate::raytracing::{RTSphere, RTSettings, RTBounces, RTIntegrator, RTCamera, RTCrop, JitterSequence, RTTriangle, RTMeshInfo};
+
+// Title of the window, which the stats are shown after while they are on
+const WINDOW_TITLE: &str = "OpenGL Raytracing Engine";
+
+// Keys for saving (with ctrl) and recalling camera bookmarks, named "1" through "9"
+const BOOKMARK_KEYS: [VirtualKeyCode; 9] = [
+    VirtualKeyCode::Key1, VirtualKeyCode::Key2, VirtualKeyCode::Key3,
+    VirtualKeyCode::Key4, VirtualKeyCode::Key5, VirtualKeyCode::Key6,
+    VirtualKeyCode::Key7, VirtualKeyCode::Key8, VirtualKeyCode::Key9,
+];
+
+/**
+ * Enum for what the pixels read back from the GPU are for, see readback::Readback.
+ */
+enum ReadbackRequest {
+    Screenshot( String, output::Metadata ), // The screen, to be saved to a PNG file at the path
+    #[cfg(feature = "oidn")]
+    OidnColor,  // The accumulated image, to be denoised with Open Image Denoise
+    #[cfg(feature = "oidn")]
+    OidnAlbedo, // The albedo guide, for Open Image Denoise
+    #[cfg(feature = "oidn")]
+    OidnNormal, // The normal guide, for Open Image Denoise
+}
+
+/**
+ * Struct for what the update callback can change every frame, see Engine::on_update().
+ * Changing the camera or the settings restarts the accumulation, like moving with the keys does.
+ */
+pub struct FrameUpdate<'a> {
+    pub time: f32, // The scene's time, in seconds, which stands still while the animation is paused
+    pub dt: f32,   // The time since the last frame, in seconds
+    pub camera: &'a mut camera::Camera,
+    pub lens: &'a mut camera::Lens, // How the camera's rays are projected
+    pub settings: &'a mut RTSettings,
+    pub post_settings: &'a mut postprocess::PostSettings, // Which apply from the next frame, as this frame's render size is already picked
+}
+
+// The callback which is run every frame, see Engine::on_update()
+type UpdateCallback = Box<dyn FnMut( &mut FrameUpdate ) + Send>;
+
+/**
+ * Struct for a model which was loaded ahead of rendering, see Engine::load_scene().
+ */
+struct LoadedScene {
+    path: String,
+    triangles: Vec<RTTriangle>,
+    meshes: Vec<RTMeshInfo>,
+}
+
+/**
+ * Struct for what the engine hands the render loop, besides the window's input.
+ */
+struct RenderSetup {
+    config: config::EngineConfig,
+    config_watcher: Option<config::ConfigWatcher>, // Reloads the config when its file changes, while rendering to the window
+    scene: Option<LoadedScene>, // The scene to render, or None to load the config's (or the offline render's)
+    on_update: Option<UpdateCallback>,
+    render_passes: Vec<( Box<dyn postchain::RenderPass>, postchain::PassPlacement )>, // Passes which are added to the post chain, in the order they were given
+    events: events::EventBus, // Where the render loop sends what happens, see Engine::subscribe()
+}
+
+/**
+ * Struct for the engine, which is how the raytracer is embedded into other projects.
+ * It is made with a config, given a scene and a callback which is run every frame, and then either opens the window or renders offline.
+ * See examples/pan.rs, and main.rs for the command line which is built on it.
+ */
+pub struct Engine {
+    config: config::EngineConfig,
+    config_watcher: Option<config::ConfigWatcher>, // Watches the file the config was loaded from, if any
+    scene: Option<LoadedScene>,
+    on_update: Option<UpdateCallback>,
+    render_passes: Vec<( Box<dyn postchain::RenderPass>, postchain::PassPlacement )>,
+    events: events::EventBus,
+}
+
+/**
+ * Engine functions.
+ */
+impl Engine {
+    /**
+     * Creates an engine with a config, which is kept as it is.
+     *
+     * @param config The config.
+     */
+    pub fn new( config: config::EngineConfig ) -> Engine {
+        Engine { config, config_watcher: None, scene: None, on_update: None, render_passes: Vec::new(), events: events::EventBus::new() }
+    }
+
+    /**
+     * Creates an engine with the config in the config file (see EngineConfig::path()), which is reloaded whenever the file changes while the window is open.
+     *
+     * @return The engine, or an error if the file couldn't be read or parsed.
+     */
+    pub fn from_config_file() -> Result<Engine, EngineError> {
+        let path = config::EngineConfig::path();
+        let config_watcher = config::ConfigWatcher::new( &path );
+        Ok( Engine { config_watcher: Some( config_watcher ), ..Engine::new( config::EngineConfig::load( &path )? ) } )
+    }
+
+    /**
+     * Gets the engine's config.
+     */
+    pub fn config( &self ) -> &config::EngineConfig {
+        &self.config
+    }
+
+    /**
+     * Loads the model which is rendered, instead of the config's or the offline render's, see mesh::Model::load_from_file().
+     *
+     * @param path The path to the model.
+     * @return Ok, or an error if the model couldn't be loaded.
+     */
+    pub fn load_scene( &mut self, path: &str ) -> Result<(), EngineError> {
+        let path = assets::scene_path( path );
+        let ( triangles, meshes ) = mesh::Model::new().load_from_file( &path )?.generate_raytracing_structs();
+        self.scene = Some( LoadedScene { path, triangles, meshes } );
+        Ok( () )
+    }
+
+    /**
+     * Sets the callback which is run every frame on the render thread, after the camera is moved with the keys and before the frame is rendered.
+     *
+     * @param callback The callback, which replaces the one before it.
+     */
+    pub fn on_update<F: FnMut( &mut FrameUpdate ) + Send + 'static>( &mut self, callback: F ) {
+        self.on_update = Some( Box::new( callback ) );
+    }
+
+    /**
+     * Adds a pass to the post chain, which runs on the image every frame between the tracing and the screen, see postchain::RenderPass.
+     * Passes placed at the same spot run in the order they were added.
+     *
+     * @param pass The pass, which is set up once the render starts.
+     * @param placement Where the pass is put in the chain, where naming a pass which isn't in it stops the render with an error.
+     */
+    pub fn add_render_pass<P: postchain::RenderPass + 'static>( &mut self, pass: P, placement: postchain::PassPlacement ) {
+        self.render_passes.push( ( Box::new( pass ), placement ) );
+    }
+
+    /**
+     * Subscribes to the engine's events, which are sent from the render thread while it renders, see events::EngineEvent.
+     *
+     * @return The receiver of the events.
+     */
+    pub fn subscribe( &mut self ) -> std::sync::mpsc::Receiver<EngineEvent> {
+        self.events.subscribe()
+    }
+
+    /**
+     * Gets what the render loop is handed.
+     */
+    fn setup( self ) -> RenderSetup {
+        assets::set_root( &self.config.render.asset_root );
+        RenderSetup { config: self.config, config_watcher: self.config_watcher, scene: self.scene, on_update: self.on_update, render_passes: self.render_passes, events: self.events }
+    }
+
+    /**
+     * Opens the window and renders to it on another thread, while the events are handled on this thread until the window is closed.
+     * The window is made with the backend the engine was built with, which is GLFW if it was built with both.
+     *
+     * @return Ok once the window is closed, or an error if it couldn't be created.
+     *         (winit exits the process when its window is closed, so with it, this only returns errors)
+     */
+    pub fn run( self ) -> Result<(), EngineError> {
+        #[cfg(feature = "glfw")]
+        let result = run_window::<window::GlfwBackend>( self.setup() );
+        #[cfg(all(feature = "winit", not(feature = "glfw")))]
+        let result = run_window::<window::WinitBackend>( self.setup() );
+        #[cfg(not(any(feature = "winit", feature = "glfw")))]
+        let result = Err( EngineError::gl( "WINDOW::NO_BACKEND", "Built without a window backend (the winit or glfw feature), so it can only render offline, see --help" ) );
+        result
+    }
+
+    /**
+     * Renders offline, without a window, see headless::HeadlessSettings.
+     * The render has its own size and post-processing, so only the config's scene is used, unless the render has its own.
+     *
+     * @param headless The settings of the render.
+     * @return Ok once the render is saved, or an error if it couldn't be done.
+     */
+    pub fn render_offline( self, headless: headless::HeadlessSettings ) -> Result<(), EngineError> {
+        render_headless( headless, None, self.setup() )
+    }
+
+    /**
+     * Renders the tasks a coordinator hands out until it has no more, see farm::Worker.
+     *
+     * @param worker The connection to the coordinator.
+     * @param headless The settings of the render, which the coordinator sent.
+     * @return Ok once there are no more tasks, or an error if the render couldn't go on.
+     */
+    pub fn render_for( self, worker: farm::Worker, headless: headless::HeadlessSettings ) -> Result<(), EngineError> {
+        render_headless( headless, Some( worker ), self.setup() )
+    }
+}
+
+/**
+ * Opens the window and renders to it on another thread, while the events are handled on this thread until the window is closed.
+ *
+ * @param setup What the render loop is handed.
+ */
+#[allow(dead_code)]
+fn run_window<B: window::WindowBackend>( setup: RenderSetup ) -> Result<(), EngineError> {
+    // --- Create contexted window
+    let window_config = setup.config.window;
+    let ( backend, context_pre ) = B::create( WINDOW_TITLE, window_config.width, window_config.height, window_config.vsync )?;
+
+    // --- Set up event listeners
+    let arc_keys_mainthread = Arc::new( Mutex::new( Vec::<VirtualKeyCode>::with_capacity(10) ) );
+    let arc_keys_renderthread = Arc::clone( &arc_keys_mainthread );
+
+    let initial_size = backend.size();
+    let arc_screen_size_mainthread = Arc::new( Mutex::new( initial_size ) );
+    let arc_screen_size_renderthread = Arc::clone( &arc_screen_size_mainthread );
+
+    // Pixel which was clicked to focus on, if any
+    let arc_focus_click_mainthread = Arc::new( Mutex::new( None::<glm::Vec2> ) );
+    let arc_focus_click_renderthread = Arc::clone( &arc_focus_click_mainthread );
+
+    // Corners which were dragged between to crop the render, if any
+    let arc_crop_drag_mainthread = Arc::new( Mutex::new( None::<( glm::Vec2, glm::Vec2 )> ) );
+    let arc_crop_drag_renderthread = Arc::clone( &arc_crop_drag_mainthread );
+
+    // Pointer events for the control panel, since it was last drawn
+    #[cfg(feature = "egui")]
+    let arc_ui_events_mainthread = Arc::new( Mutex::new( Vec::<WindowEvent>::new() ) );
+    #[cfg(feature = "egui")]
+    let arc_ui_events_renderthread = Arc::clone( &arc_ui_events_mainthread );
+
+    // If the window is closing, which ends the render loop
+    let arc_closing_mainthread = Arc::new( AtomicBool::new( false ) );
+    let arc_closing_renderthread = Arc::clone( &arc_closing_mainthread );
+    
+    // --- Start render thread
+    // Spawn thread
+    let inputs = RenderInputs {
+        keys: arc_keys_renderthread,
+        screen_size: arc_screen_size_renderthread,
+        focus_click: arc_focus_click_renderthread,
+        crop_drag: arc_crop_drag_renderthread,
+        #[cfg(feature = "egui")]
+        ui_events: arc_ui_events_renderthread,
+        closing: arc_closing_renderthread,
+    };
+    let render_thread = thread::spawn ( move || {
+        if let Err( err ) = render( context_pre, inputs, setup, None, None ) {
+            error!( "{err}" );
+        }
+        gltracker::report();
+    } );
+
+    // Spawn another thread for error handling, which also tells when the render thread is done
+    let render_thread_healthy = Arc::new ( RwLock::new(true) );
+    let render_thread_watcher = Arc::clone ( &render_thread_healthy );
+    let ( render_thread_done, render_thread_finished ) = mpsc::channel::<()>();
+    thread::spawn ( move || {
+        if render_thread.join().is_err() {
+            if let Ok ( mut health ) = render_thread_watcher.write() {
+                error!( "An error occured in the render thread" );
+                *health = false;
+            }
+        }
+        let _ = render_thread_done.send( () );
+    } );
+
+    // --- Start event loop in the main thread
+    let mut cursor_position = glm::Vec2::zeros();
+    let mut crop_drag_start = None;
+    backend.run ( move | event | {
+        // Break loop if an error occurs in the render thread
+        if let Ok ( health ) = render_thread_healthy.read() {
+            if !*health {
+                return false;
+            }
+        }
+
+        // The control panel gets the pointer's events too, and takes the clicks which land on it (see ControlPanel::wants_pointer())
+        #[cfg(feature = "egui")]
+        if matches!( event, WindowEvent::CursorMoved( _ ) | WindowEvent::MouseInput( .. ) ) {
+            if let Ok( mut events ) = arc_ui_events_mainthread.lock() {
+                events.push( event );
+            }
+        }
+
+        // Handle events
+        match event {
+            //close window
+            // (The render thread is waited for, so it deletes its GL objects and reports those it left before the process exits)
+            WindowEvent::CloseRequested => {
+                arc_closing_mainthread.store( true, Ordering::Relaxed );
+                let _ = render_thread_finished.recv();
+                return false;
+            }
+
+            //window resize
+            WindowEvent::Resized( width, height ) => {
+                if let Ok( mut screen_size ) = arc_screen_size_mainthread.lock() {
+                    *screen_size = ( width, height );
+                }
+            }
+
+            //mouse input (left click to focus, right drag to crop)
+            WindowEvent::CursorMoved( position ) => {
+                cursor_position = position;
+            }
+            WindowEvent::MouseInput( MouseButton::Left, true ) => {
+                if let Ok( mut click ) = arc_focus_click_mainthread.lock() {
+                    *click = Some( cursor_position );
+                }
+            }
+            WindowEvent::MouseInput( MouseButton::Right, true ) => {
+                crop_drag_start = Some( cursor_position );
+            }
+            WindowEvent::MouseInput( MouseButton::Right, false ) => {
+                if let ( Some( start ), Ok( mut drag ) ) = ( crop_drag_start.take(), arc_crop_drag_mainthread.lock() ) {
+                    *drag = Some( ( start, cursor_position ) );
+                }
+            }
+
+            //keyboard input
+            WindowEvent::KeyboardInput( key_code, pressed ) => {
+                if let Ok( mut keys ) = arc_keys_mainthread.lock() {
+                    if pressed {
+                        if !keys.contains( &key_code ) {
+                            keys.push( key_code );
+                        }
+                    } else if keys.contains( &key_code ) {
+                        let key_index = keys.iter().position( |&k| k == key_code ).unwrap();
+                        keys.remove( key_index );
+                    }
+                }
+            }
+
+            //default
+            _ => { }
+        }
+        true
+    } );
+    Ok( () )
+}
+
+/**
+ * Struct for the input which the window passes to the render thread.
+ */
+struct RenderInputs {
+    keys: Arc<Mutex<Vec<VirtualKeyCode>>>, // Keys held down
+    screen_size: Arc<Mutex<( u32, u32 )>>, // Size of the window, in pixels
+    focus_click: Arc<Mutex<Option<glm::Vec2>>>, // Pixel which was clicked to focus on, if any
+    crop_drag: Arc<Mutex<Option<( glm::Vec2, glm::Vec2 )>>>, // Corners which were dragged between to crop the render, if any
+    #[cfg(feature = "egui")]
+    ui_events: Arc<Mutex<Vec<WindowEvent>>>, // Pointer events for the control panel
+    closing: Arc<AtomicBool>, // If the window is closing, which ends the render loop
+}
+
+/**
+ * Gets how the image being accumulated was rendered, for embedding in the files it is saved to.
+ *
+ * @param scene The path of the scene.
+ * @param settings The settings of the render.
+ * @param camera The camera the image is seen from, before its jitter.
+ * @param samples How many frames have been accumulated.
+ * @param render_time How long the image has been accumulated for.
+ * @param frame The frame of the sequence being rendered, if any.
+ *
+ * @return The metadata.
+ */
+fn render_metadata( scene: &str, settings: &RTSettings, camera: &RTCamera, samples: u32, render_time: std::time::Duration, frame: Option<u32> ) -> output::Metadata {
+    let metadata = output::Metadata::new( scene, settings.seed )
+        .with( "Samples", samples )
+        .with( "RenderTime", format!( "{:.3} s", render_time.as_secs_f32() ) )
+        .with( "Settings", serde_json::to_string( settings ).unwrap_or_default() )
+        .with( "Camera", serde_json::to_string( camera ).unwrap_or_default() );
+    match frame {
+        Some( frame ) => { metadata.with( "Frame", frame ) },
+        None => { metadata },
+    }
+}
+
+/**
+ * Gets the settings which renders start with, which offline renders override some of.
+ *
+ * @param headless The settings of the offline render, or None if rendering to the window.
+ */
+pub fn initial_settings( headless: Option<&headless::HeadlessSettings> ) -> RTSettings {
+    let mut settings = RTSettings::new();
+    if let Some( headless ) = headless {
+        if let Some( bounces ) = headless.bounces {
+            settings.max_bounces = RTBounces { total: bounces, diffuse: bounces, glossy: bounces, transmission: bounces };
+        }
+        settings.seed = headless.seed;
+    }
+    settings
+}
+
+/**
+ * Renders offline on the CPU with the reference path tracer, see reference::ReferenceRenderer.
+ * Only single images are rendered this way, without tiles, AOVs or post-processing.
+ *
+ * @param headless The settings of the render.
+ */
+fn render_reference( headless: &headless::HeadlessSettings ) -> Result<(), EngineError> {
+    if headless.sequence.is_some() || headless.tile_size.is_some() || headless.aovs.is_some() {
+        return Err( EngineError::render( "REFERENCE::UNSUPPORTED", "The CPU only renders single images, without tiles or AOVs" ) );
+    }
+    let ( triangles, _ ) = mesh::Model::new().load_from_file( &headless.scene )?.generate_raytracing_structs();
+
+    // (The camera is the one the GPU would start from)
+    let mut camera = camera::Camera::new();
+    camera.set_view_params( glm::zero(), glm::zero(), 60.0, 1.0, 10.0 );
+    let bookmarks = camera::CameraBookmarks::load( &format!( "{}.bookmarks", headless.scene ) )?;
+    let ( focus_distance, aperture_radius ) = headless.camera.apply( &mut camera, &bookmarks, 1.0, 0.0 );
+    let rtcamera = RTCamera {
+        screen_size: glm::vec2( headless.width as f32, headless.height as f32 ),
+        fov: camera.fov(),
+        focus_distance,
+        pos: camera.pos().into(),
+        local_to_world: camera.rts(),
+        projection: headless.camera.lens.projection,
+        aperture_radius,
+        distortion: headless.camera.lens.distortion,
+        chromatic_aberration: headless.camera.lens.chromatic_aberration,
+        stereo: headless.camera.lens.stereo,
+        pixel_jitter: glm::zero(),
+        shutter_close: None,
+        window: None,
+    };
+
+    // (Each of the GPU's samples is a frame of rays_per_frag paths, so the CPU traces as many)
+    let settings = initial_settings( Some( headless ) );
+    let samples = headless.samples * settings.rays_per_frag;
+    info!( "Rendering {}x{} with {samples} paths per pixel on the CPU, over {} threads", headless.width, headless.height, rayon::current_num_threads() );
+    let started = std::time::Instant::now();
+    let renderer = reference::ReferenceRenderer::new( scene_spheres( 0.0 ), triangles, settings.clone() );
+    let pixels = renderer.render( &rtcamera, samples );
+
+    let metadata = render_metadata( &headless.scene, &settings, &rtcamera, headless.samples, started.elapsed(), None )
+        .with( "Renderer", "cpu" );
+    // (Without a post chain, the physical camera's exposure is applied to what is shown as the tonemapper would, while the light itself is saved as it is)
+    let scale = headless.camera.physical.map_or( 1.0, |physical| physical.exposure().exp2() );
+    let shown: Vec<f32> = pixels.iter().map( |value| value * scale ).collect();
+    let screen = output::srgb_image( headless.width, headless.height, &shown );
+    headless.save_image( &headless.output, headless.width, headless.height, &pixels, &screen, &metadata )?;
+    info!( "Saved {} after {:.1} s", headless.output, started.elapsed().as_secs_f32() );
+    Ok( () )
+}
+
+/**
+ * Renders offline, without a window, see headless::HeadlessSettings.
+ *
+ * @param headless The settings of the render.
+ * @param worker The connection to the coordinator, if the render is of the tasks it hands out.
+ * @param setup What the render loop is handed.
+ */
+fn render_headless( mut headless: headless::HeadlessSettings, worker: Option<farm::Worker>, setup: RenderSetup ) -> Result<(), EngineError> {
+    // (The scene is looked up in the asset root if it isn't in the working directory, so its bookmarks, checkpoints and metadata are of the one which is rendered)
+    headless.scene = match &setup.scene {
+        Some( scene ) => { scene.path.clone() },
+        None => { assets::scene_path( &headless.scene ) },
+    };
+
+    // (Without a context, renders which the CPU covers fall back to it)
+    let context = match headless.renderer {
+        headless::Renderer::Gpu => { headless.create_context().map_err( Some ) },
+        headless::Renderer::Cpu => { Err( None ) },
+    };
+    let ( context, _event_loop ) = match context {
+        Ok( context ) => { context },
+        Err( err ) if worker.is_none() => {
+            if let Some( err ) = err {
+                warn!( "{err}\nRendering on the CPU instead" );
+            }
+            return render_reference( &headless );
+        },
+        Err( err ) => {
+            return Err( err.unwrap_or_else( || EngineError::render( "FARM::NO_CONTEXT", "Workers only render on the GPU" ) ) );
+        },
+    };
+    let inputs = RenderInputs {
+        keys: Arc::new( Mutex::new( Vec::new() ) ),
+        screen_size: Arc::new( Mutex::new( headless.screen_size() ) ),
+        focus_click: Arc::new( Mutex::new( None ) ),
+        crop_drag: Arc::new( Mutex::new( None ) ),
+        #[cfg(feature = "egui")]
+        ui_events: Arc::new( Mutex::new( Vec::new() ) ),
+        closing: Arc::new( AtomicBool::new( false ) ),
+    };
+    let result = render( context, inputs, setup, Some( headless ), worker );
+    gltracker::report();
+    result
+}
+
+/**
+ * Sets up the scene and renders it until the window is closed, or until an offline render is done.
+ *
+ * @param context_pre The context to render with, which is made current on this thread.
+ * @param inputs The input from the window.
+ * @param setup What the engine hands the render loop.
+ * @param headless The settings of the offline render, or None if rendering to the window.
+ * @param farm_worker The connection to the coordinator, if the render is of the tasks it hands out.
+ *
+ * @return Ok once the window is closed or the render is done, or an error if the render couldn't go on.
+ */
+fn render( context_pre: RenderContext<glutin::NotCurrent>, inputs: RenderInputs, setup: RenderSetup, headless: Option<headless::HeadlessSettings>, farm_worker: Option<farm::Worker> ) -> Result<(), EngineError> {
+    let Some( mut render_loop ) = RenderLoop::new( context_pre, inputs, setup, headless, farm_worker )? else {
+        return Ok( () );
+    };
+    let mut rendering = render_loop.start();
+    while let Ok( true ) = rendering {
+        rendering = render_loop.step();
+    }
+    unsafe { render_loop.delete() };
+    rendering.map( |_| () )
+}
+
+/**
+ * Struct for what a frame is rendered with, once the input has been handled, see RenderLoop::draw().
+ */
+#[derive(Clone, Copy)]
+struct Frame {
+    time: std::time::Instant, // When the frame started
+    dt: f32,                  // The time since the last frame, in seconds
+    width: u32,               // The size of the render, in pixels
+    height: u32,
+    previewing: bool,         // If the render is previewed at a lower resolution while the camera moves, see PostSettings::preview
+    movement: glm::Vec3,      // How far the camera was moved with the keys
+    rotation: glm::Vec3,      // How far the camera was turned with the keys
+    pointer_on_panel: bool,   // If the pointer is on the control panel, which takes its clicks from the scene
+}
+
+/**
+ * Struct for the render loop, which holds the scene, the passes and what is kept from one frame to the next.
+ * It is set up on the render thread, and stepped through a frame at a time until the window is closed or the offline render is done.
+ */
+struct RenderLoop {
+    context: RenderContext<glutin::PossiblyCurrent>,
+    inputs: RenderInputs,
+    config: config::EngineConfig,
+    config_watcher: Option<config::ConfigWatcher>,
+    on_update: Option<UpdateCallback>,
+    events: events::EventBus,
+    headless: Option<headless::HeadlessSettings>, // The settings of the offline render, or None if rendering to the window
+    farm_worker: Option<farm::Worker>,
+    camera: camera::Camera,
+
+    // The screen quad, and the shaders which trace the scene onto it (or are dispatched over it, see Shading)
+    my_vao: u32,
+    quad_index_count: i32,
+    simple_shader: shader::Shader,
+    compute_shaders: HashMap<wavefront::WorkgroupSize, shader::Shader>,
+    workgroup_tuner: wavefront::WorkgroupTuner,
+    shading: wavefront::Shading,
+    capabilities: Capabilities,
+    luminance_shader: shader::Shader,
+
+    // The passes the frame is traced with, and those which turn it into what is shown
+    accumulator: accumulator::Accumulator,
+    restir: restir::Restir,
+    light_tracer: lighttracing::LightTracer,
+    photon_map: photons::PhotonMap,
+    wavefront_queues: Option<wavefront::Wavefront>,
+    denoiser: denoiser::Denoiser,
+    color_lut: Option<postprocess::ColorLut>,
+    show_lut: bool,
+    temporal_aa: taa::TemporalAA,
+    post_chain: postchain::PostChain,
+    #[cfg(feature = "oidn")]
+    oidn_requested: bool,
+    #[cfg(feature = "oidn")]
+    oidn_shown: bool,
+    #[cfg(feature = "oidn")]
+    oidn_pixels: [Option<Vec<f32>>; 3],
+    exposure_meter: postprocess::ExposureMeter,
+    blue_noise_texture: u32,
+    volumes: Vec<volumes::Volume>,
+    show_volumes: bool,
+
+    // The scene, and the buffers it is uploaded to
+    model_path: String,
+    triangles: Vec<RTTriangle>,
+    bookmarks: camera::CameraBookmarks,
+    camera_path_file: String,
+    camera_path: camera::CameraPath,
+    camera_path_start: std::time::Instant,
+    meshes_count: usize,
+    _triangles_ssbo: shader::SSBO<raytracing::RTQuantizedTriangle>,
+    meshes_ssbo: shader::SSBO<RTMeshInfo>,
+    _sobol_ssbo: shader::SSBO<u32>,
+    ssbo_spheres: shader::SSBO<RTSphere>,
+    spheres_count: i32,
+    path_guide: guiding::PathGuide,
+    settings: RTSettings,
+    post_settings: postprocess::PostSettings,
+    #[cfg(feature = "egui")]
+    control_panel: Option<ui::ControlPanel>,
+    #[cfg(feature = "egui")]
+    sphere_materials: Vec<Option<raytracing::RTMaterial>>,
+
+    // What the frames are rendered with, see the setup in RenderLoop::new()
+    time_prev: std::time::Instant,
+    time_elapsed: f32,
+    animate: bool,
+    screen_width: u32,
+    screen_height: u32,
+    focus_distance: f32,
+    aperture_radius: f32,
+    physical_camera: Option<camera::PhysicalCamera>,
+    lens: camera::Lens,
+    lens_without_physical: ( f32, f32 ), // The fov and aperture from before the physical camera decided them, which are put back when it is removed
+    prev_camera_view: Option<( glm::Mat4, f32, f32 )>,
+    camera_moved: bool,
+    resolution_scaler: Option<postprocess::ResolutionScaler>,
+    texture_size_warned: bool,
+    gpu_profiler: profiler::GpuProfiler,
+    profiler_reported: std::time::Instant,
+    render_stats: stats::RenderStats,
+    stats_shown: std::time::Instant,
+    readback: readback::Readback<ReadbackRequest>,
+    uploads: uploads::UploadTracker,
+    prev_world_to_screen: Option<glm::Mat4>,
+    frame_index: u32,
+    metadata_camera: RTCamera,
+    image_started: std::time::Instant,
+    screenshot_requested: bool,
+    exr_requested: bool,
+    hdr_requested: bool,
+    sequence: Option<sequence::SequenceSettings>,
+    sequence_frame: Option<u32>,
+    tile_scheduler: Option<tiles::TileScheduler>,
+    checkpointer: Option<checkpoint::Checkpointer>,
+    video_encoder: Option<video::VideoEncoder>,
+    progress_reporter: Option<progress::ProgressReporter>,
+    keys_prev: Vec<VirtualKeyCode>,
+}
+
+/**
+ * RenderLoop functions.
+ */
+impl RenderLoop {
+    /**
+     * Makes the context current on this thread, and sets up the scene and everything it is rendered with.
+     *
+     * @param context_pre The context to render with.
+     * @param inputs The input from the window.
+     * @param setup What the engine hands the render loop.
+     * @param headless The settings of the offline render, or None if rendering to the window.
+     * @param farm_worker The connection to the coordinator, if the render is of the tasks it hands out.
+     *
+     * @return The render loop, None if the window's size can't be read, or an error if it couldn't be set up.
+     */
+    fn new( context_pre: RenderContext<glutin::NotCurrent>, inputs: RenderInputs, setup: RenderSetup, headless: Option<headless::HeadlessSettings>, farm_worker: Option<farm::Worker> ) -> Result<Option<RenderLoop>, EngineError> {
+        // Load OpenGL context and functions
+        let context = unsafe { context_pre.make_current()? };
+        // (Workers for one of several GPUs tell which GPU they ended up on, as picking it is up to the platform)
+        if let Some( gpu ) = multigpu::assigned_gpu() {
+            let renderer = unsafe { std::ffi::CStr::from_ptr( gl::GetString( gl::RENDERER ) as *const _ ) };
+            info!( "GPU {gpu} is {}", renderer.to_string_lossy() );
+        }
+        let initial_size = match inputs.screen_size.lock() {
+            Ok( screen_size ) => { glutin::dpi::PhysicalSize::new( screen_size.0, screen_size.1 ) },
+            Err( _ ) => { return Ok( None ) },
+        };
+
+        // OpenGL Settings
+        unsafe {
+            gl::Enable(gl::DEPTH_TEST);
+            gl::DepthFunc(gl::LESS);
+            gl::Enable(gl::CULL_FACE);
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+            // (OpenGL ES has no multisampling or sRGB switches, and its framebuffers are never sRGB-encoded unless they are made so)
+            if glapi::GlApi::current() == glapi::GlApi::Desktop {
+                gl::Disable(gl::MULTISAMPLE);
+                // The tonemap pass encodes to sRGB itself, so the framebuffer must not do it again
+                gl::Disable(gl::FRAMEBUFFER_SRGB);
+            }
+            gldebug::install();
+        }
+
+        // Set up camera
+        let mut camera = camera::Camera::new();
+        camera.set_view_params(
+            glm::zero(),
+            glm::zero(),
+            60.0,
+            1.0,
+            10.0,
+        );
+
+        let RenderSetup { config, config_watcher, scene, on_update, render_passes, mut events } = setup;
+
+        // --- Set up game objects
+        // Set up screen quad
+        let (vertices, indices) = util::create_billboard();
+        let my_vao = unsafe {util::create_vao(&vertices, &indices)};
+        let simple_shader = unsafe {
+            device::current().create_pipeline(
+                &[( &assets::path( "shaders/raytracing.vert" ), shader::ShaderType::Vertex ), ( &assets::path( "shaders/raytracing.frag" ), shader::ShaderType::Fragment )],
+                &RTIntegrator::shader_defines()
+            )?
+        };
+        // (The same shader can be dispatched as a compute shader instead, which is built the first time it is used, for each work group size)
+        // (Unless a size is given, the first frames of the compute path time each candidate size, and the fastest is kept)
+        let compute_shaders: HashMap<wavefront::WorkgroupSize, shader::Shader> = HashMap::new();
+        let workgroup_tuner = wavefront::WorkgroupTuner::new( headless.as_ref().and_then( |headless| headless.workgroup_size ) );
+        let mut shading = headless.as_ref().map_or( wavefront::Shading::Fragment, |headless| headless.shading );
+        let capabilities = Capabilities::current();
+        if shading != wavefront::Shading::Fragment && !capabilities.compute_shaders {
+            warn!( "The GPU has no compute shaders, so the {} shading falls back to fragment shading", shading.name() );
+            shading = wavefront::Shading::Fragment;
+        }
+        let luminance_shader = unsafe {
+            device::current().create_pipeline( &[( &assets::path( "shaders/raytracing.vert" ), shader::ShaderType::Vertex ), ( &assets::path( "shaders/luminance.frag" ), shader::ShaderType::Fragment )], &[] )?
+        };
+
+        // Set up accumulation buffer
+        let mut accumulator = unsafe { accumulator::Accumulator::new( initial_size.width, initial_size.height )? };
+        if headless.as_ref().is_some_and( |headless| headless.aovs.is_some() ) {
+            unsafe { accumulator.set_aovs( true )? };
+        }
+
+        // Set up reservoirs for ReSTIR direct lighting
+        let restir = unsafe { restir::Restir::new( initial_size.width, initial_size.height ) };
+
+        // Set up splats for light tracing, for the bidirectional integrator
+        let light_tracer = unsafe { lighttracing::LightTracer::new( initial_size.width, initial_size.height ) };
+
+        // Set up the photon map for caustics
+        let photon_map = unsafe { photons::PhotonMap::new( initial_size.width, initial_size.height ) };
+
+        // Set up the queues of the wavefront shading, the first time it is used
+        let wavefront_queues: Option<wavefront::Wavefront> = None;
+
+        // Set up the denoiser
+        let denoiser = unsafe { denoiser::Denoiser::new( initial_size.width, initial_size.height )? };
+
+        // Load the LUT for color grading, if there is one, which is off until toggled on
+        let lut_path = assets::path( "resources/grade.cube" );
+        let color_lut = if std::path::Path::new( &lut_path ).exists() {
+            unsafe { postprocess::ColorLut::load_cube( &lut_path ) }.map_err( |err| error!( "{err}" ) ).ok()
+        } else {
+            None
+        };
+        let show_lut = false;
+
+        // Set up temporal anti-aliasing
+        let temporal_aa = unsafe { taa::TemporalAA::new( initial_size.width, initial_size.height )? };
+
+        // Set up the post-processing passes, which turn the accumulated image into what is shown
+        let mut post_chain = unsafe { postchain::PostChain::new()? };
+        for ( pass, placement ) in render_passes {
+            unsafe { post_chain.add_render_pass( pass, placement )? };
+        }
+        info!( "Post chain: {}", post_chain.order().join( " -> " ) );
+
+        // With Open Image Denoise, if the current image should be denoised and if the result is being shown
+        #[cfg(feature = "oidn")]
+        let ( oidn_requested, oidn_shown ) = ( false, false );
+        // The image and guides read back for Open Image Denoise, which it is run on once all three have arrived
+        #[cfg(feature = "oidn")]
+        let oidn_pixels: [Option<Vec<f32>>; 3] = [None, None, None];
+
+        // Set up the exposure meter for auto-exposure
+        let exposure_meter = unsafe { postprocess::ExposureMeter::new()? };
+
+        // Set up blue noise for sampling
+        let blue_noise_texture = unsafe { bluenoise::create_texture( &assets::path( "resources/bluenoise.png" ) ) };
+
+        // Set up a cloud, which is hidden until toggled on
+        // (It is loaded from a NanoVDB file if there is one, otherwise it is generated)
+        let cloud_path = assets::path( "resources/cloud.nvdb" );
+        let ( cloud, cloud_min, cloud_max ) = match volumes::DensityGrid::load_nanovdb( &cloud_path ) {
+            Ok( cloud ) => { cloud },
+            Err( err ) => {
+                if std::path::Path::new( &cloud_path ).exists() { error!( "{err}" ); }
+                ( volumes::DensityGrid::cloud( [64, 48, 64], 0 ), glm::vec3( -6.0, 0.0, -3.0 ), glm::vec3( 0.0, 4.0, 3.0 ) )
+            },
+        };
+        let volumes = unsafe { vec![
+            volumes::Volume::new( cloud_min, cloud_max, &cloud, 4.0, glm::vec3( 0.9, 0.9, 0.9 ), 0.6 ),
+        ] };
+        let show_volumes = false;
+
+        // Load the config's model (the knight by default), unless the engine was given a scene, or an offline render is of another one
+        let ( model_path, ( triangles, meshes ) ) = match scene {
+            Some( scene ) => { ( scene.path, ( scene.triangles, scene.meshes ) ) },
+            None => {
+                let model_path = assets::scene_path( headless.as_ref().map_or( &config.render.scene, |headless| &headless.scene ) );
+                let model = mesh::Model::new().load_from_file( &model_path )?;
+                ( model_path, model.generate_raytracing_structs() )
+            },
+        };
+
+        // Load camera bookmarks saved alongside the model
+        let bookmarks = camera::CameraBookmarks::load( &format!( "{model_path}.bookmarks" ) )?;
+
+        // Camera path which is recorded interactively, and when its recording started
+        let camera_path_file = format!( "{model_path}.path.json" );
+        let camera_path = camera::CameraPath::new();
+        let camera_path_start = std::time::Instant::now();
+
+        let meshes_count = meshes.len();
+        events.emit( EngineEvent::SceneLoaded { path: model_path.clone(), triangles: triangles.len(), meshes: meshes_count } );
+
+        // Create SSBOs for triangles/meshes
+        // (The GPU gets each triangle quantized relative to its mesh's bounds, while the full triangles are kept for raycasting on the CPU)
+        let mut quantized_triangles = Vec::with_capacity( triangles.len() );
+        for mesh in &meshes {
+            let range = mesh.start_index as usize..( mesh.start_index + mesh.count ) as usize;
+            quantized_triangles.extend( triangles[range].iter().map( |triangle| raytracing::RTQuantizedTriangle::quantize( triangle, mesh ) ) );
+        }
+        // (Shaders can't read storage buffers past the GPU's limit, so larger scenes would render garbage or crash the driver)
+        let triangles_size = std::mem::size_of_val( quantized_triangles.as_slice() ) as u64;
+        if triangles_size > capabilities.max_ssbo_size {
+            return Err( EngineError::render( "CAPABILITIES::SCENE_TOO_LARGE", format!(
+                "{model_path} has {} triangles ({} MB), but the GPU's storage buffers hold at most {} MB", triangles.len(), triangles_size >> 20, capabilities.max_ssbo_size >> 20
+            ) ) );
+        }
+        let _triangles_ssbo = unsafe {
+            shader::SSBOBuilder::new()
+                .set_data( quantized_triangles )
+                .set_shader_details( simple_shader.pid, 1, "TriangleBuffer")?
+                .link()
+        };
+
+        let meshes_ssbo = unsafe {
+            shader::SSBOBuilder::new()
+                .set_data( meshes )
+                .set_shader_details( simple_shader.pid, 2, "MeshInfoBuffer")?
+                .link()
+        };
+
+        // Set up path guiding, covering the meshes and some room around them
+        let ( mut guiding_min, mut guiding_max ) = ( glm::vec3( f32::MAX, f32::MAX, f32::MAX ), glm::vec3( f32::MIN, f32::MIN, f32::MIN ) );
+        for mesh in meshes_ssbo.data() {
+            guiding_min = glm::min2( &guiding_min, &mesh.boundingbox_min.into() );
+            guiding_max = glm::max2( &guiding_max, &mesh.boundingbox_max.into() );
+        }
+        let guiding_margin = ( guiding_max - guiding_min ) * 0.5;
+        let path_guide = unsafe { guiding::PathGuide::new( guiding_min - guiding_margin, guiding_max + guiding_margin ) };
+
+        // Create SSBO for the Sobol sampler's generator matrices
+        let _sobol_ssbo = unsafe {
+            shader::SSBOBuilder::new()
+                .set_data( sobol::generator_matrices() )
+                .set_shader_details( simple_shader.pid, 3, "SobolBuffer")?
+                .link()
+        };
+
+        // Set shader settings
+        let settings = initial_settings( headless.as_ref() );
+
+        // Set post-processing settings
+        let post_settings = postprocess::PostSettings {
+            denoise: if config.denoiser.enabled && headless.is_none() { Some( config.denoiser.settings() ) } else { None },
+            taa: None,
+            exposure: 0.0,
+            auto_exposure: None,
+            tonemapper: postprocess::Tonemapper::Aces,
+            bloom: None,
+            vignette: None,
+            film_grain: None,
+            supersampling: None,
+            preview: None,
+            dynamic_resolution: None,
+            dof_preview: true,
+            white_balance: None,
+            srgb_encode: true,
+            dither: true,
+            scale: if headless.is_some() { 1.0 } else { config.render.scale },
+        };
+
+        // Create SSBO for spheres
+        // For now the data is left blank, as it is immidiately overwritten in the gameloop.
+        // However, the amount of objects must be the same so the correct amount of space is reserved.
+        // (They are updated every frame, so the buffer is persistently mapped and triple buffered)
+        let spheres_count = 4;
+        let mut spheres = Vec::new();
+        for _ in 0..spheres_count {
+            spheres.push( RTSphere::new() )
+        }
+
+        let ssbo_spheres = unsafe {
+            shader::SSBOBuilder::new()
+                .set_data( spheres )
+                .set_shader_details( simple_shader.pid, 0, "SphereBuffer" )?
+                .set_persistent( 3 )
+                .link()
+        };
+
+        // Control panel drawn over the window, which is toggled with F1, and the materials of the spheres which were edited with it
+        #[cfg(feature = "egui")]
+        let control_panel = match headless {
+            Some( _ ) => { None },
+            None => match unsafe { ui::ControlPanel::new( &context ) } {
+                Ok( control_panel ) => { Some( control_panel ) },
+                Err( err ) => { error!( "{err}" ); None },
+            },
+        };
+        #[cfg(feature = "egui")]
+        let sphere_materials: Vec<Option<raytracing::RTMaterial>> = vec![None; spheres_count as usize];
+
+        // --- What the render loop keeps from one frame to the next
+
+        // Start time
+        let time_prev = std::time::Instant::now();
+
+        // Time used for animating the scene, which can be paused so that the accumulation converges
+        // (Offline renders are of the scene standing still, as they must converge)
+        let ( time_elapsed, animate ) = ( 0.0, headless.is_none() );
+
+        // Screen size, updated when the window is resized
+        let ( screen_width, screen_height ) = ( initial_size.width, initial_size.height );
+
+        // Focus distance, updated by clicking on the screen
+        let mut focus_distance = 1.0;
+        let mut aperture_radius = 0.0;
+
+        // A physical camera decides the fov and aperture, and its exposure is added to the tonemapper's, see camera::PhysicalCamera
+        // (Offline renders have their own rather than the config's, and may be from another view than the default one)
+        let physical_camera = headless.as_ref().map_or( config.camera.physical, |headless| headless.camera.physical );
+        let lens_without_physical = ( camera.fov(), aperture_radius );
+
+        // How the camera projects the rays, which offline renders are given like the physical camera
+        let lens = headless.as_ref().map_or( config.camera.lens, |headless| headless.camera.lens );
+        match &headless {
+            Some( headless ) => { ( focus_distance, aperture_radius ) = headless.camera.apply( &mut camera, &bookmarks, focus_distance, aperture_radius ) },
+            None => {
+                if let Some( physical ) = &physical_camera {
+                    aperture_radius = camera.apply_physical( physical );
+                }
+            },
+        }
+
+        // The camera's view last frame, for telling if it is moving
+        let prev_camera_view: Option<( glm::Mat4, f32, f32 )> = None;
+
+        // If the camera moved last frame, for previewing at a lower resolution (see PostSettings::preview)
+        // (The render size is decided before this frame's movement is known, so the preview follows a frame behind)
+        let camera_moved = false;
+
+        // Picks the scale of the render while dynamic resolution is on (see PostSettings::dynamic_resolution)
+        let resolution_scaler: Option<postprocess::ResolutionScaler> = None;
+        let texture_size_warned = false; // If the render size was shrunk to fit the textures, which is only warned about once
+
+        // Times the GPU's work in each part of the frame while profiling is on, which is reported every second
+        let gpu_profiler = profiler::GpuProfiler::new( false );
+        let profiler_reported = std::time::Instant::now();
+
+        // Counts the rays traced while the stats are on, which are shown in the window's title twice a second
+        let render_stats = unsafe { stats::RenderStats::new( false ) };
+        let stats_shown = std::time::Instant::now();
+
+        // Reads back screenshots (and images for Open Image Denoise) a frame or so later, so they don't stall the view
+        let readback = unsafe { readback::Readback::<ReadbackRequest>::new() };
+
+        // Tells which of the render's inputs each shader already has, so each frame only sends what changed since the last
+        let uploads = uploads::UploadTracker::new();
+
+        // The camera's projection last frame, for the denoiser's motion vectors
+        let prev_world_to_screen: Option<glm::Mat4> = None;
+
+        // Frames displayed so far, which animates the film grain and the jitter of TAA
+        let frame_index: u32 = 0;
+
+        // If the screen should be saved to a PNG file once it is drawn, and if the accumulated image should be saved to an EXR file
+        // The camera the image is seen from and when it was started, for the metadata of saved files
+        // (Until the first frame is drawn, it is the camera as it was set up)
+        let metadata_camera = RTCamera::look_at( camera.pos(), camera.pos() + camera.front(), camera.up(), camera.fov(), glm::vec2( screen_width as f32, screen_height as f32 ) );
+        let image_started = std::time::Instant::now();
+        let screenshot_requested = false;
+        let exr_requested = false;
+        let hdr_requested = false;
+
+        // The frame of the sequence being rendered, if the offline render is of one
+        let sequence = headless.as_ref().and_then( |headless| headless.sequence.clone() );
+        let sequence_frame = sequence.as_ref().map( |sequence| sequence.start_frame );
+
+        // Tiles which the offline render is split into, if it is too large to render whole
+        let tile_scheduler = headless.as_ref().and_then( |headless| Some( tiles::TileScheduler::new( headless.width, headless.height, headless.tile_size? ) ) );
+
+        // Checkpoints of the offline render, which it is resumed from if it was stopped
+        let checkpointer = match headless.as_ref().and_then( |headless| Some( ( headless, headless.checkpoint.as_ref()? ) ) ) {
+            Some( ( headless, path ) ) => {
+                let interval = std::time::Duration::from_secs_f32( headless.checkpoint_interval );
+                Some( checkpoint::Checkpointer::new( path, interval )? )
+            },
+            None => { None },
+        };
+
+        // Encoder for sequences which are saved as a video, which their frames are streamed into as they finish
+        // (Workers return their frames to the coordinator, which encodes them)
+        let video_encoder = match ( &headless, &sequence ) {
+            ( Some( headless ), Some( sequence ) ) if headless.format == headless::OutputFormat::Video && farm_worker.is_none() => {
+                Some( video::VideoEncoder::new( &headless.output, headless.width, headless.height, sequence.fps )? )
+            },
+            _ => { None },
+        };
+
+        // Progress of the offline render, which workers leave to their coordinator
+        let progress_reporter = headless.as_ref().filter( |_| farm_worker.is_none() ).map( |headless| headless.progress_reporter() );
+
+        // Keys held down last frame, for detecting key presses
+        let keys_prev = Vec::<VirtualKeyCode>::new();
+
+        Ok( Some( RenderLoop {
+            context,
+            inputs,
+            config,
+            config_watcher,
+            on_update,
+            events,
+            headless,
+            farm_worker,
+            camera,
+            my_vao,
+            quad_index_count: indices.len() as i32,
+            simple_shader,
+            compute_shaders,
+            workgroup_tuner,
+            shading,
+            capabilities,
+            luminance_shader,
+            accumulator,
+            restir,
+            light_tracer,
+            photon_map,
+            wavefront_queues,
+            denoiser,
+            color_lut,
+            show_lut,
+            temporal_aa,
+            post_chain,
+            #[cfg(feature = "oidn")]
+            oidn_requested,
+            #[cfg(feature = "oidn")]
+            oidn_shown,
+            #[cfg(feature = "oidn")]
+            oidn_pixels,
+            exposure_meter,
+            blue_noise_texture,
+            volumes,
+            show_volumes,
+            model_path,
+            triangles,
+            bookmarks,
+            camera_path_file,
+            camera_path,
+            camera_path_start,
+            meshes_count,
+            _triangles_ssbo,
+            meshes_ssbo,
+            _sobol_ssbo,
+            ssbo_spheres,
+            spheres_count,
+            path_guide,
+            settings,
+            post_settings,
+            #[cfg(feature = "egui")]
+            control_panel,
+            #[cfg(feature = "egui")]
+            sphere_materials,
+            time_prev,
+            time_elapsed,
+            animate,
+            screen_width,
+            screen_height,
+            focus_distance,
+            aperture_radius,
+            physical_camera,
+            lens,
+            lens_without_physical,
+            prev_camera_view,
+            camera_moved,
+            resolution_scaler,
+            texture_size_warned,
+            gpu_profiler,
+            profiler_reported,
+            render_stats,
+            stats_shown,
+            readback,
+            uploads,
+            prev_world_to_screen,
+            frame_index,
+            metadata_camera,
+            image_started,
+            screenshot_requested,
+            exr_requested,
+            hdr_requested,
+            sequence,
+            sequence_frame,
+            tile_scheduler,
+            checkpointer,
+            video_encoder,
+            progress_reporter,
+            keys_prev,
+        } ) )
+    }
+
+    /**
+     * Gets the first task of workers from their coordinator, which the render starts from.
+     *
+     * @return If there is anything to render, which workers whose coordinator has no tasks left don't have,
+     *         or an error if the coordinator couldn't be reached.
+     */
+    fn start( &mut self ) -> Result<bool, EngineError> {
+        // Workers render the tasks their coordinator hands out, starting from the first
+        if let Some( worker ) = &mut self.farm_worker {
+            match worker.next_task()? {
+                Some( task ) => { task.apply( &mut self.sequence_frame, self.tile_scheduler.as_mut() ) },
+                None => { return Ok( false ) },
+            }
+        }
+        Ok( true )
+    }
+
+    /**
+     * Deletes everything the render loop made on the GPU, once it is done, while its context is still current.
+     */
+    unsafe fn delete( self ) {
+        util::delete_vao( self.my_vao );
+        self.simple_shader.delete();
+        for ( _, shader ) in self.compute_shaders {
+            shader.delete();
+        }
+        self.workgroup_tuner.delete();
+        self.luminance_shader.delete();
+
+        self.accumulator.delete();
+        self.restir.delete();
+        self.light_tracer.delete();
+        self.photon_map.delete();
+        if let Some( wavefront_queues ) = self.wavefront_queues {
+            wavefront_queues.delete();
+        }
+        self.denoiser.delete();
+        if let Some( color_lut ) = self.color_lut {
+            color_lut.delete();
+        }
+        self.temporal_aa.delete();
+        self.post_chain.delete();
+        self.exposure_meter.delete();
+        device::current().delete_texture( self.blue_noise_texture );
+        for volume in self.volumes {
+            volume.delete();
+        }
+
+        self._triangles_ssbo.delete();
+        self.meshes_ssbo.delete();
+        self._sobol_ssbo.delete();
+        self.ssbo_spheres.delete();
+        self.path_guide.delete();
+        #[cfg(feature = "egui")]
+        drop( self.control_panel );
+
+        self.gpu_profiler.delete();
+        self.render_stats.delete();
+        self.readback.delete();
+    }
+
+    /**
+     * Renders a frame, after handling the window's input and the config's changes since the last.
+     *
+     * @return If the render goes on, false once the window is closing, the offline render is done or the coordinator has no more tasks,
+     *         or an error if the render couldn't go on.
+     */
+    fn step( &mut self ) -> Result<bool, EngineError> {
+        if self.inputs.closing.load( Ordering::Relaxed ) {
+            return Ok( false );
+        }
+
+        // (Everything logged this frame is in its span)
+        let _frame_span = tracing::debug_span!( "frame", index = self.frame_index ).entered();
+
+        // Elapsed and delta time
+        let time = std::time::Instant::now();
+        let dt = time.duration_since(self.time_prev).as_secs_f32();
+        self.time_prev = time;
+        if self.animate {
+            self.time_elapsed += dt;
+        }
+        // (Sequences are of the scene at each frame's time instead)
+        if let ( Some( sequence ), Some( frame ) ) = ( &self.sequence, self.sequence_frame ) {
+            self.time_elapsed = sequence.frame_time( frame );
+        }
+
+        self.resize();
+        self.reload_config();
+        let ( movement, rotation ) = self.handle_keys( dt )?;
+        let pointer_on_panel = self.pointer_on_panel();
+
+        // Render size, which is a multiple of the screen's size when supersampling, or a fraction of it while previewing
+        // (With dynamic resolution, it is scaled by how long the last frames took)
+        let previewing = self.post_settings.preview.is_some() && self.camera_moved;
+        let ( width, height ) = self.resize_render( dt, previewing )?;
+
+        self.draw( Frame { time, dt, width, height, previewing, movement, rotation, pointer_on_panel } )?;
+        self.finish_frame( dt )
+    }
+
+    /**
+     * Resizes the context and the viewport to the window, if it was resized.
+     */
+    fn resize( &mut self ) {
+        if let Ok( screen_size ) = self.inputs.screen_size.lock() {
+            // (Minimized windows report a size of 0, which the camera can't handle)
+            if *screen_size != ( self.screen_width, self.screen_height ) && screen_size.0 > 0 && screen_size.1 > 0 {
+                ( self.screen_width, self.screen_height ) = *screen_size;
+                self.context.resize( glutin::dpi::PhysicalSize::new( self.screen_width, self.screen_height ) );
+                unsafe {
+                    gl::Viewport( 0, 0, self.screen_width as i32, self.screen_height as i32 );
+                }
+            }
+        }
+    }
+
+    /**
+     * Applies the config's changes, which apply straight away except for the scene and the asset root.
+     */
+    fn reload_config( &mut self ) {
+        if let Some( reloaded ) = self.config_watcher.as_mut().and_then( |watcher| watcher.poll() ) {
+            // (The framebuffer follows the window as it is resized, see WindowEvent::Resized)
+            if ( reloaded.window.width, reloaded.window.height ) != ( self.config.window.width, self.config.window.height ) {
+                self.context.set_size( reloaded.window.width, reloaded.window.height );
+            }
+            if reloaded.window.vsync != self.config.window.vsync && !unsafe { self.context.set_vsync( reloaded.window.vsync ) } {
+                info!( "Vsync changes the next time the engine starts, as the window's backend can't change it while the window is open" );
+            }
+            if reloaded.render.scene != self.config.render.scene {
+                info!( "The scene changes the next time the engine starts" );
+            }
+            if reloaded.render.asset_root != self.config.render.asset_root {
+                info!( "The asset root changes the next time the engine starts" );
+            }
+            self.post_settings.scale = reloaded.render.scale;
+            if reloaded.camera.physical != self.config.camera.physical {
+                if self.physical_camera.is_none() {
+                    self.lens_without_physical = ( self.camera.fov(), self.aperture_radius );
+                }
+                self.physical_camera = reloaded.camera.physical;
+                match &self.physical_camera {
+                    Some( physical ) => { self.aperture_radius = self.camera.apply_physical( physical ); },
+                    None => {
+                        let ( fov, aperture_radius ) = self.lens_without_physical;
+                        let ( pos, ang, z_near, z_far ) = ( self.camera.pos(), self.camera.ang(), self.camera.z_near(), self.camera.z_far() );
+                        self.camera.set_view_params( pos, ang, fov, z_near, z_far );
+                        self.aperture_radius = aperture_radius;
+                    },
+                }
+            }
+            if reloaded.camera.lens != self.config.camera.lens {
+                self.lens = reloaded.camera.lens;
+            }
+            if reloaded.denoiser != self.config.denoiser {
+                self.post_settings.denoise = if reloaded.denoiser.enabled { Some( reloaded.denoiser.settings() ) } else { None };
+                self.denoiser.invalidate_guides();
+            }
+            self.config = reloaded;
+            self.events.emit( EngineEvent::ConfigReloaded );
+        }
+    }
+
+    /**
+     * Handles the keys which are held down, and those which were pressed since the last frame.
+     *
+     * @param dt The time since the last frame, in seconds.
+     *
+     * @return How far the camera is moved and turned this frame, or an error if the accumulator couldn't be changed.
+     */
+    fn handle_keys( &mut self, dt: f32 ) -> Result<( glm::Vec3, glm::Vec3 ), EngineError> {
+        let ( mut movement, mut rotation ) = ( glm::Vec3::zeros(), glm::Vec3::zeros() );
+
+        if let Ok( keys ) = self.inputs.keys.lock() {
+            for key in keys.iter() { match key {
+
+                // Movement
+                key if *key == self.config.keys.left => {
+                    movement -= self.camera.left() * dt * self.config.camera.move_speed;
+                }
+                key if *key == self.config.keys.right => {
+                    movement += self.camera.left() * dt * self.config.camera.move_speed;
+                }
+                key if *key == self.config.keys.forward => {
+                    movement += self.camera.front() * dt * self.config.camera.move_speed;
+                }
+                key if *key == self.config.keys.back => {
+                    movement -= self.camera.front() * dt * self.config.camera.move_speed;
+                }
+                key if *key == self.config.keys.up => {
+                    movement += self.camera.up() * dt * self.config.camera.move_speed;
+                }
+                key if *key == self.config.keys.down => {
+                    movement -= self.camera.up() * dt * self.config.camera.move_speed;
+                }
+
+                // Rotation
+                key if *key == self.config.keys.turn_right => {
+                    rotation.y += dt * self.config.camera.rotation_speed;
+                }
+                key if *key == self.config.keys.turn_left => {
+                    rotation.y -= dt * self.config.camera.rotation_speed;
+                }
+                key if *key == self.config.keys.turn_up && rotation.x > -glm::pi::<f32>() / 2.0 => {
+                    rotation.x -= dt * self.config.camera.rotation_speed;
+                }
+                key if *key == self.config.keys.turn_down && rotation.x < glm::pi::<f32>() / 2.0 => {
+                    rotation.x += dt * self.config.camera.rotation_speed;
+                }
+
+                _ => { }
+            } }
+
+            // Bookmarks: 1-9 recalls, ctrl + 1-9 saves
+            let ctrl_held = keys.contains( &VirtualKeyCode::LControl ) || keys.contains( &VirtualKeyCode::RControl );
+            for ( i, key ) in BOOKMARK_KEYS.iter().enumerate() {
+                if !keys.contains( key ) || self.keys_prev.contains( key ) { continue; }
+                let name = ( i + 1 ).to_string();
+
+                if ctrl_held {
+                    let bookmark = camera::CameraBookmark {
+                        position: self.camera.pos(),
+                        angle: self.camera.ang(),
+                        fov: self.camera.fov(),
+                        focus_distance: self.focus_distance,
+                    };
+                    if let Err( err ) = self.bookmarks.set( &name, bookmark ) {
+                        error!( "Failed to save camera bookmark {name}: {err}" );
+                    }
+                } else if let Some( bookmark ) = self.bookmarks.get( &name ) {
+                    self.camera.set_vars( Some( bookmark.position ), Some( bookmark.angle ), Some( bookmark.fov ), None, None );
+                    self.focus_distance = bookmark.focus_distance;
+                }
+            }
+            // Camera path: K adds a keyframe, ctrl + K saves the path
+            if keys.contains( &VirtualKeyCode::K ) && !self.keys_prev.contains( &VirtualKeyCode::K ) {
+                if ctrl_held {
+                    match self.camera_path.save( &self.camera_path_file ) {
+                        Ok( () ) => info!( "Saved camera path to {}", self.camera_path_file ),
+                        Err( err ) => error!( "{err}" ),
+                    }
+                } else {
+                    if self.camera_path.keyframes().is_empty() {
+                        self.camera_path_start = std::time::Instant::now();
+                    }
+                    self.camera_path = std::mem::replace( &mut self.camera_path, camera::CameraPath::new() ).add_keyframe( camera::CameraKeyframe {
+                        time: self.camera_path_start.elapsed().as_secs_f32(),
+                        position: self.camera.pos(),
+                        target: self.camera.pos() + self.camera.front() * self.focus_distance,
+                        fov: self.camera.fov(),
+                        focus_distance: self.focus_distance,
+                    } );
+                }
+            }
+
+            // I switches to the next integrator
+            if keys.contains( &VirtualKeyCode::I ) && !self.keys_prev.contains( &VirtualKeyCode::I ) {
+                self.settings.integrator = self.settings.integrator.next();
+                info!( "Integrator: {}", self.settings.integrator.get().name() );
+            }
+
+            // T switches to the next tonemapper
+            if keys.contains( &VirtualKeyCode::T ) && !self.keys_prev.contains( &VirtualKeyCode::T ) {
+                self.post_settings.tonemapper = self.post_settings.tonemapper.next();
+                info!( "Tonemapper: {}", self.post_settings.tonemapper.name() );
+            }
+
+            // E toggles auto-exposure, and -/+ lower/raise the exposure by half a stop
+            if keys.contains( &VirtualKeyCode::E ) && !self.keys_prev.contains( &VirtualKeyCode::E ) {
+                self.post_settings.auto_exposure = match self.post_settings.auto_exposure {
+                    Some( _ ) => { None },
+                    None => { Some( postprocess::AutoExposure {
+                        key: 0.18,
+                        min_ev: -8.0,
+                        max_ev: 8.0,
+                        speed_brighten: 1.5,
+                        speed_darken: 3.0,
+                    } ) },
+                };
+                self.exposure_meter.reset();
+            }
+            if keys.contains( &VirtualKeyCode::Minus ) && !self.keys_prev.contains( &VirtualKeyCode::Minus ) {
+                self.post_settings.exposure -= 0.5;
+                info!( "Exposure: {:+} EV", self.post_settings.exposure );
+            }
+            if keys.contains( &VirtualKeyCode::Equals ) && !self.keys_prev.contains( &VirtualKeyCode::Equals ) {
+                self.post_settings.exposure += 0.5;
+                info!( "Exposure: {:+} EV", self.post_settings.exposure );
+            }
+
+            // N toggles the denoiser
+            if keys.contains( &VirtualKeyCode::N ) && !self.keys_prev.contains( &VirtualKeyCode::N ) {
+                self.post_settings.denoise = match self.post_settings.denoise {
+                    Some( _ ) => { None },
+                    None => { Some( self.config.denoiser.settings() ) },
+                };
+                self.denoiser.invalidate_guides();
+            }
+
+            // O denoises the current image with Open Image Denoise, showing it until the accumulation restarts
+            #[cfg(feature = "oidn")]
+            if keys.contains( &VirtualKeyCode::O ) && !self.keys_prev.contains( &VirtualKeyCode::O ) {
+                self.oidn_requested = true;
+            }
+
+            // M toggles temporal anti-aliasing
+            if keys.contains( &VirtualKeyCode::M ) && !self.keys_prev.contains( &VirtualKeyCode::M ) {
+                self.post_settings.taa = match self.post_settings.taa {
+                    Some( _ ) => { None },
+                    None => { Some( taa::TaaSettings { alpha: 0.1 } ) },
+                };
+                self.temporal_aa.reset();
+            }
+
+            // X switches between rendering at 1x, 2x and 4x the screen's resolution
+            if keys.contains( &VirtualKeyCode::X ) && !self.keys_prev.contains( &VirtualKeyCode::X ) {
+                self.post_settings.supersampling = match self.post_settings.supersampling {
+                    None => { Some( postprocess::Supersampling { factor: 2, filter: postprocess::ReconstructionFilter::Mitchell } ) },
+                    Some( postprocess::Supersampling { factor: 2, filter } ) => { Some( postprocess::Supersampling { factor: 4, filter } ) },
+                    Some( _ ) => { None },
+                };
+                info!( "Supersampling: {}x", self.post_settings.supersampling.map_or( 1, |supersampling| supersampling.factor ) );
+            }
+
+            // R switches between previewing at 1/2 and 1/4 of the resolution while the camera moves, or not at all
+            // ctrl + R switches the preview between upscaling over time and bilinearly
+            if keys.contains( &VirtualKeyCode::R ) && !self.keys_prev.contains( &VirtualKeyCode::R ) {
+                let temporal = postprocess::PreviewUpscale::Temporal { alpha: 0.2 };
+                self.post_settings.preview = match ( self.post_settings.preview, ctrl_held ) {
+                    ( Some( preview ), true ) => { Some( postprocess::Preview {
+                        upscale: if preview.upscale == temporal { postprocess::PreviewUpscale::Bilinear } else { temporal },
+                        ..preview
+                    } ) },
+                    ( None, _ ) => { Some( postprocess::Preview { divisor: 2, upscale: temporal } ) },
+                    ( Some( postprocess::Preview { divisor: 2, upscale } ), false ) => { Some( postprocess::Preview { divisor: 4, upscale } ) },
+                    ( Some( _ ), false ) => { None },
+                };
+                match self.post_settings.preview {
+                    Some( preview ) => { info!( "Preview: 1/{} resolution while moving, upscaled {}", preview.divisor, if preview.upscale == temporal { "over time" } else { "bilinearly" } ) },
+                    None => { info!( "Preview: off" ) },
+                }
+            }
+
+            // F7 switches dynamic resolution between resampling the accumulation as it rescales, restarting it, and off
+            if keys.contains( &VirtualKeyCode::F7 ) && !self.keys_prev.contains( &VirtualKeyCode::F7 ) {
+                let policy = match self.post_settings.dynamic_resolution {
+                    None => { Some( postprocess::RescalePolicy::Continue ) },
+                    Some( postprocess::DynamicResolution { policy: postprocess::RescalePolicy::Continue, .. } ) => { Some( postprocess::RescalePolicy::Reset ) },
+                    Some( _ ) => { None },
+                };
+                self.post_settings.dynamic_resolution = policy.map( |policy| postprocess::DynamicResolution {
+                    target_frame_time: 0.016,
+                    min_scale: 0.25,
+                    max_scale: 1.0,
+                    policy,
+                } );
+                self.resolution_scaler = self.post_settings.dynamic_resolution.as_ref().map( postprocess::ResolutionScaler::new );
+                match policy {
+                    Some( postprocess::RescalePolicy::Continue ) => { info!( "Dynamic resolution: on, resampling the accumulation" ) },
+                    Some( postprocess::RescalePolicy::Reset ) => { info!( "Dynamic resolution: on, restarting the accumulation" ) },
+                    None => { info!( "Dynamic resolution: off" ) },
+                }
+            }
+
+            // F4 switches the accumulation between full and half precision
+            if keys.contains( &VirtualKeyCode::F4 ) && !self.keys_prev.contains( &VirtualKeyCode::F4 ) {
+                let precision = match self.accumulator.precision() {
+                    accumulator::AccumulationPrecision::Full => { accumulator::AccumulationPrecision::Half },
+                    accumulator::AccumulationPrecision::Half => { accumulator::AccumulationPrecision::Full },
+                };
+                unsafe { self.accumulator.set_precision( precision )? };
+                info!( "Accumulation precision: {precision:?}" );
+            }
+
+            // G shows the image with/without encoding it to sRGB, to compare
+            if keys.contains( &VirtualKeyCode::G ) && !self.keys_prev.contains( &VirtualKeyCode::G ) {
+                self.post_settings.srgb_encode = !self.post_settings.srgb_encode;
+                info!( "sRGB encoding: {}", if self.post_settings.srgb_encode { "on" } else { "off" } );
+            }
+
+            // [ and ] close/open the camera's aperture, for depth of field
+            if keys.contains( &VirtualKeyCode::RBracket ) && !self.keys_prev.contains( &VirtualKeyCode::RBracket ) {
+                self.aperture_radius = if self.aperture_radius > 0.0 { self.aperture_radius * 2.0 } else { 0.025 };
+                info!( "Aperture radius: {}", self.aperture_radius );
+            }
+            if keys.contains( &VirtualKeyCode::LBracket ) && !self.keys_prev.contains( &VirtualKeyCode::LBracket ) {
+                self.aperture_radius = if self.aperture_radius > 0.025 { self.aperture_radius * 0.5 } else { 0.0 };
+                info!( "Aperture radius: {}", self.aperture_radius );
+            }
+
+            // Comma/period lower/raise the white balance's temperature, and semicolon/apostrophe its tint
+            for ( key, temperature_step, tint_step ) in [
+                ( VirtualKeyCode::Comma, -500.0, 0.0 ),
+                ( VirtualKeyCode::Period, 500.0, 0.0 ),
+                ( VirtualKeyCode::Semicolon, 0.0, -0.1 ),
+                ( VirtualKeyCode::Apostrophe, 0.0, 0.1 ),
+            ] {
+                if keys.contains( &key ) && !self.keys_prev.contains( &key ) {
+                    let mut white_balance = self.post_settings.white_balance.unwrap_or( postprocess::WhiteBalance { temperature: postprocess::NEUTRAL_TEMPERATURE, tint: 0.0 } );
+                    white_balance.temperature = ( white_balance.temperature + temperature_step ).clamp( 2000.0, 15000.0 );
+                    white_balance.tint += tint_step;
+                    info!( "White balance: {:.0} K, tint {:.1}", white_balance.temperature, white_balance.tint );
+                    self.post_settings.white_balance = Some( white_balance );
+                }
+            }
+
+            // B toggles bloom
+            if keys.contains( &VirtualKeyCode::B ) && !self.keys_prev.contains( &VirtualKeyCode::B ) {
+                self.post_settings.bloom = match self.post_settings.bloom {
+                    Some( _ ) => { None },
+                    None => { Some( postprocess::Bloom { threshold: 1.0, strength: 0.1, radius: 8.0 } ) },
+                };
+            }
+
+            // L grades the image with the LUT, if there is one
+            if keys.contains( &VirtualKeyCode::L ) && !self.keys_prev.contains( &VirtualKeyCode::L ) && self.color_lut.is_some() {
+                self.show_lut = !self.show_lut;
+            }
+
+            // P pauses/resumes the scene's animation
+            if keys.contains( &VirtualKeyCode::P ) && !self.keys_prev.contains( &VirtualKeyCode::P ) {
+                self.animate = !self.animate;
+            }
+
+            // V shows/hides the volumes
+            if keys.contains( &VirtualKeyCode::V ) && !self.keys_prev.contains( &VirtualKeyCode::V ) {
+                self.show_volumes = !self.show_volumes;
+            }
+
+            // F12 saves a screenshot
+            if keys.contains( &VirtualKeyCode::F12 ) && !self.keys_prev.contains( &VirtualKeyCode::F12 ) {
+                self.screenshot_requested = true;
+            }
+
+            // F11 saves the accumulated image, before any post-processing
+            if keys.contains( &VirtualKeyCode::F11 ) && !self.keys_prev.contains( &VirtualKeyCode::F11 ) {
+                self.exr_requested = true;
+            }
+
+            // F9 turns the AOVs on/off, which are then saved along with the accumulated image by F11
+            if keys.contains( &VirtualKeyCode::F9 ) && !self.keys_prev.contains( &VirtualKeyCode::F9 ) {
+                let enabled = !self.accumulator.has_aovs();
+                unsafe { self.accumulator.set_aovs( enabled )? };
+                info!( "AOVs {}", if enabled { "on" } else { "off" } );
+            }
+
+            // F8 switches between rendering with the fragment shader, the compute shader and the wavefront stages
+            if keys.contains( &VirtualKeyCode::F8 ) && !self.keys_prev.contains( &VirtualKeyCode::F8 ) {
+                self.shading = self.shading.next();
+                info!( "Shading: {}", self.shading.name() );
+            }
+
+            // F5 shows/hides the stats in the window's title
+            if keys.contains( &VirtualKeyCode::F5 ) && !self.keys_prev.contains( &VirtualKeyCode::F5 ) {
+                self.render_stats.set_enabled( !self.render_stats.enabled() );
+                if !self.render_stats.enabled() {
+                    self.context.set_title( WINDOW_TITLE );
+                }
+            }
+
+            // F6 turns the GPU profiler on/off
+            if keys.contains( &VirtualKeyCode::F6 ) && !self.keys_prev.contains( &VirtualKeyCode::F6 ) {
+                if glapi::has_timer_queries() {
+                    self.gpu_profiler.set_enabled( !self.gpu_profiler.enabled() );
+                    info!( "GPU profiler {}", if self.gpu_profiler.enabled() { "on" } else { "off" } );
+                } else {
+                    info!( "GPU profiler unavailable, as OpenGL ES has no timestamp queries" );
+                }
+            }
+
+            // F10 saves the accumulated image to a Radiance HDR file, which is what panoramas (see RTProjection::Equirectangular) are usually kept as
+            if keys.contains( &VirtualKeyCode::F10 ) && !self.keys_prev.contains( &VirtualKeyCode::F10 ) {
+                self.hdr_requested = true;
+            }
+
+            // F1 shows/hides the control panel
+            #[cfg(feature = "egui")]
+            if keys.contains( &VirtualKeyCode::F1 ) && !self.keys_prev.contains( &VirtualKeyCode::F1 ) {
+                if let Some( control_panel ) = &mut self.control_panel {
+                    control_panel.toggle();
+                }
+            }
+
+            self.keys_prev = keys.clone();
+        }
+        Ok( ( movement, rotation ) )
+    }
+
+    /**
+     * Hands the control panel the pointer's events, if it is shown, which takes the clicks on it from the scene.
+     *
+     * @return If the pointer is on the control panel.
+     */
+    fn pointer_on_panel( &mut self ) -> bool {
+        #[cfg(feature = "egui")]
+        let pointer_on_panel = match &mut self.control_panel {
+            Some( control_panel ) => {
+                for event in self.inputs.ui_events.lock().map( |mut events| std::mem::take( &mut *events ) ).unwrap_or_default() {
+                    control_panel.handle_event( &event );
+                }
+                control_panel.wants_pointer()
+            },
+            None => { false },
+        };
+        #[cfg(not(feature = "egui"))]
+        let pointer_on_panel = false;
+        pointer_on_panel
+    }
+
+    /**
+     * Picks the size of the render, and resizes the accumulation and the passes to it if it changed.
+     *
+     * @param dt The time since the last frame, in seconds.
+     * @param previewing If the render is previewed at a lower resolution, see PostSettings::preview.
+     *
+     * @return The size of the render, in pixels, or an error if the passes couldn't be resized.
+     */
+    fn resize_render( &mut self, dt: f32, previewing: bool ) -> Result<( u32, u32 ), EngineError> {
+        let render_scale = match ( self.post_settings.dynamic_resolution.as_ref(), self.resolution_scaler.as_mut() ) {
+            ( Some( dynamic_resolution ), Some( scaler ) ) => { scaler.update( dt, dynamic_resolution ) },
+            _ => { 1.0 },
+        };
+        let render_size = self.post_settings.render_size( self.screen_width, self.screen_height, previewing, render_scale );
+        // (Supersampling large screens can ask for more than the GPU's textures fit, so the render is kept within them)
+        let ( render_width, render_height ) = self.capabilities.clamp_texture_size( render_size );
+        if ( render_width, render_height ) != render_size && !self.texture_size_warned {
+            warn!( "Rendering at {render_width}x{render_height} instead of {}x{}, the largest the GPU's textures fit", render_size.0, render_size.1 );
+            self.texture_size_warned = true;
+        }
+        if self.accumulator.size() != ( render_width, render_height ) {
+            unsafe {
+                match self.post_settings.dynamic_resolution.map( |dynamic_resolution| dynamic_resolution.policy ) {
+                    Some( postprocess::RescalePolicy::Continue ) => { self.accumulator.rescale( render_width, render_height )? },
+                    _ => { self.accumulator.resize( render_width, render_height )? },
+                }
+                self.restir.resize( render_width, render_height );
+                self.light_tracer.resize( render_width, render_height );
+                self.photon_map.resize( render_width, render_height );
+                self.denoiser.resize( render_width, render_height )?;
+                if let Some( wavefront_queues ) = self.wavefront_queues.as_mut() {
+                    wavefront_queues.resize( render_width, render_height );
+                }
+                self.temporal_aa.resize( render_width, render_height )?;
+            }
+        }
+        Ok( ( render_width, render_height ) )
+    }
+
+    /**
+     * Traces the frame into the accumulation, and post-processes it onto the screen.
+     *
+     * @param frame What the frame is rendered with.
+     *
+     * @return Ok, or an error if the frame couldn't be rendered.
+     */
+    fn draw( &mut self, frame: Frame ) -> Result<(), EngineError> {
+        let Frame { time, dt, width: render_width, height: render_height, previewing, movement, rotation, pointer_on_panel } = frame;
+        let temporal_preview = previewing && self.post_settings.preview.is_some_and( |preview| matches!( preview.upscale, postprocess::PreviewUpscale::Temporal { .. } ) );
+        unsafe {
+            self.gpu_profiler.begin_frame();
+            if self.gpu_profiler.enabled() && self.profiler_reported.elapsed().as_secs_f32() >= 1.0 {
+                self.profiler_reported = std::time::Instant::now();
+                info!( "GPU: {}", self.gpu_profiler.report() );
+            }
+
+            // Clear color and depth buffers
+            gl::ClearColor(0.04, 0.05, 0.09, 1.0);
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+
+            // Activate shader
+            let workgroup_size = self.workgroup_tuner.size();
+            // (Once the tuning is done, the shaders of the other sizes are deleted)
+            if !self.workgroup_tuner.is_tuning() && self.compute_shaders.len() > 1 {
+                let unused: Vec<wavefront::WorkgroupSize> = self.compute_shaders.keys().copied().filter( |size| *size != workgroup_size ).collect();
+                for size in unused {
+                    if let Some( shader ) = self.compute_shaders.remove( &size ) {
+                        self.uploads.forget( shader.pid );
+                        shader.delete();
+                    }
+                }
+            }
+            let rt_shader = match self.shading {
+                wavefront::Shading::Compute | wavefront::Shading::Wavefront => {
+                    // (If the compute shader can't be built, the frame falls back to the fragment shader, which always is)
+                    if let Entry::Vacant( entry ) = self.compute_shaders.entry( workgroup_size ) {
+                        let path = assets::path( "shaders/raytracing.frag" );
+                        match device::current().create_pipeline(
+                            &[( &path, shader::ShaderType::Compute )],
+                            &[RTIntegrator::shader_defines().as_slice(), &[( "COMPUTE", 1 )], &workgroup_size.defines()].concat()
+                        ) {
+                            Ok( shader ) => { entry.insert( shader ); self.events.emit( EngineEvent::ShaderReloaded { path } ); },
+                            Err( err ) => { error!( "{err}\nFalling back to fragment shading" ); self.shading = wavefront::Shading::Fragment; },
+                        }
+                    }
+                    self.compute_shaders.get( &workgroup_size ).unwrap_or( &self.simple_shader )
+                },
+                wavefront::Shading::Fragment => { &self.simple_shader },
+            };
+            rt_shader.activate();
+
+            // Update camera with player movement
+            self.camera.set_vars(
+                Some( self.camera.pos() + movement ),
+                Some( self.camera.ang() + rotation ),
+                None,
+                None,
+                None
+            );
+
+            // Let the application the engine is embedded in change the camera and settings, see Engine::on_update()
+            if let Some( on_update ) = self.on_update.as_mut() {
+                on_update( &mut FrameUpdate { time: self.time_elapsed, dt, camera: &mut self.camera, lens: &mut self.lens, settings: &mut self.settings, post_settings: &mut self.post_settings } );
+            }
+
+            // While the camera moves, its depth of field is previewed by the post chain instead of sampled, as the lens takes many frames to converge
+            // (Once it stops, the lens is sampled again, which restarts the accumulation)
+            let camera_view = ( self.camera.rts(), self.camera.fov(), self.focus_distance );
+            let camera_moving = self.prev_camera_view.as_ref() != Some( &camera_view );
+            self.prev_camera_view = Some( camera_view );
+            self.camera_moved = camera_moving;
+            let dof_preview = ( self.post_settings.dof_preview && camera_moving && self.aperture_radius > 0.0 )
+                .then( || postprocess::DofPreview { aperture_radius: self.aperture_radius, focus_distance: self.focus_distance, fov: self.camera.fov() } );
+
+            // Create RTCamera and pass to shader
+            // This camera is a lot like the normal Camera, but only carries the necessary variables for the shader to use
+            let mut rtcamera = RTCamera {
+                screen_size: glm::vec2( render_width as f32, render_height as f32 ),
+                fov: self.camera.fov(),
+                focus_distance: self.focus_distance,
+                pos: self.camera.pos().into(),
+                local_to_world: self.camera.rts(),
+                projection: self.lens.projection,
+                aperture_radius: if dof_preview.is_some() { 0.0 } else { self.aperture_radius },
+                distortion: self.lens.distortion,
+                chromatic_aberration: self.lens.chromatic_aberration,
+                stereo: self.lens.stereo,
+                pixel_jitter: glm::zero(),
+                shutter_close: None,
+                window: self.tile_scheduler.as_ref().map( |tiles| tiles.window() ),
+            };
+
+            // Sequences may be seen from a camera which moves with each frame's time
+            // (Through a physical camera, it is blurred over the time its shutter is open)
+            let shutter_time = self.physical_camera.map( |physical| physical.shutter_time );
+            if let Some( view ) = self.sequence.as_ref().zip( self.sequence_frame ).and_then( |( sequence, frame )| sequence.camera( frame, self.camera.fov(), shutter_time, rtcamera.screen_size ) ) {
+                rtcamera.pos = view.pos;
+                rtcamera.local_to_world = view.local_to_world;
+                rtcamera.fov = view.fov;
+                rtcamera.focus_distance = view.focus_distance;
+                rtcamera.shutter_close = view.shutter_close;
+            }
+
+            // Focus on the clicked pixel, if any
+            // (The click is on the screen, which is smaller than the render when supersampling)
+            if let Some( click ) = self.inputs.focus_click.lock().ok().and_then( |mut click| click.take() ).filter( |_| !pointer_on_panel ) {
+                let pixel = click * ( render_width as f32 / self.screen_width as f32 );
+                if let Some( new_focus_distance ) = rtcamera.focus_on_pixel( pixel, self.ssbo_spheres.data(), &self.triangles ) {
+                    self.focus_distance = new_focus_distance;
+                }
+            }
+
+            // Crop the render to the dragged region, if any, where right clicking without dragging renders everything again
+            if let Some( ( start, end ) ) = self.inputs.crop_drag.lock().ok().and_then( |mut drag| drag.take() ).filter( |_| !pointer_on_panel ) {
+                self.settings.crop = ( glm::abs( &( end - start ) ).min() >= 4.0 )
+                    .then( || RTCrop::from_drag( start, end, glm::vec2( self.screen_width as f32, self.screen_height as f32 ) ) );
+            }
+
+            // Restart the accumulation if the camera or settings changed since last frame
+            // (The camera is watched before the jitter is applied, as the jitter changes every frame by design)
+            // (Its size is left out, as resizing the accumulator restarts it unless it is rescaled on purpose, see RescalePolicy)
+            self.accumulator.watch( "camera", &RTCamera { screen_size: glm::zero(), ..rtcamera.clone() } );
+            self.metadata_camera = RTCamera { window: None, ..rtcamera.clone() };
+            // (Switching shaders restarts it too, and the settings are sent to the shader which is switched to)
+            // (The wavefront stages leave what they don't cover to the compute shader)
+            let wavefront_shading = self.shading == wavefront::Shading::Wavefront && wavefront::Wavefront::supports( &self.settings, &rtcamera, self.accumulator.has_aovs(), self.show_volumes );
+            let shading_used = if self.shading == wavefront::Shading::Wavefront && !wavefront_shading { wavefront::Shading::Compute } else { self.shading };
+            let shading_changed = self.accumulator.watch( "shading", &shading_used );
+            if shading_changed && shading_used != self.shading {
+                info!( "The wavefront shading doesn't cover the current settings, so the compute shader is used as it is" );
+            }
+            // (Each shader has uniforms of its own, so the settings are sent to whichever hasn't had them yet)
+            self.accumulator.watch( "settings", &self.settings );
+            if self.uploads.needs_upload( rt_shader.pid, "settings", &self.settings ) {
+                self.settings.send_uniform( rt_shader, "settings" );
+            }
+            // (TAA and the temporal preview need the jitter to change while moving too, when the accumulation keeps restarting)
+            let jitter_index = if self.post_settings.taa.is_some() || temporal_preview { self.frame_index } else { self.accumulator.frame_count() };
+            rtcamera.set_frame_jitter( JitterSequence::Halton23, jitter_index );
+            // (Only the jitter changes every frame while the camera stands still, so the rest is only sent when it changes too)
+            if self.uploads.needs_upload( rt_shader.pid, "camera", &RTCamera { pixel_jitter: glm::zero(), ..rtcamera.clone() } ) {
+                rtcamera.send_uniform( rt_shader, "camera" );
+            } else {
+                rtcamera.send_jitter_uniform( rt_shader, "camera" );
+            }
+            gl::Uniform1ui( rt_shader.get_uniform_location( "motionVectors" ), self.prev_world_to_screen.is_some() as u32 );
+            if let Some( prev_world_to_screen ) = self.prev_world_to_screen {
+                gl::UniformMatrix4fv( rt_shader.get_uniform_location( "prevWorldToScreen" ), 1, gl::FALSE, prev_world_to_screen.as_ptr() );
+            }
+            self.prev_world_to_screen = rtcamera.world_to_screen();
+
+            // Update sphere objects, restarting the accumulation if they changed
+            #[allow(unused_mut)]
+            let mut spheres = scene_spheres( self.time_elapsed );
+            #[cfg(feature = "egui")]
+            for ( sphere, material ) in spheres.iter_mut().zip( &self.sphere_materials ) {
+                if let Some( material ) = material {
+                    sphere.material = material.clone();
+                }
+            }
+            // (The path guide has to relearn when the lights move)
+            if self.accumulator.watch( "spheres", &spheres ) {
+                self.path_guide.reset();
+            }
+            let ( bounds_min, bounds_max ) = raytracing::scene_bounds( &spheres, self.meshes_ssbo.data() );
+            if self.uploads.needs_upload( rt_shader.pid, "sceneBounds", &( bounds_min, bounds_max ) ) {
+                gl::Uniform3fv( rt_shader.get_uniform_location( "sceneBoundsMin" ), 1, bounds_min.as_ptr() );
+                gl::Uniform3fv( rt_shader.get_uniform_location( "sceneBoundsMax" ), 1, bounds_max.as_ptr() );
+            }
+            self.accumulator.watch( "volumes", &self.show_volumes );
+            // (The spheres and their materials are only copied into the buffer when they change, which the shaders all read)
+            self.gpu_profiler.begin_scope( "upload" );
+            if self.uploads.needs_upload( self.ssbo_spheres.id(), "spheres", &spheres ) {
+                self.ssbo_spheres.update_data( spheres );
+            }
+            self.gpu_profiler.end_scope( "upload" );
+            if self.uploads.needs_upload( rt_shader.pid, "objectCounts", &( self.spheres_count, self.meshes_count ) ) {
+                gl::Uniform1i( rt_shader.get_uniform_location( "spheresCount" ), self.spheres_count);
+                gl::Uniform1i( rt_shader.get_uniform_location( "meshesCount" ), self.meshes_count as i32);
+            }
+
+            // Draw into the accumulation buffer
+            // (The denoiser's guides only change when the accumulation restarts, so they are rendered then)
+            // (Temporal filtering, TAA and the temporal preview need them every frame though, for the motion vectors)
+            #[allow(unused_mut)]
+            let mut needs_guides = self.post_settings.denoise.is_some() || self.post_settings.taa.is_some() || dof_preview.is_some() || temporal_preview;
+            #[cfg(feature = "oidn")]
+            {
+                needs_guides |= self.oidn_requested;
+            }
+            let temporal = self.post_settings.denoise.is_some_and( |denoise| denoise.temporal.is_some() );
+            let render_guides = needs_guides && ( self.accumulator.frame_count() == 0 || !self.denoiser.has_guides() || temporal || self.post_settings.taa.is_some() || temporal_preview );
+            if self.accumulator.frame_count() == 0 {
+                self.events.emit( EngineEvent::AccumulationReset );
+            }
+            self.accumulator.begin_frame( rt_shader, 0 );
+            gl::ActiveTexture( gl::TEXTURE1 );
+            gl::BindTexture( gl::TEXTURE_2D, self.blue_noise_texture );
+            gl::Uniform1i( rt_shader.get_uniform_location( "blueNoiseTexture" ), 1 );
+            self.path_guide.send_uniforms( rt_shader );
+            self.settings.integrator.get().send_uniforms( rt_shader );
+            volumes::send_uniforms( rt_shader, if self.show_volumes { &self.volumes } else { &[] } );
+            gl::BindVertexArray(self.my_vao);
+            let draw_quad = || gl::DrawElements(
+                gl::TRIANGLES,
+                self.quad_index_count,
+                gl::UNSIGNED_INT,
+                ptr::null()
+            );
+            // (The compute shader is dispatched over the render instead, and what it stores is made visible to the following passes)
+            let trace = || match self.shading {
+                wavefront::Shading::Compute | wavefront::Shading::Wavefront => {
+                    workgroup_size.dispatch( render_width, render_height );
+                    gl::MemoryBarrier( gl::SHADER_IMAGE_ACCESS_BARRIER_BIT | gl::TEXTURE_FETCH_BARRIER_BIT | gl::TEXTURE_UPDATE_BARRIER_BIT | gl::FRAMEBUFFER_BARRIER_BIT );
+                },
+                wavefront::Shading::Fragment => { draw_quad() },
+            };
+            if wavefront_shading && self.wavefront_queues.is_none() {
+                self.wavefront_queues = Some( wavefront::Wavefront::new( render_width, render_height ) );
+            }
+
+            self.gpu_profiler.begin_scope( "trace" );
+            self.render_stats.begin_frame( rt_shader, dt );
+            let tuning_workgroups = self.shading != wavefront::Shading::Fragment && self.workgroup_tuner.is_tuning();
+            if tuning_workgroups {
+                self.workgroup_tuner.begin_frame();
+            }
+
+            // (Integrators which trace paths from the lights do so first)
+            if self.settings.integrator.get().traces_lights() {
+                self.light_tracer.begin_pass( rt_shader );
+                trace();
+                self.light_tracer.end_pass( rt_shader );
+            }
+
+            // (Caustic photons are traced first)
+            if self.settings.caustics {
+                self.photon_map.begin_pass( rt_shader );
+                trace();
+                self.photon_map.end_pass( rt_shader );
+            }
+
+            // (With ReSTIR, the reservoirs are prepared first)
+            let passes: &[restir::RestirPass] = if self.settings.restir {
+                &[restir::RestirPass::Initial, restir::RestirPass::Spatial, restir::RestirPass::Shade]
+            } else {
+                &[restir::RestirPass::Shade]
+            };
+            for pass in passes {
+                self.restir.begin_pass( rt_shader, *pass );
+                match self.wavefront_queues.as_ref().filter( |_| wavefront_shading ) {
+                    Some( wavefront_queues ) => { wavefront_queues.render( rt_shader, &self.settings, &trace ) },
+                    None => { trace() },
+                }
+                self.restir.end_pass();
+            }
+            self.accumulator.end_frame();
+            self.gpu_profiler.end_scope( "trace" );
+            if tuning_workgroups {
+                if let Some( times ) = self.workgroup_tuner.end_frame() {
+                    let times: Vec<String> = times.iter().map( |( size, milliseconds )| format!( "{size} {milliseconds:.2} ms" ) ).collect();
+                    info!( "Workgroup size: {} (of {})", self.workgroup_tuner.size(), times.join( ", " ) );
+                }
+            }
+            // (Tiled renders are timed from their first tile)
+            if self.accumulator.frame_count() == 1 && self.tile_scheduler.as_ref().is_none_or( |tiles| tiles.progress().0 == 0 ) {
+                self.image_started = time;
+            }
+            if render_guides {
+                self.gpu_profiler.begin_scope( "guides" );
+                self.denoiser.begin_guide_pass( rt_shader );
+                trace();
+                self.denoiser.end_guide_pass( rt_shader );
+                self.gpu_profiler.end_scope( "guides" );
+            }
+            if self.settings.path_guiding {
+                self.path_guide.update();
+            }
+
+            // Measure the accumulated image for auto-exposure
+            if let Some( auto_exposure ) = &self.post_settings.auto_exposure {
+                self.luminance_shader.activate();
+                self.exposure_meter.begin_pass( &self.luminance_shader, self.accumulator.texture(), 0 );
+                draw_quad();
+                self.exposure_meter.end_pass( auto_exposure, dt );
+            }
+
+            // Handle the readbacks which have arrived
+            for ( request, pixels ) in self.readback.poll() {
+                match ( request, pixels ) {
+                    ( ReadbackRequest::Screenshot( path, metadata ), readback::ReadbackImage::Screen( screen ) ) => {
+                        match output::save_png_image( &path, &screen, &metadata ) {
+                            Ok( () ) => info!( "Saved a screenshot to {path}" ),
+                            Err( err ) => error!( "{err}" ),
+                        }
+                    },
+                    #[cfg(feature = "oidn")]
+                    ( ReadbackRequest::OidnColor, readback::ReadbackImage::Texture( pixels ) ) => { self.oidn_pixels[0] = Some( pixels ) },
+                    #[cfg(feature = "oidn")]
+                    ( ReadbackRequest::OidnAlbedo, readback::ReadbackImage::Texture( pixels ) ) => { self.oidn_pixels[1] = Some( pixels ) },
+                    #[cfg(feature = "oidn")]
+                    ( ReadbackRequest::OidnNormal, readback::ReadbackImage::Texture( pixels ) ) => { self.oidn_pixels[2] = Some( pixels ) },
+                    _ => { },
+                }
+            }
+
+            // Denoise the accumulated image with Open Image Denoise, when asked to
+            // (The image and guides are read back first, and denoised once they arrive)
+            let image = self.accumulator.texture();
+            #[cfg(feature = "oidn")]
+            let image = {
+                if self.accumulator.frame_count() == 1 {
+                    self.oidn_shown = false;
+                }
+                if self.oidn_requested {
+                    self.oidn_requested = false;
+                    self.readback.read_texture( image, render_width, render_height, ReadbackRequest::OidnColor );
+                    self.readback.read_texture( self.denoiser.albedo_texture(), render_width, render_height, ReadbackRequest::OidnAlbedo );
+                    self.readback.read_texture( self.denoiser.normal_depth_texture(), render_width, render_height, ReadbackRequest::OidnNormal );
+                }
+                if let [Some( color ), Some( albedo ), Some( normal )] = &self.oidn_pixels {
+                    match self.denoiser.denoise_oidn( color, albedo, normal ) {
+                        Ok( pixels ) => {
+                            self.oidn_shown = true;
+                            match output::save_hdr( "denoised.hdr", render_width, render_height, &pixels ) {
+                                Ok( () ) => info!( "Saved the denoised image to denoised.hdr" ),
+                                Err( err ) => error!( "{err}" ),
+                            }
+                        },
+                        Err( err ) => error!( "{err}" ),
+                    }
+                    self.oidn_pixels = [None, None, None];
+                }
+                if self.oidn_shown { self.denoiser.texture() } else { image }
+            };
+            #[cfg(feature = "oidn")]
+            let oidn_shown = self.oidn_shown;
+            #[cfg(not(feature = "oidn"))]
+            let oidn_shown = false;
+
+            // Post-process the image onto the screen
+            let mut pass_context = postchain::PassContext {
+                settings: &self.post_settings,
+                denoiser: &mut self.denoiser,
+                temporal_aa: &mut self.temporal_aa,
+                exposure_meter: &self.exposure_meter,
+                camera_exposure: self.physical_camera.map_or( 0.0, |physical| physical.exposure() ),
+                color_lut: self.color_lut.as_ref().filter( |_| self.show_lut ),
+                dof: dof_preview,
+                frame_count: self.accumulator.frame_count(),
+                frame_index: self.frame_index,
+                screen_size: ( self.screen_width, self.screen_height ),
+                denoised: oidn_shown,
+                previewing,
+                profiler: &mut self.gpu_profiler,
+                draw_quad: &draw_quad,
+            };
+            pass_context.profiler.begin_scope( "post" );
+            self.post_chain.render( postchain::PassImage { texture: image, width: render_width, height: render_height }, &mut pass_context )?;
+            pass_context.profiler.end_scope( "post" );
+
+            // Show the stats, where the acceleration structure is the scene's bounds and a bounding box per mesh
+            if self.render_stats.enabled() && self.stats_shown.elapsed().as_secs_f32() >= 0.5 {
+                self.stats_shown = std::time::Instant::now();
+                let frame_stats = self.render_stats.frame_stats( self.accumulator.frame_count() * self.settings.rays_per_frag, 1 + self.meshes_count );
+                self.context.set_title( &format!( "{WINDOW_TITLE} - {frame_stats}" ) );
+            }
+            self.frame_index = self.frame_index.wrapping_add( 1 );
+
+            // Save the accumulated image
+            let metadata = || render_metadata( &self.model_path, &self.settings, &self.metadata_camera, self.accumulator.frame_count(), self.image_started.elapsed(), self.sequence_frame );
+            if self.exr_requested {
+                self.exr_requested = false;
+                let path = output::timestamped_path( "render", "exr" );
+                // (With AOVs, they are layered into the same file)
+                let saved = match self.accumulator.has_aovs() {
+                    true => { output::save_layered_exr( &path, &self.accumulator, &metadata() ) },
+                    false => { output::save_exr( &path, render_width, render_height, &self.accumulator.read_pixels(), output::ExrPrecision::Float, &metadata() ) },
+                };
+                match saved {
+                    Ok( () ) => info!( "Saved the accumulated image to {path}" ),
+                    Err( err ) => error!( "{err}" ),
+                }
+            }
+
+            if self.hdr_requested {
+                self.hdr_requested = false;
+                let path = output::timestamped_path( "render", "hdr" );
+                match output::save_hdr( &path, render_width, render_height, &self.accumulator.read_pixels() ) {
+                    Ok( () ) => info!( "Saved the accumulated image to {path}" ),
+                    Err( err ) => error!( "{err}" ),
+                }
+            }
+
+            // Read back what was drawn, before it is swapped away, to be saved once it arrives
+            if self.screenshot_requested {
+                self.screenshot_requested = false;
+                self.readback.read_screen( self.screen_width, self.screen_height, ReadbackRequest::Screenshot( output::timestamped_path( "screenshot", "png" ), metadata() ) );
+            }
+
+            // Draw the control panel over the screen (after the screenshot, which is of the render alone)
+            // (What is changed with it is rendered from the next frame, which restarts the accumulation if it has to)
+            #[cfg(feature = "egui")]
+            if let Some( control_panel ) = &mut self.control_panel {
+                let denoise = self.post_settings.denoise;
+                control_panel.draw( self.screen_width, self.screen_height, ui::PanelValues {
+                    settings: &mut self.settings,
+                    post_settings: &mut self.post_settings,
+                    camera: &mut self.camera,
+                    focus_distance: &mut self.focus_distance,
+                    aperture_radius: &mut self.aperture_radius,
+                    spheres: self.ssbo_spheres.data(),
+                    materials: &mut self.sphere_materials,
+                } );
+                if self.post_settings.denoise != denoise {
+                    self.denoiser.invalidate_guides();
+                }
+            }
+        }
+        Ok( () )
+    }
+
+    /**
+     * Hands the finished task to the coordinator, or saves the offline render once it has all its samples, and shows the frame.
+     *
+     * @param dt The time since the last frame, in seconds.
+     *
+     * @return If the render goes on, or an error if the render couldn't be saved.
+     */
+    fn finish_frame( &mut self, dt: f32 ) -> Result<bool, EngineError> {
+        self.events.emit( EngineEvent::FrameCompleted { frame_index: self.frame_index, samples: self.accumulator.frame_count(), frame_time: dt } );
+
+        // Workers return their task once it has all its samples, and move on to the next one
+        if let ( Some( worker ), Some( headless ) ) = ( &mut self.farm_worker, &self.headless ) {
+            if self.accumulator.frame_count() >= headless.samples {
+                let ( pixels, screen ) = unsafe { ( self.accumulator.read_pixels(), output::read_screen( self.screen_width, self.screen_height ) ) };
+                match worker.finish_task( &pixels, &screen ) {
+                    Ok( Some( task ) ) => {
+                        task.apply( &mut self.sequence_frame, self.tile_scheduler.as_mut() );
+                        self.accumulator.reset();
+                    },
+                    Ok( None ) => { info!( "The coordinator has no more tasks" ); return Ok( false ) },
+                    Err( err ) => { return Err( err ) },
+                }
+            }
+            self.context.swap_buffers()?;
+            return Ok( true );
+        }
+
+        // Long offline renders are checkpointed now and then, so they can be resumed if they are stopped
+        // (They are told apart by what was actually rendered, which embedders can change in on_update)
+        if let ( Some( checkpointer ), Some( headless ) ) = ( &mut self.checkpointer, &self.headless ) {
+            if checkpointer.is_due() {
+                let hash = headless.render_hash( &self.settings, &self.metadata_camera );
+                unsafe { checkpointer.update( &mut self.accumulator, hash )? };
+            }
+        }
+
+        // Offline renders report how far they have come every frame
+        if let ( Some( progress_reporter ), Some( headless ) ) = ( &mut self.progress_reporter, &self.headless ) {
+            let frames_done = self.sequence.as_ref().zip( self.sequence_frame ).map_or( 0, |( sequence, frame )| frame - sequence.start_frame );
+            let tiles_done = self.tile_scheduler.as_ref().map_or( 0, |tiles| tiles.progress().0 );
+            progress_reporter.report( frames_done, tiles_done, self.accumulator.frame_count().min( headless.samples ) );
+        }
+
+        // Offline renders are saved once they have all their samples, which ends them
+        // (Sequences go on to their next frame instead, until the last one is saved)
+        // (Tiled renders go on to their next tile instead, until the last one is stitched in)
+        let image_done = match ( &self.headless, &mut self.tile_scheduler ) {
+            ( Some( headless ), Some( tiles ) ) if self.accumulator.frame_count() >= headless.samples => {
+                let more_tiles = unsafe { tiles.finish_tile( &self.accumulator ) };
+                self.accumulator.reset();
+                let ( tile, tile_count ) = tiles.progress();
+                info!( "Finished tile {tile} of {tile_count}" );
+                if !more_tiles {
+                    tiles.restart();
+                }
+                !more_tiles
+            },
+            ( Some( headless ), None ) => { self.accumulator.frame_count() >= headless.samples },
+            _ => { false },
+        };
+        if let Some( headless ) = &self.headless {
+            if image_done {
+                let path = match self.sequence_frame {
+                    Some( frame ) if self.video_encoder.is_none() => { sequence::SequenceSettings::frame_path( &headless.output, frame ) },
+                    _ => { headless.output.clone() },
+                };
+                let metadata = render_metadata( &self.model_path, &self.settings, &self.metadata_camera, headless.samples, self.image_started.elapsed(), self.sequence_frame );
+                let saved = match ( &mut self.video_encoder, &self.tile_scheduler ) {
+                    ( Some( encoder ), Some( tiles ) ) => { encoder.write_frame( tiles.screen() ) },
+                    ( Some( encoder ), None ) => { encoder.write_frame( &unsafe { output::read_screen( self.screen_width, self.screen_height ) } ) },
+                    ( None, tiles ) => { unsafe { headless.save( &path, &self.accumulator, tiles.as_ref(), &metadata ) } },
+                };
+                let saved = saved.and_then( |()| match ( &headless.aovs, self.sequence_frame ) {
+                    ( Some( headless::AovOutput::Files( aovs ) ), Some( frame ) ) => { unsafe { output::save_aovs( &sequence::SequenceSettings::frame_path( aovs, frame ), &self.accumulator, &metadata ) } },
+                    ( Some( headless::AovOutput::Files( aovs ) ), None ) => { unsafe { output::save_aovs( aovs, &self.accumulator, &metadata ) } },
+                    _ => { Ok( () ) },
+                } );
+                match saved {
+                    Ok( () ) => match self.sequence_frame {
+                        Some( frame ) if self.video_encoder.is_some() => info!( "Encoded frame {frame} into {path}" ),
+                        _ => info!( "Saved the render to {path}" ),
+                    },
+                    Err( err ) => { return Err( err ) },
+                }
+
+                match ( &self.sequence, self.sequence_frame ) {
+                    ( Some( sequence ), Some( frame ) ) if frame < sequence.last_frame => {
+                        self.sequence_frame = Some( frame + 1 );
+                        self.accumulator.reset();
+                    },
+                    _ => {
+                        if let Some( checkpointer ) = self.checkpointer.take() {
+                            checkpointer.finish();
+                        }
+                        if let Some( encoder ) = self.video_encoder.take() {
+                            match encoder.finish() {
+                                Ok( () ) => info!( "Finished the video {path}" ),
+                                Err( err ) => error!( "{err}" ),
+                            }
+                        }
+                        self.events.emit( EngineEvent::RenderFinished { path } );
+                        return Ok( false );
+                    },
+                }
+            }
+        }
+
+        // "Flip" screen (we use "double buffering" to avoid artifacts)
+        self.context.swap_buffers()?;
+        Ok( true )
+    }
+}
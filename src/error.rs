@@ -0,0 +1,101 @@
+use thiserror::Error;
+
+/**
+ * Enum for the errors of the engine, which every fallible function returns.
+ * Each has a code, such as "OUTPUT::FILE_NOT_CREATED", which is shown as ERROR::<code> followed by the details on the next lines.
+ */
+#[derive(Debug, Error)]
+pub enum EngineError {
+    // A shader which couldn't be read, compiled or linked
+    #[error( "ERROR::SHADER::{reason}{}{}", details_lines( .path ), details_lines( .log ) )]
+    Shader { reason: &'static str, path: String, log: String },
+
+    // A context, window, framebuffer or GL call which failed
+    #[error( "ERROR::{code}{}", details_lines( .details ) )]
+    Gl { code: &'static str, details: String },
+
+    // A file, process or connection which couldn't be read or written
+    #[error( "ERROR::{code}\n{}", io_details( .path, .source ) )]
+    Io { code: &'static str, path: String, #[source] source: std::io::Error },
+
+    // Arguments, scene files and messages which are invalid (camera paths, checkpoints, LUTs, volumes)
+    #[error( "ERROR::{code}{}", details_lines( .details ) )]
+    Parse { code: &'static str, details: String },
+
+    // Images, models and textures which couldn't be loaded or saved
+    #[error( "ERROR::{code}{}", details_lines( .details ) )]
+    Asset { code: &'static str, details: String },
+
+    // A render which couldn't go on, such as one whose workers were lost
+    #[error( "ERROR::{code}{}", details_lines( .details ) )]
+    Render { code: &'static str, details: String },
+}
+
+/**
+ * Formats the details of an error, on the lines after its code.
+ */
+fn details_lines( details: &str ) -> String {
+    if details.is_empty() { String::new() } else { format!( "\n{details}" ) }
+}
+
+/**
+ * Formats an IO error, after the path or address it was of, if there is one.
+ */
+fn io_details( path: &str, source: &std::io::Error ) -> String {
+    if path.is_empty() { source.to_string() } else { format!( "{path}: {source}" ) }
+}
+
+/**
+ * EngineError functions.
+ */
+impl EngineError {
+    /**
+     * Creates an error of a shader.
+     *
+     * @param reason Why the shader failed, such as "COMPILATION_FAILED".
+     * @param path The path of the shader, or an empty string if it is of the whole program.
+     * @param log The log of the compiler or linker.
+     */
+    pub fn shader( reason: &'static str, path: impl Into<String>, log: impl Into<String> ) -> EngineError {
+        EngineError::Shader { reason, path: path.into(), log: log.into() }
+    }
+
+    /**
+     * Creates an error of OpenGL or the windowing.
+     */
+    pub fn gl( code: &'static str, details: impl Into<String> ) -> EngineError {
+        EngineError::Gl { code, details: details.into() }
+    }
+
+    /**
+     * Creates an error of reading or writing.
+     *
+     * @param code The code of the error.
+     * @param path The path or address which was read or written, or an empty string.
+     * @param source The error.
+     */
+    pub fn io( code: &'static str, path: impl Into<String>, source: std::io::Error ) -> EngineError {
+        EngineError::Io { code, path: path.into(), source }
+    }
+
+    /**
+     * Creates an error of something invalid which was parsed.
+     */
+    pub fn parse( code: &'static str, details: impl Into<String> ) -> EngineError {
+        EngineError::Parse { code, details: details.into() }
+    }
+
+    /**
+     * Creates an error of an asset.
+     */
+    pub fn asset( code: &'static str, details: impl Into<String> ) -> EngineError {
+        EngineError::Asset { code, details: details.into() }
+    }
+
+    /**
+     * Creates an error of a render.
+     */
+    pub fn render( code: &'static str, details: impl Into<String> ) -> EngineError {
+        EngineError::Render { code, details: details.into() }
+    }
+}
@@ -0,0 +1,81 @@
+use std::sync::mpsc::{ self, Receiver, Sender };
+
+/**
+ * Enum for what happens while the engine renders, which is sent to everything subscribed to it (see EventBus),
+ * so UIs and scripts can react to the renderer without polling it.
+ */
+#[derive(Clone, PartialEq, Debug)]
+pub enum EngineEvent {
+    SceneLoaded { path: String, triangles: usize, meshes: usize }, // The scene was loaded and uploaded, before the first frame is rendered
+    AccumulationReset, // The accumulation restarted (such as because the camera or the settings changed), so the next frame is its first
+    ShaderReloaded { path: String }, // A shader was built again while rendering, such as the compute shader for another work group size
+    ConfigReloaded, // The config file changed and was loaded again, see config::ConfigWatcher
+    FrameCompleted { frame_index: u32, samples: u32, frame_time: f32 }, // A frame was rendered, with how many are accumulated, and how long it took in seconds
+    RenderFinished { path: String }, // An offline render was saved for the last time, which ends it
+}
+
+/**
+ * Struct for sending the engine's events to its subscribers, which each get their own channel.
+ * Subscribers whose receivers were dropped are forgotten the next time an event is sent.
+ */
+pub struct EventBus {
+    subscribers: Vec<Sender<EngineEvent>>,
+}
+
+/**
+ * EventBus functions.
+ */
+impl EventBus {
+    /**
+     * Creates a bus without subscribers.
+     */
+    pub fn new() -> EventBus {
+        EventBus { subscribers: Vec::new() }
+    }
+
+    /**
+     * Subscribes to the events, which are received in the order they were sent, from any thread.
+     *
+     * @return The receiver of the events sent from now on.
+     */
+    pub fn subscribe( &mut self ) -> Receiver<EngineEvent> {
+        let ( sender, receiver ) = mpsc::channel();
+        self.subscribers.push( sender );
+        receiver
+    }
+
+    /**
+     * Sends an event to every subscriber.
+     *
+     * @param event The event.
+     */
+    pub fn emit( &mut self, event: EngineEvent ) {
+        self.subscribers.retain( |subscriber| subscriber.send( event.clone() ).is_ok() );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_reach_every_subscriber() {
+        let mut events = EventBus::new();
+        let ( first, second ) = ( events.subscribe(), events.subscribe() );
+        events.emit( EngineEvent::AccumulationReset );
+        events.emit( EngineEvent::ConfigReloaded );
+        for receiver in [first, second] {
+            assert_eq!( receiver.try_iter().collect::<Vec<_>>(), vec![EngineEvent::AccumulationReset, EngineEvent::ConfigReloaded] );
+        }
+    }
+
+    #[test]
+    fn dropped_subscribers_are_forgotten() {
+        let mut events = EventBus::new();
+        drop( events.subscribe() );
+        let kept = events.subscribe();
+        events.emit( EngineEvent::AccumulationReset );
+        assert_eq!( events.subscribers.len(), 1 );
+        assert_eq!( kept.try_recv(), Ok( EngineEvent::AccumulationReset ) );
+    }
+}
@@ -0,0 +1,521 @@
+use std::collections::{ BTreeMap, HashMap, VecDeque };
+use std::io::{ Read, Write };
+use std::net::{ TcpListener, TcpStream };
+use std::sync::{ mpsc, Arc, Mutex };
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use serde::{ Serialize, Deserialize };
+use tracing::{ info, warn };
+
+use crate::error::EngineError;
+use crate::headless::{ HeadlessSettings, OutputFormat };
+use crate::sequence::SequenceSettings;
+use crate::tiles::TileScheduler;
+use crate::video::VideoEncoder;
+
+// The kinds of messages sent between the coordinator and its workers, which each start with their kind and the length of their payload
+const MESSAGE_JOB: u8 = 1;      // Coordinator -> worker: the arguments of the render, as JSON
+const MESSAGE_TASK: u8 = 2;     // Coordinator -> worker: a task to render, as JSON
+const MESSAGE_DONE: u8 = 3;     // Coordinator -> worker: there are no more tasks
+const MESSAGE_RESULT: u8 = 4;   // Worker -> coordinator: a rendered task, see encode_result()
+
+// The longest payloads which are read, as the length comes from the other end, which could be anything that connects
+const MAX_JOB_SIZE: u64 = 1 << 20;  // The arguments, which fit on a command line
+const MAX_TASK_SIZE: u64 = 256;     // A task as JSON, which is a frame and a tile at most
+
+/**
+ * Struct for a part of a render which is handed out to a worker, which is a tile of an image, a frame of a sequence or a tile of a frame.
+ */
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Task {
+    pub frame: Option<u32>,
+    pub tile: Option<usize>, // The index of the tile, see tiles::TileScheduler
+}
+
+/**
+ * Task functions.
+ */
+impl Task {
+    /**
+     * Moves a render on to the task.
+     *
+     * @param sequence_frame The frame of the sequence being rendered.
+     * @param tiles The tiles of the image being rendered, if it is rendered in tiles.
+     */
+    pub fn apply( &self, sequence_frame: &mut Option<u32>, tiles: Option<&mut TileScheduler> ) {
+        if self.frame.is_some() {
+            *sequence_frame = self.frame;
+        }
+        if let ( Some( tiles ), Some( tile ) ) = ( tiles, self.tile ) {
+            tiles.select( tile );
+        }
+    }
+}
+
+impl std::fmt::Display for Task {
+    fn fmt( &self, f: &mut std::fmt::Formatter ) -> std::fmt::Result {
+        match ( self.frame, self.tile ) {
+            ( Some( frame ), Some( tile ) ) => { write!( f, "tile {tile} of frame {frame}" ) },
+            ( Some( frame ), None )         => { write!( f, "frame {frame}" ) },
+            ( None, Some( tile ) )          => { write!( f, "tile {tile}" ) },
+            ( None, None )                  => { write!( f, "the image" ) },
+        }
+    }
+}
+
+/**
+ * Enum for what the threads serving the workers tell the coordinator.
+ */
+enum WorkerMessage {
+    Rendered( TaskResult ),
+    Lost, // A worker was lost, and its task put back
+}
+
+/**
+ * Struct for a task which a worker has rendered.
+ */
+struct TaskResult {
+    task: Task,
+    pixels: Vec<f32>, // The accumulated pixels of the worker's screen, as RGB with the bottom row first
+    screen: image::RgbImage, // The worker's screen as it is shown, with the top row first
+}
+
+/**
+ * Sends a message, see MESSAGE_JOB and the others.
+ *
+ * @param stream The connection to send it over.
+ * @param kind The kind of the message.
+ * @param payload The contents of the message.
+ */
+fn write_message( stream: &mut TcpStream, kind: u8, payload: &[u8] ) -> std::io::Result<()> {
+    stream.write_all( &[kind] )?;
+    stream.write_all( &( payload.len() as u64 ).to_le_bytes() )?;
+    stream.write_all( payload )?;
+    stream.flush()
+}
+
+/**
+ * Waits for a message, see write_message().
+ *
+ * @param stream The connection to receive it over.
+ * @param max_length The longest payload which is accepted, such as max_result_size().
+ *
+ * @return The kind and the contents of the message, or an InvalidData error if it is longer.
+ */
+fn read_message( stream: &mut TcpStream, max_length: u64 ) -> std::io::Result<( u8, Vec<u8> )> {
+    let ( mut kind, mut length ) = ( [0u8; 1], [0u8; 8] );
+    stream.read_exact( &mut kind )?;
+    stream.read_exact( &mut length )?;
+    let length = u64::from_le_bytes( length );
+    if length > max_length {
+        return Err( std::io::Error::new( std::io::ErrorKind::InvalidData, format!( "The message is {length} bytes, but at most {max_length} are accepted" ) ) );
+    }
+    let length = usize::try_from( length ).map_err( |err| std::io::Error::new( std::io::ErrorKind::InvalidData, err ) )?;
+    let mut payload = vec![0u8; length];
+    stream.read_exact( &mut payload )?;
+    Ok( ( kind[0], payload ) )
+}
+
+/**
+ * Gets the length of the payload of the largest rendered task, see encode_result().
+ *
+ * @param width The width of the render, which is the size of the workers' screens.
+ * @param height The height of the render.
+ */
+fn max_result_size( width: u32, height: u32 ) -> u64 {
+    // (Each pixel is three f32s, and three bytes of the screen)
+    4 + MAX_TASK_SIZE + 8 + width as u64 * height as u64 * ( 12 + 3 )
+}
+
+/**
+ * Encodes a rendered task, as the length of the task's JSON, the JSON, the size of the screen and then its pixels, all little-endian.
+ *
+ * @param task The task.
+ * @param pixels The accumulated pixels of the screen, as RGB with the bottom row first.
+ * @param screen The screen as it is shown, with the top row first.
+ *
+ * @return The payload of a MESSAGE_RESULT.
+ */
+fn encode_result( task: Task, pixels: &[f32], screen: &image::RgbImage ) -> Vec<u8> {
+    let task = serde_json::to_vec( &task ).unwrap_or_default();
+    let mut bytes = Vec::with_capacity( 12 + task.len() + pixels.len() * 4 + screen.as_raw().len() );
+    bytes.extend_from_slice( &( task.len() as u32 ).to_le_bytes() );
+    bytes.extend_from_slice( &task );
+    bytes.extend_from_slice( &screen.width().to_le_bytes() );
+    bytes.extend_from_slice( &screen.height().to_le_bytes() );
+    for value in pixels {
+        bytes.extend_from_slice( &value.to_le_bytes() );
+    }
+    bytes.extend_from_slice( screen.as_raw() );
+    bytes
+}
+
+/**
+ * Decodes a rendered task, see encode_result().
+ *
+ * @param bytes The payload of a MESSAGE_RESULT.
+ *
+ * @return The rendered task, or None if the payload isn't one.
+ */
+fn decode_result( bytes: &[u8] ) -> Option<TaskResult> {
+    let word = |i: usize| Some( u32::from_le_bytes( bytes.get( i..i + 4 )?.try_into().ok()? ) as usize );
+    let task_end = word( 0 )?.checked_add( 4 )?;
+    let task = serde_json::from_slice( bytes.get( 4..task_end )? ).ok()?;
+    let ( width, height ) = ( word( task_end )?, word( task_end + 4 )? );
+
+    // (The size comes from the other end, so it can be anything)
+    let pixels_start = task_end.checked_add( 8 )?;
+    let screen_start = width.checked_mul( height )?.checked_mul( 12 )?.checked_add( pixels_start )?;
+    let pixels = bytes.get( pixels_start..screen_start )?.chunks_exact( 4 ).map( |value| f32::from_le_bytes( value.try_into().unwrap() ) ).collect();
+    let screen = image::RgbImage::from_raw( width as u32, height as u32, bytes.get( screen_start.. )?.to_vec() )?;
+    Some( TaskResult { task, pixels, screen } )
+}
+
+/**
+ * Struct for a worker, which renders the tasks a coordinator on another machine hands out to it, see Coordinator.
+ * The render itself is run like any offline render, with the coordinator's arguments, and moves from task to task with Task::apply().
+ */
+pub struct Worker {
+    stream: TcpStream,
+    task: Option<Task>, // The task being rendered
+}
+
+/**
+ * Worker functions.
+ */
+impl Worker {
+    /**
+     * Connects to a coordinator, which answers with the render's arguments.
+     *
+     * @param address The address of the coordinator, as host:port.
+     *
+     * @return The worker and the settings of the render, or an error if the coordinator couldn't be reached.
+     */
+    pub fn connect( address: &str ) -> Result<( Worker, HeadlessSettings ), EngineError> {
+        let lost = |err: std::io::Error| EngineError::io( "FARM::COORDINATOR_UNREACHABLE", address, err );
+        let mut stream = TcpStream::connect( address ).map_err( lost )?;
+        let ( kind, payload ) = read_message( &mut stream, MAX_JOB_SIZE ).map_err( lost )?;
+        let args: Vec<String> = match kind {
+            MESSAGE_JOB => { serde_json::from_slice( &payload ).map_err( |err| EngineError::parse( "FARM::INVALID_MESSAGE", err.to_string() ) )? },
+            _ => { return Err( EngineError::parse( "FARM::INVALID_MESSAGE", format!( "Expected the job, got a message of kind {kind}" ) ) ) },
+        };
+        let settings = HeadlessSettings::from_args( &args )?.ok_or( EngineError::parse( "FARM::INVALID_MESSAGE", "The job isn't an offline render" ) )?;
+        info!( "Connected to the coordinator at {address}" );
+        Ok( ( Worker { stream, task: None }, settings ) )
+    }
+
+    /**
+     * Waits for the coordinator to hand out the next task.
+     *
+     * @return The task, or None if there are no more.
+     */
+    pub fn next_task( &mut self ) -> Result<Option<Task>, EngineError> {
+        let ( kind, payload ) = read_message( &mut self.stream, MAX_TASK_SIZE ).map_err( |err| EngineError::io( "FARM::COORDINATOR_LOST", "", err ) )?;
+        self.task = match kind {
+            MESSAGE_TASK => { Some( serde_json::from_slice( &payload ).map_err( |err| EngineError::parse( "FARM::INVALID_MESSAGE", err.to_string() ) )? ) },
+            MESSAGE_DONE => { None },
+            _ => { return Err( EngineError::parse( "FARM::INVALID_MESSAGE", format!( "Expected a task, got a message of kind {kind}" ) ) ) },
+        };
+        Ok( self.task )
+    }
+
+    /**
+     * Returns the rendered task to the coordinator, and waits for it to hand out the next one.
+     * Must be called after the task has been drawn to the screen, and before the buffers are swapped.
+     *
+     * @param pixels The accumulated pixels of the screen, see Accumulator::read_pixels().
+     * @param screen The screen as it is shown, see output::read_screen().
+     *
+     * @return The next task, or None if there are no more.
+     */
+    pub fn finish_task( &mut self, pixels: &[f32], screen: &image::RgbImage ) -> Result<Option<Task>, EngineError> {
+        let task = self.task.take().ok_or( EngineError::render( "FARM::NO_TASK", "" ) )?;
+        write_message( &mut self.stream, MESSAGE_RESULT, &encode_result( task, pixels, screen ) )
+            .map_err( |err| EngineError::io( "FARM::COORDINATOR_LOST", "", err ) )?;
+        info!( "Finished {task}" );
+        self.next_task()
+    }
+}
+
+/**
+ * Struct for the tasks of a render which haven't been handed out yet, shared by the threads serving the workers.
+ */
+struct TaskQueue {
+    pending: VecDeque<Task>,
+    finished: bool, // If every task has been returned, so the workers are let go
+    live: usize, // How many workers are being served
+}
+
+/**
+ * Struct for a coordinator, which hands the tiles or frames of an offline render out to workers on other machines over TCP.
+ * It doesn't render itself, but stitches the returned tiles together and saves the images (or encodes the video) as they are finished.
+ * Workers which are lost while rendering (or take longer than the task timeout, or return something other than their tile) have their task handed out again, so workers can come and go during the render.
+ * Losing every worker which is connected ends the render, rather than waiting for more to connect.
+ */
+pub struct Coordinator {
+    settings: HeadlessSettings,
+    job: Vec<u8>, // The arguments the workers render with, as JSON
+}
+
+/**
+ * Coordinator functions.
+ */
+impl Coordinator {
+    /**
+     * Prepares to hand out a render.
+     *
+     * @param settings The settings of the render, which must have a coordinator address.
+     * @param args The arguments the render was started with, which the workers are given.
+     */
+    pub fn new( settings: &HeadlessSettings, args: &[String] ) -> Coordinator {
+        // (The workers get the same arguments, except for the coordinator's own)
+        let mut job = Vec::new();
+        let mut args = args.iter();
+        while let Some( arg ) = args.next() {
+            match arg.as_str() {
+                "--coordinator" => { args.next(); },
+                _ => { job.push( arg.clone() ) },
+            }
+        }
+        Coordinator { settings: settings.clone(), job: serde_json::to_vec( &job ).unwrap_or_default() }
+    }
+
+    /**
+     * Creates the tiles of an image, where images which aren't rendered in tiles are a single tile.
+     */
+    fn image_tiles( &self ) -> TileScheduler {
+        let ( width, height ) = ( self.settings.width, self.settings.height );
+        TileScheduler::new( width, height, self.settings.tile_size.unwrap_or( width.max( height ) ) )
+    }
+
+    /**
+     * Hands out the render on its address until all of it has been returned, saving the output as it is finished.
+     */
+    pub fn run( self ) -> Result<(), EngineError> {
+        let address = self.settings.coordinator.clone().unwrap_or_default();
+        let listener = TcpListener::bind( &address ).map_err( |err| EngineError::io( "FARM::ADDRESS_UNAVAILABLE", address, err ) )?;
+        self.serve( listener )
+    }
+
+    /**
+     * Hands out the render to the workers which connect to a listener, until all of it has been returned, see run().
+     *
+     * @param listener The listener, which is bound to the coordinator's address.
+     */
+    pub fn serve( self, listener: TcpListener ) -> Result<(), EngineError> {
+        let address = self.settings.coordinator.clone().unwrap_or_default();
+        let started = std::time::Instant::now();
+        let frames: Vec<Option<u32>> = match &self.settings.sequence {
+            Some( sequence ) => { ( sequence.start_frame..=sequence.last_frame ).map( Some ).collect() },
+            None => { vec![None] },
+        };
+        let tile_count = self.image_tiles().progress().1;
+        let tile_indices: Vec<Option<usize>> = match self.settings.tile_size {
+            Some( _ ) => { ( 0..tile_count ).map( Some ).collect() },
+            None => { vec![None] },
+        };
+        let tasks: VecDeque<Task> = frames.iter().flat_map( |&frame| tile_indices.iter().map( move |&tile| Task { frame, tile } ) ).collect();
+        let task_count = tasks.len();
+
+        info!( "Waiting for workers on {address}, to render {task_count} tasks" );
+
+        // Each worker is served on its own thread, which hands it tasks until there are none left
+        let queue = Arc::new( Mutex::new( TaskQueue { pending: tasks, finished: false, live: 0 } ) );
+        let workers = Arc::new( Mutex::new( Vec::<JoinHandle<()>>::new() ) );
+        let ( sender, receiver ) = mpsc::channel();
+        {
+            let ( queue, workers, job ) = ( Arc::clone( &queue ), Arc::clone( &workers ), Arc::new( self.job.clone() ) );
+            let max_result = max_result_size( self.settings.width, self.settings.height );
+            let tile_size = self.image_tiles().tile_size();
+            let timeout = Duration::from_secs_f32( self.settings.task_timeout );
+            std::thread::spawn( move || {
+                for stream in listener.incoming().flatten() {
+                    let ( queue, job, sender ) = ( Arc::clone( &queue ), Arc::clone( &job ), sender.clone() );
+                    let worker = std::thread::spawn( move || serve_worker( stream, &job, max_result, tile_size, timeout, &queue, &sender ) );
+                    if let Ok( mut workers ) = workers.lock() {
+                        workers.push( worker );
+                    }
+                }
+            } );
+        }
+
+        // Stitch the returned tiles into their images, which are saved once they have all their tiles
+        // (Videos need their frames in order, so finished frames wait for those before them)
+        let mut video_encoder = match ( self.settings.format, &self.settings.sequence ) {
+            ( OutputFormat::Video, Some( sequence ) ) => { Some( VideoEncoder::new( &self.settings.output, self.settings.width, self.settings.height, sequence.fps )? ) },
+            _ => { None },
+        };
+        let mut next_video_frame = frames[0].unwrap_or( 0 );
+        let mut video_frames = BTreeMap::<u32, image::RgbImage>::new();
+        let mut images = HashMap::<Option<u32>, ( TileScheduler, usize )>::new();
+        let mut progress_reporter = self.settings.progress_reporter();
+        progress_reporter.report( 0, 0, 0 );
+        let mut returned = 0;
+        while returned < task_count {
+            // (A worker may be lost while another one connects, so the render only ends if none are left once the loss is told)
+            let result = match receiver.recv() {
+                Ok( WorkerMessage::Rendered( result ) ) => { result },
+                Ok( WorkerMessage::Lost ) => {
+                    if queue.lock().map_or( true, |queue| queue.live == 0 ) {
+                        return Err( EngineError::render( "FARM::WORKERS_LOST", format!( "{returned} of {task_count} tasks were returned" ) ) );
+                    }
+                    continue;
+                },
+                Err( _ ) => { return Err( EngineError::render( "FARM::WORKERS_LOST", "" ) ) },
+            };
+            // (A tile which can't be stitched is handed out again, like the tasks of lost workers)
+            let ( tiles, remaining ) = images.entry( result.task.frame ).or_insert_with( || ( self.image_tiles(), tile_indices.len() ) );
+            if let Err( err ) = tiles.stitch( result.task.tile.unwrap_or( 0 ), &result.pixels, &result.screen ) {
+                warn!( "{err}\nHanding {} out again", result.task );
+                if let Ok( mut queue ) = queue.lock() {
+                    queue.pending.push_back( result.task );
+                }
+                continue;
+            }
+            returned += 1;
+            info!( "Received {} ({returned} of {task_count})", result.task );
+            progress_reporter.report( ( returned / tile_indices.len() ) as u32, returned % tile_indices.len(), 0 );
+            *remaining -= 1;
+            if *remaining > 0 {
+                continue;
+            }
+
+            let ( tiles, _ ) = images.remove( &result.task.frame ).unwrap();
+            match &mut video_encoder {
+                Some( encoder ) => {
+                    video_frames.insert( result.task.frame.unwrap_or( 0 ), tiles.screen().clone() );
+                    while let Some( frame ) = video_frames.remove( &next_video_frame ) {
+                        encoder.write_frame( &frame )?;
+                        info!( "Encoded frame {next_video_frame} into {}", self.settings.output );
+                        next_video_frame += 1;
+                    }
+                },
+                None => {
+                    let path = match result.task.frame {
+                        Some( frame ) => { SequenceSettings::frame_path( &self.settings.output, frame ) },
+                        None => { self.settings.output.clone() },
+                    };
+                    let ( width, height ) = tiles.image_size();
+                    let mut metadata = self.settings.metadata().with( "RenderTime", format!( "{:.3} s", started.elapsed().as_secs_f32() ) );
+                    if let Some( frame ) = result.task.frame {
+                        metadata = metadata.with( "Frame", frame );
+                    }
+                    self.settings.save_image( &path, width, height, tiles.pixels(), tiles.screen(), &metadata )?;
+                    info!( "Saved the render to {path}" );
+                },
+            }
+        }
+        if let Some( encoder ) = video_encoder {
+            encoder.finish()?;
+            info!( "Finished the video {}", self.settings.output );
+        }
+
+        // Let the workers go, which are all waiting for tasks by now
+        if let Ok( mut queue ) = queue.lock() {
+            queue.finished = true;
+        }
+        let workers = workers.lock().map( |mut workers| std::mem::take( &mut *workers ) ).unwrap_or_default();
+        for worker in workers {
+            let _ = worker.join();
+        }
+        Ok( () )
+    }
+}
+
+/**
+ * Serves a worker, handing it tasks until every task has been returned.
+ * If the worker is lost, or doesn't return its task in time, the task is put back for another worker to render.
+ *
+ * @param stream The connection to the worker.
+ * @param job The arguments the worker renders with, as JSON.
+ * @param max_result The length of the largest result which is accepted, see max_result_size().
+ * @param tile_size The size of the workers' screens, which results of any other size are lost for, see TileScheduler::tile_size().
+ * @param timeout How long the worker may take to return a task.
+ * @param queue The tasks which haven't been handed out yet.
+ * @param results Where the rendered tasks are sent, and the loss of the worker told.
+ */
+fn serve_worker( mut stream: TcpStream, job: &[u8], max_result: u64, tile_size: ( u32, u32 ), timeout: Duration, queue: &Mutex<TaskQueue>, results: &mpsc::Sender<WorkerMessage> ) {
+    let peer = stream.peer_addr().map_or( "a worker".to_string(), |peer| peer.to_string() );
+    if stream.set_read_timeout( Some( timeout ) ).is_err() || write_message( &mut stream, MESSAGE_JOB, job ).is_err() {
+        return;
+    }
+    if let Ok( mut queue ) = queue.lock() {
+        queue.live += 1;
+    }
+    info!( "Worker {peer} connected" );
+
+    loop {
+        // (Workers wait while the last tasks are being rendered, in case one of those is lost and handed out again)
+        let next = match queue.lock() {
+            Ok( queue ) if queue.finished => { None },
+            Ok( mut queue ) => { Some( queue.pending.pop_front() ) },
+            Err( _ ) => { None },
+        };
+        let task = match next {
+            Some( Some( task ) ) => { task },
+            Some( None ) => { std::thread::sleep( Duration::from_millis( 100 ) ); continue },
+            None => { let _ = write_message( &mut stream, MESSAGE_DONE, &[] ); return },
+        };
+
+        let rendered = write_message( &mut stream, MESSAGE_TASK, &serde_json::to_vec( &task ).unwrap_or_default() )
+            .and_then( |()| read_message( &mut stream, max_result ) )
+            .ok()
+            .filter( |( kind, _ )| *kind == MESSAGE_RESULT )
+            .and_then( |( _, payload )| decode_result( &payload ) )
+            .filter( |result| result.task == task && result.screen.dimensions() == tile_size );
+        match rendered {
+            Some( result ) => { let _ = results.send( WorkerMessage::Rendered( result ) ); },
+            None => {
+                warn!( "Lost worker {peer}, handing {task} out again" );
+                if let Ok( mut queue ) = queue.lock() {
+                    queue.pending.push_front( task );
+                    queue.live -= 1;
+                }
+                let _ = results.send( WorkerMessage::Lost );
+                return;
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn results_round_trip() {
+        let task = Task { frame: Some( 3 ), tile: Some( 1 ) };
+        let screen = image::RgbImage::from_raw( 2, 1, vec![1, 2, 3, 4, 5, 6] ).unwrap();
+        let result = decode_result( &encode_result( task, &[0.5; 6], &screen ) ).unwrap();
+        assert!( result.task == task && result.pixels == [0.5; 6] && result.screen == screen );
+    }
+
+    #[test]
+    fn results_of_impossible_sizes_are_invalid() {
+        let task = serde_json::to_vec( &Task { frame: None, tile: Some( 0 ) } ).unwrap();
+        let mut bytes = ( task.len() as u32 ).to_le_bytes().to_vec();
+        bytes.extend_from_slice( &task );
+        bytes.extend_from_slice( &u32::MAX.to_le_bytes() );
+        bytes.extend_from_slice( &u32::MAX.to_le_bytes() );
+        assert!( decode_result( &bytes ).is_none() );
+    }
+
+    #[test]
+    fn losing_every_worker_ends_the_render() {
+        let args: Vec<String> = ["--headless", "--coordinator", "127.0.0.1:0", "--tile-size", "64", "--task-timeout", "0.2"].iter().map( |arg| arg.to_string() ).collect();
+        let settings = HeadlessSettings::from_args( &args ).unwrap().unwrap();
+        let listener = TcpListener::bind( "127.0.0.1:0" ).unwrap();
+        let address = listener.local_addr().unwrap();
+        let coordinator = std::thread::spawn( move || Coordinator::new( &settings, &args ).serve( listener ) );
+
+        // (The worker takes its task, but never returns it)
+        let mut stream = TcpStream::connect( address ).unwrap();
+        assert_eq!( read_message( &mut stream, MAX_JOB_SIZE ).unwrap().0, MESSAGE_JOB );
+        assert_eq!( read_message( &mut stream, MAX_TASK_SIZE ).unwrap().0, MESSAGE_TASK );
+        match coordinator.join().unwrap() {
+            Err( EngineError::Render { code, .. } ) => { assert_eq!( code, "FARM::WORKERS_LOST" ) },
+            _ => { panic!( "The render should end once its only worker is lost" ) },
+        }
+    }
+}
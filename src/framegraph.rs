@@ -0,0 +1,364 @@
+use std::collections::{ HashMap, HashSet };
+
+use crate::device::{ self, Device, TextureFilter, TextureFormat };
+use crate::error::EngineError;
+use crate::postchain::PassImage;
+use crate::util;
+use crate::gltracker::{ self, GlObject };
+
+/**
+ * Enum for the images which the passes of the post chain read, besides the image they are given.
+ * Declaring them lets the frame graph check that they exist, and make what was stored into them visible before they are read.
+ */
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Resource {
+    Accumulation,                      // The accumulated image, before any pass (see accumulator::Accumulator)
+    Guides,                            // The denoiser's albedo, normal and depth guides (see denoiser::Denoiser)
+    Motion,                            // The denoiser's motion vectors
+    Target( &'static str, &'static str ), // A target of a pass earlier in the chain, by the pass's and the target's names
+}
+
+/**
+ * Enum for the size of a target, which follows the frame's sizes.
+ */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TargetSize {
+    Input,  // The size of the image the pass is given
+    Half,   // Half the size of the image the pass is given, rounded up
+    Screen, // The size of the screen
+}
+
+/**
+ * Struct for a target which a pass renders to, which the frame graph allocates and resizes for it.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TargetDesc {
+    pub name: &'static str, // The name of the target, which the pass binds it by, and later passes read it by
+    pub format: TextureFormat,
+    pub size: TargetSize,
+}
+
+/**
+ * Struct for what a pass declares it reads besides its image, and the targets it renders to.
+ */
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct PassIo {
+    pub reads: Vec<Resource>,
+    pub targets: Vec<TargetDesc>,
+}
+
+/**
+ * Struct for the framebuffer a pass renders to, which follows the size of what is rendered into it.
+ * The texture is filtered linearly, for passes which sample between its pixels.
+ */
+pub struct RenderTarget {
+    framebuffer: u32,
+    texture: device::Texture,
+    width: u32,
+    height: u32,
+    format: TextureFormat,
+}
+
+/**
+ * RenderTarget functions.
+ */
+impl RenderTarget {
+    /**
+     * Creates a render target, whose framebuffer is created when it is first bound.
+     *
+     * @param format The format of the texture.
+     */
+    fn new( format: TextureFormat ) -> RenderTarget {
+        RenderTarget { framebuffer: 0, texture: 0, width: 0, height: 0, format }
+    }
+
+    /**
+     * Binds the framebuffer to render to, (re)creating it if it isn't the given size.
+     *
+     * @param width The width to render at, in pixels.
+     * @param height The height to render at, in pixels.
+     *
+     * @return An error if the driver can't render to the framebuffer at the size, which is deleted then, so the next bind tries again.
+     */
+    unsafe fn bind( &mut self, width: u32, height: u32 ) -> Result<(), EngineError> {
+        if ( width, height ) != ( self.width, self.height ) {
+            self.delete();
+            self.width = width;
+            self.height = height;
+
+            self.texture = device::current().create_texture( width, height, self.format, TextureFilter::Linear );
+
+            gl::GenFramebuffers( 1, &mut self.framebuffer );
+            gltracker::created( GlObject::Framebuffer, &[self.framebuffer] );
+            gl::BindFramebuffer( gl::FRAMEBUFFER, self.framebuffer );
+            gl::FramebufferTexture2D( gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D, self.texture, 0 );
+            if let Err( err ) = util::check_framebuffer( "FRAMEGRAPH::FRAMEBUFFER_INCOMPLETE" ) {
+                self.delete();
+                ( self.width, self.height ) = ( 0, 0 );
+                return Err( err );
+            }
+        }
+
+        gl::BindFramebuffer( gl::FRAMEBUFFER, self.framebuffer );
+        gl::Viewport( 0, 0, width as i32, height as i32 );
+        Ok( () )
+    }
+
+    /**
+     * Deletes the framebuffer and its texture, if they were created.
+     */
+    unsafe fn delete( &mut self ) {
+        if self.framebuffer != 0 {
+            gl::DeleteFramebuffers( 1, &self.framebuffer );
+            gltracker::deleted( GlObject::Framebuffer, &[self.framebuffer] );
+            device::current().delete_texture( self.texture );
+            self.framebuffer = 0;
+        }
+    }
+
+    /**
+     * Gets the image which was rendered to the framebuffer.
+     */
+    fn image( &self ) -> PassImage {
+        PassImage { texture: self.texture, width: self.width, height: self.height }
+    }
+}
+
+/**
+ * Struct for the frame graph of the post chain, which owns the targets every pass renders to, from what the passes declare (see PassIo).
+ * The targets are allocated when they are first bound, and resized here whenever the frame's sizes change, rather than by each pass.
+ * Targets which are written with image stores (rather than drawn into) are made visible with a barrier before the next pass which reads them.
+ */
+pub struct FrameGraph {
+    passes: HashMap<&'static str, PassIo>, // What each pass declared, by its name
+    targets: HashMap<( &'static str, &'static str ), ( TargetDesc, RenderTarget )>, // The targets, by the pass's and the target's names
+    stored: HashSet<Resource>, // Resources which were written with image stores since a barrier was last issued
+}
+
+/**
+ * FrameGraph functions.
+ */
+impl FrameGraph {
+    /**
+     * Creates a graph without passes.
+     */
+    pub fn new() -> FrameGraph {
+        FrameGraph { passes: HashMap::new(), targets: HashMap::new(), stored: HashSet::new() }
+    }
+
+    /**
+     * Declares what a pass reads and writes.
+     * Its targets are made here, but their framebuffers only once they are bound.
+     *
+     * @param pass The name of the pass.
+     * @param io What the pass reads and writes.
+     *
+     * @return Ok, or an error if a pass of the name was already declared, or if the pass has two targets of the same name.
+     */
+    pub fn declare( &mut self, pass: &'static str, io: PassIo ) -> Result<(), EngineError> {
+        if self.passes.contains_key( pass ) {
+            return Err( EngineError::parse( "FRAMEGRAPH::DUPLICATE_PASS", pass.to_string() ) );
+        }
+        for ( i, target ) in io.targets.iter().enumerate() {
+            if io.targets[..i].iter().any( |other| other.name == target.name ) {
+                return Err( EngineError::parse( "FRAMEGRAPH::DUPLICATE_TARGET", format!( "{pass}.{}", target.name ) ) );
+            }
+        }
+
+        for target in &io.targets {
+            self.targets.insert( ( pass, target.name ), ( *target, RenderTarget::new( target.format ) ) );
+        }
+        self.passes.insert( pass, io );
+        Ok( () )
+    }
+
+    /**
+     * Checks that every target a pass reads is rendered by a pass before it.
+     *
+     * @param order The names of the passes, in the order they run.
+     *
+     * @return Ok, or an error naming the first read which isn't.
+     */
+    pub fn check_order( &self, order: &[&'static str] ) -> Result<(), EngineError> {
+        for ( i, pass ) in order.iter().enumerate() {
+            let Some( io ) = self.passes.get( pass ) else { continue };
+            for read in &io.reads {
+                let Resource::Target( other, name ) = *read else { continue };
+                if !order[..i].contains( &other ) || !self.targets.contains_key( &( other, name ) ) {
+                    return Err( EngineError::parse( "FRAMEGRAPH::UNKNOWN_RESOURCE", format!( "{pass} reads {other}.{name}, which no pass before it renders to" ) ) );
+                }
+            }
+        }
+        Ok( () )
+    }
+
+    /**
+     * Marks a resource as written with image stores, so it is made visible before it is next read, see before_pass().
+     *
+     * @param resource The resource.
+     */
+    pub fn stored( &mut self, resource: Resource ) {
+        self.stored.insert( resource );
+    }
+
+    /**
+     * Gets the targets a pass renders to, for the frame's sizes, and issues a barrier first if anything it reads was stored into.
+     *
+     * @param pass The name of the pass.
+     * @param input The size of the image the pass is given.
+     * @param screen The size of the screen.
+     *
+     * @return The pass's targets, or an error if the pass was never declared.
+     */
+    pub unsafe fn before_pass( &mut self, pass: &'static str, input: ( u32, u32 ), screen: ( u32, u32 ) ) -> Result<PassTargets<'_>, EngineError> {
+        // (Any pass's target may be the image the pass is given, so those are always made visible)
+        let reads = match self.passes.get( pass ) {
+            Some( io ) => { io.reads.as_slice() },
+            None => { return Err( EngineError::render( "FRAMEGRAPH::UNDECLARED_PASS", pass ) ) },
+        };
+        let reads_stored = self.stored.iter().any( |stored| matches!( stored, Resource::Target( .. ) ) || reads.contains( stored ) );
+        if reads_stored {
+            gl::MemoryBarrier( gl::TEXTURE_FETCH_BARRIER_BIT | gl::SHADER_IMAGE_ACCESS_BARRIER_BIT | gl::FRAMEBUFFER_BARRIER_BIT );
+            self.stored.clear();
+        }
+        Ok( PassTargets { graph: self, pass, input, screen } )
+    }
+
+    /**
+     * Gets the image of a pass's target, as it was last rendered.
+     *
+     * @param pass The name of the pass.
+     * @param name The name of the target.
+     */
+    pub fn image( &self, pass: &'static str, name: &'static str ) -> Option<PassImage> {
+        self.targets.get( &( pass, name ) ).map( |( _, target )| target.image() )
+    }
+
+    /**
+     * Deletes the framebuffers and textures of every target, once the graph is done with.
+     */
+    pub unsafe fn delete( self ) {
+        for ( _, ( _, mut target ) ) in self.targets {
+            target.delete();
+        }
+    }
+}
+
+/**
+ * Struct for the targets of the pass which is rendering, see FrameGraph::before_pass().
+ */
+pub struct PassTargets<'a> {
+    graph: &'a mut FrameGraph,
+    pass: &'static str,
+    input: ( u32, u32 ),
+    screen: ( u32, u32 ),
+}
+
+/**
+ * PassTargets functions.
+ */
+impl PassTargets<'_> {
+    /**
+     * Gets the size a target is rendered at this frame.
+     *
+     * @param size The size of the target.
+     */
+    pub fn size( &self, size: TargetSize ) -> ( u32, u32 ) {
+        match size {
+            TargetSize::Input => { self.input },
+            TargetSize::Half => { ( self.input.0.div_ceil( 2 ).max( 1 ), self.input.1.div_ceil( 2 ).max( 1 ) ) },
+            TargetSize::Screen => { self.screen },
+        }
+    }
+
+    /**
+     * Binds one of the pass's targets to render to, resizing it first if the frame's sizes changed.
+     *
+     * @param name The name of the target, as the pass declared it.
+     *
+     * @return The size of the target, which the viewport is set to, or an error if the pass didn't declare the target or it couldn't be rendered to.
+     */
+    pub unsafe fn bind( &mut self, name: &'static str ) -> Result<( u32, u32 ), EngineError> {
+        let size = match self.graph.targets.get( &( self.pass, name ) ) {
+            Some( ( desc, _ ) ) => { self.size( desc.size ) },
+            None => { return Err( EngineError::render( "FRAMEGRAPH::UNDECLARED_TARGET", format!( "{}.{name}", self.pass ) ) ) },
+        };
+        if let Some( ( _, target ) ) = self.graph.targets.get_mut( &( self.pass, name ) ) {
+            target.bind( size.0, size.1 )?;
+        }
+        Ok( size )
+    }
+
+    /**
+     * Gets the image of one of the pass's targets, as it was last rendered.
+     *
+     * @param name The name of the target.
+     *
+     * @return The image, or an error if the pass didn't declare the target.
+     */
+    pub fn image( &self, name: &'static str ) -> Result<PassImage, EngineError> {
+        self.graph.image( self.pass, name ).ok_or_else( || EngineError::render( "FRAMEGRAPH::UNDECLARED_TARGET", format!( "{}.{name}", self.pass ) ) )
+    }
+
+    /**
+     * Gets the image of a target of a pass earlier in the chain, which this pass declared it reads.
+     *
+     * @param pass The name of the other pass.
+     * @param name The name of its target.
+     *
+     * @return The image, or an error if this pass didn't declare it reads the target.
+     */
+    pub fn read( &self, pass: &'static str, name: &'static str ) -> Result<PassImage, EngineError> {
+        let declared = self.graph.passes.get( self.pass ).is_some_and( |io| io.reads.contains( &Resource::Target( pass, name ) ) );
+        let image = if declared { self.graph.image( pass, name ) } else { None };
+        image.ok_or_else( || EngineError::render( "FRAMEGRAPH::UNDECLARED_READ", format!( "{} reads {pass}.{name}, which it didn't declare", self.pass ) ) )
+    }
+
+    /**
+     * Marks one of the pass's targets as written with image stores, so it is made visible before the next pass reads it.
+     *
+     * @param name The name of the target.
+     */
+    pub fn stored( &mut self, name: &'static str ) {
+        self.graph.stored( Resource::Target( self.pass, name ) );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target( name: &'static str ) -> TargetDesc {
+        TargetDesc { name, format: TextureFormat::Rgba16F, size: TargetSize::Input }
+    }
+
+    #[test]
+    fn passes_only_read_targets_rendered_before_them() {
+        let mut graph = FrameGraph::new();
+        graph.declare( "Bloom", PassIo { reads: vec![], targets: vec![target( "output" )] } ).unwrap();
+        graph.declare( "Debug", PassIo { reads: vec![Resource::Target( "Bloom", "output" )], targets: vec![target( "output" )] } ).unwrap();
+        assert!( graph.check_order( &["Bloom", "Debug"] ).is_ok() );
+        assert!( graph.check_order( &["Debug", "Bloom"] ).is_err() );
+    }
+
+    #[test]
+    fn names_are_declared_once() {
+        let mut graph = FrameGraph::new();
+        graph.declare( "Bloom", PassIo::default() ).unwrap();
+        assert!( graph.declare( "Bloom", PassIo::default() ).is_err() );
+        assert!( graph.declare( "Twice", PassIo { reads: vec![], targets: vec![target( "a" ), target( "a" )] } ).is_err() );
+    }
+
+    #[test]
+    fn undeclared_targets_are_errors() {
+        let mut graph = FrameGraph::new();
+        graph.declare( "Bloom", PassIo { reads: vec![], targets: vec![target( "output" )] } ).unwrap();
+        graph.declare( "Debug", PassIo { reads: vec![Resource::Target( "Bloom", "output" )], targets: vec![] } ).unwrap();
+        // (Nothing was stored, so no barrier is issued, and no context is needed)
+        assert!( unsafe { graph.before_pass( "Unknown", ( 4, 4 ), ( 4, 4 ) ) }.is_err() );
+        let targets = unsafe { graph.before_pass( "Debug", ( 4, 4 ), ( 4, 4 ) ) }.unwrap();
+        assert!( targets.read( "Bloom", "output" ).is_ok() );
+        assert!( targets.read( "Bloom", "blur0" ).is_err() );
+        assert!( targets.image( "output" ).is_err() );
+    }
+}
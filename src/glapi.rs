@@ -0,0 +1,330 @@
+use std::sync::OnceLock;
+
+use crate::capabilities::Capabilities;
+use crate::gltracker::{ self, GlObject };
+
+// Environment variable which makes the contexts OpenGL ES ones even where desktop OpenGL is available, for trying the ES path
+pub const FORCE_ES_VAR: &str = "OPENGL_RAYTRACING_GLES";
+
+// What the shaders get instead of their #version line on OpenGL ES
+// (GLSL ES has no implicit conversions between ints and floats, which the shaders rely on, so they are turned on with the extension Mesa and most drivers have)
+// (GLSL ES has no default precision for floats in fragment shaders, or for images)
+const ES_SHADER_HEADER: &str = "#version 310 es
+#extension GL_EXT_shader_implicit_conversions : enable
+precision highp float;
+precision highp int;
+precision highp sampler2D;
+precision highp sampler3D;
+precision highp usampler2D;
+precision highp isampler2D;
+precision highp sampler2DArray;
+precision highp image2D;
+#define GLES 1
+";
+
+// What the WebGL2 renderer's shaders get instead of their #version line (GLSL ES 3.00 has no extensions for implicit conversions, so they are written without them)
+const WEBGL_SHADER_HEADER: &str = "#version 300 es
+precision highp float;
+precision highp int;
+precision highp sampler2D;
+#define WEBGL 1
+";
+
+// The API of the context the engine renders with, once it is made current, see GlApi::detect()
+static CURRENT_API: OnceLock<GlApi> = OnceLock::new();
+
+/**
+ * Enum for the OpenGL API which a context is created with, and which the shaders and the few calls which differ must suit.
+ * The engine is written for desktop OpenGL 4.4, and OpenGL ES 3.1 has everything it is built on (storage buffers, compute shaders and image stores),
+ * so only what ES lacks has a path of its own, through the functions of this module.
+ * Float textures must be renderable there, which most drivers have as EXT_color_buffer_float, and are sampled without filtering unless they have OES_texture_float_linear.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GlApi {
+    Desktop,    // OpenGL 4.4 core
+    Es,         // OpenGL ES 3.1, such as on Raspberry Pis and Android devices
+    WebGl2,     // WebGL2 (OpenGL ES 3.0) in a browser, which only the fragment-only renderer runs on, see web::WebRenderer
+}
+
+/**
+ * GlApi functions.
+ */
+impl GlApi {
+    /**
+     * Gets the APIs a context should be created with, in order, where ES is tried if desktop OpenGL 4.4 isn't available.
+     */
+    pub fn requested() -> Vec<GlApi> {
+        match std::env::var_os( FORCE_ES_VAR ) {
+            Some( _ ) => { vec![GlApi::Es] },
+            None => { vec![GlApi::Desktop, GlApi::Es] },
+        }
+    }
+
+    /**
+     * Gets the version of the API a context must have at least.
+     */
+    pub fn version( &self ) -> ( u8, u8 ) {
+        match self {
+            GlApi::Desktop  => { ( 4, 4 ) },
+            GlApi::Es       => { ( 3, 1 ) },
+            GlApi::WebGl2   => { ( 2, 0 ) },
+        }
+    }
+
+    /**
+     * Gets the API's name, as it is logged.
+     */
+    pub fn name( &self ) -> &'static str {
+        match self {
+            GlApi::Desktop  => { "OpenGL" },
+            GlApi::Es       => { "OpenGL ES" },
+            GlApi::WebGl2   => { "WebGL" },
+        }
+    }
+
+    /**
+     * Gets what glutin is asked for to create a context with the API.
+     */
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn gl_request( &self ) -> glutin::GlRequest {
+        let api = match self {
+            GlApi::Desktop  => { glutin::Api::OpenGl },
+            GlApi::Es | GlApi::WebGl2 => { glutin::Api::OpenGlEs },
+        };
+        glutin::GlRequest::Specific( api, self.version() )
+    }
+
+    /**
+     * Tells which API the current context has from its version string, and remembers it for the rest of the process.
+     * The OpenGL functions must be loaded.
+     */
+    pub unsafe fn detect() -> GlApi {
+        let version = gl::GetString( gl::VERSION );
+        let es = !version.is_null() && std::ffi::CStr::from_ptr( version as *const _ ).to_string_lossy().starts_with( "OpenGL ES" );
+        let api = if es { GlApi::Es } else { GlApi::Desktop };
+        *CURRENT_API.get_or_init( || api )
+    }
+
+    /**
+     * Remembers that the engine renders with WebGL2 for the rest of the process, as the browser's context has no version string to detect it from.
+     */
+    #[cfg(target_arch = "wasm32")]
+    pub fn detect_webgl2() -> GlApi {
+        *CURRENT_API.get_or_init( || GlApi::WebGl2 )
+    }
+
+    /**
+     * Gets the API of the context the engine renders with, which is desktop OpenGL until one has been made current.
+     */
+    pub fn current() -> GlApi {
+        CURRENT_API.get().copied().unwrap_or( GlApi::Desktop )
+    }
+
+    /**
+     * Gets the line(s) a shader source's #version line is replaced with, for the shader to compile for the API.
+     *
+     * @param version The shader's #version line.
+     */
+    pub fn shader_header<'a>( &self, version: &'a str ) -> &'a str {
+        match self {
+            GlApi::Desktop  => { version },
+            GlApi::Es       => { ES_SHADER_HEADER.trim_end() },
+            GlApi::WebGl2   => { WEBGL_SHADER_HEADER.trim_end() },
+        }
+    }
+}
+
+/**
+ * Gets the format pixels are read back with, which ES only has RGBA for (for floats, and other than the implementation's own format).
+ *
+ * @param format The format the pixels should have, such as gl::RGB.
+ */
+pub fn read_format( format: gl::types::GLenum ) -> gl::types::GLenum {
+    match GlApi::current() {
+        GlApi::Desktop  => { format },
+        GlApi::Es | GlApi::WebGl2 => { gl::RGBA },
+    }
+}
+
+/**
+ * Gets which of the RGBA channels a format has.
+ *
+ * @param format The format, such as gl::RGB.
+ *
+ * @return The first channel, and how many there are.
+ */
+pub fn format_channels( format: gl::types::GLenum ) -> ( usize, usize ) {
+    match format {
+        gl::RED     => { ( 0, 1 ) },
+        gl::GREEN   => { ( 1, 1 ) },
+        gl::BLUE    => { ( 2, 1 ) },
+        gl::RG      => { ( 0, 2 ) },
+        gl::RGB     => { ( 0, 3 ) },
+        _           => { ( 0, 4 ) },
+    }
+}
+
+/**
+ * Keeps the channels of pixels which were read with read_format(), but should have another format.
+ *
+ * @param pixels The pixels as they were read.
+ * @param format The format the pixels should have.
+ */
+pub fn keep_channels<T: Copy>( pixels: &[T], format: gl::types::GLenum ) -> Vec<T> {
+    let ( read, ( first, channels ) ) = ( format_channels( read_format( format ) ).1, format_channels( format ) );
+    if read == channels {
+        return pixels.to_vec();
+    }
+    pixels.chunks( read ).flat_map( |pixel| pixel[first..first + channels].iter().copied() ).collect()
+}
+
+/**
+ * Reads a level of a float texture into memory, or into the bound pixel pack buffer if the pixels are null.
+ * ES can't read textures back itself, so the texture is attached to a framebuffer there and read like the screen.
+ *
+ * @param texture The texture.
+ * @param level The mipmap level.
+ * @param width The width of the level, in pixels.
+ * @param height The height of the level, in pixels.
+ * @param format The format the pixels should have, such as gl::RGB, which they are read with if the API can (see read_format()).
+ * @param pixels Where the pixels are read to, which must fit them as they are read.
+ */
+pub unsafe fn read_texture( texture: u32, level: i32, width: u32, height: u32, format: gl::types::GLenum, pixels: *mut core::ffi::c_void ) {
+    gl::PixelStorei( gl::PACK_ALIGNMENT, 1 );
+    match GlApi::current() {
+        GlApi::Desktop => {
+            gl::BindTexture( gl::TEXTURE_2D, texture );
+            gl::GetTexImage( gl::TEXTURE_2D, level, format, gl::FLOAT, pixels );
+            gl::BindTexture( gl::TEXTURE_2D, 0 );
+        },
+        GlApi::Es | GlApi::WebGl2 => {
+            let ( mut prev_framebuffer, mut framebuffer ) = ( 0, 0 );
+            gl::GetIntegerv( gl::READ_FRAMEBUFFER_BINDING, &mut prev_framebuffer );
+            gl::GenFramebuffers( 1, &mut framebuffer );
+            gltracker::created( GlObject::Framebuffer, &[framebuffer] );
+            gl::BindFramebuffer( gl::READ_FRAMEBUFFER, framebuffer );
+            gl::FramebufferTexture2D( gl::READ_FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D, texture, level );
+            gl::ReadBuffer( gl::COLOR_ATTACHMENT0 );
+            gl::ReadPixels( 0, 0, width as i32, height as i32, read_format( format ), gl::FLOAT, pixels );
+            gl::BindFramebuffer( gl::READ_FRAMEBUFFER, prev_framebuffer as u32 );
+            gl::DeleteFramebuffers( 1, &framebuffer );
+            gltracker::deleted( GlObject::Framebuffer, &[framebuffer] );
+        },
+    }
+}
+
+/**
+ * Fills part of the bound buffer with a 32-bit value, which ES can only do by uploading the value over it.
+ *
+ * @param target The target the buffer is bound to.
+ * @param offset Where the part starts, in bytes.
+ * @param size The size of the part, in bytes, or None for the rest of the buffer.
+ * @param value The value.
+ */
+pub unsafe fn clear_buffer_u32( target: gl::types::GLenum, offset: isize, size: Option<isize>, value: u32 ) {
+    let size = size.unwrap_or_else( || {
+        let mut buffer_size = 0;
+        gl::GetBufferParameteriv( target, gl::BUFFER_SIZE, &mut buffer_size );
+        buffer_size as isize - offset
+    } );
+    match GlApi::current() {
+        GlApi::Desktop => {
+            gl::ClearBufferSubData( target, gl::R32UI, offset, size, gl::RED_INTEGER, gl::UNSIGNED_INT, &value as *const u32 as *const _ );
+        },
+        GlApi::Es | GlApi::WebGl2 => {
+            let values = vec![value; size as usize / 4];
+            gl::BufferSubData( target, offset, size, values.as_ptr() as *const _ );
+        },
+    }
+}
+
+/**
+ * Reads part of the bound buffer into memory, which ES can only do by mapping it.
+ *
+ * @param target The target the buffer is bound to.
+ * @param offset Where the part starts, in bytes.
+ * @param data Where the part is read to, which must fit it.
+ */
+pub unsafe fn read_buffer<T: Copy>( target: gl::types::GLenum, offset: isize, data: &mut [T] ) {
+    let size = std::mem::size_of_val( data ) as isize;
+    match GlApi::current() {
+        GlApi::Desktop => {
+            gl::GetBufferSubData( target, offset, size, data.as_mut_ptr() as *mut _ );
+        },
+        GlApi::Es | GlApi::WebGl2 => {
+            let mapped = gl::MapBufferRange( target, offset, size, gl::MAP_READ_BIT ) as *const T;
+            if !mapped.is_null() {
+                data.copy_from_slice( std::slice::from_raw_parts( mapped, data.len() ) );
+            }
+            gl::UnmapBuffer( target );
+        },
+    }
+}
+
+/**
+ * Copies the whole of one 2D texture into another of the same size and format, which ES 3.1 can only do by blitting between framebuffers.
+ *
+ * @param from The texture to copy.
+ * @param to The texture to copy into.
+ * @param width The width of the textures, in pixels.
+ * @param height The height of the textures, in pixels.
+ */
+pub unsafe fn copy_texture( from: u32, to: u32, width: u32, height: u32 ) {
+    match GlApi::current() {
+        GlApi::Desktop => {
+            gl::CopyImageSubData(
+                from, gl::TEXTURE_2D, 0, 0, 0, 0,
+                to, gl::TEXTURE_2D, 0, 0, 0, 0,
+                width as i32, height as i32, 1
+            );
+        },
+        GlApi::Es | GlApi::WebGl2 => {
+            let ( mut prev_read, mut prev_draw ) = ( 0, 0 );
+            gl::GetIntegerv( gl::READ_FRAMEBUFFER_BINDING, &mut prev_read );
+            gl::GetIntegerv( gl::DRAW_FRAMEBUFFER_BINDING, &mut prev_draw );
+            let mut framebuffers = [0; 2];
+            gl::GenFramebuffers( 2, framebuffers.as_mut_ptr() );
+            gltracker::created( GlObject::Framebuffer, &framebuffers );
+            gl::BindFramebuffer( gl::READ_FRAMEBUFFER, framebuffers[0] );
+            gl::FramebufferTexture2D( gl::READ_FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D, from, 0 );
+            gl::BindFramebuffer( gl::DRAW_FRAMEBUFFER, framebuffers[1] );
+            gl::FramebufferTexture2D( gl::DRAW_FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D, to, 0 );
+            gl::BlitFramebuffer( 0, 0, width as i32, height as i32, 0, 0, width as i32, height as i32, gl::COLOR_BUFFER_BIT, gl::NEAREST );
+            gl::BindFramebuffer( gl::READ_FRAMEBUFFER, prev_read as u32 );
+            gl::BindFramebuffer( gl::DRAW_FRAMEBUFFER, prev_draw as u32 );
+            gl::DeleteFramebuffers( 2, framebuffers.as_ptr() );
+            gltracker::deleted( GlObject::Framebuffer, &framebuffers );
+        },
+    }
+}
+
+/**
+ * Gets if the GPU's work can be timed with timer queries, which ES only has as an extension (EXT_disjoint_timer_query), see Capabilities.
+ */
+pub fn has_timer_queries() -> bool {
+    Capabilities::current().timer_queries
+}
+
+/**
+ * Binds the screen for reading its pixels, from the buffer which was drawn to.
+ * Headless contexts may only have a front buffer, while ES always reads a surface's back buffer.
+ */
+pub unsafe fn bind_screen_for_reading() {
+    gl::BindFramebuffer( gl::READ_FRAMEBUFFER, 0 );
+    let double_buffered = match GlApi::current() {
+        GlApi::Desktop => {
+            let mut double_buffered: gl::types::GLboolean = gl::FALSE;
+            gl::GetBooleanv( gl::DOUBLEBUFFER, &mut double_buffered );
+            double_buffered == gl::TRUE
+        },
+        GlApi::Es | GlApi::WebGl2 => { true },
+    };
+    gl::ReadBuffer( if double_buffered { gl::BACK } else { gl::FRONT } );
+}
+
+/**
+ * Gets if buffers can be given immutable storage which stays mapped, which ES only has as an extension (EXT_buffer_storage), see Capabilities.
+ */
+pub fn has_buffer_storage() -> bool {
+    Capabilities::current().buffer_storage
+}
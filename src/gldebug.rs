@@ -0,0 +1,167 @@
+use std::ffi::{ CStr, c_void };
+
+use tracing::{ debug, error, info, warn };
+
+// Environment variable for the least severe driver messages which are printed, see DebugSeverity::from_name(), or "off"
+pub const SEVERITY_VAR: &str = "OPENGL_RAYTRACING_GL_DEBUG";
+
+// Environment variable which makes debug builds panic on the first GL error, where it happened
+pub const PANIC_VAR: &str = "OPENGL_RAYTRACING_GL_PANIC";
+
+/**
+ * Enum for the severities of the driver's debug messages, from least to most severe.
+ */
+#[derive(Clone, Copy, PartialEq, PartialOrd, Debug)]
+pub enum DebugSeverity {
+    Notification,   // Anything which isn't a problem, such as where buffers are placed
+    Low,            // Redundant state changes and small performance warnings
+    Medium,         // Deprecated or undefined behavior and major performance warnings
+    High,           // Errors, and undefined behavior which may crash
+}
+
+/**
+ * DebugSeverity functions.
+ */
+impl DebugSeverity {
+    // Every severity, from least to most severe
+    const ALL: [DebugSeverity; 4] = [DebugSeverity::Notification, DebugSeverity::Low, DebugSeverity::Medium, DebugSeverity::High];
+
+    /**
+     * Gets a severity from its name, such as "medium".
+     *
+     * @return The severity, or None if the name isn't one.
+     */
+    pub fn from_name( name: &str ) -> Option<DebugSeverity> {
+        DebugSeverity::ALL.into_iter().find( |severity| severity.name() == name.trim().to_lowercase() )
+    }
+
+    /**
+     * Gets the name of the severity.
+     */
+    pub fn name( &self ) -> &'static str {
+        match self {
+            DebugSeverity::Notification => { "notification" },
+            DebugSeverity::Low          => { "low" },
+            DebugSeverity::Medium       => { "medium" },
+            DebugSeverity::High         => { "high" },
+        }
+    }
+
+    /**
+     * Gets the severity's GLenum.
+     */
+    fn to_gl( self ) -> gl::types::GLenum {
+        match self {
+            DebugSeverity::Notification => { gl::DEBUG_SEVERITY_NOTIFICATION },
+            DebugSeverity::Low          => { gl::DEBUG_SEVERITY_LOW },
+            DebugSeverity::Medium       => { gl::DEBUG_SEVERITY_MEDIUM },
+            DebugSeverity::High         => { gl::DEBUG_SEVERITY_HIGH },
+        }
+    }
+
+    /**
+     * Gets a severity from its GLenum, or None if it isn't one.
+     */
+    fn from_gl( severity: gl::types::GLenum ) -> Option<DebugSeverity> {
+        DebugSeverity::ALL.into_iter().find( |known| known.to_gl() == severity )
+    }
+}
+
+/**
+ * Gets the least severe messages which are printed, which are the medium ones unless SEVERITY_VAR says otherwise.
+ *
+ * @return The severity, or None if no messages are printed.
+ */
+fn min_severity() -> Option<DebugSeverity> {
+    match std::env::var( SEVERITY_VAR ) {
+        Ok( name ) if name.trim() == "off" => { None },
+        Ok( name ) => {
+            Some( DebugSeverity::from_name( &name ).unwrap_or_else( || {
+                warn!( "{SEVERITY_VAR} must be one of notification, low, medium, high or off, not {name}" );
+                DebugSeverity::Medium
+            } ) )
+        },
+        Err( _ ) => { Some( DebugSeverity::Medium ) },
+    }
+}
+
+/**
+ * Gets the name of a message's source.
+ */
+fn source_name( source: gl::types::GLenum ) -> &'static str {
+    match source {
+        gl::DEBUG_SOURCE_API                => { "API" },
+        gl::DEBUG_SOURCE_WINDOW_SYSTEM      => { "window system" },
+        gl::DEBUG_SOURCE_SHADER_COMPILER    => { "shader compiler" },
+        gl::DEBUG_SOURCE_THIRD_PARTY        => { "third party" },
+        gl::DEBUG_SOURCE_APPLICATION        => { "application" },
+        _                                   => { "other" },
+    }
+}
+
+/**
+ * Gets the name of a message's type.
+ */
+fn type_name( message_type: gl::types::GLenum ) -> &'static str {
+    match message_type {
+        gl::DEBUG_TYPE_ERROR                => { "error" },
+        gl::DEBUG_TYPE_DEPRECATED_BEHAVIOR  => { "deprecated behavior" },
+        gl::DEBUG_TYPE_UNDEFINED_BEHAVIOR   => { "undefined behavior" },
+        gl::DEBUG_TYPE_PORTABILITY          => { "portability" },
+        gl::DEBUG_TYPE_PERFORMANCE          => { "performance" },
+        gl::DEBUG_TYPE_MARKER               => { "marker" },
+        _                                   => { "other" },
+    }
+}
+
+/**
+ * Called by the driver with each of its debug messages which isn't filtered out, see install().
+ * The user parameter is non-null if errors panic.
+ */
+extern "system" fn debug_callback(
+    source: gl::types::GLenum,
+    message_type: gl::types::GLenum,
+    id: gl::types::GLuint,
+    severity: gl::types::GLenum,
+    _length: gl::types::GLsizei,
+    message: *const gl::types::GLchar,
+    user_param: *mut c_void,
+) {
+    // (The message is null-terminated, whatever its length says)
+    let message = unsafe { CStr::from_ptr( message ) }.to_string_lossy();
+    let ( source, message_type_name, message ) = ( source_name( source ), type_name( message_type ), message.trim_end() );
+
+    // (The callback can't unwind, so the panic aborts right there, where the backtrace still shows the call which caused it)
+    if message_type == gl::DEBUG_TYPE_ERROR && !user_param.is_null() {
+        panic!( "GL {source} {message_type_name} ({id}): {message}" );
+    }
+    match DebugSeverity::from_gl( severity ) {
+        Some( DebugSeverity::High )                 => { error!( target: "gl", source, kind = message_type_name, id, "{message}" ) },
+        Some( DebugSeverity::Medium )               => { warn!( target: "gl", source, kind = message_type_name, id, "{message}" ) },
+        Some( DebugSeverity::Low )                  => { info!( target: "gl", source, kind = message_type_name, id, "{message}" ) },
+        Some( DebugSeverity::Notification ) | None  => { debug!( target: "gl", source, kind = message_type_name, id, "{message}" ) },
+    }
+}
+
+/**
+ * Logs the driver's debug messages (KHR_debug) from now on, which are otherwise lost, as they come, with the target "gl" and a level by their severity.
+ * The messages are filtered by SEVERITY_VAR, and debug builds panic on GL errors if PANIC_VAR is set.
+ * Most drivers only send the less severe messages to contexts created with the debug flag, which debug builds are.
+ * The context must be current.
+ */
+pub unsafe fn install() {
+    let Some( min_severity ) = min_severity() else { return };
+    if !gl::DebugMessageCallback::is_loaded() {
+        return;
+    }
+    let panics = cfg!(debug_assertions) && std::env::var_os( PANIC_VAR ).is_some();
+
+    gl::Enable( gl::DEBUG_OUTPUT );
+    gl::Enable( gl::DEBUG_OUTPUT_SYNCHRONOUS );
+    for severity in DebugSeverity::ALL {
+        let enabled = if severity >= min_severity { gl::TRUE } else { gl::FALSE };
+        gl::DebugMessageControl( gl::DONT_CARE, gl::DONT_CARE, severity.to_gl(), 0, std::ptr::null(), enabled );
+    }
+    let user_param = if panics { std::ptr::dangling() } else { std::ptr::null() };
+    gl::DebugMessageCallback( Some( debug_callback ), user_param );
+}
@@ -0,0 +1,184 @@
+use std::backtrace::Backtrace;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::sync::{ Arc, Mutex, OnceLock };
+use std::sync::atomic::{ AtomicU64, Ordering };
+
+use tracing::{ debug, warn };
+
+/**
+ * Enum for the kinds of OpenGL objects which are tracked.
+ */
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum GlObject {
+    Program,
+    Buffer,
+    Texture,
+    Framebuffer,
+}
+
+/**
+ * GlObject functions.
+ */
+impl GlObject {
+    /**
+     * Gets the name of the kind, as it is reported.
+     */
+    pub fn name( &self ) -> &'static str {
+        match self {
+            GlObject::Program       => { "program" },
+            GlObject::Buffer        => { "buffer" },
+            GlObject::Texture       => { "texture" },
+            GlObject::Framebuffer   => { "framebuffer" },
+        }
+    }
+}
+
+// The objects which are alive, by their context, kind and name, with the backtrace of where each was created
+// (Each context has names of its own, so those of contexts on other threads don't collide)
+type LiveObjects = HashMap<( u64, GlObject, u32 ), Arc<Backtrace>>;
+
+// The id the next context which is made current gets
+static NEXT_CONTEXT: AtomicU64 = AtomicU64::new( 1 );
+
+thread_local! {
+    // The id of the context which is current on this thread, or 0 if none has been made current
+    static CONTEXT: Cell<u64> = const { Cell::new( 0 ) };
+}
+
+/**
+ * Gets the objects which are alive.
+ */
+fn live() -> &'static Mutex<LiveObjects> {
+    static LIVE: OnceLock<Mutex<LiveObjects>> = OnceLock::new();
+    LIVE.get_or_init( || Mutex::new( HashMap::new() ) )
+}
+
+/**
+ * Records that a context was made current on this thread, which the objects created and deleted on the thread are then tracked under.
+ */
+pub fn context_made_current() {
+    CONTEXT.with( |context| context.set( NEXT_CONTEXT.fetch_add( 1, Ordering::Relaxed ) ) );
+}
+
+/**
+ * Gets the id of the context which is current on this thread.
+ */
+fn current_context() -> u64 {
+    CONTEXT.with( |context| context.get() )
+}
+
+/**
+ * Records that objects were created, with a backtrace of where, so they can be reported if they are never deleted (see report()).
+ * Only debug builds track objects, as capturing the backtraces would slow down release builds which create objects while rendering.
+ *
+ * @param kind The kind of the objects.
+ * @param ids The objects' names, as OpenGL gave them.
+ */
+pub fn created( kind: GlObject, ids: &[u32] ) {
+    if !cfg!(debug_assertions) {
+        return;
+    }
+    let backtrace = Arc::new( Backtrace::force_capture() );
+    let context = current_context();
+    if let Ok( mut live ) = live().lock() {
+        for &id in ids.iter().filter( |&&id| id != 0 ) {
+            live.insert( ( context, kind, id ), backtrace.clone() );
+        }
+    }
+}
+
+/**
+ * Records that objects were deleted.
+ *
+ * @param kind The kind of the objects.
+ * @param ids The objects' names.
+ */
+pub fn deleted( kind: GlObject, ids: &[u32] ) {
+    if !cfg!(debug_assertions) {
+        return;
+    }
+    let context = current_context();
+    if let Ok( mut live ) = live().lock() {
+        for &id in ids {
+            live.remove( &( context, kind, id ) );
+        }
+    }
+}
+
+/**
+ * Reports the objects of the context current on this thread which were created but never deleted, once the context is done with.
+ * Each place they were created at is logged once with how many it left, and its backtrace at the debug level.
+ * The objects are forgotten then, as they go with the context.
+ *
+ * @return How many objects were never deleted.
+ */
+pub fn report() -> usize {
+    let context = current_context();
+    let mut leaked = Vec::new();
+    let Ok( mut all ) = live().lock() else { return 0 };
+    all.retain( |( object_context, kind, _ ), backtrace| {
+        if *object_context == context {
+            leaked.push( ( *kind, backtrace.clone() ) );
+        }
+        *object_context != context
+    } );
+    drop( all );
+    if leaked.is_empty() {
+        debug!( "Every GL object was deleted" );
+        return 0;
+    }
+
+    // (Objects created together, or in the same place, are reported together)
+    let mut sites: HashMap<String, HashMap<GlObject, usize>> = HashMap::new();
+    for ( kind, backtrace ) in leaked.iter() {
+        *sites.entry( backtrace.to_string() ).or_default().entry( *kind ).or_default() += 1;
+    }
+    let mut counts: HashMap<GlObject, usize> = HashMap::new();
+    for ( kind, _ ) in leaked.iter() {
+        *counts.entry( *kind ).or_default() += 1;
+    }
+    let mut counts: Vec<String> = counts.into_iter().map( |( kind, count )| format!( "{count} {}s", kind.name() ) ).collect();
+    counts.sort();
+    warn!( "{} GL objects were never deleted ({}), from {} places, which RUST_LOG=debug shows", leaked.len(), counts.join( ", " ), sites.len() );
+    for ( backtrace, kinds ) in sites {
+        let kinds: Vec<String> = kinds.into_iter().map( |( kind, count )| format!( "{count} {}", kind.name() ) ).collect();
+        debug!( "{} created at:\n{backtrace}", kinds.join( ", " ) );
+    }
+    leaked.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg_attr(not(debug_assertions), ignore)]
+    fn deleted_objects_are_forgotten() {
+        // (The ids are far from those a driver would give the other tests' contexts)
+        let ids = [u32::MAX - 2, u32::MAX - 1];
+        created( GlObject::Framebuffer, &ids );
+        let is_live = |id: u32| live().lock().unwrap().contains_key( &( current_context(), GlObject::Framebuffer, id ) );
+        assert!( is_live( ids[0] ) && is_live( ids[1] ) );
+        deleted( GlObject::Framebuffer, &ids[..1] );
+        assert!( !is_live( ids[0] ) && is_live( ids[1] ) );
+        deleted( GlObject::Framebuffer, &ids[1..] );
+    }
+
+    #[test]
+    #[cfg_attr(not(debug_assertions), ignore)]
+    fn contexts_are_reported_apart() {
+        // (Each thread makes a context of its own current, which gives out the same name)
+        let id = u32::MAX - 3;
+        let in_context = move || {
+            context_made_current();
+            created( GlObject::Texture, &[id] );
+            std::thread::sleep( std::time::Duration::from_millis( 50 ) );
+            report()
+        };
+        let threads = [std::thread::spawn( in_context ), std::thread::spawn( in_context )];
+        for thread in threads {
+            assert_eq!( thread.join().unwrap(), 1 );
+        }
+    }
+}
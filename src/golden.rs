@@ -0,0 +1,237 @@
+use tracing::info;
+
+use crate::capabilities::Capabilities;
+use crate::output;
+
+// Directory of the stored images, which are committed along with the scenes they are of
+pub const GOLDEN_DIR: &str = "tests/golden";
+
+// Directory which the renders and their differences are saved to when they don't match
+pub const DIFF_DIR: &str = "target/golden";
+
+// Environment variable which makes the tests store their renders as the new golden images, after an intended change
+pub const UPDATE_VAR: &str = "OPENGL_RAYTRACING_UPDATE_GOLDEN";
+
+/**
+ * Struct for how far a render may be from its golden image, which it never matches exactly across GPUs and drivers.
+ * The images are compared as 8-bit sRGB, where differences are about as visible in the darks as in the lights.
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct Tolerance {
+    pub channel: u8,    // Difference in any channel beyond which a pixel is an outlier
+    pub outliers: f32,  // Share of the pixels which may be outliers, [0, 1]
+    pub mean: f32,      // Mean difference of the channels over the image, out of 255
+}
+
+/**
+ * Tolerance functions.
+ */
+impl Tolerance {
+    /**
+     * Gets the tolerance for noisy renders, which differ a little everywhere when the GPU rounds its random numbers differently,
+     * but shouldn't differ by much anywhere.
+     */
+    pub fn noisy() -> Tolerance {
+        Tolerance { channel: 24, outliers: 0.02, mean: 3.0 }
+    }
+}
+
+/**
+ * Struct for the difference between a render and its golden image, see compare().
+ */
+pub struct Comparison {
+    pub outliers: f32,          // Share of the pixels which are outliers, see Tolerance
+    pub mean: f32,              // Mean difference of the channels, out of 255
+    pub diff: image::RgbImage,  // The difference of each pixel, brightened so small differences show
+}
+
+/**
+ * Comparison functions.
+ */
+impl Comparison {
+    /**
+     * Checks if the difference is within a tolerance.
+     */
+    pub fn within( &self, tolerance: &Tolerance ) -> bool {
+        self.outliers <= tolerance.outliers && self.mean <= tolerance.mean
+    }
+}
+
+/**
+ * Compares a render to its golden image.
+ *
+ * @param expected The golden image.
+ * @param actual The render.
+ * @param tolerance The tolerance, whose outlier limit is counted by.
+ *
+ * @return The difference, or None if the images are of different sizes.
+ */
+pub fn compare( expected: &image::RgbImage, actual: &image::RgbImage, tolerance: &Tolerance ) -> Option<Comparison> {
+    if expected.dimensions() != actual.dimensions() {
+        return None;
+    }
+    let ( mut outliers, mut total ) = ( 0, 0 );
+    let mut diff = image::RgbImage::new( actual.width(), actual.height() );
+    for ( ( expected, actual ), diff ) in expected.pixels().zip( actual.pixels() ).zip( diff.pixels_mut() ) {
+        let channels: Vec<u8> = expected.0.iter().zip( actual.0 ).map( |( &a, b )| a.abs_diff( b ) ).collect();
+        if channels.iter().any( |&channel| channel > tolerance.channel ) {
+            outliers += 1;
+        }
+        total += channels.iter().map( |&channel| channel as u32 ).sum::<u32>();
+        *diff = image::Rgb( [channels[0].saturating_mul( 8 ), channels[1].saturating_mul( 8 ), channels[2].saturating_mul( 8 )] );
+    }
+    let pixels = ( actual.width() * actual.height() ).max( 1 ) as f32;
+    Some( Comparison { outliers: outliers as f32 / pixels, mean: total as f32 / ( pixels * 3.0 ), diff } )
+}
+
+/**
+ * Checks a render against the golden image of the same name, panicking if it is too different.
+ * On failure, the render and the difference are saved to DIFF_DIR as <name>.png and <name>.diff.png.
+ * A missing golden image fails too, and renders are only stored as their golden images while UPDATE_VAR is set.
+ *
+ * @param name The name of the image, which its file is named after.
+ * @param width The width of the render, in pixels.
+ * @param height The height of the render, in pixels.
+ * @param pixels The render, as RGB with the bottom row first.
+ * @param tolerance How far the render may be from the golden image.
+ */
+pub fn check( name: &str, width: u32, height: u32, pixels: &[f32], tolerance: &Tolerance ) {
+    let actual = output::srgb_image( width, height, pixels );
+    let golden_path = format!( "{GOLDEN_DIR}/{name}.png" );
+    if std::env::var_os( UPDATE_VAR ).is_some() {
+        std::fs::create_dir_all( GOLDEN_DIR ).unwrap();
+        actual.save( &golden_path ).unwrap();
+        info!( "Stored {golden_path}, rendered with {}, which later renders are compared to", Capabilities::current().renderer );
+        return;
+    }
+    // (A missing image fails, as storing the render would pass without comparing it to anything)
+    let expected = match image::open( &golden_path ) {
+        Ok( expected ) => { expected.into_rgb8() },
+        Err( err ) => { panic!( "{golden_path} couldn't be read ({err}), run with {UPDATE_VAR}=1 to store the render as it" ) },
+    };
+
+    let comparison = compare( &expected, &actual, tolerance );
+    if comparison.as_ref().is_some_and( |comparison| comparison.within( tolerance ) ) {
+        return;
+    }
+    std::fs::create_dir_all( DIFF_DIR ).unwrap();
+    let actual_path = format!( "{DIFF_DIR}/{name}.png" );
+    actual.save( &actual_path ).unwrap();
+    match comparison {
+        Some( comparison ) => {
+            let diff_path = format!( "{DIFF_DIR}/{name}.diff.png" );
+            comparison.diff.save( &diff_path ).unwrap();
+            panic!(
+                "{name} differs from {golden_path} by {:.2} on average, with {:.1}% outliers (at most {:.2} and {:.1}%), see {actual_path} and {diff_path}",
+                comparison.mean, comparison.outliers * 100.0, tolerance.mean, tolerance.outliers * 100.0
+            );
+        },
+        None => {
+            panic!( "{name} is {}x{}, but {golden_path} is {}x{}, see {actual_path}", actual.width(), actual.height(), expected.width(), expected.height() );
+        },
+    }
+}
+
+mod tests {
+    use super::*;
+    use crate::integrators::AmbientOcclusion;
+    use crate::raytracing::{ RTCamera, RTIntegrator, RTMaterial, RTSettings, RTSphere, RTTriangle };
+    use crate::testing::{ TestScene, with_gl };
+
+    // Size of the reference renders, which are small so the tests stay fast even on software drivers
+    const WIDTH: u32 = 64;
+    const HEIGHT: u32 = 48;
+
+    // Frames accumulated into each reference render
+    const FRAMES: u32 = 16;
+
+    /**
+     * Gets a quad of two triangles, with flat normals facing +y, for a floor.
+     */
+    fn floor( half_size: f32, height: f32, color: glm::Vec4 ) -> Vec<RTTriangle> {
+        let corner = |x: f32, z: f32| glm::vec3( x * half_size, height, z * half_size ).into();
+        let material = RTMaterial { color, ..RTMaterial::new() };
+        let normal: crate::raytracing::Vec3a16 = glm::vec3( 0.0, 1.0, 0.0 ).into();
+        [[( -1.0, -1.0 ), ( -1.0, 1.0 ), ( 1.0, 1.0 )], [( -1.0, -1.0 ), ( 1.0, 1.0 ), ( 1.0, -1.0 )]].iter()
+            .map( |corners| RTTriangle {
+                p0: corner( corners[0].0, corners[0].1 ),
+                p1: corner( corners[1].0, corners[1].1 ),
+                p2: corner( corners[2].0, corners[2].1 ),
+                normal0: normal,
+                normal1: normal,
+                normal2: normal,
+                material: material.clone(),
+            } )
+            .collect()
+    }
+
+    /**
+     * Gets the camera which the reference scenes are seen from.
+     */
+    fn camera() -> RTCamera {
+        RTCamera::look_at( glm::vec3( 0.0, 3.0, -10.0 ), glm::vec3( 0.0, 1.0, 0.0 ), glm::vec3( 0.0, 1.0, 0.0 ), 60.0, glm::vec2( WIDTH as f32, HEIGHT as f32 ) )
+    }
+
+    /**
+     * Gets the settings which a reference scene is rendered with, which are the interactive ones with a fixed seed.
+     */
+    fn settings( integrator: RTIntegrator ) -> RTSettings {
+        RTSettings { integrator, seed: 1, ..crate::engine::initial_settings( None ) }
+    }
+
+    /**
+     * Renders a reference scene and checks it against its golden image.
+     */
+    fn check_scene( name: &'static str, scene: TestScene, settings: RTSettings ) {
+        let pixels = with_gl( move || unsafe { scene.render( &camera(), &settings, FRAMES ) } ).unwrap();
+        check( name, WIDTH, HEIGHT, &pixels, &Tolerance::noisy() );
+    }
+
+    #[test]
+    fn comparison_counts_outliers() {
+        let expected = image::RgbImage::from_pixel( 4, 4, image::Rgb( [100, 100, 100] ) );
+        let mut actual = expected.clone();
+        assert!( compare( &expected, &actual, &Tolerance::noisy() ).unwrap().within( &Tolerance { channel: 0, outliers: 0.0, mean: 0.0 } ) );
+
+        actual.put_pixel( 1, 2, image::Rgb( [100, 200, 100] ) );
+        let comparison = compare( &expected, &actual, &Tolerance::noisy() ).unwrap();
+        assert_eq!( comparison.outliers, 1.0 / 16.0 );
+        assert!( ( comparison.mean - 100.0 / 48.0 ).abs() < 1e-4 );
+        assert_eq!( comparison.diff.get_pixel( 1, 2 ).0, [0, 255, 0] );
+        assert!( !comparison.within( &Tolerance::noisy() ) );
+
+        assert!( compare( &expected, &image::RgbImage::new( 4, 3 ), &Tolerance::noisy() ).is_none() );
+    }
+
+    #[test]
+    #[ignore = "needs an OpenGL context, run with cargo test -- --include-ignored"]
+    fn spheres_path_traced() {
+        let scene = TestScene { spheres: crate::scene::scene_spheres( 0.0 ), triangles: vec![] };
+        check_scene( "spheres_path_traced", scene, settings( RTIntegrator::PathTrace ) );
+    }
+
+    #[test]
+    #[ignore = "needs an OpenGL context, run with cargo test -- --include-ignored"]
+    fn floor_direct_light() {
+        let light = RTSphere {
+            radius: 1.0,
+            center: glm::vec3( -2.0, 4.0, 0.0 ).into(),
+            material: RTMaterial { emission_color: glm::vec4( 1.0, 0.9, 0.8, 8.0 ), ..RTMaterial::new() },
+        };
+        let ball = RTSphere {
+            radius: 1.0,
+            center: glm::vec3( 1.0, 1.0, 0.0 ).into(),
+            material: RTMaterial { color: glm::vec4( 0.8, 0.3, 0.2, 1.0 ), ..RTMaterial::new() },
+        };
+        let scene = TestScene { spheres: vec![light, ball], triangles: floor( 6.0, 0.0, glm::vec4( 0.7, 0.7, 0.7, 1.0 ) ) };
+        check_scene( "floor_direct_light", scene, settings( RTIntegrator::DirectLight ) );
+    }
+
+    #[test]
+    #[ignore = "needs an OpenGL context, run with cargo test -- --include-ignored"]
+    fn floor_ambient_occlusion() {
+        let ball = RTSphere { radius: 1.0, center: glm::vec3( 0.0, 1.0, 0.0 ).into(), material: RTMaterial::new() };
+        let scene = TestScene { spheres: vec![ball], triangles: floor( 6.0, 0.0, glm::vec4( 1.0, 1.0, 1.0, 1.0 ) ) };
+        check_scene( "floor_ambient_occlusion", scene, settings( RTIntegrator::AmbientOcclusion( AmbientOcclusion { distance: 2.0, falloff: 1.0 } ) ) );
+    }
+}
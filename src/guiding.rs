@@ -0,0 +1,101 @@
+use crate::device::{ self, BufferUsage, Device };
+use crate::glapi;
+use crate::shader::Shader;
+
+// Amount of cells along each axis of the guide's grid
+pub const GUIDING_RESOLUTION: usize = 16;
+
+// Amount of directional bins in each cell (8x8, equal-area)
+pub const GUIDING_BINS: usize = 64;
+
+// Bindings of the guide's buffers in the shader
+const LEARNING_BINDING: u32 = 6;
+const SAMPLING_BINDING: u32 = 7;
+
+/**
+ * Struct for path guiding, which learns where light comes from so bounces can be steered towards it.
+ * The scene's bounds are split into a grid of cells, where each cell holds a histogram of the light arriving from each direction.
+ *
+ * Paths add the light they find to the learning buffer as they're traced, and bounces are steered using the sampling buffer.
+ * The learning buffer is copied into the sampling buffer between frames, so the distribution doesn't change while it's sampled.
+ */
+pub struct PathGuide {
+    buffers: [device::Buffer; 2],
+    bounds_min: glm::Vec3,
+    bounds_max: glm::Vec3,
+}
+
+/**
+ * PathGuide functions.
+ */
+impl PathGuide {
+    /**
+     * Creates an empty guide covering the given bounds.
+     * Points outside the bounds use the closest cell.
+     *
+     * @param bounds_min The bottom left corner of the bounds.
+     * @param bounds_max The top right corner of the bounds.
+     */
+    pub unsafe fn new( bounds_min: glm::Vec3, bounds_max: glm::Vec3 ) -> PathGuide {
+        let mut guide = PathGuide {
+            buffers: [0; 2],
+            bounds_min,
+            bounds_max,
+        };
+
+        let size = GUIDING_RESOLUTION.pow( 3 ) * GUIDING_BINS * std::mem::size_of::<u32>();
+        for ( buffer, binding ) in guide.buffers.iter_mut().zip( [LEARNING_BINDING, SAMPLING_BINDING] ) {
+            *buffer = device::current().create_buffer();
+            device::current().allocate_buffer( *buffer, size, None, BufferUsage::Storage );
+            device::current().bind_storage_buffer( *buffer, binding );
+        }
+
+        guide.reset();
+        guide
+    }
+
+    /**
+     * Forgets everything learned, which must be done when the lighting of the scene changes.
+     */
+    pub unsafe fn reset( &mut self ) {
+        for buffer in self.buffers {
+            gl::BindBuffer( gl::SHADER_STORAGE_BUFFER, buffer );
+            glapi::clear_buffer_u32( gl::SHADER_STORAGE_BUFFER, 0, None, 0 );
+        }
+        gl::BindBuffer( gl::SHADER_STORAGE_BUFFER, 0 );
+    }
+
+    /**
+     * Deletes the guide's buffers, once it is done with.
+     */
+    pub unsafe fn delete( self ) {
+        for buffer in self.buffers {
+            device::current().delete_buffer( buffer );
+        }
+    }
+
+    /**
+     * Makes what was learned so far available for sampling.
+     * Must be called between frames.
+     */
+    pub unsafe fn update( &self ) {
+        gl::MemoryBarrier( gl::SHADER_STORAGE_BARRIER_BIT );
+        gl::BindBuffer( gl::COPY_READ_BUFFER, self.buffers[0] );
+        gl::BindBuffer( gl::COPY_WRITE_BUFFER, self.buffers[1] );
+        let size = ( GUIDING_RESOLUTION.pow( 3 ) * GUIDING_BINS * std::mem::size_of::<u32>() ) as isize;
+        gl::CopyBufferSubData( gl::COPY_READ_BUFFER, gl::COPY_WRITE_BUFFER, 0, 0, size );
+        gl::BindBuffer( gl::COPY_READ_BUFFER, 0 );
+        gl::BindBuffer( gl::COPY_WRITE_BUFFER, 0 );
+    }
+
+    /**
+     * Sends the guide's bounds to the shader.
+     * The shader must be active.
+     *
+     * @param shader The shader.
+     */
+    pub unsafe fn send_uniforms( &self, shader: &Shader ) {
+        gl::Uniform3fv( shader.get_uniform_location( "guidingBoundsMin" ), 1, self.bounds_min.as_ptr() );
+        gl::Uniform3fv( shader.get_uniform_location( "guidingBoundsMax" ), 1, self.bounds_max.as_ptr() );
+    }
+}
@@ -0,0 +1,850 @@
+use glutin::NotCurrent;
+use glutin::event_loop::EventLoop;
+use tracing::{ info, warn };
+
+use crate::error::EngineError;
+use crate::accumulator::Accumulator;
+use crate::camera::{ Camera, CameraBookmarks, Lens, PhysicalCamera };
+use crate::context::RenderContext;
+use crate::glapi::GlApi;
+use crate::multigpu::{ self, GpuDevice };
+use crate::output;
+use crate::progress::ProgressReporter;
+use crate::raytracing::{ RTCamera, RTProjection, RTSettings, RTStereo };
+use crate::sequence::{ SequenceSettings, SequenceCamera, Turntable };
+use crate::tiles::TileScheduler;
+use crate::wavefront::{ Shading, WorkgroupSize };
+
+/**
+ * How to run an offline render, which is printed by --help.
+ */
+pub const USAGE: &str = "\
+Usage: opengl_raytracing_engine --headless [options]
+
+Renders the scene without a window, and saves the image once it has enough samples.
+
+Options:
+    --scene <path>           The OBJ model to render (resources/Knight.obj)
+    --width <pixels>         The width of the image (1280)
+    --height <pixels>        The height of the image (720)
+    --samples <count>        How many frames are accumulated (256)
+    --tile-size <pixels>     Renders the image in square tiles of this size, for images too large to render whole
+    --checkpoint <path>      Saves the render to this file now and then, and resumes from it if it is already there
+    --checkpoint-interval <seconds>
+                             How often the checkpoint is saved (60)
+    --progress <seconds>     How often the progress and the time left are printed, or 0 to not print them (5)
+    --bounces <count>        Limit for every kind of bounce (the interactive defaults)
+    --seed <number>          Seed of the random numbers, so the same seed gives the same image (0)
+    --renderer <gpu|cpu>     Renders on the GPU, or with the reference path tracer on the CPU, which only covers the
+                             materials (no fog, sun or volumes) and single images without tiles or AOVs. The CPU is
+                             also used when there is no OpenGL context to render with (gpu)
+    --shading <fragment|compute|wavefront>
+                             Renders the frames with a fullscreen fragment shader, a compute dispatch, or compute stages
+                             which work through queues of rays, for what they cover (fragment)
+    --workgroup-size <XxY|auto>
+                             The size of the compute shader's work groups, in pixels, or auto to time a few sizes over
+                             the first frames and keep the fastest (auto)
+    --output <path>          The file the image is saved to (render.png)
+    --format <png|exr|hdr|mp4|webm>
+                             The format of the file (from the output's extension)
+    --aovs <path|layers>     Also saves the albedo, normal, depth, object ID, direct and indirect light to EXR files
+                             named after this, with each one's name before the extension (render.exr -> render_albedo.exr),
+                             or as layers of the output with \"layers\", which must then be an EXR file
+    --bookmark <name>        Starts from a camera bookmark saved with the scene
+    --position <x,y,z>       The camera's position
+    --angle <x,y,z>          The camera's rotation, in degrees
+    --fov <degrees>          The camera's field of view
+    --focus-distance <units> Distance to the plane in focus
+    --aperture <units>       Radius of the lens, or 0 for a pinhole
+    --projection <perspective|orthographic|fisheye|equirectangular>
+                             How the camera projects the rays, where orthographic rays are parallel, for isometric
+                             views and technical illustrations, and equirectangular renders are 360x180 degree
+                             panoramas, for skyboxes and VR (perspective)
+    --ortho-height <units>   The height of what an orthographic camera sees (10)
+    --fisheye-fov <degrees>  How far a fisheye sees across the circle which fits the image's height (180)
+    --distortion <k1,k2>     The lens's radial distortion, where positive values bulge out (barrel) and negative ones
+                             pinch in (pincushion), for matching renders to footage from a real camera (0,0)
+    --chromatic-aberration <amount>
+                             How much larger the red channel is than the green, and the blue smaller, within -1 and 1 (0)
+    --stereo <mono|sbs|ou>   Renders an eye in each half of the image, side by side or over and under (the left eye on
+                             the left or top), for VR video (mono)
+    --ipd <units>            The distance between the eyes of a stereo camera (0.064)
+    --focal-length <mm>      Sees the scene through a physical camera, which decides the fov, aperture and exposure from
+    --f-stop <number>        its lens, f-stop, shutter time and ISO, where those not given are a 50mm lens at f/8, 1/125s
+    --shutter <seconds>      and ISO 100. Its exposure is added to the tonemapper's, and --fov and --aperture override it
+    --iso <number>
+
+Render farms:
+    --coordinator <address>  Hands the tiles or frames out to workers which connect to this address (0.0.0.0:7878),
+                             and saves the output once they are all returned, without rendering itself
+    --worker <address>       Renders tiles or frames for the coordinator at this address, with its options
+    --task-timeout <seconds> How long a worker may take to return a task before it is counted as lost, and the task is
+                             handed out again (3600)
+Workers need the scene (and camera paths) at the same paths as the coordinator, and renders need --tile-size or --frames.
+
+Several GPUs (experimental):
+    --gpus <count|list>      Splits the tiles or frames over several GPUs on this machine, with a worker process per GPU,
+                             where images not rendered in tiles are split into a few tiles per GPU. A count picks them
+                             with DRI_PRIME=0, DRI_PRIME=1... (Mesa), and a list picks each with environment variables,
+                             split by commas and joined by +, such as DISPLAY=:0.0,DISPLAY=:0.1
+
+Sequences:
+    --frames <first>-<last>  Renders these frames one after the other, each with all its samples
+    --fps <frames>           Frames per second of the scene's time (24)
+    --camera-path <path>     The camera follows a path saved with ctrl + K (its frames, unless --frames is given)
+    --turntable <x,y,z,radius,height>
+                             The camera circles once around x,y,z over the frames
+    --resume-from <frame>    Starts from this frame, to resume a sequence which was stopped
+Each frame is saved to the output with its number in place of the #s (render_####.png), or before the extension.
+Seen through a physical camera (such as with --shutter), a moving camera is blurred over the time its shutter is open.
+Outputs which are videos (.mp4, .webm, .mkv or .mov) are encoded with ffmpeg instead, which must be installed.
+EXR files and AOVs need the engine to be built with the exr feature, and videos with the video feature.
+
+Benchmarks:
+    --bench                  Renders a fixed suite of scenes instead, each for a while, and saves how fast they went
+    --bench-scenes <list>    The scenes, split by commas (sphere-field,cornell-box,big-mesh)
+    --bench-seconds <seconds>
+                             How long each scene is rendered for (10)
+    --width, --height        The size of the frames (1280x720)
+    --output <path>          The report, as JSON (replaced) or CSV (added to, a row per scene) by its extension (bench.json)
+The report has the ms per frame, rays per second and how far the image still changed over the second half of each
+scene, along with the GPU and the git commit, to compare runs across commits and GPUs.
+";
+
+/**
+ * Enum for the formats which offline renders can be saved in.
+ */
+#[derive(Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Png,    // What is shown on the screen, post-processed to 8 bits
+    Exr,    // The accumulated image before any post-processing, as 32-bit floats
+    Hdr,    // The accumulated image before any post-processing, as Radiance RGBE
+    Video,  // The frames of a sequence as they are shown, encoded with ffmpeg, see video::VideoEncoder
+}
+
+/**
+ * OutputFormat functions.
+ */
+impl OutputFormat {
+    /**
+     * Gets a format from its name, which is also the extension of its files.
+     *
+     * @param name The name, such as "exr".
+     *
+     * @return The format, or None if there is none by that name.
+     */
+    pub fn from_name( name: &str ) -> Option<OutputFormat> {
+        match name.to_ascii_lowercase().as_str() {
+            "png"   => { Some( OutputFormat::Png ) },
+            "exr"   => { Some( OutputFormat::Exr ) },
+            "hdr"   => { Some( OutputFormat::Hdr ) },
+            "mp4" | "webm" | "mkv" | "mov" => { Some( OutputFormat::Video ) },
+            _       => { None },
+        }
+    }
+}
+
+/**
+ * Enum for where the AOVs of an offline render are saved.
+ */
+#[derive(Clone, PartialEq)]
+pub enum AovOutput {
+    Files( String ),    // An EXR file per AOV, named after the path, see output::save_aovs()
+    Layers,             // Layers of the output, which must be an EXR file, see output::save_layered_exr()
+}
+
+/**
+ * Struct for overriding the camera of an offline render, where anything left as None is kept as it is.
+ * The bookmark is applied first, then the physical camera, so the other values override them in turn.
+ */
+#[derive(Clone, PartialEq, Default, Debug)]
+pub struct CameraOverride {
+    pub bookmark: Option<String>, // Name of a bookmark saved with the scene, see camera::CameraBookmarks
+    pub position: Option<glm::Vec3>,
+    pub angle: Option<glm::Vec3>, // In degrees
+    pub fov: Option<f32>, // In degrees
+    pub focus_distance: Option<f32>,
+    pub aperture_radius: Option<f32>,
+    pub physical: Option<PhysicalCamera>, // Also decides the exposure, which is added to the tonemapper's
+    pub lens: Lens, // How the rays are projected, which offline renders don't take from the config
+}
+
+/**
+ * CameraOverride functions.
+ */
+impl CameraOverride {
+    /**
+     * Applies the override to a camera, starting with the bookmark and physical camera (if any) and then the values which were given.
+     *
+     * @param camera The camera.
+     * @param bookmarks The bookmarks saved with the scene.
+     * @param focus_distance The focus distance the camera has without the override.
+     * @param aperture_radius The aperture radius the camera has without the override.
+     *
+     * @return The focus distance and aperture radius with the override.
+     */
+    pub fn apply( &self, camera: &mut Camera, bookmarks: &CameraBookmarks, mut focus_distance: f32, mut aperture_radius: f32 ) -> ( f32, f32 ) {
+        if let Some( name ) = &self.bookmark {
+            match bookmarks.get( name ) {
+                Some( bookmark ) => {
+                    camera.set_vars( Some( bookmark.position ), Some( bookmark.angle ), Some( bookmark.fov ), None, None );
+                    focus_distance = bookmark.focus_distance;
+                },
+                None => { warn!( "There is no camera bookmark named {name}, so the default view is used instead" ) },
+            }
+        }
+        if let Some( physical ) = &self.physical {
+            aperture_radius = camera.apply_physical( physical );
+        }
+        camera.set_vars(
+            self.position,
+            self.angle.map( |angle| angle * ( glm::pi::<f32>() / 180.0 ) ),
+            self.fov,
+            None,
+            None
+        );
+        ( self.focus_distance.unwrap_or( focus_distance ), self.aperture_radius.unwrap_or( aperture_radius ) )
+    }
+}
+
+/**
+ * Enum for what offline renders are rendered with.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Renderer {
+    Gpu,    // The shader, through OpenGL, falling back to the CPU when there is no context to render with
+    Cpu,    // The reference path tracer on the CPU, see reference::ReferenceRenderer
+}
+
+/**
+ * Struct for the settings of an offline render, where the scene is rendered without a window and saved once it has enough samples.
+ * It is started from the command line with --headless, see from_args() and USAGE.
+ */
+#[derive(Clone)]
+pub struct HeadlessSettings {
+    pub scene: String, // The OBJ model which is rendered
+    pub width: u32,
+    pub height: u32,
+    pub samples: u32, // How many frames are accumulated before the image is saved
+    pub tile_size: Option<u32>, // The size of the tiles the image is rendered in, or None to render it whole, see tiles::TileScheduler
+    pub bounces: Option<u32>, // Limit for every kind of bounce, or None to keep the interactive defaults
+    pub seed: u32,
+    pub renderer: Renderer,
+    pub shading: Shading, // How the frames are rendered
+    pub workgroup_size: Option<WorkgroupSize>, // The size of the compute path's work groups, or None to tune it, see WorkgroupTuner
+    pub output: String, // The file the image is saved to
+    pub format: OutputFormat,
+    pub aovs: Option<AovOutput>, // Where the AOVs are saved, or None to not render them
+    pub camera: CameraOverride,
+    pub sequence: Option<SequenceSettings>, // The frames of an animation, or None to render a single image
+    pub checkpoint: Option<String>, // The file which the render is checkpointed to, see checkpoint::Checkpointer
+    pub checkpoint_interval: f32, // How often the checkpoint is saved, in seconds
+    pub progress_interval: f32, // How often the progress is printed, in seconds, or 0 to not print it
+    pub coordinator: Option<String>, // The address which workers connect to, when the render is handed out to them, see farm::Coordinator
+    pub task_timeout: f32, // How long the coordinator waits for a worker to return a task, in seconds
+    pub gpus: Option<Vec<GpuDevice>>, // The GPUs which the render is split over, or None to render on one, see multigpu::render()
+}
+
+/**
+ * Parses a value given to an argument.
+ *
+ * @param arg The argument, for the error.
+ * @param value The value.
+ *
+ * @return The parsed value, or an error if it couldn't be parsed.
+ */
+pub fn parse_value<T: std::str::FromStr>( arg: &str, value: &str ) -> Result<T, EngineError> {
+    value.trim().parse::<T>().map_err( |_| EngineError::parse( "HEADLESS::INVALID_VALUE", format!( "{arg} {value}" ) ) )
+}
+
+/**
+ * Parses a vector given to an argument as x,y,z.
+ *
+ * @param arg The argument, for the error.
+ * @param value The value.
+ *
+ * @return The parsed vector, or an error if it couldn't be parsed.
+ */
+fn parse_vec3( arg: &str, value: &str ) -> Result<glm::Vec3, EngineError> {
+    let components = value.split( ',' ).map( |component| parse_value::<f32>( arg, component ) ).collect::<Result<Vec<f32>, EngineError>>()?;
+    match components[..] {
+        [x, y, z]   => { Ok( glm::vec3( x, y, z ) ) },
+        _           => { Err( EngineError::parse( "HEADLESS::INVALID_VALUE", format!( "{arg} {value}\nExpected three values, as x,y,z" ) ) ) },
+    }
+}
+
+/**
+ * Parses a vector given to an argument as x,y.
+ *
+ * @param arg The argument, for the error.
+ * @param value The value.
+ *
+ * @return The parsed vector, or an error if it couldn't be parsed.
+ */
+fn parse_vec2( arg: &str, value: &str ) -> Result<glm::Vec2, EngineError> {
+    let components = value.split( ',' ).map( |component| parse_value::<f32>( arg, component ) ).collect::<Result<Vec<f32>, EngineError>>()?;
+    match components[..] {
+        [x, y]  => { Ok( glm::vec2( x, y ) ) },
+        _       => { Err( EngineError::parse( "HEADLESS::INVALID_VALUE", format!( "{arg} {value}\nExpected two values, as x,y" ) ) ) },
+    }
+}
+
+/**
+ * Parses a range of frames given to an argument as first-last, or a single frame.
+ *
+ * @param arg The argument, for the error.
+ * @param value The value.
+ *
+ * @return The first and last frame, or an error if they couldn't be parsed.
+ */
+fn parse_frames( arg: &str, value: &str ) -> Result<( u32, u32 ), EngineError> {
+    let ( first, last ) = match value.split_once( '-' ) {
+        Some( ( first, last ) ) => { ( parse_value( arg, first )?, parse_value( arg, last )? ) },
+        None                    => { let frame = parse_value( arg, value )?; ( frame, frame ) },
+    };
+    if first > last {
+        return Err( EngineError::parse( "HEADLESS::INVALID_VALUE", format!( "{arg} {value}\nThe first frame must not be after the last" ) ) );
+    }
+    Ok( ( first, last ) )
+}
+
+/**
+ * Parses a turntable given to an argument as x,y,z,radius,height.
+ *
+ * @param arg The argument, for the error.
+ * @param value The value.
+ *
+ * @return The turntable, or an error if it couldn't be parsed.
+ */
+fn parse_turntable( arg: &str, value: &str ) -> Result<Turntable, EngineError> {
+    let components = value.split( ',' ).map( |component| parse_value::<f32>( arg, component ) ).collect::<Result<Vec<f32>, EngineError>>()?;
+    match components[..] {
+        [x, y, z, radius, height]   => { Ok( Turntable { center: glm::vec3( x, y, z ), radius, height } ) },
+        _                           => { Err( EngineError::parse( "HEADLESS::INVALID_VALUE", format!( "{arg} {value}\nExpected five values, as x,y,z,radius,height" ) ) ) },
+    }
+}
+
+/**
+ * Gets the projection which was given by name, along with its values, which may each be given before or after it.
+ *
+ * @param name The name of the projection, or None if it wasn't given.
+ * @param ortho_height The height of an orthographic projection, if it was given.
+ * @param fisheye_fov The fov of a fisheye projection, if it was given.
+ *
+ * @return The projection, or an error if the name is unknown, or a value was given to another projection.
+ */
+fn parse_projection( name: Option<&str>, ortho_height: Option<f32>, fisheye_fov: Option<f32> ) -> Result<RTProjection, EngineError> {
+    let projection = match name.unwrap_or( "perspective" ) {
+        "perspective"       => { RTProjection::Perspective },
+        "orthographic"      => { RTProjection::Orthographic { height: ortho_height.unwrap_or( 10.0 ) } },
+        "fisheye"           => { RTProjection::Fisheye { fov: fisheye_fov.unwrap_or( 180.0 ) } },
+        "equirectangular"   => { RTProjection::Equirectangular },
+        name                => { return Err( EngineError::parse( "HEADLESS::INVALID_VALUE", format!( "--projection {name}\nThe projection must be perspective, orthographic, fisheye or equirectangular" ) ) ) },
+    };
+
+    // (Values of other projections would be ignored without a word)
+    if ortho_height.is_some() && !matches!( projection, RTProjection::Orthographic { .. } ) {
+        return Err( EngineError::parse( "HEADLESS::INVALID_VALUE", "--ortho-height is only used with --projection orthographic" ) );
+    }
+    if fisheye_fov.is_some() && !matches!( projection, RTProjection::Fisheye { .. } ) {
+        return Err( EngineError::parse( "HEADLESS::INVALID_VALUE", "--fisheye-fov is only used with --projection fisheye" ) );
+    }
+    Ok( projection )
+}
+
+/**
+ * Gets the stereo mode which was given by name, along with the distance between the eyes, which may be given before or after it.
+ *
+ * @param name The name of the stereo mode, or None if it wasn't given.
+ * @param ipd The distance between the eyes, if it was given.
+ *
+ * @return The stereo mode, or an error if the name is unknown, or the distance was given without eyes to put it between.
+ */
+fn parse_stereo( name: Option<&str>, ipd: Option<f32> ) -> Result<RTStereo, EngineError> {
+    let stereo = match name.unwrap_or( "mono" ) {
+        "mono"  => { RTStereo::Mono },
+        "sbs"   => { RTStereo::SideBySide { ipd: ipd.unwrap_or( 0.064 ) } },
+        "ou"    => { RTStereo::OverUnder { ipd: ipd.unwrap_or( 0.064 ) } },
+        name    => { return Err( EngineError::parse( "HEADLESS::INVALID_VALUE", format!( "--stereo {name}\nThe stereo mode must be mono, sbs or ou" ) ) ) },
+    };
+    if ipd.is_some() && stereo == RTStereo::Mono {
+        return Err( EngineError::parse( "HEADLESS::INVALID_VALUE", "--ipd is only used with --stereo sbs or ou" ) );
+    }
+    Ok( stereo )
+}
+
+/**
+ * HeadlessSettings functions.
+ */
+impl HeadlessSettings {
+    /**
+     * Reads the settings from the command line, for example: --headless --width 1920 --height 1080 --samples 1024 --output render.exr
+     * Anything not given is left at its default, see USAGE.
+     *
+     * @param args The command line's arguments, without the program's name.
+     *
+     * @return The settings, or None if --headless wasn't given, or an error if the arguments are wrong.
+     */
+    pub fn from_args( args: &[String] ) -> Result<Option<HeadlessSettings>, EngineError> {
+        if !args.iter().any( |arg| arg == "--headless" ) {
+            return Ok( None );
+        }
+
+        let mut settings = HeadlessSettings {
+            scene: "resources/Knight.obj".to_string(),
+            width: 1280,
+            height: 720,
+            samples: 256,
+            tile_size: None,
+            bounces: None,
+            seed: 0,
+            renderer: Renderer::Gpu,
+            shading: Shading::Fragment,
+            workgroup_size: None,
+            output: "render.png".to_string(),
+            format: OutputFormat::Png,
+            aovs: None,
+            camera: CameraOverride::default(),
+            sequence: None,
+            checkpoint: None,
+            checkpoint_interval: 60.0,
+            progress_interval: 5.0,
+            coordinator: None,
+            task_timeout: 3600.0,
+            gpus: None,
+        };
+        let mut format = None;
+        let ( mut frames, mut fps, mut resume_from, mut sequence_camera ) = ( None, 24.0, None, SequenceCamera::Still );
+        let ( mut projection, mut ortho_height, mut fisheye_fov ) = ( None, None, None );
+        let ( mut stereo, mut ipd ) = ( None, None );
+        let mut args = args.iter();
+        while let Some( arg ) = args.next() {
+            if arg == "--headless" {
+                continue;
+            }
+            let value = args.next().ok_or( EngineError::parse( "HEADLESS::MISSING_VALUE", arg.to_string() ) )?;
+            let count = || parse_value::<u32>( arg, value ).ok().filter( |&n| n > 0 ).ok_or( EngineError::parse( "HEADLESS::INVALID_VALUE", format!( "{arg} {value}" ) ) );
+            let positive = || parse_value::<f32>( arg, value ).ok().filter( |&x| x > 0.0 ).ok_or( EngineError::parse( "HEADLESS::INVALID_VALUE", format!( "{arg} {value}" ) ) );
+            match arg.as_str() {
+                "--scene"           => { settings.scene = value.clone() },
+                "--width"           => { settings.width = count()? },
+                "--height"          => { settings.height = count()? },
+                "--samples"         => { settings.samples = count()? },
+                "--tile-size"       => { settings.tile_size = Some( count()? ) },
+                "--bounces"         => { settings.bounces = Some( parse_value( arg, value )? ) },
+                "--seed"            => { settings.seed = parse_value( arg, value )? },
+                "--renderer"        => { settings.renderer = match value.as_str() { "gpu" => { Renderer::Gpu }, "cpu" => { Renderer::Cpu }, _ => { return Err( EngineError::parse( "HEADLESS::INVALID_VALUE", format!( "{arg} {value}\nThe renderer must be gpu or cpu" ) ) ) } } },
+                "--shading"         => { settings.shading = Shading::from_name( value ).ok_or( EngineError::parse( "HEADLESS::INVALID_VALUE", format!( "{arg} {value}\nThe shading must be fragment, compute or wavefront" ) ) )? },
+                "--workgroup-size"  => { settings.workgroup_size = if value == "auto" { None } else { Some( WorkgroupSize::from_name( value ).ok_or( EngineError::parse( "HEADLESS::INVALID_VALUE", format!( "{arg} {value}\nThe size must be auto, or the width and height as XxY" ) ) )? ) } },
+                "--output"          => { settings.output = value.clone() },
+                "--format"          => { format = Some( OutputFormat::from_name( value ).ok_or( EngineError::parse( "HEADLESS::UNKNOWN_FORMAT", format!( "{value}\nThe format must be png, exr, hdr or a video's" ) ) )? ) },
+                "--aovs"            => { settings.aovs = Some( match value.as_str() { "layers" => { AovOutput::Layers }, _ => { AovOutput::Files( value.clone() ) } } ) },
+                "--bookmark"        => { settings.camera.bookmark = Some( value.clone() ) },
+                "--position"        => { settings.camera.position = Some( parse_vec3( arg, value )? ) },
+                "--angle"           => { settings.camera.angle = Some( parse_vec3( arg, value )? ) },
+                "--fov"             => { settings.camera.fov = Some( parse_value( arg, value )? ) },
+                "--focus-distance"  => { settings.camera.focus_distance = Some( parse_value( arg, value )? ) },
+                "--aperture"        => { settings.camera.aperture_radius = Some( parse_value( arg, value )? ) },
+                "--focal-length"    => { settings.camera.physical.get_or_insert_with( PhysicalCamera::new ).focal_length = positive()? },
+                "--f-stop"          => { settings.camera.physical.get_or_insert_with( PhysicalCamera::new ).f_stop = positive()? },
+                "--shutter"         => { settings.camera.physical.get_or_insert_with( PhysicalCamera::new ).shutter_time = positive()? },
+                "--iso"             => { settings.camera.physical.get_or_insert_with( PhysicalCamera::new ).iso = positive()? },
+                "--projection"      => { projection = Some( value.as_str() ) },
+                "--ortho-height"    => { ortho_height = Some( positive()? ) },
+                "--fisheye-fov"     => { fisheye_fov = Some( positive()? ) },
+                "--distortion"      => { settings.camera.lens.distortion = parse_vec2( arg, value )? },
+                "--stereo"          => { stereo = Some( value.as_str() ) },
+                "--ipd"             => { ipd = Some( parse_value( arg, value )? ) },
+                "--chromatic-aberration" => { settings.camera.lens.chromatic_aberration = parse_value( arg, value )? },
+                "--frames"          => { frames = Some( parse_frames( arg, value )? ) },
+                "--fps"             => { fps = parse_value::<f32>( arg, value ).ok().filter( |&fps| fps > 0.0 ).ok_or( EngineError::parse( "HEADLESS::INVALID_VALUE", format!( "{arg} {value}" ) ) )? },
+                "--camera-path"     => { sequence_camera = SequenceCamera::Path( crate::camera::CameraPath::load( value )? ) },
+                "--turntable"       => { sequence_camera = SequenceCamera::Turntable( parse_turntable( arg, value )? ) },
+                "--resume-from"     => { resume_from = Some( parse_value::<u32>( arg, value )? ) },
+                "--checkpoint"      => { settings.checkpoint = Some( value.clone() ) },
+                "--progress"        => { settings.progress_interval = parse_value::<f32>( arg, value ).ok().filter( |&seconds| seconds >= 0.0 ).ok_or( EngineError::parse( "HEADLESS::INVALID_VALUE", format!( "{arg} {value}" ) ) )? },
+                "--coordinator"     => { settings.coordinator = Some( value.clone() ) },
+                "--task-timeout"    => { settings.task_timeout = positive()? },
+                "--gpus"            => { settings.gpus = Some( GpuDevice::parse_list( arg, value )? ) },
+                "--checkpoint-interval" => { settings.checkpoint_interval = parse_value::<f32>( arg, value ).ok().filter( |&seconds| seconds > 0.0 ).ok_or( EngineError::parse( "HEADLESS::INVALID_VALUE", format!( "{arg} {value}" ) ) )? },
+                _                   => { return Err( EngineError::parse( "HEADLESS::UNKNOWN_ARGUMENT", format!( "{arg}\nRun with --help to see the arguments" ) ) ) },
+            }
+        }
+
+        settings.camera.lens.projection = parse_projection( projection, ortho_height, fisheye_fov )?;
+        settings.camera.lens.stereo = parse_stereo( stereo, ipd )?;
+        settings.camera.lens.validate().map_err( |err| EngineError::parse( "HEADLESS::INVALID_VALUE", err ) )?;
+
+        // (Without --format, the output's extension decides it)
+        let extension = std::path::Path::new( &settings.output ).extension().and_then( |extension| extension.to_str() ).unwrap_or( "" );
+        settings.format = match format.or( OutputFormat::from_name( extension ) ) {
+            Some( format ) => { format },
+            None => { return Err( EngineError::parse( "HEADLESS::UNKNOWN_FORMAT", format!( "{}\nThe output must be a .png, .exr, .hdr or video file, or --format be given", settings.output ) ) ) },
+        };
+
+        // (EXR files and videos are only written by the engine if it was built with the features for them)
+        if ( settings.format == OutputFormat::Exr || settings.aovs.is_some() ) && !cfg!( feature = "exr" ) {
+            return Err( EngineError::parse( "HEADLESS::FEATURE_NOT_BUILT", "EXR files and AOVs need the exr feature, build with --features exr" ) );
+        }
+        if settings.format == OutputFormat::Video && !cfg!( feature = "video" ) {
+            return Err( EngineError::parse( "HEADLESS::FEATURE_NOT_BUILT", "Videos need the video feature, build with --features video" ) );
+        }
+
+        // (Camera paths have frames of their own, which the other sequences have to be given)
+        if let ( None, SequenceCamera::Path( path ) ) = ( frames, &sequence_camera ) {
+            let ( start, end ) = path.time_range().ok_or( EngineError::parse( "HEADLESS::EMPTY_CAMERA_PATH", "The camera path has no keyframes" ) )?;
+            frames = Some( ( ( start * fps ).ceil().max( 0.0 ) as u32, ( end * fps ).floor().max( 0.0 ) as u32 ) );
+        }
+        settings.sequence = match frames {
+            Some( ( first_frame, last_frame ) ) => {
+                let start_frame = resume_from.unwrap_or( first_frame );
+                if !( first_frame..=last_frame ).contains( &start_frame ) {
+                    return Err( EngineError::parse( "HEADLESS::INVALID_VALUE", format!( "--resume-from {start_frame}\nThe frame must be within {first_frame}-{last_frame}" ) ) );
+                }
+                Some( SequenceSettings { first_frame, last_frame, start_frame, fps, camera: sequence_camera } )
+            },
+            None if resume_from.is_some() || !matches!( sequence_camera, SequenceCamera::Still ) => {
+                return Err( EngineError::parse( "HEADLESS::MISSING_FRAMES", "Sequences must be given --frames" ) );
+            },
+            None => { None },
+        };
+
+        // (A video is one file, which can't be added to once ffmpeg has closed it)
+        if settings.format == OutputFormat::Video {
+            match &settings.sequence {
+                None => { return Err( EngineError::parse( "HEADLESS::VIDEO_WITHOUT_FRAMES", "Videos must be given --frames" ) ) },
+                Some( sequence ) if sequence.start_frame != sequence.first_frame => {
+                    return Err( EngineError::parse( "HEADLESS::VIDEO_NOT_RESUMABLE", "Videos can't be resumed, render to images to be able to" ) );
+                },
+                Some( _ ) => {},
+            }
+        }
+
+        // (Checkpoints are of a single accumulation, while sequences resume from frames instead)
+        if settings.checkpoint.is_some() && ( settings.sequence.is_some() || settings.tile_size.is_some() ) {
+            return Err( EngineError::parse( "HEADLESS::CHECKPOINT_UNSUPPORTED", "Only single images rendered whole can be checkpointed, use --resume-from for sequences" ) );
+        }
+
+        // (The accumulator only holds the last tile's AOVs, and checkpoints don't keep them)
+        if settings.aovs.is_some() && ( settings.tile_size.is_some() || settings.checkpoint.is_some() ) {
+            return Err( EngineError::parse( "HEADLESS::AOVS_UNSUPPORTED", "AOVs can't be saved from renders in tiles or with checkpoints" ) );
+        }
+        if settings.aovs == Some( AovOutput::Layers ) && settings.format != OutputFormat::Exr {
+            return Err( EngineError::parse( "HEADLESS::AOVS_UNSUPPORTED", "AOVs can only be layered into EXR files" ) );
+        }
+
+        // (Workers return the image as it is shown and as it is accumulated, which is all the coordinator can save)
+        // (Several GPUs are handed the render the same way, by a coordinator of their own)
+        if settings.gpus.is_some() {
+            if settings.coordinator.is_some() || settings.renderer == Renderer::Cpu {
+                return Err( EngineError::parse( "HEADLESS::MULTIGPU_UNSUPPORTED", "--gpus can't be combined with --coordinator or --renderer cpu" ) );
+            }
+            if settings.aovs.is_some() || settings.checkpoint.is_some() {
+                return Err( EngineError::parse( "HEADLESS::MULTIGPU_UNSUPPORTED", "Renders split over GPUs can't have AOVs or checkpoints" ) );
+            }
+        }
+        if settings.coordinator.is_some() {
+            if settings.tile_size.is_none() && settings.sequence.is_none() {
+                return Err( EngineError::parse( "HEADLESS::NOTHING_TO_HAND_OUT", "Renders handed out to workers must be given --tile-size or --frames" ) );
+            }
+            if settings.aovs.is_some() || settings.checkpoint.is_some() {
+                return Err( EngineError::parse( "HEADLESS::FARM_UNSUPPORTED", "Renders handed out to workers can't have AOVs or checkpoints" ) );
+            }
+        }
+        Ok( Some( settings ) )
+    }
+
+    /**
+     * Gets a hash of everything the image depends on, which tells apart checkpoints of other renders.
+     * The settings and camera are the ones a frame was rendered with, as the arguments don't tell what embedders change.
+     * The samples aren't part of it, so a render can be resumed with more of them.
+     *
+     * @param settings The settings the frame was rendered with.
+     * @param camera The camera the frame was rendered with, before its jitter.
+     *
+     * @return The hash.
+     */
+    pub fn render_hash( &self, settings: &RTSettings, camera: &RTCamera ) -> u64 {
+        let scene = std::fs::read( &self.scene ).unwrap_or_default();
+        let settings = serde_json::to_vec( settings ).unwrap_or_default();
+        let camera = serde_json::to_vec( camera ).unwrap_or_default();
+        let renderer = format!( "{:?} {:?} {:?}", self.renderer, self.shading, self.tile_size );
+        crate::checkpoint::hash( &[&scene, &settings, &camera, renderer.as_bytes()] )
+    }
+
+    /**
+     * Gets how the render is made as far as its settings tell, for embedding in the files it is saved to.
+     * The render itself adds the rest (the camera it ended up with, the time it took...).
+     */
+    pub fn metadata( &self ) -> output::Metadata {
+        output::Metadata::new( &self.scene, self.seed )
+            .with( "Samples", self.samples )
+            .with( "Bounces", self.bounces.map_or( "default".to_string(), |bounces| bounces.to_string() ) )
+            .with( "CameraOverride", format!( "{:?}", self.camera ) )
+    }
+
+    /**
+     * Creates a reporter of the render's progress, which prints it every so often unless told not to.
+     */
+    pub fn progress_reporter( &self ) -> ProgressReporter {
+        let frames = self.sequence.as_ref().map_or( 1, |sequence| sequence.last_frame - sequence.start_frame + 1 );
+        let tiles = self.tile_size.map_or( 1, |tile_size| ( self.width.div_ceil( tile_size ) * self.height.div_ceil( tile_size ) ) as usize );
+        let mut reporter = ProgressReporter::new( frames, tiles, self.samples );
+
+        if self.progress_interval > 0.0 {
+            let interval = std::time::Duration::from_secs_f32( self.progress_interval );
+            let mut last_printed: Option<std::time::Instant> = None;
+            reporter.on_progress( move |progress| {
+                if last_printed.is_none_or( |last_printed| last_printed.elapsed() >= interval ) {
+                    info!( "Progress: {progress}" );
+                    last_printed = Some( std::time::Instant::now() );
+                }
+            } );
+        }
+        reporter
+    }
+
+    /**
+     * Gets the size of the screen which is rendered on, which is a tile's when rendering in tiles.
+     */
+    pub fn screen_size( &self ) -> ( u32, u32 ) {
+        match self.tile_size {
+            Some( tile_size ) => { ( self.width.min( tile_size ), self.height.min( tile_size ) ) },
+            None => { ( self.width, self.height ) },
+        }
+    }
+
+    /**
+     * Creates an OpenGL context without a window, at the size of the screen, see screen_size() and create_context().
+     */
+    pub fn create_context( &self ) -> Result<( RenderContext<NotCurrent>, Option<EventLoop<()>> ), EngineError> {
+        let ( width, height ) = self.screen_size();
+        create_context( width, height )
+    }
+
+    /**
+     * Saves the finished render to a file.
+     * PNGs are what is shown on the screen (post-processed), while EXR and HDR files are the accumulated image, see OutputFormat.
+     * EXR files also get the AOVs as layers, when they are to be layered.
+     * Must be called before the buffers are swapped.
+     *
+     * @param path The path of the file, which is the output unless rendering a sequence, see SequenceSettings::frame_path().
+     * @param accumulator The accumulator holding the render.
+     * @param tiles The tiles of the render, which are saved instead of the accumulator when rendering in tiles.
+     * @param metadata How the render was made, which is embedded in PNG and EXR files.
+     */
+    pub unsafe fn save( &self, path: &str, accumulator: &Accumulator, tiles: Option<&TileScheduler>, metadata: &output::Metadata ) -> Result<(), EngineError> {
+        // (Tiled renders are saved from their stitched image, as the accumulator only holds the last tile)
+        if let Some( tiles ) = tiles {
+            let ( width, height ) = tiles.image_size();
+            return self.save_image( path, width, height, tiles.pixels(), tiles.screen(), metadata );
+        }
+        let ( width, height ) = accumulator.size();
+        match self.format {
+            OutputFormat::Png => { output::save_png( path, self.width, self.height, metadata ) },
+            OutputFormat::Exr if self.aovs == Some( AovOutput::Layers ) => { output::save_layered_exr( path, accumulator, metadata ) },
+            OutputFormat::Exr => { output::save_exr( path, width, height, &accumulator.read_pixels(), output::ExrPrecision::Float, metadata ) },
+            OutputFormat::Hdr => { output::save_hdr( path, width, height, &accumulator.read_pixels() ) },
+            OutputFormat::Video => { Err( EngineError::parse( "HEADLESS::NOT_AN_IMAGE", format!( "{path}\nVideos are written with a VideoEncoder" ) ) ) },
+        }
+    }
+
+    /**
+     * Saves a finished render which was read back already, such as one stitched together from tiles, see save().
+     *
+     * @param path The path of the file.
+     * @param width The width of the image, in pixels.
+     * @param height The height of the image, in pixels.
+     * @param pixels The accumulated image, as RGB with the bottom row first.
+     * @param screen The image as it is shown (post-processed), with the top row first.
+     * @param metadata How the render was made, which is embedded in PNG and EXR files.
+     */
+    pub fn save_image( &self, path: &str, width: u32, height: u32, pixels: &[f32], screen: &image::RgbImage, metadata: &output::Metadata ) -> Result<(), EngineError> {
+        match self.format {
+            OutputFormat::Png => { output::save_png_image( path, screen, metadata ) },
+            OutputFormat::Exr => { output::save_exr( path, width, height, pixels, output::ExrPrecision::Float, metadata ) },
+            OutputFormat::Hdr => { output::save_hdr( path, width, height, pixels ) },
+            OutputFormat::Video => { Err( EngineError::parse( "HEADLESS::NOT_AN_IMAGE", format!( "{path}\nVideos are written with a VideoEncoder" ) ) ) },
+        }
+    }
+}
+
+/**
+ * Creates an OpenGL context without a window.
+ * OSMesa is tried first, as it needs no display at all (servers, CI), and otherwise a hidden context is created through the display,
+ * with OpenGL ES if desktop OpenGL isn't available (see GlApi::requested()).
+ * The event loop of the hidden context is returned with it, and must be kept until the render is done.
+ *
+ * @param width The width of the context's framebuffer, in pixels.
+ * @param height The height of the context's framebuffer, in pixels.
+ */
+pub fn create_context( width: u32, height: u32 ) -> Result<( RenderContext<NotCurrent>, Option<EventLoop<()>> ), EngineError> {
+    let size = glutin::dpi::PhysicalSize::new( width, height );
+
+    // (Workers for one of several GPUs skip it, as it renders on the CPU, and so do renders which must use ES, as it is desktop OpenGL)
+    #[cfg(all(unix, not(target_os = "macos")))]
+    if multigpu::assigned_gpu().is_none() && GlApi::requested().contains( &GlApi::Desktop ) {
+        use glutin::platform::unix::HeadlessContextExt;
+        match glutin::ContextBuilder::new().build_osmesa( size ) {
+            Ok( context ) => { return Ok( ( RenderContext::Headless( context ), None ) ) },
+            Err( err ) => { warn!( "OSMesa is unavailable ({err}), rendering through the display instead" ) },
+        }
+    }
+
+    // (Creating the event loop panics when there is no display, which is quieted into an error)
+    let hook = std::panic::take_hook();
+    std::panic::set_hook( Box::new( |_| {} ) );
+    let event_loop = std::panic::catch_unwind( new_event_loop );
+    std::panic::set_hook( hook );
+    let event_loop = event_loop.map_err( |_| EngineError::gl( "HEADLESS::CONTEXT_NOT_CREATED", "There is neither OSMesa nor a display to render with" ) )?;
+    let mut errors = Vec::new();
+    for api in GlApi::requested() {
+        match glutin::ContextBuilder::new().with_gl( api.gl_request() ).build_headless( &event_loop, size ) {
+            Ok( context ) => { return Ok( ( RenderContext::Headless( context ), Some( event_loop ) ) ) },
+            Err( err ) => { errors.push( format!( "{api:?}: {err}" ) ) },
+        }
+    }
+    Err( EngineError::gl( "HEADLESS::CONTEXT_NOT_CREATED", errors.join( "\n" ) ) )
+}
+
+/**
+ * Creates the event loop of a hidden context.
+ * It may be created on any thread, as the tests create their context on one of their own (see testing::with_gl()).
+ */
+fn new_event_loop() -> EventLoop<()> {
+    #[cfg(all(unix, not(target_os = "macos")))]
+    {
+        use glutin::platform::unix::EventLoopBuilderExtUnix;
+        glutin::event_loop::EventLoopBuilder::new().with_any_thread( true ).build()
+    }
+    #[cfg(not(all(unix, not(target_os = "macos"))))]
+    {
+        EventLoop::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /**
+     * Reads the settings from arguments split by spaces, after --headless.
+     */
+    fn from_args( args: &str ) -> Result<HeadlessSettings, EngineError> {
+        let args: Vec<String> = std::iter::once( "--headless" ).chain( args.split_whitespace() ).map( str::to_string ).collect();
+        HeadlessSettings::from_args( &args ).map( |settings| settings.unwrap() )
+    }
+
+    /**
+     * Gets the code of the error which the arguments are refused with.
+     */
+    fn error_code( args: &str ) -> &'static str {
+        match from_args( args ) {
+            Err( EngineError::Parse { code, .. } ) => { code },
+            Err( err ) => { panic!( "{args} was refused with {err}" ) },
+            Ok( _ ) => { panic!( "{args} was accepted" ) },
+        }
+    }
+
+    #[test]
+    fn renders_are_only_offline_with_headless() {
+        assert!( HeadlessSettings::from_args( &["--width".to_string(), "64".to_string()] ).unwrap().is_none() );
+        let settings = from_args( "--width 64 --height 48 --samples 8 --seed 3" ).unwrap();
+        assert_eq!( ( settings.width, settings.height, settings.samples, settings.seed ), ( 64, 48, 8, 3 ) );
+        assert_eq!( error_code( "--width" ), "HEADLESS::MISSING_VALUE" );
+        assert_eq!( error_code( "--size 64" ), "HEADLESS::UNKNOWN_ARGUMENT" );
+        assert_eq!( error_code( "--width 0" ), "HEADLESS::INVALID_VALUE" );
+    }
+
+    #[test]
+    fn formats_are_inferred_from_the_extension() {
+        assert!( from_args( "" ).unwrap().format == OutputFormat::Png );
+        assert!( from_args( "--output render.HDR" ).unwrap().format == OutputFormat::Hdr );
+        assert!( from_args( "--output render.img --format hdr" ).unwrap().format == OutputFormat::Hdr );
+        assert_eq!( error_code( "--output render.img" ), "HEADLESS::UNKNOWN_FORMAT" );
+        assert_eq!( error_code( "--format tiff" ), "HEADLESS::UNKNOWN_FORMAT" );
+
+        // (Formats which need a feature are refused by builds without it)
+        match from_args( "--output render.exr" ) {
+            Ok( settings ) => { assert!( cfg!( feature = "exr" ) && settings.format == OutputFormat::Exr ) },
+            Err( err ) => { assert!( !cfg!( feature = "exr" ) && matches!( err, EngineError::Parse { code: "HEADLESS::FEATURE_NOT_BUILT", .. } ) ) },
+        }
+        if !cfg!( feature = "video" ) {
+            assert_eq!( error_code( "--output render.mp4 --frames 0-9" ), "HEADLESS::FEATURE_NOT_BUILT" );
+        }
+    }
+
+    #[test]
+    fn frames_are_ranges_or_single_frames() {
+        let sequence = from_args( "--frames 3-7" ).unwrap().sequence.unwrap();
+        assert_eq!( ( sequence.first_frame, sequence.last_frame, sequence.start_frame, sequence.fps ), ( 3, 7, 3, 24.0 ) );
+        let sequence = from_args( "--frames 5 --fps 30" ).unwrap().sequence.unwrap();
+        assert_eq!( ( sequence.first_frame, sequence.last_frame, sequence.fps ), ( 5, 5, 30.0 ) );
+        assert_eq!( from_args( "--frames 3-7 --resume-from 6" ).unwrap().sequence.unwrap().start_frame, 6 );
+        assert!( from_args( "" ).unwrap().sequence.is_none() );
+
+        assert_eq!( error_code( "--frames 7-3" ), "HEADLESS::INVALID_VALUE" );
+        assert_eq!( error_code( "--frames 3-x" ), "HEADLESS::INVALID_VALUE" );
+        assert_eq!( error_code( "--frames 3-7 --resume-from 8" ), "HEADLESS::INVALID_VALUE" );
+        assert_eq!( error_code( "--resume-from 3" ), "HEADLESS::MISSING_FRAMES" );
+        assert_eq!( error_code( "--turntable 0,0,0,5,1" ), "HEADLESS::MISSING_FRAMES" );
+    }
+
+    #[test]
+    fn physical_cameras_must_have_positive_settings() {
+        let physical = from_args( "--f-stop 2.8 --shutter 0.01" ).unwrap().camera.physical.unwrap();
+        assert_eq!( physical, PhysicalCamera { f_stop: 2.8, shutter_time: 0.01, ..PhysicalCamera::new() } );
+        assert!( from_args( "" ).unwrap().camera.physical.is_none() );
+        for args in ["--f-stop 0", "--f-stop -2", "--shutter 0", "--shutter -0.01", "--focal-length 0", "--iso nan"] {
+            assert_eq!( error_code( args ), "HEADLESS::INVALID_VALUE", "{args}" );
+        }
+    }
+
+    #[test]
+    fn lenses_are_given_by_name_and_values() {
+        let lens = from_args( "--ortho-height 4 --projection orthographic --stereo ou" ).unwrap().camera.lens;
+        assert_eq!( ( lens.projection, lens.stereo ), ( RTProjection::Orthographic { height: 4.0 }, RTStereo::OverUnder { ipd: 0.064 } ) );
+        assert_eq!( from_args( "--projection fisheye" ).unwrap().camera.lens.projection, RTProjection::Fisheye { fov: 180.0 } );
+        assert_eq!( from_args( "--distortion 0.1,-0.02" ).unwrap().camera.lens.distortion, glm::vec2( 0.1, -0.02 ) );
+
+        assert_eq!( error_code( "--ortho-height 4" ), "HEADLESS::INVALID_VALUE" );
+        assert_eq!( error_code( "--projection fisheye --ortho-height 4" ), "HEADLESS::INVALID_VALUE" );
+        assert_eq!( error_code( "--projection cylindrical" ), "HEADLESS::INVALID_VALUE" );
+        assert_eq!( error_code( "--ipd 0.07" ), "HEADLESS::INVALID_VALUE" );
+        assert_eq!( error_code( "--stereo sbs --ipd -0.07" ), "HEADLESS::INVALID_VALUE" );
+        assert_eq!( error_code( "--chromatic-aberration 1.5" ), "HEADLESS::INVALID_VALUE" );
+    }
+
+    #[test]
+    fn incompatible_flags_are_refused() {
+        assert_eq!( error_code( "--checkpoint render.ckpt --frames 0-9" ), "HEADLESS::CHECKPOINT_UNSUPPORTED" );
+        assert_eq!( error_code( "--checkpoint render.ckpt --tile-size 256" ), "HEADLESS::CHECKPOINT_UNSUPPORTED" );
+        assert_eq!( error_code( "--coordinator 0.0.0.0:7878" ), "HEADLESS::NOTHING_TO_HAND_OUT" );
+        assert_eq!( error_code( "--gpus 2 --coordinator 0.0.0.0:7878 --tile-size 256" ), "HEADLESS::MULTIGPU_UNSUPPORTED" );
+        assert_eq!( error_code( "--gpus 2 --renderer cpu" ), "HEADLESS::MULTIGPU_UNSUPPORTED" );
+        assert_eq!( error_code( "--gpus 2 --checkpoint render.ckpt" ), "HEADLESS::MULTIGPU_UNSUPPORTED" );
+        assert_eq!( error_code( "--renderer vulkan" ), "HEADLESS::INVALID_VALUE" );
+        assert!( from_args( "--coordinator 0.0.0.0:7878 --frames 0-9" ).unwrap().coordinator.is_some() );
+
+        // (AOVs are checked after the feature they need)
+        if cfg!( feature = "exr" ) {
+            assert_eq!( error_code( "--aovs layers" ), "HEADLESS::AOVS_UNSUPPORTED" );
+            assert_eq!( error_code( "--output render.exr --aovs aovs.exr --tile-size 256" ), "HEADLESS::AOVS_UNSUPPORTED" );
+        } else {
+            assert_eq!( error_code( "--aovs layers" ), "HEADLESS::FEATURE_NOT_BUILT" );
+        }
+    }
+
+    #[test]
+    fn checkpoints_tell_apart_what_was_rendered() {
+        let headless = from_args( "--width 64 --height 48" ).unwrap();
+        let settings = RTSettings::new();
+        let camera = RTCamera::look_at( glm::zero(), glm::vec3( 0.0, 0.0, 1.0 ), glm::vec3( 0.0, 1.0, 0.0 ), 60.0, glm::vec2( 64.0, 48.0 ) );
+        let hash = headless.render_hash( &settings, &camera );
+        assert_eq!( headless.render_hash( &settings.clone(), &camera.clone() ), hash );
+        assert_ne!( headless.render_hash( &RTSettings { direct_clamp: 10.0, ..settings.clone() }, &camera ), hash );
+        assert_ne!( headless.render_hash( &settings, &RTCamera { fov: 45.0, ..camera.clone() } ), hash );
+        let tiled = from_args( "--width 64 --height 48 --tile-size 16" ).unwrap();
+        assert_ne!( tiled.render_hash( &settings, &camera ), hash );
+    }
+}
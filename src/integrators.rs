@@ -0,0 +1,146 @@
+use serde::{Serialize, Deserialize};
+
+use crate::shader::Shader;
+
+/**
+ * Trait for the integrators, which decide how the light arriving along each camera ray is estimated.
+ * The integrators all live in the same shader, and the one to use is picked at runtime by settings.integrator.
+ * Each integrator supplies the defines its shader code needs, and the uniforms it needs every frame.
+ */
+pub trait Integrator {
+    /**
+     * Gets the name of the integrator, as shown when it is selected.
+     */
+    fn name( &self ) -> &'static str;
+
+    /**
+     * Gets the name of the shader define holding the integrator's id, which settings.integrator is compared against.
+     */
+    fn define( &self ) -> &'static str;
+
+    /**
+     * Gets any other defines the integrator's shader code needs.
+     */
+    fn defines( &self ) -> Vec<( &'static str, u32 )> {
+        vec![]
+    }
+
+    /**
+     * Checks if the integrator splats paths traced from the lights to the camera, using the LightTracer pass.
+     */
+    fn traces_lights( &self ) -> bool {
+        false
+    }
+
+    /**
+     * Sends the integrator's uniforms to the shader, which is done every frame.
+     * The shader must be active.
+     *
+     * @param shader The shader.
+     */
+    unsafe fn send_uniforms( &self, _shader: &Shader ) {}
+}
+
+/**
+ * Full global illumination, bouncing up to the limits of max_bounces.
+ */
+pub struct PathTracer;
+
+impl Integrator for PathTracer {
+    fn name( &self ) -> &'static str { "Path tracer" }
+    fn define( &self ) -> &'static str { "INTEGRATOR_PATH_TRACE" }
+}
+
+/**
+ * Only light arriving directly from emissive spheres at the first surface, for fast previews.
+ */
+pub struct DirectOnly;
+
+impl Integrator for DirectOnly {
+    fn name( &self ) -> &'static str { "Direct light" }
+    fn define( &self ) -> &'static str { "INTEGRATOR_DIRECT_LIGHT" }
+}
+
+/**
+ * Path tracing combined with light tracing, where paths from emissive spheres are connected to the camera.
+ * Light tracing needs a pinhole camera (perspective, mono, no aperture), otherwise it falls back to path tracing.
+ */
+pub struct Bidirectional;
+
+impl Integrator for Bidirectional {
+    fn name( &self ) -> &'static str { "Bidirectional" }
+    fn define( &self ) -> &'static str { "INTEGRATOR_BIDIRECTIONAL" }
+    fn traces_lights( &self ) -> bool { true }
+}
+
+/**
+ * Clay look, where surfaces are darkened by other surfaces within `distance`.
+ * Occluders count less the further away they are, with `falloff` as the exponent (0 is a hard cutoff).
+ */
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct AmbientOcclusion {
+    pub distance: f32,
+    pub falloff: f32,
+}
+
+impl Integrator for AmbientOcclusion {
+    fn name( &self ) -> &'static str { "Ambient occlusion" }
+    fn define( &self ) -> &'static str { "INTEGRATOR_AMBIENT_OCCLUSION" }
+
+    unsafe fn send_uniforms( &self, shader: &Shader ) {
+        gl::Uniform1f( shader.get_uniform_location( "aoDistance" ), self.distance );
+        gl::Uniform1f( shader.get_uniform_location( "aoFalloff" ), self.falloff );
+    }
+}
+
+/**
+ * Enum for what the debug integrator shows.
+ */
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum DebugView {
+    Normal, // The normal of the first surface, mapped from [-1, 1] to [0, 1]
+    Albedo, // The color of the first surface's material
+    Depth,  // The distance to the first surface, where closer is brighter
+}
+
+/**
+ * DebugView functions.
+ */
+impl DebugView {
+    /**
+     * Gets the id of the view, as used by the shader.
+     */
+    pub fn id( &self ) -> u32 {
+        match self {
+            DebugView::Normal   => { 0 },
+            DebugView::Albedo   => { 1 },
+            DebugView::Depth    => { 2 },
+        }
+    }
+}
+
+/**
+ * Shows information about the first surface hit, for finding problems with the scene.
+ */
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Debug {
+    pub view: DebugView,
+}
+
+impl Integrator for Debug {
+    fn name( &self ) -> &'static str { "Debug" }
+    fn define( &self ) -> &'static str { "INTEGRATOR_DEBUG" }
+
+    fn defines( &self ) -> Vec<( &'static str, u32 )> {
+        vec![
+            ( "DEBUG_VIEW_NORMAL", DebugView::Normal.id() ),
+            ( "DEBUG_VIEW_ALBEDO", DebugView::Albedo.id() ),
+            ( "DEBUG_VIEW_DEPTH", DebugView::Depth.id() ),
+        ]
+    }
+
+    unsafe fn send_uniforms( &self, shader: &Shader ) {
+        gl::Uniform1ui( shader.get_uniform_location( "debugView" ), self.view.id() );
+    }
+}
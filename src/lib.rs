@@ -0,0 +1,85 @@
+/*!
+ * A raytracer for OpenGL, which renders spheres and triangle meshes into a window or offline.
+ * Other projects embed it through Engine, which opens the window or renders offline with the settings and scene it is given.
+ */
+
+// (The unsafe functions all need the context to be current, which their docs say, rather than each having a safety section)
+#![allow(clippy::missing_safety_doc)]
+// (Types are made with new(), like everywhere else in the engine, rather than with Default as well)
+#![allow(clippy::new_without_default)]
+
+extern crate nalgebra_glm as glm;
+
+pub mod error;
+pub mod util;
+pub mod assets;
+pub mod shader;
+pub mod camera;
+pub mod raytracing;
+pub mod scene;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod config;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod engine;
+pub mod events;
+pub mod mesh;
+pub mod accumulator;
+pub mod bluenoise;
+pub mod sobol;
+pub mod restir;
+pub mod guiding;
+pub mod lighttracing;
+pub mod photons;
+pub mod integrators;
+pub mod volumes;
+pub mod postprocess;
+pub mod denoiser;
+pub mod taa;
+pub mod postchain;
+pub mod framegraph;
+pub mod output;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod context;
+pub mod device;
+pub mod glapi;
+pub mod capabilities;
+pub mod webscene;
+pub mod web;
+pub mod gldebug;
+pub mod gltracker;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod headless;
+pub mod sequence;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod video;
+pub mod tiles;
+pub mod checkpoint;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod farm;
+pub mod progress;
+pub mod wavefront;
+pub mod profiler;
+pub mod stats;
+pub mod readback;
+pub mod reference;
+pub mod uploads;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod multigpu;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod window;
+pub mod offscreen;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod bench;
+#[cfg(all(feature = "egui", not(target_arch = "wasm32")))]
+pub mod ui;
+#[cfg(test)]
+mod testing;
+#[cfg(test)]
+mod golden;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use config::EngineConfig;
+#[cfg(not(target_arch = "wasm32"))]
+pub use engine::{ Engine, FrameUpdate };
+pub use error::EngineError;
+pub use events::EngineEvent;
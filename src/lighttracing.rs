@@ -0,0 +1,95 @@
+use crate::device::{ self, BufferUsage, Device };
+use crate::glapi;
+use crate::shader::Shader;
+
+// Binding of the splat buffer in the shader
+const SPLAT_BINDING: u32 = 8;
+
+/**
+ * Struct for the light tracing pass of the bidirectional integrator.
+ * Before each frame is shaded, paths are traced from the lights and every surface they hit is connected to the camera.
+ * The light this carries to the camera is added ("splatted") to whichever pixel it lands on, in a buffer of fixed-point RGB values.
+ * The shading pass then adds each pixel's splats to what was found by the paths from the camera.
+ */
+pub struct LightTracer {
+    buffer: device::Buffer,
+    width: u32,
+    height: u32,
+}
+
+/**
+ * LightTracer functions.
+ */
+impl LightTracer {
+    /**
+     * Creates the splat buffer, and binds it to the shader's binding.
+     *
+     * @param width The width of the screen, in pixels.
+     * @param height The height of the screen, in pixels.
+     */
+    pub unsafe fn new( width: u32, height: u32 ) -> LightTracer {
+        let mut light_tracer = LightTracer {
+            buffer: 0,
+            width,
+            height,
+        };
+        light_tracer.buffer = device::current().create_buffer();
+        light_tracer.allocate();
+        light_tracer
+    }
+
+    /**
+     * Allocates the splat buffer at the current size.
+     */
+    unsafe fn allocate( &self ) {
+        let size = self.width as usize * self.height as usize * 3 * std::mem::size_of::<u32>();
+        device::current().allocate_buffer( self.buffer, size, None, BufferUsage::Storage );
+        device::current().bind_storage_buffer( self.buffer, SPLAT_BINDING );
+    }
+
+    /**
+     * Resizes the splat buffer.
+     *
+     * @param width The new width, in pixels.
+     * @param height The new height, in pixels.
+     */
+    pub unsafe fn resize( &mut self, width: u32, height: u32 ) {
+        self.width = width;
+        self.height = height;
+        self.allocate();
+    }
+
+    /**
+     * Deletes the splat buffer, once it is done with.
+     */
+    pub unsafe fn delete( self ) {
+        device::current().delete_buffer( self.buffer );
+    }
+
+    /**
+     * Prepares a shader for rendering the light tracing pass.
+     * Clears the splats of the previous frame, and masks the color output so the pass can be rendered into the accumulator without touching it.
+     * The shader must be active.
+     *
+     * @param shader The shader.
+     */
+    pub unsafe fn begin_pass( &self, shader: &Shader ) {
+        gl::BindBuffer( gl::SHADER_STORAGE_BUFFER, self.buffer );
+        glapi::clear_buffer_u32( gl::SHADER_STORAGE_BUFFER, 0, None, 0 );
+        gl::BindBuffer( gl::SHADER_STORAGE_BUFFER, 0 );
+
+        gl::ColorMask( gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE );
+        gl::Uniform1ui( shader.get_uniform_location( "lightTracingPass" ), 1 );
+    }
+
+    /**
+     * Finishes rendering the light tracing pass, making the splats visible to the shading pass.
+     *
+     * @param shader The shader.
+     */
+    pub unsafe fn end_pass( &self, shader: &Shader ) {
+        gl::ColorMask( gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE );
+        gl::Uniform1ui( shader.get_uniform_location( "lightTracingPass" ), 0 );
+        gl::MemoryBarrier( gl::SHADER_STORAGE_BARRIER_BIT );
+    }
+}
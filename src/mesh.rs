@@ -1,3 +1,6 @@
+use tracing::info;
+
+use crate::error::EngineError;
 use crate::raytracing::{RTTriangle, RTMeshInfo, RTMaterial};
 
 /**
@@ -26,24 +29,51 @@ impl Model {
      * Creates a new, empty model.
      */
     pub fn new() -> Model {
-        return Model { meshes: Vec::new() }
+        Model { meshes: Vec::new() }
     }
 
     /**
      * Loads a .obj file into the model.
      * 
      * @param path The path for the .obj file.
+     * 
+     * @return The model, or an error if the file couldn't be read.
      */
-    pub fn load_from_file( mut self, path: &str ) -> Model {
+    pub fn load_from_file( self, path: &str ) -> Result<Model, EngineError> {
         let (parts, _materials)
-        = tobj::load_obj(path,
-            &tobj::LoadOptions{
-                triangulate: true,
-                single_index: true,
-                ..Default::default()
-            }
-        ).expect("Failed to load model");
+        = tobj::load_obj(path, &load_options())
+            .map_err( |err| EngineError::asset( "MESH::FILE_NOT_SUCCESSFULLY_READ", format!( "{path}: {err}" ) ) )?;
+        info!( path, parts = parts.len(), "Loaded model" );
+
+        Ok( self.add_parts( parts ) )
+    }
 
+    /**
+     * Loads the contents of a .obj file into the model, for when there is no file to read (such as in a browser).
+     * Its materials are ignored, like those of files.
+     *
+     * @param name What the contents are of, for the log and the error.
+     * @param bytes The contents of the .obj file.
+     *
+     * @return The model, or an error if the contents couldn't be parsed.
+     */
+    pub fn load_from_bytes( self, name: &str, bytes: &[u8] ) -> Result<Model, EngineError> {
+        let (parts, _materials)
+        = tobj::load_obj_buf(&mut std::io::BufReader::new( bytes ), &load_options(), |_| Ok( Default::default() ))
+            .map_err( |err| EngineError::asset( "MESH::FILE_NOT_SUCCESSFULLY_READ", format!( "{name}: {err}" ) ) )?;
+        info!( name, parts = parts.len(), "Loaded model" );
+
+        Ok( self.add_parts( parts ) )
+    }
+
+    /**
+     * Adds the parts of a loaded .obj file to the model, as meshes.
+     *
+     * @param parts The parts.
+     *
+     * @return The model.
+     */
+    fn add_parts( mut self, parts: Vec<tobj::Model> ) -> Model {
         for part in parts {
             let ( positions, indices ) = ( part.mesh.positions, part.mesh.indices );
             let ( positions_len, indices_len ) = ( positions.len(), indices.len() );
@@ -51,7 +81,7 @@ impl Model {
                 Mesh {
                     vertices: positions,
                     normals: part.mesh.normals,
-                    indices: indices,
+                    indices,
                     colors: [1.0, 0.0, 0.0, 1.0].iter().cloned().cycle().take(positions_len*4).collect(),
                     index_count: indices_len as i32,
                 }
@@ -139,17 +169,28 @@ impl Model {
 
             // Create and push raytracing mesh to `meshes`
             meshes.push( RTMeshInfo {
-                start_index: start_index,
+                start_index,
                 count: triangles.len() as u32 - start_index,
                 boundingbox_min: boundingbox_min.into(),
                 boundingbox_max: boundingbox_max.into(),
             } );
 
             // Set start index for next part
-            start_index = triangles.len() as u32 - 1;
+            start_index = triangles.len() as u32;
         }
 
         // Return triangles and meshes
         ( triangles, meshes ) 
     }
-}
\ No newline at end of file
+}
+
+/**
+ * Gets how .obj files are loaded: as triangles, with one index for the position and normal of each vertex.
+ */
+fn load_options() -> tobj::LoadOptions {
+    tobj::LoadOptions {
+        triangulate: true,
+        single_index: true,
+        ..Default::default()
+    }
+}
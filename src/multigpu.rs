@@ -0,0 +1,141 @@
+use std::net::TcpListener;
+use std::process::{ Child, Command };
+use tracing::{ error, info };
+
+use crate::error::EngineError;
+use crate::farm::Coordinator;
+use crate::headless::HeadlessSettings;
+
+// Environment variable which tells a worker the index of the GPU it was started for, see assigned_gpu()
+const GPU_INDEX_VAR: &str = "OPENGL_RAYTRACING_GPU";
+
+// How many tiles each GPU gets when the image isn't split into tiles already, so faster GPUs can take more of them
+const TILES_PER_GPU: u32 = 4;
+
+/**
+ * Struct for a GPU which renders its share of a render, in a process of its own.
+ * OpenGL has no way to pick a GPU when creating a context, so each is picked the way the platform does for a process,
+ * through its environment (such as DRI_PRIME=1 with Mesa, or DISPLAY=:0.1 for a GPU driving another X screen).
+ */
+#[derive(Clone, PartialEq, Debug)]
+pub struct GpuDevice {
+    pub env: Vec<( String, String )>, // The environment variables which pick the GPU
+}
+
+impl std::fmt::Display for GpuDevice {
+    fn fmt( &self, f: &mut std::fmt::Formatter ) -> std::fmt::Result {
+        if self.env.is_empty() {
+            return write!( f, "the default GPU" );
+        }
+        let env: Vec<String> = self.env.iter().map( |( name, value )| format!( "{name}={value}" ) ).collect();
+        write!( f, "{}", env.join( " " ) )
+    }
+}
+
+/**
+ * GpuDevice functions.
+ */
+impl GpuDevice {
+    /**
+     * Parses the GPUs given to --gpus, which are either a count, picked with DRI_PRIME=0, DRI_PRIME=1...,
+     * or a list of GPUs split by commas, each picked by environment variables split by plus signs,
+     * such as DISPLAY=:0.0,DISPLAY=:0.1 or DRI_PRIME=0,__NV_PRIME_RENDER_OFFLOAD=1+__GLX_VENDOR_LIBRARY_NAME=nvidia.
+     *
+     * @param arg The argument, for the error.
+     * @param value The value.
+     *
+     * @return The GPUs, or an error if they couldn't be parsed.
+     */
+    pub fn parse_list( arg: &str, value: &str ) -> Result<Vec<GpuDevice>, EngineError> {
+        if let Ok( count ) = value.trim().parse::<u32>() {
+            if count == 0 {
+                return Err( EngineError::parse( "MULTIGPU::INVALID_VALUE", format!( "{arg} {value}\nThere must be at least one GPU" ) ) );
+            }
+            return Ok( ( 0..count ).map( |i| GpuDevice { env: vec![( "DRI_PRIME".to_string(), i.to_string() )] } ).collect() );
+        }
+        value.split( ',' ).map( |device| {
+            let env = device.split( '+' ).map( |var| match var.trim().split_once( '=' ) {
+                Some( ( name, value ) ) if !name.is_empty() => { Ok( ( name.to_string(), value.to_string() ) ) },
+                _ => { Err( EngineError::parse( "MULTIGPU::INVALID_VALUE", format!( "{arg} {value}\nEach GPU must be a count, or variables as NAME=value joined by +" ) ) ) },
+            } ).collect::<Result<Vec<_>, EngineError>>()?;
+            Ok( GpuDevice { env } )
+        } ).collect()
+    }
+}
+
+/**
+ * Gets the index of the GPU this process renders for, if it was started by render() as one of several.
+ * Such workers must render through the display, as OSMesa would render on the CPU whichever GPU was picked.
+ */
+pub fn assigned_gpu() -> Option<usize> {
+    std::env::var( GPU_INDEX_VAR ).ok()?.parse().ok()
+}
+
+/**
+ * Gets the size of the tiles an image is split into, for each GPU to get a few of them.
+ *
+ * @param width The width of the image, in pixels.
+ * @param height The height of the image, in pixels.
+ * @param gpus How many GPUs render it.
+ */
+fn tile_size( width: u32, height: u32, gpus: usize ) -> u32 {
+    let tiles = gpus as u32 * TILES_PER_GPU;
+    ( ( width as f32 * height as f32 / tiles as f32 ).sqrt().ceil() as u32 ).max( 64 ).min( width.max( height ) )
+}
+
+/**
+ * Renders offline on several GPUs at once (experimental), by handing the tiles or frames of the render out to a worker per GPU.
+ * The workers are processes of their own, which are started with their GPU picked and connect to a coordinator on this machine,
+ * which composites their tiles into the image and saves it, the same as for a render farm (see farm::Coordinator).
+ * Images which aren't rendered in tiles already are split into a few tiles per GPU.
+ *
+ * @param settings The settings of the render, which must have GPUs.
+ * @param args The arguments the render was started with, which the workers are given.
+ */
+pub fn render( settings: &HeadlessSettings, args: &[String] ) -> Result<(), EngineError> {
+    let devices = settings.gpus.clone().unwrap_or_default();
+    let listener = TcpListener::bind( "127.0.0.1:0" ).map_err( |err| EngineError::io( "MULTIGPU::ADDRESS_UNAVAILABLE", "", err ) )?;
+    let address = listener.local_addr().map_err( |err| EngineError::io( "MULTIGPU::ADDRESS_UNAVAILABLE", "", err ) )?.to_string();
+
+    // (The workers get the same arguments, except for the GPUs, and the tiles the image is split into if it wasn't already)
+    let mut settings = settings.clone();
+    let mut job = Vec::new();
+    let mut job_args = args.iter();
+    while let Some( arg ) = job_args.next() {
+        match arg.as_str() {
+            "--gpus" => { job_args.next(); },
+            _ => { job.push( arg.clone() ) },
+        }
+    }
+    if settings.tile_size.is_none() && settings.sequence.is_none() {
+        let tile_size = tile_size( settings.width, settings.height, devices.len() );
+        settings.tile_size = Some( tile_size );
+        job.extend( ["--tile-size".to_string(), tile_size.to_string()] );
+    }
+    settings.coordinator = Some( address.clone() );
+
+    // Start a worker per GPU, and stop if they all fail, as the coordinator would otherwise wait for workers forever
+    let executable = std::env::current_exe().map_err( |err| EngineError::io( "MULTIGPU::WORKERS_NOT_STARTED", "", err ) )?;
+    let mut workers: Vec<Child> = Vec::with_capacity( devices.len() );
+    for ( i, device ) in devices.iter().enumerate() {
+        info!( "Starting a worker for GPU {i} ({device})" );
+        let worker = Command::new( &executable )
+            .args( ["--worker", &address] )
+            .envs( device.env.iter().map( |( name, value )| ( name, value ) ) )
+            .env( GPU_INDEX_VAR, i.to_string() )
+            .spawn()
+            .map_err( |err| EngineError::io( "MULTIGPU::WORKERS_NOT_STARTED", "", err ) )?;
+        workers.push( worker );
+    }
+    let watcher = std::thread::spawn( move || {
+        let succeeded = workers.iter_mut().map( |worker| worker.wait().is_ok_and( |status| status.success() ) ).filter( |&succeeded| succeeded ).count();
+        if succeeded == 0 {
+            error!( "ERROR::MULTIGPU::WORKERS_FAILED\nNone of the GPUs could render, see the workers' errors above" );
+            std::process::exit( 1 );
+        }
+    } );
+
+    Coordinator::new( &settings, &job ).serve( listener )?;
+    let _ = watcher.join();
+    Ok( () )
+}
@@ -0,0 +1,175 @@
+use std::ptr;
+
+use crate::accumulator::Accumulator;
+use crate::device::{ self, Device };
+use crate::error::EngineError;
+use crate::raytracing::{ self, RTCamera, RTIntegrator, RTMeshInfo, RTQuantizedTriangle, RTSettings, RTSphere, RTTriangle };
+use crate::shader::{ SSBOBuilder, Shader, ShaderType };
+use crate::{ assets, bluenoise, sobol, util, volumes };
+
+/**
+ * Struct for rendering a scene of its own into an accumulator, with the fragment shader, without a window or any of the render loop's passes.
+ * This is what the tests and the benchmarks render with, whose scenes are made in code rather than loaded.
+ */
+pub struct OffscreenRenderer {
+    shader: Shader,
+    accumulator: Accumulator,
+    buffers: Vec<device::Buffer>,
+    vao: u32,
+    index_count: i32,
+    blue_noise_texture: u32,
+    spheres: Vec<RTSphere>,
+    meshes: Vec<RTMeshInfo>,
+}
+
+/**
+ * Puts the triangles of several meshes into one list, and gets the bounds of each mesh, like mesh::Model::generate_raytracing_structs().
+ *
+ * @param parts The triangles of each mesh.
+ *
+ * @return The triangles, and the meshes which cover them.
+ */
+pub fn meshes_of( parts: &[Vec<RTTriangle>] ) -> ( Vec<RTTriangle>, Vec<RTMeshInfo> ) {
+    let ( mut triangles, mut meshes ) = ( Vec::new(), Vec::new() );
+    for part in parts.iter().filter( |part| !part.is_empty() ) {
+        let ( mut min, mut max ) = ( glm::vec3( f32::MAX, f32::MAX, f32::MAX ), glm::vec3( f32::MIN, f32::MIN, f32::MIN ) );
+        for p in part.iter().flat_map( |triangle| [triangle.p0, triangle.p1, triangle.p2] ) {
+            min = glm::min2( &min, &p.into() );
+            max = glm::max2( &max, &p.into() );
+        }
+        meshes.push( RTMeshInfo { start_index: triangles.len() as u32, count: part.len() as u32, boundingbox_min: min.into(), boundingbox_max: max.into() } );
+        triangles.extend( part.iter().cloned() );
+    }
+    ( triangles, meshes )
+}
+
+/**
+ * OffscreenRenderer functions.
+ */
+impl OffscreenRenderer {
+    /**
+     * Builds the shader and uploads the scene.
+     * The context must be current.
+     *
+     * @param spheres The spheres.
+     * @param triangles The triangles, see meshes_of().
+     * @param meshes The meshes which cover the triangles.
+     * @param width The width of the frames, in pixels.
+     * @param height The height of the frames, in pixels.
+     *
+     * @return The renderer, or an error if the shaders couldn't be built or the accumulator can't be rendered to.
+     */
+    pub unsafe fn new( spheres: Vec<RTSphere>, triangles: &[RTTriangle], meshes: Vec<RTMeshInfo>, width: u32, height: u32 ) -> Result<OffscreenRenderer, EngineError> {
+        let shader = device::current().create_pipeline(
+            &[( &assets::path( "shaders/raytracing.vert" ), ShaderType::Vertex ), ( &assets::path( "shaders/raytracing.frag" ), ShaderType::Fragment )],
+            &RTIntegrator::shader_defines()
+        )?;
+
+        // Upload the scene, with the triangles quantized to their meshes like the render loop does
+        // (Buffers which are empty can't be bound, so each has at least one element, which the counts leave out)
+        let placeholder_mesh = RTMeshInfo { start_index: 0, count: 0, boundingbox_min: glm::Vec3::zeros().into(), boundingbox_max: glm::Vec3::zeros().into() };
+        let mut quantized_triangles = Vec::with_capacity( triangles.len() );
+        for mesh in &meshes {
+            let range = mesh.start_index as usize..( mesh.start_index + mesh.count ) as usize;
+            quantized_triangles.extend( triangles[range].iter().map( |triangle| RTQuantizedTriangle::quantize( triangle, mesh ) ) );
+        }
+        if quantized_triangles.is_empty() {
+            quantized_triangles.push( RTQuantizedTriangle::quantize( &RTTriangle::new(), &placeholder_mesh ) );
+        }
+        let buffers = vec![
+            SSBOBuilder::new()
+                .set_data( if spheres.is_empty() { vec![RTSphere::new()] } else { spheres.clone() } )
+                .set_shader_details( shader.pid, 0, "SphereBuffer" )?
+                .link().id(),
+            SSBOBuilder::new()
+                .set_data( quantized_triangles )
+                .set_shader_details( shader.pid, 1, "TriangleBuffer" )?
+                .link().id(),
+            SSBOBuilder::new()
+                .set_data( if meshes.is_empty() { vec![placeholder_mesh] } else { meshes.clone() } )
+                .set_shader_details( shader.pid, 2, "MeshInfoBuffer" )?
+                .link().id(),
+            SSBOBuilder::new()
+                .set_data( sobol::generator_matrices() )
+                .set_shader_details( shader.pid, 3, "SobolBuffer" )?
+                .link().id(),
+        ];
+
+        let ( vertices, indices ) = util::create_billboard();
+        Ok( OffscreenRenderer {
+            shader,
+            accumulator: Accumulator::new( width, height )?,
+            buffers,
+            vao: util::create_vao( &vertices, &indices ),
+            index_count: indices.len() as i32,
+            blue_noise_texture: bluenoise::create_texture( &assets::path( "resources/bluenoise.png" ) ),
+            spheres,
+            meshes,
+        } )
+    }
+
+    /**
+     * Gets the raytracing shader, such as for counting its rays (see stats::RenderStats).
+     */
+    pub fn shader( &self ) -> &Shader {
+        &self.shader
+    }
+
+    /**
+     * Gets how many frames have been accumulated.
+     */
+    pub fn frame_count( &self ) -> u32 {
+        self.accumulator.frame_count()
+    }
+
+    /**
+     * Renders a frame, accumulating it like the render loop does while the camera and settings stay the same,
+     * so the same seed and frame count give the same image.
+     *
+     * @param camera The camera, whose screen size must be the size of the frames.
+     * @param settings The settings.
+     */
+    pub unsafe fn render_frame( &mut self, camera: &RTCamera, settings: &RTSettings ) {
+        let shader = &self.shader;
+        shader.activate();
+        settings.send_uniform( shader, "settings" );
+        camera.send_uniform( shader, "camera" );
+        let ( bounds_min, bounds_max ) = raytracing::scene_bounds( &self.spheres, &self.meshes );
+        gl::Uniform3fv( shader.get_uniform_location( "sceneBoundsMin" ), 1, bounds_min.as_ptr() );
+        gl::Uniform3fv( shader.get_uniform_location( "sceneBoundsMax" ), 1, bounds_max.as_ptr() );
+        gl::Uniform1i( shader.get_uniform_location( "spheresCount" ), self.spheres.len() as i32 );
+        gl::Uniform1i( shader.get_uniform_location( "meshesCount" ), self.meshes.len() as i32 );
+        settings.integrator.get().send_uniforms( shader );
+        volumes::send_uniforms( shader, &[] );
+
+        self.accumulator.begin_frame( shader, 0 );
+        gl::ActiveTexture( gl::TEXTURE1 );
+        gl::BindTexture( gl::TEXTURE_2D, self.blue_noise_texture );
+        gl::Uniform1i( shader.get_uniform_location( "blueNoiseTexture" ), 1 );
+        gl::BindVertexArray( self.vao );
+        gl::DrawElements( gl::TRIANGLES, self.index_count, gl::UNSIGNED_INT, ptr::null() );
+        self.accumulator.end_frame();
+    }
+
+    /**
+     * Reads back the accumulated image.
+     *
+     * @return The pixels as RGB, with the bottom row first.
+     */
+    pub unsafe fn read_pixels( &self ) -> Vec<f32> {
+        self.accumulator.read_pixels()
+    }
+
+    /**
+     * Deletes the shader, the accumulator and the scene's buffers.
+     */
+    pub unsafe fn delete( self ) {
+        util::delete_vao( self.vao );
+        device::current().delete_texture( self.blue_noise_texture );
+        for buffer in self.buffers {
+            device::current().delete_buffer( buffer );
+        }
+        self.accumulator.delete();
+        self.shader.delete();
+    }
+}
@@ -0,0 +1,354 @@
+use std::time::{ SystemTime, UNIX_EPOCH };
+
+#[cfg(feature = "exr")]
+use exr::prelude::{ f16, AnyChannel, AnyChannels, AttributeValue, FlatSamples, Image, ImageAttributes, SmallVec, SpecificChannels, Text, WritableImage };
+
+use crate::error::EngineError;
+use crate::accumulator::Accumulator;
+#[cfg(feature = "exr")]
+use crate::accumulator::Aov;
+use crate::glapi;
+
+/**
+ * Enum for the precision of the channels of an EXR file.
+ */
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq)]
+pub enum ExrPrecision {
+    Half,   // 16-bit floats, which is half the size and plenty for grading, but tops out at 65504
+    Float,  // 32-bit floats, which keep the accumulated image exactly
+}
+
+/**
+ * Struct for how a render was made (settings, camera, seed, samples...), which is embedded in the files it is saved to.
+ * PNGs get it as text chunks and EXR files as attributes of their header, so any saved image can be traced back to how it was rendered.
+ */
+#[derive(Clone, Default)]
+pub struct Metadata {
+    pub entries: Vec<( String, String )>, // The keys and values, in the order they are written
+}
+
+/**
+ * Metadata functions.
+ */
+impl Metadata {
+    /**
+     * Creates the metadata of a render, with what every render has.
+     *
+     * @param scene The path of the scene.
+     * @param seed The seed of the random numbers.
+     */
+    pub fn new( scene: &str, seed: u32 ) -> Metadata {
+        let scene_hash = crate::checkpoint::hash( &[&std::fs::read( scene ).unwrap_or_default()] );
+        Metadata::default()
+            .with( "Software", concat!( "opengl_raytracing_engine ", env!( "CARGO_PKG_VERSION" ) ) )
+            .with( "Scene", scene )
+            .with( "SceneHash", format!( "{scene_hash:016x}" ) )
+            .with( "Seed", seed )
+    }
+
+    /**
+     * Adds an entry, replacing any with the same key.
+     *
+     * @param key The key, such as "Seed".
+     * @param value The value.
+     */
+    pub fn with( mut self, key: &str, value: impl ToString ) -> Metadata {
+        self.entries.retain( |( other, _ )| other != key );
+        self.entries.push( ( key.to_string(), value.to_string() ) );
+        self
+    }
+
+    /**
+     * Adds the entries to the attributes of an EXR file's header.
+     * (EXR text may only hold Latin-1, so anything else is left out)
+     *
+     * @param attributes The attributes.
+     */
+    #[cfg(feature = "exr")]
+    fn add_to_exr( &self, attributes: &mut ImageAttributes ) {
+        for ( key, value ) in &self.entries {
+            if let ( Some( key ), Some( value ) ) = ( Text::new_or_none( key ), Text::new_or_none( value ) ) {
+                attributes.other.insert( key, AttributeValue::Text( value ) );
+            }
+        }
+    }
+}
+
+/**
+ * Reads back what has been drawn to the screen this frame, before the buffers are swapped.
+ * GL's rows go from the bottom up, so they are flipped to go from the top down like an image file's.
+ *
+ * @param width The width of the screen, in pixels.
+ * @param height The height of the screen, in pixels.
+ *
+ * @return The screen's pixels.
+ */
+pub unsafe fn read_screen( width: u32, height: u32 ) -> image::RgbImage {
+    let channels = glapi::format_channels( glapi::read_format( gl::RGB ) ).1;
+    let mut pixels = vec![0u8; width as usize * height as usize * channels];
+    glapi::bind_screen_for_reading();
+    gl::PixelStorei( gl::PACK_ALIGNMENT, 1 );
+    gl::ReadPixels( 0, 0, width as i32, height as i32, glapi::read_format( gl::RGB ), gl::UNSIGNED_BYTE, pixels.as_mut_ptr() as *mut _ );
+    let pixels = glapi::keep_channels( &pixels, gl::RGB );
+
+    let row_size = width as usize * 3;
+    let flipped: Vec<u8> = pixels.chunks( row_size ).rev().flatten().copied().collect();
+    image::RgbImage::from_raw( width, height, flipped ).unwrap()
+}
+
+/**
+ * Saves what has been drawn to the screen this frame to a PNG file, as it is shown (tonemapped, graded and dithered).
+ * Must be called before the buffers are swapped.
+ *
+ * @param path The path of the file.
+ * @param width The width of the screen, in pixels.
+ * @param height The height of the screen, in pixels.
+ * @param metadata How the render was made, which is written to text chunks.
+ */
+pub unsafe fn save_png( path: &str, width: u32, height: u32, metadata: &Metadata ) -> Result<(), EngineError> {
+    save_png_image( path, &read_screen( width, height ), metadata )
+}
+
+/**
+ * Converts an accumulated image to 8-bit sRGB, clipping what is too bright, without any tonemapping or other post-processing.
+ * This is for images which weren't rendered through the post chain, such as those of the CPU reference, see reference::ReferenceRenderer.
+ *
+ * @param width The width of the image, in pixels.
+ * @param height The height of the image, in pixels.
+ * @param pixels The image, as RGB with the bottom row first.
+ *
+ * @return The image, with the top row first.
+ */
+pub fn srgb_image( width: u32, height: u32, pixels: &[f32] ) -> image::RgbImage {
+    let encode = |linear: f32| {
+        let linear = linear.clamp( 0.0, 1.0 );
+        let srgb = if linear <= 0.003_130_8 { linear * 12.92 } else { 1.055 * linear.powf( 1.0 / 2.4 ) - 0.055 };
+        ( srgb * 255.0 ).round() as u8
+    };
+    let flipped: Vec<u8> = pixels.chunks( width as usize * 3 ).rev().flatten().map( |&v| encode( v ) ).collect();
+    image::RgbImage::from_raw( width, height, flipped ).unwrap()
+}
+
+/**
+ * Saves an image to a PNG file, such as a screen which was read back already.
+ *
+ * @param path The path of the file.
+ * @param image The image.
+ * @param metadata How the render was made, which is written to text chunks.
+ */
+pub fn save_png_image( path: &str, image: &image::RgbImage, metadata: &Metadata ) -> Result<(), EngineError> {
+    let failed = |err: &dyn std::fmt::Display| EngineError::asset( "OUTPUT::FILE_NOT_SUCCESSFULLY_WRITTEN", format!( "{path}: {err}" ) );
+    let file = std::fs::File::create( path ).map_err( |err| EngineError::io( "OUTPUT::FILE_NOT_CREATED", path, err ) )?;
+    let mut encoder = png::Encoder::new( std::io::BufWriter::new( file ), image.width(), image.height() );
+    encoder.set_color( png::ColorType::Rgb );
+    encoder.set_depth( png::BitDepth::Eight );
+    // (International text chunks, as the values may hold any characters)
+    for ( key, value ) in &metadata.entries {
+        encoder.add_itxt_chunk( key.clone(), value.clone() ).map_err( |err| failed( &err ) )?;
+    }
+    encoder.write_header()
+        .and_then( |mut writer| writer.write_image_data( image.as_raw() ) )
+        .map_err( |err| failed( &err ) )
+}
+
+/**
+ * Saves linear HDR pixels, such as the accumulated image, to an OpenEXR file, keeping their full range for grading and compositing.
+ *
+ * @param path The path of the file.
+ * @param width The width of the image, in pixels.
+ * @param height The height of the image, in pixels.
+ * @param pixels The pixels as RGB, with the bottom row first, see Accumulator::read_pixels().
+ * @param precision The precision of the file's channels.
+ * @param metadata How the render was made, which is written to the header.
+ */
+#[cfg(feature = "exr")]
+pub fn save_exr( path: &str, width: u32, height: u32, pixels: &[f32], precision: ExrPrecision, metadata: &Metadata ) -> Result<(), EngineError> {
+    let ( width, height ) = ( width as usize, height as usize );
+    // (EXR's rows go from the top down)
+    let pixel = |x: usize, y: usize| {
+        let i = ( ( height - 1 - y ) * width + x ) * 3;
+        ( pixels[i], pixels[i + 1], pixels[i + 2] )
+    };
+
+    match precision {
+        ExrPrecision::Half => {
+            let mut image = Image::from_channels( ( width, height ), SpecificChannels::rgb( |exr::prelude::Vec2( x, y )| {
+                let ( r, g, b ) = pixel( x, y );
+                ( f16::from_f32( r ), f16::from_f32( g ), f16::from_f32( b ) )
+            } ) );
+            metadata.add_to_exr( &mut image.attributes );
+            image.write().to_file( path )
+        },
+        ExrPrecision::Float => {
+            let mut image = Image::from_channels( ( width, height ), SpecificChannels::rgb( |exr::prelude::Vec2( x, y )| pixel( x, y ) ) );
+            metadata.add_to_exr( &mut image.attributes );
+            image.write().to_file( path )
+        },
+    }.map_err( |err| EngineError::asset( "OUTPUT::FILE_NOT_SUCCESSFULLY_WRITTEN", format!( "{path}: {err}" ) ) )
+}
+
+/**
+ * Saves the accumulated AOVs to OpenEXR files, one per AOV, with the AOV's name put before the extension (render.exr -> render_albedo.exr).
+ * AOVs with a single channel (depth, object ID) are saved as grey.
+ *
+ * @param path The path which the files are named after.
+ * @param accumulator The accumulator holding the AOVs, which must have them on.
+ * @param metadata How the render was made, which is written to the headers.
+ */
+#[cfg(feature = "exr")]
+pub unsafe fn save_aovs( path: &str, accumulator: &Accumulator, metadata: &Metadata ) -> Result<(), EngineError> {
+    let ( width, height ) = accumulator.size();
+    for aov in Aov::ALL {
+        let pixels = accumulator.read_aov( aov ).ok_or( EngineError::render( "OUTPUT::NO_AOVS", "The AOVs must be turned on to be saved" ) )?;
+        let pixels = match aov.channels() {
+            1 => { pixels.iter().flat_map( |&value| [value; 3] ).collect() },
+            _ => { pixels },
+        };
+        save_exr( &with_suffix( path, &format!( "_{}", aov.name() ) ), width, height, &pixels, ExrPrecision::Float, metadata )?;
+    }
+    Ok( () )
+}
+
+/**
+ * Gets the names of an AOV's channels in a layered EXR file, which are those compositors (Nuke, Fusion) recognize the passes by.
+ *
+ * @param aov The AOV.
+ *
+ * @return The names, one per channel of the AOV.
+ */
+#[cfg(feature = "exr")]
+fn exr_channel_names( aov: Aov ) -> &'static [&'static str] {
+    match aov {
+        Aov::Albedo     => { &["albedo.R", "albedo.G", "albedo.B"] },
+        Aov::Normal     => { &["N.X", "N.Y", "N.Z"] },
+        Aov::Depth      => { &["Z"] },
+        Aov::ObjectId   => { &["objectId.id"] },
+        Aov::Direct     => { &["direct.R", "direct.G", "direct.B"] },
+        Aov::Indirect   => { &["indirect.R", "indirect.G", "indirect.B"] },
+    }
+}
+
+/**
+ * Saves the accumulated image and all its AOVs to a single layered OpenEXR file, as 32-bit floats.
+ * The image is in the R, G and B channels, and the AOVs in layers of their own, see exr_channel_names().
+ *
+ * @param path The path of the file.
+ * @param accumulator The accumulator holding the image and the AOVs, which must have them on.
+ * @param metadata How the render was made, which is written to the header.
+ */
+#[cfg(feature = "exr")]
+pub unsafe fn save_layered_exr( path: &str, accumulator: &Accumulator, metadata: &Metadata ) -> Result<(), EngineError> {
+    let ( width, height ) = accumulator.size();
+    let mut channels = SmallVec::new();
+    let mut add_channels = |names: &[&str], pixels: &[f32]| {
+        // (The pixels are interleaved with the bottom row first, while EXR's channels are apart with the top row first)
+        let row_size = width as usize * names.len();
+        for ( c, name ) in names.iter().enumerate() {
+            let samples = pixels.chunks( row_size ).rev().flat_map( |row| row.iter().skip( c ).step_by( names.len() ).copied() ).collect();
+            channels.push( AnyChannel::new( *name, FlatSamples::F32( samples ) ) );
+        }
+    };
+
+    add_channels( &["R", "G", "B"], &accumulator.read_pixels() );
+    for aov in Aov::ALL {
+        let pixels = accumulator.read_aov( aov ).ok_or( EngineError::render( "OUTPUT::NO_AOVS", "The AOVs must be turned on to be saved" ) )?;
+        add_channels( exr_channel_names( aov ), &pixels );
+    }
+
+    let mut image = Image::from_channels( ( width as usize, height as usize ), AnyChannels::sort( channels ) );
+    metadata.add_to_exr( &mut image.attributes );
+    image.write()
+        .to_file( path )
+        .map_err( |err| EngineError::asset( "OUTPUT::FILE_NOT_SUCCESSFULLY_WRITTEN", format!( "{path}: {err}" ) ) )
+}
+
+/**
+ * Gets the error of saving an EXR file without the exr feature, which the EXR functions fail with when it isn't built in.
+ * (Offline renders are checked for it before they start, see headless::HeadlessSettings::from_args())
+ */
+#[cfg(not(feature = "exr"))]
+fn exr_not_built( path: &str ) -> EngineError {
+    EngineError::asset( "OUTPUT::EXR_NOT_BUILT", format!( "{path}\nBuilt without the exr feature, build with --features exr to save EXR files" ) )
+}
+
+#[cfg(not(feature = "exr"))]
+pub fn save_exr( path: &str, _width: u32, _height: u32, _pixels: &[f32], _precision: ExrPrecision, _metadata: &Metadata ) -> Result<(), EngineError> {
+    Err( exr_not_built( path ) )
+}
+
+#[cfg(not(feature = "exr"))]
+pub unsafe fn save_aovs( path: &str, _accumulator: &Accumulator, _metadata: &Metadata ) -> Result<(), EngineError> {
+    Err( exr_not_built( path ) )
+}
+
+#[cfg(not(feature = "exr"))]
+pub unsafe fn save_layered_exr( path: &str, _accumulator: &Accumulator, _metadata: &Metadata ) -> Result<(), EngineError> {
+    Err( exr_not_built( path ) )
+}
+
+/**
+ * Saves linear HDR pixels to a Radiance HDR (RGBE) file, which keeps their range with 8-bit mantissas and a shared exponent.
+ * Rendered panoramas saved this way can be used as environment maps, as that is the format they are usually kept in.
+ *
+ * @param path The path of the file.
+ * @param width The width of the image, in pixels.
+ * @param height The height of the image, in pixels.
+ * @param pixels The pixels as RGB, with the bottom row first, see Accumulator::read_pixels().
+ */
+pub fn save_hdr( path: &str, width: u32, height: u32, pixels: &[f32] ) -> Result<(), EngineError> {
+    // (The file's rows go from the top down)
+    let rows: Vec<image::Rgb<f32>> = pixels.chunks( width as usize * 3 ).rev()
+        .flat_map( |row| row.chunks( 3 ).map( |p| image::Rgb( [p[0], p[1], p[2]] ) ) )
+        .collect();
+
+    let file = std::fs::File::create( path ).map_err( |err| EngineError::io( "OUTPUT::FILE_NOT_CREATED", path, err ) )?;
+    image::codecs::hdr::HdrEncoder::new( std::io::BufWriter::new( file ) )
+        .encode( &rows, width as usize, height as usize )
+        .map_err( |err| EngineError::asset( "OUTPUT::FILE_NOT_SUCCESSFULLY_WRITTEN", format!( "{path}: {err}" ) ) )
+}
+
+/**
+ * Puts a suffix before the extension of a path, or at its end if it has none.
+ *
+ * @param path The path.
+ * @param suffix The suffix.
+ *
+ * @return The path with the suffix, e.g. render.png -> render_suffix.png.
+ */
+pub fn with_suffix( path: &str, suffix: &str ) -> String {
+    // (Dots in the folders aren't extensions)
+    match path.rfind( '.' ).filter( |&dot| !path[dot..].contains( ['/', '\\'] ) ) {
+        Some( dot ) => { format!( "{}{suffix}{}", &path[..dot], &path[dot..] ) },
+        None        => { format!( "{path}{suffix}" ) },
+    }
+}
+
+/**
+ * Gets a file name with the current date and time (UTC), so files saved one after the other don't overwrite each other.
+ *
+ * @param prefix What the file name starts with.
+ * @param extension The extension of the file, without the dot.
+ *
+ * @return The file name, as prefix_YYYYMMDD_HHMMSS.extension.
+ */
+pub fn timestamped_path( prefix: &str, extension: &str ) -> String {
+    let seconds = SystemTime::now().duration_since( UNIX_EPOCH ).map_or( 0, |time| time.as_secs() );
+    let ( days, time_of_day ) = ( ( seconds / 86400 ) as i64, seconds % 86400 );
+
+    // Convert the days since 1970-01-01 to a date, see Howard Hinnant's civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid( 146097 );
+    let day_of_era = z.rem_euclid( 146097 );
+    let year_of_era = ( day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096 ) / 365;
+    let day_of_year = day_of_era - ( 365 * year_of_era + year_of_era / 4 - year_of_era / 100 );
+    let month_index = ( 5 * day_of_year + 2 ) / 153;
+    let day = day_of_year - ( 153 * month_index + 2 ) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + ( month <= 2 ) as i64;
+
+    format!(
+        "{prefix}_{year:04}{month:02}{day:02}_{:02}{:02}{:02}.{extension}",
+        time_of_day / 3600, time_of_day / 60 % 60, time_of_day % 60
+    )
+}
@@ -0,0 +1,106 @@
+use crate::device::{ self, BufferUsage, Device };
+use crate::glapi;
+use crate::shader::Shader;
+
+// Amount of photons each frag traces per frame, which must match the shader
+const PHOTONS_PER_FRAG: usize = 1;
+
+// Amount of cells in the hash grid, which must match the shader
+const GRID_SIZE: usize = 1 << 20;
+
+// Size of a photon in the shader (std430), in bytes
+const PHOTON_SIZE: usize = 32;
+
+// Bindings of the photon buffers in the shader
+const PHOTON_BINDING: u32 = 9;
+const GRID_BINDING: u32 = 10;
+
+/**
+ * Struct for the caustic photon map.
+ * Before each frame is shaded, photons are traced from the lights, and those which reach a diffuse surface through specular bounces are stored.
+ * Shading then gathers the photons around the first diffuse surface it hits, which finds caustics that are very unlikely to be found by paths from the camera.
+ *
+ * The photons are stored in a hash grid, where each cell is a linked list of the photons in it.
+ * Photons only live for a single frame, and the accumulation averages them over time.
+ */
+pub struct PhotonMap {
+    buffers: [device::Buffer; 2],
+    capacity: usize,
+}
+
+/**
+ * PhotonMap functions.
+ */
+impl PhotonMap {
+    /**
+     * Creates the photon buffers, and binds them to the shader's bindings.
+     *
+     * @param width The width of the screen, in pixels.
+     * @param height The height of the screen, in pixels.
+     */
+    pub unsafe fn new( width: u32, height: u32 ) -> PhotonMap {
+        let mut photon_map = PhotonMap {
+            buffers: [0; 2],
+            capacity: 0,
+        };
+        photon_map.buffers = [device::current().create_buffer(), device::current().create_buffer()];
+        photon_map.resize( width, height );
+        photon_map
+    }
+
+    /**
+     * Resizes the photon buffer, as every frag traces its own photons.
+     *
+     * @param width The new width, in pixels.
+     * @param height The new height, in pixels.
+     */
+    pub unsafe fn resize( &mut self, width: u32, height: u32 ) {
+        self.capacity = width as usize * height as usize * PHOTONS_PER_FRAG;
+
+        // (The photon buffer starts with the amount of photons stored, padded to the photons' alignment of 16 bytes)
+        let sizes = [16 + self.capacity * PHOTON_SIZE, GRID_SIZE * std::mem::size_of::<u32>()];
+        for ( ( buffer, binding ), size ) in self.buffers.iter().zip( [PHOTON_BINDING, GRID_BINDING] ).zip( sizes ) {
+            device::current().allocate_buffer( *buffer, size, None, BufferUsage::Storage );
+            device::current().bind_storage_buffer( *buffer, binding );
+        }
+    }
+
+    /**
+     * Deletes the photon buffers, once they are done with.
+     */
+    pub unsafe fn delete( self ) {
+        for buffer in self.buffers {
+            device::current().delete_buffer( buffer );
+        }
+    }
+
+    /**
+     * Prepares a shader for rendering the photon pass.
+     * Clears the photons of the previous frame, and masks the color output so the pass can be rendered into the accumulator without touching it.
+     * The shader must be active.
+     *
+     * @param shader The shader.
+     */
+    pub unsafe fn begin_pass( &self, shader: &Shader ) {
+        gl::BindBuffer( gl::SHADER_STORAGE_BUFFER, self.buffers[0] );
+        glapi::clear_buffer_u32( gl::SHADER_STORAGE_BUFFER, 0, Some( 4 ), 0 );
+        gl::BindBuffer( gl::SHADER_STORAGE_BUFFER, self.buffers[1] );
+        glapi::clear_buffer_u32( gl::SHADER_STORAGE_BUFFER, 0, None, u32::MAX );
+        gl::BindBuffer( gl::SHADER_STORAGE_BUFFER, 0 );
+
+        gl::ColorMask( gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE );
+        gl::Uniform1ui( shader.get_uniform_location( "photonPass" ), 1 );
+        gl::Uniform1ui( shader.get_uniform_location( "photonCapacity" ), self.capacity as u32 );
+    }
+
+    /**
+     * Finishes rendering the photon pass, making the photons visible to the shading pass.
+     *
+     * @param shader The shader.
+     */
+    pub unsafe fn end_pass( &self, shader: &Shader ) {
+        gl::ColorMask( gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE );
+        gl::Uniform1ui( shader.get_uniform_location( "photonPass" ), 0 );
+        gl::MemoryBarrier( gl::SHADER_STORAGE_BARRIER_BIT );
+    }
+}
@@ -0,0 +1,852 @@
+use crate::assets;
+use crate::error::EngineError;
+use crate::device::{ self, Device, TextureFormat };
+use crate::framegraph::{ FrameGraph, PassIo, PassTargets, Resource, TargetDesc, TargetSize };
+use crate::shader::{ Shader, ShaderType };
+use crate::postprocess::{ PostSettings, ExposureMeter, ColorLut, DofPreview };
+use crate::denoiser::Denoiser;
+use crate::taa::TemporalAA;
+use crate::profiler::GpuProfiler;
+use crate::gltracker::{ self, GlObject };
+
+/**
+ * Struct for an image passed between the passes of the post chain.
+ */
+#[derive(Clone, Copy, PartialEq)]
+pub struct PassImage {
+    pub texture: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/**
+ * Struct for what the passes of the post chain need from the rest of the renderer, which is gathered every frame.
+ * The denoiser and TAA are owned by the renderer rather than by their passes, as it renders their guides and resizes them with the accumulator.
+ */
+pub struct PassContext<'a> {
+    pub settings: &'a PostSettings,
+    pub denoiser: &'a mut Denoiser,
+    pub temporal_aa: &'a mut TemporalAA,
+    pub exposure_meter: &'a ExposureMeter,
+    pub camera_exposure: f32, // The physical camera's exposure in stops (see camera::PhysicalCamera::exposure()), or 0 without one
+    pub color_lut: Option<&'a ColorLut>, // The LUT to grade with, or None if grading is off
+    pub dof: Option<DofPreview>, // The lens to preview, or None if the image was rendered through the lens
+    pub frame_count: u32, // How many frames have been accumulated into the image
+    pub frame_index: u32, // Counts the displayed frames, which animates the noise of the film grain and dither
+    pub screen_size: ( u32, u32 ),
+    pub denoised: bool, // If the image has already been denoised (by Open Image Denoise), so the denoise pass is skipped
+    pub previewing: bool, // If the image was rendered at the preview's resolution, see PostSettings::render_size()
+    pub profiler: &'a mut GpuProfiler, // Times each pass, under its name
+    pub draw_quad: &'a dyn Fn(), // Draws the fullscreen quad, which the passes' shaders are run over
+}
+
+/**
+ * Trait for the passes of the post chain, which each take the image of the pass before and render their own.
+ * Each pass owns its shaders, while the targets it renders to are declared (see io()) and owned by the chain's FrameGraph.
+ */
+pub trait PostPass {
+    /**
+     * Gets the name of the pass, as used to reorder it.
+     */
+    fn name( &self ) -> &'static str;
+
+    /**
+     * Gets what the pass reads besides its image, and the targets it renders to, which is asked once as it is added to the chain.
+     */
+    fn io( &self ) -> PassIo {
+        PassIo::default()
+    }
+
+    /**
+     * Checks if the pass is on, which is decided by the post settings.
+     * Passes which are off are skipped, passing the image on as it is.
+     *
+     * @param context What the passes need from the renderer.
+     */
+    fn enabled( &self, context: &PassContext ) -> bool;
+
+    /**
+     * Renders the pass.
+     *
+     * @param image The image of the pass before.
+     * @param context What the passes need from the renderer.
+     * @param targets The targets the pass declared, at this frame's sizes.
+     *
+     * @return The image the pass rendered, or an error if it couldn't render to its targets.
+     */
+    unsafe fn render( &mut self, image: PassImage, context: &mut PassContext, targets: &mut PassTargets ) -> Result<PassImage, EngineError>;
+
+    /**
+     * Deletes the pass's shaders, once the chain is done with.
+     */
+    unsafe fn delete( self: Box<Self> );
+}
+
+/**
+ * Struct for what a RenderPass gets to see of the frame, which is gathered every frame.
+ */
+pub struct FrameContext<'a> {
+    pub settings: &'a PostSettings,
+    pub frame_count: u32, // How many frames have been accumulated into the image
+    pub frame_index: u32, // Counts the displayed frames
+    pub screen_size: ( u32, u32 ),
+    pub previewing: bool, // If the image was rendered at the preview's resolution, see PostSettings::render_size()
+    pub albedo_texture: u32, // The denoiser's guides, which are only rendered while the denoiser, TAA or a preview needs them
+    pub normal_depth_texture: u32,
+    pub draw_quad: &'a dyn Fn(), // Draws the fullscreen quad, which the pass's shaders can be run over
+}
+
+/**
+ * Trait for passes which are added to the post chain from outside the engine, such as debug views or stylization, see Engine::add_render_pass().
+ * They are like the built-in passes (see PostPass), but they are always on, and are told when the size of their image changes.
+ * Their targets are declared like the built-in passes', and their shaders can be built with link_shader().
+ */
+pub trait RenderPass: Send {
+    /**
+     * Gets the name of the pass, which it is shown with in the chain's order and timed under.
+     */
+    fn name( &self ) -> &'static str;
+
+    /**
+     * Gets what the pass reads besides its image, and the targets it renders to, see PostPass::io().
+     */
+    fn io( &self ) -> PassIo {
+        PassIo::default()
+    }
+
+    /**
+     * Sets up the pass once the context is current, before its first frame, such as by building its shaders.
+     *
+     * @return Ok, or an error if the pass couldn't be set up, which stops the render.
+     */
+    unsafe fn setup( &mut self ) -> Result<(), EngineError> {
+        Ok( () )
+    }
+
+    /**
+     * Resizes the pass, before the first frame, and whenever the size of the image it is given changes.
+     *
+     * @param width The width of the image, in pixels.
+     * @param height The height of the image, in pixels.
+     */
+    unsafe fn resize( &mut self, _width: u32, _height: u32 ) {}
+
+    /**
+     * Renders the pass.
+     *
+     * @param image The image of the pass before.
+     * @param frame What the pass gets to see of the frame.
+     * @param targets The targets the pass declared, at this frame's sizes.
+     *
+     * @return The image the pass rendered, or the one it was given if it rendered straight onto it,
+     * or an error if it couldn't render, which stops the render.
+     */
+    unsafe fn execute( &mut self, image: PassImage, frame: &mut FrameContext, targets: &mut PassTargets ) -> Result<PassImage, EngineError>;
+
+    /**
+     * Deletes what the pass made in setup(), such as its shaders, once the chain is done with.
+     * Its targets are deleted by the chain.
+     */
+    unsafe fn delete( self: Box<Self> ) {}
+}
+
+/**
+ * Enum for where a RenderPass is put in the post chain, next to the built-in passes' names (see PostChain::order()).
+ * The passes before the tonemapper (such as "Tonemap") work on the linear HDR image, and the ones after on the display's.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PassPlacement {
+    First,                  // Straight after the image is traced, before the denoiser
+    Before( &'static str ), // Before the pass with the name
+    After( &'static str ),  // After the pass with the name
+    Last,                   // Just before the image is shown
+}
+
+/**
+ * Struct for a RenderPass in the post chain, which keeps track of the size it was last resized to.
+ */
+struct PluginPass {
+    pass: Box<dyn RenderPass>,
+    size: Option<( u32, u32 )>,
+}
+
+impl PostPass for PluginPass {
+    fn name( &self ) -> &'static str { self.pass.name() }
+    fn io( &self ) -> PassIo { self.pass.io() }
+    fn enabled( &self, _context: &PassContext ) -> bool { true }
+
+    unsafe fn render( &mut self, image: PassImage, context: &mut PassContext, targets: &mut PassTargets ) -> Result<PassImage, EngineError> {
+        if self.size != Some( ( image.width, image.height ) ) {
+            self.pass.resize( image.width, image.height );
+            self.size = Some( ( image.width, image.height ) );
+        }
+        let mut frame = FrameContext {
+            settings: context.settings,
+            frame_count: context.frame_count,
+            frame_index: context.frame_index,
+            screen_size: context.screen_size,
+            previewing: context.previewing,
+            albedo_texture: context.denoiser.albedo_texture(),
+            normal_depth_texture: context.denoiser.normal_depth_texture(),
+            draw_quad: context.draw_quad,
+        };
+        let image = self.pass.execute( image, &mut frame, targets );
+        // (Passes may leave their own framebuffer bound, which the next pass would otherwise draw over)
+        gl::BindFramebuffer( gl::FRAMEBUFFER, 0 );
+        image
+    }
+
+    unsafe fn delete( self: Box<Self> ) {
+        self.pass.delete();
+    }
+}
+
+/**
+ * Links one of the post chain's shaders, which run over the fullscreen quad.
+ * The vertex shader is the engine's own, in the asset root (see assets::root()).
+ *
+ * @param fragment_path The path of the fragment shader, which is kept as it is, so the engine's own are given with assets::path().
+ */
+pub unsafe fn link_shader( fragment_path: &str ) -> Result<Shader, EngineError> {
+    device::current().create_pipeline( &[( &assets::path( "shaders/raytracing.vert" ), ShaderType::Vertex ), ( fragment_path, ShaderType::Fragment )], &[] )
+}
+
+/**
+ * Binds a texture to a texture unit, and sets a sampler uniform to it.
+ * The shader must be active.
+ *
+ * @param shader The shader.
+ * @param name The name of the sampler uniform.
+ * @param texture The texture.
+ * @param texture_unit The texture unit.
+ */
+pub unsafe fn bind_texture( shader: &Shader, name: &str, texture: u32, texture_unit: u32 ) {
+    gl::ActiveTexture( gl::TEXTURE0 + texture_unit );
+    gl::BindTexture( gl::TEXTURE_2D, texture );
+    gl::Uniform1i( shader.get_uniform_location( name ), texture_unit as i32 );
+}
+
+// The name of the target most passes render their image to
+const OUTPUT: &str = "output";
+
+/**
+ * Gets what a pass which renders its image to a single target declares.
+ *
+ * @param format The format of the target.
+ * @param size The size of the target.
+ * @param reads What the pass reads besides its image.
+ */
+fn output( format: TextureFormat, size: TargetSize, reads: Vec<Resource> ) -> PassIo {
+    PassIo { reads, targets: vec![TargetDesc { name: OUTPUT, format, size }] }
+}
+
+/**
+ * Pass which denoises the image with the temporal and À-Trous filters of the Denoiser.
+ */
+pub struct DenoisePass {
+    temporal_shader: Shader,
+    atrous_shader: Shader,
+}
+
+impl DenoisePass {
+    /**
+     * Links the pass's shaders.
+     */
+    pub unsafe fn new() -> Result<DenoisePass, EngineError> {
+        Ok( DenoisePass {
+            temporal_shader: link_shader( &assets::path( "shaders/temporal.frag" ) )?,
+            atrous_shader: link_shader( &assets::path( "shaders/atrous.frag" ) )?,
+        } )
+    }
+}
+
+impl PostPass for DenoisePass {
+    fn name( &self ) -> &'static str { "Denoise" }
+    fn io( &self ) -> PassIo { PassIo { reads: vec![Resource::Guides, Resource::Motion], targets: vec![] } }
+    fn enabled( &self, context: &PassContext ) -> bool { context.settings.denoise.is_some() && !context.denoised }
+
+    unsafe fn render( &mut self, image: PassImage, context: &mut PassContext, _targets: &mut PassTargets ) -> Result<PassImage, EngineError> {
+        let Some( denoise ) = context.settings.denoise.as_ref() else { return Ok( image ) };
+        let mut texture = image.texture;
+
+        if let Some( temporal ) = &denoise.temporal {
+            self.temporal_shader.activate();
+            context.denoiser.begin_temporal_pass( &self.temporal_shader, texture, temporal );
+            ( context.draw_quad )();
+            context.denoiser.end_temporal_pass();
+            texture = context.denoiser.temporal_texture();
+        }
+        self.atrous_shader.activate();
+        for iteration in 0..denoise.iterations {
+            context.denoiser.begin_iteration( &self.atrous_shader, texture, denoise, iteration );
+            ( context.draw_quad )();
+            context.denoiser.end_iteration();
+        }
+        if denoise.iterations > 0 {
+            texture = context.denoiser.texture();
+        }
+        Ok( PassImage { texture, ..image } )
+    }
+
+    unsafe fn delete( self: Box<Self> ) {
+        self.temporal_shader.delete();
+        self.atrous_shader.delete();
+    }
+}
+
+/**
+ * Pass which anti-aliases the image over time with TemporalAA.
+ */
+pub struct TaaPass {
+    shader: Shader,
+}
+
+impl TaaPass {
+    /**
+     * Links the pass's shaders.
+     */
+    pub unsafe fn new() -> Result<TaaPass, EngineError> {
+        Ok( TaaPass { shader: link_shader( &assets::path( "shaders/taa.frag" ) )? } )
+    }
+}
+
+impl PostPass for TaaPass {
+    fn name( &self ) -> &'static str { "TAA" }
+    fn io( &self ) -> PassIo { PassIo { reads: vec![Resource::Motion], targets: vec![] } }
+    fn enabled( &self, context: &PassContext ) -> bool { context.settings.taa.is_some() }
+
+    unsafe fn render( &mut self, image: PassImage, context: &mut PassContext, _targets: &mut PassTargets ) -> Result<PassImage, EngineError> {
+        let Some( taa ) = context.settings.taa.as_ref() else { return Ok( image ) };
+        self.shader.activate();
+        context.temporal_aa.begin_pass( &self.shader, image.texture, context.denoiser.motion_texture(), context.frame_count, taa );
+        ( context.draw_quad )();
+        context.temporal_aa.end_pass();
+        Ok( PassImage { texture: context.temporal_aa.texture(), ..image } )
+    }
+
+    unsafe fn delete( self: Box<Self> ) {
+        self.shader.delete();
+    }
+}
+
+/**
+ * Pass which previews the camera's depth of field, for while the camera moves and the lens would take many frames to converge.
+ * The image is rendered through a pinhole, and each pixel gathers its neighbours whose circle of confusion reaches it.
+ * The circles of confusion come from the depth of the denoiser's guides, so those must be rendered.
+ */
+pub struct DofPass {
+    shader: Shader,
+}
+
+impl DofPass {
+    /**
+     * Links the pass's shaders.
+     */
+    pub unsafe fn new() -> Result<DofPass, EngineError> {
+        Ok( DofPass { shader: link_shader( &assets::path( "shaders/dof.frag" ) )? } )
+    }
+}
+
+impl PostPass for DofPass {
+    fn name( &self ) -> &'static str { "DOF preview" }
+    fn io( &self ) -> PassIo { output( TextureFormat::Rgba32F, TargetSize::Input, vec![Resource::Guides] ) }
+    fn enabled( &self, context: &PassContext ) -> bool { context.dof.is_some() }
+
+    unsafe fn render( &mut self, image: PassImage, context: &mut PassContext, targets: &mut PassTargets ) -> Result<PassImage, EngineError> {
+        let Some( dof ) = context.dof else { return Ok( image ) };
+        targets.bind( OUTPUT )?;
+        self.shader.activate();
+        bind_texture( &self.shader, "image", image.texture, 0 );
+        bind_texture( &self.shader, "guideNormalDepth", context.denoiser.normal_depth_texture(), 1 );
+        dof.send_uniform( &self.shader, "dof" );
+        ( context.draw_quad )();
+        gl::BindFramebuffer( gl::FRAMEBUFFER, 0 );
+        targets.image( OUTPUT )
+    }
+
+    unsafe fn delete( self: Box<Self> ) {
+        self.shader.delete();
+    }
+}
+
+/**
+ * Pass which filters the supersampled image down to the screen's size.
+ */
+pub struct DownscalePass {
+    shader: Shader,
+}
+
+impl DownscalePass {
+    /**
+     * Links the pass's shaders.
+     */
+    pub unsafe fn new() -> Result<DownscalePass, EngineError> {
+        Ok( DownscalePass { shader: link_shader( &assets::path( "shaders/downscale.frag" ) )? } )
+    }
+}
+
+impl PostPass for DownscalePass {
+    fn name( &self ) -> &'static str { "Downscale" }
+    fn io( &self ) -> PassIo { output( TextureFormat::Rgba32F, TargetSize::Screen, vec![] ) }
+    fn enabled( &self, context: &PassContext ) -> bool { context.settings.supersampling.is_some() && !context.previewing }
+
+    unsafe fn render( &mut self, image: PassImage, context: &mut PassContext, targets: &mut PassTargets ) -> Result<PassImage, EngineError> {
+        let Some( supersampling ) = context.settings.supersampling.as_ref() else { return Ok( image ) };
+        targets.bind( OUTPUT )?;
+        self.shader.activate();
+        bind_texture( &self.shader, "image", image.texture, 0 );
+        supersampling.send_uniform( &self.shader, "downscale" );
+        ( context.draw_quad )();
+        gl::BindFramebuffer( gl::FRAMEBUFFER, 0 );
+        targets.image( OUTPUT )
+    }
+
+    unsafe fn delete( self: Box<Self> ) {
+        self.shader.delete();
+    }
+}
+
+/**
+ * Pass which upscales the preview's image to the screen's size, while the camera moves, see Preview.
+ * When upscaling temporally, it keeps its own history like TemporalAA, which is forgotten whenever the preview stops.
+ * The motion vectors come from the denoiser's guides, so those must be rendered.
+ */
+pub struct UpscalePass {
+    shader: Shader,
+    current: usize, // Which of HISTORY was upscaled into last
+    prev_frame_index: Option<u32>, // The frame the history was last upscaled in
+}
+
+impl UpscalePass {
+    // The targets the upscaled image and its history are swapped between
+    const HISTORY: [&'static str; 2] = ["history0", "history1"];
+
+    /**
+     * Links the pass's shaders.
+     */
+    pub unsafe fn new() -> Result<UpscalePass, EngineError> {
+        Ok( UpscalePass {
+            shader: link_shader( &assets::path( "shaders/upscale.frag" ) )?,
+            current: 0,
+            prev_frame_index: None,
+        } )
+    }
+}
+
+impl PostPass for UpscalePass {
+    fn name( &self ) -> &'static str { "Upscale" }
+    fn io( &self ) -> PassIo {
+        PassIo {
+            reads: vec![Resource::Motion],
+            targets: Self::HISTORY.map( |name| TargetDesc { name, format: TextureFormat::Rgba32F, size: TargetSize::Screen } ).to_vec(),
+        }
+    }
+    fn enabled( &self, context: &PassContext ) -> bool { context.settings.preview.is_some() && context.previewing }
+
+    unsafe fn render( &mut self, image: PassImage, context: &mut PassContext, targets: &mut PassTargets ) -> Result<PassImage, EngineError> {
+        let Some( preview ) = context.settings.preview.as_ref() else { return Ok( image ) };
+        let ( width, height ) = context.screen_size;
+        // (The history is only valid if it was upscaled last frame, at the same size)
+        let history = targets.image( Self::HISTORY[self.current] )?;
+        let history_valid = self.prev_frame_index == Some( context.frame_index.wrapping_sub( 1 ) ) && ( history.width, history.height ) == ( width, height );
+
+        self.current = 1 - self.current;
+        let history_texture = history.texture;
+        targets.bind( Self::HISTORY[self.current] )?;
+        self.shader.activate();
+        bind_texture( &self.shader, "image", image.texture, 0 );
+        bind_texture( &self.shader, "history", history_texture, 1 );
+        bind_texture( &self.shader, "guideMotion", context.denoiser.motion_texture(), 2 );
+        preview.send_uniform( &self.shader, "preview" );
+        gl::Uniform1ui( self.shader.get_uniform_location( "historyValid" ), history_valid as u32 );
+        gl::Uniform2i( self.shader.get_uniform_location( "screenSize" ), width as i32, height as i32 );
+        ( context.draw_quad )();
+        gl::BindFramebuffer( gl::FRAMEBUFFER, 0 );
+        self.prev_frame_index = Some( context.frame_index );
+        targets.image( Self::HISTORY[self.current] )
+    }
+
+    unsafe fn delete( self: Box<Self> ) {
+        self.shader.delete();
+    }
+}
+
+/**
+ * Pass which adds bloom to the HDR image.
+ * The bright parts are kept at half the size, blurred along each axis in turn, and then added back onto the image.
+ */
+pub struct BloomPass {
+    shader: Shader,
+}
+
+impl BloomPass {
+    // The stages of the bloom, as used by the shader
+    const STAGE_PREFILTER: u32 = 0;
+    const STAGE_BLUR: u32 = 1;
+    const STAGE_COMPOSITE: u32 = 2;
+
+    // The half-size targets the bright parts are blurred between
+    const BLUR: [&'static str; 2] = ["blur0", "blur1"];
+
+    /**
+     * Links the pass's shaders.
+     */
+    pub unsafe fn new() -> Result<BloomPass, EngineError> {
+        Ok( BloomPass { shader: link_shader( &assets::path( "shaders/bloom.frag" ) )? } )
+    }
+}
+
+impl PostPass for BloomPass {
+    fn name( &self ) -> &'static str { "Bloom" }
+    fn io( &self ) -> PassIo {
+        let mut io = output( TextureFormat::Rgba32F, TargetSize::Input, vec![] );
+        io.targets.extend( Self::BLUR.map( |name| TargetDesc { name, format: TextureFormat::Rgba16F, size: TargetSize::Half } ) );
+        io
+    }
+    fn enabled( &self, context: &PassContext ) -> bool { context.settings.bloom.is_some() }
+
+    unsafe fn render( &mut self, image: PassImage, context: &mut PassContext, targets: &mut PassTargets ) -> Result<PassImage, EngineError> {
+        let Some( bloom ) = context.settings.bloom.as_ref() else { return Ok( image ) };
+        self.shader.activate();
+        bloom.send_uniform( &self.shader, "bloom" );
+
+        // Keep the bright parts, then blur them horizontally and vertically
+        targets.bind( Self::BLUR[0] )?;
+        bind_texture( &self.shader, "image", image.texture, 0 );
+        gl::Uniform1ui( self.shader.get_uniform_location( "stage" ), Self::STAGE_PREFILTER );
+        ( context.draw_quad )();
+
+        gl::Uniform1ui( self.shader.get_uniform_location( "stage" ), Self::STAGE_BLUR );
+        for ( read, write, direction ) in [( 0, 1, [1, 0] ), ( 1, 0, [0, 1] )] {
+            targets.bind( Self::BLUR[write] )?;
+            bind_texture( &self.shader, "image", targets.image( Self::BLUR[read] )?.texture, 0 );
+            gl::Uniform2i( self.shader.get_uniform_location( "direction" ), direction[0], direction[1] );
+            ( context.draw_quad )();
+        }
+
+        // Add them onto the image
+        targets.bind( OUTPUT )?;
+        bind_texture( &self.shader, "image", image.texture, 0 );
+        bind_texture( &self.shader, "bloomTexture", targets.image( Self::BLUR[0] )?.texture, 1 );
+        gl::Uniform1ui( self.shader.get_uniform_location( "stage" ), Self::STAGE_COMPOSITE );
+        ( context.draw_quad )();
+        gl::BindFramebuffer( gl::FRAMEBUFFER, 0 );
+        targets.image( OUTPUT )
+    }
+
+    unsafe fn delete( self: Box<Self> ) {
+        self.shader.delete();
+    }
+}
+
+/**
+ * Pass which white balances, exposes, vignettes and tonemaps the HDR image, and encodes it to sRGB for the screen.
+ * It is always on, as the passes after it expect colors in [0, 1].
+ */
+pub struct TonemapPass {
+    shader: Shader,
+}
+
+impl TonemapPass {
+    /**
+     * Links the pass's shaders.
+     */
+    pub unsafe fn new() -> Result<TonemapPass, EngineError> {
+        Ok( TonemapPass { shader: link_shader( &assets::path( "shaders/tonemap.frag" ) )? } )
+    }
+}
+
+impl PostPass for TonemapPass {
+    fn name( &self ) -> &'static str { "Tonemap" }
+    fn io( &self ) -> PassIo { output( TextureFormat::Rgba16F, TargetSize::Input, vec![] ) }
+    fn enabled( &self, _context: &PassContext ) -> bool { true }
+
+    unsafe fn render( &mut self, image: PassImage, context: &mut PassContext, targets: &mut PassTargets ) -> Result<PassImage, EngineError> {
+        let settings = context.settings;
+        targets.bind( OUTPUT )?;
+        self.shader.activate();
+        bind_texture( &self.shader, "image", image.texture, 0 );
+        gl::Uniform1ui( self.shader.get_uniform_location( "whiteBalance" ), settings.white_balance.is_some() as u32 );
+        if let Some( white_balance ) = settings.white_balance {
+            gl::UniformMatrix3fv( self.shader.get_uniform_location( "whiteBalanceMatrix" ), 1, gl::FALSE, white_balance.matrix().as_ptr() );
+        }
+        gl::Uniform1f( self.shader.get_uniform_location( "exposure" ), settings.exposure + context.camera_exposure );
+        context.exposure_meter.send_uniforms( &self.shader );
+        gl::Uniform1ui( self.shader.get_uniform_location( "tonemapper" ), settings.tonemapper.id() );
+        gl::Uniform1ui( self.shader.get_uniform_location( "vignette" ), settings.vignette.is_some() as u32 );
+        if let Some( vignette ) = settings.vignette {
+            vignette.send_uniform( &self.shader, "vignetteEffect" );
+        }
+        gl::Uniform1ui( self.shader.get_uniform_location( "srgbEncode" ), settings.srgb_encode as u32 );
+        ( context.draw_quad )();
+        gl::BindFramebuffer( gl::FRAMEBUFFER, 0 );
+        targets.image( OUTPUT )
+    }
+
+    unsafe fn delete( self: Box<Self> ) {
+        self.shader.delete();
+    }
+}
+
+/**
+ * Pass which grades the image with a LUT.
+ */
+pub struct LutPass {
+    shader: Shader,
+}
+
+impl LutPass {
+    /**
+     * Links the pass's shaders.
+     */
+    pub unsafe fn new() -> Result<LutPass, EngineError> {
+        Ok( LutPass { shader: link_shader( &assets::path( "shaders/lut.frag" ) )? } )
+    }
+}
+
+impl PostPass for LutPass {
+    fn name( &self ) -> &'static str { "LUT" }
+    fn io( &self ) -> PassIo { output( TextureFormat::Rgba16F, TargetSize::Input, vec![] ) }
+    fn enabled( &self, context: &PassContext ) -> bool { context.color_lut.is_some() }
+
+    unsafe fn render( &mut self, image: PassImage, context: &mut PassContext, targets: &mut PassTargets ) -> Result<PassImage, EngineError> {
+        let Some( color_lut ) = context.color_lut else { return Ok( image ) };
+        targets.bind( OUTPUT )?;
+        self.shader.activate();
+        bind_texture( &self.shader, "image", image.texture, 0 );
+        color_lut.send_uniforms( &self.shader, 1 );
+        ( context.draw_quad )();
+        gl::BindFramebuffer( gl::FRAMEBUFFER, 0 );
+        targets.image( OUTPUT )
+    }
+
+    unsafe fn delete( self: Box<Self> ) {
+        self.shader.delete();
+    }
+}
+
+/**
+ * Pass which adds film grain to the image.
+ */
+pub struct FilmGrainPass {
+    shader: Shader,
+}
+
+impl FilmGrainPass {
+    /**
+     * Links the pass's shaders.
+     */
+    pub unsafe fn new() -> Result<FilmGrainPass, EngineError> {
+        Ok( FilmGrainPass { shader: link_shader( &assets::path( "shaders/grain.frag" ) )? } )
+    }
+}
+
+impl PostPass for FilmGrainPass {
+    fn name( &self ) -> &'static str { "Film grain" }
+    fn io( &self ) -> PassIo { output( TextureFormat::Rgba16F, TargetSize::Input, vec![] ) }
+    fn enabled( &self, context: &PassContext ) -> bool { context.settings.film_grain.is_some() }
+
+    unsafe fn render( &mut self, image: PassImage, context: &mut PassContext, targets: &mut PassTargets ) -> Result<PassImage, EngineError> {
+        let Some( film_grain ) = context.settings.film_grain.as_ref() else { return Ok( image ) };
+        targets.bind( OUTPUT )?;
+        self.shader.activate();
+        bind_texture( &self.shader, "image", image.texture, 0 );
+        film_grain.send_uniform( &self.shader, "grain" );
+        gl::Uniform1ui( self.shader.get_uniform_location( "frameIndex" ), context.frame_index );
+        ( context.draw_quad )();
+        gl::BindFramebuffer( gl::FRAMEBUFFER, 0 );
+        targets.image( OUTPUT )
+    }
+
+    unsafe fn delete( self: Box<Self> ) {
+        self.shader.delete();
+    }
+}
+
+/**
+ * Pass which dithers the image, and quantizes it to 8 bits.
+ * It should be the last pass, as anything after it would undo the dithering.
+ */
+pub struct DitherPass {
+    shader: Shader,
+}
+
+impl DitherPass {
+    /**
+     * Links the pass's shaders.
+     */
+    pub unsafe fn new() -> Result<DitherPass, EngineError> {
+        Ok( DitherPass { shader: link_shader( &assets::path( "shaders/dither.frag" ) )? } )
+    }
+}
+
+impl PostPass for DitherPass {
+    fn name( &self ) -> &'static str { "Dither" }
+    fn io( &self ) -> PassIo { output( TextureFormat::Rgba8, TargetSize::Input, vec![] ) }
+    fn enabled( &self, context: &PassContext ) -> bool { context.settings.dither }
+
+    unsafe fn render( &mut self, image: PassImage, context: &mut PassContext, targets: &mut PassTargets ) -> Result<PassImage, EngineError> {
+        targets.bind( OUTPUT )?;
+        self.shader.activate();
+        bind_texture( &self.shader, "image", image.texture, 0 );
+        gl::Uniform1ui( self.shader.get_uniform_location( "frameIndex" ), context.frame_index );
+        ( context.draw_quad )();
+        gl::BindFramebuffer( gl::FRAMEBUFFER, 0 );
+        targets.image( OUTPUT )
+    }
+
+    unsafe fn delete( self: Box<Self> ) {
+        self.shader.delete();
+    }
+}
+
+/**
+ * Struct for the chain of post-processing passes, which turns the accumulated image into what is shown on the screen.
+ * The passes run in order, each on the image of the pass before, skipping those which are off.
+ * The image of the last pass is then copied onto the screen.
+ */
+pub struct PostChain {
+    passes: Vec<Box<dyn PostPass>>,
+    graph: FrameGraph, // The targets the passes render to, see PostPass::io()
+    framebuffer: u32,
+}
+
+/**
+ * PostChain functions.
+ */
+impl PostChain {
+    /**
+     * Creates the chain with every pass, in the default order.
+     *
+     * @return The chain, or an error if one of the passes' shaders couldn't be built.
+     */
+    pub unsafe fn new() -> Result<PostChain, EngineError> {
+        let mut framebuffer: gl::types::GLuint = 0;
+        gl::GenFramebuffers( 1, &mut framebuffer );
+        gltracker::created( GlObject::Framebuffer, &[framebuffer] );
+
+        let mut post_chain = PostChain {
+            passes: vec![
+                Box::new( DenoisePass::new()? ),
+                Box::new( TaaPass::new()? ),
+                Box::new( DofPass::new()? ),
+                Box::new( DownscalePass::new()? ),
+                Box::new( UpscalePass::new()? ),
+                Box::new( BloomPass::new()? ),
+                Box::new( TonemapPass::new()? ),
+                Box::new( LutPass::new()? ),
+                Box::new( FilmGrainPass::new()? ),
+                Box::new( DitherPass::new()? ),
+            ],
+            graph: FrameGraph::new(),
+            framebuffer,
+        };
+        for pass in &post_chain.passes {
+            post_chain.graph.declare( pass.name(), pass.io() )?;
+        }
+        post_chain.graph.check_order( &post_chain.order() )?;
+        Ok( post_chain )
+    }
+
+    /**
+     * Gets the names of the passes, in the order they run.
+     */
+    pub fn order( &self ) -> Vec<&'static str> {
+        self.passes.iter().map( |pass| pass.name() ).collect()
+    }
+
+    #[allow(dead_code)]
+    /**
+     * Moves a pass to another place in the chain.
+     *
+     * @param name The name of the pass.
+     * @param index Where the pass is moved to, which is clamped to the end of the chain.
+     *
+     * @return Ok, or an error if there is no such pass, or if it would then read a target which is rendered after it (in which case it isn't moved).
+     */
+    pub fn move_pass( &mut self, name: &str, index: usize ) -> Result<(), EngineError> {
+        let from = self.passes.iter().position( |pass| pass.name() == name )
+            .ok_or( EngineError::parse( "POSTCHAIN::NO_SUCH_PASS", name.to_string() ) )?;
+        let pass = self.passes.remove( from );
+        let to = index.min( self.passes.len() );
+        self.passes.insert( to, pass );
+        if let Err( err ) = self.graph.check_order( &self.order() ) {
+            let pass = self.passes.remove( to );
+            self.passes.insert( from, pass );
+            return Err( err );
+        }
+        Ok( () )
+    }
+
+    /**
+     * Adds a pass from outside the engine to the chain, setting it up first.
+     * The context must be current.
+     *
+     * @param pass The pass.
+     * @param placement Where the pass is put in the chain.
+     *
+     * @return Ok, or an error if the pass couldn't be set up, if it was placed next to a pass which isn't in the chain,
+     *         or if its name is taken or it reads a target which isn't rendered before it.
+     */
+    pub unsafe fn add_render_pass( &mut self, mut pass: Box<dyn RenderPass>, placement: PassPlacement ) -> Result<(), EngineError> {
+        let position = |name: &str| self.passes.iter().position( |pass| pass.name() == name )
+            .ok_or( EngineError::parse( "POSTCHAIN::NO_SUCH_PASS", name.to_string() ) );
+        let index = match placement {
+            PassPlacement::First => { 0 },
+            PassPlacement::Before( name ) => { position( name )? },
+            PassPlacement::After( name ) => { position( name )? + 1 },
+            PassPlacement::Last => { self.passes.len() },
+        };
+        pass.setup()?;
+        self.graph.declare( pass.name(), pass.io() )?;
+        self.passes.insert( index, Box::new( PluginPass { pass, size: None } ) );
+        self.graph.check_order( &self.order() )
+    }
+
+    /**
+     * Runs the passes on an image, and copies the result onto the screen.
+     *
+     * @param image The accumulated image.
+     * @param context What the passes need from the renderer.
+     *
+     * @return Ok, or the error of the first pass which couldn't render, which stops the render.
+     */
+    pub unsafe fn render( &mut self, image: PassImage, context: &mut PassContext ) -> Result<(), EngineError> {
+        let mut image = image;
+        for pass in self.passes.iter_mut() {
+            if pass.enabled( context ) {
+                let mut targets = self.graph.before_pass( pass.name(), ( image.width, image.height ), context.screen_size )?;
+                context.profiler.begin_scope( pass.name() );
+                let rendered = pass.render( image, context, &mut targets );
+                context.profiler.end_scope( pass.name() );
+                image = rendered?;
+            }
+        }
+
+        let ( screen_width, screen_height ) = context.screen_size;
+        let blit_filter = if ( image.width, image.height ) == context.screen_size { gl::NEAREST } else { gl::LINEAR };
+        gl::BindFramebuffer( gl::READ_FRAMEBUFFER, self.framebuffer );
+        gl::FramebufferTexture2D( gl::READ_FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D, image.texture, 0 );
+        gl::BindFramebuffer( gl::DRAW_FRAMEBUFFER, 0 );
+        gl::BlitFramebuffer(
+            0, 0, image.width as i32, image.height as i32,
+            0, 0, screen_width as i32, screen_height as i32,
+            gl::COLOR_BUFFER_BIT, blit_filter
+        );
+        gl::BindFramebuffer( gl::FRAMEBUFFER, 0 );
+        gl::Viewport( 0, 0, screen_width as i32, screen_height as i32 );
+        Ok( () )
+    }
+
+    /**
+     * Deletes the passes' shaders, the targets they render to, and the framebuffer the image is copied through, once the chain is done with.
+     */
+    pub unsafe fn delete( self ) {
+        for pass in self.passes {
+            pass.delete();
+        }
+        self.graph.delete();
+        gl::DeleteFramebuffers( 1, &self.framebuffer );
+        gltracker::deleted( GlObject::Framebuffer, &[self.framebuffer] );
+    }
+}
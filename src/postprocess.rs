@@ -0,0 +1,758 @@
+use crate::error::EngineError;
+use crate::glapi;
+use crate::shader::Shader;
+use crate::denoiser::AtrousSettings;
+use crate::taa::TaaSettings;
+use crate::gltracker::{ self, GlObject };
+use crate::util;
+
+// Size of the texture the luminance is measured in, which is reduced to a single texel by its mipmaps
+const METER_SIZE: u32 = 256;
+
+// Dynamic resolution: the scale moves in steps of this, frames are timed for this long after each step before the next,
+// and the frame time may be this far off the target (as a fraction of it) before the scale steps at all
+const SCALE_STEP: f32 = 0.125;
+const SCALE_SETTLE_FRAMES: u32 = 8;
+const FRAME_TIME_TOLERANCE: f32 = 0.15;
+
+// The color temperature which white balance leaves as it is, in Kelvin, which is about that of D65 (the white of sRGB)
+pub const NEUTRAL_TEMPERATURE: f32 = 6504.0;
+
+/**
+ * Enum for the tonemapping operators, which compress the accumulated HDR image into the range the screen can show.
+ */
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq)]
+pub enum Tonemapper {
+    Clamp,      // Colors are clamped to [0, 1], which clips bright areas harshly
+    Reinhard,   // Luminance is mapped by L / (1 + L), which never clips but flattens the highlights
+    Filmic,     // John Hable's Uncharted 2 curve, which has a toe and a shoulder like film
+    Aces,       // Krzysztof Narkowicz's fit of the ACES filmic curve, which is contrasty and desaturates the highlights
+}
+
+/**
+ * Tonemapper functions.
+ */
+impl Tonemapper {
+    /**
+     * Gets every tonemapper, in the order of their ids.
+     */
+    pub fn all() -> [Tonemapper; 4] {
+        [ Tonemapper::Clamp, Tonemapper::Reinhard, Tonemapper::Filmic, Tonemapper::Aces ]
+    }
+
+    /**
+     * Gets the id of the tonemapper, as used by the shader.
+     */
+    pub fn id( &self ) -> u32 {
+        match self {
+            Tonemapper::Clamp       => { 0 },
+            Tonemapper::Reinhard    => { 1 },
+            Tonemapper::Filmic      => { 2 },
+            Tonemapper::Aces        => { 3 },
+        }
+    }
+
+    /**
+     * Gets the name of the tonemapper, as shown when it is selected.
+     */
+    pub fn name( &self ) -> &'static str {
+        match self {
+            Tonemapper::Clamp       => { "Clamp" },
+            Tonemapper::Reinhard    => { "Reinhard" },
+            Tonemapper::Filmic      => { "Filmic" },
+            Tonemapper::Aces        => { "ACES" },
+        }
+    }
+
+    /**
+     * Gets the tonemapper after this one, wrapping around after the last.
+     */
+    pub fn next( &self ) -> Tonemapper {
+        let all = Tonemapper::all();
+        all[( self.id() as usize + 1 ) % all.len()]
+    }
+}
+
+/**
+ * Struct for the settings of auto-exposure, which exposes the image so that its log-average luminance becomes middle grey.
+ * The exposure adapts over time like an eye, rather than jumping whenever the image changes.
+ */
+#[derive(Clone, Copy, PartialEq)]
+pub struct AutoExposure {
+    pub key: f32,               // The luminance the log-average is exposed to, where 0.18 is middle grey
+    pub min_ev: f32,            // The lowest exposure adjustment, in stops
+    pub max_ev: f32,            // The highest exposure adjustment, in stops
+    pub speed_brighten: f32,    // How fast the exposure rises when the image gets darker, in 1/seconds
+    pub speed_darken: f32,      // How fast the exposure falls when the image gets brighter, in 1/seconds
+}
+
+/**
+ * Enum for the reconstruction filters, which the supersampled image is downscaled to the screen with.
+ * The filters are separable, and their radii are in screen pixels.
+ */
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq)]
+pub enum ReconstructionFilter {
+    Box,            // Averages the pixels within the screen pixel, radius 0.5
+    Gaussian,       // Gaussian with a standard deviation of 0.5, radius 1.5
+    BlackmanHarris, // Blackman-Harris window, which is much like a Gaussian with less blur, radius 1.5
+    Mitchell,       // Mitchell-Netravali with B = C = 1/3, whose negative lobes keep it sharp, radius 2
+}
+
+/**
+ * ReconstructionFilter functions.
+ */
+impl ReconstructionFilter {
+    /**
+     * Gets the id of the filter, as used by the shader.
+     */
+    pub fn id( &self ) -> u32 {
+        match self {
+            ReconstructionFilter::Box               => { 0 },
+            ReconstructionFilter::Gaussian          => { 1 },
+            ReconstructionFilter::BlackmanHarris    => { 2 },
+            ReconstructionFilter::Mitchell          => { 3 },
+        }
+    }
+}
+
+/**
+ * Struct for supersampling, where the image is rendered at a multiple of the screen's size and filtered down to it.
+ */
+#[derive(Clone, Copy, PartialEq)]
+pub struct Supersampling {
+    pub factor: u32, // How many rendered pixels there are along each axis of a screen pixel, usually 2 or 4
+    pub filter: ReconstructionFilter,
+}
+
+/**
+ * Supersampling functions.
+ */
+impl Supersampling {
+    /**
+     * Sends the supersampling to the downscale shader.
+     * The shader must be active.
+     *
+     * @param shader The downscale shader.
+     * @param uniform_name The name of the supersampling uniform.
+     */
+    pub unsafe fn send_uniform( &self, shader: &Shader, uniform_name: &str ) {
+        gl::Uniform1i( shader.get_uniform_location( format!("{uniform_name}.factor").as_str() ), self.factor.max( 1 ) as i32 );
+        gl::Uniform1ui( shader.get_uniform_location( format!("{uniform_name}.reconstructionFilter").as_str() ), self.filter.id() );
+    }
+}
+
+/**
+ * Enum for how the preview's image is upscaled to the screen, see Preview.
+ */
+#[derive(Clone, Copy, PartialEq)]
+pub enum PreviewUpscale {
+    Bilinear,                   // Filters the image up as it is, which is blurry but has no lag
+    Temporal { alpha: f32 },    // Blends each jittered frame into a history at the screen's size, which follows the surfaces like TAA does
+}
+
+/**
+ * Struct for previewing at a lower resolution while the camera moves, so navigating heavy scenes stays responsive.
+ * Once the camera stops, the image is rendered at its full resolution again, which restarts the accumulation.
+ */
+#[derive(Clone, Copy, PartialEq)]
+pub struct Preview {
+    pub divisor: u32, // How many screen pixels there are along each axis of a rendered pixel, usually 2 or 4
+    pub upscale: PreviewUpscale,
+}
+
+/**
+ * Preview functions.
+ */
+impl Preview {
+    /**
+     * Sends the preview to the upscale shader.
+     * The shader must be active.
+     *
+     * @param shader The upscale shader.
+     * @param uniform_name The name of the preview uniform.
+     */
+    pub unsafe fn send_uniform( &self, shader: &Shader, uniform_name: &str ) {
+        let ( temporal, alpha ) = match self.upscale {
+            PreviewUpscale::Bilinear            => { ( false, 1.0 ) },
+            PreviewUpscale::Temporal { alpha }  => { ( true, alpha ) },
+        };
+        gl::Uniform1ui( shader.get_uniform_location( format!("{uniform_name}.temporal").as_str() ), temporal as u32 );
+        gl::Uniform1f( shader.get_uniform_location( format!("{uniform_name}.alpha").as_str() ), alpha );
+    }
+}
+
+/**
+ * Enum for what happens to the accumulation when the dynamic resolution changes the render size.
+ */
+#[derive(Clone, Copy, PartialEq)]
+pub enum RescalePolicy {
+    Reset,      // The accumulation restarts at the new size
+    Continue,   // The accumulated image is resampled to the new size and accumulated on, see Accumulator::rescale()
+}
+
+/**
+ * Struct for dynamic resolution, where the render size is scaled every frame to keep the frame time at a target, see ResolutionScaler.
+ */
+#[derive(Clone, Copy, PartialEq)]
+pub struct DynamicResolution {
+    pub target_frame_time: f32, // In seconds, such as 0.016 for 60 frames per second
+    pub min_scale: f32, // The smallest scale of each axis of the render
+    pub max_scale: f32,
+    pub policy: RescalePolicy,
+}
+
+/**
+ * Struct for a vignette, which darkens the image towards its corners like a real lens does.
+ * The distance from the center is measured so that the corners are at 1.
+ */
+#[derive(Clone, Copy, PartialEq)]
+pub struct Vignette {
+    pub strength: f32, // How much the corners are darkened, from 0 (not at all) to 1 (black)
+    pub radius: f32, // The distance from the center where the darkening starts
+    pub softness: f32, // The distance over which the darkening goes from none to full
+}
+
+/**
+ * Vignette functions.
+ */
+impl Vignette {
+    /**
+     * Sends the vignette to the tonemap shader.
+     * The shader must be active.
+     *
+     * @param shader The tonemap shader.
+     * @param uniform_name The name of the vignette uniform.
+     */
+    pub unsafe fn send_uniform( &self, shader: &Shader, uniform_name: &str ) {
+        gl::Uniform1f( shader.get_uniform_location( format!("{uniform_name}.strength").as_str() ), self.strength );
+        gl::Uniform1f( shader.get_uniform_location( format!("{uniform_name}.radius").as_str() ), self.radius );
+        gl::Uniform1f( shader.get_uniform_location( format!("{uniform_name}.softness").as_str() ), self.softness );
+    }
+}
+
+/**
+ * Struct for film grain, which is noise that changes every displayed frame.
+ * It is added after tonemapping, and is strongest in the midtones like the grain of real film.
+ */
+#[derive(Clone, Copy, PartialEq)]
+pub struct FilmGrain {
+    pub strength: f32, // The standard deviation of the grain in the midtones, where 1 is the full range of the screen
+    pub size: f32, // The size of each grain, in pixels
+}
+
+/**
+ * FilmGrain functions.
+ */
+impl FilmGrain {
+    /**
+     * Sends the film grain to the grain shader.
+     * The shader must be active.
+     *
+     * @param shader The grain shader.
+     * @param uniform_name The name of the film grain uniform.
+     */
+    pub unsafe fn send_uniform( &self, shader: &Shader, uniform_name: &str ) {
+        gl::Uniform1f( shader.get_uniform_location( format!("{uniform_name}.strength").as_str() ), self.strength );
+        gl::Uniform1f( shader.get_uniform_location( format!("{uniform_name}.size").as_str() ), self.size );
+    }
+}
+
+/**
+ * Struct for bloom, where the parts of the image brighter than a threshold glow onto their surroundings.
+ * It is added before tonemapping, so the glow is as bright as the light which causes it.
+ */
+#[derive(Clone, Copy, PartialEq)]
+pub struct Bloom {
+    pub threshold: f32, // The luminance above which pixels glow, where 1 is white before exposure
+    pub strength: f32, // How much of the glow is added onto the image
+    pub radius: f32, // The standard deviation of the glow, in pixels at half the image's size
+}
+
+/**
+ * Bloom functions.
+ */
+impl Bloom {
+    /**
+     * Sends the bloom to the bloom shader.
+     * The shader must be active.
+     *
+     * @param shader The bloom shader.
+     * @param uniform_name The name of the bloom uniform.
+     */
+    pub unsafe fn send_uniform( &self, shader: &Shader, uniform_name: &str ) {
+        gl::Uniform1f( shader.get_uniform_location( format!("{uniform_name}.threshold").as_str() ), self.threshold );
+        gl::Uniform1f( shader.get_uniform_location( format!("{uniform_name}.strength").as_str() ), self.strength );
+        gl::Uniform1f( shader.get_uniform_location( format!("{uniform_name}.radius").as_str() ), self.radius );
+    }
+}
+
+/**
+ * Struct for white balance, which makes light of a color temperature look white, as a camera would.
+ * The image is adapted from the white of the temperature and tint to the white of NEUTRAL_TEMPERATURE with the Bradford transform.
+ * It is applied to the linear image before tonemapping, so the colors of the lights are left as they are.
+ */
+#[derive(Clone, Copy, PartialEq)]
+pub struct WhiteBalance {
+    pub temperature: f32, // The temperature which becomes white, in Kelvin, where lower cools the image down and higher warms it up
+    pub tint: f32, // Moves the white off the temperature's, where positive makes the image more magenta (for green light) and negative more green
+}
+
+/**
+ * WhiteBalance functions.
+ */
+impl WhiteBalance {
+    /**
+     * Gets the chromaticity of a black body, using the cubic spline fit of Kim et al. to the Planckian locus.
+     *
+     * @param temperature The temperature, which is clamped to the range of the fit, [1667, 25000] Kelvin.
+     *
+     * @return The CIE 1931 xy chromaticity.
+     */
+    fn planckian_xy( temperature: f32 ) -> glm::Vec2 {
+        let t = temperature.clamp( 1667.0, 25000.0 ) as f64;
+        let ( t2, t3 ) = ( t * t, t * t * t );
+        let x = if t <= 4000.0 {
+            -0.2661239e9 / t3 - 0.2343589e6 / t2 + 0.8776956e3 / t + 0.179910
+        } else {
+            -3.0258469e9 / t3 + 2.1070379e6 / t2 + 0.2226347e3 / t + 0.240390
+        };
+        let ( x2, x3 ) = ( x * x, x * x * x );
+        let y = if t <= 2222.0 {
+            -1.1063814 * x3 - 1.34811020 * x2 + 2.18555832 * x - 0.20219683
+        } else if t <= 4000.0 {
+            -0.9549476 * x3 - 1.37418593 * x2 + 2.09137015 * x - 0.16748867
+        } else {
+            3.0817580 * x3 - 5.87338670 * x2 + 3.75112997 * x - 0.37001483
+        };
+        glm::vec2( x as f32, y as f32 )
+    }
+
+    /**
+     * Gets the XYZ of the white of a temperature and tint, with a luminance of 1.
+     * The tint moves the white along the v axis of CIE 1960 UCS, which is about perpendicular to the Planckian locus.
+     *
+     * @param temperature The temperature, in Kelvin.
+     * @param tint The tint.
+     */
+    fn white_xyz( temperature: f32, tint: f32 ) -> glm::Vec3 {
+        let xy = WhiteBalance::planckian_xy( temperature );
+        let denominator = -2.0 * xy.x + 12.0 * xy.y + 3.0;
+        let ( u, v ) = ( 4.0 * xy.x / denominator, 6.0 * xy.y / denominator + tint * 0.01 );
+
+        let denominator = 2.0 * u - 8.0 * v + 4.0;
+        let ( x, y ) = ( 3.0 * u / denominator, 2.0 * v / denominator );
+        glm::vec3( x / y, 1.0, ( 1.0 - x - y ) / y )
+    }
+
+    /**
+     * Gets the matrix which white balances a linear sRGB color.
+     * At NEUTRAL_TEMPERATURE and no tint, this is the identity.
+     */
+    pub fn matrix( &self ) -> glm::Mat3 {
+        let rgb_to_xyz = glm::mat3(
+            0.4124564, 0.3575761, 0.1804375,
+            0.2126729, 0.7151522, 0.0721750,
+            0.0193339, 0.119192, 0.9503041,
+        );
+        let bradford = glm::mat3(
+            0.8951, 0.2664, -0.1614,
+            -0.7502, 1.7135, 0.0367,
+            0.0389, -0.0685, 1.0296,
+        );
+
+        // Scale the cone responses of the source white to those of the neutral white
+        let source = bradford * WhiteBalance::white_xyz( self.temperature, self.tint );
+        let target = bradford * WhiteBalance::white_xyz( NEUTRAL_TEMPERATURE, 0.0 );
+        let scale = glm::Mat3::from_diagonal( &target.component_div( &source ) );
+
+        glm::inverse( &rgb_to_xyz ) * glm::inverse( &bradford ) * scale * bradford * rgb_to_xyz
+    }
+}
+
+/**
+ * Struct for the lens which the DOF pass previews, see DofPass.
+ * It matches the thin lens the camera samples, with the focus plane perpendicular to the camera's front.
+ */
+#[derive(Clone, Copy, PartialEq)]
+pub struct DofPreview {
+    pub aperture_radius: f32,
+    pub focus_distance: f32,
+    pub fov: f32, // The vertical field of view, in degrees
+}
+
+/**
+ * DofPreview functions.
+ */
+impl DofPreview {
+    /**
+     * Sends the lens to the DOF shader.
+     * The shader must be active.
+     *
+     * @param shader The DOF shader.
+     * @param uniform_name The name of the lens uniform.
+     */
+    pub unsafe fn send_uniform( &self, shader: &Shader, uniform_name: &str ) {
+        gl::Uniform1f( shader.get_uniform_location( format!("{uniform_name}.apertureRadius").as_str() ), self.aperture_radius );
+        gl::Uniform1f( shader.get_uniform_location( format!("{uniform_name}.focusDistance").as_str() ), self.focus_distance );
+        gl::Uniform1f( shader.get_uniform_location( format!("{uniform_name}.tanHalfFov").as_str() ), ( self.fov.to_radians() * 0.5 ).tan() );
+    }
+}
+
+/**
+ * Struct for the settings of the post-processing, which is applied to the accumulated image when it is displayed.
+ * Changing these doesn't restart the accumulation, as they don't affect the image being accumulated.
+ */
+#[derive(Clone, Copy, PartialEq)]
+pub struct PostSettings {
+    pub denoise: Option<AtrousSettings>, // Denoises the image before anything else, see Denoiser
+    pub taa: Option<TaaSettings>, // Anti-aliases the image over time while the camera moves, after the denoiser, see TemporalAA
+    pub dof_preview: bool, // Previews the depth of field with a blur while the camera moves, instead of sampling the lens, see DofPass
+    pub exposure: f32, // Exposure compensation in stops (EV), where each stop doubles the brightness
+    pub white_balance: Option<WhiteBalance>,
+    pub auto_exposure: Option<AutoExposure>,
+    pub tonemapper: Tonemapper,
+    pub bloom: Option<Bloom>,
+    pub vignette: Option<Vignette>,
+    pub film_grain: Option<FilmGrain>,
+    pub supersampling: Option<Supersampling>, // Renders at a higher resolution than the screen, see render_size()
+    pub preview: Option<Preview>, // Renders at a lower resolution than the screen while the camera moves, see render_size()
+    pub dynamic_resolution: Option<DynamicResolution>, // Scales the render size to hit a frame time, see render_size()
+    pub srgb_encode: bool, // Encodes the linear image to sRGB for the screen, which can be turned off to see the image as it would look without it
+    pub dither: bool, // Adds noise of a step of 8-bit color before the image is quantized to it, which hides banding in smooth gradients
+    pub scale: f32, // Scales the render size relative to the screen, before everything else in render_size()
+}
+
+/**
+ * PostSettings functions.
+ */
+impl PostSettings {
+    /**
+     * Gets the size the image is rendered at, which is the screen's size unless supersampling or previewing.
+     * Everything before the downscale and upscale passes (accumulation, denoising, TAA) works at this size, see DownscalePass and UpscalePass.
+     * While previewing, the supersampling is left out, as the preview is about being fast.
+     * The screen's size is first scaled by the settings' scale, and with dynamic resolution, the result is then scaled by the ResolutionScaler's scale.
+     *
+     * @param screen_width The width of the screen, in pixels.
+     * @param screen_height The height of the screen, in pixels.
+     * @param previewing If the camera is moving, so the preview's resolution is used if there is a preview.
+     * @param scale The scale of the dynamic resolution, which is ignored while it is off.
+     */
+    pub fn render_size( &self, screen_width: u32, screen_height: u32, previewing: bool, scale: f32 ) -> ( u32, u32 ) {
+        let ( screen_width, screen_height ) = ( ( ( screen_width as f32 * self.scale ).round() as u32 ).max( 1 ), ( ( screen_height as f32 * self.scale ).round() as u32 ).max( 1 ) );
+        let ( width, height ) = match self.preview.filter( |_| previewing ) {
+            Some( preview ) => {
+                let divisor = preview.divisor.max( 1 );
+                ( screen_width.div_ceil( divisor ), screen_height.div_ceil( divisor ) )
+            },
+            None => {
+                let factor = self.supersampling.map_or( 1, |supersampling| supersampling.factor.max( 1 ) );
+                ( screen_width * factor, screen_height * factor )
+            },
+        };
+        match self.dynamic_resolution {
+            Some( _ ) => { ( ( ( width as f32 * scale ).round() as u32 ).max( 1 ), ( ( height as f32 * scale ).round() as u32 ).max( 1 ) ) },
+            None => { ( width, height ) },
+        }
+    }
+}
+
+/**
+ * Struct for picking the scale of the dynamic resolution, from how long the frames take.
+ * The frame time is smoothed, and as it is roughly proportional to the amount of pixels, the scale of each axis follows its square root.
+ * The scale moves in steps, and only once the frame time is clearly off the target, so it settles instead of changing (and resizing the buffers) every frame.
+ */
+pub struct ResolutionScaler {
+    scale: f32,
+    frame_time: Option<f32>, // The smoothed frame time at the current scale
+    frames: u32, // Frames timed at the current scale
+}
+
+/**
+ * ResolutionScaler functions.
+ */
+impl ResolutionScaler {
+    /**
+     * Creates a scaler, starting at the largest scale.
+     *
+     * @param settings The settings of the dynamic resolution.
+     */
+    pub fn new( settings: &DynamicResolution ) -> ResolutionScaler {
+        ResolutionScaler { scale: settings.max_scale, frame_time: None, frames: 0 }
+    }
+
+    /**
+     * Times a frame, stepping the scale towards what it takes to hit the target frame time.
+     *
+     * @param frame_time How long the last frame took, in seconds.
+     * @param settings The settings of the dynamic resolution.
+     *
+     * @return The new scale.
+     */
+    pub fn update( &mut self, frame_time: f32, settings: &DynamicResolution ) -> f32 {
+        let smoothed = self.frame_time.map_or( frame_time, |smoothed| smoothed + ( frame_time - smoothed ) * 0.25 );
+        self.frame_time = Some( smoothed );
+        self.frames += 1;
+
+        let ratio = settings.target_frame_time / smoothed.max( 1e-6 );
+        let off_target = !( 1.0 - FRAME_TIME_TOLERANCE..=1.0 + FRAME_TIME_TOLERANCE ).contains( &ratio );
+        if self.frames >= SCALE_SETTLE_FRAMES && off_target {
+            let scale = ( ( self.scale * ratio.sqrt() / SCALE_STEP ).round() * SCALE_STEP ).clamp( settings.min_scale, settings.max_scale );
+            if scale != self.scale {
+                self.scale = scale;
+                self.frame_time = None;
+                self.frames = 0;
+            }
+        }
+        self.scale
+    }
+
+}
+
+/**
+ * Struct for measuring the brightness of the image, for auto-exposure.
+ * The log of each pixel's luminance is drawn into a small texture, which mipmapping averages down to a single texel.
+ * That average is read back every frame, and the exposure adapts towards what it takes to bring it to the key.
+ */
+pub struct ExposureMeter {
+    framebuffer: u32,
+    texture: u32,
+    levels: i32,
+    adapted_ev: Option<f32>,
+}
+
+/**
+ * ExposureMeter functions.
+ */
+impl ExposureMeter {
+    /**
+     * Creates the meter's framebuffer and its mipmapped R32F texture.
+     *
+     * @return The meter, or an error if the driver can't render to its framebuffer.
+     */
+    pub unsafe fn new() -> Result<ExposureMeter, EngineError> {
+        let mut meter = ExposureMeter {
+            framebuffer: 0,
+            texture: 0,
+            levels: METER_SIZE.ilog2() as i32 + 1,
+            adapted_ev: None,
+        };
+
+        gl::GenTextures( 1, &mut meter.texture );
+        gltracker::created( GlObject::Texture, &[meter.texture] );
+        gl::BindTexture( gl::TEXTURE_2D, meter.texture );
+        gl::TexStorage2D( gl::TEXTURE_2D, meter.levels, gl::R32F, METER_SIZE as i32, METER_SIZE as i32 );
+        gl::TexParameteri( gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST_MIPMAP_NEAREST as i32 );
+        gl::TexParameteri( gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32 );
+        gl::BindTexture( gl::TEXTURE_2D, 0 );
+
+        gl::GenFramebuffers( 1, &mut meter.framebuffer );
+        gltracker::created( GlObject::Framebuffer, &[meter.framebuffer] );
+        gl::BindFramebuffer( gl::FRAMEBUFFER, meter.framebuffer );
+        gl::FramebufferTexture2D( gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D, meter.texture, 0 );
+        util::check_framebuffer( "POSTPROCESS::FRAMEBUFFER_INCOMPLETE" )?;
+        gl::BindFramebuffer( gl::FRAMEBUFFER, 0 );
+
+        Ok( meter )
+    }
+
+    /**
+     * Forgets the adapted exposure, so the next measurement is used right away.
+     * Also makes send_uniforms() send no adjustment until then, which is what's wanted while auto-exposure is off.
+     */
+    pub fn reset( &mut self ) {
+        self.adapted_ev = None;
+    }
+
+    /**
+     * Prepares the luminance shader for measuring an image, and binds the meter's framebuffer.
+     * The shader must be active.
+     *
+     * @param shader The luminance shader.
+     * @param image The texture to measure.
+     * @param texture_unit The texture unit to bind the image to.
+     */
+    pub unsafe fn begin_pass( &self, shader: &Shader, image: u32, texture_unit: u32 ) {
+        gl::BindFramebuffer( gl::FRAMEBUFFER, self.framebuffer );
+        gl::Viewport( 0, 0, METER_SIZE as i32, METER_SIZE as i32 );
+
+        gl::ActiveTexture( gl::TEXTURE0 + texture_unit );
+        gl::BindTexture( gl::TEXTURE_2D, image );
+        gl::Uniform1i( shader.get_uniform_location( "image" ), texture_unit as i32 );
+        gl::Uniform1i( shader.get_uniform_location( "meterSize" ), METER_SIZE as i32 );
+    }
+
+    /**
+     * Finishes measuring, reading back the log-average luminance and adapting the exposure towards it.
+     * Unbinds the framebuffer, so following draws go to the screen.
+     *
+     * @param settings The auto-exposure settings.
+     * @param dt The time since the last measurement, in seconds.
+     */
+    pub unsafe fn end_pass( &mut self, settings: &AutoExposure, dt: f32 ) {
+        gl::BindFramebuffer( gl::FRAMEBUFFER, 0 );
+
+        // Average the log luminance down to a single texel, and read it back
+        gl::BindTexture( gl::TEXTURE_2D, self.texture );
+        gl::GenerateMipmap( gl::TEXTURE_2D );
+        gl::BindTexture( gl::TEXTURE_2D, 0 );
+        let mut texel = [0.0f32; 4];
+        glapi::read_texture( self.texture, self.levels - 1, 1, 1, gl::RED, texel.as_mut_ptr() as *mut _ );
+        let log_average = texel[0];
+
+        // The exposure which brings the average to the key, in stops
+        // (An image with NaNs in it keeps the current exposure)
+        if !log_average.is_finite() { return; }
+        let target_ev = ( settings.key.log2() - log_average / std::f32::consts::LN_2 ).clamp( settings.min_ev, settings.max_ev );
+
+        // Adapt towards it exponentially, so that the speed doesn't depend on the frame rate
+        self.adapted_ev = Some( match self.adapted_ev {
+            Some( adapted_ev ) => {
+                let speed = if target_ev > adapted_ev { settings.speed_brighten } else { settings.speed_darken };
+                adapted_ev + ( target_ev - adapted_ev ) * ( 1.0 - ( -dt * speed ).exp() )
+            },
+            None => { target_ev },
+        } );
+    }
+
+    /**
+     * Sends the adapted exposure to the tonemap shader, which is added to the exposure of the post settings.
+     * The shader must be active.
+     *
+     * @param shader The tonemap shader.
+     */
+    pub unsafe fn send_uniforms( &self, shader: &Shader ) {
+        gl::Uniform1f( shader.get_uniform_location( "autoExposure" ), self.adapted_ev.unwrap_or( 0.0 ) );
+    }
+
+    /**
+     * Deletes the meter's framebuffer and texture, once it is done with.
+     */
+    pub unsafe fn delete( self ) {
+        gl::DeleteFramebuffers( 1, &self.framebuffer );
+        gltracker::deleted( GlObject::Framebuffer, &[self.framebuffer] );
+        gl::DeleteTextures( 1, &self.texture );
+        gltracker::deleted( GlObject::Texture, &[self.texture] );
+    }
+}
+
+/**
+ * Struct for a 3D color lookup table (LUT), which grades the final image.
+ * LUTs map display colors to display colors, so the lookup is done after tonemapping.
+ */
+pub struct ColorLut {
+    texture: u32,
+    size: u32,
+    domain_min: glm::Vec3,
+    domain_max: glm::Vec3,
+}
+
+/**
+ * ColorLut functions.
+ */
+impl ColorLut {
+    /**
+     * Loads a LUT from an Adobe/Resolve .cube file, as exported by DaVinci Resolve or Blender, onto a 3D texture.
+     * Only 3D LUTs are supported, with the red index changing fastest as the format has it.
+     * https://kono.phpage.fr/images/a/a1/Adobe-cube-lut-specification-1.0.pdf
+     *
+     * @param path The path to the file.
+     *
+     * @return The LUT, or an error if the file couldn't be read.
+     */
+    pub unsafe fn load_cube( path: &str ) -> Result<ColorLut, EngineError> {
+        let text = std::fs::read_to_string( path ).map_err( |err| EngineError::io( "POSTPROCESS::FAILED_TO_READ_FILE", path, err ) )?;
+        let invalid = |line: usize, reason: &str| EngineError::parse( "POSTPROCESS::INVALID_CUBE", format!( "{path}:{line}: {reason}" ) );
+
+        let ( mut size, mut domain_min, mut domain_max ) = ( 0, glm::vec3( 0.0, 0.0, 0.0 ), glm::vec3( 1.0, 1.0, 1.0 ) );
+        let mut data = Vec::new();
+        for ( i, line ) in text.lines().enumerate() {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let parse_vec3 = |words: &[&str]| -> Result<glm::Vec3, EngineError> {
+                let values: Vec<f32> = words.iter().map( |word| word.parse::<f32>() ).collect::<Result<_, _>>()
+                    .map_err( |_| invalid( i + 1, "Expected numbers" ) )?;
+                match values[..] {
+                    [r, g, b] => { Ok( glm::vec3( r, g, b ) ) },
+                    _ => { Err( invalid( i + 1, "Expected 3 numbers" ) ) },
+                }
+            };
+
+            match words.first() {
+                None => { },
+                Some( word ) if word.starts_with( '#' ) => { },
+                Some( &"TITLE" ) => { },
+                Some( &"LUT_1D_SIZE" ) => { return Err( invalid( i + 1, "1D LUTs are not supported" ) ); },
+                Some( &"LUT_3D_SIZE" ) => {
+                    size = words.get( 1 ).and_then( |word| word.parse::<u32>().ok() ).filter( |&size| size >= 2 )
+                        .ok_or_else( || invalid( i + 1, "Expected a size of at least 2" ) )?;
+                },
+                Some( &"DOMAIN_MIN" ) => { domain_min = parse_vec3( &words[1..] )?; },
+                Some( &"DOMAIN_MAX" ) => { domain_max = parse_vec3( &words[1..] )?; },
+                Some( &"LUT_3D_INPUT_RANGE" ) => {
+                    // (Resolve's way of giving the same domain for all channels)
+                    let range: Vec<f32> = words[1..].iter().filter_map( |word| word.parse::<f32>().ok() ).collect();
+                    let [min, max] = range[..] else { return Err( invalid( i + 1, "Expected 2 numbers" ) ); };
+                    ( domain_min, domain_max ) = ( glm::vec3( min, min, min ), glm::vec3( max, max, max ) );
+                },
+                Some( _ ) => { data.extend_from_slice( parse_vec3( &words )?.as_slice() ); },
+            }
+        }
+        if size == 0 {
+            return Err( invalid( 0, "Missing LUT_3D_SIZE" ) );
+        }
+        if data.len() != ( size * size * size * 3 ) as usize {
+            return Err( invalid( 0, &format!( "Expected {} entries, found {}", size * size * size, data.len() / 3 ) ) );
+        }
+
+        let mut texture = 0;
+        gl::GenTextures( 1, &mut texture );
+        gltracker::created( GlObject::Texture, &[texture] );
+        gl::BindTexture( gl::TEXTURE_3D, texture );
+        gl::PixelStorei( gl::UNPACK_ALIGNMENT, 1 );
+        gl::TexImage3D(
+            gl::TEXTURE_3D,
+            0,
+            gl::RGB32F as i32,
+            size as i32,
+            size as i32,
+            size as i32,
+            0,
+            gl::RGB,
+            gl::FLOAT,
+            data.as_ptr() as *const _
+        );
+        gl::TexParameteri( gl::TEXTURE_3D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32 );
+        gl::TexParameteri( gl::TEXTURE_3D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32 );
+        gl::TexParameteri( gl::TEXTURE_3D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32 );
+        gl::TexParameteri( gl::TEXTURE_3D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32 );
+        gl::TexParameteri( gl::TEXTURE_3D, gl::TEXTURE_WRAP_R, gl::CLAMP_TO_EDGE as i32 );
+        gl::BindTexture( gl::TEXTURE_3D, 0 );
+
+        Ok( ColorLut { texture, size, domain_min, domain_max } )
+    }
+
+    /**
+     * Binds the LUT for the LUT shader, and sends its uniforms.
+     * The shader must be active.
+     *
+     * @param shader The LUT shader.
+     * @param texture_unit The texture unit to bind the LUT to.
+     */
+    pub unsafe fn send_uniforms( &self, shader: &Shader, texture_unit: u32 ) {
+        gl::ActiveTexture( gl::TEXTURE0 + texture_unit );
+        gl::BindTexture( gl::TEXTURE_3D, self.texture );
+        gl::Uniform1i( shader.get_uniform_location( "lutTexture" ), texture_unit as i32 );
+        gl::Uniform1f( shader.get_uniform_location( "lutSize" ), self.size as f32 );
+        gl::Uniform3fv( shader.get_uniform_location( "lutDomainMin" ), 1, self.domain_min.as_ptr() );
+        gl::Uniform3fv( shader.get_uniform_location( "lutDomainMax" ), 1, self.domain_max.as_ptr() );
+    }
+
+    /**
+     * Deletes the LUT's texture, once it is done with.
+     */
+    pub unsafe fn delete( self ) {
+        gl::DeleteTextures( 1, &self.texture );
+        gltracker::deleted( GlObject::Texture, &[self.texture] );
+    }
+}
@@ -0,0 +1,200 @@
+use std::collections::VecDeque;
+
+use tracing::span::EnteredSpan;
+
+use crate::glapi;
+
+// How many frames the queries are read back after, so reading them doesn't wait for the GPU to catch up
+const QUERY_LATENCY: usize = 3;
+
+// How many frames each scope's time is averaged over
+const AVERAGE_FRAMES: usize = 60;
+
+/**
+ * Struct for the queries of one frame, which are reused once they have been read back.
+ */
+struct ProfilerFrame {
+    queries: Vec<u32>,
+    used: usize, // How many of the queries have been written this frame
+    scopes: Vec<( &'static str, usize, Option<usize> )>, // The name of each scope, and the indices of its start and end queries
+}
+
+/**
+ * Struct for profiling the GPU, by timing named scopes of each frame (such as tracing, denoising and post-processing) with timestamp queries.
+ * The timestamps are written by the GPU as it reaches them, so they measure the GPU's work between them rather than how long the CPU took to issue it.
+ *
+ * Each frame's queries are read back a few frames later, when they are done, and each scope keeps a rolling average of its times.
+ * Scopes may be nested, and may be left out of frames, such as the guides which are only rendered some frames.
+ * While profiling is off, the scopes aren't timed, but each is still a span of the logs (see the tracing crate), whose times are the CPU's.
+ */
+pub struct GpuProfiler {
+    enabled: bool,
+    frames: Vec<ProfilerFrame>,
+    current: usize,
+    averages: Vec<( &'static str, VecDeque<f64> )>, // The latest times of each scope in milliseconds, in the order the scopes were first seen
+    spans: Vec<( &'static str, EnteredSpan )>, // The spans of the scopes which are open
+}
+
+/**
+ * GpuProfiler functions.
+ */
+#[allow(dead_code)]
+impl GpuProfiler {
+    /**
+     * Creates a profiler.
+     * The queries are created as they are first needed, and it stays off where there are no timestamp queries (OpenGL ES).
+     *
+     * @param enabled If the scopes are timed from the start.
+     */
+    pub fn new( enabled: bool ) -> GpuProfiler {
+        GpuProfiler {
+            enabled: enabled && glapi::has_timer_queries(),
+            frames: ( 0..QUERY_LATENCY ).map( |_| ProfilerFrame { queries: Vec::new(), used: 0, scopes: Vec::new() } ).collect(),
+            current: 0,
+            averages: Vec::new(),
+            spans: Vec::new(),
+        }
+    }
+
+    /**
+     * Turns profiling on or off, which forgets the times measured so far.
+     *
+     * @param enabled If the scopes are timed.
+     */
+    pub fn set_enabled( &mut self, enabled: bool ) {
+        self.enabled = enabled && glapi::has_timer_queries();
+        for frame in self.frames.iter_mut() {
+            frame.used = 0;
+            frame.scopes.clear();
+        }
+        self.averages.clear();
+    }
+
+    /**
+     * Gets if the scopes are timed.
+     */
+    pub fn enabled( &self ) -> bool {
+        self.enabled
+    }
+
+    /**
+     * Starts the next frame, reading back the queries of the frame which last used its queries.
+     * Queries which aren't done yet are skipped, instead of waiting for them.
+     */
+    pub unsafe fn begin_frame( &mut self ) {
+        if !self.enabled {
+            return;
+        }
+        self.current = ( self.current + 1 ) % QUERY_LATENCY;
+
+        let frame = &mut self.frames[self.current];
+        frame.used = 0;
+        for ( name, start, end ) in frame.scopes.drain( .. ) {
+            let Some( end ) = end else { continue };
+            let ( mut start_available, mut end_available ) = ( 0, 0 );
+            gl::GetQueryObjectiv( frame.queries[start], gl::QUERY_RESULT_AVAILABLE, &mut start_available );
+            gl::GetQueryObjectiv( frame.queries[end], gl::QUERY_RESULT_AVAILABLE, &mut end_available );
+            if start_available == 0 || end_available == 0 {
+                continue;
+            }
+            let ( mut start_time, mut end_time ) = ( 0u64, 0u64 );
+            gl::GetQueryObjectui64v( frame.queries[start], gl::QUERY_RESULT, &mut start_time );
+            gl::GetQueryObjectui64v( frame.queries[end], gl::QUERY_RESULT, &mut end_time );
+            let milliseconds = end_time.saturating_sub( start_time ) as f64 / 1e6;
+
+            let index = match self.averages.iter().position( |( scope, _ )| *scope == name ) {
+                Some( index ) => { index },
+                None => {
+                    self.averages.push( ( name, VecDeque::with_capacity( AVERAGE_FRAMES ) ) );
+                    self.averages.len() - 1
+                },
+            };
+            let times = &mut self.averages[index].1;
+            if times.len() == AVERAGE_FRAMES {
+                times.pop_front();
+            }
+            times.push_back( milliseconds );
+        }
+    }
+
+    /**
+     * Writes a timestamp into the current frame, creating a query for it if the frame has none to spare.
+     *
+     * @return The index of the timestamp's query.
+     */
+    unsafe fn timestamp( &mut self ) -> usize {
+        let frame = &mut self.frames[self.current];
+        let index = frame.used;
+        frame.used += 1;
+        if index == frame.queries.len() {
+            let mut query = 0;
+            gl::GenQueries( 1, &mut query );
+            frame.queries.push( query );
+        }
+        gl::QueryCounter( frame.queries[index], gl::TIMESTAMP );
+        index
+    }
+
+    /**
+     * Starts timing a scope.
+     *
+     * @param name The name of the scope, such as "trace".
+     */
+    pub unsafe fn begin_scope( &mut self, name: &'static str ) {
+        self.spans.push( ( name, tracing::debug_span!( "scope", name ).entered() ) );
+        if !self.enabled {
+            return;
+        }
+        let start = self.timestamp();
+        self.frames[self.current].scopes.push( ( name, start, None ) );
+    }
+
+    /**
+     * Stops timing a scope, which must have been started this frame.
+     *
+     * @param name The name of the scope.
+     */
+    pub unsafe fn end_scope( &mut self, name: &'static str ) {
+        if let Some( open ) = self.spans.iter().rposition( |( scope, _ )| *scope == name ) {
+            self.spans.truncate( open );
+        }
+        if !self.enabled {
+            return;
+        }
+        let open = self.frames[self.current].scopes.iter().rposition( |( scope, _, end )| *scope == name && end.is_none() );
+        if let Some( open ) = open {
+            let end = self.timestamp();
+            self.frames[self.current].scopes[open].2 = Some( end );
+        }
+    }
+
+    /**
+     * Gets the average time of each scope, over the last frames it was in.
+     *
+     * @return The names of the scopes and their average times in milliseconds, in the order the scopes were first seen.
+     */
+    pub fn averages( &self ) -> Vec<( &'static str, f64 )> {
+        self.averages.iter()
+            .map( |( name, times )| ( *name, times.iter().sum::<f64>() / times.len().max( 1 ) as f64 ) )
+            .collect()
+    }
+
+    /**
+     * Formats the average times as a report, such as "trace 12.40 ms, denoise 1.10 ms".
+     */
+    pub fn report( &self ) -> String {
+        self.averages().iter()
+            .map( |( name, milliseconds )| format!( "{name} {milliseconds:.2} ms" ) )
+            .collect::<Vec<_>>()
+            .join( ", " )
+    }
+
+    /**
+     * Deletes the queries, once the profiler is done with.
+     */
+    pub unsafe fn delete( self ) {
+        for frame in self.frames.iter().filter( |frame| !frame.queries.is_empty() ) {
+            gl::DeleteQueries( frame.queries.len() as i32, frame.queries.as_ptr() );
+        }
+    }
+}
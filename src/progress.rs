@@ -0,0 +1,170 @@
+use std::sync::mpsc;
+use std::time::{ Duration, Instant };
+
+/**
+ * Struct for how far an offline render has come, which is reported to the render's listeners every frame.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Progress {
+    pub frames_done: u32, // Frames of the sequence which are finished, or 0 for a single image
+    pub frames: u32, // Frames in the sequence, or 1 for a single image
+    pub tiles_done: usize, // Tiles of the current frame which are finished, see tiles::TileScheduler
+    pub tiles: usize, // Tiles in a frame, or 1 if it is rendered whole
+    pub samples: u32, // Samples of the current tile
+    pub samples_per_tile: u32,
+    pub elapsed: Duration, // Time since the render started reporting
+    pub remaining: Option<Duration>, // Estimated time left, or None until there is anything to estimate it from
+}
+
+/**
+ * Progress functions.
+ */
+impl Progress {
+    /**
+     * Gets how many samples the whole render has, over all frames and tiles.
+     */
+    pub fn total_samples( &self ) -> u64 {
+        self.frames as u64 * self.tiles as u64 * self.samples_per_tile as u64
+    }
+
+    /**
+     * Gets how many samples of the whole render are done.
+     */
+    pub fn samples_done( &self ) -> u64 {
+        ( self.frames_done as u64 * self.tiles as u64 + self.tiles_done as u64 ) * self.samples_per_tile as u64 + self.samples as u64
+    }
+
+    /**
+     * Gets how much of the whole render is done.
+     *
+     * @return The part which is done, from 0 to 1.
+     */
+    pub fn fraction( &self ) -> f32 {
+        match self.total_samples() {
+            0 => { 1.0 },
+            total => { ( self.samples_done() as f64 / total as f64 ).min( 1.0 ) as f32 },
+        }
+    }
+}
+
+impl std::fmt::Display for Progress {
+    fn fmt( &self, f: &mut std::fmt::Formatter ) -> std::fmt::Result {
+        write!( f, "{:.1}%", self.fraction() * 100.0 )?;
+        if self.frames > 1 {
+            write!( f, ", frame {} of {}", self.frames_done + 1, self.frames )?;
+        }
+        if self.tiles > 1 {
+            write!( f, ", tile {} of {}", self.tiles_done + 1, self.tiles )?;
+        }
+        write!( f, ", {} of {} samples, {} elapsed", self.samples, self.samples_per_tile, format_duration( self.elapsed ) )?;
+        match self.remaining {
+            Some( remaining ) => { write!( f, ", {} left", format_duration( remaining ) ) },
+            None => { Ok( () ) },
+        }
+    }
+}
+
+/**
+ * Formats a duration to the second, as 1h 02m 03s, 2m 03s or 3s.
+ *
+ * @param duration The duration.
+ *
+ * @return The formatted duration.
+ */
+pub fn format_duration( duration: Duration ) -> String {
+    let seconds = duration.as_secs();
+    match ( seconds / 3600, seconds / 60 % 60, seconds % 60 ) {
+        ( 0, 0, s ) => { format!( "{s}s" ) },
+        ( 0, m, s ) => { format!( "{m}m {s:02}s" ) },
+        ( h, m, s ) => { format!( "{h}h {m:02}m {s:02}s" ) },
+    }
+}
+
+// A listener which is called with every report, see ProgressReporter::on_progress()
+pub type ProgressCallback = Box<dyn FnMut( &Progress )>;
+
+/**
+ * Struct for reporting the progress of an offline render to whoever listens, such as a progress bar in a terminal or a GUI.
+ * Listeners are either callbacks, which are called on the render's thread, or channels, which can be read from any thread.
+ *
+ * The time left is estimated from how fast samples have been rendered since the first report.
+ * Anything done before it (such as a checkpoint which was resumed from) counts towards the progress, but not the speed.
+ */
+pub struct ProgressReporter {
+    frames: u32,
+    tiles: usize,
+    samples_per_tile: u32,
+    started: Option<( Instant, u64 )>, // When the first report was, and how many samples were done by then
+    callbacks: Vec<ProgressCallback>,
+    senders: Vec<mpsc::Sender<Progress>>,
+}
+
+/**
+ * ProgressReporter functions.
+ */
+impl ProgressReporter {
+    /**
+     * Creates a reporter for a render.
+     *
+     * @param frames How many frames the render has, or 1 for a single image.
+     * @param tiles How many tiles each frame is rendered in, or 1 if they are rendered whole.
+     * @param samples_per_tile How many samples each tile gets.
+     */
+    pub fn new( frames: u32, tiles: usize, samples_per_tile: u32 ) -> ProgressReporter {
+        ProgressReporter { frames, tiles, samples_per_tile, started: None, callbacks: Vec::new(), senders: Vec::new() }
+    }
+
+    /**
+     * Adds a callback, which is called with every report.
+     *
+     * @param callback The callback.
+     */
+    pub fn on_progress( &mut self, callback: impl FnMut( &Progress ) + 'static ) {
+        self.callbacks.push( Box::new( callback ) );
+    }
+
+    /**
+     * Adds a channel, which is sent every report.
+     * (Channels whose receivers are dropped are removed at the next report)
+     *
+     * @return The receiving end of the channel.
+     */
+    #[allow(dead_code)]
+    pub fn channel( &mut self ) -> mpsc::Receiver<Progress> {
+        let ( sender, receiver ) = mpsc::channel();
+        self.senders.push( sender );
+        receiver
+    }
+
+    /**
+     * Reports how far the render has come.
+     *
+     * @param frames_done Frames which are finished.
+     * @param tiles_done Tiles of the current frame which are finished.
+     * @param samples Samples of the current tile.
+     */
+    pub fn report( &mut self, frames_done: u32, tiles_done: usize, samples: u32 ) {
+        let mut progress = Progress {
+            frames_done,
+            frames: self.frames,
+            tiles_done,
+            tiles: self.tiles,
+            samples,
+            samples_per_tile: self.samples_per_tile,
+            elapsed: Duration::ZERO,
+            remaining: None,
+        };
+        let ( started, samples_at_start ) = *self.started.get_or_insert( ( Instant::now(), progress.samples_done() ) );
+        progress.elapsed = started.elapsed();
+        let samples_since_start = progress.samples_done().saturating_sub( samples_at_start );
+        if samples_since_start > 0 {
+            let samples_left = progress.total_samples().saturating_sub( progress.samples_done() );
+            progress.remaining = Some( progress.elapsed.mul_f64( samples_left as f64 / samples_since_start as f64 ) );
+        }
+
+        for callback in &mut self.callbacks {
+            callback( &progress );
+        }
+        self.senders.retain( |sender| sender.send( progress ).is_ok() );
+    }
+}
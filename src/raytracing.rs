@@ -1,12 +1,18 @@
+use serde::{Serialize, Deserialize};
+
 use crate::shader::Shader;
+use crate::integrators::{Integrator, PathTracer, DirectOnly, Bidirectional, AmbientOcclusion, Debug, DebugView};
 
 /**
  * Vec3 for GLSL, put after normal floats.
  * Since GLSL std140/430 causes misalignment with vec3s, I had to make this abomination...
  * https://www.khronos.org/opengl/wiki/Interface_Block_(GLSL)#Memory_layout
  * https://stackoverflow.com/questions/38172696/should-i-ever-use-a-vec3-inside-of-a-uniform-buffer-or-shader-storage-buffer-o
+ * It is saved through glm::Vec3, as [x, y, z] without the padding.
  */
 #[repr(C, align(16))]
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(from = "glm::Vec3", into = "glm::Vec3")]
 pub struct Vec3a16 {
     pub x: f32,
     pub y: f32,
@@ -33,46 +39,490 @@ impl From<Vec3a16> for glm::Vec3 {
     }
 }
 
+/**
+ * Struct for a ray, used for raycasting on the CPU.
+ */
+pub struct RTRay {
+    pub origin: glm::Vec3,
+    pub dir: glm::Vec3,
+}
+
+// Same epsilon as the shader, used for triangle intersections
+const EPSILON: f32 = 0.001;
+
+/**
+ * Gets the distance to the closest intersection between a ray and the scene, mirroring CalculateRayCollision() in the shader.
+ *
+ * @param ray The ray. Its direction must be normalized.
+ * @param spheres The spheres in the scene.
+ * @param triangles The triangles in the scene.
+ *
+ * @return The distance to the closest intersection, or None if nothing was hit.
+ */
+pub fn raycast( ray: &RTRay, spheres: &[RTSphere], triangles: &[RTTriangle] ) -> Option<f32> {
+    spheres.iter().filter_map( |sphere| sphere.intersect( ray ) )
+        .chain( triangles.iter().filter_map( |triangle| triangle.intersect( ray ) ) )
+        .min_by( |a, b| a.total_cmp( b ) )
+}
+
+/**
+ * Gets the bounds of the scene, which are the smallest box containing every object.
+ *
+ * @param spheres The spheres in the scene.
+ * @param meshes The meshes in the scene.
+ *
+ * @return The bottom left and top right corners of the bounds.
+ */
+pub fn scene_bounds( spheres: &[RTSphere], meshes: &[RTMeshInfo] ) -> ( glm::Vec3, glm::Vec3 ) {
+    let ( mut bounds_min, mut bounds_max ) = ( glm::vec3( f32::MAX, f32::MAX, f32::MAX ), glm::vec3( f32::MIN, f32::MIN, f32::MIN ) );
+    for sphere in spheres {
+        let ( center, radius ): ( glm::Vec3, glm::Vec3 ) = ( sphere.center.into(), glm::vec3( sphere.radius, sphere.radius, sphere.radius ) );
+        bounds_min = glm::min2( &bounds_min, &( center - radius ) );
+        bounds_max = glm::max2( &bounds_max, &( center + radius ) );
+    }
+    for mesh in meshes {
+        bounds_min = glm::min2( &bounds_min, &mesh.boundingbox_min.into() );
+        bounds_max = glm::max2( &bounds_max, &mesh.boundingbox_max.into() );
+    }
+    ( bounds_min, bounds_max )
+}
+
+/**
+ * Gets an element of the Halton sequence, which is the radical inverse of its index in a given base.
+ * https://en.wikipedia.org/wiki/Halton_sequence
+ *
+ * @param index The index of the element. Index 0 gives 0, so sequences usually start at 1.
+ * @param base The base, which should be prime (and different for each dimension).
+ *
+ * @return The element, [0, 1).
+ */
+pub fn halton( mut index: u32, base: u32 ) -> f32 {
+    let ( mut result, mut fraction ) = ( 0.0, 1.0 );
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * ( index % base ) as f32;
+        index /= base;
+    }
+    result
+}
+
+/**
+ * Enum for sequences which generate per-frame sub-pixel jitter.
+ * Low-discrepancy sequences cover the pixel evenly over a few frames, which white noise does not.
+ */
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum JitterSequence {
+    None,       // No jitter, rays go through the pixel center
+    Halton23,   // Halton sequence with bases 2 and 3, cycling every `period` frames
+    R2,         // Roberts' R2 sequence, http://extremelearning.com.au/unreasonable-effectiveness-of-quasirandom-sequences/
+}
+
+/**
+ * JitterSequence functions.
+ */
+impl JitterSequence {
+    // How many frames the Halton sequence runs for before repeating
+    const HALTON_PERIOD: u32 = 16;
+
+    /**
+     * Gets the jitter for a frame.
+     *
+     * @param frame_index The index of the frame.
+     * @return The offset from the pixel center, in pixels, [-0.5, 0.5) in both axes.
+     */
+    pub fn sample( &self, frame_index: u32 ) -> glm::Vec2 {
+        match self {
+            JitterSequence::None => { glm::zero() },
+            JitterSequence::Halton23 => {
+                let i = frame_index % Self::HALTON_PERIOD + 1;
+                glm::vec2( halton( i, 2 ) - 0.5, halton( i, 3 ) - 0.5 )
+            },
+            JitterSequence::R2 => {
+                // 1/g and 1/g^2, where g is the plastic number
+                let ( a1, a2 ) = ( 0.754_877_7_f64, 0.569_840_3_f64 );
+                let n = frame_index as f64 + 1.0;
+                glm::vec2( ( 0.5 + a1 * n ).fract() as f32 - 0.5, ( 0.5 + a2 * n ).fract() as f32 - 0.5 )
+            },
+        }
+    }
+}
+
+/**
+ * Struct for the limits of how many times paths may bounce.
+ * Each lobe has its own limit, so bounces can be spent where they matter, and the total is capped on top of that.
+ */
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct RTBounces {
+    pub total: u32,
+    pub diffuse: u32,
+    pub glossy: u32, // Bounces off the specular lobe, however smooth it is
+    pub transmission: u32, // Bounces through surfaces (No materials transmit light yet, so this has no effect)
+}
+
+/**
+ * Struct for a homogeneous participating medium, such as mist, which fills the whole scene.
+ * Rays travelling through it are scattered at random distances, which are shorter the denser it is.
+ */
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct RTMedium {
+    pub density: f32, // The chance of scattering per unit of distance (extinction coefficient)
+    pub albedo: glm::Vec3, // The fraction of light which is scattered rather than absorbed when scattering, per color channel
+    pub anisotropy: f32, // The Henyey-Greenstein g, from -1 (scatters backwards) through 0 (evenly in all directions) to 1 (forwards)
+}
+
+/**
+ * RTMedium functions.
+ */
+impl RTMedium {
+    /**
+     * Sends the RTMedium's data to a uniform variable in a given shader.
+     * The shader must be active.
+     *
+     * @param shader The shader.
+     * @param uniform_name The name of the uniform variable in the shader.
+     */
+    pub unsafe fn send_uniform( &self, shader: &Shader, uniform_name: &str ) {
+        gl::Uniform1f( shader.get_uniform_location( format!("{uniform_name}.density").as_str() ), self.density);
+        gl::Uniform3fv( shader.get_uniform_location( format!("{uniform_name}.albedo").as_str() ), 1, self.albedo.as_ptr());
+        gl::Uniform1f( shader.get_uniform_location( format!("{uniform_name}.anisotropy").as_str() ), self.anisotropy);
+    }
+}
+
+/**
+ * Struct for the sun, which is a directional light infinitely far away.
+ * It lights the scene from outside the fog, so its light only passes through the fog within the scene's bounds.
+ */
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct RTSun {
+    pub direction: glm::Vec3, // The direction towards the sun
+    pub color: glm::Vec3, // The light arriving from the sun, on a surface facing it
+    pub god_ray_steps: u32, // Steps taken along camera rays to add the sun's light scattered by the fog (god rays), or 0 to leave them to path tracing
+                            // The steps only cover the fog within the scene's bounds
+}
+
+/**
+ * RTSun functions.
+ */
+impl RTSun {
+    /**
+     * Sends the RTSun's data to a uniform variable in a given shader.
+     * The shader must be active.
+     *
+     * @param shader The shader.
+     * @param uniform_name The name of the uniform variable in the shader.
+     */
+    pub unsafe fn send_uniform( &self, shader: &Shader, uniform_name: &str ) {
+        gl::Uniform3fv( shader.get_uniform_location( format!("{uniform_name}.direction").as_str() ), 1, self.direction.normalize().as_ptr());
+        gl::Uniform3fv( shader.get_uniform_location( format!("{uniform_name}.color").as_str() ), 1, self.color.as_ptr());
+        gl::Uniform1ui( shader.get_uniform_location( format!("{uniform_name}.godRaySteps").as_str() ), self.god_ray_steps);
+    }
+}
+
+/**
+ * Struct for a crop region, which only the pixels within are rendered in, for looking at one part of a heavy frame.
+ * The corners are fractions of the image, from (0, 0) in the bottom left to (1, 1) in the top right, so the region keeps up with resizes.
+ */
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct RTCrop {
+    pub min: glm::Vec2,
+    pub max: glm::Vec2,
+}
+
+/**
+ * RTCrop functions.
+ */
+impl RTCrop {
+    /**
+     * Creates a crop region between two opposite corners, which are dragged between on the screen.
+     *
+     * @param start The corner where the drag started, in pixels with (0, 0) in the top left corner like window coordinates.
+     * @param end The corner where the drag ended, in the same coordinates.
+     * @param screen_size The size of the screen, in pixels.
+     *
+     * @return The region, clamped to the screen.
+     */
+    pub fn from_drag( start: glm::Vec2, end: glm::Vec2, screen_size: glm::Vec2 ) -> RTCrop {
+        let to_uv = |pixel: glm::Vec2| glm::clamp( &glm::vec2( pixel.x / screen_size.x, 1.0 - pixel.y / screen_size.y ), 0.0, 1.0 );
+        let ( a, b ) = ( to_uv( start ), to_uv( end ) );
+        RTCrop { min: glm::min2( &a, &b ), max: glm::max2( &a, &b ) }
+    }
+
+    /**
+     * Sends the RTCrop's data to a uniform variable in a given shader.
+     * The shader must be active.
+     *
+     * @param shader The shader.
+     * @param uniform_name The name of the uniform variable in the shader.
+     */
+    pub unsafe fn send_uniform( &self, shader: &Shader, uniform_name: &str ) {
+        gl::Uniform2fv( shader.get_uniform_location( format!("{uniform_name}.min").as_str() ), 1, self.min.as_ptr());
+        gl::Uniform2fv( shader.get_uniform_location( format!("{uniform_name}.max").as_str() ), 1, self.max.as_ptr());
+    }
+}
+
 /**
  * Struct for storing raytracing settings.
  */
 #[repr(C, align(16))]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct RTSettings {
-    pub max_bounces: u32,
+    pub max_bounces: RTBounces,
     pub rays_per_frag: u32,
     pub diverge_strength: f32,
+    pub sampler: RTSampler,
+    pub roulette: RTRoulette,
+    pub roulette_start: u32, // The bounce from which Russian roulette may terminate paths
+    pub integrator: RTIntegrator,
+    pub next_event_estimation: bool, // Sample emissive spheres directly at each diffuse bounce, using shadow rays
+    pub restir: bool, // Light the first surface with ReSTIR, which reuses light samples between frames and neighbouring pixels
+    pub path_guiding: bool, // Steer diffuse bounces towards where light was found before, using a PathGuide
+    pub caustics: bool, // Find caustics with a photon map, gathered at the first diffuse surface
+    pub caustic_radius: f32, // Radius around a surface which caustic photons are gathered from
+    pub seed: u32, // Seed of all random numbers, so the same seed and inputs give the same render
+                   // (Except caustics, as photons are summed in whichever order they were stored, which changes the rounding)
+    pub direct_clamp: f32, // Limit for the brightness of light reaching the camera after at most one bounce, or 0 for no limit
+    pub indirect_clamp: f32, // Limit for the brightness of light reaching the camera after more bounces, or 0 for no limit
+    pub t_max: f32, // Rays ignore anything further away than this, and go into the environment instead
+    pub clip_to_bounds: bool, // Rays which miss the scene's bounds skip testing the objects, see scene_bounds()
+    pub regularization: f32, // How much smooth surfaces are roughened after rough bounces, from 0 (off) to 1
+                             // This is biased, but gets rid of most of the noise of caustics seen through diffuse bounces
+    pub fog: Option<RTMedium>, // Medium filling the scene, which only paths from the camera see (not ReSTIR, light tracing or caustics)
+                               // Rays which miss everything scatter before t_max, so the environment is hidden unless t_max is lowered
+    pub sun: Option<RTSun>, // Directional light, which only paths from the camera see like the fog
+    pub crop: Option<RTCrop>, // Region of the image which is rendered, where the pixels outside keep what they last had
 }
 
 /**
  * Functions for dealing with raytrace settings.
  */
 impl RTSettings {
+    /**
+     * Creates the settings which the engine starts with.
+     */
+    pub fn new() -> RTSettings {
+        RTSettings {
+            max_bounces: RTBounces {
+                total: 3,
+                diffuse: 3,
+                glossy: 3,
+                transmission: 3,
+            },
+            rays_per_frag: 8,
+            diverge_strength: 0.07,
+            sampler: RTSampler::Sobol,
+            roulette: RTRoulette::Throughput,
+            roulette_start: 0,
+            integrator: RTIntegrator::PathTrace,
+            next_event_estimation: true,
+            restir: false,
+            path_guiding: false,
+            caustics: false,
+            caustic_radius: 0.05,
+            seed: 0,
+            direct_clamp: 0.0,
+            indirect_clamp: 10.0,
+            t_max: f32::MAX,
+            clip_to_bounds: true,
+            regularization: 0.0,
+            fog: None,
+            sun: None,
+            crop: None,
+        }
+    }
+
     /**
      * Sends the RTSettings' data to a uniform variable in a given shader.
      * 
      * @param shader The shader.
      * @param uniform_name The name of the uniform variable in the shader.
      */
-    pub unsafe fn send_uniform( self, shader: &Shader, uniform_name: &str ) {
+    pub unsafe fn send_uniform( &self, shader: &Shader, uniform_name: &str ) {
         // Temporarily switch to the shader we're setting uniforms for
         let mut prev_pid: gl::types::GLint = 0;
         gl::GetIntegerv(gl::CURRENT_PROGRAM,&mut prev_pid);
         shader.activate();
         
         // Set uniforms
-        gl::Uniform1ui( shader.get_uniform_location( format!("{uniform_name}.maxBounces").as_str() ), self.max_bounces);
+        gl::Uniform1ui( shader.get_uniform_location( format!("{uniform_name}.maxBounces").as_str() ), self.max_bounces.total);
+        gl::Uniform1ui( shader.get_uniform_location( format!("{uniform_name}.maxDiffuseBounces").as_str() ), self.max_bounces.diffuse);
+        gl::Uniform1ui( shader.get_uniform_location( format!("{uniform_name}.maxGlossyBounces").as_str() ), self.max_bounces.glossy);
+        gl::Uniform1ui( shader.get_uniform_location( format!("{uniform_name}.maxTransmissionBounces").as_str() ), self.max_bounces.transmission);
         gl::Uniform1ui( shader.get_uniform_location( format!("{uniform_name}.raysPerFrag").as_str() ), self.rays_per_frag);
         gl::Uniform1f( shader.get_uniform_location( format!("{uniform_name}.divergeStrength").as_str() ), self.diverge_strength);
+        gl::Uniform1ui( shader.get_uniform_location( format!("{uniform_name}.sampler").as_str() ), self.sampler.id());
+        gl::Uniform1ui( shader.get_uniform_location( format!("{uniform_name}.roulette").as_str() ), self.roulette.id());
+        gl::Uniform1ui( shader.get_uniform_location( format!("{uniform_name}.rouletteStart").as_str() ), self.roulette_start);
+        gl::Uniform1ui( shader.get_uniform_location( format!("{uniform_name}.integrator").as_str() ), self.integrator.id());
+        gl::Uniform1ui( shader.get_uniform_location( format!("{uniform_name}.nextEventEstimation").as_str() ), self.next_event_estimation as u32);
+        gl::Uniform1ui( shader.get_uniform_location( format!("{uniform_name}.restir").as_str() ), self.restir as u32);
+        gl::Uniform1ui( shader.get_uniform_location( format!("{uniform_name}.pathGuiding").as_str() ), self.path_guiding as u32);
+        gl::Uniform1ui( shader.get_uniform_location( format!("{uniform_name}.caustics").as_str() ), self.caustics as u32);
+        gl::Uniform1f( shader.get_uniform_location( format!("{uniform_name}.causticRadius").as_str() ), self.caustic_radius);
+        gl::Uniform1ui( shader.get_uniform_location( format!("{uniform_name}.seed").as_str() ), self.seed);
+        gl::Uniform1f( shader.get_uniform_location( format!("{uniform_name}.directClamp").as_str() ), self.direct_clamp);
+        gl::Uniform1f( shader.get_uniform_location( format!("{uniform_name}.indirectClamp").as_str() ), self.indirect_clamp);
+        gl::Uniform1f( shader.get_uniform_location( format!("{uniform_name}.tMax").as_str() ), self.t_max);
+        gl::Uniform1ui( shader.get_uniform_location( format!("{uniform_name}.clipToBounds").as_str() ), self.clip_to_bounds as u32);
+        gl::Uniform1f( shader.get_uniform_location( format!("{uniform_name}.regularization").as_str() ), self.regularization);
+        gl::Uniform1ui( shader.get_uniform_location( format!("{uniform_name}.fog").as_str() ), self.fog.is_some() as u32);
+        if let Some( fog ) = self.fog {
+            fog.send_uniform( shader, format!("{uniform_name}.fogMedium").as_str() );
+        }
+        gl::Uniform1ui( shader.get_uniform_location( format!("{uniform_name}.sun").as_str() ), self.sun.is_some() as u32);
+        if let Some( sun ) = self.sun {
+            sun.send_uniform( shader, format!("{uniform_name}.sunLight").as_str() );
+        }
+        gl::Uniform1ui( shader.get_uniform_location( format!("{uniform_name}.crop").as_str() ), self.crop.is_some() as u32);
+        if let Some( crop ) = self.crop {
+            crop.send_uniform( shader, format!("{uniform_name}.cropRegion").as_str() );
+        }
+        if let RTRoulette::Constant{ survival } = self.roulette {
+            gl::Uniform1f( shader.get_uniform_location( format!("{uniform_name}.rouletteSurvival").as_str() ), survival);
+        }
         
         // Switch back and return
         gl::UseProgram( prev_pid as u32 );
     }
 }
 
+/**
+ * Enum for the different ways random numbers are sampled when tracing paths.
+ */
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum RTSampler {
+    Random,     // Uniform random numbers (white noise), stratified over the rays of a frag for the pixel and lens
+    BlueNoise,  // Blue noise for the first few dimensions, which spreads error evenly over neighbouring pixels
+    Sobol,      // Owen-scrambled Sobol sequence, which converges faster over many samples
+}
+
+/**
+ * RTSampler functions.
+ */
+impl RTSampler {
+    /**
+     * Gets the id of the sampler, as used by the shader.
+     */
+    pub fn id( &self ) -> u32 {
+        match self {
+            RTSampler::Random       => { 0 },
+            RTSampler::BlueNoise    => { 1 },
+            RTSampler::Sobol        => { 2 },
+        }
+    }
+}
+
+/**
+ * Enum for selecting an integrator, see integrators::Integrator.
+ */
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum RTIntegrator {
+    PathTrace,
+    DirectLight,
+    Bidirectional,
+    AmbientOcclusion( AmbientOcclusion ),
+    Debug( Debug ),
+}
+
+/**
+ * RTIntegrator functions.
+ */
+impl RTIntegrator {
+    /**
+     * Gets every integrator, with the default parameters of those which have them.
+     */
+    pub fn all() -> [RTIntegrator; 5] {
+        [
+            RTIntegrator::PathTrace,
+            RTIntegrator::DirectLight,
+            RTIntegrator::Bidirectional,
+            RTIntegrator::AmbientOcclusion( AmbientOcclusion { distance: 1.0, falloff: 1.0 } ),
+            RTIntegrator::Debug( Debug { view: DebugView::Normal } ),
+        ]
+    }
+
+    /**
+     * Gets the id of the integrator, as used by the shader.
+     */
+    pub fn id( &self ) -> u32 {
+        match self {
+            RTIntegrator::PathTrace            => { 0 },
+            RTIntegrator::DirectLight          => { 1 },
+            RTIntegrator::Bidirectional        => { 2 },
+            RTIntegrator::AmbientOcclusion(..) => { 3 },
+            RTIntegrator::Debug(..)            => { 4 },
+        }
+    }
+
+    /**
+     * Gets the integrator itself.
+     */
+    pub fn get( &self ) -> &dyn Integrator {
+        match self {
+            RTIntegrator::PathTrace                => { &PathTracer },
+            RTIntegrator::DirectLight              => { &DirectOnly },
+            RTIntegrator::Bidirectional            => { &Bidirectional },
+            RTIntegrator::AmbientOcclusion( ao )   => { ao },
+            RTIntegrator::Debug( debug )           => { debug },
+        }
+    }
+
+    /**
+     * Gets the integrator after this one in all(), for cycling through them.
+     */
+    pub fn next( &self ) -> RTIntegrator {
+        let all = RTIntegrator::all();
+        all[( self.id() as usize + 1 ) % all.len()]
+    }
+
+    /**
+     * Gets the defines of every integrator, which the shader must be built with.
+     */
+    pub fn shader_defines() -> Vec<( &'static str, u32 )> {
+        let mut defines = Vec::new();
+        for integrator in RTIntegrator::all() {
+            defines.push( ( integrator.get().define(), integrator.id() ) );
+            defines.extend( integrator.get().defines() );
+        }
+        defines
+    }
+}
+
+/**
+ * Enum for the strategies of Russian roulette, which randomly terminates paths that are unlikely to contribute much.
+ * Surviving paths are weighted up by the inverse of their survival probability, so the result stays unbiased.
+ */
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum RTRoulette {
+    Off,                            // Paths always continue until they reach max_bounces
+    Throughput,                     // Paths survive with probability equal to their brightest color channel
+    Constant { survival: f32 },     // Paths survive with probability `survival`
+}
+
+/**
+ * RTRoulette functions.
+ */
+impl RTRoulette {
+    /**
+     * Gets the id of the strategy, as used by the shader.
+     */
+    pub fn id( &self ) -> u32 {
+        match self {
+            RTRoulette::Off             => { 0 },
+            RTRoulette::Throughput      => { 1 },
+            RTRoulette::Constant{..}    => { 2 },
+        }
+    }
+}
+
 /**
  * Struct for a raytracing material.
  */
 #[repr(C, align(16))]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct RTMaterial {
     pub color: glm::Vec4,
     pub emission_color: glm::Vec4,
@@ -96,6 +546,7 @@ impl RTMaterial {
  * Struct for a raytraced sphere.
  */
 #[repr(C, align(16))]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct RTSphere {
     pub radius: f32,
     pub center: Vec3a16,
@@ -112,10 +563,33 @@ impl RTSphere {
     pub fn new() -> RTSphere {
         RTSphere { radius: 0.0, center: glm::vec3(0.0, 0.0, 0.0).into(), material: RTMaterial::new() }
     }
+
+    /**
+     * Checks for an intersection between a ray and the sphere.
+     *
+     * @param ray The ray.
+     * @return The distance to the intersection, or None if the ray misses (or the sphere is behind it).
+     */
+    pub fn intersect( &self, ray: &RTRay ) -> Option<f32> {
+        let offset_origin = ray.origin - glm::Vec3::from( self.center );
+
+        // Solve for distance with a quadratic equation
+        let a = ray.dir.dot( &ray.dir );
+        let b = 2.0 * offset_origin.dot( &ray.dir );
+        let c = offset_origin.dot( &offset_origin ) - self.radius * self.radius;
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            return None
+        }
+
+        let dist = ( -b - discriminant.sqrt() ) / ( 2.0 * a );
+        if dist >= 0.0 { Some( dist ) } else { None }
+    }
 }
 
 // RTTriangle
 #[repr(C, align(16))]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct RTTriangle {
     pub p0: Vec3a16,
     pub p1: Vec3a16,
@@ -129,6 +603,7 @@ pub struct RTTriangle {
 /**
  * RTTriangle functions.
  */
+#[allow(dead_code)]
 impl RTTriangle {
     /**
      * Creates a new, blank, RTTriangle.
@@ -144,12 +619,141 @@ impl RTTriangle {
             material: RTMaterial::new(),
         }
     }
+
+    /**
+     * Checks for an intersection between a ray and the triangle, using the Möller-Trumbore algorithm.
+     * Like the shader, rays pass through triangles which are oriented counter-clockwise relative to them.
+     *
+     * @param ray The ray.
+     * @return The distance to the intersection, or None if the ray misses.
+     */
+    pub fn intersect( &self, ray: &RTRay ) -> Option<f32> {
+        self.intersect_barycentric( ray ).map( |( dist, _ )| dist )
+    }
+
+    /**
+     * Checks for an intersection between a ray and the triangle, like intersect(), also telling where on the triangle it is.
+     *
+     * @param ray The ray.
+     * @return The distance to the intersection and its barycentric coordinates (the weights of p1 and p2), or None if the ray misses.
+     */
+    pub fn intersect_barycentric( &self, ray: &RTRay ) -> Option<( f32, glm::Vec2 )> {
+        let ( p0, p1, p2 ) = ( glm::Vec3::from( self.p0 ), glm::Vec3::from( self.p1 ), glm::Vec3::from( self.p2 ) );
+        let ( v0, v1 ) = ( p1 - p0, p2 - p0 );
+        let v0v1c = v0.cross( &v1 );
+
+        // Cull back-faces (which also handles parallel rays)
+        let determinant = -ray.dir.dot( &v0v1c );
+        if determinant < EPSILON {
+            return None
+        }
+        let inv_determinant = 1.0 / determinant;
+
+        // Calculate distance to triangle and barycentric coordinates
+        let v3 = ray.origin - p0;
+        let v3dirc = v3.cross( &ray.dir );
+        let dist = v3.dot( &v0v1c ) * inv_determinant;
+        let u = v1.dot( &v3dirc ) * inv_determinant;
+        let v = -v0.dot( &v3dirc ) * inv_determinant;
+
+        if dist >= 0.0 && u >= 0.0 && v >= 0.0 && u + v <= 1.0 { Some( ( dist, glm::vec2( u, v ) ) ) } else { None }
+    }
+
+    /**
+     * Gets the shading normal at a point on the triangle, interpolated between the normals of its corners like the shader.
+     *
+     * @param barycentric The barycentric coordinates of the point, see intersect_barycentric().
+     */
+    pub fn normal_at( &self, barycentric: glm::Vec2 ) -> glm::Vec3 {
+        let ( u, v ) = ( barycentric.x, barycentric.y );
+        ( glm::Vec3::from( self.normal0 ) * ( 1.0 - u - v ) + glm::Vec3::from( self.normal1 ) * u + glm::Vec3::from( self.normal2 ) * v ).normalize()
+    }
+}
+
+/**
+ * Packs two values between 0 and 1 into 16 bits each, like GLSL's packUnorm2x16().
+ */
+fn pack_unorm_2x16( x: f32, y: f32 ) -> u32 {
+    let quantize = |v: f32| ( v.clamp( 0.0, 1.0 ) * 65535.0 ).round() as u32;
+    quantize( x ) | ( quantize( y ) << 16 )
+}
+
+/**
+ * Encodes a unit vector as a point on an octahedron, unfolded onto a square between 0 and 1,
+ * which a pair of 16-bit values holds far more evenly (and precisely) than the vector's components would.
+ *
+ * @param n The vector.
+ * @return The vector packed into 2x16 bits.
+ */
+fn encode_octahedral( n: glm::Vec3 ) -> u32 {
+    let n = n / ( n.x.abs() + n.y.abs() + n.z.abs() ).max( EPSILON );
+    let sign = |v: f32| if v >= 0.0 { 1.0 } else { -1.0 };
+    let ( x, y ) = if n.z >= 0.0 {
+        ( n.x, n.y )
+    } else {
+        // (The lower half of the octahedron is folded over the corners of the upper half)
+        ( ( 1.0 - n.y.abs() ) * sign( n.x ), ( 1.0 - n.x.abs() ) * sign( n.y ) )
+    };
+    pack_unorm_2x16( x * 0.5 + 0.5, y * 0.5 + 0.5 )
+}
+
+// RTQuantizedTriangle
+/**
+ * Struct for a triangle as it is stored on the GPU, which takes 96 bytes instead of an RTTriangle's 160.
+ * The positions are quantized to 16 bits along each axis of their mesh's bounding box,
+ * and the normals are octahedral-encoded into 16-bit pairs, which the shader decodes when it loads the triangle.
+ */
+#[repr(C, align(16))]
+#[derive(Clone, PartialEq, Debug)]
+pub struct RTQuantizedTriangle {
+    pub positions: [u32; 5], // The corners' 9 coordinates, two to a u32 (the last half is unused)
+    pub normals: [u32; 3],
+    pub material: RTMaterial,
+}
+
+/**
+ * RTQuantizedTriangle functions.
+ */
+impl RTQuantizedTriangle {
+    /**
+     * Quantizes a triangle relative to the bounds of the mesh it is in.
+     *
+     * @param triangle The triangle.
+     * @param mesh The mesh, whose bounding box must hold the triangle.
+     */
+    pub fn quantize( triangle: &RTTriangle, mesh: &RTMeshInfo ) -> RTQuantizedTriangle {
+        let ( min, max ) = ( glm::Vec3::from( mesh.boundingbox_min ), glm::Vec3::from( mesh.boundingbox_max ) );
+        // (Flat meshes have no extent along some axis, where every position is at the minimum)
+        let extent = ( max - min ).map( |v| v.max( f32::MIN_POSITIVE ) );
+        let coordinates: Vec<f32> = [triangle.p0, triangle.p1, triangle.p2].iter()
+            .flat_map( |p| {
+                let relative = ( glm::Vec3::from( *p ) - min ).component_div( &extent );
+                [relative.x, relative.y, relative.z]
+            } )
+            .chain( std::iter::once( 0.0 ) )
+            .collect();
+
+        let mut positions = [0; 5];
+        for ( i, pair ) in coordinates.chunks( 2 ).enumerate() {
+            positions[i] = pack_unorm_2x16( pair[0], pair[1] );
+        }
+        RTQuantizedTriangle {
+            positions,
+            normals: [
+                encode_octahedral( triangle.normal0.into() ),
+                encode_octahedral( triangle.normal1.into() ),
+                encode_octahedral( triangle.normal2.into() ),
+            ],
+            material: triangle.material.clone(),
+        }
+    }
 }
 
 /**
  * Struct for holding mesh information.
  */
 #[repr(C, align(16))]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct RTMeshInfo {
     pub start_index: u32,
     pub count: u32,
@@ -157,29 +761,295 @@ pub struct RTMeshInfo {
     pub boundingbox_max: Vec3a16,
 }
 
+/**
+ * Enum for the different ways the raytracing camera can project rays into the scene.
+ */
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum RTProjection {
+    Perspective,                    // Rays spread out from the camera's position, using its fov
+    Orthographic { height: f32 },   // Rays are parallel, fired from a plane `height` units tall
+    Fisheye { fov: f32 },           // Equidistant fisheye, `fov` degrees across the circle which fits the screen's height, with blank corners
+    Equirectangular,                // Full 360x180 degree panorama
+}
+
+/**
+ * RTProjection functions.
+ */
+impl RTProjection {
+    /**
+     * Gets the id of the projection, as used by the shader.
+     */
+    pub fn id( &self ) -> u32 {
+        match self {
+            RTProjection::Perspective       => { 0 },
+            RTProjection::Orthographic{..}  => { 1 },
+            RTProjection::Fisheye{..}       => { 2 },
+            RTProjection::Equirectangular   => { 3 },
+        }
+    }
+}
+
+/**
+ * Enum for stereo rendering, where each half of the screen shows one eye.
+ * The eyes are offset along the camera's right by half the interpupillary distance (ipd) each, with the left eye on the left/top.
+ */
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum RTStereo {
+    Mono,
+    SideBySide { ipd: f32 },
+    OverUnder { ipd: f32 },
+}
+
+/**
+ * RTStereo functions.
+ */
+impl RTStereo {
+    /**
+     * Gets the id of the stereo mode, as used by the shader.
+     */
+    pub fn id( &self ) -> u32 {
+        match self {
+            RTStereo::Mono              => { 0 },
+            RTStereo::SideBySide{..}    => { 1 },
+            RTStereo::OverUnder{..}     => { 2 },
+        }
+    }
+
+    /**
+     * Gets the interpupillary distance, which is 0 for mono rendering.
+     */
+    pub fn ipd( &self ) -> f32 {
+        match self {
+            RTStereo::Mono => { 0.0 },
+            RTStereo::SideBySide { ipd } | RTStereo::OverUnder { ipd } => { *ipd },
+        }
+    }
+}
+
 /**
  * Struct for a raytracing camera.
+ * The lens distortion is given as the radial coefficients (k1, k2) of the Brown-Conrady model, where positive values give pincushion distortion and negative values give barrel distortion.
+ * Chromatic aberration magnifies the red and blue channels by (1 + chromatic_aberration) and (1 - chromatic_aberration), respectively.
+ * The pixel jitter offsets all rays fired from the camera, in pixels. It's meant to change every frame, see JitterSequence.
+ * For motion blur, shutter_close is the local_to_world of the camera when the shutter closes, with local_to_world being when it opens.
+ * With a window, the screen is only a part of the image, see RTWindow.
  */
 #[repr(C, align(16))]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct RTCamera {
     pub screen_size: glm::Vec2,
     pub fov: f32,
     pub focus_distance: f32,
     pub pos: Vec3a16,
     pub local_to_world: glm::Mat4,
+    pub projection: RTProjection,
+    pub aperture_radius: f32,
+    pub distortion: glm::Vec2,
+    pub chromatic_aberration: f32,
+    pub stereo: RTStereo,
+    pub pixel_jitter: glm::Vec2,
+    pub shutter_close: Option<glm::Mat4>,
+    pub window: Option<RTWindow>,
+}
+
+/**
+ * Struct for the window of an image which a camera fires rays through, for rendering the image in parts (see tiles::TileScheduler).
+ * The screen is the size of the window, and each of its pixels fires the rays of the image's pixel it covers.
+ */
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct RTWindow {
+    pub offset: glm::Vec2, // Where the window's bottom left corner is in the image, in pixels from the image's bottom left corner
+    pub image_size: glm::Vec2, // The size of the whole image, in pixels
 }
 
 /**
  * Functions for dealing with the raytracing camera.
  */
+#[allow(dead_code)]
 impl RTCamera {
+    /**
+     * Creates a perspective raytracing camera at a position, looking towards a target.
+     * The camera's basis is stored in local_to_world with the same column layout as Camera's RTS (right, up, front).
+     *
+     * @param eye The position of the camera.
+     * @param target The point the camera looks towards. The focus distance is set to the distance to this point.
+     * @param up The world's up direction, used to orient the camera around its front. Must not be parallel to the view direction.
+     * @param fov_degrees The vertical field of view, in degrees.
+     * @param screen_size The size of the screen, in pixels.
+     */
+    pub fn look_at( eye: glm::Vec3, target: glm::Vec3, up: glm::Vec3, fov_degrees: f32, screen_size: glm::Vec2 ) -> RTCamera {
+        let front = ( target - eye ).normalize();
+        let right = up.cross( &front ).normalize();
+        let up = front.cross( &right );
+
+        let mut local_to_world = glm::translation( &eye );
+        local_to_world.set_column( 0, &glm::vec4( right.x, right.y, right.z, 0.0 ) );
+        local_to_world.set_column( 1, &glm::vec4( up.x, up.y, up.z, 0.0 ) );
+        local_to_world.set_column( 2, &glm::vec4( front.x, front.y, front.z, 0.0 ) );
+
+        RTCamera {
+            screen_size,
+            fov: fov_degrees,
+            focus_distance: ( target - eye ).magnitude(),
+            pos: eye.into(),
+            local_to_world,
+            projection: RTProjection::Perspective,
+            aperture_radius: 0.0,
+            distortion: glm::zero(),
+            chromatic_aberration: 0.0,
+            stereo: RTStereo::Mono,
+            pixel_jitter: glm::zero(),
+            shutter_close: None,
+            window: None,
+        }
+    }
+
+    /**
+     * Sets the sub-pixel jitter for a frame, which offsets every ray fired from the camera that frame.
+     *
+     * @param sequence The sequence to take the jitter from.
+     * @param frame_index The index of the frame.
+     */
+    pub fn set_frame_jitter( &mut self, sequence: JitterSequence, frame_index: u32 ) -> &RTCamera {
+        self.pixel_jitter = sequence.sample( frame_index );
+        self
+    }
+
+    /**
+     * Gets the ray through the center of a pixel, mirroring GenerateRay() in the shader without any jitter or lens sampling.
+     *
+     * @param pixel The pixel, with (0, 0) in the top left corner like window coordinates.
+     * @return The ray, or None if the pixel does not map to a ray (outside of a fisheye's circle, past half its fov from the front).
+     */
+    pub fn primary_ray( &self, pixel: glm::Vec2 ) -> Option<RTRay> {
+        // Convert to the shader's uv coordinates, which have (0, 0) in the bottom left corner of the image
+        let ( offset, image_size ) = self.window.map_or( ( glm::Vec2::zeros(), self.screen_size ), |window| ( window.offset, window.image_size ) );
+        let mut uv = ( glm::vec2( pixel.x + 0.5, self.screen_size.y - pixel.y - 0.5 ) + offset ).component_div( &image_size );
+        let mut view_size = image_size;
+        let mut view_offset = 0.0;
+
+        // Find the eye in stereo modes
+        match self.stereo {
+            RTStereo::SideBySide { ipd } => {
+                view_offset = if uv.x < 0.5 { -0.5 * ipd } else { 0.5 * ipd };
+                view_size.x *= 0.5;
+                uv.x = ( uv.x * 2.0 ).fract();
+            },
+            RTStereo::OverUnder { ipd } => {
+                view_offset = if uv.y >= 0.5 { -0.5 * ipd } else { 0.5 * ipd };
+                view_size.y *= 0.5;
+                uv.y = ( uv.y * 2.0 ).fract();
+            },
+            RTStereo::Mono => { },
+        }
+
+        // Apply lens distortion
+        let aspect = view_size.x / view_size.y;
+        let p = ( uv - glm::vec2( 0.5, 0.5 ) ).component_mul( &glm::vec2( aspect, 1.0 ) );
+        let r2 = p.dot( &p );
+        let distorted = p * ( 1.0 + self.distortion.x * r2 + self.distortion.y * r2 * r2 );
+        uv = glm::vec2( distorted.x / aspect + 0.5, distorted.y + 0.5 );
+
+        // Generate the ray in local space, then transform it to world space
+        let ( origin_local, dir_local ) = match self.projection {
+            RTProjection::Orthographic { height } => {
+                let plane_pos = ( uv - glm::vec2( 0.5, 0.5 ) ).component_mul( &glm::vec2( height * aspect, height ) );
+                ( glm::vec3( plane_pos.x + view_offset, plane_pos.y, 0.0 ), glm::vec3( 0.0, 0.0, 1.0 ) )
+            },
+            RTProjection::Fisheye { fov } => {
+                let p = ( uv - glm::vec2( 0.5, 0.5 ) ).component_mul( &glm::vec2( 2.0 * aspect, 2.0 ) );
+                let ( r, theta ) = ( p.magnitude(), p.magnitude() * fov.to_radians() * 0.5 );
+                if theta > fov.to_radians() * 0.5 {
+                    return None
+                }
+                let dir_plane = if r > 0.0 { p / r * theta.sin() } else { glm::Vec2::zeros() };
+                ( glm::vec3( view_offset, 0.0, 0.0 ), glm::vec3( dir_plane.x, dir_plane.y, theta.cos() ) )
+            },
+            RTProjection::Equirectangular => {
+                let phi = ( uv.x - 0.5 ) * 2.0 * std::f32::consts::PI;
+                let theta = ( uv.y - 0.5 ) * std::f32::consts::PI;
+                ( glm::vec3( view_offset, 0.0, 0.0 ), glm::vec3( phi.sin() * theta.cos(), theta.sin(), phi.cos() * theta.cos() ) )
+            },
+            RTProjection::Perspective => {
+                let plane_height = ( self.fov.to_radians() * 0.5 ).tan() * 2.0;
+                let plane_pos = ( uv - glm::vec2( 0.5, 0.5 ) ).component_mul( &glm::vec2( plane_height * aspect, plane_height ) );
+                ( glm::vec3( view_offset, 0.0, 0.0 ), glm::vec3( plane_pos.x, plane_pos.y, 1.0 ) )
+            },
+        };
+
+        Some( RTRay {
+            origin: ( self.local_to_world * glm::vec4( origin_local.x, origin_local.y, origin_local.z, 1.0 ) ).xyz(),
+            dir: ( self.local_to_world * glm::vec4( dir_local.x, dir_local.y, dir_local.z, 0.0 ) ).xyz().normalize(),
+        } )
+    }
+
+    /**
+     * Gets the matrix which projects points in the world onto the screen, for finding where surfaces were in earlier frames.
+     * Projected points are divided by their w, after which x and y are the shader's uv coordinates in [0, 1] (without any jitter).
+     * Only undistorted mono perspective cameras without a window give a matrix, as the other projections can't be expressed as one.
+     */
+    pub fn world_to_screen( &self ) -> Option<glm::Mat4> {
+        if self.projection != RTProjection::Perspective || self.stereo != RTStereo::Mono || self.distortion != glm::Vec2::zeros() || self.window.is_some() {
+            return None;
+        }
+
+        // Normalize the basis like the shader does, so the camera's scale doesn't matter
+        let mut local_to_world = self.local_to_world;
+        for i in 0..3 {
+            let axis = local_to_world.column( i ).xyz().normalize();
+            local_to_world.set_column( i, &glm::vec4( axis.x, axis.y, axis.z, 0.0 ) );
+        }
+
+        // The screen covers [-0.5, 0.5] of the plane at distance 1, scaled by its size there
+        let plane_height = ( self.fov.to_radians() * 0.5 ).tan() * 2.0;
+        let aspect = self.screen_size.x / self.screen_size.y;
+        let projection = glm::mat4(
+            1.0 / ( plane_height * aspect ), 0.0, 0.5, 0.0,
+            0.0, 1.0 / plane_height, 0.5, 0.0,
+            0.0, 0.0, 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0,
+        );
+        Some( projection * glm::inverse( &local_to_world ) )
+    }
+
+    /**
+     * Focuses the camera on whatever is under a pixel, by raycasting the scene on the CPU.
+     * The focus distance is measured along the camera's front, as the shader's focus plane is perpendicular to it.
+     *
+     * @param pixel The pixel, with (0, 0) in the top left corner like window coordinates.
+     * @param spheres The spheres in the scene.
+     * @param triangles The triangles in the scene.
+     *
+     * @return The new focus distance, or None if nothing was hit (in which case the camera is left as is).
+     */
+    pub fn focus_on_pixel( &mut self, pixel: glm::Vec2, spheres: &[RTSphere], triangles: &[RTTriangle] ) -> Option<f32> {
+        let ray = self.primary_ray( pixel )?;
+        let dist = raycast( &ray, spheres, triangles )?;
+        let front = self.local_to_world.column( 2 ).xyz().normalize();
+        self.focus_distance = dist * ray.dir.dot( &front );
+        Some( self.focus_distance )
+    }
+
+    /**
+     * Updates the camera's screen size, e.g. after the window has been resized.
+     *
+     * @param width The new width of the screen, in pixels.
+     * @param height The new height of the screen, in pixels.
+     */
+    pub fn set_screen_size( &mut self, width: u32, height: u32 ) -> &RTCamera {
+        self.screen_size = glm::vec2( width.max( 1 ) as f32, height.max( 1 ) as f32 );
+        self
+    }
+
     /**
      * Sends the RTCamera's data to a uniform variable in a given shader.
      * 
      * @param shader The shader.
      * @param uniform_name The name of the uniform variable in the shader.
      */
-    pub unsafe fn send_uniform( self, shader: &Shader, uniform_name: &str ) {
+    pub unsafe fn send_uniform( &self, shader: &Shader, uniform_name: &str ) {
         // Temporarily switch to the shader we're setting uniforms for
         let mut prev_pid: gl::types::GLint = 0;
         gl::GetIntegerv(gl::CURRENT_PROGRAM,&mut prev_pid);
@@ -191,8 +1061,40 @@ impl RTCamera {
         gl::Uniform1f( shader.get_uniform_location( format!("{uniform_name}.focusDistance").as_str() ), self.focus_distance);
         gl::Uniform3f( shader.get_uniform_location( format!("{uniform_name}.pos").as_str() ), self.pos.x, self.pos.y, self.pos.z);
         shader.set_uniform_mat4( format!("{uniform_name}.localToWorld").as_str(), self.local_to_world);
+        gl::Uniform1ui( shader.get_uniform_location( format!("{uniform_name}.projection").as_str() ), self.projection.id());
+        gl::Uniform1f( shader.get_uniform_location( format!("{uniform_name}.apertureRadius").as_str() ), self.aperture_radius);
+        gl::Uniform2f( shader.get_uniform_location( format!("{uniform_name}.distortion").as_str() ), self.distortion.x, self.distortion.y);
+        gl::Uniform1f( shader.get_uniform_location( format!("{uniform_name}.chromaticAberration").as_str() ), self.chromatic_aberration);
+        gl::Uniform1ui( shader.get_uniform_location( format!("{uniform_name}.stereo").as_str() ), self.stereo.id());
+        gl::Uniform1f( shader.get_uniform_location( format!("{uniform_name}.ipd").as_str() ), self.stereo.ipd());
+        gl::Uniform2f( shader.get_uniform_location( format!("{uniform_name}.pixelJitter").as_str() ), self.pixel_jitter.x, self.pixel_jitter.y);
+        gl::Uniform1ui( shader.get_uniform_location( format!("{uniform_name}.motionBlur").as_str() ), self.shutter_close.is_some() as u32);
+        shader.set_uniform_mat4( format!("{uniform_name}.localToWorldClose").as_str(), self.shutter_close.unwrap_or( self.local_to_world ));
+        let ( window_offset, image_size ) = self.window.map_or( ( glm::Vec2::zeros(), self.screen_size ), |window| ( window.offset, window.image_size ) );
+        gl::Uniform2f( shader.get_uniform_location( format!("{uniform_name}.windowOffset").as_str() ), window_offset.x, window_offset.y);
+        gl::Uniform2f( shader.get_uniform_location( format!("{uniform_name}.imageSize").as_str() ), image_size.x, image_size.y);
+        match self.projection {
+            RTProjection::Orthographic { height } => {
+                gl::Uniform1f( shader.get_uniform_location( format!("{uniform_name}.orthoHeight").as_str() ), height);
+            },
+            RTProjection::Fisheye { fov } => {
+                gl::Uniform1f( shader.get_uniform_location( format!("{uniform_name}.fisheyeFov").as_str() ), fov);
+            },
+            _ => { },
+        }
 
         // Switch back and return
         gl::UseProgram( prev_pid as u32 );
     }
+
+    /**
+     * Sends only the RTCamera's jitter to a uniform variable in a given shader, for frames where nothing else about it changed.
+     * The shader must be active.
+     *
+     * @param shader The shader.
+     * @param uniform_name The name of the uniform variable in the shader.
+     */
+    pub unsafe fn send_jitter_uniform( &self, shader: &Shader, uniform_name: &str ) {
+        gl::Uniform2f( shader.get_uniform_location( format!("{uniform_name}.pixelJitter").as_str() ), self.pixel_jitter.x, self.pixel_jitter.y);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,188 @@
+use std::collections::VecDeque;
+
+use crate::glapi;
+use crate::gltracker::{ self, GlObject };
+
+/**
+ * Enum for the pixels of a finished readback.
+ */
+#[allow(dead_code)]
+pub enum ReadbackImage {
+    Screen( image::RgbImage ),  // The screen, as it is shown, with the rows from the top down
+    Texture( Vec<f32> ),        // A texture as RGB, with the bottom row first
+}
+
+/**
+ * Struct for a readback which the GPU hasn't finished yet.
+ */
+struct PendingRead<T> {
+    buffer: u32,
+    fence: gl::types::GLsync,
+    width: u32,
+    height: u32,
+    screen: bool, // If the screen is read, rather than a texture
+    channels: usize, // How many channels each pixel was read with, see glapi::read_format()
+    tag: T,
+}
+
+/**
+ * Struct for reading pixels back from the GPU without waiting for it, through pixel buffer objects (PBOs).
+ * glReadPixels and glGetTexImage into client memory stall until the GPU has drawn everything before them,
+ * so they are read into a PBO instead, and a fence tells when the copy is done, usually a frame later.
+ *
+ * Reads are tagged with what they are for, and are handed back by poll() in the order they were started.
+ * Two PBOs are kept, so a readback every frame never waits, and more are created while more reads are in flight.
+ */
+pub struct Readback<T> {
+    free: Vec<u32>,
+    pending: VecDeque<PendingRead<T>>,
+}
+
+/**
+ * Readback functions.
+ */
+#[allow(dead_code)]
+impl<T> Readback<T> {
+    /**
+     * Creates the pixel buffer objects.
+     */
+    pub unsafe fn new() -> Readback<T> {
+        let mut buffers = [0; 2];
+        gl::GenBuffers( 2, buffers.as_mut_ptr() );
+        gltracker::created( GlObject::Buffer, &buffers );
+        Readback { free: buffers.to_vec(), pending: VecDeque::new() }
+    }
+
+    /**
+     * Takes a free PBO, sized for a read and bound to the pixel pack target, which reads then go to.
+     *
+     * @param size The size of the read, in bytes.
+     */
+    unsafe fn bind_buffer( &mut self, size: usize ) -> u32 {
+        let buffer = self.free.pop().unwrap_or_else( || {
+            let mut buffer = 0;
+            gl::GenBuffers( 1, &mut buffer );
+            gltracker::created( GlObject::Buffer, &[buffer] );
+            buffer
+        } );
+        gl::BindBuffer( gl::PIXEL_PACK_BUFFER, buffer );
+        gl::BufferData( gl::PIXEL_PACK_BUFFER, size as isize, std::ptr::null(), gl::STREAM_READ );
+        gl::PixelStorei( gl::PACK_ALIGNMENT, 1 );
+        buffer
+    }
+
+    /**
+     * Fences a read which was started into a PBO, and unbinds it.
+     */
+    unsafe fn push( &mut self, buffer: u32, width: u32, height: u32, screen: bool, tag: T ) {
+        gl::BindBuffer( gl::PIXEL_PACK_BUFFER, 0 );
+        let fence = gl::FenceSync( gl::SYNC_GPU_COMMANDS_COMPLETE, 0 );
+        let channels = glapi::format_channels( glapi::read_format( gl::RGB ) ).1;
+        self.pending.push_back( PendingRead { buffer, fence, width, height, screen, channels, tag } );
+    }
+
+    /**
+     * Starts reading back what has been drawn to the screen this frame, before the buffers are swapped.
+     *
+     * @param width The width of the screen, in pixels.
+     * @param height The height of the screen, in pixels.
+     * @param tag What the read is for, which it is handed back with.
+     */
+    pub unsafe fn read_screen( &mut self, width: u32, height: u32, tag: T ) {
+        let channels = glapi::format_channels( glapi::read_format( gl::RGB ) ).1;
+        let buffer = self.bind_buffer( width as usize * height as usize * channels );
+        glapi::bind_screen_for_reading();
+        gl::ReadPixels( 0, 0, width as i32, height as i32, glapi::read_format( gl::RGB ), gl::UNSIGNED_BYTE, std::ptr::null_mut() );
+        self.push( buffer, width, height, true, tag );
+    }
+
+    /**
+     * Starts reading back a texture as RGB floats.
+     *
+     * @param texture The texture.
+     * @param width The width of the texture, in pixels.
+     * @param height The height of the texture, in pixels.
+     * @param tag What the read is for, which it is handed back with.
+     */
+    pub unsafe fn read_texture( &mut self, texture: u32, width: u32, height: u32, tag: T ) {
+        let channels = glapi::format_channels( glapi::read_format( gl::RGB ) ).1;
+        let buffer = self.bind_buffer( width as usize * height as usize * channels * std::mem::size_of::<f32>() );
+        glapi::read_texture( texture, 0, width, height, gl::RGB, std::ptr::null_mut() );
+        self.push( buffer, width, height, false, tag );
+    }
+
+    /**
+     * Gets if any reads haven't been handed back yet.
+     */
+    pub fn is_pending( &self ) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /**
+     * Copies a finished read out of its PBO, and frees the PBO for the next read.
+     */
+    unsafe fn take( &mut self, read: PendingRead<T> ) -> ( T, ReadbackImage ) {
+        gl::DeleteSync( read.fence );
+        gl::BindBuffer( gl::PIXEL_PACK_BUFFER, read.buffer );
+        let pixels = read.width as usize * read.height as usize * read.channels;
+        let image = if read.screen {
+            let mapped = gl::MapBufferRange( gl::PIXEL_PACK_BUFFER, 0, pixels as isize, gl::MAP_READ_BIT ) as *const u8;
+            // (GL's rows go from the bottom up, so they are flipped to go from the top down like an image file's)
+            let rows = glapi::keep_channels( std::slice::from_raw_parts( mapped, pixels ), gl::RGB );
+            let flipped: Vec<u8> = rows.chunks( read.width as usize * 3 ).rev().flatten().copied().collect();
+            ReadbackImage::Screen( image::RgbImage::from_raw( read.width, read.height, flipped ).unwrap() )
+        } else {
+            let mapped = gl::MapBufferRange( gl::PIXEL_PACK_BUFFER, 0, ( pixels * std::mem::size_of::<f32>() ) as isize, gl::MAP_READ_BIT ) as *const f32;
+            ReadbackImage::Texture( glapi::keep_channels( std::slice::from_raw_parts( mapped, pixels ), gl::RGB ) )
+        };
+        gl::UnmapBuffer( gl::PIXEL_PACK_BUFFER );
+        gl::BindBuffer( gl::PIXEL_PACK_BUFFER, 0 );
+        self.free.push( read.buffer );
+        ( read.tag, image )
+    }
+
+    /**
+     * Hands back the reads which the GPU has finished, without waiting for the rest.
+     *
+     * @return The finished reads with their tags, in the order they were started.
+     */
+    pub unsafe fn poll( &mut self ) -> Vec<( T, ReadbackImage )> {
+        let mut finished = Vec::new();
+        while let Some( read ) = self.pending.front() {
+            let status = gl::ClientWaitSync( read.fence, gl::SYNC_FLUSH_COMMANDS_BIT, 0 );
+            if status != gl::ALREADY_SIGNALED && status != gl::CONDITION_SATISFIED {
+                break;
+            }
+            let read = self.pending.pop_front().unwrap();
+            finished.push( self.take( read ) );
+        }
+        finished
+    }
+
+    /**
+     * Waits for every read to finish, such as before exiting.
+     *
+     * @return The reads with their tags, in the order they were started.
+     */
+    pub unsafe fn finish( &mut self ) -> Vec<( T, ReadbackImage )> {
+        let mut finished = Vec::new();
+        while let Some( read ) = self.pending.pop_front() {
+            gl::ClientWaitSync( read.fence, gl::SYNC_FLUSH_COMMANDS_BIT, u64::MAX );
+            finished.push( self.take( read ) );
+        }
+        finished
+    }
+
+    /**
+     * Deletes the PBOs, dropping the reads which haven't been handed back (see finish()).
+     */
+    pub unsafe fn delete( self ) {
+        let mut buffers = self.free;
+        for read in self.pending {
+            gl::DeleteSync( read.fence );
+            buffers.push( read.buffer );
+        }
+        gl::DeleteBuffers( buffers.len() as i32, buffers.as_ptr() );
+        gltracker::deleted( GlObject::Buffer, &buffers );
+    }
+}
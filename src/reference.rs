@@ -0,0 +1,206 @@
+use rand::{ Rng, SeedableRng, rngs::StdRng };
+use rayon::prelude::*;
+
+use crate::raytracing::{ RTRay, RTSphere, RTTriangle, RTMaterial, RTSettings, RTCamera, RTProjection, RTRoulette };
+
+/**
+ * Struct for where a ray hit the scene, see ReferenceRenderer::closest_hit().
+ */
+struct ReferenceHit<'a> {
+    dist: f32,
+    pos: glm::Vec3,
+    normal: glm::Vec3,
+    material: &'a RTMaterial,
+}
+
+/**
+ * Maps a pair of uniform random numbers to a uniformly distributed direction, like SampleSphere() in the shader.
+ */
+fn sample_sphere( u: glm::Vec2 ) -> glm::Vec3 {
+    let ( ang, z ) = ( u.x * 2.0 * std::f32::consts::PI, 1.0 - 2.0 * u.y );
+    let r = ( 1.0 - z * z ).max( 0.0 ).sqrt();
+    glm::vec3( ang.cos() * r, ang.sin() * r, z )
+}
+
+/**
+ * Maps a pair of uniform random numbers to a uniformly distributed point within the unit disc, like SampleDisc() in the shader.
+ */
+fn sample_disc( u: glm::Vec2 ) -> glm::Vec2 {
+    let ang = u.x * 2.0 * std::f32::consts::PI;
+    glm::vec2( ang.cos(), ang.sin() ) * u.y.sqrt()
+}
+
+/**
+ * Struct for a path tracer which runs on the CPU, over every core with rayon.
+ * It traces the same scene and materials as the shader's path tracer (Trace() in shaders/raytracing.frag), but only what every path does there:
+ * bouncing off the diffuse and specular lobes, with their limits, path regularization, Russian roulette and clamping.
+ * What only makes the image converge faster (next event estimation, ReSTIR, path guiding, caustics) is left out,
+ * so it converges to the same image as the shader, which makes it a reference to check the shader against.
+ * Fog, the sun, volumes and the integrators other than the path tracer aren't covered.
+ *
+ * It needs no OpenGL, so offline renders also fall back to it where there is no context to render with.
+ */
+pub struct ReferenceRenderer {
+    spheres: Vec<RTSphere>,
+    triangles: Vec<RTTriangle>,
+    settings: RTSettings,
+}
+
+/**
+ * ReferenceRenderer functions.
+ */
+impl ReferenceRenderer {
+    /**
+     * Creates a renderer of a scene.
+     *
+     * @param spheres The spheres in the scene.
+     * @param triangles The triangles in the scene.
+     * @param settings The settings of the render, of which the bounces, roulette, clamping, regularization, t_max and seed are used.
+     */
+    pub fn new( spheres: Vec<RTSphere>, triangles: Vec<RTTriangle>, settings: RTSettings ) -> ReferenceRenderer {
+        ReferenceRenderer { spheres, triangles, settings }
+    }
+
+    /**
+     * Gets the first intersection which a ray makes with the scene, mirroring CalculateRayCollision() in the shader.
+     *
+     * @param ray The ray. Its direction must be normalized.
+     * @return The hit, or None if the ray hits nothing before t_max.
+     */
+    fn closest_hit( &self, ray: &RTRay ) -> Option<ReferenceHit<'_>> {
+        let mut closest: Option<ReferenceHit> = None;
+        for sphere in &self.spheres {
+            if let Some( dist ) = sphere.intersect( ray ).filter( |&dist| closest.as_ref().is_none_or( |hit| dist < hit.dist ) ) {
+                let pos = ray.origin + ray.dir * dist;
+                closest = Some( ReferenceHit { dist, pos, normal: ( pos - glm::Vec3::from( sphere.center ) ).normalize(), material: &sphere.material } );
+            }
+        }
+        for triangle in &self.triangles {
+            if let Some( ( dist, barycentric ) ) = triangle.intersect_barycentric( ray ).filter( |&( dist, _ )| closest.as_ref().is_none_or( |hit| dist < hit.dist ) ) {
+                closest = Some( ReferenceHit { dist, pos: ray.origin + ray.dir * dist, normal: triangle.normal_at( barycentric ), material: &triangle.material } );
+            }
+        }
+        closest.filter( |hit| hit.dist <= self.settings.t_max )
+    }
+
+    /**
+     * Limits the brightness of light reaching the camera, like ClampLight() in the shader.
+     *
+     * @param light The light.
+     * @param bounces How many times the light bounced before reaching the camera.
+     */
+    fn clamp_light( &self, light: glm::Vec3, bounces: u32 ) -> glm::Vec3 {
+        let limit = if bounces <= 1 { self.settings.direct_clamp } else { self.settings.indirect_clamp };
+        let brightest = light.max();
+        if limit > 0.0 && brightest > limit { light * ( limit / brightest ) } else { light }
+    }
+
+    /**
+     * Traces a path through the scene, mirroring Trace() in the shader without what it does to converge faster.
+     * The environment is black, like the shader's, so paths which leave the scene find no more light.
+     *
+     * @param ray The ray the path starts with.
+     * @param rng The random numbers of the path.
+     *
+     * @return The light which the path brings back.
+     */
+    fn trace( &self, mut ray: RTRay, rng: &mut StdRng ) -> glm::Vec3 {
+        let bounces = &self.settings.max_bounces;
+        let ( mut incoming_light, mut ray_color ) = ( glm::Vec3::zeros(), glm::vec3( 1.0, 1.0, 1.0 ) );
+        let ( mut diffuse_bounces, mut glossy_bounces ) = ( 0, 0 );
+        let mut path_roughness: f32 = 0.0;
+
+        for i in 0..bounces.total {
+            let Some( hit ) = self.closest_hit( &ray ) else { break };
+            let material = hit.material;
+
+            // Pick a lobe, and bounce off it
+            let choices = glm::vec2( rng.gen::<f32>(), rng.gen::<f32>() );
+            let is_specular = material.specular_color.w >= choices.x;
+            let specular_dir = ray.dir - hit.normal * ( 2.0 * ray.dir.dot( &hit.normal ) );
+            let diffuse_dir = ( hit.normal + sample_sphere( glm::vec2( rng.gen(), rng.gen() ) ) ).try_normalize( 1e-6 ).unwrap_or( hit.normal );
+            let smoothness = material.smoothness.min( 1.0 - self.settings.regularization * path_roughness );
+            path_roughness = path_roughness.max( if is_specular { 1.0 - smoothness } else { 1.0 } );
+            let blend = if is_specular { smoothness } else { 0.0 };
+            ray = RTRay { origin: hit.pos, dir: ( diffuse_dir * ( 1.0 - blend ) + specular_dir * blend ).normalize() };
+
+            let emitted_light = material.emission_color.xyz() * material.emission_color.w;
+            incoming_light += self.clamp_light( emitted_light.component_mul( &ray_color ), i );
+
+            // Stop if the path has bounced off the chosen lobe as much as it may
+            let lobe_bounces = if is_specular { &mut glossy_bounces } else { &mut diffuse_bounces };
+            *lobe_bounces += 1;
+            if *lobe_bounces > if is_specular { bounces.glossy } else { bounces.diffuse } {
+                break;
+            }
+
+            ray_color = ray_color.component_mul( &if is_specular { material.specular_color.xyz() } else { material.color.xyz() } );
+
+            // Russian roulette
+            if self.settings.roulette != RTRoulette::Off && i >= self.settings.roulette_start {
+                let survival = match self.settings.roulette {
+                    RTRoulette::Constant { survival } => { survival },
+                    _ => { ray_color.max() },
+                }.min( 1.0 );
+                if choices.y >= survival {
+                    break;
+                }
+                ray_color /= survival;
+            }
+        }
+        incoming_light
+    }
+
+    /**
+     * Gets a ray from the camera through a random point of a pixel, through a random point of the lens with depth of field.
+     * The rays are spread evenly over the pixel, rather than jittered like the shader's (see RTSettings::diverge_strength).
+     *
+     * @param camera The camera.
+     * @param pixel The pixel, with (0, 0) in the top left corner.
+     * @param rng The random numbers of the pixel.
+     *
+     * @return The ray, or None if the point does not map to a ray (outside of a fisheye's circle).
+     */
+    fn camera_ray( camera: &RTCamera, pixel: glm::Vec2, rng: &mut StdRng ) -> Option<RTRay> {
+        let ray = camera.primary_ray( pixel + glm::vec2( rng.gen::<f32>() - 0.5, rng.gen::<f32>() - 0.5 ) )?;
+        if camera.aperture_radius <= 0.0 || camera.projection != RTProjection::Perspective {
+            return Some( ray );
+        }
+        let right = camera.local_to_world.column( 0 ).xyz().normalize();
+        let up = camera.local_to_world.column( 1 ).xyz().normalize();
+        let front = camera.local_to_world.column( 2 ).xyz().normalize();
+        let focus_point = ray.origin + ray.dir * ( camera.focus_distance / ray.dir.dot( &front ) );
+        let lens_point = sample_disc( glm::vec2( rng.gen(), rng.gen() ) ) * camera.aperture_radius;
+        let origin = ray.origin + right * lens_point.x + up * lens_point.y;
+        Some( RTRay { origin, dir: ( focus_point - origin ).normalize() } )
+    }
+
+    /**
+     * Renders the scene, with the rows spread over the threads.
+     * Every pixel has random numbers of its own, seeded from the settings' seed, so the same seed gives the same image.
+     *
+     * @param camera The camera, whose screen is the size of the image.
+     * @param samples How many paths are traced through each pixel.
+     *
+     * @return The image, as RGB with the bottom row first (like Accumulator::read_pixels()).
+     */
+    pub fn render( &self, camera: &RTCamera, samples: u32 ) -> Vec<f32> {
+        let ( width, height ) = ( camera.screen_size.x as usize, camera.screen_size.y as usize );
+        let mut pixels = vec![0.0; width * height * 3];
+        pixels.par_chunks_mut( width * 3 ).enumerate().for_each( |( row, row_pixels )| {
+            let y = height - 1 - row;
+            for x in 0..width {
+                let mut rng = StdRng::seed_from_u64( ( ( self.settings.seed as u64 ) << 32 ) | ( y * width + x ) as u64 );
+                let mut light = glm::Vec3::zeros();
+                for _ in 0..samples {
+                    if let Some( ray ) = Self::camera_ray( camera, glm::vec2( x as f32, y as f32 ), &mut rng ) {
+                        light += self.trace( ray, &mut rng );
+                    }
+                }
+                let color = light / samples.max( 1 ) as f32;
+                row_pixels[x * 3..x * 3 + 3].copy_from_slice( color.as_slice() );
+            }
+        } );
+        pixels
+    }
+}
@@ -0,0 +1,125 @@
+use crate::device::{ self, BufferUsage, Device };
+use crate::shader::Shader;
+
+// Size of a reservoir in the shader (std430), in bytes
+const RESERVOIR_SIZE: usize = 64;
+
+// Bindings of the reservoir buffers in the shader
+const RESERVOIR_BINDING: u32 = 4;
+const FINAL_RESERVOIR_BINDING: u32 = 5;
+
+/**
+ * Enum for the passes of ReSTIR, which are rendered in order every frame.
+ */
+#[derive(Clone, Copy, PartialEq)]
+pub enum RestirPass {
+    Shade,      // The normal render, which lights the first surface using the final reservoirs
+    Initial,    // Picks light samples from a set of random candidates, and reuses the previous frame's reservoirs (temporal reuse)
+    Spatial,    // Reuses the reservoirs of neighbouring pixels (spatial reuse), giving the final reservoirs
+}
+
+/**
+ * RestirPass functions.
+ */
+impl RestirPass {
+    /**
+     * Gets the id of the pass, as used by the shader.
+     */
+    pub fn id( &self ) -> u32 {
+        match self {
+            RestirPass::Shade   => { 0 },
+            RestirPass::Initial => { 1 },
+            RestirPass::Spatial => { 2 },
+        }
+    }
+}
+
+/**
+ * Struct for the buffers of ReSTIR (reservoir-based spatiotemporal importance resampling) for direct lighting.
+ * https://research.nvidia.com/publication/2020-07_spatiotemporal-reservoir-resampling-real-time-ray-tracing-dynamic-direct
+ *
+ * Each pixel has a reservoir, which holds a single light sample picked out of many, weighted so it stays unbiased.
+ * The reservoirs of the initial pass are kept apart from the final ones, so the spatial pass doesn't read what it writes,
+ * and the final ones are kept until the next frame for temporal reuse.
+ */
+pub struct Restir {
+    buffers: [device::Buffer; 2],
+    width: u32,
+    height: u32,
+}
+
+/**
+ * Restir functions.
+ */
+impl Restir {
+    /**
+     * Creates the reservoir buffers, and binds them to the shader's bindings.
+     *
+     * @param width The width of the screen, in pixels.
+     * @param height The height of the screen, in pixels.
+     */
+    pub unsafe fn new( width: u32, height: u32 ) -> Restir {
+        let mut restir = Restir {
+            buffers: [0; 2],
+            width,
+            height,
+        };
+        restir.buffers = [device::current().create_buffer(), device::current().create_buffer()];
+        restir.allocate();
+        restir
+    }
+
+    /**
+     * Allocates the reservoir buffers at the current size, with all reservoirs empty.
+     */
+    unsafe fn allocate( &self ) {
+        let data = vec![0u8; self.width as usize * self.height as usize * RESERVOIR_SIZE];
+        for ( buffer, binding ) in self.buffers.iter().zip( [RESERVOIR_BINDING, FINAL_RESERVOIR_BINDING] ) {
+            device::current().allocate_buffer( *buffer, data.len(), Some( &data ), BufferUsage::Storage );
+            device::current().bind_storage_buffer( *buffer, binding );
+        }
+    }
+
+    /**
+     * Resizes the reservoir buffers, which empties them.
+     *
+     * @param width The new width, in pixels.
+     * @param height The new height, in pixels.
+     */
+    pub unsafe fn resize( &mut self, width: u32, height: u32 ) {
+        self.width = width;
+        self.height = height;
+        self.allocate();
+    }
+
+    /**
+     * Deletes the reservoir buffers, once they are done with.
+     */
+    pub unsafe fn delete( self ) {
+        for buffer in self.buffers {
+            device::current().delete_buffer( buffer );
+        }
+    }
+
+    /**
+     * Prepares a shader for rendering a pass.
+     * The color output is masked during the reservoir passes, so they can be rendered into the accumulator without touching it.
+     * The shader must be active.
+     *
+     * @param shader The shader.
+     * @param pass The pass.
+     */
+    pub unsafe fn begin_pass( &self, shader: &Shader, pass: RestirPass ) {
+        let write_color = ( pass == RestirPass::Shade ) as u8;
+        gl::ColorMask( write_color, write_color, write_color, write_color );
+        gl::Uniform1ui( shader.get_uniform_location( "restirPass" ), pass.id() );
+    }
+
+    /**
+     * Finishes rendering a pass, making its reservoirs visible to the following passes.
+     */
+    pub unsafe fn end_pass( &self ) {
+        gl::ColorMask( gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE );
+        gl::MemoryBarrier( gl::SHADER_STORAGE_BARRIER_BIT );
+    }
+}
@@ -0,0 +1,159 @@
+use serde::{ Serialize, Deserialize };
+
+use crate::error::EngineError;
+use crate::raytracing::{ RTCamera, RTMaterial, RTMeshInfo, RTSettings, RTSphere, RTTriangle };
+
+/**
+ * Struct for a whole scene as the GPU gets it: the spheres, the triangles of every mesh and the meshes which cover them,
+ * along with the camera and settings it is rendered with, if it has its own.
+ * Scenes are stored as JSON, e.g. `{ "spheres": [ { "radius": 1.0, "center": [0, 1, 0], "material": { ... } } ] }`,
+ * where anything but the spheres may be left out.
+ */
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct Scene {
+    pub spheres: Vec<RTSphere>,
+    #[serde(default)]
+    pub triangles: Vec<RTTriangle>,
+    #[serde(default)]
+    pub meshes: Vec<RTMeshInfo>,         // The ranges of the triangles which make up each mesh, see mesh::Model::generate_raytracing_structs()
+    pub camera: Option<RTCamera>,        // The camera the scene is seen from, or None to keep the engine's
+    pub settings: Option<RTSettings>,    // The settings the scene is rendered with, or None to keep the engine's
+}
+
+/**
+ * Scene functions.
+ */
+#[allow(dead_code)]
+impl Scene {
+    /**
+     * Loads a scene from a JSON file.
+     *
+     * @param path The path to the file.
+     * @return The scene, or an error if the file could not be read or parsed.
+     */
+    pub fn load( path: &str ) -> Result<Scene, EngineError> {
+        let contents = std::fs::read_to_string( path )
+            .map_err( |err| EngineError::io( "SCENE::FILE_NOT_READ", path, err ) )?;
+        Scene::from_json( path, &contents )
+    }
+
+    /**
+     * Parses a scene from JSON, for when it wasn't read from a file (such as in a browser).
+     *
+     * @param name What the JSON is of, such as its path, for the error.
+     * @param json The JSON.
+     * @return The scene, or an error if the JSON could not be parsed.
+     */
+    pub fn from_json( name: &str, json: &str ) -> Result<Scene, EngineError> {
+        let scene: Scene = serde_json::from_str( json )
+            .map_err( |err| EngineError::parse( "SCENE::INVALID_FILE", format!( "{name}: {err}" ) ) )?;
+
+        // (Meshes which reach past the triangles would have the shader read past their buffer)
+        if let Some( mesh ) = scene.meshes.iter().find( |mesh| ( mesh.start_index as usize + mesh.count as usize ) > scene.triangles.len() ) {
+            return Err( EngineError::parse( "SCENE::INVALID_FILE", format!( "{name}\nA mesh covers triangles {} to {}, but there are only {}", mesh.start_index, mesh.start_index + mesh.count, scene.triangles.len() ) ) );
+        }
+        Ok( scene )
+    }
+
+    /**
+     * Saves the scene to a JSON file.
+     *
+     * @param path The path to the file.
+     * @return Ok, or an error if the file could not be written.
+     */
+    pub fn save( &self, path: &str ) -> Result<(), EngineError> {
+        let contents = serde_json::to_string_pretty( self )
+            .map_err( |err| EngineError::parse( "SCENE::NOT_SERIALIZED", err.to_string() ) )?;
+        std::fs::write( path, contents )
+            .map_err( |err| EngineError::io( "SCENE::FILE_NOT_WRITTEN", path, err ) )
+    }
+}
+
+/**
+ * Gets the spheres of the built-in scene, which move with its time.
+ * The window and the WebGL2 renderer both start with them.
+ *
+ * @param time The time of the scene, in seconds.
+ */
+pub fn scene_spheres( time: f32 ) -> Vec<RTSphere> {
+    vec![
+        RTSphere {
+            radius: 50.0,
+            center: glm::vec3((time*0.5).sin() * 100.0 , time.cos() * 100.0, 0.0).into(),
+            material: RTMaterial {
+                color: glm::vec4(1.0, 0.7, 0.3, 1.0),
+                emission_color: glm::vec4(1.0, 0.7, 0.3, 1.0),
+                specular_color: glm::vec4(1.0, 1.0, 1.0, 0.0),
+                smoothness: 0.5,
+            }
+        },
+        RTSphere {
+            radius: 150.0,
+            center: glm::vec3(0.0, -150.0, 0.0).into(),
+            material: RTMaterial {
+                color: glm::vec4(1.0, 1.0, 1.0, 1.0),
+                emission_color: glm::vec4(1.0, 1.0, 1.0, 0.0),
+                specular_color: glm::vec4(1.0, 0.0, 0.0, 0.0),
+                smoothness: 0.3,
+            }
+        },
+        RTSphere {
+            radius: 1.0,
+            center: glm::vec3(3.0, 1.25, 0.0).into(),
+            material: RTMaterial {
+                color: glm::vec4(0.0, 0.0, 1.0, 1.0),
+                emission_color: glm::vec4(0.0, 0.0, 1.0, 1.0),
+                specular_color: glm::vec4(0.0, 1.0, 1.0, 0.0),
+                smoothness: 0.3,
+            }
+        },
+        RTSphere {
+            radius: 2.0,
+            center: glm::vec3(2.5, -0.5, 2.5).into(),
+            material: RTMaterial {
+                color: glm::vec4(1.0, 1.0, 1.0, 1.0),
+                emission_color: glm::vec4(0.0, 0.0, 1.0, 0.0),
+                specular_color: glm::vec4(0.0, 1.0, 1.0, 1.0),
+                smoothness: 1.0,
+            }
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integrators::AmbientOcclusion;
+    use crate::raytracing::{ RTIntegrator, RTRoulette, RTStereo, RTSun };
+
+    #[test]
+    fn scenes_survive_json() {
+        let ( triangles, meshes ) = crate::offscreen::meshes_of( &[vec![RTTriangle { material: RTMaterial { smoothness: 0.5, ..RTMaterial::new() }, ..RTTriangle::new() }]] );
+        let mut camera = RTCamera::look_at( glm::vec3( 1.0, 2.0, 3.0 ), glm::zero(), glm::vec3( 0.0, 1.0, 0.0 ), 45.0, glm::vec2( 64.0, 48.0 ) );
+        camera.stereo = RTStereo::SideBySide { ipd: 0.064 };
+        let scene = Scene {
+            spheres: scene_spheres( 1.5 ),
+            triangles,
+            meshes,
+            camera: Some( camera ),
+            settings: Some( RTSettings {
+                integrator: RTIntegrator::AmbientOcclusion( AmbientOcclusion { distance: 2.0, falloff: 0.5 } ),
+                roulette: RTRoulette::Constant { survival: 0.8 },
+                sun: Some( RTSun { direction: glm::vec3( 0.0, 1.0, 0.0 ), color: glm::vec3( 1.0, 0.9, 0.8 ), god_ray_steps: 4 } ),
+                ..crate::engine::initial_settings( None )
+            } ),
+        };
+        let json = serde_json::to_string( &scene ).unwrap();
+        assert_eq!( serde_json::from_str::<Scene>( &json ).unwrap(), scene );
+
+        // (Padded vectors are saved like glm's)
+        assert!( json.contains( "\"center\":[" ) );
+    }
+
+    #[test]
+    fn scenes_may_only_have_spheres() {
+        let scene: Scene = serde_json::from_str( r#"{ "spheres": [ { "radius": 1.0, "center": [0, 1, 0], "material": { "color": [1, 0, 0, 1], "emission_color": [0, 0, 0, 0], "specular_color": [0, 0, 0, 0], "smoothness": 0 } } ] }"# ).unwrap();
+        assert_eq!( scene.spheres[0].center, glm::vec3( 0.0, 1.0, 0.0 ).into() );
+        assert!( scene.triangles.is_empty() && scene.camera.is_none() && scene.settings.is_none() );
+    }
+}
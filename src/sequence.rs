@@ -0,0 +1,166 @@
+extern crate nalgebra_glm as glm;
+
+use crate::camera::CameraPath;
+use crate::output;
+use crate::raytracing::RTCamera;
+
+/**
+ * Struct for a turntable, where the camera circles around a point while looking at it.
+ * It goes around once over the whole sequence, so the last frame leads back into the first when looped.
+ */
+#[derive(Clone, PartialEq)]
+pub struct Turntable {
+    pub center: glm::Vec3, // The point which is circled around and looked at
+    pub radius: f32,
+    pub height: f32, // Height of the camera above the center
+}
+
+/**
+ * Enum for what the camera of a sequence follows.
+ */
+#[derive(Clone)]
+pub enum SequenceCamera {
+    Still,                      // The camera stands still, so only the scene is animated
+    Path( CameraPath ),         // The camera follows a recorded path, see camera::CameraPath
+    Turntable( Turntable ),     // The camera circles around a point
+}
+
+/**
+ * Struct for a sequence of frames, which are rendered one after the other and saved to numbered files.
+ * Each frame is of the scene at its own time, and is accumulated until it has all its samples before the next is started.
+ */
+#[derive(Clone)]
+pub struct SequenceSettings {
+    pub first_frame: u32,
+    pub last_frame: u32, // The last frame which is rendered, inclusive
+    pub start_frame: u32, // The frame which the render starts from, which is later than the first when resuming
+    pub fps: f32,
+    pub camera: SequenceCamera,
+}
+
+/**
+ * SequenceSettings functions.
+ */
+impl SequenceSettings {
+    /**
+     * Gets the time of the scene in a frame.
+     *
+     * @param frame The frame.
+     *
+     * @return The time, in seconds.
+     */
+    pub fn frame_time( &self, frame: u32 ) -> f32 {
+        frame as f32 / self.fps
+    }
+
+    /**
+     * Gets the file a frame is saved to, from the output of the render.
+     * A run of #s in the output is replaced by the frame's number, padded with zeros to as many digits, e.g. frame_####.png -> frame_0042.png.
+     * Without any #s, the number is put before the extension instead, e.g. render.png -> render_0042.png.
+     *
+     * @param output The output of the render.
+     * @param frame The frame.
+     *
+     * @return The path of the frame's file.
+     */
+    pub fn frame_path( output: &str, frame: u32 ) -> String {
+        if let Some( start ) = output.find( '#' ) {
+            let digits = output[start..].chars().take_while( |&c| c == '#' ).count();
+            return format!( "{}{frame:0digits$}{}", &output[..start], &output[start + digits..] );
+        }
+        output::with_suffix( output, &format!( "_{frame:04}" ) )
+    }
+
+    /**
+     * Gets the camera which a frame is seen from.
+     * With a shutter time, the camera is motion blurred over the time the shutter is open from the frame's time, see RTCamera::shutter_close.
+     *
+     * @param frame The frame.
+     * @param fov The field of view of the camera, in degrees, which paths have their own of.
+     * @param shutter_time How long the shutter is open, in seconds, or None to not blur the camera's motion.
+     * @param screen_size The size of the screen, in pixels.
+     *
+     * @return The camera, or None if the camera stands still.
+     */
+    pub fn camera( &self, frame: u32, fov: f32, shutter_time: Option<f32>, screen_size: glm::Vec2 ) -> Option<RTCamera> {
+        let time = self.frame_time( frame );
+        match &self.camera {
+            SequenceCamera::Still => { None },
+            SequenceCamera::Path( path ) => {
+                match shutter_time {
+                    Some( shutter_time ) => { path.sample_shutter( time, shutter_time, screen_size ) },
+                    None => { path.sample( time, screen_size ) },
+                }
+            },
+            SequenceCamera::Turntable( turntable ) => {
+                // (It goes around once over the frames, so it turns by a frame's share of the circle every 1 / fps seconds)
+                let frames = ( self.last_frame - self.first_frame + 1 ) as f32;
+                let angle_at = |time: f32| ( time - self.frame_time( self.first_frame ) ) * self.fps / frames * 2.0 * glm::pi::<f32>();
+                let view_at = |angle: f32| {
+                    let eye = turntable.center + glm::vec3( angle.sin() * turntable.radius, turntable.height, -angle.cos() * turntable.radius );
+                    RTCamera::look_at( eye, turntable.center, glm::vec3( 0.0, 1.0, 0.0 ), fov, screen_size )
+                };
+                let mut camera = view_at( angle_at( time ) );
+                camera.shutter_close = shutter_time.map( |shutter_time| view_at( angle_at( time + shutter_time ) ).local_to_world );
+                Some( camera )
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::CameraKeyframe;
+
+    fn settings( camera: SequenceCamera ) -> SequenceSettings {
+        SequenceSettings { first_frame: 10, last_frame: 33, start_frame: 10, fps: 24.0, camera }
+    }
+
+    #[test]
+    fn frame_numbers_replace_the_hashes() {
+        assert_eq!( SequenceSettings::frame_path( "frame_####.png", 42 ), "frame_0042.png" );
+        assert_eq!( SequenceSettings::frame_path( "frames/##_shot.exr", 7 ), "frames/07_shot.exr" );
+        // (Numbers longer than the run of #s aren't cut short)
+        assert_eq!( SequenceSettings::frame_path( "frame_##.png", 1234 ), "frame_1234.png" );
+    }
+
+    #[test]
+    fn frame_numbers_go_before_the_extension_without_hashes() {
+        assert_eq!( SequenceSettings::frame_path( "render.png", 42 ), "render_0042.png" );
+        assert_eq!( SequenceSettings::frame_path( "renders/shot.exr", 123456 ), "renders/shot_123456.exr" );
+    }
+
+    #[test]
+    fn turntables_go_around_once() {
+        let turntable = settings( SequenceCamera::Turntable( Turntable { center: glm::vec3( 1.0, 0.0, 2.0 ), radius: 5.0, height: 1.0 } ) );
+        let screen_size = glm::vec2( 64.0, 64.0 );
+        let first = turntable.camera( turntable.first_frame, 60.0, None, screen_size ).unwrap();
+        assert!( ( first.pos.x - 1.0 ).abs() < 1e-5 && ( first.pos.z + 3.0 ).abs() < 1e-5 && first.pos.y == 1.0 );
+
+        // (It would be back at the start one frame after the last, so the sequence loops)
+        let frames = turntable.last_frame - turntable.first_frame + 1;
+        let looped = turntable.camera( turntable.first_frame + frames, 60.0, None, screen_size ).unwrap();
+        assert!( ( looped.local_to_world - first.local_to_world ).abs().max() < 1e-4 );
+        let halfway = turntable.camera( turntable.first_frame + frames / 2, 60.0, None, screen_size ).unwrap();
+        assert!( ( halfway.pos.z - 7.0 ).abs() < 1e-4 );
+
+        assert!( settings( SequenceCamera::Still ).camera( 12, 60.0, None, screen_size ).is_none() );
+    }
+
+    #[test]
+    fn moving_cameras_are_blurred_over_the_shutter() {
+        let keyframe = |time: f32, x: f32| CameraKeyframe { time, position: glm::vec3( x, 0.0, 0.0 ), target: glm::vec3( x, 0.0, 10.0 ), fov: 60.0, focus_distance: 5.0 };
+        let path = CameraPath::new().add_keyframe( keyframe( 0.0, 0.0 ) ).add_keyframe( keyframe( 2.0, 2.0 ) );
+        let screen_size = glm::vec2( 64.0, 64.0 );
+        let sequence = settings( SequenceCamera::Path( path.clone() ) );
+
+        let camera = sequence.camera( 12, 60.0, Some( 0.25 ), screen_size ).unwrap();
+        assert_eq!( camera.shutter_close, Some( path.sample( 0.75, screen_size ).unwrap().local_to_world ) );
+        assert_eq!( sequence.camera( 12, 60.0, None, screen_size ).unwrap().shutter_close, None );
+
+        let turntable = settings( SequenceCamera::Turntable( Turntable { center: glm::zero(), radius: 5.0, height: 1.0 } ) );
+        let close = turntable.camera( 12, 60.0, Some( 1.0 / 24.0 ), screen_size ).unwrap().shutter_close.unwrap();
+        assert!( ( close - turntable.camera( 13, 60.0, None, screen_size ).unwrap().local_to_world ).abs().max() < 1e-5 );
+    }
+}
@@ -1,12 +1,17 @@
-use gl;
 use std::{
     ptr,
     str,
-    ffi::CString,
+    ffi::{CString, c_void},
     path::Path,
 };
 
-use crate::util::{byte_size_of_array, pointer_to_array};
+use tracing::{ debug, warn };
+
+use crate::device::{ self, BufferUsage, Device };
+use crate::error::EngineError;
+use crate::glapi::{ self, GlApi };
+use crate::util::{byte_size_of_array, bytes_of_array, pointer_to_array};
+use crate::gltracker::{ self, GlObject };
 
 /**
  * Struct for a compiled shader program.
@@ -21,40 +26,66 @@ pub struct Shader {
 pub struct ShaderBuilder {
     pid: u32,
     shaders: Vec::<u32>,
+    defines: String,
 }
 
 /**
  * Enum for different shader types.
  */
+#[derive(Clone, Copy)]
 pub enum ShaderType {
     Vertex,
     Fragment,
+    Compute,
 }
 
 /**
  * Type casting ShaderType -> GLenum.
 */
-impl Into<gl::types::GLenum> for ShaderType {
-    fn into ( self ) -> gl::types::GLenum {
-        match self {
+impl From<ShaderType> for gl::types::GLenum {
+    fn from ( shader_type: ShaderType ) -> gl::types::GLenum {
+        match shader_type {
             ShaderType::Vertex      => { gl::VERTEX_SHADER },
             ShaderType::Fragment    => { gl::FRAGMENT_SHADER },
+            ShaderType::Compute     => { gl::COMPUTE_SHADER },
         }
     }
 }
 
+/**
+ * Inserts defines into a shader's source, which must come after its #version line.
+ * Other APIs than desktop OpenGL have their own #version line, which the shader's is replaced with, see GlApi::shader_header().
+ *
+ * @param shader_src The shader's source.
+ * @param api The API the shader is compiled for.
+ * @param defines The #define lines.
+ *
+ * @return The source to compile.
+ */
+pub fn prepare_source( shader_src: &str, api: GlApi, defines: &str ) -> String {
+    match shader_src.split_once( '\n' ) {
+        Some( ( version, rest ) ) if version.starts_with( "#version" ) => { format!( "{}\n{defines}{rest}", api.shader_header( version ) ) },
+        _ => { format!( "{defines}{shader_src}" ) },
+    }
+}
+
 /**
  * ShaderType functions.
  */
 impl ShaderType {
     /**
      * Automatically detect filetype and create the corresponding enum.
+     *
+     * @param path The path of the shader, for the error.
+     * @param ext The extension of the shader.
      */
-    fn from_ext ( ext: &std::ffi::OsStr ) -> Result<ShaderType, String> {
-        match ext.to_str().expect("ERROR::SHADER::EXTENSION_NOT_RECOGNIZED") {
-            "vert" => { Ok(ShaderType::Vertex) },
-            "frag" => { Ok(ShaderType::Fragment) },
-            e => { Err(e.to_string()) },
+    #[allow(dead_code)]
+    fn from_ext ( path: &str, ext: &std::ffi::OsStr ) -> Result<ShaderType, EngineError> {
+        match ext.to_str() {
+            Some( "vert" ) => { Ok(ShaderType::Vertex) },
+            Some( "frag" ) => { Ok(ShaderType::Fragment) },
+            Some( "comp" ) => { Ok(ShaderType::Compute) },
+            _ => { Err( EngineError::shader( "EXTENSION_NOT_RECOGNIZED", path, ext.to_string_lossy() ) ) },
         }
     }
 }
@@ -67,7 +98,9 @@ impl ShaderBuilder {
      * Constructor.
      */
     pub unsafe fn new() -> ShaderBuilder {
-        ShaderBuilder { pid: gl::CreateProgram(), shaders: vec![] }
+        let pid = gl::CreateProgram();
+        gltracker::created( GlObject::Program, &[pid] );
+        ShaderBuilder { pid, shaders: vec![], defines: String::new() }
     }
 
     /**
@@ -80,8 +113,7 @@ impl ShaderBuilder {
     unsafe fn get_shader_err( &self, shader_id: u32 ) -> Result<String, String> {
         // Fetch log and success status
         let mut success = i32::from( gl::FALSE );
-        let mut log = Vec::with_capacity( 512 );
-        log.set_len( 512-1 );
+        let mut log = vec![0u8; 512-1];
         gl::GetShaderiv( shader_id, gl::COMPILE_STATUS, &mut success );
 
         // If successful, return Ok
@@ -97,7 +129,7 @@ impl ShaderBuilder {
             log.as_mut_ptr() as *mut gl::types::GLchar
         );
 
-        return Err( String::from_utf8_lossy( &log ).to_string() );
+        Err( String::from_utf8_lossy( &log ).to_string() )
     }
 
     /**
@@ -108,8 +140,7 @@ impl ShaderBuilder {
     unsafe fn get_linker_err( &self ) -> Result<String, String> {
         // Fetch log and success status
         let mut success = i32::from( gl::FALSE );
-        let mut log = Vec::with_capacity( 512 );
-        log.set_len( 512-1 );
+        let mut log = vec![0u8; 512-1];
         gl::GetProgramiv( self.pid, gl::LINK_STATUS, &mut success );
 
         // If successful, return Ok
@@ -125,7 +156,19 @@ impl ShaderBuilder {
             log.as_mut_ptr() as *mut gl::types::GLchar
         );
 
-        return Err( String::from_utf8_lossy( &log ).to_string() );
+        Err( String::from_utf8_lossy( &log ).to_string() )
+    }
+
+    /**
+     * Adds defines to the shaders compiled after this, which are inserted right after their #version line.
+     * 
+     * @param defines The names and values of the defines.
+     */
+    pub fn define( mut self, defines: &[( &str, u32 )] ) -> ShaderBuilder {
+        for ( name, value ) in defines {
+            self.defines += &format!( "#define {name} {value}\n" );
+        }
+        self
     }
 
     /**
@@ -133,24 +176,30 @@ impl ShaderBuilder {
      * 
      * @param shader_src The shader.
      * @param shader_type The type of shader.
+     * @param shader_path Path to the shader file, for the error if it doesn't compile.
+     * 
+     * @return The ShaderBuilder, or the compiler's log if the shader has errors.
      */
-    pub unsafe fn compile( mut self, shader_src: &str, shader_type: ShaderType ) -> ShaderBuilder {
+    pub unsafe fn compile( mut self, shader_src: &str, shader_type: ShaderType, shader_path: &str ) -> Result<ShaderBuilder, EngineError> {
+        let shader_src = prepare_source( shader_src, GlApi::current(), &self.defines );
+
         // Create and compile the shader
-        let ( shader, shader_cstr ) = (
-            gl::CreateShader( shader_type.into() ),
-            CString::new( shader_src.as_bytes() ).unwrap(),
-        );
+        // (GL takes the source as a C string, so a NUL byte in it would cut it short)
+        let shader_cstr = CString::new( shader_src.as_bytes() )
+            .map_err( |err| EngineError::shader( "INVALID_SOURCE", shader_path, format!( "The source has a NUL byte at {}", err.nul_position() ) ) )?;
+        let shader = gl::CreateShader( shader_type.into() );
         gl::ShaderSource( shader, 1, &shader_cstr.as_ptr(), ptr::null() );
         gl::CompileShader( shader );
 
         // Error handling
         if let Err(err) = self.get_shader_err( shader ) {
-            panic!("ERROR::SHADER::COMPILATION_FAILED\n{}", err);
+            gl::DeleteShader( shader );
+            return Err( EngineError::shader( "COMPILATION_FAILED", shader_path, err ) );
         }
 
         // Add compiled shader to pipeline and return
         self.shaders.push( shader );
-        self
+        Ok( self )
     }
 
     /**
@@ -158,31 +207,44 @@ impl ShaderBuilder {
      * 
      * @param shader_path Path to the shader file.
      */
-    pub unsafe fn attach_shader( self, shader_path: &str ) -> ShaderBuilder {
+    #[allow(dead_code)]
+    pub unsafe fn attach_shader( self, shader_path: &str ) -> Result<ShaderBuilder, EngineError> {
         let path = Path::new( shader_path );
         if let Some(ext) = path.extension() {
             // Attempt getting shadertype from  extension
-            let shader_type = ShaderType::from_ext( ext )
-                .expect( &format!( "ERROR::SHADER::FAILED_TO_PARSE_EXTENSION\n{}" , ext.to_string_lossy().to_string()) );
-
-            // Attempt reading contents of file
-            let shader_src = std::fs::read_to_string( path )
-                .expect( &format!( "ERROR:SHADER:FAILED_TO_READ_FILE\n{}", shader_path ) );
+            let shader_type = ShaderType::from_ext( shader_path, ext )?;
 
             // Compile and return
-            self.compile( &shader_src, shader_type )
+            self.attach_shader_as( shader_path, shader_type )
         } else {
-            panic!( "ERROR::SHADER::FAILED_TO_READ_EXTENSION" );
+            Err( EngineError::shader( "FAILED_TO_READ_EXTENSION", shader_path, "" ) )
         }
     }
 
+    /**
+     * Attaches a shader file to the ShaderBuilder pipeline as a type of shader, whatever its extension.
+     * This lets a file be built as more than one type, such as a fragment shader which is also a compute shader when COMPUTE is defined.
+     * 
+     * @param shader_path Path to the shader file.
+     * @param shader_type The type of shader.
+     */
+    pub unsafe fn attach_shader_as( self, shader_path: &str, shader_type: ShaderType ) -> Result<ShaderBuilder, EngineError> {
+        // Attempt reading contents of file
+        let shader_src = std::fs::read_to_string( shader_path )
+            .map_err( |err| EngineError::io( "SHADER::FAILED_TO_READ_FILE", shader_path, err ) )?;
+        debug!( path = shader_path, defines = self.defines.lines().count(), "Compiling shader" );
+
+        // Compile and return
+        self.compile( &shader_src, shader_type, shader_path )
+    }
+
     /**
      * Links and finalizes the shader pipeline.
      * 
-     * @return The finished shader pipeline.
+     * @return The finished shader pipeline, or the linker's log if the shaders don't link.
      */
     #[must_use = "The shader must be linked or it is useless."]
-    pub unsafe fn link( self ) -> Shader {
+    pub unsafe fn link( self ) -> Result<Shader, EngineError> {
         // Attach shaders
         for &shader in &self.shaders {
             gl::AttachShader( self.pid, shader );
@@ -190,19 +252,22 @@ impl ShaderBuilder {
 
         // Link and errorhandle
         gl::LinkProgram( self.pid );
-        if let Err(err) = self.get_linker_err() {
-            panic!("ERROR::SHADER::COMPILATION_FAILED\n{}", err);
-        }
+        let linked = self.get_linker_err();
 
         // Delete shaders as they are now part of the greater shader pipeline
         for &shader in &self.shaders {
             gl::DeleteShader( shader );
         }
+        if let Err(err) = linked {
+            gl::DeleteProgram( self.pid );
+            gltracker::deleted( GlObject::Program, &[self.pid] );
+            return Err( EngineError::shader( "LINKING_FAILED", "", err ) );
+        }
 
         // Return
-        Shader {
+        Ok( Shader {
             pid: self.pid,
-        }
+        } )
     }
 }
 
@@ -226,9 +291,21 @@ impl Shader {
      * @return The location of the uniform variable, or -1 if it does not exist.
      */
     pub unsafe fn get_uniform_location( &self, name: &str) -> gl::types::GLint {
-        let name_cstring = CString::new(name).unwrap();
-        let name_ptr: *const i8 = name_cstring.as_ptr() as *const i8;
-        return gl::GetUniformLocation(self.pid, name_ptr);
+        // (Setting a uniform at -1 does nothing, so a name which can't be looked up is only warned about)
+        self.try_uniform_location( name ).unwrap_or_else( |err| { warn!( "{err}" ); -1 } )
+    }
+
+    /**
+     * Gets the location of a uniform variable in a shader, see get_uniform_location().
+     *
+     * @param name The name of the uniform variable.
+     *
+     * @return The location of the uniform variable, or -1 if it does not exist, or an error if the name has a NUL byte.
+     */
+    pub unsafe fn try_uniform_location( &self, name: &str ) -> Result<gl::types::GLint, EngineError> {
+        let name_cstring = CString::new( name ).map_err( |_| EngineError::shader( "INVALID_UNIFORM_NAME", "", format!( "The uniform {name:?} has a NUL byte in its name" ) ) )?;
+        let name_ptr: *const i8 = name_cstring.as_ptr();
+        Ok( gl::GetUniformLocation( self.pid, name_ptr ) )
     }
 
     /**
@@ -237,19 +314,39 @@ impl Shader {
     pub unsafe fn set_uniform_mat4( &self, name: &str, value: glm::Mat4 ) {
         gl::UniformMatrix4fv( self.get_uniform_location( name ), 1, gl::FALSE, value.as_ptr());
     }
+
+    /**
+     * Deletes the shader program, once it is done with.
+     */
+    pub unsafe fn delete( self ) {
+        gl::DeleteProgram( self.pid );
+        gltracker::deleted( GlObject::Program, &[self.pid] );
+    }
 }
 
 /**
  * SSBO - Shader Storage Buffer Object. Can store at least 128MB.
  * https://www.khronos.org/opengl/wiki/Shader_Storage_Buffer_Object.
  */
-#[allow(dead_code)]
+#[allow(dead_code, clippy::upper_case_acronyms)]
 pub struct SSBO<T> {
     pid: u32,
     bid: u32,
     binding: u32,
     data: Vec<T>,
     data_size: isize,
+    mapping: Option<PersistentMapping>,
+}
+
+/**
+ * Struct for the copies of a persistently mapped SSBO's data, which are written in turn so the GPU can still be reading the others.
+ * @see SSBOBuilder::set_persistent()
+ */
+struct PersistentMapping {
+    pointer: *mut c_void,
+    stride: isize, // Distance between the copies, in bytes
+    fences: Vec<gl::types::GLsync>, // Fence after the last commands which read each copy, or null if there are none left to wait for
+    current: usize, // The copy the shader reads
 }
 
 /**
@@ -261,6 +358,7 @@ pub struct SSBOBuilder<T> {
     bid: u32,
     binding: u32,
     data: Vec<T>,
+    copies: usize,
 }
 
 /**
@@ -273,14 +371,12 @@ impl<T> SSBOBuilder<T> {
      */
     #[must_use = "The SSBO must be initialized."]
     pub unsafe fn new() -> SSBOBuilder<T> {
-        let mut buffer_id: gl::types::GLuint = 0;
-        gl::GenBuffers(1, &mut buffer_id);
-
         SSBOBuilder {
             pid: 0,
-            bid: buffer_id,
+            bid: device::current().create_buffer(),
             binding: 0,
             data: Vec::new(),
+            copies: 0,
         }
     }
 
@@ -291,21 +387,13 @@ impl<T> SSBOBuilder<T> {
      * @param data The data.
      */
     #[must_use = "The SSBO must have data to be initialized."]
-    pub unsafe fn set_data( self, data: Vec<T> ) -> SSBOBuilder<T> {
-        //let data = &data[..];
-
-        // Get data size and pointer reference
-        let ( data_size, data_ref ) = (
-            byte_size_of_array( &data ),
-            pointer_to_array( &data ),
-        );
-
+    pub unsafe fn set_data( mut self, data: Vec<T> ) -> SSBOBuilder<T> {
         // Set buffer data
-        gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, self.bid);
-        gl::BufferData(gl::SHADER_STORAGE_BUFFER, data_size, data_ref, gl::DYNAMIC_COPY);
-        gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, 0);
+        let bytes = bytes_of_array( &data );
+        device::current().allocate_buffer( self.bid, bytes.len(), Some( bytes ), BufferUsage::Storage );
 
-        // Return
+        // Keep a copy of the data on the CPU and return
+        self.data = data;
         self
     }
 
@@ -315,25 +403,43 @@ impl<T> SSBOBuilder<T> {
      * @param shader_pid The program ID of the compiled shader which uses the SSBO.
      * @param shader_binding The binding number of the SSBO within the shader.
      * @param shader_buffer_name The name of the SSBO/buffer within the shader.
+     *
+     * @return The SSBOBuilder, or an error if the name has a NUL byte.
      */
     #[must_use = "The SSBO must contain details about the shader it is used in to function."]
-    pub unsafe fn set_shader_details( mut self, shader_pid: u32, shader_binding: u32, shader_buffer_name: &str ) -> SSBOBuilder<T> {
+    pub unsafe fn set_shader_details( mut self, shader_pid: u32, shader_binding: u32, shader_buffer_name: &str ) -> Result<SSBOBuilder<T>, EngineError> {
         // Set vars
         self.pid = shader_pid;
         self.binding = shader_binding;
         
         // Find block index and connect to it
-        let name_c_str = CString::new( shader_buffer_name ).unwrap();
+        let name_c_str = CString::new( shader_buffer_name )
+            .map_err( |_| EngineError::shader( "INVALID_BUFFER_NAME", "", format!( "The buffer {shader_buffer_name:?} has a NUL byte in its name" ) ) )?;
         let block_index: gl::types::GLuint = gl::GetProgramResourceIndex(
             shader_pid,
             gl::SHADER_STORAGE_BLOCK,
-            name_c_str.as_ptr() as *const i8
+            name_c_str.as_ptr()
         );
         
-        gl::ShaderStorageBlockBinding( shader_pid, block_index, shader_binding );
-        gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, shader_binding, self.bid);
+        // (ES can't rebind blocks, so there the binding must be the one in the shader's layout)
+        if GlApi::current() == GlApi::Desktop {
+            gl::ShaderStorageBlockBinding( shader_pid, block_index, shader_binding );
+        }
+        device::current().bind_storage_buffer( self.bid, shader_binding );
 
         // Return
+        Ok( self )
+    }
+
+    /**
+     * Makes the SSBO persistently mapped, for data which changes every frame.
+     * The buffer holds several copies of the data, and each update writes the next one straight through the mapping,
+     * waiting on a fence only if the GPU is still reading it, where updating a buffer in use would otherwise stall until the GPU is done with it.
+     * 
+     * @param copies How many copies the buffer holds, such as 3 for triple buffering.
+     */
+    pub fn set_persistent( mut self, copies: usize ) -> SSBOBuilder<T> {
+        self.copies = copies;
         self
     }
 
@@ -344,13 +450,42 @@ impl<T> SSBOBuilder<T> {
      * @return The fully initialized SSBO object.
      */
     #[must_use = "The SSBO must be linked to a shader or it is useless."]
-    pub unsafe fn link ( self ) -> SSBO<T> {
+    pub unsafe fn link ( mut self ) -> SSBO<T> {
+        let data_size = byte_size_of_array( &self.data );
+        let mut mapping = None;
+        // (Without immutable storage, as on ES, the buffer is orphaned on each update instead)
+        if self.copies > 0 && glapi::has_buffer_storage() {
+            // Replace the buffer's data with immutable storage for the copies, which are spaced as far apart as the bindings' offsets must be
+            let mut alignment = 0;
+            gl::GetIntegerv( gl::SHADER_STORAGE_BUFFER_OFFSET_ALIGNMENT, &mut alignment );
+            let stride = ( data_size.max( 1 ) + alignment as isize - 1 ) / alignment as isize * alignment as isize;
+            let flags = gl::MAP_WRITE_BIT | gl::MAP_PERSISTENT_BIT | gl::MAP_COHERENT_BIT;
+            gl::BindBuffer( gl::SHADER_STORAGE_BUFFER, self.bid );
+            gl::BufferStorage( gl::SHADER_STORAGE_BUFFER, stride * self.copies as isize, ptr::null(), flags );
+            let pointer = gl::MapBufferRange( gl::SHADER_STORAGE_BUFFER, 0, stride * self.copies as isize, flags );
+            gl::BindBuffer( gl::SHADER_STORAGE_BUFFER, 0 );
+
+            if pointer.is_null() {
+                // (Drivers which can't map it get a new buffer which is orphaned instead, as immutable storage can't be given data another way)
+                debug!( "The buffer could not be persistently mapped, so it is orphaned on each update instead" );
+                device::current().delete_buffer( self.bid );
+                self.bid = device::current().create_buffer();
+                device::current().allocate_buffer( self.bid, data_size as usize, Some( bytes_of_array( &self.data ) ), BufferUsage::Storage );
+                device::current().bind_storage_buffer( self.bid, self.binding );
+            } else {
+                ( pointer as *mut u8 ).copy_from_nonoverlapping( pointer_to_array( &self.data ) as *const u8, data_size as usize );
+                gl::BindBufferRange( gl::SHADER_STORAGE_BUFFER, self.binding, self.bid, 0, data_size );
+                mapping = Some( PersistentMapping { pointer, stride, fences: vec![ptr::null(); self.copies], current: 0 } );
+            }
+        }
+
         SSBO {
             pid: self.pid,
             bid: self.bid,
             binding: self.binding,
+            data_size,
             data: self.data,
-            data_size: 0,//byte_size_of_array( &self.data ),
+            mapping,
         }
     }
 }
@@ -362,6 +497,8 @@ impl<T> SSBO<T> {
     /**
      * Updates the data in the SSBO.
      * The new data size cannot exceed the original data size.
+     * Unless the SSBO is persistently mapped, the buffer's storage is orphaned first, so the driver gives it new storage to write to
+     * while the frames in flight keep reading the old one, instead of waiting for them to finish.
      * 
      * @param new_data The new data.
      */
@@ -373,13 +510,52 @@ impl<T> SSBO<T> {
         );
 
         // Copy new data into buffer
-        gl::BindBuffer( gl::SHADER_STORAGE_BUFFER, self.bid );
-        let p = gl::MapBuffer( gl::SHADER_STORAGE_BUFFER, gl::WRITE_ONLY );
-        p.copy_from( new_data_ref, new_data_size as usize );
-        gl::UnmapBuffer(gl::SHADER_STORAGE_BUFFER);
-        gl::BindBuffer( gl::SHADER_STORAGE_BUFFER, 0 );
+        if let Some( mapping ) = &mut self.mapping {
+            // (Everything so far read the current copy, so it is fenced before writing the next one, which waits for its own fence if it has one)
+            mapping.fences[mapping.current] = gl::FenceSync( gl::SYNC_GPU_COMMANDS_COMPLETE, 0 );
+            mapping.current = ( mapping.current + 1 ) % mapping.fences.len();
+            let fence = std::mem::replace( &mut mapping.fences[mapping.current], ptr::null() );
+            if !fence.is_null() {
+                while gl::ClientWaitSync( fence, gl::SYNC_FLUSH_COMMANDS_BIT, 1_000_000 ) == gl::TIMEOUT_EXPIRED {}
+                gl::DeleteSync( fence );
+            }
+
+            let offset = mapping.current as isize * mapping.stride;
+            ( mapping.pointer as *mut u8 ).offset( offset ).copy_from_nonoverlapping( new_data_ref as *const u8, new_data_size as usize );
+            gl::BindBufferRange( gl::SHADER_STORAGE_BUFFER, self.binding, self.bid, offset, self.data_size );
+        } else {
+            device::current().allocate_buffer( self.bid, self.data_size as usize, None, BufferUsage::Storage );
+            device::current().write_buffer( self.bid, 0, bytes_of_array( &new_data ) );
+        }
 
-        // Return
+        // Keep a copy of the data on the CPU and return
+        self.data = new_data;
         self
     }
+
+    /**
+     * Gets the CPU-side copy of the data in the SSBO.
+     */
+    pub fn data( &self ) -> &[T] {
+        &self.data
+    }
+
+    /**
+     * Gets the GL name of the SSBO's buffer.
+     */
+    pub fn id( &self ) -> u32 {
+        self.bid
+    }
+
+    /**
+     * Deletes the SSBO's buffer, which also unmaps it, and the fences of its copies.
+     */
+    pub unsafe fn delete( self ) {
+        if let Some( mapping ) = self.mapping {
+            for fence in mapping.fences.into_iter().filter( |fence| !fence.is_null() ) {
+                gl::DeleteSync( fence );
+            }
+        }
+        device::current().delete_buffer( self.bid );
+    }
 }
\ No newline at end of file
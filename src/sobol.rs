@@ -0,0 +1,73 @@
+// Primitive polynomials and initial direction numbers for the Sobol sequence, from Joe & Kuo (new-joe-kuo-6.21201)
+// https://web.maths.unsw.edu.au/~fkuo/sobol/
+// Each entry is (degree s, polynomial coefficients a, initial direction numbers m_1..m_s), starting at the second dimension
+const DIRECTION_NUMBERS: [(u32, u32, &[u32]); 31] = [
+    ( 1, 0,  &[1] ),
+    ( 2, 1,  &[1, 3] ),
+    ( 3, 1,  &[1, 3, 1] ),
+    ( 3, 2,  &[1, 1, 1] ),
+    ( 4, 1,  &[1, 1, 3, 3] ),
+    ( 4, 4,  &[1, 3, 5, 13] ),
+    ( 5, 2,  &[1, 1, 5, 5, 17] ),
+    ( 5, 4,  &[1, 1, 5, 5, 5] ),
+    ( 5, 7,  &[1, 1, 7, 11, 19] ),
+    ( 5, 11, &[1, 1, 5, 1, 1] ),
+    ( 5, 13, &[1, 1, 1, 3, 11] ),
+    ( 5, 14, &[1, 3, 5, 5, 31] ),
+    ( 6, 1,  &[1, 3, 3, 9, 7, 49] ),
+    ( 6, 13, &[1, 1, 1, 15, 21, 21] ),
+    ( 6, 16, &[1, 3, 1, 13, 27, 49] ),
+    ( 6, 19, &[1, 1, 1, 15, 7, 5] ),
+    ( 6, 22, &[1, 3, 1, 15, 13, 25] ),
+    ( 6, 25, &[1, 1, 5, 5, 19, 61] ),
+    ( 7, 1,  &[1, 3, 7, 11, 23, 15, 103] ),
+    ( 7, 4,  &[1, 3, 7, 13, 13, 15, 69] ),
+    ( 7, 7,  &[1, 1, 3, 13, 7, 35, 63] ),
+    ( 7, 8,  &[1, 3, 5, 9, 1, 25, 53] ),
+    ( 7, 14, &[1, 3, 1, 13, 9, 35, 107] ),
+    ( 7, 19, &[1, 3, 1, 5, 27, 61, 31] ),
+    ( 7, 21, &[1, 1, 5, 11, 19, 41, 61] ),
+    ( 7, 28, &[1, 3, 5, 3, 3, 13, 69] ),
+    ( 7, 31, &[1, 1, 7, 13, 1, 19, 1] ),
+    ( 7, 32, &[1, 3, 7, 5, 13, 19, 59] ),
+    ( 7, 37, &[1, 1, 3, 9, 25, 29, 41] ),
+    ( 7, 41, &[1, 3, 5, 13, 23, 1, 55] ),
+    ( 7, 42, &[1, 3, 7, 3, 13, 59, 17] ),
+];
+
+// Amount of dimensions the Sobol sequence is generated for
+pub const SOBOL_DIMENSIONS: usize = DIRECTION_NUMBERS.len() + 1;
+
+/**
+ * Generates the generator matrices of the Sobol sequence.
+ * Each dimension's matrix is 32 direction numbers, where the i-th sample is the XOR of the direction numbers of its set bits.
+ * https://web.maths.unsw.edu.au/~fkuo/sobol/joe-kuo-notes.pdf
+ *
+ * @return The matrices of all dimensions, one after the other.
+ */
+pub fn generator_matrices() -> Vec<u32> {
+    let mut matrices = Vec::with_capacity( SOBOL_DIMENSIONS * 32 );
+
+    // The first dimension is the van der Corput sequence
+    matrices.extend( ( 0..32 ).map( |i| 1u32 << ( 31 - i ) ) );
+
+    // The remaining dimensions are generated by a recurrence from their initial direction numbers
+    for ( s, a, m ) in DIRECTION_NUMBERS {
+        let s = s as usize;
+        let mut v = [0u32; 32];
+        for i in 0..32 {
+            v[i] = if i < s {
+                m[i] << ( 31 - i )
+            } else {
+                let mut value = v[i - s] ^ ( v[i - s] >> s );
+                for k in 1..s {
+                    value ^= ( ( a >> ( s - 1 - k ) ) & 1 ) * v[i - k];
+                }
+                value
+            };
+        }
+        matrices.extend_from_slice( &v );
+    }
+
+    matrices
+}
@@ -0,0 +1,191 @@
+use crate::device::{ self, BufferUsage, Device };
+use crate::glapi;
+use crate::shader::Shader;
+
+// Binding of the ray counter in the shader, which counts the primary and then the secondary rays
+const RAY_COUNTER_BINDING: u32 = 15;
+
+// GL_NVX_gpu_memory_info, which tells how much video memory there is and how much is left (in KiB)
+const NVX_GPU_MEMORY_INFO: &str = "GL_NVX_gpu_memory_info";
+const GPU_MEMORY_INFO_TOTAL_AVAILABLE_MEMORY_NVX: gl::types::GLenum = 0x9048;
+const GPU_MEMORY_INFO_CURRENT_AVAILABLE_VIDMEM_NVX: gl::types::GLenum = 0x9049;
+
+/**
+ * Struct for the statistics of the latest frames, see RenderStats.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct FrameStats {
+    pub frame_time: f32, // The smoothed time a frame takes, in milliseconds
+    pub samples: u32, // Samples accumulated into each pixel
+    pub primary_rays_per_second: f64, // The rays traced from the camera
+    pub secondary_rays_per_second: f64, // Every other ray, such as bounces and shadow rays
+    pub bvh_nodes: usize, // Nodes of the acceleration structure, which are the scene's bounds and a bounding box per mesh
+    pub vram_used: Option<u64>, // Video memory in use, in bytes, or None if the driver doesn't tell
+}
+
+impl std::fmt::Display for FrameStats {
+    fn fmt( &self, f: &mut std::fmt::Formatter ) -> std::fmt::Result {
+        write!( f, "{:.1} ms, {} spp, {:.1} M primary rays/s, {:.1} M secondary rays/s, {} BVH nodes",
+            self.frame_time, self.samples, self.primary_rays_per_second / 1e6, self.secondary_rays_per_second / 1e6, self.bvh_nodes )?;
+        match self.vram_used {
+            Some( vram_used ) => { write!( f, ", {} MB VRAM", vram_used / ( 1024 * 1024 ) ) },
+            None => { Ok( () ) },
+        }
+    }
+}
+
+/**
+ * Checks if the driver has an OpenGL extension.
+ *
+ * @param name The name of the extension, such as "GL_NVX_gpu_memory_info".
+ */
+unsafe fn has_extension( name: &str ) -> bool {
+    let mut count = 0;
+    gl::GetIntegerv( gl::NUM_EXTENSIONS, &mut count );
+    ( 0..count.max( 0 ) as u32 ).any( |i| {
+        let extension = gl::GetStringi( gl::EXTENSIONS, i );
+        !extension.is_null() && std::ffi::CStr::from_ptr( extension as *const _ ).to_bytes() == name.as_bytes()
+    } )
+}
+
+/**
+ * Struct for gathering the statistics of the render, such as how many rays it traces per second.
+ * While counting, the shader adds every ray it traces to a counter, telling the primary rays (the first ray after each camera ray) from the rest.
+ * Counting has a cost of its own, so it is only done while the stats are on.
+ *
+ * The counter is double-buffered, and each is read two frames after it was counted into, so reading it doesn't wait for the frame to finish.
+ */
+pub struct RenderStats {
+    enabled: bool,
+    buffers: [device::Buffer; 2],
+    current: usize,
+    counted: [bool; 2], // If each counter holds a frame's rays, which haven't been read yet
+    frame_time: Option<f32>, // The smoothed frame time, in seconds
+    rays: [u32; 2], // The primary and secondary rays of the latest frame which was read
+    vram_info: bool, // If the driver tells how much video memory is used
+}
+
+/**
+ * RenderStats functions.
+ */
+#[allow(dead_code)]
+impl RenderStats {
+    /**
+     * Creates the ray counters.
+     *
+     * @param enabled If the rays are counted from the start.
+     */
+    pub unsafe fn new( enabled: bool ) -> RenderStats {
+        let mut stats = RenderStats {
+            enabled,
+            buffers: [0; 2],
+            current: 0,
+            counted: [false; 2],
+            frame_time: None,
+            rays: [0; 2],
+            vram_info: has_extension( NVX_GPU_MEMORY_INFO ),
+        };
+        for buffer in stats.buffers.iter_mut() {
+            *buffer = device::current().create_buffer();
+            device::current().allocate_buffer( *buffer, 8, None, BufferUsage::Readback );
+        }
+        stats
+    }
+
+    /**
+     * Deletes the ray counters, once they are done with.
+     */
+    pub unsafe fn delete( self ) {
+        for buffer in self.buffers {
+            device::current().delete_buffer( buffer );
+        }
+    }
+
+    /**
+     * Turns the stats on or off, which forgets the rays counted so far.
+     *
+     * @param enabled If the rays are counted.
+     */
+    pub fn set_enabled( &mut self, enabled: bool ) {
+        self.enabled = enabled;
+        self.counted = [false; 2];
+        self.rays = [0; 2];
+    }
+
+    /**
+     * Gets if the stats are on.
+     */
+    pub fn enabled( &self ) -> bool {
+        self.enabled
+    }
+
+    /**
+     * Starts counting the rays of a frame, reading back the rays of the frame the counter was last used for.
+     * The shader must be active.
+     *
+     * @param shader The raytracing shader.
+     * @param frame_time How long the last frame took, in seconds.
+     */
+    pub unsafe fn begin_frame( &mut self, shader: &Shader, frame_time: f32 ) {
+        gl::Uniform1ui( shader.get_uniform_location( "countRays" ), self.enabled as u32 );
+        if !self.enabled {
+            return;
+        }
+        self.frame_time = Some( self.frame_time.map_or( frame_time, |smoothed| smoothed + ( frame_time - smoothed ) * 0.1 ) );
+
+        self.current = 1 - self.current;
+        let buffer = self.buffers[self.current];
+        gl::BindBuffer( gl::SHADER_STORAGE_BUFFER, buffer );
+        if self.counted[self.current] {
+            glapi::read_buffer( gl::SHADER_STORAGE_BUFFER, 0, &mut self.rays );
+        }
+        glapi::clear_buffer_u32( gl::SHADER_STORAGE_BUFFER, 0, None, 0 );
+        gl::BindBuffer( gl::SHADER_STORAGE_BUFFER, 0 );
+        device::current().bind_storage_buffer( buffer, RAY_COUNTER_BINDING );
+        self.counted[self.current] = true;
+    }
+
+    /**
+     * Gets the rays of the latest frame which was read, see begin_frame().
+     *
+     * @return The primary and the secondary rays, which are 0 until a frame has been read.
+     */
+    pub fn rays( &self ) -> ( u32, u32 ) {
+        ( self.rays[0], self.rays[1] )
+    }
+
+    /**
+     * Gets how much video memory is in use, if the driver tells.
+     *
+     * @return The memory in use in bytes, or None.
+     */
+    pub unsafe fn vram_used( &self ) -> Option<u64> {
+        if !self.vram_info {
+            return None;
+        }
+        let ( mut total, mut available ) = ( 0, 0 );
+        gl::GetIntegerv( GPU_MEMORY_INFO_TOTAL_AVAILABLE_MEMORY_NVX, &mut total );
+        gl::GetIntegerv( GPU_MEMORY_INFO_CURRENT_AVAILABLE_VIDMEM_NVX, &mut available );
+        Some( ( total - available ).max( 0 ) as u64 * 1024 )
+    }
+
+    /**
+     * Gets the statistics of the latest frames.
+     * The rays are of a frame from two frames ago, and are 0 until one has been read.
+     *
+     * @param samples Samples accumulated into each pixel.
+     * @param bvh_nodes Nodes of the scene's acceleration structure.
+     */
+    pub unsafe fn frame_stats( &self, samples: u32, bvh_nodes: usize ) -> FrameStats {
+        let frame_time = self.frame_time.unwrap_or( 0.0 );
+        let per_second = |rays: u32| if frame_time > 0.0 { rays as f64 / frame_time as f64 } else { 0.0 };
+        FrameStats {
+            frame_time: frame_time * 1000.0,
+            samples,
+            primary_rays_per_second: per_second( self.rays[0] ),
+            secondary_rays_per_second: per_second( self.rays[1] ),
+            bvh_nodes,
+            vram_used: self.vram_used(),
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use crate::device::{ self, Device, TextureFilter, TextureFormat };
+use crate::error::EngineError;
+use crate::shader::Shader;
+use crate::util;
+use crate::gltracker::{ self, GlObject };
+
+/**
+ * Struct for the settings of temporal anti-aliasing.
+ */
+#[derive(Clone, Copy, PartialEq)]
+pub struct TaaSettings {
+    pub alpha: f32, // How much of each new frame is blended into the history, where lower is smoother but lags more
+}
+
+/**
+ * TaaSettings functions.
+ */
+impl TaaSettings {
+    /**
+     * Sends the settings to the TAA shader.
+     * The shader must be active.
+     *
+     * @param shader The TAA shader.
+     * @param uniform_name The name of the settings uniform.
+     */
+    pub unsafe fn send_uniform( &self, shader: &Shader, uniform_name: &str ) {
+        gl::Uniform1f( shader.get_uniform_location( format!("{uniform_name}.alpha").as_str() ), self.alpha );
+    }
+}
+
+/**
+ * Struct for temporal anti-aliasing (TAA), for when the camera moves and the accumulation restarts every frame.
+ * The camera is jittered by a different sub-pixel offset every frame, and each frame is blended into a history which follows the surfaces on the screen.
+ * The motion vectors are the ones the denoiser renders with its guides, and the history is clamped to the colors around each pixel so it doesn't ghost.
+ *
+ * Once the camera stops, the accumulated image takes over as it gets more frames than the history would weight.
+ * Like the accumulator, it owns two framebuffers which it renders to in turn, reading the history from the other.
+ */
+pub struct TemporalAA {
+    framebuffers: [u32; 2],
+    textures: [device::Texture; 2],
+    width: u32,
+    height: u32,
+    current: usize,
+    has_history: bool,
+}
+
+/**
+ * TemporalAA functions.
+ */
+impl TemporalAA {
+    /**
+     * Creates the framebuffers.
+     *
+     * @param width The width of the framebuffers, in pixels.
+     * @param height The height of the framebuffers, in pixels.
+     *
+     * @return The TAA, or an error if the driver can't render to its framebuffers.
+     */
+    pub unsafe fn new( width: u32, height: u32 ) -> Result<TemporalAA, EngineError> {
+        let mut taa = TemporalAA {
+            framebuffers: [0; 2],
+            textures: [0; 2],
+            width,
+            height,
+            current: 0,
+            has_history: false,
+        };
+        taa.create_framebuffers()?;
+        Ok( taa )
+    }
+
+    /**
+     * Creates the framebuffers and their textures, at the current size.
+     * The textures are filtered linearly, as the history is sampled between pixels.
+     *
+     * @return An error if a framebuffer is incomplete, whose objects are still created, so they are deleted along with the rest.
+     */
+    unsafe fn create_framebuffers( &mut self ) -> Result<(), EngineError> {
+        gl::GenFramebuffers( 2, self.framebuffers.as_mut_ptr() );
+        gltracker::created( GlObject::Framebuffer, &self.framebuffers );
+
+        let mut complete = Ok( () );
+        for i in 0..2 {
+            self.textures[i] = device::current().create_texture( self.width, self.height, TextureFormat::Rgba32F, TextureFilter::Linear );
+            gl::BindFramebuffer( gl::FRAMEBUFFER, self.framebuffers[i] );
+            gl::FramebufferTexture2D( gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D, self.textures[i], 0 );
+            complete = complete.and( util::check_framebuffer( "TAA::FRAMEBUFFER_INCOMPLETE" ) );
+        }
+
+        gl::BindTexture( gl::TEXTURE_2D, 0 );
+        gl::BindFramebuffer( gl::FRAMEBUFFER, 0 );
+        complete
+    }
+
+    /**
+     * Deletes the framebuffers and their textures.
+     */
+    unsafe fn delete_framebuffers( &mut self ) {
+        gl::DeleteFramebuffers( 2, self.framebuffers.as_ptr() );
+        gltracker::deleted( GlObject::Framebuffer, &self.framebuffers );
+        for texture in self.textures {
+            device::current().delete_texture( texture );
+        }
+    }
+
+    /**
+     * Deletes the TAA's framebuffers and textures, once it is done with.
+     */
+    pub unsafe fn delete( mut self ) {
+        self.delete_framebuffers();
+    }
+
+    /**
+     * Resizes the framebuffers, which forgets the history.
+     *
+     * @param width The new width, in pixels.
+     * @param height The new height, in pixels.
+     *
+     * @return An error if the driver can't render to the framebuffers at the new size.
+     */
+    pub unsafe fn resize( &mut self, width: u32, height: u32 ) -> Result<(), EngineError> {
+        self.delete_framebuffers();
+        self.width = width;
+        self.height = height;
+        self.reset();
+        self.create_framebuffers()
+    }
+
+    /**
+     * Forgets the history, so the next frame starts it over.
+     */
+    pub fn reset( &mut self ) {
+        self.has_history = false;
+    }
+
+    /**
+     * Prepares the TAA shader for resolving a frame, and binds the framebuffer to write to.
+     * The shader must be active.
+     *
+     * @param shader The TAA shader.
+     * @param image The texture holding the current image.
+     * @param motion The texture holding the motion vectors, see Denoiser::motion_texture().
+     * @param frame_count How many frames have been accumulated into the image.
+     * @param settings The settings of the TAA.
+     */
+    pub unsafe fn begin_pass( &self, shader: &Shader, image: u32, motion: u32, frame_count: u32, settings: &TaaSettings ) {
+        let ( read, write ) = ( self.current, 1 - self.current );
+
+        gl::BindFramebuffer( gl::FRAMEBUFFER, self.framebuffers[write] );
+        gl::Viewport( 0, 0, self.width as i32, self.height as i32 );
+
+        for ( unit, ( texture, name ) ) in [( image, "image" ), ( self.textures[read], "history" ), ( motion, "guideMotion" )].into_iter().enumerate() {
+            gl::ActiveTexture( gl::TEXTURE0 + unit as u32 );
+            gl::BindTexture( gl::TEXTURE_2D, texture );
+            gl::Uniform1i( shader.get_uniform_location( name ), unit as i32 );
+        }
+        settings.send_uniform( shader, "taa" );
+        gl::Uniform1ui( shader.get_uniform_location( "historyValid" ), self.has_history as u32 );
+        gl::Uniform1ui( shader.get_uniform_location( "frameCount" ), frame_count );
+    }
+
+    /**
+     * Finishes resolving a frame, making it the history.
+     * Unbinds the framebuffer, so following draws go to the screen.
+     */
+    pub unsafe fn end_pass( &mut self ) {
+        gl::BindFramebuffer( gl::FRAMEBUFFER, 0 );
+        self.current = 1 - self.current;
+        self.has_history = true;
+    }
+
+    /**
+     * Gets the texture holding the latest resolved frame.
+     */
+    pub fn texture( &self ) -> u32 {
+        self.textures[self.current]
+    }
+}
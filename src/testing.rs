@@ -0,0 +1,366 @@
+use std::thread;
+use std::panic::{ self, AssertUnwindSafe };
+use std::sync::{ Mutex, OnceLock, mpsc };
+
+use tracing::warn;
+
+use crate::capabilities::Capabilities;
+use crate::error::EngineError;
+use crate::glapi::GlApi;
+use crate::gltracker;
+use crate::headless;
+use crate::integrators::{ Debug, DebugView };
+use crate::offscreen::{ self, OffscreenRenderer };
+use crate::raytracing::{ self, RTCamera, RTIntegrator, RTMaterial, RTRay, RTSettings, RTSphere, RTTriangle };
+
+// Size of the test context's own framebuffer, which nothing is drawn to, as the frames are rendered into an accumulator of their own size
+const CONTEXT_SIZE: u32 = 16;
+
+// The EGL platform which needs neither a display nor a window, from EGL_MESA_platform_surfaceless
+const EGL_PLATFORM_SURFACELESS_MESA: khronos_egl::Enum = 0x31DD;
+
+type GlJob = Box<dyn FnOnce() + Send>;
+
+/**
+ * Struct for the thread which the tests' OpenGL context is current on.
+ * Contexts can only be current on one thread at a time, and the tests run on many, so they send what they render to this one instead.
+ */
+struct GlThread {
+    jobs: Mutex<mpsc::Sender<GlJob>>,
+    error: Option<String>, // Why the context couldn't be created, which it can't without OSMesa, a display or EGL
+}
+
+/**
+ * Creates a surfaceless EGL context and makes it current, for when there is neither OSMesa nor a display.
+ * Mesa has one whether or not there is a GPU, which falls back to llvmpipe rendering on the CPU.
+ *
+ * @return The EGL library, which the context is kept current with while it is loaded, or an error if there is no such context.
+ */
+unsafe fn create_egl_context() -> Result<khronos_egl::DynamicInstance<khronos_egl::EGL1_5>, String> {
+    let library = libloading::Library::new( "libEGL.so.1" ).map_err( |err| err.to_string() )?;
+    let egl = khronos_egl::DynamicInstance::<khronos_egl::EGL1_5>::load_required_from( library ).map_err( |err| err.to_string() )?;
+    let display = egl.get_platform_display( EGL_PLATFORM_SURFACELESS_MESA, khronos_egl::DEFAULT_DISPLAY, &[khronos_egl::ATTRIB_NONE] ).map_err( |err| err.to_string() )?;
+    egl.initialize( display ).map_err( |err| err.to_string() )?;
+    egl.bind_api( khronos_egl::OPENGL_API ).map_err( |err| err.to_string() )?;
+    let config = egl.choose_first_config( display, &[khronos_egl::SURFACE_TYPE, 0, khronos_egl::RENDERABLE_TYPE, khronos_egl::OPENGL_BIT, khronos_egl::NONE] )
+        .map_err( |err| err.to_string() )?
+        .ok_or( "There is no surfaceless config which renders with OpenGL" )?;
+    let ( major, minor ) = GlApi::Desktop.version();
+    let context = egl.create_context( display, config, None, &[
+        khronos_egl::CONTEXT_MAJOR_VERSION, major as khronos_egl::Int,
+        khronos_egl::CONTEXT_MINOR_VERSION, minor as khronos_egl::Int,
+        khronos_egl::CONTEXT_OPENGL_PROFILE_MASK, khronos_egl::CONTEXT_OPENGL_CORE_PROFILE_BIT,
+        khronos_egl::NONE,
+    ] ).map_err( |err| err.to_string() )?;
+    egl.make_current( display, None, None, Some( context ) ).map_err( |err| err.to_string() )?;
+    gl::load_with( |symbol| egl.get_proc_address( symbol ).map_or( std::ptr::null(), |function| function as *const _ ) );
+    gltracker::context_made_current();
+    GlApi::detect();
+    Capabilities::detect();
+    Ok( egl )
+}
+
+/**
+ * Gets the thread of the tests' context, starting it and creating the context the first time.
+ */
+fn gl_thread() -> &'static GlThread {
+    static GL_THREAD: OnceLock<GlThread> = OnceLock::new();
+    GL_THREAD.get_or_init( || {
+        let ( jobs, receiver ) = mpsc::channel::<GlJob>();
+        let ( started, error ) = mpsc::channel();
+        thread::spawn( move || {
+            // (The context is created like offline renders create theirs, and else as a surfaceless EGL one)
+            let context: Result<Box<dyn std::any::Any>, String> = headless::create_context( CONTEXT_SIZE, CONTEXT_SIZE )
+                .and_then( |( context, event_loop )| Ok( ( unsafe { context.make_current()? }, event_loop ) ) )
+                .map( |context| Box::new( context ) as Box<dyn std::any::Any> )
+                .or_else( |err| unsafe { create_egl_context() }.map( |egl| Box::new( egl ) as Box<dyn std::any::Any> ).map_err( |egl_err| format!( "{err}\nEGL: {egl_err}" ) ) );
+            if let Err( err ) = &context {
+                warn!( "{err}\nThe tests which need OpenGL fail without a context" );
+            }
+            started.send( context.as_ref().err().cloned() ).ok();
+            for job in receiver {
+                job();
+            }
+        } );
+        GlThread { jobs: Mutex::new( jobs ), error: error.recv().unwrap_or( Some( "The thread of the context stopped".to_string() ) ) }
+    } )
+}
+
+/**
+ * Runs a function on the thread where the tests' OpenGL context is current, and waits for it.
+ * Panics in the function (such as failed assertions) are passed on to the test which called it.
+ * The tests which call it are ignored unless run with --include-ignored (or --ignored), as they need a context, and fail if there is none.
+ *
+ * @param job The function.
+ *
+ * @return What the function returned.
+ */
+pub fn with_gl<R: Send + 'static>( job: impl FnOnce() -> R + Send + 'static ) -> R {
+    let gl_thread = gl_thread();
+    if let Some( err ) = &gl_thread.error {
+        panic!( "There is no OpenGL context to run the test with:\n{err}" );
+    }
+    let ( result, receiver ) = mpsc::channel();
+    let job: GlJob = Box::new( move || { result.send( panic::catch_unwind( AssertUnwindSafe( job ) ) ).ok(); } );
+    gl_thread.jobs.lock().unwrap_or_else( |err| err.into_inner() ).send( job ).expect( "The thread of the context stopped" );
+    match receiver.recv().expect( "The thread of the context stopped" ) {
+        Ok( value ) => { value },
+        Err( panic ) => { panic::resume_unwind( panic ) },
+    }
+}
+
+/**
+ * Loads the OpenGL functions for glow, for the tests of what renders through it (see device::GlowDevice).
+ * Must be called on the thread of the context, see with_gl().
+ *
+ * @return The context, as glow sees it.
+ */
+pub unsafe fn glow_context() -> glow::Context {
+    // (EGL's loader finds every function whichever way the context was created, as both go through the same dispatch)
+    static EGL: OnceLock<khronos_egl::DynamicInstance<khronos_egl::EGL1_5>> = OnceLock::new();
+    let egl = EGL.get_or_init( || {
+        let library = libloading::Library::new( "libEGL.so.1" ).expect( "There is no EGL to load the functions with" );
+        khronos_egl::DynamicInstance::<khronos_egl::EGL1_5>::load_required_from( library ).expect( "There is no EGL to load the functions with" )
+    } );
+    glow::Context::from_loader_function( |symbol| egl.get_proc_address( symbol ).map_or( std::ptr::null(), |function| function as *const _ ) )
+}
+
+/**
+ * Struct for a small scene which the tests render, as the GPU gets it.
+ */
+pub struct TestScene {
+    pub spheres: Vec<RTSphere>,
+    pub triangles: Vec<RTTriangle>,     // Which all go in one mesh
+}
+
+/**
+ * TestScene functions.
+ */
+impl TestScene {
+    /**
+     * Gets the settings which the scenes are rendered with, which are the interactive ones with a debug view of the surfaces,
+     * and rays through the centers of the pixels, so each pixel is what RTCamera::primary_ray() hits.
+     *
+     * @param view What the debug integrator shows.
+     */
+    pub fn settings( view: DebugView ) -> RTSettings {
+        RTSettings {
+            integrator: RTIntegrator::Debug( Debug { view } ),
+            rays_per_frag: 1,
+            diverge_strength: 0.0,
+            ..crate::engine::initial_settings( None )
+        }
+    }
+
+    /**
+     * Renders the scene with the fragment shader, the way the render loop does, and reads it back, see OffscreenRenderer.
+     * Must be called on the thread of the context, see with_gl().
+     *
+     * @param camera The camera, whose screen size is the size of the frame.
+     * @param settings The settings.
+     * @param frames How many frames are accumulated.
+     *
+     * @return The pixels as RGB, with the bottom row first, or an error if the shaders couldn't be built.
+     */
+    pub unsafe fn render( &self, camera: &RTCamera, settings: &RTSettings, frames: u32 ) -> Result<Vec<f32>, EngineError> {
+        let ( triangles, meshes ) = offscreen::meshes_of( std::slice::from_ref( &self.triangles ) );
+        let mut renderer = OffscreenRenderer::new( self.spheres.clone(), &triangles, meshes, camera.screen_size.x as u32, camera.screen_size.y as u32 )?;
+        for _ in 0..frames {
+            renderer.render_frame( camera, settings );
+        }
+        let pixels = renderer.read_pixels();
+        renderer.delete();
+        Ok( pixels )
+    }
+}
+
+/**
+ * Gets a pixel of a frame which was read back.
+ *
+ * @param pixels The pixels as RGB, with the bottom row first.
+ * @param width The width of the frame.
+ * @param height The height of the frame.
+ * @param pixel The pixel, with (0, 0) in the top left corner like RTCamera::primary_ray().
+ */
+pub fn pixel_at( pixels: &[f32], width: u32, height: u32, pixel: ( u32, u32 ) ) -> glm::Vec3 {
+    let index = ( ( height - 1 - pixel.1 ) * width + pixel.0 ) as usize * 3;
+    glm::vec3( pixels[index], pixels[index + 1], pixels[index + 2] )
+}
+
+mod tests {
+    use super::*;
+
+    // Size of the frames which are rendered
+    const WIDTH: u32 = 32;
+    const HEIGHT: u32 = 24;
+
+    /**
+     * Gets a sphere of a color.
+     */
+    fn sphere( center: glm::Vec3, radius: f32, color: glm::Vec3 ) -> RTSphere {
+        RTSphere {
+            radius,
+            center: center.into(),
+            material: RTMaterial { color: glm::vec4( color.x, color.y, color.z, 1.0 ), ..RTMaterial::new() },
+        }
+    }
+
+    /**
+     * Gets a triangle with flat normals, facing the rays which it is clockwise to (which hit it, see RTTriangle::intersect()).
+     */
+    fn triangle( p0: glm::Vec3, p1: glm::Vec3, p2: glm::Vec3, color: glm::Vec3 ) -> RTTriangle {
+        let normal = ( p1 - p0 ).cross( &( p2 - p0 ) ).normalize();
+        RTTriangle {
+            p0: p0.into(),
+            p1: p1.into(),
+            p2: p2.into(),
+            normal0: normal.into(),
+            normal1: normal.into(),
+            normal2: normal.into(),
+            material: RTMaterial { color: glm::vec4( color.x, color.y, color.z, 1.0 ), ..RTMaterial::new() },
+        }
+    }
+
+    /**
+     * Gets the camera which the scenes are seen from, at the origin and looking down +z.
+     */
+    fn camera() -> RTCamera {
+        RTCamera::look_at( glm::zero(), glm::vec3( 0.0, 0.0, 1.0 ), glm::vec3( 0.0, 1.0, 0.0 ), 60.0, glm::vec2( WIDTH as f32, HEIGHT as f32 ) )
+    }
+
+    /**
+     * Gets a scene with a sphere in front of a triangle, which covers the rest of the middle of the screen.
+     */
+    fn scene() -> TestScene {
+        TestScene {
+            spheres: vec![sphere( glm::vec3( 0.5, 0.0, 4.0 ), 1.0, glm::vec3( 0.2, 0.4, 0.8 ) )],
+            triangles: vec![triangle( glm::vec3( -6.0, -4.0, 8.0 ), glm::vec3( 0.0, 6.0, 8.0 ), glm::vec3( 6.0, -4.0, 8.0 ), glm::vec3( 0.9, 0.6, 0.1 ) )],
+        }
+    }
+
+    /**
+     * Gets the distance which the CPU finds along the ray through a pixel, or None if it misses.
+     */
+    fn cpu_distance( scene: &TestScene, camera: &RTCamera, pixel: ( u32, u32 ) ) -> Option<f32> {
+        let ray: RTRay = camera.primary_ray( glm::vec2( pixel.0 as f32, pixel.1 as f32 ) )?;
+        raytracing::raycast( &ray, &scene.spheres, &scene.triangles )
+    }
+
+    #[test]
+    fn sphere_intersection_is_at_the_near_side() {
+        let sphere = sphere( glm::vec3( 0.0, 0.0, 5.0 ), 1.0, glm::vec3( 1.0, 1.0, 1.0 ) );
+        let ray = RTRay { origin: glm::zero(), dir: glm::vec3( 0.0, 0.0, 1.0 ) };
+        assert!( ( sphere.intersect( &ray ).unwrap() - 4.0 ).abs() < 1e-5 );
+        assert_eq!( sphere.intersect( &RTRay { origin: glm::zero(), dir: glm::vec3( 0.0, 0.0, -1.0 ) } ), None );
+        assert_eq!( sphere.intersect( &RTRay { origin: glm::vec3( 2.0, 0.0, 0.0 ), dir: glm::vec3( 0.0, 0.0, 1.0 ) } ), None );
+    }
+
+    #[test]
+    fn triangle_intersection_culls_back_faces() {
+        let triangle = triangle( glm::vec3( -1.0, -1.0, 3.0 ), glm::vec3( 0.0, 1.0, 3.0 ), glm::vec3( 1.0, -1.0, 3.0 ), glm::vec3( 1.0, 1.0, 1.0 ) );
+        let ( dist, barycentric ) = triangle.intersect_barycentric( &RTRay { origin: glm::zero(), dir: glm::vec3( 0.0, 0.0, 1.0 ) } ).unwrap();
+        assert!( ( dist - 3.0 ).abs() < 1e-5 );
+        assert!( ( barycentric - glm::vec2( 0.5, 0.25 ) ).magnitude() < 1e-5 );
+        assert_eq!( triangle.intersect( &RTRay { origin: glm::vec3( 0.0, 0.0, 6.0 ), dir: glm::vec3( 0.0, 0.0, -1.0 ) } ), None );
+    }
+
+    #[test]
+    #[ignore = "needs an OpenGL context, run with cargo test -- --include-ignored"]
+    fn materials_reach_the_shader() {
+        let pixels = with_gl( || unsafe { scene().render( &camera(), &TestScene::settings( DebugView::Albedo ), 1 ) } ).unwrap();
+        let scene = scene();
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                let ray = camera().primary_ray( glm::vec2( x as f32, y as f32 ) ).unwrap();
+                let sphere_dist = scene.spheres[0].intersect( &ray );
+                let triangle_dist = scene.triangles[0].intersect( &ray );
+                // (Pixels on the edges of the surfaces may go either way, so only those clearly on one are checked)
+                let expected = match ( sphere_dist, triangle_dist ) {
+                    ( Some( _ ), _ ) => { scene.spheres[0].material.color.xyz() },
+                    ( None, Some( _ ) ) => { scene.triangles[0].material.color.xyz() },
+                    ( None, None ) => { glm::zero() },
+                };
+                let edge = [( 1, 0 ), ( 0, 1 )].iter().any( |&( dx, dy )| {
+                    let neighbor = camera().primary_ray( glm::vec2( ( x + dx ) as f32, ( y + dy ) as f32 ) ).unwrap();
+                    ( scene.spheres[0].intersect( &neighbor ).is_some(), scene.triangles[0].intersect( &neighbor ).is_some() ) != ( sphere_dist.is_some(), triangle_dist.is_some() )
+                } );
+                if !edge {
+                    let actual = pixel_at( &pixels, WIDTH, HEIGHT, ( x, y ) );
+                    assert!( ( actual - expected ).abs().max() < 1e-3, "pixel ({x}, {y}) is {actual:?}, expected {expected:?}" );
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[ignore = "needs an OpenGL context, run with cargo test -- --include-ignored"]
+    fn depth_matches_the_cpu() {
+        let pixels = with_gl( || unsafe { scene().render( &camera(), &TestScene::settings( DebugView::Depth ), 1 ) } ).unwrap();
+        let ( scene, camera ) = ( scene(), camera() );
+        let ( mut checked, mut hits ) = ( 0, 0 );
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                let Some( dist ) = cpu_distance( &scene, &camera, ( x, y ) ) else { continue };
+                hits += 1;
+                // (The debug view shows the depth as 1 / (1 + distance))
+                let actual = pixel_at( &pixels, WIDTH, HEIGHT, ( x, y ) ).x;
+                let gpu_dist = 1.0 / actual - 1.0;
+                // (The triangle's corners are quantized to 16 bits of its mesh's bounds, so they are a little off)
+                if ( gpu_dist - dist ).abs() < 1e-2 {
+                    checked += 1;
+                } else {
+                    // (Which surface is hit may differ right at the sphere's silhouette)
+                    let silhouette = scene.spheres[0].intersect( &camera.primary_ray( glm::vec2( x as f32 + 1.0, y as f32 ) ).unwrap() ).is_some()
+                        != scene.spheres[0].intersect( &camera.primary_ray( glm::vec2( x as f32, y as f32 ) ).unwrap() ).is_some();
+                    assert!( silhouette, "pixel ({x}, {y}) is {gpu_dist} away on the GPU, and {dist} on the CPU" );
+                }
+            }
+        }
+        // (The sphere and the triangle cover about half of the screen, and only the pixels on the silhouette may go unchecked)
+        assert!( hits > WIDTH * HEIGHT / 3 && checked > hits * 9 / 10, "{checked} of the {hits} pixels which hit were checked" );
+    }
+
+    #[test]
+    #[ignore = "needs an OpenGL context, run with cargo test -- --include-ignored"]
+    fn triangle_normals_survive_quantization() {
+        let scene = TestScene { spheres: vec![], triangles: scene().triangles };
+        let normal: glm::Vec3 = scene.triangles[0].normal0.into();
+        let pixels = with_gl( move || unsafe { scene.render( &camera(), &TestScene::settings( DebugView::Normal ), 1 ) } ).unwrap();
+        let actual = pixel_at( &pixels, WIDTH, HEIGHT, ( WIDTH / 2, HEIGHT / 2 ) ) * 2.0 - glm::vec3( 1.0, 1.0, 1.0 );
+        assert!( ( actual - normal ).magnitude() < 1e-3, "the normal is {actual:?}, expected {normal:?}" );
+    }
+
+    #[test]
+    #[ignore = "needs an OpenGL context, run with cargo test -- --include-ignored"]
+    fn fisheye_corners_are_blank() {
+        // (A triangle far wider than the screen, which the corners would see at 75 degrees if they weren't cut off at 45)
+        let scene = TestScene { spheres: vec![], triangles: vec![triangle( glm::vec3( -100.0, -100.0, 8.0 ), glm::vec3( 0.0, 100.0, 8.0 ), glm::vec3( 100.0, -100.0, 8.0 ), glm::vec3( 0.9, 0.6, 0.1 ) )] };
+        let camera = RTCamera { projection: raytracing::RTProjection::Fisheye { fov: 90.0 }, ..camera() };
+        assert!( camera.primary_ray( glm::vec2( 0.0, 0.0 ) ).is_none() );
+        assert!( camera.primary_ray( glm::vec2( ( WIDTH / 2 ) as f32, 0.0 ) ).is_some() );
+
+        let pixels = with_gl( move || unsafe { scene.render( &camera, &TestScene::settings( DebugView::Albedo ), 1 ) } ).unwrap();
+        assert_eq!( pixel_at( &pixels, WIDTH, HEIGHT, ( 0, 0 ) ), glm::Vec3::zeros() );
+        assert_eq!( pixel_at( &pixels, WIDTH, HEIGHT, ( WIDTH - 1, HEIGHT - 1 ) ), glm::Vec3::zeros() );
+        assert!( ( pixel_at( &pixels, WIDTH, HEIGHT, ( WIDTH / 2, HEIGHT / 2 ) ) - glm::vec3( 0.9, 0.6, 0.1 ) ).abs().max() < 1e-3 );
+    }
+
+    #[test]
+    #[ignore = "needs an OpenGL context, run with cargo test -- --include-ignored"]
+    fn empty_scenes_render_black() {
+        let scene = TestScene { spheres: vec![], triangles: vec![] };
+        let pixels = with_gl( move || unsafe { scene.render( &camera(), &TestScene::settings( DebugView::Albedo ), 1 ) } ).unwrap();
+        assert!( pixels.iter().all( |&value| value == 0.0 ) );
+    }
+
+    #[test]
+    #[ignore = "needs an OpenGL context, run with cargo test -- --include-ignored"]
+    fn rendering_leaves_no_objects() {
+        // (The other tests render on the same context, and delete what they made as this does)
+        let leaked = with_gl( || unsafe {
+            scene().render( &camera(), &TestScene::settings( DebugView::Albedo ), 2 ).unwrap();
+            gltracker::report()
+        } );
+        assert_eq!( leaked, 0 );
+    }
+}
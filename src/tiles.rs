@@ -0,0 +1,263 @@
+extern crate nalgebra_glm as glm;
+
+use crate::error::EngineError;
+use crate::accumulator::Accumulator;
+use crate::output;
+use crate::raytracing::RTWindow;
+
+/**
+ * Struct for a tile of an image, in pixels from the image's bottom left corner like GL's.
+ */
+#[derive(Clone, Copy, PartialEq)]
+pub struct Tile {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32, // Can be less than the tile size, for the tiles at the image's right and top edges
+    pub height: u32,
+}
+
+/**
+ * Struct for rendering an image in tiles, one after the other, which are stitched into the whole image as they finish.
+ * Very large images then only need a screen (and buffers) the size of a tile, and no single draw takes long enough to time out.
+ *
+ * Every tile is rendered on a screen of the full tile size, where the tiles at the edges only keep the part that is in the image.
+ * The tiles are post-processed on their own, so effects which reach across the screen (bloom, auto-exposure, vignette...) should be off.
+ */
+pub struct TileScheduler {
+    image_width: u32,
+    image_height: u32,
+    tile_width: u32,
+    tile_height: u32,
+    tiles: Vec<Tile>,
+    current: usize,
+    pixels: Vec<f32>, // The accumulated image, as RGB with the bottom row first, see Accumulator::read_pixels()
+    screen: image::RgbImage, // The image as it is shown (post-processed), with the top row first
+}
+
+/**
+ * TileScheduler functions.
+ */
+impl TileScheduler {
+    /**
+     * Splits an image into tiles, which are rendered from the bottom left, row by row.
+     *
+     * @param image_width The width of the image, in pixels.
+     * @param image_height The height of the image, in pixels.
+     * @param tile_size The width and height of the tiles, in pixels, which are no larger than the image.
+     */
+    pub fn new( image_width: u32, image_height: u32, tile_size: u32 ) -> TileScheduler {
+        let ( tile_width, tile_height ) = ( tile_size.min( image_width ), tile_size.min( image_height ) );
+        let mut tiles = Vec::new();
+        for y in ( 0..image_height ).step_by( tile_height as usize ) {
+            for x in ( 0..image_width ).step_by( tile_width as usize ) {
+                tiles.push( Tile { x, y, width: tile_width.min( image_width - x ), height: tile_height.min( image_height - y ) } );
+            }
+        }
+
+        TileScheduler {
+            image_width,
+            image_height,
+            tile_width,
+            tile_height,
+            tiles,
+            current: 0,
+            pixels: vec![0.0; image_width as usize * image_height as usize * 3],
+            screen: image::RgbImage::new( image_width, image_height ),
+        }
+    }
+
+    /**
+     * Gets the tile which is being rendered.
+     */
+    pub fn tile( &self ) -> Tile {
+        self.tiles[self.current]
+    }
+
+    /**
+     * Gets how many tiles the image is split into, and which of them is being rendered.
+     *
+     * @return The index of the current tile, and the count of tiles.
+     */
+    pub fn progress( &self ) -> ( usize, usize ) {
+        ( self.current, self.tiles.len() )
+    }
+
+    /**
+     * Gets the window of the image which the camera fires rays through for the current tile.
+     */
+    pub fn window( &self ) -> RTWindow {
+        let tile = self.tile();
+        RTWindow {
+            offset: glm::vec2( tile.x as f32, tile.y as f32 ),
+            image_size: glm::vec2( self.image_width as f32, self.image_height as f32 ),
+        }
+    }
+
+    /**
+     * Stitches the current tile into the image, once it has all its samples, and moves on to the next one.
+     * Must be called after the tile has been drawn to the screen, and before the buffers are swapped.
+     *
+     * @param accumulator The accumulator holding the tile.
+     *
+     * @return If there are more tiles to render, or if the image is done.
+     */
+    pub unsafe fn finish_tile( &mut self, accumulator: &Accumulator ) -> bool {
+        let pixels = accumulator.read_pixels();
+        let screen = output::read_screen( self.tile_width, self.tile_height );
+        // (Both are read back at the tile's size, so they always fit)
+        let _ = self.stitch( self.current, &pixels, &screen );
+
+        self.current += 1;
+        self.current < self.tiles.len()
+    }
+
+    /**
+     * Stitches a rendered tile into the image, such as one rendered by a worker on another machine, see farm::Coordinator.
+     *
+     * @param index The index of the tile.
+     * @param pixels The accumulated pixels of the tile's screen, as RGB with the bottom row first.
+     * @param screen The tile's screen as it is shown, with the top row first.
+     *
+     * @return An error if the tile's screen isn't the size of a tile.
+     */
+    pub fn stitch( &mut self, index: usize, pixels: &[f32], screen: &image::RgbImage ) -> Result<(), EngineError> {
+        let tile = self.tiles[index];
+        let ( tile_x, tile_y, tile_width ) = ( tile.x as usize, tile.y as usize, tile.width as usize );
+        let row_size = self.tile_width as usize * 3;
+        if pixels.len() != row_size * self.tile_height as usize || screen.dimensions() != ( self.tile_width, self.tile_height ) {
+            return Err( EngineError::render( "TILES::WRONG_TILE_SIZE", format!( "{}x{} instead of {}x{}", screen.width(), screen.height(), self.tile_width, self.tile_height ) ) );
+        }
+
+        // The accumulated image has its rows from the bottom, like the image's pixels
+        for row in 0..tile.height as usize {
+            let source = &pixels[row * row_size..row * row_size + tile_width * 3];
+            let start = ( ( tile_y + row ) * self.image_width as usize + tile_x ) * 3;
+            self.pixels[start..start + tile_width * 3].copy_from_slice( source );
+        }
+
+        // The screen has its rows from the top, so the tile's bottom row is the screen's last
+        for row in 0..tile.height {
+            for column in 0..tile.width {
+                let pixel = *screen.get_pixel( column, self.tile_height - 1 - row );
+                self.screen.put_pixel( tile.x + column, self.image_height - 1 - ( tile.y + row ), pixel );
+            }
+        }
+        Ok( () )
+    }
+
+    /**
+     * Moves on to a tile out of order, for workers which are given the tiles to render, see farm::Worker.
+     *
+     * @param index The index of the tile.
+     */
+    pub fn select( &mut self, index: usize ) {
+        self.current = index;
+    }
+
+    /**
+     * Starts over from the first tile, for rendering another image of the same size.
+     */
+    pub fn restart( &mut self ) {
+        self.current = 0;
+    }
+
+    /**
+     * Gets the stitched image as it is shown (post-processed).
+     */
+    pub fn screen( &self ) -> &image::RgbImage {
+        &self.screen
+    }
+
+    /**
+     * Gets the stitched accumulated image, as RGB with the bottom row first.
+     */
+    pub fn pixels( &self ) -> &[f32] {
+        &self.pixels
+    }
+
+    /**
+     * Gets the size of the tiles, which every tile is rendered on a screen of, see stitch().
+     */
+    pub fn tile_size( &self ) -> ( u32, u32 ) {
+        ( self.tile_width, self.tile_height )
+    }
+
+    /**
+     * Gets the size of the whole image.
+     */
+    pub fn image_size( &self ) -> ( u32, u32 ) {
+        ( self.image_width, self.image_height )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn images_are_split_row_by_row_with_smaller_edge_tiles() {
+        let tiles = TileScheduler::new( 100, 70, 32 );
+        assert_eq!( tiles.progress(), ( 0, 12 ) );
+        assert_eq!( tiles.tile_size(), ( 32, 32 ) );
+
+        // (Every pixel is in exactly one tile)
+        let mut covered = vec![0; 100 * 70];
+        for tile in &tiles.tiles {
+            for y in tile.y..tile.y + tile.height {
+                for x in tile.x..tile.x + tile.width {
+                    covered[( y * 100 + x ) as usize] += 1;
+                }
+            }
+        }
+        assert!( covered.iter().all( |&count| count == 1 ) );
+
+        let ( right, top ) = ( tiles.tiles[3], tiles.tiles[11] );
+        assert!( right.x == 96 && right.y == 0 && right.width == 4 && right.height == 32 );
+        assert!( top.x == 96 && top.y == 64 && top.width == 4 && top.height == 6 );
+    }
+
+    #[test]
+    fn tiles_are_no_larger_than_the_image() {
+        let tiles = TileScheduler::new( 20, 10, 64 );
+        assert_eq!( tiles.progress(), ( 0, 1 ) );
+        assert_eq!( tiles.tile_size(), ( 20, 10 ) );
+    }
+
+    #[test]
+    fn windows_follow_the_selected_tile() {
+        let mut tiles = TileScheduler::new( 100, 70, 32 );
+        tiles.select( 5 );
+        let window = tiles.window();
+        assert_eq!( ( window.offset, window.image_size ), ( glm::vec2( 32.0, 32.0 ), glm::vec2( 100.0, 70.0 ) ) );
+        tiles.restart();
+        assert_eq!( tiles.progress(), ( 0, 12 ) );
+    }
+
+    #[test]
+    fn edge_tiles_are_stitched_into_place() {
+        // (A 3x2 image in tiles of 2, so the right tile only keeps the first column of its screen)
+        let mut tiles = TileScheduler::new( 3, 2, 2 );
+        let pixels: Vec<f32> = ( 0..12 ).map( |i| i as f32 ).collect();
+        let screen = image::RgbImage::from_fn( 2, 2, |x, y| image::Rgb( [x as u8, y as u8, 9] ) );
+        tiles.stitch( 1, &pixels, &screen ).unwrap();
+
+        // The tile's bottom row is the image's, and its top row the screen's first
+        assert_eq!( &tiles.pixels()[6..9], &[0.0, 1.0, 2.0] );
+        assert_eq!( &tiles.pixels()[15..18], &[6.0, 7.0, 8.0] );
+        assert_eq!( *tiles.screen().get_pixel( 2, 1 ), image::Rgb( [0, 1, 9] ) );
+        assert_eq!( *tiles.screen().get_pixel( 2, 0 ), image::Rgb( [0, 0, 9] ) );
+        assert_eq!( *tiles.screen().get_pixel( 0, 0 ), image::Rgb( [0, 0, 0] ) );
+    }
+
+    #[test]
+    fn tiles_of_the_wrong_size_are_refused() {
+        let mut tiles = TileScheduler::new( 100, 70, 32 );
+        let wrong_screen = image::RgbImage::new( 4, 32 );
+        let result = tiles.stitch( 3, &[0.0; 4 * 32 * 3], &wrong_screen );
+        assert!( matches!( result, Err( EngineError::Render { code: "TILES::WRONG_TILE_SIZE", .. } ) ) );
+
+        let short_pixels = vec![0.0; 32 * 32 * 3 - 1];
+        assert!( tiles.stitch( 0, &short_pixels, &image::RgbImage::new( 32, 32 ) ).is_err() );
+        assert!( tiles.pixels().iter().all( |&value| value == 0.0 ) );
+    }
+}
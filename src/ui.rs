@@ -0,0 +1,261 @@
+use std::sync::Arc;
+
+use glutin::PossiblyCurrent;
+use glutin::event::MouseButton;
+
+use crate::error::EngineError;
+use crate::camera::Camera;
+use crate::context::RenderContext;
+use crate::denoiser::AtrousSettings;
+use crate::postprocess::{ PostSettings, Tonemapper };
+use crate::raytracing::{ RTSettings, RTIntegrator, RTRoulette, RTMaterial, RTSphere };
+use crate::window::WindowEvent;
+
+/**
+ * Struct for what the control panel edits, which it changes in place so the next frame is rendered with the changes.
+ * The accumulation restarts by itself when the settings, camera or spheres change, as they are watched by the accumulator.
+ */
+pub struct PanelValues<'a> {
+    pub settings: &'a mut RTSettings,
+    pub post_settings: &'a mut PostSettings,
+    pub camera: &'a mut Camera,
+    pub focus_distance: &'a mut f32,
+    pub aperture_radius: &'a mut f32,
+    pub spheres: &'a [RTSphere], // The spheres as the scene has them, which the materials are edited from
+    pub materials: &'a mut [Option<RTMaterial>], // The edited material of each sphere, if it was edited, which replaces the scene's
+}
+
+/**
+ * Struct for a control panel drawn over the window with egui, for changing the render's settings while it renders.
+ * The window's pointer events are passed to it, and it is drawn on top of the screen once the frame is done.
+ * Points are taken as pixels, so the panel isn't scaled to the display.
+ */
+pub struct ControlPanel {
+    egui: egui::Context,
+    painter: egui_glow::Painter,
+    events: Vec<egui::Event>, // Events since the panel was last drawn
+    pointer: egui::Pos2, // Where the cursor last was
+    visible: bool,
+    started: std::time::Instant,
+}
+
+/**
+ * ControlPanel functions.
+ */
+impl ControlPanel {
+    /**
+     * Creates the control panel, which is hidden until it is toggled.
+     *
+     * @param context The context it is drawn with, which must be current.
+     *
+     * @return The control panel, or an error if egui's shaders couldn't be built.
+     */
+    pub unsafe fn new( context: &RenderContext<PossiblyCurrent> ) -> Result<ControlPanel, EngineError> {
+        let gl = egui_glow::glow::Context::from_loader_function( |symbol| context.get_proc_address( symbol ) );
+        let painter = egui_glow::Painter::new( Arc::new( gl ), "", None ).map_err( |err| EngineError::gl( "UI::PAINTER_NOT_CREATED", err.to_string() ) )?;
+        Ok( ControlPanel {
+            egui: egui::Context::default(),
+            painter,
+            events: Vec::new(),
+            pointer: egui::Pos2::ZERO,
+            visible: false,
+            started: std::time::Instant::now(),
+        } )
+    }
+
+    /**
+     * Shows or hides the panel.
+     */
+    pub fn toggle( &mut self ) {
+        self.visible = !self.visible;
+    }
+
+    /**
+     * Passes an event from the window to the panel, which only uses the pointer's.
+     *
+     * @param event The event.
+     */
+    pub fn handle_event( &mut self, event: &WindowEvent ) {
+        match *event {
+            WindowEvent::CursorMoved( position ) => {
+                self.pointer = egui::pos2( position.x, position.y );
+                self.events.push( egui::Event::PointerMoved( self.pointer ) );
+            },
+            WindowEvent::MouseInput( button, pressed ) => {
+                let button = match button {
+                    MouseButton::Left => { egui::PointerButton::Primary },
+                    MouseButton::Right => { egui::PointerButton::Secondary },
+                    MouseButton::Middle => { egui::PointerButton::Middle },
+                    MouseButton::Other( _ ) => { return },
+                };
+                self.events.push( egui::Event::PointerButton { pos: self.pointer, button, pressed, modifiers: egui::Modifiers::default() } );
+            },
+            _ => {},
+        }
+    }
+
+    /**
+     * Checks if the pointer is over the panel or dragging one of its widgets, in which case clicks are the panel's and not the scene's.
+     */
+    pub fn wants_pointer( &self ) -> bool {
+        self.visible && self.egui.wants_pointer_input()
+    }
+
+    /**
+     * Lays the panel out, applying what was changed with it since the last frame, and draws it over the screen.
+     * This changes the blending and the other state egui draws with, which is set back to what the engine draws with.
+     *
+     * @param screen_width The width of the screen, in pixels.
+     * @param screen_height The height of the screen, in pixels.
+     * @param values What the panel edits.
+     */
+    pub unsafe fn draw( &mut self, screen_width: u32, screen_height: u32, values: PanelValues ) {
+        let events = std::mem::take( &mut self.events );
+        if !self.visible {
+            return;
+        }
+        let input = egui::RawInput {
+            screen_rect: Some( egui::Rect::from_min_size( egui::Pos2::ZERO, egui::vec2( screen_width as f32, screen_height as f32 ) ) ),
+            pixels_per_point: Some( 1.0 ),
+            time: Some( self.started.elapsed().as_secs_f64() ),
+            events,
+            ..Default::default()
+        };
+        let output = self.egui.run( input, |ctx| {
+            egui::Window::new( "Settings" ).default_width( 260.0 ).show( ctx, |ui| {
+                egui::ScrollArea::vertical().show( ui, |ui| panel_ui( ui, values ) );
+            } );
+        } );
+        let primitives = self.egui.tessellate( output.shapes );
+        self.painter.paint_and_update_textures( [screen_width, screen_height], 1.0, &primitives, &output.textures_delta );
+
+        // (As set up in render())
+        gl::Disable( gl::SCISSOR_TEST );
+        gl::Enable( gl::DEPTH_TEST );
+        gl::Enable( gl::CULL_FACE );
+        gl::BlendFunc( gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA );
+        gl::BindVertexArray( 0 );
+    }
+}
+
+impl Drop for ControlPanel {
+    fn drop( &mut self ) {
+        self.painter.destroy();
+    }
+}
+
+/**
+ * Lays out the widgets of the panel.
+ *
+ * @param ui The panel's window.
+ * @param values What the panel edits.
+ */
+fn panel_ui( ui: &mut egui::Ui, values: PanelValues ) {
+    let PanelValues { settings, post_settings, camera, focus_distance, aperture_radius, spheres, materials } = values;
+
+    egui::CollapsingHeader::new( "Rendering" ).default_open( true ).show( ui, |ui| {
+        egui::ComboBox::from_label( "Integrator" ).selected_text( settings.integrator.get().name() ).show_ui( ui, |ui| {
+            for integrator in RTIntegrator::all() {
+                let selected = integrator.id() == settings.integrator.id();
+                if ui.selectable_label( selected, integrator.get().name() ).clicked() && !selected {
+                    settings.integrator = integrator;
+                }
+            }
+        } );
+        ui.add( egui::Slider::new( &mut settings.rays_per_frag, 1..=16 ).text( "Rays per pixel" ) );
+        ui.add( egui::Slider::new( &mut settings.max_bounces.total, 0..=32 ).text( "Bounces" ) );
+        ui.add( egui::Slider::new( &mut settings.max_bounces.diffuse, 0..=32 ).text( "Diffuse bounces" ) );
+        ui.add( egui::Slider::new( &mut settings.max_bounces.glossy, 0..=32 ).text( "Glossy bounces" ) );
+        ui.add( egui::Slider::new( &mut settings.diverge_strength, 0.0..=0.5 ).text( "Anti-aliasing spread" ) );
+
+        let roulette_name = match settings.roulette {
+            RTRoulette::Off             => { "Off" },
+            RTRoulette::Throughput      => { "Throughput" },
+            RTRoulette::Constant { .. } => { "Constant" },
+        };
+        egui::ComboBox::from_label( "Russian roulette" ).selected_text( roulette_name ).show_ui( ui, |ui| {
+            ui.selectable_value( &mut settings.roulette, RTRoulette::Off, "Off" );
+            ui.selectable_value( &mut settings.roulette, RTRoulette::Throughput, "Throughput" );
+            if ui.selectable_label( matches!( settings.roulette, RTRoulette::Constant { .. } ), "Constant" ).clicked() && !matches!( settings.roulette, RTRoulette::Constant { .. } ) {
+                settings.roulette = RTRoulette::Constant { survival: 0.8 };
+            }
+        } );
+        if let RTRoulette::Constant { survival } = &mut settings.roulette {
+            ui.add( egui::Slider::new( survival, 0.05..=1.0 ).text( "Survival" ) );
+        }
+        if settings.roulette != RTRoulette::Off {
+            ui.add( egui::Slider::new( &mut settings.roulette_start, 0..=16 ).text( "Roulette from bounce" ) );
+        }
+
+        ui.checkbox( &mut settings.next_event_estimation, "Next event estimation" );
+        ui.checkbox( &mut settings.restir, "ReSTIR" );
+        ui.checkbox( &mut settings.path_guiding, "Path guiding" );
+        ui.checkbox( &mut settings.caustics, "Caustics" );
+        ui.add( egui::Slider::new( &mut settings.direct_clamp, 0.0..=100.0 ).text( "Direct clamp (0 is off)" ) );
+        ui.add( egui::Slider::new( &mut settings.indirect_clamp, 0.0..=100.0 ).text( "Indirect clamp (0 is off)" ) );
+        ui.add( egui::Slider::new( &mut settings.regularization, 0.0..=1.0 ).text( "Regularization" ) );
+    } );
+
+    egui::CollapsingHeader::new( "Camera" ).show( ui, |ui| {
+        let mut fov = camera.fov();
+        if ui.add( egui::Slider::new( &mut fov, 10.0..=120.0 ).text( "Field of view" ) ).changed() {
+            camera.set_vars( None, None, Some( fov ), None, None );
+        }
+        ui.add( egui::Slider::new( focus_distance, 0.1..=1000.0 ).logarithmic( true ).text( "Focus distance" ) );
+        ui.add( egui::Slider::new( aperture_radius, 0.0..=2.0 ).text( "Aperture radius" ) );
+    } );
+
+    egui::CollapsingHeader::new( "Materials" ).show( ui, |ui| {
+        for ( i, ( sphere, material ) ) in spheres.iter().zip( materials.iter_mut() ).enumerate() {
+            ui.push_id( i, |ui| {
+                egui::CollapsingHeader::new( format!( "Sphere {i}" ) ).show( ui, |ui| {
+                    let mut edited = material.clone().unwrap_or_else( || sphere.material.clone() );
+                    let mut color = [edited.color.x, edited.color.y, edited.color.z];
+                    let mut emission = [edited.emission_color.x, edited.emission_color.y, edited.emission_color.z];
+                    let mut specular = [edited.specular_color.x, edited.specular_color.y, edited.specular_color.z];
+                    let mut changed = false;
+                    ui.horizontal( |ui| { changed |= ui.color_edit_button_rgb( &mut color ).changed(); ui.label( "Color" ); } );
+                    ui.horizontal( |ui| { changed |= ui.color_edit_button_rgb( &mut emission ).changed(); ui.label( "Emission" ); } );
+                    changed |= ui.add( egui::Slider::new( &mut edited.emission_color.w, 0.0..=20.0 ).text( "Emission strength" ) ).changed();
+                    ui.horizontal( |ui| { changed |= ui.color_edit_button_rgb( &mut specular ).changed(); ui.label( "Specular" ); } );
+                    changed |= ui.add( egui::Slider::new( &mut edited.specular_color.w, 0.0..=1.0 ).text( "Specular probability" ) ).changed();
+                    changed |= ui.add( egui::Slider::new( &mut edited.smoothness, 0.0..=1.0 ).text( "Smoothness" ) ).changed();
+                    if changed {
+                        edited.color = glm::vec4( color[0], color[1], color[2], edited.color.w );
+                        edited.emission_color = glm::vec4( emission[0], emission[1], emission[2], edited.emission_color.w );
+                        edited.specular_color = glm::vec4( specular[0], specular[1], specular[2], edited.specular_color.w );
+                        *material = Some( edited );
+                    }
+                    if material.is_some() && ui.button( "Reset" ).clicked() {
+                        *material = None;
+                    }
+                } );
+            } );
+        }
+    } );
+
+    egui::CollapsingHeader::new( "Post-processing" ).show( ui, |ui| {
+        egui::ComboBox::from_label( "Tonemapper" ).selected_text( post_settings.tonemapper.name() ).show_ui( ui, |ui| {
+            for tonemapper in Tonemapper::all() {
+                ui.selectable_value( &mut post_settings.tonemapper, tonemapper, tonemapper.name() );
+            }
+        } );
+        ui.add( egui::Slider::new( &mut post_settings.exposure, -8.0..=8.0 ).step_by( 0.1 ).text( "Exposure (EV)" ) );
+
+        let mut denoise = post_settings.denoise.is_some();
+        if ui.checkbox( &mut denoise, "Denoise" ).changed() {
+            post_settings.denoise = denoise.then( AtrousSettings::interactive );
+        }
+        if let Some( atrous ) = &mut post_settings.denoise {
+            ui.add( egui::Slider::new( &mut atrous.iterations, 1..=8 ).text( "Iterations" ) );
+            ui.add( egui::Slider::new( &mut atrous.sigma_color, 0.01..=4.0 ).text( "Color sigma" ) );
+            ui.add( egui::Slider::new( &mut atrous.sigma_normal, 0.01..=1.0 ).text( "Normal sigma" ) );
+            ui.add( egui::Slider::new( &mut atrous.sigma_depth, 0.001..=1.0 ).logarithmic( true ).text( "Depth sigma" ) );
+            let mut temporal = atrous.temporal.is_some();
+            if ui.checkbox( &mut temporal, "Filter over time" ).changed() {
+                atrous.temporal = if temporal { AtrousSettings::interactive().temporal } else { None };
+            }
+        }
+        ui.checkbox( &mut post_settings.dither, "Dither" );
+    } );
+}
@@ -0,0 +1,57 @@
+use std::any::Any;
+use std::collections::HashMap;
+
+/**
+ * Struct for telling which inputs of the render (settings, camera, spheres and their materials) have changed since they were last sent to the GPU,
+ * so each frame only sends what changed, instead of every uniform and buffer.
+ *
+ * Uniforms belong to a program, so each input is tracked per program (or buffer) it is sent to, under the GL name of that program.
+ * The value last sent is kept, and compared against what would be sent now.
+ * It only tells what the GPU already has, unlike Accumulator::watch(), which tells when the accumulation has to restart.
+ */
+pub struct UploadTracker {
+    uploaded: HashMap<( u32, String ), Box<dyn Any>>,
+}
+
+/**
+ * UploadTracker functions.
+ */
+impl UploadTracker {
+    /**
+     * Creates a tracker, which knows of no uploads yet.
+     */
+    pub fn new() -> UploadTracker {
+        UploadTracker { uploaded: HashMap::new() }
+    }
+
+    /**
+     * Checks if an input differs from what was last sent to a program or buffer, and takes it as sent if it does.
+     * Inputs are told apart by name, and the value is only compared against values sent under the same name to the same target.
+     *
+     * @param target The GL name of the program or buffer the input is sent to.
+     * @param name The name of the input.
+     * @param value The current value of the input.
+     *
+     * @return If the input must be sent (which is always the case the first time).
+     */
+    pub fn needs_upload<T: PartialEq + Clone + 'static>( &mut self, target: u32, name: &str, value: &T ) -> bool {
+        let key = ( target, name.to_string() );
+        let changed = match self.uploaded.get( &key ).and_then( |prev| prev.downcast_ref::<T>() ) {
+            Some( prev ) => { prev != value },
+            None => { true },
+        };
+        if changed {
+            self.uploaded.insert( key, Box::new( value.clone() ) );
+        }
+        changed
+    }
+
+    /**
+     * Forgets what was sent to a program or buffer, such as when it is deleted (as its GL name may be reused) or its contents are lost.
+     *
+     * @param target The GL name of the program or buffer.
+     */
+    pub fn forget( &mut self, target: u32 ) {
+        self.uploaded.retain( |( uploaded_target, _ ), _| *uploaded_target != target );
+    }
+}
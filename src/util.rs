@@ -1,5 +1,7 @@
 use std::{ mem, os::raw::c_void, ffi::CString };
 
+use crate::error::EngineError;
+use crate::gltracker::{ self, GlObject };
 
 /**
  * Gets the size of an array.
@@ -8,7 +10,7 @@ use std::{ mem, os::raw::c_void, ffi::CString };
  * @return The size of the array in bytes.
  */
 pub fn byte_size_of_array<T>(val: &[T]) -> isize {
-    std::mem::size_of_val(&val[..]) as isize
+    std::mem::size_of_val(val) as isize
 }
 
 /**
@@ -18,12 +20,22 @@ pub fn byte_size_of_array<T>(val: &[T]) -> isize {
  * @return The c-style pointer to the array.
  */
 pub fn pointer_to_array<T>(val: &[T]) -> *const c_void {
-    if val.len() <= 0 {
-        return 0 as *const c_void
+    if val.is_empty() {
+        return std::ptr::null()
     }
     &val[0] as *const T as *const c_void
 }
 
+/**
+ * Gets the bytes of an array.
+ * 
+ * @param val The array.
+ * @return The bytes which the array is made of.
+ */
+pub fn bytes_of_array<T>(val: &[T]) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(val.as_ptr() as *const u8, std::mem::size_of_val(val)) }
+}
+
 /**
  * Gets the size of a given type.
  */
@@ -31,6 +43,7 @@ pub fn size_of<T>() -> i32 {
     mem::size_of::<T>() as i32
 }
 
+#[allow(dead_code)]
 /**
  * Gets the offset for a given amount of a type.
  * 
@@ -41,6 +54,7 @@ pub fn offset<T>(n: u32) -> *const c_void {
     (n * mem::size_of::<T>() as u32) as *const T as *const c_void
 }
 
+#[allow(dead_code)]
 /**
  * Casts a rust string into C's i8 array.
  * 
@@ -49,7 +63,64 @@ pub fn offset<T>(n: u32) -> *const c_void {
  */
 pub fn str_as_i8(s: &str) -> *const i8 {
     let c_str = CString::new( s ).unwrap();
-    c_str.as_ptr() as *const i8
+    c_str.as_ptr()
+}
+
+#[allow(dead_code)]
+/**
+ * Loads an image file as a color texture, such as an albedo map.
+ * Color images are stored in sRGB, so the texture is made sRGB and GL decodes it to linear when it is sampled.
+ * Textures holding data rather than color (such as the blue noise) must not be loaded with this.
+ *
+ * @param path The path of the image file.
+ *
+ * @return The id of the texture, or an error if the file couldn't be read.
+ */
+pub unsafe fn load_color_texture(path: &str) -> Result<u32, EngineError> {
+    let img = image::open( path )
+        .map_err( |e| EngineError::asset( "TEXTURE::FILE_NOT_SUCCESSFULLY_READ", format!( "{path}: {e}" ) ) )?
+        .into_rgba8();
+
+    let mut texture: gl::types::GLuint = 0;
+    gl::GenTextures(1, &mut texture);
+    gltracker::created( GlObject::Texture, &[texture] );
+    gl::BindTexture(gl::TEXTURE_2D, texture);
+    gl::TexImage2D(
+        gl::TEXTURE_2D,
+        0,
+        gl::SRGB8_ALPHA8 as i32,
+        img.width() as i32,
+        img.height() as i32,
+        0,
+        gl::RGBA,
+        gl::UNSIGNED_BYTE,
+        pointer_to_array( img.as_raw() )
+    );
+    gl::GenerateMipmap(gl::TEXTURE_2D);
+    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR_MIPMAP_LINEAR as i32);
+    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::REPEAT as i32);
+    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::REPEAT as i32);
+    gl::BindTexture(gl::TEXTURE_2D, 0);
+
+    Ok( texture )
+}
+
+/**
+ * Checks that the bound framebuffer is complete, which it isn't when the driver can't render to its attachments.
+ * Incomplete framebuffers are unbound, so nothing is drawn to them.
+ *
+ * @param code The code of the error, such as "ACCUMULATOR::FRAMEBUFFER_INCOMPLETE".
+ *
+ * @return An error with the framebuffer's status if it is incomplete.
+ */
+pub unsafe fn check_framebuffer(code: &'static str) -> Result<(), EngineError> {
+    let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+    if status == gl::FRAMEBUFFER_COMPLETE {
+        return Ok(());
+    }
+    gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+    Err(EngineError::gl(code, format!("The framebuffer's status is {status:#x}")))
 }
 
 /**
@@ -61,7 +132,7 @@ pub fn str_as_i8(s: &str) -> *const i8 {
  * 
  * @return The id of the generated VAO.
  */
-pub unsafe fn create_vao(vertices: &Vec<f32>, indices: &Vec<u32>) -> u32 {
+pub unsafe fn create_vao(vertices: &[f32], indices: &[u32]) -> u32 {
     // Generate & bind VAO
     let mut vao: gl::types::GLuint = 0;
     gl::GenVertexArrays(1, &mut vao);
@@ -70,6 +141,7 @@ pub unsafe fn create_vao(vertices: &Vec<f32>, indices: &Vec<u32>) -> u32 {
     // Generate & bind VBO
     let mut vbo: gl::types::GLuint = 0;
     gl::GenBuffers(1, &mut vbo);
+    gltracker::created( GlObject::Buffer, &[vbo] );
     gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
 
     // Fill VBO with data
@@ -94,6 +166,7 @@ pub unsafe fn create_vao(vertices: &Vec<f32>, indices: &Vec<u32>) -> u32 {
     // Generate & bind IBO/EBO
     let mut ebo: gl::types::GLuint = 0;
     gl::GenBuffers(1, &mut ebo);
+    gltracker::created( GlObject::Buffer, &[ebo] );
     gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ebo);
 
     // Fill IBO/EBO with data
@@ -114,6 +187,26 @@ pub unsafe fn create_vao(vertices: &Vec<f32>, indices: &Vec<u32>) -> u32 {
     vao
 }
 
+/**
+ * Deletes a VAO made by create_vao(), along with its VBO and IBO/EBO.
+ * 
+ * @param vao The id of the VAO.
+ */
+pub unsafe fn delete_vao(vao: u32) {
+    // Find the buffers, which the VAO holds on to
+    let (mut vbo, mut ebo) = (0, 0);
+    gl::BindVertexArray(vao);
+    gl::GetVertexAttribiv(0, gl::VERTEX_ATTRIB_ARRAY_BUFFER_BINDING, &mut vbo);
+    gl::GetIntegerv(gl::ELEMENT_ARRAY_BUFFER_BINDING, &mut ebo);
+    gl::BindVertexArray(0);
+
+    let buffers = [vbo as u32, ebo as u32];
+    gl::DeleteBuffers(2, buffers.as_ptr());
+    gltracker::deleted( GlObject::Buffer, &buffers );
+    gl::DeleteVertexArrays(1, &vao);
+}
+
+#[allow(dead_code)]
 /**
  * Creates the vertices and indices for a triangle made of smaller triangles.
  * 
@@ -129,7 +222,7 @@ pub fn create_triangle_triangle(triangle_width: i32, triangle_height: i32) -> (V
 
     for y in 0..triangle_height {
         let y_float: f32 = y as f32 / triangle_height as f32;
-        let x_offset: f32 = -y_float / 2 as f32;
+        let x_offset: f32 = -y_float / 2.0;
         for x in y..triangle_width {
             let x_float: f32 = x as f32 / triangle_width as f32;
             
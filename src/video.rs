@@ -0,0 +1,100 @@
+use std::io::Write;
+use std::process::Child;
+#[cfg(feature = "video")]
+use std::process::{ Command, Stdio };
+
+use crate::error::EngineError;
+
+/**
+ * Struct for encoding the frames of a sequence straight into a video, so they don't have to be saved as images first.
+ * The frames are streamed to an ffmpeg process as raw pixels, so ffmpeg must be installed for videos to be encoded.
+ */
+pub struct VideoEncoder {
+    process: Child,
+    path: String,
+    width: u32,
+    height: u32,
+}
+
+/**
+ * Gets ffmpeg's arguments for encoding to a file, which are chosen from its extension.
+ * WebM files are encoded with VP9, and everything else (mp4, mkv, mov) with H.264.
+ *
+ * @param path The path of the file.
+ *
+ * @return The arguments.
+ */
+#[cfg(feature = "video")]
+fn codec_args( path: &str ) -> &'static [&'static str] {
+    if path.to_ascii_lowercase().ends_with( ".webm" ) {
+        &["-c:v", "libvpx-vp9", "-crf", "30", "-b:v", "0", "-pix_fmt", "yuv420p"]
+    } else {
+        &["-c:v", "libx264", "-crf", "18", "-preset", "slow", "-pix_fmt", "yuv420p"]
+    }
+}
+
+/**
+ * VideoEncoder functions.
+ */
+impl VideoEncoder {
+    /**
+     * Starts encoding a video.
+     *
+     * @param path The path of the video, whose extension decides the codec.
+     * @param width The width of the frames, in pixels.
+     * @param height The height of the frames, in pixels.
+     * @param fps Frames per second of the video.
+     *
+     * @return The encoder, or an error if ffmpeg couldn't be started.
+     */
+    #[cfg(feature = "video")]
+    pub fn new( path: &str, width: u32, height: u32, fps: f32 ) -> Result<VideoEncoder, EngineError> {
+        let process = Command::new( "ffmpeg" )
+            .args( ["-y", "-loglevel", "error"] )
+            .args( ["-f", "rawvideo", "-pix_fmt", "rgb24"] )
+            .args( ["-s", &format!( "{width}x{height}" ), "-r", &fps.to_string(), "-i", "-"] )
+            // (4:2:0 chroma needs an even size, so odd frames are padded by a pixel)
+            .args( ["-vf", "pad=ceil(iw/2)*2:ceil(ih/2)*2"] )
+            .args( codec_args( path ) )
+            .arg( path )
+            .stdin( Stdio::piped() )
+            .spawn()
+            // (Most likely ffmpeg isn't installed, which it must be to encode videos)
+            .map_err( |err| EngineError::io( "VIDEO::FFMPEG_NOT_STARTED", "ffmpeg", err ) )?;
+        Ok( VideoEncoder { process, path: path.to_string(), width, height } )
+    }
+
+    /**
+     * Fails to start encoding a video, as the engine was built without the video feature.
+     * (Offline renders are checked for it before they start, see headless::HeadlessSettings::from_args())
+     */
+    #[cfg(not(feature = "video"))]
+    pub fn new( path: &str, _width: u32, _height: u32, _fps: f32 ) -> Result<VideoEncoder, EngineError> {
+        Err( EngineError::render( "VIDEO::NOT_BUILT", format!( "{path}\nBuilt without the video feature, build with --features video to encode videos" ) ) )
+    }
+
+    /**
+     * Adds a frame to the video.
+     *
+     * @param frame The frame, which must be the size the encoder was started with.
+     */
+    pub fn write_frame( &mut self, frame: &image::RgbImage ) -> Result<(), EngineError> {
+        if frame.dimensions() != ( self.width, self.height ) {
+            return Err( EngineError::render( "VIDEO::WRONG_FRAME_SIZE", format!( "{}x{} instead of {}x{}", frame.width(), frame.height(), self.width, self.height ) ) );
+        }
+        let stdin = self.process.stdin.as_mut().ok_or( EngineError::render( "VIDEO::FFMPEG_CLOSED", "" ) )?;
+        stdin.write_all( frame.as_raw() ).map_err( |err| EngineError::io( "VIDEO::FRAME_NOT_WRITTEN", self.path.as_str(), err ) )
+    }
+
+    /**
+     * Finishes the video, waiting for ffmpeg to encode the last frames and close the file.
+     */
+    pub fn finish( mut self ) -> Result<(), EngineError> {
+        drop( self.process.stdin.take() );
+        let status = self.process.wait().map_err( |err| EngineError::io( "VIDEO::FFMPEG_FAILED", self.path.as_str(), err ) )?;
+        if !status.success() {
+            return Err( EngineError::render( "VIDEO::FFMPEG_FAILED", format!( "{}: {status}", self.path ) ) );
+        }
+        Ok( () )
+    }
+}
@@ -0,0 +1,357 @@
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use tracing::warn;
+
+use crate::error::EngineError;
+use crate::shader::Shader;
+use crate::util::pointer_to_array;
+use crate::gltracker::{ self, GlObject };
+
+// Maximum amount of volumes in the scene, which must match the shader
+pub const MAX_VOLUMES: usize = 4;
+
+// Texture units of the first volume's density and temperature textures, with the rest following them
+const FIRST_TEXTURE_UNIT: u32 = 2;
+const FIRST_TEMPERATURE_TEXTURE_UNIT: u32 = FIRST_TEXTURE_UNIT + MAX_VOLUMES as u32;
+
+// Largest size along each axis of a grid loaded from a NanoVDB file, as it is stored densely
+const MAX_NANOVDB_SIZE: usize = 512;
+
+// Layout of NanoVDB files (NanoVDB.h), for the parts which are read
+const NANOVDB_MAGIC: u64 = 0x304244566f6e614e; // "NanoVDB0"
+const NANOVDB_FILE_HEADER_SIZE: usize = 16;
+const NANOVDB_FILE_METADATA_SIZE: usize = 176;
+const NANOVDB_GRID_DATA_SIZE: usize = 672;
+const NANOVDB_LEAF_SIZE: usize = 2144; // For float grids, which is 96 bytes of header followed by 8x8x8 values
+const NANOVDB_GRID_TYPE_FLOAT: u32 = 1;
+
+/**
+ * Struct for a grid of densities, from 0 (empty) to 1 (as dense as the volume gets).
+ * The densities are stored x first, then y, then z.
+ */
+pub struct DensityGrid {
+    pub size: [usize; 3],
+    pub densities: Vec<f32>,
+}
+
+/**
+ * DensityGrid functions.
+ */
+#[allow(dead_code)]
+impl DensityGrid {
+    /**
+     * Creates a grid by evaluating a function at the center of each cell.
+     *
+     * @param size The amount of cells along each axis.
+     * @param density The density at a point, where the grid spans [0, 1] along each axis.
+     */
+    pub fn from_fn( size: [usize; 3], density: impl Fn( glm::Vec3 ) -> f32 ) -> DensityGrid {
+        let mut densities = Vec::with_capacity( size[0] * size[1] * size[2] );
+        for z in 0..size[2] { for y in 0..size[1] { for x in 0..size[0] {
+            let point = glm::vec3( x as f32 + 0.5, y as f32 + 0.5, z as f32 + 0.5 ).component_div( &glm::vec3( size[0] as f32, size[1] as f32, size[2] as f32 ) );
+            densities.push( density( point ).clamp( 0.0, 1.0 ) );
+        } } }
+        DensityGrid { size, densities }
+    }
+
+    /**
+     * Loads a grid from a raw file of 8-bit densities, which is how many volume datasets are stored.
+     *
+     * @param path The path to the file.
+     * @param size The amount of cells along each axis.
+     *
+     * @return The grid, or an error if the file couldn't be read or has the wrong size.
+     */
+    pub fn load_raw( path: &str, size: [usize; 3] ) -> Result<DensityGrid, EngineError> {
+        let bytes = std::fs::read( path ).map_err( |err| EngineError::io( "VOLUMES::FAILED_TO_READ_FILE", path, err ) )?;
+        if bytes.len() != size[0] * size[1] * size[2] {
+            return Err( EngineError::parse( "VOLUMES::WRONG_SIZE", format!( "{path} has {} bytes, expected {}x{}x{}", bytes.len(), size[0], size[1], size[2] ) ) );
+        }
+        Ok( DensityGrid { size, densities: bytes.iter().map( |&b| b as f32 / 255.0 ).collect() } )
+    }
+
+    /**
+     * Loads the first grid of a NanoVDB file, such as a smoke or cloud cache exported from a production tool.
+     * The grid is stored densely over its bounds, and its densities are divided by the largest of them so they fit in [0, 1].
+     * Only uncompressed float grids are supported, and only the voxels of leaf nodes are read, so constant regions stored as tiles are left empty.
+     * https://github.com/AcademySoftwareFoundation/openvdb/tree/master/nanovdb
+     *
+     * @param path The path to the file.
+     *
+     * @return The grid and the bottom left and top right corners of its bounds in the world, or an error if the file couldn't be read.
+     */
+    pub fn load_nanovdb( path: &str ) -> Result<( DensityGrid, glm::Vec3, glm::Vec3 ), EngineError> {
+        let bytes = std::fs::read( path ).map_err( |err| EngineError::io( "VOLUMES::FAILED_TO_READ_FILE", path, err ) )?;
+        let invalid = |reason: &str| EngineError::parse( "VOLUMES::INVALID_NANOVDB", format!( "{path}: {reason}" ) );
+        // (Offsets and sizes come from the file, so any of them can be out of range, or overflow)
+        let read = |offset: usize, size: usize| offset.checked_add( size ).and_then( |end| bytes.get( offset..end ) ).ok_or_else( || invalid( "File ends early" ) );
+        let read_u16 = |offset: usize| read( offset, 2 ).map( |b| u16::from_le_bytes( b.try_into().unwrap() ) );
+        let read_u32 = |offset: usize| read( offset, 4 ).map( |b| u32::from_le_bytes( b.try_into().unwrap() ) );
+        let read_i32 = |offset: usize| read( offset, 4 ).map( |b| i32::from_le_bytes( b.try_into().unwrap() ) );
+        let read_u64 = |offset: usize| read( offset, 8 ).map( |b| u64::from_le_bytes( b.try_into().unwrap() ) );
+        let read_f32 = |offset: usize| read( offset, 4 ).map( |b| f32::from_le_bytes( b.try_into().unwrap() ) );
+        let read_f64 = |offset: usize| read( offset, 8 ).map( |b| f64::from_le_bytes( b.try_into().unwrap() ) );
+
+        // File header and the metadata of the first grid
+        if read_u64( 0 )? != NANOVDB_MAGIC { return Err( invalid( "Not a NanoVDB file" ) ); }
+        if read_u16( 12 )? == 0 { return Err( invalid( "The file has no grids" ) ); }
+        if read_u16( 14 )? != 0 { return Err( invalid( "Compressed files are not supported" ) ); }
+        let metadata = NANOVDB_FILE_HEADER_SIZE;
+        if read_u32( metadata + 32 )? != NANOVDB_GRID_TYPE_FLOAT { return Err( invalid( "Only float grids are supported" ) ); }
+        let world_bounds = [
+            glm::vec3( read_f64( metadata + 40 )? as f32, read_f64( metadata + 48 )? as f32, read_f64( metadata + 56 )? as f32 ),
+            glm::vec3( read_f64( metadata + 64 )? as f32, read_f64( metadata + 72 )? as f32, read_f64( metadata + 80 )? as f32 ),
+        ];
+        let index_min = [read_i32( metadata + 88 )?, read_i32( metadata + 92 )?, read_i32( metadata + 96 )?];
+        let index_max = [read_i32( metadata + 100 )?, read_i32( metadata + 104 )?, read_i32( metadata + 108 )?];
+        let name_size = read_u32( metadata + 136 )? as usize;
+        let size = [0, 1, 2].map( |axis| index_max[axis].checked_sub( index_min[axis] ).and_then( |n| n.checked_add( 1 ) ).map_or( 0, |n| n.max( 0 ) as usize ) );
+        if size.iter().any( |&n| n == 0 || n > MAX_NANOVDB_SIZE ) {
+            return Err( invalid( &format!( "The grid is {}x{}x{}, which must be within 1-{MAX_NANOVDB_SIZE} along each axis", size[0], size[1], size[2] ) ) );
+        }
+
+        // The grid itself, where the tree follows the grid's header and the leaves are stored one after the other
+        let grid = metadata + NANOVDB_FILE_METADATA_SIZE + name_size;
+        if read_u64( grid )? != NANOVDB_MAGIC { return Err( invalid( "The grid is corrupt" ) ); }
+        let tree = grid + NANOVDB_GRID_DATA_SIZE;
+        let leaves = usize::try_from( read_u64( tree )? ).ok().and_then( |offset| tree.checked_add( offset ) ).ok_or_else( || invalid( "The tree is corrupt" ) )?;
+        let leaf_count = read_u32( tree + 32 )? as usize;
+
+        let mut densities = vec![0.0f32; size[0] * size[1] * size[2]];
+        for i in 0..leaf_count {
+            let leaf = i.checked_mul( NANOVDB_LEAF_SIZE ).and_then( |offset| leaves.checked_add( offset ) ).ok_or_else( || invalid( "File ends early" ) )?;
+            let origin = [read_i32( leaf )?, read_i32( leaf + 4 )?, read_i32( leaf + 8 )?];
+            let value_mask = read( leaf + 16, 64 )?;
+            for voxel in 0..512 {
+                if value_mask[voxel / 8] & ( 1 << ( voxel % 8 ) ) == 0 { continue; }
+
+                // (Voxels are ordered z first within a leaf)
+                // (In i64, as origins at the edge of i32's range would overflow)
+                let index = [origin[0] as i64 + ( voxel >> 6 ) as i64, origin[1] as i64 + ( ( voxel >> 3 ) & 7 ) as i64, origin[2] as i64 + ( voxel & 7 ) as i64];
+                let cell = [0, 1, 2].map( |axis| index[axis] - index_min[axis] as i64 );
+                if ( 0..3 ).any( |axis| cell[axis] < 0 || cell[axis] as usize >= size[axis] ) { continue; }
+                densities[cell[0] as usize + ( cell[1] as usize + cell[2] as usize * size[1] ) * size[0]] = read_f32( leaf + 96 + voxel * 4 )?;
+            }
+        }
+
+        let max_density = densities.iter().cloned().fold( 0.0, f32::max );
+        if max_density > 0.0 {
+            densities.iter_mut().for_each( |density| *density = ( *density / max_density ).max( 0.0 ) );
+        }
+        Ok( ( DensityGrid { size, densities }, world_bounds[0], world_bounds[1] ) )
+    }
+
+    /**
+     * Creates a cloud, which is fractal value noise fading out towards the edges of the grid.
+     *
+     * @param size The amount of cells along each axis.
+     * @param seed The seed of the noise.
+     */
+    pub fn cloud( size: [usize; 3], seed: u64 ) -> DensityGrid {
+        // Random values on a lattice, which wraps around
+        const LATTICE: usize = 16;
+        let mut rng = StdRng::seed_from_u64( seed );
+        let lattice: Vec<f32> = ( 0..LATTICE.pow( 3 ) ).map( |_| rng.gen() ).collect();
+        let value = |p: glm::Vec3| {
+            let ( cell, t ) = ( glm::floor( &p ), glm::fract( &p ) );
+            let t = t.component_mul( &t ).component_mul( &( glm::vec3( 3.0, 3.0, 3.0 ) - t * 2.0 ) );
+            let at = |dx: usize, dy: usize, dz: usize| {
+                let index = |c: f32, d: usize| ( c as i64 + d as i64 ).rem_euclid( LATTICE as i64 ) as usize;
+                lattice[index( cell.x, dx ) + index( cell.y, dy ) * LATTICE + index( cell.z, dz ) * LATTICE * LATTICE]
+            };
+            let lerp = |a: f32, b: f32, t: f32| a + ( b - a ) * t;
+            lerp(
+                lerp( lerp( at( 0, 0, 0 ), at( 1, 0, 0 ), t.x ), lerp( at( 0, 1, 0 ), at( 1, 1, 0 ), t.x ), t.y ),
+                lerp( lerp( at( 0, 0, 1 ), at( 1, 0, 1 ), t.x ), lerp( at( 0, 1, 1 ), at( 1, 1, 1 ), t.x ), t.y ),
+                t.z,
+            )
+        };
+
+        DensityGrid::from_fn( size, |p| {
+            let noise: f32 = ( 0..4 ).map( |octave| value( p * 4.0 * 2f32.powi( octave ) ) * 0.5f32.powi( octave + 1 ) ).sum();
+            let falloff = 1.0 - ( p - glm::vec3( 0.5, 0.5, 0.5 ) ).magnitude() * 2.0;
+            ( noise + falloff - 0.6 ) * 3.0
+        } )
+    }
+}
+
+/**
+ * Struct for a heterogeneous volume, which is a box in the scene filled with a medium whose density is read from a 3D texture.
+ * Rays find where they scatter in it with delta tracking, and shadow rays find how much light makes it through with ratio tracking.
+ * https://jannovak.info/publications/SDTracking/index.html
+ */
+pub struct Volume {
+    pub bounds_min: glm::Vec3,
+    pub bounds_max: glm::Vec3,
+    pub density: f32, // The density where the grid is 1, which is also the most dense the volume gets
+    pub albedo: glm::Vec3, // The fraction of light which is scattered rather than absorbed when scattering, per color channel
+    pub anisotropy: f32, // The Henyey-Greenstein g, see RTMedium
+    pub emission: f32, // Strength of the light emitted at max_temperature, or 0 if the volume doesn't glow, see set_temperature()
+    pub max_temperature: f32, // The temperature where the temperature grid is 1, in kelvin
+    texture: u32,
+    temperature_texture: u32,
+}
+
+/**
+ * Volume functions.
+ */
+#[allow(dead_code)]
+impl Volume {
+    /**
+     * Creates a volume, uploading its densities to a 3D texture.
+     *
+     * @param bounds_min The bottom left corner of the box.
+     * @param bounds_max The top right corner of the box.
+     * @param grid The densities, which are stretched over the box.
+     * @param density The density where the grid is 1.
+     * @param albedo The scattering albedo.
+     * @param anisotropy The Henyey-Greenstein g.
+     */
+    pub unsafe fn new( bounds_min: glm::Vec3, bounds_max: glm::Vec3, grid: &DensityGrid, density: f32, albedo: glm::Vec3, anisotropy: f32 ) -> Volume {
+        Volume {
+            bounds_min,
+            bounds_max,
+            density,
+            albedo,
+            anisotropy,
+            emission: 0.0,
+            max_temperature: 0.0,
+            texture: Volume::create_texture( grid ),
+            temperature_texture: 0,
+        }
+    }
+
+    /**
+     * Makes the volume glow like fire, emitting the light of a black body at the temperature of each point.
+     * The light is emitted where the volume absorbs, so it gets brighter with density and the part of the albedo which is absorbed.
+     * Its strength follows the Stefan-Boltzmann law, growing with the temperature to the fourth power.
+     *
+     * @param grid The temperatures, from 0 to 1, which are stretched over the volume's box like the densities.
+     * @param max_temperature The temperature where the grid is 1, in kelvin.
+     * @param emission The strength of the light emitted at max_temperature.
+     */
+    pub unsafe fn set_temperature( &mut self, grid: &DensityGrid, max_temperature: f32, emission: f32 ) -> &Volume {
+        if self.temperature_texture != 0 {
+            gl::DeleteTextures( 1, &self.temperature_texture );
+            gltracker::deleted( GlObject::Texture, &[self.temperature_texture] );
+        }
+        self.temperature_texture = Volume::create_texture( grid );
+        self.max_temperature = max_temperature;
+        self.emission = emission;
+        self
+    }
+
+    /**
+     * Deletes the volume's textures, once it is done with.
+     */
+    pub unsafe fn delete( self ) {
+        let textures = [self.texture, self.temperature_texture];
+        let textures: Vec<u32> = textures.into_iter().filter( |&texture| texture != 0 ).collect();
+        gl::DeleteTextures( textures.len() as i32, textures.as_ptr() );
+        gltracker::deleted( GlObject::Texture, &textures );
+    }
+
+    /**
+     * Uploads a grid to a 3D texture, which is interpolated linearly.
+     *
+     * @param grid The grid.
+     *
+     * @return The texture.
+     */
+    unsafe fn create_texture( grid: &DensityGrid ) -> u32 {
+        let mut texture = 0;
+        gl::GenTextures( 1, &mut texture );
+        gltracker::created( GlObject::Texture, &[texture] );
+        gl::BindTexture( gl::TEXTURE_3D, texture );
+        gl::TexImage3D(
+            gl::TEXTURE_3D, 0, gl::R32F as i32,
+            grid.size[0] as i32, grid.size[1] as i32, grid.size[2] as i32,
+            0, gl::RED, gl::FLOAT, pointer_to_array( &grid.densities )
+        );
+        gl::TexParameteri( gl::TEXTURE_3D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32 );
+        gl::TexParameteri( gl::TEXTURE_3D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32 );
+        for wrap in [gl::TEXTURE_WRAP_S, gl::TEXTURE_WRAP_T, gl::TEXTURE_WRAP_R] {
+            gl::TexParameteri( gl::TEXTURE_3D, wrap, gl::CLAMP_TO_EDGE as i32 );
+        }
+        gl::BindTexture( gl::TEXTURE_3D, 0 );
+        texture
+    }
+}
+
+/**
+ * Sends the volumes to the shader, binding their textures to the texture units from FIRST_TEXTURE_UNIT and FIRST_TEMPERATURE_TEXTURE_UNIT.
+ * The shader must be active.
+ *
+ * @param shader The shader.
+ * @param volumes The volumes, of which only the first MAX_VOLUMES are used.
+ */
+pub unsafe fn send_uniforms( shader: &Shader, volumes: &[Volume] ) {
+    let count = volumes.len().min( MAX_VOLUMES );
+    if volumes.len() > MAX_VOLUMES {
+        warn!( "Only the first {MAX_VOLUMES} of {} volumes are rendered", volumes.len() );
+    }
+
+    gl::Uniform1i( shader.get_uniform_location( "volumesCount" ), count as i32 );
+    // (The samplers of unused volumes are given their units too, as they would otherwise be on unit 0 with the accumulation's 2D texture,
+    // which strict drivers such as Mesa refuse to draw with)
+    for i in 0..MAX_VOLUMES {
+        gl::Uniform1i( shader.get_uniform_location( format!("volumeDensities[{i}]").as_str() ), ( FIRST_TEXTURE_UNIT + i as u32 ) as i32 );
+        gl::Uniform1i( shader.get_uniform_location( format!("volumeTemperatures[{i}]").as_str() ), ( FIRST_TEMPERATURE_TEXTURE_UNIT + i as u32 ) as i32 );
+    }
+    for ( i, volume ) in volumes.iter().take( count ).enumerate() {
+        gl::ActiveTexture( gl::TEXTURE0 + FIRST_TEXTURE_UNIT + i as u32 );
+        gl::BindTexture( gl::TEXTURE_3D, volume.texture );
+        gl::ActiveTexture( gl::TEXTURE0 + FIRST_TEMPERATURE_TEXTURE_UNIT + i as u32 );
+        gl::BindTexture( gl::TEXTURE_3D, volume.temperature_texture );
+
+        gl::Uniform3fv( shader.get_uniform_location( format!("volumes[{i}].boundsMin").as_str() ), 1, volume.bounds_min.as_ptr() );
+        gl::Uniform3fv( shader.get_uniform_location( format!("volumes[{i}].boundsMax").as_str() ), 1, volume.bounds_max.as_ptr() );
+        gl::Uniform1f( shader.get_uniform_location( format!("volumes[{i}].density").as_str() ), volume.density );
+        gl::Uniform3fv( shader.get_uniform_location( format!("volumes[{i}].albedo").as_str() ), 1, volume.albedo.as_ptr() );
+        gl::Uniform1f( shader.get_uniform_location( format!("volumes[{i}].anisotropy").as_str() ), volume.anisotropy );
+        gl::Uniform1f( shader.get_uniform_location( format!("volumes[{i}].emission").as_str() ), volume.emission );
+        gl::Uniform1f( shader.get_uniform_location( format!("volumes[{i}].maxTemperature").as_str() ), volume.max_temperature );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Writes a NanoVDB file with only the headers which are read before the leaves, and the given bounds of its grid
+    fn nanovdb_file( index_min: [i32; 3], index_max: [i32; 3], leaves_offset: u64 ) -> String {
+        let metadata = NANOVDB_FILE_HEADER_SIZE;
+        let grid = metadata + NANOVDB_FILE_METADATA_SIZE;
+        let mut bytes = vec![0u8; grid + NANOVDB_GRID_DATA_SIZE + 36];
+        let mut write = |offset: usize, value: &[u8]| bytes[offset..offset + value.len()].copy_from_slice( value );
+        write( 0, &NANOVDB_MAGIC.to_le_bytes() );
+        write( 12, &1u16.to_le_bytes() );
+        write( metadata + 32, &NANOVDB_GRID_TYPE_FLOAT.to_le_bytes() );
+        for axis in 0..3 {
+            write( metadata + 88 + axis * 4, &index_min[axis].to_le_bytes() );
+            write( metadata + 100 + axis * 4, &index_max[axis].to_le_bytes() );
+        }
+        write( grid, &NANOVDB_MAGIC.to_le_bytes() );
+        write( grid + NANOVDB_GRID_DATA_SIZE, &leaves_offset.to_le_bytes() );
+        write( grid + NANOVDB_GRID_DATA_SIZE + 32, &1u32.to_le_bytes() );
+
+        let path = std::env::temp_dir().join( format!( "volumes-{}-{leaves_offset}-{}.nvdb", std::process::id(), index_min[0] ) );
+        std::fs::write( &path, bytes ).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    fn is_invalid( path: &str ) -> bool {
+        let result = DensityGrid::load_nanovdb( path );
+        let _ = std::fs::remove_file( path );
+        matches!( result, Err( EngineError::Parse { code, .. } ) if code == "VOLUMES::INVALID_NANOVDB" )
+    }
+
+    #[test]
+    fn bounds_which_overflow_are_invalid() {
+        assert!( is_invalid( &nanovdb_file( [i32::MIN, 0, 0], [i32::MAX, 7, 7], 0 ) ) );
+    }
+
+    #[test]
+    fn offsets_which_overflow_are_invalid() {
+        assert!( is_invalid( &nanovdb_file( [0, 0, 0], [7, 7, 7], u64::MAX ) ) );
+    }
+}
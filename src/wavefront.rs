@@ -0,0 +1,450 @@
+use tracing::warn;
+
+use crate::raytracing::{ RTSettings, RTIntegrator, RTCamera };
+use crate::device::{ self, BufferUsage, Device };
+use crate::glapi;
+use crate::capabilities::Capabilities;
+use crate::shader::Shader;
+
+// Sizes of the wavefront path's structs in the shader (std430), in bytes
+const PATH_SIZE: usize = 80;
+const RAY_SIZE: usize = 32;
+const HIT_SIZE: usize = 128;
+const SHADOW_RAY_SIZE: usize = 32;
+
+// The queues start with their amounts of rays, padded to the rays' alignment of 16 bytes
+const QUEUE_HEADER_SIZE: usize = 16;
+
+// Shadow rays each path may trace per bounce, to the sampled light and to the sun
+const SHADOW_RAYS_PER_PATH: usize = 2;
+
+// Bindings of the wavefront buffers in the shader
+const PATH_BINDING: u32 = 11;
+const RAY_BINDING: u32 = 12;
+const HIT_BINDING: u32 = 13;
+const SHADOW_RAY_BINDING: u32 = 14;
+
+/**
+ * Enum for how the frames are rendered.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Shading {
+    Fragment,   // A fullscreen quad, where each frag traces its pixel's paths from start to end
+    Compute,    // The same shader dispatched as a compute shader, which stores to the accumulation as an image
+    Wavefront,  // The compute shader split into stages which work through queues, see Wavefront
+}
+
+/**
+ * Shading functions.
+ */
+impl Shading {
+    /**
+     * Gets a shading from its name.
+     *
+     * @param name The name, such as "compute".
+     *
+     * @return The shading, or None if there is none by that name.
+     */
+    pub fn from_name( name: &str ) -> Option<Shading> {
+        match name {
+            "fragment"  => { Some( Shading::Fragment ) },
+            "compute"   => { Some( Shading::Compute ) },
+            "wavefront" => { Some( Shading::Wavefront ) },
+            _           => { None },
+        }
+    }
+
+    /**
+     * Gets the name of the shading.
+     */
+    pub fn name( &self ) -> &'static str {
+        match self {
+            Shading::Fragment   => { "fragment" },
+            Shading::Compute    => { "compute" },
+            Shading::Wavefront  => { "wavefront" },
+        }
+    }
+
+    /**
+     * Gets the shading which comes after this one, for switching between them.
+     */
+    pub fn next( &self ) -> Shading {
+        match self {
+            Shading::Fragment   => { Shading::Compute },
+            Shading::Compute    => { Shading::Wavefront },
+            Shading::Wavefront  => { Shading::Fragment },
+        }
+    }
+}
+
+// Frames each candidate of the WorkgroupTuner is rendered for before it is timed, while its shader warms up
+const TUNING_WARMUP_FRAMES: usize = 2;
+
+// Frames each candidate of the WorkgroupTuner is timed over
+const TUNING_FRAMES: usize = 8;
+
+/**
+ * Struct for the size of the compute path's work groups, in pixels along each side.
+ * It is compiled into the shader (WORKGROUP_SIZE_X and WORKGROUP_SIZE_Y), so each size has a shader of its own.
+ */
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct WorkgroupSize {
+    pub x: u32,
+    pub y: u32,
+}
+
+impl std::fmt::Display for WorkgroupSize {
+    fn fmt( &self, f: &mut std::fmt::Formatter ) -> std::fmt::Result {
+        write!( f, "{}x{}", self.x, self.y )
+    }
+}
+
+/**
+ * WorkgroupSize functions.
+ */
+impl WorkgroupSize {
+    // The sizes which the WorkgroupTuner tries, which are all 32 to 256 invocations (whole warps and wavefronts)
+    pub const CANDIDATES: [WorkgroupSize; 6] = [
+        WorkgroupSize { x: 8, y: 4 },
+        WorkgroupSize { x: 8, y: 8 },
+        WorkgroupSize { x: 16, y: 4 },
+        WorkgroupSize { x: 16, y: 8 },
+        WorkgroupSize { x: 32, y: 4 },
+        WorkgroupSize { x: 16, y: 16 },
+    ];
+
+    /**
+     * Gets a size from its name, such as "16x8".
+     *
+     * @param name The name, as the width and the height.
+     *
+     * @return The size, or None if the name isn't a size.
+     */
+    pub fn from_name( name: &str ) -> Option<WorkgroupSize> {
+        let ( x, y ) = name.split_once( 'x' )?;
+        let size = WorkgroupSize { x: x.trim().parse().ok()?, y: y.trim().parse().ok()? };
+        if size.x == 0 || size.y == 0 { None } else { Some( size ) }
+    }
+
+    /**
+     * Gets the defines which compile the size into the shader.
+     */
+    pub fn defines( &self ) -> [( &'static str, u32 ); 2] {
+        [( "WORKGROUP_SIZE_X", self.x ), ( "WORKGROUP_SIZE_Y", self.y )]
+    }
+
+    /**
+     * Dispatches the compute shader over a render, which must have been compiled with this size.
+     *
+     * @param width The width of the render, in pixels.
+     * @param height The height of the render, in pixels.
+     */
+    pub unsafe fn dispatch( &self, width: u32, height: u32 ) {
+        device::current().dispatch( [width.div_ceil( self.x ), height.div_ceil( self.y ), 1] );
+    }
+}
+
+/**
+ * Struct for picking the fastest work group size on the GPU which is rendering, by timing a few frames with each of the candidates.
+ * Which size is fastest depends on the GPU (the size of its warps, its caches) and on how much the paths diverge,
+ * so the candidates are timed on the actual render, the first frames the compute path is used.
+ *
+ * Each frame, the tracing is timed with a TIME_ELAPSED query, which are all read once every candidate has been timed.
+ */
+pub struct WorkgroupTuner {
+    candidates: Vec<WorkgroupSize>,
+    queries: Vec<Vec<u32>>, // The queries which timed the frames of each candidate
+    current: usize,
+    frame: usize, // Frames rendered with the current candidate
+    timing: bool, // If a query was begun this frame
+    best: Option<WorkgroupSize>,
+}
+
+/**
+ * WorkgroupTuner functions.
+ */
+#[allow(dead_code)]
+impl WorkgroupTuner {
+    /**
+     * Creates a tuner, for the candidates the GPU can dispatch (see Capabilities::fits_work_group()).
+     *
+     * @param size The size to use, or None to time the candidates and use the fastest.
+     *             Sizes the GPU can't dispatch are replaced by the largest candidate it can.
+     */
+    pub fn new( size: Option<WorkgroupSize> ) -> WorkgroupTuner {
+        let capabilities = Capabilities::current();
+        let mut candidates: Vec<WorkgroupSize> = WorkgroupSize::CANDIDATES.into_iter().filter( |size| capabilities.fits_work_group( size.x, size.y ) ).collect();
+        if candidates.is_empty() {
+            candidates.push( WorkgroupSize::CANDIDATES[0] );
+        }
+        let size = size.map( |size| {
+            if capabilities.fits_work_group( size.x, size.y ) {
+                return size;
+            }
+            let fallback = candidates[candidates.len() - 1];
+            warn!( "Work groups of {size} are larger than the GPU's ({} invocations at most), so {fallback} is used instead", capabilities.max_compute_invocations );
+            fallback
+        } );
+        // (Timer queries are an extension on ES, so 8x8 is used there, which every ES device can run)
+        let size = size.or( ( !glapi::has_timer_queries() ).then_some( WorkgroupSize::CANDIDATES[1] ) );
+        WorkgroupTuner {
+            queries: vec![Vec::new(); candidates.len()],
+            candidates,
+            current: 0,
+            frame: 0,
+            timing: false,
+            best: size,
+        }
+    }
+
+    /**
+     * Gets the size to render this frame with, which is the candidate being timed while tuning.
+     */
+    pub fn size( &self ) -> WorkgroupSize {
+        self.best.unwrap_or( self.candidates[self.current] )
+    }
+
+    /**
+     * Gets if the candidates are still being timed.
+     */
+    pub fn is_tuning( &self ) -> bool {
+        self.best.is_none()
+    }
+
+    /**
+     * Starts timing the tracing of a frame, if the candidate has warmed up.
+     */
+    pub unsafe fn begin_frame( &mut self ) {
+        if !self.is_tuning() || self.frame < TUNING_WARMUP_FRAMES {
+            return;
+        }
+        let mut query = 0;
+        gl::GenQueries( 1, &mut query );
+        gl::BeginQuery( gl::TIME_ELAPSED, query );
+        self.queries[self.current].push( query );
+        self.timing = true;
+    }
+
+    /**
+     * Stops timing the tracing of a frame, moving on to the next candidate once this one has been timed over enough frames.
+     *
+     * @return The median time of each candidate in milliseconds, fastest first, on the frame the tuning finishes. Otherwise None.
+     */
+    pub unsafe fn end_frame( &mut self ) -> Option<Vec<( WorkgroupSize, f64 )>> {
+        if !self.is_tuning() {
+            return None;
+        }
+        if self.timing {
+            gl::EndQuery( gl::TIME_ELAPSED );
+            self.timing = false;
+        }
+        self.frame += 1;
+        if self.frame < TUNING_WARMUP_FRAMES + TUNING_FRAMES {
+            return None;
+        }
+        self.frame = 0;
+        self.current += 1;
+        if self.current < self.candidates.len() {
+            return None;
+        }
+
+        // (Reading the last candidate's queries waits for its frames to finish, which happens once)
+        let mut times: Vec<( WorkgroupSize, f64 )> = self.candidates.iter().zip( &self.queries ).map( |( size, queries )| {
+            let mut frame_times: Vec<f64> = queries.iter().map( |&query| {
+                let mut nanoseconds = 0u64;
+                gl::GetQueryObjectui64v( query, gl::QUERY_RESULT, &mut nanoseconds );
+                nanoseconds as f64 / 1e6
+            } ).collect();
+            gl::DeleteQueries( queries.len() as i32, queries.as_ptr() );
+            frame_times.sort_by( |a, b| a.total_cmp( b ) );
+            ( *size, frame_times.get( frame_times.len() / 2 ).copied().unwrap_or( f64::MAX ) )
+        } ).collect();
+        times.sort_by( |a, b| a.1.total_cmp( &b.1 ) );
+        self.queries.clear();
+        self.best = Some( times[0].0 );
+        Some( times )
+    }
+
+    /**
+     * Deletes the queries of a tuning which hasn't finished, once the tuner is done with.
+     */
+    pub unsafe fn delete( self ) {
+        for queries in self.queries.iter().filter( |queries| !queries.is_empty() ) {
+            gl::DeleteQueries( queries.len() as i32, queries.as_ptr() );
+        }
+    }
+}
+
+/**
+ * Enum for the stages of the wavefront path, which are dispatched in order.
+ */
+#[derive(Clone, Copy, PartialEq)]
+pub enum WavefrontStage {
+    Generate,   // Starts the paths, queueing their camera rays
+    Intersect,  // Finds what the queued rays hit
+    Shade,      // Adds the light the rays found, queues shadow rays to the lights, and queues the next bounce's rays
+    Shadow,     // Traces the shadow rays, dropping the light of those which are blocked
+    Accumulate, // Blends the light of the paths into the accumulation
+}
+
+/**
+ * WavefrontStage functions.
+ */
+impl WavefrontStage {
+    /**
+     * Gets the id of the stage, as used by the shader.
+     */
+    pub fn id( &self ) -> u32 {
+        match self {
+            WavefrontStage::Generate    => { 1 },
+            WavefrontStage::Intersect   => { 2 },
+            WavefrontStage::Shade       => { 3 },
+            WavefrontStage::Shadow      => { 4 },
+            WavefrontStage::Accumulate  => { 5 },
+        }
+    }
+}
+
+/**
+ * Struct for the buffers of the wavefront path tracer, which splits the paths of a frame into stages instead of tracing each from start to end.
+ * https://research.nvidia.com/publication/2013-07_megakernels-considered-harmful-wavefront-path-tracing-gpus
+ *
+ * Each pixel has a path, whose rays are kept in one of two queues: the one being traced, and the one of the next bounce.
+ * Every bounce intersects the queued rays, shades what they hit, then traces the shadow rays the shading queued, so each stage only does one kind of work.
+ * As paths end, the queues shrink, and the invocations beyond them return at once instead of idling next to long paths.
+ *
+ * Only surfaces lit by the emissive spheres and the sun are covered, see supports(), where anything else is left to the compute shader as it is.
+ */
+pub struct Wavefront {
+    buffers: [device::Buffer; 4],
+    capacity: usize,
+}
+
+/**
+ * Wavefront functions.
+ */
+impl Wavefront {
+    /**
+     * Creates the wavefront buffers, and binds them to the shader's bindings.
+     *
+     * @param width The width of the screen, in pixels.
+     * @param height The height of the screen, in pixels.
+     */
+    pub unsafe fn new( width: u32, height: u32 ) -> Wavefront {
+        let mut wavefront = Wavefront {
+            buffers: [0; 4],
+            capacity: 0,
+        };
+        wavefront.buffers = [(); 4].map( |_| device::current().create_buffer() );
+        wavefront.resize( width, height );
+        wavefront
+    }
+
+    /**
+     * Resizes the buffers, as every pixel has its own path.
+     *
+     * @param width The new width, in pixels.
+     * @param height The new height, in pixels.
+     */
+    pub unsafe fn resize( &mut self, width: u32, height: u32 ) {
+        self.capacity = width as usize * height as usize;
+
+        let sizes = [
+            self.capacity * PATH_SIZE,
+            QUEUE_HEADER_SIZE + 2 * self.capacity * RAY_SIZE,
+            self.capacity * HIT_SIZE,
+            QUEUE_HEADER_SIZE + SHADOW_RAYS_PER_PATH * self.capacity * SHADOW_RAY_SIZE,
+        ];
+        let bindings = [PATH_BINDING, RAY_BINDING, HIT_BINDING, SHADOW_RAY_BINDING];
+        for ( ( buffer, binding ), size ) in self.buffers.iter().zip( bindings ).zip( sizes ) {
+            device::current().allocate_buffer( *buffer, size, None, BufferUsage::Storage );
+            device::current().bind_storage_buffer( *buffer, binding );
+        }
+    }
+
+    /**
+     * Deletes the wavefront buffers, once they are done with.
+     */
+    pub unsafe fn delete( self ) {
+        for buffer in self.buffers {
+            device::current().delete_buffer( buffer );
+        }
+    }
+
+    /**
+     * Checks if the wavefront path covers everything a frame is rendered with.
+     * It doesn't cover the fog, volumes, ReSTIR, path guiding, caustics, light tracing, chromatic aberration, the AOVs, or the integrators which don't trace paths from the camera.
+     *
+     * @param settings The raytracing settings.
+     * @param camera The raytracing camera.
+     * @param aovs If the accumulator has AOVs.
+     * @param volumes If any volumes are shown.
+     */
+    pub fn supports( settings: &RTSettings, camera: &RTCamera, aovs: bool, volumes: bool ) -> bool {
+        matches!( settings.integrator, RTIntegrator::PathTrace | RTIntegrator::DirectLight )
+            && !settings.restir
+            && !settings.path_guiding
+            && !settings.caustics
+            && settings.fog.is_none()
+            && camera.chromatic_aberration == 0.0
+            && !aovs
+            && !volumes
+    }
+
+    /**
+     * Empties one of the counts at the start of a buffer.
+     *
+     * @param buffer Index of the buffer.
+     * @param count Index of the count.
+     */
+    unsafe fn clear_count( &self, buffer: usize, count: usize ) {
+        gl::BindBuffer( gl::SHADER_STORAGE_BUFFER, self.buffers[buffer] );
+        glapi::clear_buffer_u32( gl::SHADER_STORAGE_BUFFER, ( count * 4 ) as isize, Some( 4 ), 0 );
+        gl::BindBuffer( gl::SHADER_STORAGE_BUFFER, 0 );
+    }
+
+    /**
+     * Dispatches a stage, making what it wrote visible to the following stages.
+     *
+     * @param shader The compute shader.
+     * @param stage The stage.
+     * @param dispatch Dispatches the shader over the pixels.
+     */
+    unsafe fn run( &self, shader: &Shader, stage: WavefrontStage, dispatch: &dyn Fn() ) {
+        gl::Uniform1ui( shader.get_uniform_location( "wavefrontStage" ), stage.id() );
+        dispatch();
+        gl::MemoryBarrier( gl::SHADER_STORAGE_BARRIER_BIT | gl::BUFFER_UPDATE_BARRIER_BIT );
+    }
+
+    /**
+     * Renders a frame into the accumulator, in place of the shading pass.
+     * Every bounce is dispatched whether any paths are left or not, so the frame takes no readbacks to know when it's done.
+     * The compute shader must be active, and the accumulator's frame begun.
+     *
+     * @param shader The compute shader.
+     * @param settings The raytracing settings.
+     * @param dispatch Dispatches the shader over the pixels.
+     */
+    pub unsafe fn render( &self, shader: &Shader, settings: &RTSettings, dispatch: &dyn Fn() ) {
+        let mut queue = 0;
+        for ray in 0..settings.rays_per_frag {
+            gl::Uniform1ui( shader.get_uniform_location( "wavefrontRay" ), ray );
+            gl::Uniform1ui( shader.get_uniform_location( "wavefrontQueue" ), queue );
+            self.clear_count( 1, 1 - queue as usize );
+            self.run( shader, WavefrontStage::Generate, dispatch );
+
+            for _ in 0..settings.max_bounces.total {
+                // (The rays queued by the last stage are traced, and the other queue gets the next bounce's)
+                queue = 1 - queue;
+                gl::Uniform1ui( shader.get_uniform_location( "wavefrontQueue" ), queue );
+                self.run( shader, WavefrontStage::Intersect, dispatch );
+                self.clear_count( 1, 1 - queue as usize );
+                self.clear_count( 3, 0 );
+                self.run( shader, WavefrontStage::Shade, dispatch );
+                self.run( shader, WavefrontStage::Shadow, dispatch );
+            }
+        }
+        self.run( shader, WavefrontStage::Accumulate, dispatch );
+        gl::Uniform1ui( shader.get_uniform_location( "wavefrontStage" ), 0 );
+    }
+}
@@ -0,0 +1,488 @@
+use glow::HasContext;
+use tracing::{ info, warn };
+
+use crate::capabilities::Capabilities;
+use crate::device::{ Device, GlowDevice, TextureFilter, TextureFormat };
+use crate::error::EngineError;
+use crate::mesh;
+use crate::raytracing::{ RTCamera, RTMeshInfo, RTProjection, RTSettings, RTSphere, RTStereo, RTTriangle };
+use crate::scene::{ self, Scene };
+use crate::shader::ShaderType;
+use crate::webscene::SceneTextures;
+
+// The texture units the path tracer reads its textures from
+const SPHERE_UNIT: u32 = 0;
+const TRIANGLE_UNIT: u32 = 1;
+const MESH_UNIT: u32 = 2;
+const ACCUMULATION_UNIT: u32 = 3;
+
+/**
+ * Struct for the renderer which runs on WebGL2, where the engine's renderer can't, as it is built on storage buffers, compute shaders and image stores.
+ * It traces the scene in a fragment shader (webgl.frag) which reads the scene from textures, accumulating each frame into a float texture
+ * which is then shown on the canvas. Only the path tracer runs this way, without the post-processing, denoising or the window's controls.
+ *
+ * It goes through glow, so it is built natively as well, where the tests render with it.
+ */
+pub struct WebRenderer {
+    device: GlowDevice,
+    pipeline: glow::Program,
+    vertex_array: glow::VertexArray,
+    scene: [glow::Texture; 3],                                  // The spheres, triangles and meshes, see webscene
+    spheres_count: u32,
+    meshes_count: u32,
+    accumulation: [( glow::Texture, glow::Framebuffer ); 2],    // Read and written in turns, as a texture can't be both at once
+    current: usize,                                             // The accumulation target which has the image
+    width: u32,
+    height: u32,
+    frame_count: u32,
+    pub camera: RTCamera,
+    pub settings: RTSettings,
+}
+
+/**
+ * WebRenderer functions.
+ */
+impl WebRenderer {
+    /**
+     * Creates a renderer on a context, with the built-in scene's spheres, seen from where the window starts.
+     * Rendering to float textures must be supported, which WebGL2 has as EXT_color_buffer_float.
+     *
+     * @param gl The context, which must be current whenever the renderer is used.
+     * @param width The width of the canvas, in pixels.
+     * @param height The height of the canvas, in pixels.
+     *
+     * @return The renderer, or an error if the shaders didn't build or float textures can't be rendered to.
+     */
+    pub unsafe fn new( gl: glow::Context, width: u32, height: u32 ) -> Result<WebRenderer, EngineError> {
+        let device = GlowDevice::new( gl );
+        let pipeline = device.create_pipeline( &[( "shaders/webgl.vert", ShaderType::Vertex ), ( "shaders/webgl.frag", ShaderType::Fragment )], &[] )?;
+        let vertex_array = device.gl.create_vertex_array().map_err( |err| EngineError::gl( "WEBGL::VERTEX_ARRAY_NOT_CREATED", err ) )?;
+        let scene = [(); 3].map( |_| device.create_texture( 1, 1, TextureFormat::Rgba32F, TextureFilter::Nearest ) );
+        let accumulation = [Self::create_target( &device, width, height )?, Self::create_target( &device, width, height )?];
+
+        let mut renderer = WebRenderer {
+            device,
+            pipeline,
+            vertex_array,
+            scene,
+            spheres_count: 0,
+            meshes_count: 0,
+            accumulation,
+            current: 0,
+            width,
+            height,
+            frame_count: 0,
+            camera: RTCamera::look_at( glm::zero(), glm::vec3( 0.0, 0.0, 1.0 ), glm::vec3( 0.0, 1.0, 0.0 ), 60.0, glm::vec2( width as f32, height as f32 ) ),
+            settings: RTSettings::new(),
+        };
+        renderer.set_objects( &scene::scene_spheres( 0.0 ), &[], &[] )?;
+        Ok( renderer )
+    }
+
+    /**
+     * Creates an accumulation target, which is cleared to black.
+     *
+     * @param device The device.
+     * @param width The width of the target, in pixels.
+     * @param height The height of the target, in pixels.
+     *
+     * @return The target's texture and framebuffer, or an error if float textures can't be rendered to.
+     */
+    unsafe fn create_target( device: &GlowDevice, width: u32, height: u32 ) -> Result<( glow::Texture, glow::Framebuffer ), EngineError> {
+        let texture = device.create_texture( width.max( 1 ), height.max( 1 ), TextureFormat::Rgba32F, TextureFilter::Nearest );
+        let framebuffer = device.gl.create_framebuffer().map_err( |err| EngineError::gl( "WEBGL::FRAMEBUFFER_NOT_CREATED", err ) )?;
+        device.gl.bind_framebuffer( glow::FRAMEBUFFER, Some( framebuffer ) );
+        device.gl.framebuffer_texture_2d( glow::FRAMEBUFFER, glow::COLOR_ATTACHMENT0, glow::TEXTURE_2D, Some( texture ), 0 );
+        let status = device.gl.check_framebuffer_status( glow::FRAMEBUFFER );
+        device.gl.clear_color( 0.0, 0.0, 0.0, 1.0 );
+        device.gl.clear( glow::COLOR_BUFFER_BIT );
+        device.gl.bind_framebuffer( glow::FRAMEBUFFER, None );
+        if status != glow::FRAMEBUFFER_COMPLETE {
+            device.gl.delete_framebuffer( framebuffer );
+            device.delete_texture( texture );
+            return Err( EngineError::gl( "WEBGL::FLOAT_TARGETS_UNSUPPORTED", format!( "Float textures can't be rendered to (status {status:#x}), which needs EXT_color_buffer_float" ) ) );
+        }
+        Ok( ( texture, framebuffer ) )
+    }
+
+    /**
+     * Replaces the objects of the scene, and starts accumulating anew.
+     *
+     * @param spheres The spheres.
+     * @param triangles The triangles of every mesh.
+     * @param meshes The meshes, which cover the triangles.
+     *
+     * @return Ok, or an error if the scene doesn't fit in the textures.
+     */
+    pub unsafe fn set_objects( &mut self, spheres: &[RTSphere], triangles: &[RTTriangle], meshes: &[RTMeshInfo] ) -> Result<(), EngineError> {
+        let textures = SceneTextures::pack( spheres, triangles, meshes, Capabilities::current().max_texture_size )?;
+        for ( scene_texture, packed ) in self.scene.iter_mut().zip( [&textures.spheres, &textures.triangles, &textures.meshes] ) {
+            self.device.delete_texture( *scene_texture );
+            *scene_texture = self.device.create_texture( packed.width, packed.height, TextureFormat::Rgba32F, TextureFilter::Nearest );
+            self.device.write_texture( *scene_texture, packed.width, packed.height, packed.floats() );
+        }
+        ( self.spheres_count, self.meshes_count ) = ( textures.spheres_count, textures.meshes_count );
+        info!( spheres = spheres.len(), triangles = triangles.len(), meshes = meshes.len(), "Uploaded the scene's textures" );
+        self.reset();
+        Ok( () )
+    }
+
+    /**
+     * Replaces the scene with one loaded from JSON (see scene::Scene), along with its camera and settings if it has them.
+     * What WebGL2 can't render is logged and left out.
+     *
+     * @param json The scene, as JSON.
+     *
+     * @return Ok, or an error if the scene couldn't be parsed or doesn't fit in the textures.
+     */
+    pub unsafe fn load_scene( &mut self, json: &str ) -> Result<(), EngineError> {
+        let scene = Scene::from_json( "scene", json )?;
+        self.set_objects( &scene.spheres, &scene.triangles, &scene.meshes )?;
+        if let Some( camera ) = scene.camera {
+            self.camera = RTCamera { screen_size: glm::vec2( self.width as f32, self.height as f32 ), ..camera };
+        }
+        if let Some( settings ) = scene.settings {
+            self.settings = settings;
+        }
+        let unsupported = unsupported( &self.camera, &self.settings );
+        if !unsupported.is_empty() {
+            warn!( "WebGL2 renders without {}", unsupported.join( ", " ) );
+        }
+        Ok( () )
+    }
+
+    /**
+     * Replaces the meshes of the scene with a model, keeping its spheres.
+     *
+     * @param name What the model is of, for the log and the error.
+     * @param obj The contents of the model's .obj file.
+     *
+     * @return Ok, or an error if the model couldn't be parsed or doesn't fit in the textures.
+     */
+    pub unsafe fn load_model( &mut self, name: &str, obj: &[u8] ) -> Result<(), EngineError> {
+        let ( triangles, meshes ) = mesh::Model::new().load_from_bytes( name, obj )?.generate_raytracing_structs();
+        self.set_objects( &scene::scene_spheres( 0.0 ), &triangles, &meshes )
+    }
+
+    /**
+     * Resizes the image to the canvas, and starts accumulating anew.
+     *
+     * @param width The width of the canvas, in pixels.
+     * @param height The height of the canvas, in pixels.
+     *
+     * @return Ok, or an error if the new targets couldn't be created.
+     */
+    pub unsafe fn resize( &mut self, width: u32, height: u32 ) -> Result<(), EngineError> {
+        let accumulation = [Self::create_target( &self.device, width, height )?, Self::create_target( &self.device, width, height )?];
+        for ( texture, framebuffer ) in std::mem::replace( &mut self.accumulation, accumulation ) {
+            self.device.gl.delete_framebuffer( framebuffer );
+            self.device.delete_texture( texture );
+        }
+        ( self.width, self.height ) = ( width, height );
+        self.camera.set_screen_size( width, height );
+        self.reset();
+        Ok( () )
+    }
+
+    /**
+     * Starts accumulating anew, as must be done once the camera or settings have changed.
+     */
+    pub fn reset( &mut self ) {
+        self.frame_count = 0;
+    }
+
+    /**
+     * Gets how many frames have been accumulated.
+     */
+    pub fn frame_count( &self ) -> u32 {
+        self.frame_count
+    }
+
+    /**
+     * Traces a frame, blending it into the accumulated image, and shows the image on the canvas.
+     */
+    pub unsafe fn render_frame( &mut self ) {
+        let gl = &self.device.gl;
+        let ( read, written ) = ( self.accumulation[self.current], self.accumulation[1 - self.current] );
+        gl.use_program( Some( self.pipeline ) );
+        gl.bind_vertex_array( Some( self.vertex_array ) );
+        gl.viewport( 0, 0, self.width as i32, self.height as i32 );
+
+        // Bind the scene and the accumulated image
+        for ( unit, texture, name ) in [
+            ( SPHERE_UNIT, self.scene[0], "sphereTexture" ),
+            ( TRIANGLE_UNIT, self.scene[1], "triangleTexture" ),
+            ( MESH_UNIT, self.scene[2], "meshTexture" ),
+            ( ACCUMULATION_UNIT, read.0, "accumulation" ),
+        ] {
+            gl.active_texture( glow::TEXTURE0 + unit );
+            gl.bind_texture( glow::TEXTURE_2D, Some( texture ) );
+            gl.uniform_1_i32( self.uniform( name ).as_ref(), unit as i32 );
+        }
+        gl.uniform_1_i32( self.uniform( "spheresCount" ).as_ref(), self.spheres_count as i32 );
+        gl.uniform_1_i32( self.uniform( "meshesCount" ).as_ref(), self.meshes_count as i32 );
+
+        // Camera and settings
+        gl.uniform_2_f32( self.uniform( "screenSize" ).as_ref(), self.width as f32, self.height as f32 );
+        gl.uniform_1_f32( self.uniform( "fov" ).as_ref(), self.camera.fov );
+        gl.uniform_1_f32( self.uniform( "focusDistance" ).as_ref(), self.camera.focus_distance );
+        gl.uniform_1_f32( self.uniform( "apertureRadius" ).as_ref(), self.camera.aperture_radius );
+        gl.uniform_matrix_4_f32_slice( self.uniform( "localToWorld" ).as_ref(), false, self.camera.local_to_world.as_slice() );
+        gl.uniform_1_i32( self.uniform( "projection" ).as_ref(), self.camera.projection.id() as i32 );
+        match self.camera.projection {
+            RTProjection::Orthographic { height } => { gl.uniform_1_f32( self.uniform( "orthoHeight" ).as_ref(), height ) },
+            RTProjection::Fisheye { fov } => { gl.uniform_1_f32( self.uniform( "fisheyeFov" ).as_ref(), fov ) },
+            _ => {},
+        }
+        gl.uniform_1_i32( self.uniform( "maxBounces" ).as_ref(), self.settings.max_bounces.total as i32 );
+        gl.uniform_1_i32( self.uniform( "raysPerFrag" ).as_ref(), self.settings.rays_per_frag.max( 1 ) as i32 );
+        gl.uniform_1_f32( self.uniform( "divergeStrength" ).as_ref(), self.settings.diverge_strength );
+        gl.uniform_1_i32( self.uniform( "nextEventEstimation" ).as_ref(), self.settings.next_event_estimation as i32 );
+        gl.uniform_1_f32( self.uniform( "directClamp" ).as_ref(), self.settings.direct_clamp );
+        gl.uniform_1_f32( self.uniform( "indirectClamp" ).as_ref(), self.settings.indirect_clamp );
+        gl.uniform_1_f32( self.uniform( "tMax" ).as_ref(), self.settings.t_max );
+        gl.uniform_1_u32( self.uniform( "seed" ).as_ref(), self.settings.seed );
+        gl.uniform_1_u32( self.uniform( "frameCount" ).as_ref(), self.frame_count );
+
+        // Trace into the other target, which then has the image
+        gl.uniform_1_i32( self.uniform( "displayPass" ).as_ref(), 0 );
+        gl.bind_framebuffer( glow::FRAMEBUFFER, Some( written.1 ) );
+        gl.draw_arrays( glow::TRIANGLES, 0, 3 );
+        self.current = 1 - self.current;
+        self.frame_count += 1;
+
+        // Show it on the canvas
+        gl.uniform_1_i32( self.uniform( "displayPass" ).as_ref(), 1 );
+        gl.bind_texture( glow::TEXTURE_2D, Some( written.0 ) );
+        gl.bind_framebuffer( glow::FRAMEBUFFER, None );
+        gl.draw_arrays( glow::TRIANGLES, 0, 3 );
+
+        gl.bind_texture( glow::TEXTURE_2D, None );
+        gl.bind_vertex_array( None );
+        gl.use_program( None );
+    }
+
+    /**
+     * Reads the accumulated image.
+     *
+     * @return The image's RGBA pixels, row by row from the bottom.
+     */
+    pub unsafe fn read_pixels( &self ) -> Vec<f32> {
+        let mut pixels = vec![0.0f32; ( self.width * self.height * 4 ) as usize];
+        let bytes = std::slice::from_raw_parts_mut( pixels.as_mut_ptr() as *mut u8, std::mem::size_of_val( pixels.as_slice() ) );
+        self.device.gl.bind_framebuffer( glow::FRAMEBUFFER, Some( self.accumulation[self.current].1 ) );
+        self.device.gl.read_pixels( 0, 0, self.width as i32, self.height as i32, glow::RGBA, glow::FLOAT, glow::PixelPackData::Slice( bytes ) );
+        self.device.gl.bind_framebuffer( glow::FRAMEBUFFER, None );
+        pixels
+    }
+
+    /**
+     * Gets the location of one of the path tracer's uniforms, which is None if the shader compiler removed it.
+     */
+    unsafe fn uniform( &self, name: &str ) -> Option<glow::UniformLocation> {
+        self.device.gl.get_uniform_location( self.pipeline, name )
+    }
+
+    /**
+     * Deletes the renderer's objects, giving back the context.
+     */
+    pub unsafe fn delete( self ) -> glow::Context {
+        for ( texture, framebuffer ) in self.accumulation {
+            self.device.gl.delete_framebuffer( framebuffer );
+            self.device.delete_texture( texture );
+        }
+        for texture in self.scene {
+            self.device.delete_texture( texture );
+        }
+        self.device.gl.delete_vertex_array( self.vertex_array );
+        self.device.gl.delete_program( self.pipeline );
+        self.device.gl
+    }
+}
+
+/**
+ * Gets what of a camera and settings WebGL2 renders without, see webgl.frag.
+ *
+ * @param camera The camera.
+ * @param settings The settings.
+ *
+ * @return The features which are left out.
+ */
+pub fn unsupported( camera: &RTCamera, settings: &RTSettings ) -> Vec<&'static str> {
+    [
+        ( camera.stereo != RTStereo::Mono, "stereo" ),
+        ( camera.distortion != glm::Vec2::zeros(), "lens distortion" ),
+        ( camera.chromatic_aberration != 0.0, "chromatic aberration" ),
+        ( settings.fog.is_some(), "fog" ),
+        ( settings.sun.is_some(), "the sun" ),
+        ( settings.restir, "ReSTIR" ),
+        ( settings.path_guiding, "path guiding" ),
+        ( settings.caustics, "caustics" ),
+        ( settings.crop.is_some(), "cropping" ),
+    ].into_iter().filter_map( |( used, feature )| used.then_some( feature ) ).collect()
+}
+
+/**
+ * Struct for a WebGL2 renderer on a canvas, which is what the page's JavaScript creates and calls every animation frame.
+ * E.g. `const canvas = new WebCanvas( "canvas" ); requestAnimationFrame( function frame() { canvas.frame(); requestAnimationFrame( frame ); } );`
+ */
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub struct WebCanvas {
+    canvas: web_sys::HtmlCanvasElement,
+    renderer: WebRenderer,
+}
+
+/**
+ * WebCanvas functions, which JavaScript sees with the names they have here.
+ */
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+impl WebCanvas {
+    /**
+     * Creates a renderer on the page's canvas with an ID.
+     *
+     * @param canvas_id The ID of the canvas.
+     */
+    #[wasm_bindgen::prelude::wasm_bindgen(constructor)]
+    pub fn new( canvas_id: &str ) -> Result<WebCanvas, wasm_bindgen::JsValue> {
+        use wasm_bindgen::JsCast;
+
+        let canvas = web_sys::window()
+            .and_then( |window| window.document() )
+            .and_then( |document| document.get_element_by_id( canvas_id ) )
+            .and_then( |element| element.dyn_into::<web_sys::HtmlCanvasElement>().ok() )
+            .ok_or_else( || js_error( EngineError::gl( "WEBGL::CANVAS_NOT_FOUND", format!( "There is no canvas with the ID {canvas_id}" ) ) ) )?;
+        let context = canvas.get_context( "webgl2" )?
+            .and_then( |context| context.dyn_into::<web_sys::WebGl2RenderingContext>().ok() )
+            .ok_or_else( || js_error( EngineError::gl( "WEBGL::NOT_SUPPORTED", "The browser has no WebGL2" ) ) )?;
+
+        let gl = glow::Context::from_webgl2_context( context );
+        crate::glapi::GlApi::detect_webgl2();
+        unsafe {
+            Capabilities::detect_webgl2( &gl );
+            let renderer = WebRenderer::new( gl, canvas.width(), canvas.height() ).map_err( js_error )?;
+            Ok( WebCanvas { canvas, renderer } )
+        }
+    }
+
+    /**
+     * Replaces the scene with one given as JSON, see scene::Scene.
+     */
+    pub fn load_scene( &mut self, json: &str ) -> Result<(), wasm_bindgen::JsValue> {
+        unsafe { self.renderer.load_scene( json ) }.map_err( js_error )
+    }
+
+    /**
+     * Replaces the meshes with the contents of a .obj file, such as one fetched by the page.
+     */
+    pub fn load_model( &mut self, name: &str, obj: &[u8] ) -> Result<(), wasm_bindgen::JsValue> {
+        unsafe { self.renderer.load_model( name, obj ) }.map_err( js_error )
+    }
+
+    /**
+     * Moves the camera to a position, looking towards a target which is in focus.
+     */
+    pub fn look_at( &mut self, eye_x: f32, eye_y: f32, eye_z: f32, target_x: f32, target_y: f32, target_z: f32 ) {
+        let camera = &self.renderer.camera;
+        self.renderer.camera = RTCamera {
+            aperture_radius: camera.aperture_radius,
+            ..RTCamera::look_at( glm::vec3( eye_x, eye_y, eye_z ), glm::vec3( target_x, target_y, target_z ), glm::vec3( 0.0, 1.0, 0.0 ), camera.fov, camera.screen_size )
+        };
+        self.renderer.reset();
+    }
+
+    /**
+     * Renders a frame, after resizing the image if the canvas was resized.
+     */
+    pub fn frame( &mut self ) -> Result<(), wasm_bindgen::JsValue> {
+        let size = ( self.canvas.width(), self.canvas.height() );
+        if size != ( self.renderer.width, self.renderer.height ) {
+            unsafe { self.renderer.resize( size.0, size.1 ) }.map_err( js_error )?;
+        }
+        unsafe { self.renderer.render_frame() };
+        Ok( () )
+    }
+
+    /**
+     * Gets how many frames have been accumulated.
+     */
+    pub fn frame_count( &self ) -> u32 {
+        self.renderer.frame_count()
+    }
+}
+
+/**
+ * Turns an error into what JavaScript gets thrown.
+ */
+#[cfg(target_arch = "wasm32")]
+fn js_error( err: EngineError ) -> wasm_bindgen::JsValue {
+    wasm_bindgen::JsValue::from_str( &err.to_string() )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::offscreen;
+    use crate::raytracing::RTMaterial;
+    use crate::testing;
+
+    #[test]
+    fn unsupported_features_are_listed() {
+        let camera = RTCamera { stereo: RTStereo::SideBySide { ipd: 0.064 }, ..RTCamera::look_at( glm::zero(), glm::vec3( 0.0, 0.0, 1.0 ), glm::vec3( 0.0, 1.0, 0.0 ), 60.0, glm::vec2( 4.0, 4.0 ) ) };
+        assert!( unsupported( &RTCamera { stereo: RTStereo::Mono, ..camera.clone() }, &RTSettings::new() ).is_empty() );
+        assert!( unsupported( &RTCamera { stereo: RTStereo::Mono, projection: RTProjection::Equirectangular, ..camera.clone() }, &RTSettings::new() ).is_empty() );
+        assert_eq!( unsupported( &camera, &RTSettings { caustics: true, ..RTSettings::new() } ), vec!["stereo", "caustics"] );
+    }
+
+    #[test]
+    #[ignore = "needs an OpenGL context, run with cargo test -- --include-ignored"]
+    fn emissive_spheres_light_the_image() {
+        // (A white sphere filling the view, in front of a glowing one behind the camera)
+        let pixels = testing::with_gl( || unsafe {
+            let mut renderer = WebRenderer::new( testing::glow_context(), 8, 8 ).unwrap();
+            let white = RTMaterial { color: glm::vec4( 1.0, 1.0, 1.0, 1.0 ), ..RTMaterial::new() };
+            let light = RTMaterial { emission_color: glm::vec4( 1.0, 1.0, 1.0, 4.0 ), ..RTMaterial::new() };
+            renderer.set_objects( &[
+                RTSphere { radius: 4.0, center: glm::vec3( 0.0, 0.0, 6.0 ).into(), material: white },
+                RTSphere { radius: 1.0, center: glm::vec3( 0.0, 0.0, -2.0 ).into(), material: light },
+            ], &[], &[] ).unwrap();
+            for _ in 0..4 {
+                renderer.render_frame();
+            }
+            assert_eq!( renderer.frame_count(), 4 );
+            let pixels = renderer.read_pixels();
+            renderer.delete();
+            pixels
+        } );
+        assert_eq!( pixels.len(), 8 * 8 * 4 );
+        let brightness = pixels.chunks( 4 ).map( |pixel| pixel[0] ).sum::<f32>() / 64.0;
+        assert!( brightness > 0.01 && pixels.iter().all( |value| value.is_finite() ), "brightness {brightness}" );
+    }
+
+    #[test]
+    #[ignore = "needs an OpenGL context, run with cargo test -- --include-ignored"]
+    fn meshes_are_read_from_their_textures() {
+        // (A glowing triangle in the middle of the view, facing the camera)
+        let pixels = testing::with_gl( || unsafe {
+            let mut renderer = WebRenderer::new( testing::glow_context(), 8, 8 ).unwrap();
+            renderer.settings.diverge_strength = 0.0;
+            let triangle = RTTriangle {
+                p0: glm::vec3( -1.0, -1.0, 3.0 ).into(),
+                p1: glm::vec3( 0.0, 1.0, 3.0 ).into(),
+                p2: glm::vec3( 1.0, -1.0, 3.0 ).into(),
+                material: RTMaterial { emission_color: glm::vec4( 1.0, 1.0, 1.0, 1.0 ), ..RTMaterial::new() },
+                ..RTTriangle::new()
+            };
+            let ( triangles, meshes ) = offscreen::meshes_of( &[vec![triangle]] );
+            renderer.set_objects( &[], &triangles, &meshes ).unwrap();
+            renderer.render_frame();
+            let pixels = renderer.read_pixels();
+            renderer.delete();
+            pixels
+        } );
+        let red = |x: usize, y: usize| pixels[( y * 8 + x ) * 4];
+        assert!( ( red( 4, 4 ) - 1.0 ).abs() < 0.01, "center {}", red( 4, 4 ) );
+        assert_eq!( red( 0, 0 ), 0.0 );
+    }
+}
@@ -0,0 +1,174 @@
+use crate::error::EngineError;
+use crate::raytracing::{ RTMaterial, RTMeshInfo, RTSphere, RTTriangle, Vec3a16 };
+
+// The width of the scene's textures, in texels, which every WebGL2 context has (see Capabilities::minimum())
+pub const SCENE_TEXTURE_WIDTH: u32 = 1024;
+
+// How many RGBA texels each object takes up, which webgl.frag reads them by (see LoadSphere(), LoadTriangle() and LoadMesh())
+pub const SPHERE_TEXELS: u32 = 5;     // center and radius, color, emission color, specular color, smoothness
+pub const TRIANGLE_TEXELS: u32 = 9;   // p0 and smoothness, p1, p2, normal0, normal1, normal2, color, emission color, specular color
+pub const MESH_TEXELS: u32 = 2;       // bounding box min and start index, bounding box max and count
+
+// The largest index which a float holds exactly, as the meshes' triangle ranges are stored in floats
+const MAX_EXACT_INDEX: u32 = 1 << 24;
+
+/**
+ * Struct for the texels of one of the scene's RGBA32F textures, with its rows filled left to right, bottom up.
+ */
+#[derive(Clone, PartialEq, Debug)]
+pub struct SceneTexture {
+    pub width: u32,
+    pub height: u32,
+    pub texels: Vec<[f32; 4]>, // width * height texels, where the last row is padded with zeroes
+}
+
+/**
+ * Struct for a scene as WebGL2 gets it, which has no storage buffers, so the spheres, triangles and meshes are each stored in a texture instead.
+ * The shader fetches each object's texels by index, see webgl.frag.
+ */
+#[derive(Clone, PartialEq, Debug)]
+pub struct SceneTextures {
+    pub spheres: SceneTexture,
+    pub triangles: SceneTexture,
+    pub meshes: SceneTexture,
+    pub spheres_count: u32,
+    pub meshes_count: u32,
+}
+
+/**
+ * SceneTexture functions.
+ */
+impl SceneTexture {
+    /**
+     * Lays texels out in rows of SCENE_TEXTURE_WIDTH.
+     *
+     * @param name What the texels are of, for the error.
+     * @param texels The texels.
+     * @param max_texture_size The largest height the texture may have, see Capabilities::max_texture_size.
+     *
+     * @return The texture, which has at least one row, or an error if the texels need more rows than that.
+     */
+    fn new( name: &str, mut texels: Vec<[f32; 4]>, max_texture_size: u32 ) -> Result<SceneTexture, EngineError> {
+        let height = ( texels.len() as u64 ).div_ceil( SCENE_TEXTURE_WIDTH as u64 ).max( 1 );
+        if height > max_texture_size as u64 {
+            return Err( EngineError::render( "WEBGL::SCENE_TOO_LARGE", format!( "The {name} need {height} rows of texels, but textures may only have {max_texture_size}" ) ) );
+        }
+        texels.resize( ( SCENE_TEXTURE_WIDTH as u64 * height ) as usize, [0.0; 4] );
+        Ok( SceneTexture { width: SCENE_TEXTURE_WIDTH, height: height as u32, texels } )
+    }
+
+    /**
+     * Gets the texels as the floats which are uploaded.
+     */
+    pub fn floats( &self ) -> &[f32] {
+        self.texels.as_flattened()
+    }
+}
+
+/**
+ * SceneTextures functions.
+ */
+impl SceneTextures {
+    /**
+     * Packs a scene into textures.
+     *
+     * @param spheres The spheres.
+     * @param triangles The triangles of every mesh.
+     * @param meshes The meshes, which cover the triangles.
+     * @param max_texture_size The largest width and height of the textures, see Capabilities::max_texture_size.
+     *
+     * @return The textures, or an error if the scene doesn't fit in them.
+     */
+    pub fn pack( spheres: &[RTSphere], triangles: &[RTTriangle], meshes: &[RTMeshInfo], max_texture_size: u32 ) -> Result<SceneTextures, EngineError> {
+        // (The shader can't tell a rounded index from the one which was meant)
+        if triangles.len() > MAX_EXACT_INDEX as usize {
+            return Err( EngineError::render( "WEBGL::SCENE_TOO_LARGE", format!( "The scene has {} triangles, but only {MAX_EXACT_INDEX} can be indexed", triangles.len() ) ) );
+        }
+
+        let sphere_texels = spheres.iter().flat_map( |sphere| {
+            let [color, emission, specular, smoothness] = material_texels( &sphere.material );
+            [texel( sphere.center, sphere.radius ), color, emission, specular, smoothness]
+        } ).collect();
+        let triangle_texels = triangles.iter().flat_map( |triangle| {
+            let [color, emission, specular, _] = material_texels( &triangle.material );
+            [
+                texel( triangle.p0, triangle.material.smoothness ),
+                texel( triangle.p1, 0.0 ),
+                texel( triangle.p2, 0.0 ),
+                texel( triangle.normal0, 0.0 ),
+                texel( triangle.normal1, 0.0 ),
+                texel( triangle.normal2, 0.0 ),
+                color,
+                emission,
+                specular,
+            ]
+        } ).collect();
+        let mesh_texels = meshes.iter().flat_map( |mesh| {
+            [texel( mesh.boundingbox_min, mesh.start_index as f32 ), texel( mesh.boundingbox_max, mesh.count as f32 )]
+        } ).collect();
+
+        Ok( SceneTextures {
+            spheres: SceneTexture::new( "spheres", sphere_texels, max_texture_size )?,
+            triangles: SceneTexture::new( "triangles", triangle_texels, max_texture_size )?,
+            meshes: SceneTexture::new( "meshes", mesh_texels, max_texture_size )?,
+            spheres_count: spheres.len() as u32,
+            meshes_count: meshes.len() as u32,
+        } )
+    }
+}
+
+/**
+ * Makes a texel of a position (or direction) and one more value.
+ */
+fn texel( v: Vec3a16, w: f32 ) -> [f32; 4] {
+    [v.x, v.y, v.z, w]
+}
+
+/**
+ * Makes the texels of a material: its color, emission color, specular color and smoothness.
+ */
+fn material_texels( material: &RTMaterial ) -> [[f32; 4]; 4] {
+    [material.color.into(), material.emission_color.into(), material.specular_color.into(), [material.smoothness, 0.0, 0.0, 0.0]]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn objects_are_packed_into_rows() {
+        let sphere = RTSphere { radius: 2.0, center: glm::vec3( 1.0, 2.0, 3.0 ).into(), material: RTMaterial { smoothness: 0.25, ..RTMaterial::new() } };
+        let spheres = vec![sphere; 300];
+        let textures = SceneTextures::pack( &spheres, &[], &[], 2048 ).unwrap();
+
+        // (300 spheres take 1500 texels, so two rows)
+        assert_eq!( ( textures.spheres.width, textures.spheres.height ), ( SCENE_TEXTURE_WIDTH, 2 ) );
+        assert_eq!( textures.spheres.floats().len(), ( SCENE_TEXTURE_WIDTH * 2 * 4 ) as usize );
+        let last = ( 299 * SPHERE_TEXELS ) as usize;
+        assert_eq!( textures.spheres.texels[last], [1.0, 2.0, 3.0, 2.0] );
+        assert_eq!( textures.spheres.texels[last + 4], [0.25, 0.0, 0.0, 0.0] );
+        assert_eq!( textures.spheres.texels[( 300 * SPHERE_TEXELS ) as usize], [0.0; 4] );
+
+        // (Empty textures still have a row, as WebGL2 can't make them without one)
+        assert_eq!( textures.triangles.height, 1 );
+        assert_eq!( textures.spheres_count, 300 );
+    }
+
+    #[test]
+    fn meshes_keep_their_triangle_ranges() {
+        let ( triangles, meshes ) = crate::offscreen::meshes_of( &[vec![RTTriangle::new(); 3], vec![RTTriangle::new(); 2]] );
+        let textures = SceneTextures::pack( &[], &triangles, &meshes, 2048 ).unwrap();
+        assert_eq!( textures.meshes_count, 2 );
+        assert_eq!( textures.meshes.texels[( MESH_TEXELS ) as usize][3], 3.0 );
+        assert_eq!( textures.meshes.texels[( MESH_TEXELS + 1 ) as usize][3], 2.0 );
+        assert_eq!( textures.triangles.texels.len(), SCENE_TEXTURE_WIDTH as usize );
+    }
+
+    #[test]
+    fn scenes_which_need_taller_textures_are_refused() {
+        let spheres = vec![RTSphere::new(); ( SCENE_TEXTURE_WIDTH * 3 ) as usize];
+        assert!( SceneTextures::pack( &spheres, &[], &[], 15 ).is_ok() );
+        let err = SceneTextures::pack( &spheres, &[], &[], 14 ).unwrap_err();
+        assert!( err.to_string().contains( "WEBGL::SCENE_TOO_LARGE" ) );
+    }
+}
@@ -0,0 +1,418 @@
+use glutin::NotCurrent;
+use glutin::event::{ MouseButton, VirtualKeyCode };
+
+use crate::error::EngineError;
+use crate::context::RenderContext;
+#[cfg(any(feature = "winit", feature = "glfw"))]
+use crate::glapi::GlApi;
+
+/**
+ * Enum for the input and window events which the engine handles, whichever backend the window is made with.
+ * Keys and buttons are glutin's, which the other backends translate theirs to.
+ */
+#[allow(dead_code)]
+#[derive(Clone, Copy)]
+pub enum WindowEvent {
+    CloseRequested,                 // The window's close button was pressed
+    Resized( u32, u32 ),            // The window's framebuffer was resized, to this many pixels
+    CursorMoved( glm::Vec2 ),       // The cursor moved, to this pixel with (0, 0) in the top left corner
+    MouseInput( MouseButton, bool ), // A mouse button was pressed (true) or released (false)
+    KeyboardInput( VirtualKeyCode, bool ), // A key was pressed (true) or released (false)
+}
+
+/**
+ * Trait for the OpenGL context of a window, which the render thread draws with, see RenderContext::Windowed.
+ * It is created on the thread with the event loop and sent to the render thread, where it is made current.
+ */
+pub trait WindowContext: Send {
+    /**
+     * Makes the context current on this thread.
+     */
+    unsafe fn make_current( &mut self ) -> Result<(), EngineError>;
+
+    /**
+     * Gets the address of an OpenGL function, once the context is current.
+     */
+    fn get_proc_address( &self, symbol: &str ) -> *const core::ffi::c_void;
+
+    /**
+     * Resizes the framebuffer, for backends which don't follow the window by themselves.
+     *
+     * @param width The new width, in pixels.
+     * @param height The new height, in pixels.
+     */
+    fn resize( &self, width: u32, height: u32 );
+
+    /**
+     * Sets the window's title.
+     *
+     * @param title The new title.
+     */
+    fn set_title( &self, title: &str );
+
+    /**
+     * Sets the size of the window, which the framebuffer follows (see WindowEvent::Resized).
+     *
+     * @param width The new width, in logical pixels.
+     * @param height The new height, in logical pixels.
+     */
+    fn set_size( &self, width: u32, height: u32 );
+
+    /**
+     * Turns vsync on or off, for backends which can change it once the context is made.
+     * Must be called on the thread which the context is current on.
+     *
+     * @param vsync If the frames are shown in step with the display's refresh rate.
+     *
+     * @return If vsync was changed, or false if it only changes once the window is created again.
+     */
+    unsafe fn set_vsync( &self, vsync: bool ) -> bool;
+
+    /**
+     * Shows what has been drawn, by swapping the window's buffers.
+     */
+    fn swap_buffers( &self ) -> Result<(), EngineError>;
+}
+
+/**
+ * Trait for a windowing library which the engine's window can be made with, such as winit or GLFW (see the cargo features),
+ * so the engine can be embedded into an application which already uses one of them.
+ * The backend creates the window and its context, and runs the event loop on the main thread while the render thread draws.
+ */
+pub trait WindowBackend: Sized {
+    /**
+     * Creates the window, with an OpenGL context of at least 4.4.
+     *
+     * @param title The title of the window.
+     * @param width The width of the window, in logical pixels (which may be scaled to the display).
+     * @param height The height of the window, in logical pixels.
+     * @param vsync If the frames are shown in step with the display's refresh rate.
+     *
+     * @return The backend and the window's context, which isn't current yet, or an error if the window couldn't be created.
+     */
+    fn create( title: &str, width: u32, height: u32, vsync: bool ) -> Result<( Self, RenderContext<NotCurrent> ), EngineError>;
+
+    /**
+     * Gets the size of the window's framebuffer, in pixels.
+     */
+    fn size( &self ) -> ( u32, u32 );
+
+    /**
+     * Runs the event loop on this thread, passing the events to a handler until it returns false.
+     *
+     * @param handler Handles an event, and returns if the event loop should keep running.
+     */
+    fn run<F: FnMut( WindowEvent ) -> bool + 'static>( self, handler: F );
+}
+
+/**
+ * Struct for a window made with winit, through glutin, which also creates the offline renders' contexts.
+ */
+#[cfg(feature = "winit")]
+#[allow(dead_code)]
+pub struct WinitBackend {
+    event_loop: glutin::event_loop::EventLoop<()>,
+    size: ( u32, u32 ),
+}
+
+/**
+ * Struct for a winit window's context, which glutin changes the type of as it is made current.
+ */
+#[cfg(feature = "winit")]
+#[allow(dead_code)]
+struct WinitContext {
+    not_current: Option<glutin::WindowedContext<NotCurrent>>,
+    current: Option<glutin::WindowedContext<glutin::PossiblyCurrent>>,
+}
+
+// (The context is only sent to the render thread before it is made current, like glutin's own contexts which aren't current)
+#[cfg(feature = "winit")]
+unsafe impl Send for WinitContext {}
+
+#[cfg(feature = "winit")]
+impl WindowContext for WinitContext {
+    unsafe fn make_current( &mut self ) -> Result<(), EngineError> {
+        let context = self.not_current.take().ok_or( EngineError::gl( "WINDOW::CONTEXT_ALREADY_CURRENT", "" ) )?;
+        self.current = Some( context.make_current().map_err( |( _, err )| EngineError::gl( "WINDOW::CONTEXT_NOT_CURRENT", err.to_string() ) )? );
+        Ok( () )
+    }
+
+    fn get_proc_address( &self, symbol: &str ) -> *const core::ffi::c_void {
+        self.current.as_ref().map_or( std::ptr::null(), |context| context.get_proc_address( symbol ) )
+    }
+
+    fn resize( &self, width: u32, height: u32 ) {
+        if let Some( context ) = &self.current {
+            context.resize( glutin::dpi::PhysicalSize::new( width, height ) );
+        }
+    }
+
+    fn set_title( &self, title: &str ) {
+        if let Some( context ) = &self.current {
+            context.window().set_title( title );
+        }
+    }
+
+    fn set_size( &self, width: u32, height: u32 ) {
+        if let Some( context ) = &self.current {
+            context.window().set_inner_size( glutin::dpi::LogicalSize::new( width, height ) );
+        }
+    }
+
+    unsafe fn set_vsync( &self, _vsync: bool ) -> bool {
+        // (glutin only sets the swap interval as it builds the context)
+        false
+    }
+
+    fn swap_buffers( &self ) -> Result<(), EngineError> {
+        match &self.current {
+            Some( context ) => { context.swap_buffers().map_err( |err| EngineError::gl( "WINDOW::SWAP_FAILED", err.to_string() ) ) },
+            None => { Err( EngineError::gl( "WINDOW::CONTEXT_NOT_CURRENT", "" ) ) },
+        }
+    }
+}
+
+#[cfg(feature = "winit")]
+impl WindowBackend for WinitBackend {
+    fn create( title: &str, width: u32, height: u32, vsync: bool ) -> Result<( Self, RenderContext<NotCurrent> ), EngineError> {
+        let window_builder = glutin::window::WindowBuilder::new()
+            .with_title( title )
+            .with_resizable( true )
+            .with_inner_size( glutin::dpi::LogicalSize::new( width, height ) );
+        let event_loop = glutin::event_loop::EventLoop::new();
+
+        // (Desktop OpenGL is asked for first, and OpenGL ES where it isn't available, see GlApi::requested())
+        let mut errors = Vec::new();
+        let mut created = None;
+        for api in GlApi::requested() {
+            match glutin::ContextBuilder::new().with_gl( api.gl_request() ).with_vsync( vsync ).build_windowed( window_builder.clone(), &event_loop ) {
+                Ok( context ) => { created = Some( context ); break },
+                Err( err ) => { errors.push( format!( "{api:?}: {err}" ) ) },
+            }
+        }
+        let context = created.ok_or_else( || EngineError::gl( "WINDOW::NOT_CREATED", errors.join( "\n" ) ) )?;
+        let size = context.window().inner_size();
+        let context = WinitContext { not_current: Some( context ), current: None };
+        Ok( ( WinitBackend { event_loop, size: ( size.width, size.height ) }, RenderContext::Windowed( Box::new( context ) ) ) )
+    }
+
+    fn size( &self ) -> ( u32, u32 ) {
+        self.size
+    }
+
+    fn run<F: FnMut( WindowEvent ) -> bool + 'static>( self, mut handler: F ) {
+        use glutin::event::{ Event, ElementState, KeyboardInput, WindowEvent as WinitEvent };
+        use glutin::event_loop::ControlFlow;
+
+        self.event_loop.run( move | event, _, control_flow | {
+            *control_flow = ControlFlow::Wait;
+            let event = match event {
+                Event::WindowEvent { event: WinitEvent::CloseRequested, .. } => { WindowEvent::CloseRequested },
+                Event::WindowEvent { event: WinitEvent::Resized( size ), .. } => { WindowEvent::Resized( size.width, size.height ) },
+                Event::WindowEvent { event: WinitEvent::CursorMoved { position, .. }, .. } => {
+                    WindowEvent::CursorMoved( glm::vec2( position.x as f32, position.y as f32 ) )
+                },
+                Event::WindowEvent { event: WinitEvent::MouseInput { state, button, .. }, .. } => {
+                    WindowEvent::MouseInput( button, state == ElementState::Pressed )
+                },
+                Event::WindowEvent { event: WinitEvent::KeyboardInput {
+                    input: KeyboardInput { state, virtual_keycode: Some( key_code ), .. }, ..
+                }, .. } => { WindowEvent::KeyboardInput( key_code, state == ElementState::Pressed ) },
+                _ => { return },
+            };
+            if !handler( event ) {
+                *control_flow = ControlFlow::Exit;
+            }
+        } );
+    }
+}
+
+/**
+ * Struct for a window made with GLFW, which must be installed (the glfw crate is built without its bundled copy).
+ * GLFW's windows may only be changed from the main thread, so the render thread's changes are sent to the event loop to make.
+ */
+#[cfg(feature = "glfw")]
+pub struct GlfwBackend {
+    glfw: glfw::Glfw,
+    window: glfw::PWindow,
+    events: glfw::GlfwReceiver<( f64, glfw::WindowEvent )>,
+    changes: std::sync::mpsc::Receiver<GlfwChange>,
+}
+
+/**
+ * Enum for the changes to a GLFW window which the render thread sends to the event loop.
+ */
+#[cfg(feature = "glfw")]
+enum GlfwChange {
+    Title( String ),
+    Size( u32, u32 ), // In screen coordinates, which are logical pixels
+}
+
+/**
+ * Struct for a GLFW window's context, which GLFW lets other threads draw with.
+ */
+#[cfg(feature = "glfw")]
+struct GlfwContext {
+    context: std::cell::RefCell<glfw::PRenderContext>,
+    changes: std::sync::mpsc::Sender<GlfwChange>,
+}
+
+#[cfg(feature = "glfw")]
+impl WindowContext for GlfwContext {
+    unsafe fn make_current( &mut self ) -> Result<(), EngineError> {
+        use glfw::Context;
+        self.context.get_mut().make_current();
+        Ok( () )
+    }
+
+    fn get_proc_address( &self, symbol: &str ) -> *const core::ffi::c_void {
+        self.context.borrow_mut().get_proc_address( symbol ) as *const _
+    }
+
+    fn resize( &self, _width: u32, _height: u32 ) {
+        // (GLFW resizes the framebuffer with the window)
+    }
+
+    fn set_title( &self, title: &str ) {
+        let _ = self.changes.send( GlfwChange::Title( title.to_string() ) );
+    }
+
+    fn set_size( &self, width: u32, height: u32 ) {
+        let _ = self.changes.send( GlfwChange::Size( width, height ) );
+    }
+
+    unsafe fn set_vsync( &self, vsync: bool ) -> bool {
+        // (The swap interval is the current context's, which may be set from any thread)
+        glfw::ffi::glfwSwapInterval( vsync as i32 );
+        true
+    }
+
+    fn swap_buffers( &self ) -> Result<(), EngineError> {
+        use glfw::Context;
+        self.context.borrow_mut().swap_buffers();
+        Ok( () )
+    }
+}
+
+/**
+ * Translates a GLFW key to the engine's keys, for the keys which have one.
+ */
+#[cfg(feature = "glfw")]
+fn glfw_key( key: glfw::Key ) -> Option<VirtualKeyCode> {
+    use glfw::Key;
+    Some( match key {
+        Key::A => { VirtualKeyCode::A }, Key::B => { VirtualKeyCode::B }, Key::C => { VirtualKeyCode::C }, Key::D => { VirtualKeyCode::D },
+        Key::E => { VirtualKeyCode::E }, Key::F => { VirtualKeyCode::F }, Key::G => { VirtualKeyCode::G }, Key::H => { VirtualKeyCode::H },
+        Key::I => { VirtualKeyCode::I }, Key::J => { VirtualKeyCode::J }, Key::K => { VirtualKeyCode::K }, Key::L => { VirtualKeyCode::L },
+        Key::M => { VirtualKeyCode::M }, Key::N => { VirtualKeyCode::N }, Key::O => { VirtualKeyCode::O }, Key::P => { VirtualKeyCode::P },
+        Key::Q => { VirtualKeyCode::Q }, Key::R => { VirtualKeyCode::R }, Key::S => { VirtualKeyCode::S }, Key::T => { VirtualKeyCode::T },
+        Key::U => { VirtualKeyCode::U }, Key::V => { VirtualKeyCode::V }, Key::W => { VirtualKeyCode::W }, Key::X => { VirtualKeyCode::X },
+        Key::Y => { VirtualKeyCode::Y }, Key::Z => { VirtualKeyCode::Z },
+        Key::Num0 => { VirtualKeyCode::Key0 }, Key::Num1 => { VirtualKeyCode::Key1 }, Key::Num2 => { VirtualKeyCode::Key2 },
+        Key::Num3 => { VirtualKeyCode::Key3 }, Key::Num4 => { VirtualKeyCode::Key4 }, Key::Num5 => { VirtualKeyCode::Key5 },
+        Key::Num6 => { VirtualKeyCode::Key6 }, Key::Num7 => { VirtualKeyCode::Key7 }, Key::Num8 => { VirtualKeyCode::Key8 },
+        Key::Num9 => { VirtualKeyCode::Key9 },
+        Key::F1 => { VirtualKeyCode::F1 }, Key::F2 => { VirtualKeyCode::F2 }, Key::F3 => { VirtualKeyCode::F3 }, Key::F4 => { VirtualKeyCode::F4 },
+        Key::F5 => { VirtualKeyCode::F5 }, Key::F6 => { VirtualKeyCode::F6 }, Key::F7 => { VirtualKeyCode::F7 }, Key::F8 => { VirtualKeyCode::F8 },
+        Key::F9 => { VirtualKeyCode::F9 }, Key::F10 => { VirtualKeyCode::F10 }, Key::F11 => { VirtualKeyCode::F11 }, Key::F12 => { VirtualKeyCode::F12 },
+        Key::Up => { VirtualKeyCode::Up }, Key::Down => { VirtualKeyCode::Down }, Key::Left => { VirtualKeyCode::Left }, Key::Right => { VirtualKeyCode::Right },
+        Key::LeftBracket => { VirtualKeyCode::LBracket }, Key::RightBracket => { VirtualKeyCode::RBracket },
+        Key::Minus => { VirtualKeyCode::Minus }, Key::Equal => { VirtualKeyCode::Equals },
+        Key::Comma => { VirtualKeyCode::Comma }, Key::Period => { VirtualKeyCode::Period },
+        Key::Semicolon => { VirtualKeyCode::Semicolon }, Key::Apostrophe => { VirtualKeyCode::Apostrophe },
+        Key::Space => { VirtualKeyCode::Space }, Key::Escape => { VirtualKeyCode::Escape },
+        Key::LeftShift => { VirtualKeyCode::LShift }, Key::RightShift => { VirtualKeyCode::RShift },
+        Key::LeftControl => { VirtualKeyCode::LControl }, Key::RightControl => { VirtualKeyCode::RControl },
+        _ => { return None },
+    } )
+}
+
+#[cfg(feature = "glfw")]
+impl WindowBackend for GlfwBackend {
+    fn create( title: &str, width: u32, height: u32, vsync: bool ) -> Result<( Self, RenderContext<NotCurrent> ), EngineError> {
+        use glfw::Context;
+        let mut glfw = glfw::init( glfw::log_errors ).map_err( |err| EngineError::gl( "WINDOW::NOT_CREATED", format!( "{err:?}" ) ) )?;
+        glfw.window_hint( glfw::WindowHint::Resizable( true ) );
+        glfw.window_hint( glfw::WindowHint::ScaleToMonitor( true ) );
+        glfw.window_hint( glfw::WindowHint::OpenGlDebugContext( cfg!(debug_assertions) ) );
+        let mut created = None;
+        for api in GlApi::requested() {
+            let ( major, minor ) = api.version();
+            glfw.window_hint( glfw::WindowHint::ContextVersion( major as u32, minor as u32 ) );
+            match api {
+                GlApi::Desktop => {
+                    glfw.window_hint( glfw::WindowHint::ClientApi( glfw::ClientApiHint::OpenGl ) );
+                    glfw.window_hint( glfw::WindowHint::OpenGlProfile( glfw::OpenGlProfileHint::Core ) );
+                },
+                GlApi::Es | GlApi::WebGl2 => {
+                    glfw.window_hint( glfw::WindowHint::ClientApi( glfw::ClientApiHint::OpenGlEs ) );
+                    glfw.window_hint( glfw::WindowHint::OpenGlProfile( glfw::OpenGlProfileHint::Any ) );
+                },
+            }
+            created = glfw.create_window( width, height, title, glfw::WindowMode::Windowed );
+            if created.is_some() { break }
+        }
+        let ( mut window, events ) = created.ok_or( EngineError::gl( "WINDOW::NOT_CREATED", "GLFW couldn't create the window" ) )?;
+        window.set_framebuffer_size_polling( true );
+        window.set_close_polling( true );
+        window.set_cursor_pos_polling( true );
+        window.set_mouse_button_polling( true );
+        window.set_key_polling( true );
+
+        // (The swap interval is the current context's, so vsync is set before the context is handed to the render thread)
+        window.make_current();
+        glfw.set_swap_interval( if vsync { glfw::SwapInterval::Sync( 1 ) } else { glfw::SwapInterval::None } );
+        glfw::make_context_current( None );
+
+        let ( sender, changes ) = std::sync::mpsc::channel();
+        let context = GlfwContext { context: std::cell::RefCell::new( window.render_context() ), changes: sender };
+        Ok( ( GlfwBackend { glfw, window, events, changes }, RenderContext::Windowed( Box::new( context ) ) ) )
+    }
+
+    fn size( &self ) -> ( u32, u32 ) {
+        let ( width, height ) = self.window.get_framebuffer_size();
+        ( width.max( 0 ) as u32, height.max( 0 ) as u32 )
+    }
+
+    fn run<F: FnMut( WindowEvent ) -> bool + 'static>( mut self, mut handler: F ) {
+        // (The loop wakes up now and then without events too, to make the changes which the render thread sent)
+        loop {
+            self.glfw.wait_events_timeout( 0.1 );
+            for change in self.changes.try_iter() {
+                match change {
+                    GlfwChange::Title( title ) => { self.window.set_title( &title ) },
+                    GlfwChange::Size( width, height ) => { self.window.set_size( width as i32, height as i32 ) },
+                }
+            }
+            for ( _, event ) in glfw::flush_messages( &self.events ) {
+                let event = match event {
+                    glfw::WindowEvent::Close => { WindowEvent::CloseRequested },
+                    glfw::WindowEvent::FramebufferSize( width, height ) => { WindowEvent::Resized( width.max( 0 ) as u32, height.max( 0 ) as u32 ) },
+                    // (The cursor is in screen coordinates, which are scaled to the framebuffer's pixels like winit's)
+                    glfw::WindowEvent::CursorPos( x, y ) => {
+                        let ( ( framebuffer_width, framebuffer_height ), ( window_width, window_height ) ) = ( self.window.get_framebuffer_size(), self.window.get_size() );
+                        let scale = glm::vec2( framebuffer_width as f32 / window_width.max( 1 ) as f32, framebuffer_height as f32 / window_height.max( 1 ) as f32 );
+                        WindowEvent::CursorMoved( glm::vec2( x as f32, y as f32 ).component_mul( &scale ) )
+                    },
+                    glfw::WindowEvent::MouseButton( button, action, _ ) => {
+                        let button = match button {
+                            glfw::MouseButtonLeft => { MouseButton::Left },
+                            glfw::MouseButtonRight => { MouseButton::Right },
+                            glfw::MouseButtonMiddle => { MouseButton::Middle },
+                            button => { MouseButton::Other( button as u16 ) },
+                        };
+                        WindowEvent::MouseInput( button, action != glfw::Action::Release )
+                    },
+                    glfw::WindowEvent::Key( key, _, action, _ ) if action != glfw::Action::Repeat => {
+                        let Some( key ) = glfw_key( key ) else { continue };
+                        WindowEvent::KeyboardInput( key, action == glfw::Action::Press )
+                    },
+                    _ => { continue },
+                };
+                if !handler( event ) {
+                    return;
+                }
+            }
+        }
+    }
+}